@@ -3,18 +3,18 @@ use std::fmt::Display;
 
 use solana_clap_utils::input_validators::is_slot;
 use solana_client::rpc_config::RpcSendTransactionConfig;
-use solana_sdk::commitment_config::CommitmentLevel::Finalized;
-use spl_token::instruction::approve;
+use spl_token::instruction::{approve, initialize_account, mint_to};
 
 use port_finance_staking::instruction::{
     add_sub_reward_pool, change_admin, change_duration, change_owner, change_reward_supply,
     init_staking_pool,
 };
-use port_finance_staking::solana_program::clock::Slot;
-use port_finance_staking::state::staking_pool::StakingPool;
+use port_finance_staking::slots::{SlotDelta, Slots};
+use port_finance_staking::state::{stake_account::StakeAccount, staking_pool::StakingPool};
 use {
     clap::{
-        crate_description, crate_name, crate_version, value_t, App, AppSettings, Arg, SubCommand,
+        crate_description, crate_name, crate_version, value_t, App, AppSettings, Arg, ArgMatches,
+        SubCommand,
     },
     solana_clap_utils::{
         fee_payer::fee_payer_arg,
@@ -41,6 +41,8 @@ struct Config {
     staking_program_id: Pubkey,
     verbose: bool,
     dry_run: bool,
+    commitment: CommitmentConfig,
+    skip_preflight: bool,
 }
 
 type Error = Box<dyn std::error::Error>;
@@ -131,6 +133,7 @@ fn main() {
                 .global(true)
                 .help("Simulate transaction instead of executing"),
         )
+        .args(&send_transaction_args())
         .subcommand(
             SubCommand::with_name("init-staking-pool")
                 .about("Create a new staking pool")
@@ -149,9 +152,25 @@ fn main() {
                         .validator(is_pubkey)
                         .value_name("PUBKEY")
                         .takes_value(true)
-                        .required(true)
+                        .required_unless("mint_rewards")
                         .help("Token account that transfers reward from"),
                 )
+                .arg(
+                    Arg::with_name("mint_rewards")
+                        .long("mint-rewards")
+                        .takes_value(false)
+                        .requires("mint_authority")
+                        .conflicts_with_all(&["reward_supply_pubkey", "sub_reward_supply_pubkey"])
+                        .help("Create the reward supply accounts and mint the rewards into them"),
+                )
+                .arg(
+                    Arg::with_name("mint_authority")
+                        .long("mint_authority")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .help("Mint authority of the reward mints, used with --mint-rewards"),
+                )
                 .arg(
                     Arg::with_name("sub_reward_supply_pubkey")
                         .long("sub_supply_pubkey")
@@ -212,24 +231,7 @@ fn main() {
                         .takes_value(true)
                         .help("Amount of sub reward given"),
                 )
-                .arg(
-                    Arg::with_name("duration_of_rewarding")
-                        .long("duration")
-                        .validator(is_slot)
-                        .value_name("SLOT")
-                        .takes_value(true)
-                        .required(true)
-                        .help("Duration of rewarding"),
-                )
-                .arg(
-                    Arg::with_name("earliest_reward_claim_time")
-                        .long("claim-time")
-                        .validator(is_slot)
-                        .value_name("SLOT")
-                        .takes_value(true)
-                        .required(true)
-                        .help("Earliest time to claim the reward"),
-                ),
+                .args(&reward_slots_args()),
         ).subcommand(SubCommand::with_name("add-sub-reward")
         .about("Add sub reward")
         .arg(
@@ -460,6 +462,45 @@ fn main() {
                         .help("Pubkey of the staking pool"),
                 )
         )
+        .subcommand(
+            SubCommand::with_name("show-pool")
+                .about("Show the reward configuration and size of a staking pool")
+                .arg(
+                    Arg::with_name("staking_pool")
+                        .long("pool")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Staking pool to show"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("show-stake-account")
+                .about("Show the deposit and the reward a stake account could claim now")
+                .arg(
+                    Arg::with_name("stake_account")
+                        .long("stake-account")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Stake account to show"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("decode")
+                .about("Decode and show every field of a staking pool or stake account")
+                .arg(
+                    Arg::with_name("account")
+                        .long("account")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Account to decode"),
+                )
+        )
         .subcommand(
             SubCommand::with_name("change-staking-pool-admin")
                 .about("Change the admin of the new staking pool")
@@ -517,6 +558,8 @@ fn main() {
         let staking_program_id = pubkey_of(&matches, "staking_program_id").unwrap();
         let verbose = matches.is_present("verbose");
         let dry_run = matches.is_present("dry_run");
+        let commitment = commitment_of(&matches);
+        let skip_preflight = skip_preflight_of(&matches);
 
         Config {
             rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
@@ -524,15 +567,15 @@ fn main() {
             staking_program_id,
             verbose,
             dry_run,
+            commitment,
+            skip_preflight,
         }
     };
 
     let _ = match matches.subcommand() {
         ("init-staking-pool", Some(arg_matches)) => {
             let transfer_authority = keypair_of(arg_matches, "transfer_authority").unwrap();
-            let reward_supply = pubkey_of(arg_matches, "reward_supply_pubkey").unwrap();
             let reward_token_mint = pubkey_of(arg_matches, "reward_token_mint").unwrap();
-            let sub_reward_supply = pubkey_of(arg_matches, "sub_reward_supply_pubkey");
             let sub_reward_token_mint = pubkey_of(arg_matches, "sub_reward_token_mint");
             let staking_program_owner_authority =
                 pubkey_of(arg_matches, "staking_program_owner_authority").unwrap();
@@ -540,8 +583,37 @@ fn main() {
                 pubkey_of(arg_matches, "staking_program_admin_authority").unwrap();
             let supply = value_of(arg_matches, "reward_supply_amount").unwrap();
             let sub_supply = value_of(arg_matches, "sub_reward_supply_amount");
-            let duration = value_of(arg_matches, "duration_of_rewarding").unwrap();
-            let claim_time = value_of(arg_matches, "earliest_reward_claim_time").unwrap();
+            let (reward_supply, sub_reward_supply) = if arg_matches.is_present("mint_rewards") {
+                let mint_authority = keypair_of(arg_matches, "mint_authority").unwrap();
+                let reward_supply = command_mint_reward_supply(
+                    &config,
+                    &mint_authority,
+                    transfer_authority.pubkey(),
+                    reward_token_mint,
+                    supply,
+                )
+                .unwrap();
+                let sub_reward_supply = match (sub_reward_token_mint, sub_supply) {
+                    (Some(sub_reward_token_mint), Some(sub_supply)) => Some(
+                        command_mint_reward_supply(
+                            &config,
+                            &mint_authority,
+                            transfer_authority.pubkey(),
+                            sub_reward_token_mint,
+                            sub_supply,
+                        )
+                        .unwrap(),
+                    ),
+                    _ => None,
+                };
+                (reward_supply, sub_reward_supply)
+            } else {
+                (
+                    pubkey_of(arg_matches, "reward_supply_pubkey").unwrap(),
+                    pubkey_of(arg_matches, "sub_reward_supply_pubkey"),
+                )
+            };
+            let (duration, claim_time) = reward_slots_of(arg_matches);
             command_init_staking_pool(
                 &config,
                 transfer_authority,
@@ -657,6 +729,18 @@ fn main() {
                 staking_pool,
             )
         }
+        ("show-pool", Some(arg_matches)) => {
+            let staking_pool = pubkey_of(arg_matches, "staking_pool").unwrap();
+            command_show_pool(&config, staking_pool)
+        }
+        ("show-stake-account", Some(arg_matches)) => {
+            let stake_account = pubkey_of(arg_matches, "stake_account").unwrap();
+            command_show_stake_account(&config, stake_account)
+        }
+        ("decode", Some(arg_matches)) => {
+            let account = pubkey_of(arg_matches, "account").unwrap();
+            command_decode(&config, account)
+        }
         ("change-staking-pool-admin", Some(arg_matches)) => {
             let mut wallet_manager = None;
             let old_staking_pool_admin = signer_from_path(
@@ -684,6 +768,117 @@ fn main() {
     });
 }
 
+fn command_show_pool(config: &Config, staking_pool_pubkey: Pubkey) -> CommandResult {
+    let staking_pool =
+        StakingPool::unpack(&config.rpc_client.get_account(&staking_pool_pubkey)?.data)?;
+    println!(
+        "Staking pool {}\n\
+        Owner {}\n\
+        Admin {}\n\
+        Reward token pool {}\n\
+        Reward token decimals {}\n\
+        Reward rate per slot {}\n\
+        Pool size {}\n\
+        Duration {}\n\
+        End time {}\n\
+        Earliest reward claim time {}\n\
+        Total distributed {}",
+        staking_pool_pubkey,
+        staking_pool.owner_authority,
+        staking_pool.admin_authority,
+        staking_pool.reward_token_pool,
+        staking_pool.reward_token_decimals,
+        staking_pool.rate_per_slot.reward,
+        staking_pool.pool_size,
+        staking_pool.duration,
+        staking_pool.end_time,
+        staking_pool.earliest_reward_claim_time,
+        staking_pool.total_distributed,
+    );
+    if let Some(sub_reward_token_pool) = staking_pool.sub_reward_token_pool {
+        println!(
+            "Sub reward token pool {}\n\
+            Sub reward token decimals {}\n\
+            Sub reward rate per slot {}\n\
+            Sub total distributed {}",
+            sub_reward_token_pool,
+            staking_pool
+                .sub_reward_token_decimals
+                .map_or_else(|| "unknown".to_owned(), |decimals| decimals.to_string()),
+            staking_pool.rate_per_slot.sub_reward.unwrap_or_default(),
+            staking_pool.sub_total_distributed,
+        );
+    }
+    Ok(())
+}
+
+fn command_show_stake_account(config: &Config, stake_account_pubkey: Pubkey) -> CommandResult {
+    let stake_account =
+        StakeAccount::unpack(&config.rpc_client.get_account(&stake_account_pubkey)?.data)?;
+    let mut staking_pool = StakingPool::unpack(
+        &config
+            .rpc_client
+            .get_account(&stake_account.pool_pubkey)?
+            .data,
+    )?;
+    let current_slot = config.rpc_client.get_slot()?;
+    // accrue the pool up to now locally, the same way claiming would on chain
+    let pool_rate = staking_pool.claim_reward(current_slot)?;
+    let (pending_reward, pending_sub_reward) = stake_account.pending_reward(pool_rate)?;
+    println!(
+        "Stake account {}\n\
+        Owner {}\n\
+        Staking pool {}\n\
+        Deposited amount {}\n\
+        Pending reward at slot {} {}",
+        stake_account_pubkey,
+        stake_account.owner,
+        stake_account.pool_pubkey,
+        stake_account.deposited_amount,
+        current_slot,
+        pending_reward,
+    );
+    if let Some(pending_sub_reward) = pending_sub_reward {
+        println!("Pending sub reward {}", pending_sub_reward);
+    }
+    if current_slot < staking_pool.earliest_reward_claim_time {
+        println!(
+            "Rewards can't be claimed before slot {}",
+            staking_pool.earliest_reward_claim_time
+        );
+    }
+    Ok(())
+}
+
+fn command_decode(config: &Config, account_pubkey: Pubkey) -> CommandResult {
+    let account = config.rpc_client.get_account(&account_pubkey)?;
+    println!(
+        "Account {}\n\
+        Owner {}\n\
+        Length {}",
+        account_pubkey,
+        account.owner,
+        account.data.len(),
+    );
+    if account.owner != config.staking_program_id {
+        println!(
+            "Account is not owned by the staking program {}",
+            config.staking_program_id
+        );
+    }
+    println!("{}", decode_account(&account.data)?);
+    Ok(())
+}
+
+/// Unpack staking program account data as the account type of its length, formatting every field
+fn decode_account(data: &[u8]) -> Result<String, Error> {
+    Ok(match data.len() {
+        StakingPool::LEN => format!("Staking pool {:#?}", StakingPool::unpack(data)?),
+        StakeAccount::LEN => format!("Stake account {:#?}", StakeAccount::unpack(data)?),
+        len => return Err(format!("No staking program account is {} bytes long", len).into()),
+    })
+}
+
 #[allow(clippy::too_many_arguments)]
 fn command_change_staking_pool_admin(
     config: &Config,
@@ -747,7 +942,7 @@ fn command_change_duration(
     config: &Config,
     admin_authority: Box<dyn Signer>,
     staking_pool: Pubkey,
-    amount: i64,
+    amount: SlotDelta,
 ) -> CommandResult {
     let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
 
@@ -875,6 +1070,63 @@ fn command_change_reward_supply(
     }
 }
 
+fn command_mint_reward_supply(
+    config: &Config,
+    mint_authority: &Keypair,
+    owner: Pubkey,
+    reward_token_mint: Pubkey,
+    amount: u64,
+) -> Result<Pubkey, Error> {
+    let reward_supply_keypair = Keypair::new();
+    let reward_supply_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Token::LEN)?;
+
+    println!(
+        "reward supply {} \n \
+        reward mint {} amount {} \n",
+        reward_supply_keypair.pubkey(),
+        reward_token_mint,
+        amount
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &config.fee_payer.pubkey(),
+                &reward_supply_keypair.pubkey(),
+                reward_supply_balance,
+                Token::LEN as u64,
+                &spl_token::id(),
+            ),
+            initialize_account(
+                &spl_token::id(),
+                &reward_supply_keypair.pubkey(),
+                &reward_token_mint,
+                &owner,
+            )?,
+            mint_to(
+                &spl_token::id(),
+                &reward_token_mint,
+                &reward_supply_keypair.pubkey(),
+                &mint_authority.pubkey(),
+                &[],
+                amount,
+            )?,
+        ],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    let signers: Vec<&dyn Signer> = vec![
+        config.fee_payer.as_ref(),
+        &reward_supply_keypair,
+        mint_authority,
+    ];
+    transaction.sign(&signers, recent_blockhash);
+    send_transaction(config, transaction)?;
+    Ok(reward_supply_keypair.pubkey())
+}
+
 #[allow(clippy::too_many_arguments)]
 fn command_init_staking_pool(
     config: &Config,
@@ -887,8 +1139,8 @@ fn command_init_staking_pool(
     staking_program_admin_authority: Pubkey,
     supply: u64,
     sub_supply: Option<u64>,
-    duration: u64,
-    claim_time: Slot,
+    duration: Slots,
+    claim_time: Slots,
 ) -> CommandResult {
     let staking_pool_keypair = Keypair::new();
     let reward_pool_keypair = Keypair::new();
@@ -1067,15 +1319,172 @@ fn send_transaction(
             .rpc_client
             .send_and_confirm_transaction_with_spinner_and_config(
                 &transaction,
-                CommitmentConfig {
-                    commitment: Finalized,
-                },
-                RpcSendTransactionConfig {
-                    skip_preflight: true,
-                    ..RpcSendTransactionConfig::default()
-                },
+                config.commitment,
+                send_transaction_config(config.commitment, config.skip_preflight),
             )?;
         println!("Signature: {}", signature);
     }
     Ok(())
 }
+
+fn reward_slots_args<'a, 'b>() -> [Arg<'a, 'b>; 2] {
+    [
+        Arg::with_name("duration_of_rewarding")
+            .long("duration")
+            .validator(is_slot)
+            .value_name("SLOT")
+            .takes_value(true)
+            .required(true)
+            .help("Duration of rewarding, in slots"),
+        Arg::with_name("earliest_reward_claim_time")
+            .long("claim-time")
+            .validator(is_slot)
+            .value_name("SLOT")
+            .takes_value(true)
+            .required(true)
+            .help("Earliest slot to claim the reward"),
+    ]
+}
+
+fn reward_slots_of(matches: &ArgMatches<'_>) -> (Slots, Slots) {
+    (
+        value_of(matches, "duration_of_rewarding").unwrap(),
+        value_of(matches, "earliest_reward_claim_time").unwrap(),
+    )
+}
+
+fn send_transaction_args<'a, 'b>() -> [Arg<'a, 'b>; 3] {
+    [
+        Arg::with_name("commitment")
+            .long("commitment")
+            .value_name("LEVEL")
+            .takes_value(true)
+            .possible_values(&["processed", "confirmed", "finalized"])
+            .default_value("finalized")
+            .global(true)
+            .help("Commitment level to wait for after sending a transaction"),
+        Arg::with_name("skip_preflight")
+            .long("skip-preflight")
+            .takes_value(false)
+            .global(true)
+            .help("Skip the preflight simulation of sent transactions [default]"),
+        Arg::with_name("no_skip_preflight")
+            .long("no-skip-preflight")
+            .takes_value(false)
+            .conflicts_with("skip_preflight")
+            .global(true)
+            .help("Run the preflight simulation of sent transactions and report its errors"),
+    ]
+}
+
+fn commitment_of(matches: &ArgMatches<'_>) -> CommitmentConfig {
+    match matches.value_of("commitment") {
+        Some("processed") => CommitmentConfig::processed(),
+        Some("confirmed") => CommitmentConfig::confirmed(),
+        _ => CommitmentConfig::finalized(),
+    }
+}
+
+fn skip_preflight_of(matches: &ArgMatches<'_>) -> bool {
+    !matches.is_present("no_skip_preflight")
+}
+
+fn send_transaction_config(
+    commitment: CommitmentConfig,
+    skip_preflight: bool,
+) -> RpcSendTransactionConfig {
+    RpcSendTransactionConfig {
+        skip_preflight,
+        preflight_commitment: Some(commitment.commitment),
+        ..RpcSendTransactionConfig::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use port_finance_staking::state::PROGRAM_VERSION;
+
+    fn send_transaction_config_from(args: &[&str]) -> (CommitmentConfig, RpcSendTransactionConfig) {
+        let matches = App::new("test")
+            .args(&send_transaction_args())
+            .get_matches_from(args);
+        let commitment = commitment_of(&matches);
+        (
+            commitment,
+            send_transaction_config(commitment, skip_preflight_of(&matches)),
+        )
+    }
+
+    #[test]
+    fn reward_slots_flags() {
+        let matches = App::new("test")
+            .args(&reward_slots_args())
+            .get_matches_from(&["test", "--duration", "1000", "--claim-time", "42"]);
+        assert_eq!(reward_slots_of(&matches), (Slots(1000), Slots(42)));
+
+        // seconds or fractions of slots are rejected rather than misread
+        for duration in ["-1", "1.5", "10s"] {
+            assert!(App::new("test")
+                .args(&reward_slots_args())
+                .get_matches_from_safe(&["test", "--duration", duration, "--claim-time", "42"])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn decode_account_by_length() {
+        let staking_pool = StakingPool {
+            version: PROGRAM_VERSION,
+            pool_size: 42,
+            duration: 1000,
+            ..StakingPool::default()
+        };
+        let mut data = vec![0; StakingPool::LEN];
+        StakingPool::pack(staking_pool, &mut data).unwrap();
+        let decoded = decode_account(&data).unwrap();
+        assert!(decoded.starts_with("Staking pool StakingPool {"));
+        assert!(decoded.contains("pool_size: 42,"));
+        assert!(decoded.contains("duration: 1000,"));
+
+        let owner = Pubkey::new_unique();
+        let stake_account = StakeAccount {
+            version: PROGRAM_VERSION,
+            owner,
+            deposited_amount: 7,
+            ..StakeAccount::default()
+        };
+        let mut data = vec![0; StakeAccount::LEN];
+        StakeAccount::pack(stake_account, &mut data).unwrap();
+        let decoded = decode_account(&data).unwrap();
+        assert!(decoded.starts_with("Stake account StakeAccount {"));
+        assert!(decoded.contains(&format!("owner: {:?},", owner)));
+        assert!(decoded.contains("deposited_amount: 7,"));
+
+        assert!(decode_account(&[0; 3]).is_err());
+    }
+
+    #[test]
+    fn send_transaction_config_flags() {
+        // defaults keep the finalized commitment and skip preflight
+        let (commitment, send_config) = send_transaction_config_from(&["test"]);
+        assert_eq!(commitment, CommitmentConfig::finalized());
+        assert!(send_config.skip_preflight);
+
+        let (commitment, send_config) = send_transaction_config_from(&[
+            "test",
+            "--commitment",
+            "confirmed",
+            "--no-skip-preflight",
+        ]);
+        assert_eq!(commitment, CommitmentConfig::confirmed());
+        assert!(!send_config.skip_preflight);
+        assert_eq!(
+            send_config.preflight_commitment,
+            Some(CommitmentConfig::confirmed().commitment)
+        );
+
+        let (_, send_config) = send_transaction_config_from(&["test", "--skip-preflight"]);
+        assert!(send_config.skip_preflight);
+    }
+}
@@ -75,6 +75,20 @@ pub enum StakingError {
     ReduceRewardTooMuch,
     #[error("The staking pool already has a sub reward")]
     AlreadyHasSubReward,
+    #[error("The staking pool has no pending owner to accept")]
+    NoPendingOwner,
+    #[error("Earliest reward claim time can only be moved forward")]
+    InvalidClaimTimeChange,
+    #[error("Reward token mint is not an initialized mint")]
+    InvalidRewardTokenMint,
+    #[error("Reward rate per slot of the staking pool rounds down to zero")]
+    RewardRateTooLow,
+    #[error("Reward destination is not the associated token account of the stake account owner")]
+    InvalidAssociatedTokenAccount,
+    #[error("associated token account create failed")]
+    AssociatedTokenAccountCreateFailed,
+    #[error("Claimed reward exceeds the reward left in the reward token pool")]
+    RewardExceedsSupply,
 }
 
 impl From<StakingError> for ProgramError {
@@ -1,14 +1,17 @@
 use core::convert::TryInto;
 use std::mem::size_of;
 
-use solana_program::clock::Slot;
 use solana_program::instruction::{AccountMeta, Instruction};
 
 use crate::dummy_id;
 use crate::error::StakingError;
 use crate::instruction::StakingInstruction::*;
+use crate::slots::{SlotDelta, Slots};
 use crate::solana_program::pubkey::PUBKEY_BYTES;
-use crate::solana_program::{msg, program_error::ProgramError, pubkey::Pubkey, sysvar};
+use crate::solana_program::{
+    msg, program_error::ProgramError, pubkey::Pubkey, system_program, sysvar,
+};
+use spl_associated_token_account::get_associated_token_address;
 
 /// Instructions supported by the lending program.
 #[derive(Clone, Debug, PartialEq)]
@@ -29,8 +32,8 @@ pub enum StakingInstruction {
     InitStakingPool {
         supply: u64, // rate per slot = supply / duration
         sub_supply: Option<u64>,
-        duration: u64, // num of slots
-        earliest_reward_claim_time: Slot,
+        duration: Slots,
+        earliest_reward_claim_time: Slots,
         bump_seed_staking_program: u8,
         pool_owner_authority: Pubkey,
         admin_authority: Pubkey,
@@ -46,17 +49,25 @@ pub enum StakingInstruction {
 
     /// Deposit to a stake account.
     ///
+    /// The authority must be the staking pool owner or admin. For pools backing a lending
+    /// reserve the owner is the lending market authority, which signs through CPI when
+    /// collateral is deposited; the stake account owner cannot deposit directly.
+    ///
     /// Accounts expected by this instruction:
-    ///   0. `[signer]` authority.
+    ///   0. `[signer]` authority, staking pool owner or admin.
     ///   1. `[writable]` Stake account.
     ///   2. `[writable]` Staking pool.
     ///   3. `[]` Clock sysvar.
     Deposit(u64),
 
-    /// Withdrawn to a stake account.
+    /// Withdraw from a stake account.
+    ///
+    /// Same authority model as `Deposit`. The stake account holds no tokens, the principal is
+    /// the collateral custodied by the pool owner, so the stake account owner withdraws by
+    /// withdrawing that collateral rather than calling this directly.
     ///
     /// Accounts expected by this instruction:
-    ///   0. `[signer]` authority.
+    ///   0. `[signer]` authority, staking pool owner or admin.
     ///   1. `[writable]` Stake account.
     ///   2. `[writable]` Staking pool.
     ///   3. `[]` Clock sysvar.
@@ -78,9 +89,11 @@ pub enum StakingInstruction {
     ClaimReward,
 
     /// Update the earliest_reward_claim_tim
+    /// Admin override that can also move the time backward and unlock rewards early.
+    /// Prefer `AdvanceEarliestRewardClaimTime`.
     /// 0. `[signer]` admin authority .
     /// 1. `[writable]` Staking Pool
-    UpdateEarliestRewardClaimTime(Slot),
+    UpdateEarliestRewardClaimTime(Slots),
 
     ///Add Reward Supply
     /// 0. `[signer]` Transfer reward token authority (supply authority or admin authority).
@@ -96,7 +109,9 @@ pub enum StakingInstruction {
     /// 10. `[writable, optional]` Sub Reward token pool
     ChangeRewardSupply(i64, Option<i64>),
 
-    ///Change Staking Pool Owner
+    ///Change Staking Pool Owner in a single step.
+    /// Prefer `ProposeNewOwner` followed by `AcceptOwnership`, which cannot hand the
+    /// pool to a key nobody controls. Clears any pending owner proposal.
     /// 0. `[signer]` Current owner
     /// 1. `[writable]` Staking Pool
     ChangeOwner(Pubkey),
@@ -105,7 +120,7 @@ pub enum StakingInstruction {
     /// 0. `[signer]` Admin authority.
     /// 1. `[writable]` Staking Pool
     /// 2. `[]` Clock sysvar
-    ChangeDuration(i64),
+    ChangeDuration(SlotDelta),
 
     ///Add Sub reward
     /// 0. `[signer]` Admin authority.
@@ -124,6 +139,42 @@ pub enum StakingInstruction {
     /// 0. `[signer]` Current Admin
     /// 1. `[writable]` Staking Pool
     ChangeAdmin(Pubkey),
+
+    ///Propose a new Staking Pool Owner, replacing any pending proposal.
+    /// The proposed owner takes control once it signs `AcceptOwnership`.
+    /// 0. `[signer]` Current owner
+    /// 1. `[writable]` Staking Pool
+    ProposeNewOwner(Pubkey),
+
+    ///Accept a pending Staking Pool Owner proposal
+    /// 0. `[signer]` Pending owner
+    /// 1. `[writable]` Staking Pool
+    AcceptOwnership,
+
+    ///Move the earliest_reward_claim_time forward, rejecting a time earlier than the current one
+    /// 0. `[signer]` Admin authority.
+    /// 1. `[writable]` Staking Pool
+    AdvanceEarliestRewardClaimTime(Slots),
+
+    /// Claim all unclaimed Reward from a stake account into the associated token accounts of
+    /// the stake account owner, creating them first if they don't exist yet
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[signer, writable]` Stake account owner, pays for created token accounts.
+    ///   1. `[writable]` Stake account.
+    ///   2. `[writable]` Staking pool.
+    ///   3. `[writable]` Reward token pool.
+    ///   4. `[writable]` Reward destination, associated token account of the owner.
+    ///   5. `[]` Staking Pool owner derived from staking pool pubkey
+    ///   6. `[]` Clock sysvar.
+    ///   7. `[]` Token program.
+    ///   8. `[]` Reward token mint.
+    ///   9. `[]` System program.
+    ///   10. `[]` Associated token account program.
+    ///   11. `[writable, optional]` Sub Reward token pool.
+    ///   12. `[writable, optional]` Sub Reward destination, associated token account of the owner.
+    ///   13. `[optional]` Sub Reward token mint.
+    ClaimRewardToAssociatedTokenAccount,
 }
 
 impl StakingInstruction {
@@ -135,8 +186,8 @@ impl StakingInstruction {
                 0 => {
                     let (supply, rest) = Self::unpack_u64(rest)?;
                     let (sub_supply, rest) = Self::unpack_option_u64(rest)?;
-                    let (duration, rest) = Self::unpack_u64(rest)?;
-                    let (earliest_reward_claim_time, rest) = Self::unpack_u64(rest)?;
+                    let (duration, rest) = Self::unpack_slots(rest)?;
+                    let (earliest_reward_claim_time, rest) = Self::unpack_slots(rest)?;
                     let (bump_seed_staking_program, rest) = Self::unpack_u8(rest)?;
                     let (pool_owner_authority, rest) = Self::unpack_pubkey(rest)?;
                     let (admin_authority, rest) = Self::unpack_pubkey(rest)?;
@@ -164,7 +215,7 @@ impl StakingInstruction {
                 }
                 4 => Ok((ClaimReward, rest)),
                 5 => {
-                    let (time, rest) = Self::unpack_u64(rest)?;
+                    let (time, rest) = Self::unpack_slots(rest)?;
                     Ok((UpdateEarliestRewardClaimTime(time), rest))
                 }
                 6 => {
@@ -177,7 +228,7 @@ impl StakingInstruction {
                     Ok((ChangeOwner(new_owner), rest))
                 }
                 8 => {
-                    let (amount, rest) = Self::unpack_i64(rest)?;
+                    let (amount, rest) = Self::unpack_slot_delta(rest)?;
                     Ok((ChangeDuration(amount), rest))
                 }
                 9 => {
@@ -188,6 +239,16 @@ impl StakingInstruction {
                     let (new_owner, rest) = Self::unpack_pubkey(rest)?;
                     Ok((ChangeAdmin(new_owner), rest))
                 }
+                11 => {
+                    let (new_owner, rest) = Self::unpack_pubkey(rest)?;
+                    Ok((ProposeNewOwner(new_owner), rest))
+                }
+                12 => Ok((AcceptOwnership, rest)),
+                13 => {
+                    let (time, rest) = Self::unpack_slots(rest)?;
+                    Ok((AdvanceEarliestRewardClaimTime(time), rest))
+                }
+                14 => Ok((ClaimRewardToAssociatedTokenAccount, rest)),
                 _ => {
                     msg!("Instruction cannot be unpacked");
                     Err(StakingError::InstructionUnpackError.into())
@@ -239,6 +300,16 @@ impl StakingInstruction {
         Ok((value, rest))
     }
 
+    fn unpack_slots(input: &[u8]) -> Result<(Slots, &[u8]), ProgramError> {
+        let (value, rest) = Self::unpack_u64(input)?;
+        Ok((Slots(value), rest))
+    }
+
+    fn unpack_slot_delta(input: &[u8]) -> Result<(SlotDelta, &[u8]), ProgramError> {
+        let (value, rest) = Self::unpack_i64(input)?;
+        Ok((SlotDelta(value), rest))
+    }
+
     fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
         if input.is_empty() {
             msg!("u8 cannot be unpacked");
@@ -352,6 +423,20 @@ impl StakingInstruction {
                 buf.push(10);
                 buf.extend_from_slice(new_admin.as_ref());
             }
+            Self::ProposeNewOwner(new_owner) => {
+                buf.push(11);
+                buf.extend_from_slice(new_owner.as_ref());
+            }
+            Self::AcceptOwnership => {
+                buf.push(12);
+            }
+            Self::AdvanceEarliestRewardClaimTime(slot) => {
+                buf.push(13);
+                buf.extend_from_slice(&slot.to_le_bytes());
+            }
+            Self::ClaimRewardToAssociatedTokenAccount => {
+                buf.push(14);
+            }
         };
         buf
     }
@@ -426,14 +511,47 @@ pub fn change_admin(
     }
 }
 
+pub fn propose_new_owner(
+    program_id: Pubkey,
+    new_owner: Pubkey,
+    current_owner: Pubkey,
+    staking_pool: Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(current_owner, true),
+        AccountMeta::new(staking_pool, false),
+    ];
+    Instruction {
+        program_id,
+        accounts,
+        data: StakingInstruction::ProposeNewOwner(new_owner).pack(),
+    }
+}
+
+pub fn accept_ownership(
+    program_id: Pubkey,
+    pending_owner: Pubkey,
+    staking_pool: Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(pending_owner, true),
+        AccountMeta::new(staking_pool, false),
+    ];
+    Instruction {
+        program_id,
+        accounts,
+        data: StakingInstruction::AcceptOwnership.pack(),
+    }
+}
+
 /// Creates an InitStakingPool instruction
 #[allow(clippy::too_many_arguments)]
 pub fn init_staking_pool(
     program_id: Pubkey,
     supply: u64,
     sub_supply: Option<u64>,
-    duration: u64,
-    earliest_reward_claim_time: Slot,
+    duration: Slots,
+    earliest_reward_claim_time: Slots,
     transfer_reward_token_authority: Pubkey,
     reward_token_supply: Pubkey,
     reward_token_pool: Pubkey,
@@ -602,9 +720,65 @@ pub fn claim_reward(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn claim_reward_to_associated_token_account(
+    program_id: Pubkey,
+    stake_account_owner: Pubkey,
+    stake_account: Pubkey,
+    staking_pool: Pubkey,
+    reward_token_pool: Pubkey,
+    sub_reward_token_pool: Option<Pubkey>,
+    reward_token_mint: Pubkey,
+    sub_reward_token_mint: Option<Pubkey>,
+) -> Instruction {
+    let (staking_program_derived, _bump_seed) =
+        Pubkey::find_program_address(&[staking_pool.as_ref()], &program_id);
+
+    let write_accounts = create_write_accounts(vec![
+        stake_account,
+        staking_pool,
+        reward_token_pool,
+        get_associated_token_address(&stake_account_owner, &reward_token_mint),
+    ]);
+
+    let read_accounts = create_read_accounts(vec![
+        staking_program_derived,
+        sysvar::clock::id(),
+        spl_token::id(),
+        reward_token_mint,
+        system_program::id(),
+        spl_associated_token_account::id(),
+    ]);
+
+    let optional_accounts = if let Some([sub_reward_token_pool, sub_reward_token_mint]) =
+        sub_reward_token_pool.and_then(|pool| sub_reward_token_mint.map(|mint| [pool, mint]))
+    {
+        create_write_accounts(vec![
+            sub_reward_token_pool,
+            get_associated_token_address(&stake_account_owner, &sub_reward_token_mint),
+        ])
+        .chain(create_read_accounts(vec![sub_reward_token_mint]))
+        .collect()
+    } else {
+        vec![]
+    };
+    let accounts = vec![AccountMeta::new(stake_account_owner, true)]
+        .into_iter()
+        .chain(write_accounts)
+        .chain(read_accounts)
+        .chain(optional_accounts)
+        .collect();
+
+    Instruction {
+        program_id,
+        accounts,
+        data: ClaimRewardToAssociatedTokenAccount.pack(),
+    }
+}
+
 pub fn update_earliest_reward_claim_time(
     program_id: Pubkey,
-    time: Slot,
+    time: Slots,
     admin_authority: Pubkey,
     staking_pool: Pubkey,
 ) -> Instruction {
@@ -620,6 +794,24 @@ pub fn update_earliest_reward_claim_time(
     }
 }
 
+pub fn advance_earliest_reward_claim_time(
+    program_id: Pubkey,
+    time: Slots,
+    admin_authority: Pubkey,
+    staking_pool: Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(admin_authority, true),
+        AccountMeta::new(staking_pool, false),
+    ];
+
+    Instruction {
+        program_id,
+        accounts,
+        data: AdvanceEarliestRewardClaimTime(time).pack(),
+    }
+}
+
 // Change reward supply instructions
 #[allow(clippy::too_many_arguments)]
 pub fn change_reward_supply(
@@ -670,7 +862,7 @@ pub fn change_reward_supply(
 // Change reward supply instructions
 pub fn change_duration(
     program_id: Pubkey,
-    amount: i64,
+    amount: SlotDelta,
     admin_authority: Pubkey,
     staking_pool: Pubkey,
 ) -> Instruction {
@@ -727,3 +919,109 @@ pub fn add_sub_reward_pool(
         data: StakingInstruction::AddSubRewardPool(amount).pack(),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slot_arguments_pack_as_raw_values() {
+        let pool_owner_authority = Pubkey::new_unique();
+        let admin_authority = Pubkey::new_unique();
+        let init = InitStakingPool {
+            supply: 100,
+            sub_supply: None,
+            duration: Slots(1000),
+            earliest_reward_claim_time: Slots(42),
+            bump_seed_staking_program: 255,
+            pool_owner_authority,
+            admin_authority,
+        };
+        let mut expected = vec![0];
+        expected.extend_from_slice(&100u64.to_le_bytes());
+        expected.extend_from_slice(&[0; 9]);
+        expected.extend_from_slice(&1000u64.to_le_bytes());
+        expected.extend_from_slice(&42u64.to_le_bytes());
+        expected.push(255);
+        expected.extend_from_slice(pool_owner_authority.as_ref());
+        expected.extend_from_slice(admin_authority.as_ref());
+        assert_eq!(init.pack(), expected);
+
+        let cases = [
+            (
+                UpdateEarliestRewardClaimTime(Slots(7)),
+                5,
+                7u64.to_le_bytes(),
+            ),
+            (ChangeDuration(SlotDelta(-300)), 8, (-300i64).to_le_bytes()),
+            (ChangeDuration(SlotDelta(300)), 8, 300i64.to_le_bytes()),
+            (
+                AdvanceEarliestRewardClaimTime(Slots(u64::MAX)),
+                13,
+                u64::MAX.to_le_bytes(),
+            ),
+        ];
+        for (instruction, tag, raw) in cases {
+            let mut expected = vec![tag];
+            expected.extend_from_slice(&raw);
+            assert_eq!(instruction.pack(), expected);
+        }
+    }
+
+    #[test]
+    fn slot_arguments_round_trip() {
+        let instructions = [
+            InitStakingPool {
+                supply: 100,
+                sub_supply: Some(50),
+                duration: Slots(1000),
+                earliest_reward_claim_time: Slots(42),
+                bump_seed_staking_program: 255,
+                pool_owner_authority: Pubkey::new_unique(),
+                admin_authority: Pubkey::new_unique(),
+            },
+            UpdateEarliestRewardClaimTime(Slots(7)),
+            ChangeDuration(SlotDelta(i64::MIN)),
+            ChangeDuration(SlotDelta(i64::MAX)),
+            AdvanceEarliestRewardClaimTime(Slots(0)),
+        ];
+        for instruction in instructions {
+            assert_eq!(
+                StakingInstruction::unpack(&instruction.pack()).unwrap(),
+                instruction
+            );
+        }
+    }
+    #[test]
+    fn claim_reward_to_associated_token_account_destinations() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let reward_token_mint = Pubkey::new_unique();
+        let sub_reward_token_mint = Pubkey::new_unique();
+        let instruction = claim_reward_to_associated_token_account(
+            program_id,
+            owner,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Some(Pubkey::new_unique()),
+            reward_token_mint,
+            Some(sub_reward_token_mint),
+        );
+        assert_eq!(instruction.data, vec![14]);
+        assert_eq!(instruction.accounts.len(), 14);
+        assert!(instruction.accounts[0].is_signer && instruction.accounts[0].is_writable);
+        assert_eq!(
+            instruction.accounts[4].pubkey,
+            get_associated_token_address(&owner, &reward_token_mint)
+        );
+        assert_eq!(
+            instruction.accounts[12].pubkey,
+            get_associated_token_address(&owner, &sub_reward_token_mint)
+        );
+        assert_eq!(
+            StakingInstruction::unpack(&instruction.data).unwrap(),
+            ClaimRewardToAssociatedTokenAccount
+        );
+    }
+}
@@ -3,6 +3,7 @@ pub mod error;
 pub mod instruction;
 pub mod math;
 pub mod processor;
+pub mod slots;
 pub mod state;
 
 // pub mod state;
@@ -1,10 +1,15 @@
+use num_traits::FromPrimitive;
 use solana_program::account_info::next_account_info;
-use spl_token::state::Account;
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account,
+};
+use spl_token::error::TokenError;
+use spl_token::state::{Account, Mint};
 
 use crate::error::StakingError;
 use crate::instruction::StakingInstruction;
+use crate::slots::{SlotDelta, Slots};
 use crate::solana_program::account_info::{next_account_infos, AccountInfo};
-use crate::solana_program::clock::Slot;
 use crate::solana_program::entrypoint::ProgramResult;
 use crate::solana_program::msg;
 use crate::solana_program::program::{invoke, invoke_signed};
@@ -14,7 +19,10 @@ use crate::solana_program::pubkey::Pubkey;
 use crate::solana_program::rent::Rent;
 use crate::solana_program::sysvar::clock::Clock;
 use crate::solana_program::sysvar::Sysvar;
-use crate::state::{stake_account::StakeAccount, staking_pool::StakingPool};
+use crate::state::{
+    stake_account::StakeAccount,
+    staking_pool::{RatePerSlot, StakingPool},
+};
 
 pub fn process_instruction(
     program_id: &Pubkey,
@@ -63,7 +71,7 @@ pub fn process_instruction(
         }
         StakingInstruction::UpdateEarliestRewardClaimTime(time) => {
             msg!("Instruction: update earliest reward claim time");
-            process_update_earliest_reward_claim_time(program_id, time, accounts)
+            process_update_earliest_reward_claim_time(program_id, time, true, accounts)
         }
         StakingInstruction::ChangeRewardSupply(amount, sub_amount) => {
             msg!("Instruction: add reward supply to current staking pool");
@@ -85,6 +93,22 @@ pub fn process_instruction(
             msg!("Instruction: Changing admin of staking pool");
             process_change_admin(program_id, new_admin, accounts)
         }
+        StakingInstruction::ProposeNewOwner(new_owner) => {
+            msg!("Instruction: Proposing new owner of staking pool");
+            process_propose_new_owner(program_id, new_owner, accounts)
+        }
+        StakingInstruction::AcceptOwnership => {
+            msg!("Instruction: Accepting ownership of staking pool");
+            process_accept_ownership(program_id, accounts)
+        }
+        StakingInstruction::AdvanceEarliestRewardClaimTime(time) => {
+            msg!("Instruction: advance earliest reward claim time");
+            process_update_earliest_reward_claim_time(program_id, time, false, accounts)
+        }
+        StakingInstruction::ClaimRewardToAssociatedTokenAccount => {
+            msg!("Instruction: claim reward to associated token account");
+            process_claim_reward_to_associated_token_account(program_id, accounts)
+        }
     }
 }
 fn process_add_sub_reward_pool(
@@ -141,6 +165,7 @@ fn process_add_sub_reward_pool(
             msg!("Reward token mint is not owned by the token program provided");
             return Err(StakingError::InvalidTokenOwner.into());
         }
+        let sub_reward_token_mint = unpack_reward_token_mint(sub_reward_token_mint_info)?;
 
         if sub_reward_token_supply_info.owner != token_program_info.key
             || sub_reward_token_pool_info.owner != token_program_info.key
@@ -164,6 +189,7 @@ fn process_add_sub_reward_pool(
         let clock = &Clock::from_account_info(clock_info)?;
 
         staking_pool.add_sub_reward(amount, clock.slot, *sub_reward_token_pool_info.key)?;
+        staking_pool.sub_reward_token_decimals = Some(sub_reward_token_mint.decimals);
         spl_token_init_account(TokenInitializeAccountParams {
             account: sub_reward_token_pool_info.clone(),
             mint: sub_reward_token_mint_info.clone(),
@@ -187,6 +213,13 @@ fn process_add_sub_reward_pool(
         Err(StakingError::InvalidArgumentError.into())
     }
 }
+fn unpack_reward_token_mint(mint_info: &AccountInfo) -> Result<Mint, ProgramError> {
+    Mint::unpack(&mint_info.data.borrow()).map_err(|_| {
+        msg!("Reward token mint is not an initialized mint");
+        StakingError::InvalidRewardTokenMint.into()
+    })
+}
+
 fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
     if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
         msg!(
@@ -235,6 +268,72 @@ fn process_change_owner(
         }
 
         staking_pool.owner_authority = new_owner;
+        staking_pool.pending_owner = None;
+        StakingPool::pack(staking_pool, &mut staking_pool_info.data.borrow_mut())?;
+        Ok(())
+    } else {
+        msg!("Wrong number of accounts");
+        Err(StakingError::InvalidArgumentError.into())
+    }
+}
+
+fn process_propose_new_owner(
+    program_id: &Pubkey,
+    new_owner: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    if let [current_owner_info, staking_pool_info] = next_account_infos(account_info_iter, 2)? {
+        if !current_owner_info.is_signer {
+            msg!("To propose a new owner, the current owner must be a signer");
+            return Err(StakingError::InvalidArgumentError.into());
+        }
+
+        let mut staking_pool = StakingPool::unpack(&staking_pool_info.data.borrow())?;
+        if *current_owner_info.key != staking_pool.owner_authority {
+            msg!("Owner didn't sign for proposing new owner");
+            return Err(StakingError::InvalidSigner.into());
+        }
+
+        if staking_pool_info.owner != program_id {
+            msg!("Staking pool is not owned by the staking program");
+            return Err(StakingError::InvalidAccountOwner.into());
+        }
+
+        staking_pool.pending_owner = Some(new_owner);
+        StakingPool::pack(staking_pool, &mut staking_pool_info.data.borrow_mut())?;
+        Ok(())
+    } else {
+        msg!("Wrong number of accounts");
+        Err(StakingError::InvalidArgumentError.into())
+    }
+}
+
+fn process_accept_ownership(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    if let [pending_owner_info, staking_pool_info] = next_account_infos(account_info_iter, 2)? {
+        if !pending_owner_info.is_signer {
+            msg!("To accept ownership, the pending owner must be a signer");
+            return Err(StakingError::InvalidArgumentError.into());
+        }
+
+        let mut staking_pool = StakingPool::unpack(&staking_pool_info.data.borrow())?;
+        let pending_owner = staking_pool.pending_owner.ok_or_else(|| {
+            msg!("Staking pool has no pending owner");
+            StakingError::NoPendingOwner
+        })?;
+        if *pending_owner_info.key != pending_owner {
+            msg!("Pending owner didn't sign for accepting ownership");
+            return Err(StakingError::InvalidSigner.into());
+        }
+
+        if staking_pool_info.owner != program_id {
+            msg!("Staking pool is not owned by the staking program");
+            return Err(StakingError::InvalidAccountOwner.into());
+        }
+
+        staking_pool.owner_authority = pending_owner;
+        staking_pool.pending_owner = None;
         StakingPool::pack(staking_pool, &mut staking_pool_info.data.borrow_mut())?;
         Ok(())
     } else {
@@ -277,7 +376,7 @@ fn process_change_admin(
 
 fn process_change_duration(
     program_id: &Pubkey,
-    amount: i64,
+    amount: SlotDelta,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
@@ -451,8 +550,8 @@ fn process_init_staking_pool(
     program_id: &Pubkey,
     supply: u64,
     sub_supply: Option<u64>,
-    duration: u64,
-    earliest_reward_claim_time: Slot,
+    duration: Slots,
+    earliest_reward_claim_time: Slots,
     bump_seed_staking_program: u8,
     pool_owner_authority: Pubkey,
     admin_authority: Pubkey,
@@ -463,10 +562,20 @@ fn process_init_staking_pool(
         return Err(StakingError::InvalidSupplyError.into());
     }
 
-    if duration == 0 {
+    if sub_supply == Some(0) {
+        msg!("staking pool must have non zero sub supply");
+        return Err(StakingError::InvalidSupplyError.into());
+    }
+
+    if duration == Slots(0) {
         msg!("staking pool must have non zero duration");
         return Err(StakingError::InvalidDurationError.into());
     }
+
+    if !RatePerSlot::init(supply, sub_supply, duration.into())?.is_positive() {
+        msg!("staking pool supply is too small to distribute over its duration");
+        return Err(StakingError::RewardRateTooLow.into());
+    }
     let account_info_iter = &mut accounts.iter();
     if let [transfer_reward_token_authority_info, reward_token_supply_info, reward_token_pool_info, sub_reward_token_supply_info, sub_reward_token_pool_info, staking_pool_info, reward_token_mint_info, sub_reward_token_mint_info, staking_program_derived_info, rent_info, token_program_info] =
         next_account_infos(account_info_iter, 11)?
@@ -506,6 +615,7 @@ fn process_init_staking_pool(
             msg!("Reward token mint is not owned by the token program provided");
             return Err(StakingError::InvalidTokenOwner.into());
         }
+        let reward_token_mint = unpack_reward_token_mint(reward_token_mint_info)?;
 
         if reward_token_supply_info.owner != token_program_info.key
             || reward_token_pool_info.owner != token_program_info.key
@@ -542,11 +652,8 @@ fn process_init_staking_pool(
             token_program: token_program_info.clone(),
         })?;
 
+        let mut sub_reward_token_decimals = None;
         if let Some(sub_supply) = sub_supply {
-            if sub_supply == 0 {
-                msg!("staking pool must have non zero sub supply");
-                return Err(StakingError::InvalidSupplyError.into());
-            }
             let sub_reward_supply_token_account =
                 Account::unpack(&sub_reward_token_supply_info.data.borrow())
                     .map_err(|_| StakingError::InvalidRewardTokenSupplyAccount)?;
@@ -568,6 +675,8 @@ fn process_init_staking_pool(
                 msg!("Reward token mint is not owned by the token program provided");
                 return Err(StakingError::InvalidTokenOwner.into());
             }
+            sub_reward_token_decimals =
+                Some(unpack_reward_token_mint(sub_reward_token_mint_info)?.decimals);
 
             if sub_reward_token_supply_info.owner != token_program_info.key
                 || sub_reward_token_pool_info.owner != token_program_info.key
@@ -606,6 +715,8 @@ fn process_init_staking_pool(
             earliest_reward_claim_time,
             bump_seed_staking_program,
         )?;
+        staking_pool.reward_token_decimals = reward_token_mint.decimals;
+        staking_pool.sub_reward_token_decimals = sub_reward_token_decimals;
         StakingPool::pack(staking_pool, &mut staking_pool_info.data.borrow_mut())?;
 
         Ok(())
@@ -673,16 +784,22 @@ fn process_deposit(program_id: &Pubkey, amount: u64, accounts: &[AccountInfo]) -
         let mut staking_pool = StakingPool::unpack(&staking_pool_info.data.borrow())
             .map_err(|_| StakingError::InvalidStakingPool)?;
 
+        let mut stake_account = StakeAccount::unpack(&stake_account_info.data.borrow())
+            .map_err(|_| StakingError::InvalidStakeAccount)?;
+
+        // The stake balance mirrors collateral custodied by the pool owner (the lending market
+        // authority, signing through CPI), so the stake account owner cannot move it directly.
         if authority_info.key != &staking_pool.owner_authority
             && authority_info.key != &staking_pool.admin_authority
         {
-            msg!("deposit to account must be signed by the owner of the staking pool");
+            if authority_info.key == &stake_account.owner {
+                msg!("Stake account owner cannot deposit directly, it must go through the staking pool owner");
+            } else {
+                msg!("deposit to account must be signed by the owner of the staking pool");
+            }
             return Err(StakingError::InvalidSigner.into());
         }
 
-        let mut stake_account = StakeAccount::unpack(&stake_account_info.data.borrow())
-            .map_err(|_| StakingError::InvalidStakeAccount)?;
-
         if staking_pool_info.key != &stake_account.pool_pubkey {
             msg!("The staking pool is not the one that the stake account belongs to");
             return Err(StakingError::InvalidStakingPool.into());
@@ -727,16 +844,22 @@ fn process_withdraw(program_id: &Pubkey, amount: u64, accounts: &[AccountInfo])
         let mut staking_pool = StakingPool::unpack(&staking_pool_info.data.borrow())
             .map_err(|_| StakingError::InvalidStakingPool)?;
 
+        let mut stake_account = StakeAccount::unpack(&stake_account_info.data.borrow())
+            .map_err(|_| StakingError::InvalidStakeAccount)?;
+
+        // The stake balance mirrors collateral custodied by the pool owner (the lending market
+        // authority, signing through CPI), so the stake account owner cannot move it directly.
         if authority.key != &staking_pool.owner_authority
             && authority.key != &staking_pool.admin_authority
         {
-            msg!("withdraw from stake account must be signed by the owner of the staking pool");
+            if authority.key == &stake_account.owner {
+                msg!("Stake account owner cannot withdraw directly, it must go through the staking pool owner");
+            } else {
+                msg!("withdraw from stake account must be signed by the owner of the staking pool");
+            }
             return Err(StakingError::InvalidSigner.into());
         }
 
-        let mut stake_account = StakeAccount::unpack(&stake_account_info.data.borrow())
-            .map_err(|_| StakingError::InvalidStakeAccount)?;
-
         if staking_pool_info.key != &stake_account.pool_pubkey {
             msg!("The staking pool is not the one that the stake account belongs to");
             return Err(StakingError::InvalidStakingPool.into());
@@ -826,6 +949,7 @@ fn process_claim_reward(program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
             return Err(StakingError::InvalidRewardTokenPoolOwner.into());
         }
 
+        check_reward_supply(reward_token_pool_info, reward_claim_amount)?;
         //Todo remove debug log
         msg!("claim amount {}", reward_claim_amount);
         spl_token_transfer(TokenTransferParams {
@@ -850,6 +974,7 @@ fn process_claim_reward(program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
                 msg!("reward token pool is not the one associated with the staking pool");
                 return Err(StakingError::InvalidRewardTokenPool.into());
             }
+            check_reward_supply(sub_reward_token_pool_info, sub_reward_claim_amount)?;
             spl_token_transfer(TokenTransferParams {
                 source: sub_reward_token_pool_info.clone(),
                 destination: sub_reward_destination_info.clone(),
@@ -860,6 +985,7 @@ fn process_claim_reward(program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
             })?;
         }
 
+        staking_pool.record_distribution(reward_claim_amount, sub_reward_claim_amount)?;
         StakeAccount::pack(stake_account, &mut stake_account_info.data.borrow_mut())?;
         StakingPool::pack(staking_pool, &mut staking_pool_info.data.borrow_mut())?;
         Ok(())
@@ -869,9 +995,77 @@ fn process_claim_reward(program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
     }
 }
 
+/// Fails a claim paying out more than is left in the reward token pool, so the distributed
+/// total never exceeds the supply the pool was funded with
+fn check_reward_supply(reward_token_pool_info: &AccountInfo, amount: u64) -> ProgramResult {
+    let reward_token_pool = Account::unpack(&reward_token_pool_info.data.borrow())
+        .map_err(|_| StakingError::InvalidRewardTokenPool)?;
+    if amount > reward_token_pool.amount {
+        msg!(
+            "Claim of {} exceeds the reward left in the pool {}",
+            amount,
+            reward_token_pool.amount
+        );
+        return Err(StakingError::RewardExceedsSupply.into());
+    }
+    Ok(())
+}
+
+fn process_claim_reward_to_associated_token_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    if let [stake_account_owner, stake_account_info, staking_pool_info, reward_token_pool_info, reward_destination_info, staking_program_derived_info, clock_info, token_program_info, reward_token_mint_info, system_program_info, associated_token_program_info] =
+        next_account_infos(account_info_iter, 11)?
+    {
+        create_associated_token_account_if_absent(AssociatedTokenAccountParams {
+            account: reward_destination_info.clone(),
+            mint: reward_token_mint_info.clone(),
+            owner: stake_account_owner.clone(),
+            system_program: system_program_info.clone(),
+            token_program: token_program_info.clone(),
+            associated_token_program: associated_token_program_info.clone(),
+        })?;
+        let mut claim_reward_accounts = vec![
+            stake_account_owner.clone(),
+            stake_account_info.clone(),
+            staking_pool_info.clone(),
+            reward_token_pool_info.clone(),
+            reward_destination_info.clone(),
+            staking_program_derived_info.clone(),
+            clock_info.clone(),
+            token_program_info.clone(),
+        ];
+
+        if !account_info_iter.as_slice().is_empty() {
+            if let [sub_reward_token_pool_info, sub_reward_destination_info, sub_reward_token_mint_info] =
+                next_account_infos(account_info_iter, 3)?
+            {
+                create_associated_token_account_if_absent(AssociatedTokenAccountParams {
+                    account: sub_reward_destination_info.clone(),
+                    mint: sub_reward_token_mint_info.clone(),
+                    owner: stake_account_owner.clone(),
+                    system_program: system_program_info.clone(),
+                    token_program: token_program_info.clone(),
+                    associated_token_program: associated_token_program_info.clone(),
+                })?;
+                claim_reward_accounts.push(sub_reward_token_pool_info.clone());
+                claim_reward_accounts.push(sub_reward_destination_info.clone());
+            }
+        }
+
+        process_claim_reward(program_id, &claim_reward_accounts)
+    } else {
+        msg!("Wrong number of accounts");
+        Err(StakingError::InvalidArgumentError.into())
+    }
+}
+
 fn process_update_earliest_reward_claim_time(
     program_id: &Pubkey,
-    time: Slot,
+    time: Slots,
+    allow_backward: bool,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
@@ -896,7 +1090,12 @@ fn process_update_earliest_reward_claim_time(
             return Err(StakingError::InvalidSigner.into());
         }
 
-        staking_pool.earliest_reward_claim_time = time;
+        if !allow_backward && time < Slots(staking_pool.earliest_reward_claim_time) {
+            msg!("Earliest reward claim time cannot be moved before the current one");
+            return Err(StakingError::InvalidClaimTimeChange.into());
+        }
+
+        staking_pool.earliest_reward_claim_time = time.into();
 
         StakingPool::pack(staking_pool, &mut staking_pool_info.data.borrow_mut())?;
         Ok(())
@@ -921,8 +1120,16 @@ fn spl_token_init_account(params: TokenInitializeAccountParams<'_>) -> ProgramRe
         mint.key,
         owner.key,
     )?;
+    let account_key = *account.key;
     let result = invoke(&ix, &[account, mint, owner, rent, token_program]);
-    result.map_err(|_| StakingError::TokenInitializeAccountFailed.into())
+    result.map_err(|error| {
+        msg!(
+            "Initializing token account {} failed: {}",
+            account_key,
+            describe_token_error(&error)
+        );
+        StakingError::TokenInitializeAccountFailed.into()
+    })
 }
 
 /// Issue a spl_token `Transfer` instruction.
@@ -936,6 +1143,7 @@ fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult {
         amount,
         authority_signer_seeds,
     } = params;
+    let (source_key, destination_key) = (*source.key, *destination.key);
     let result = if authority_signer_seeds.is_empty() {
         invoke(
             &spl_token::instruction::transfer(
@@ -963,7 +1171,77 @@ fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult {
         )
     };
 
-    result.map_err(|_| StakingError::TokenTransferFailed.into())
+    result.map_err(|error| {
+        msg!(
+            "Transfer of {} from {} to {} failed: {}",
+            amount,
+            source_key,
+            destination_key,
+            describe_token_error(&error)
+        );
+        StakingError::TokenTransferFailed.into()
+    })
+}
+
+/// Describe an error returned by the token program, naming the SPL Token error for its custom
+/// error codes
+fn describe_token_error(error: &ProgramError) -> String {
+    match error {
+        ProgramError::Custom(code) => TokenError::from_u32(*code)
+            .map(|token_error| token_error.to_string())
+            .unwrap_or_else(|| error.to_string()),
+        _ => error.to_string(),
+    }
+}
+
+/// Create the associated token account of `owner` for `mint`, paid for by `owner`, unless it
+/// already exists.
+fn create_associated_token_account_if_absent(
+    params: AssociatedTokenAccountParams<'_>,
+) -> ProgramResult {
+    let AssociatedTokenAccountParams {
+        account,
+        mint,
+        owner,
+        system_program,
+        token_program,
+        associated_token_program,
+    } = params;
+    if account.key != &get_associated_token_address(owner.key, mint.key) {
+        msg!("Reward destination is not the associated token account of the stake account owner");
+        return Err(StakingError::InvalidAssociatedTokenAccount.into());
+    }
+    if associated_token_program.key != &spl_associated_token_account::id() {
+        msg!(
+            "Associated token account program provided is not the associated token account program"
+        );
+        return Err(StakingError::InvalidArgumentError.into());
+    }
+    if account.owner == token_program.key {
+        return Ok(());
+    }
+    let result = invoke(
+        &create_associated_token_account(owner.key, owner.key, mint.key),
+        &[
+            owner.clone(),
+            account,
+            owner,
+            mint,
+            system_program,
+            token_program,
+            associated_token_program,
+        ],
+    );
+    result.map_err(|_| StakingError::AssociatedTokenAccountCreateFailed.into())
+}
+
+struct AssociatedTokenAccountParams<'a> {
+    account: AccountInfo<'a>,
+    mint: AccountInfo<'a>,
+    owner: AccountInfo<'a>,
+    system_program: AccountInfo<'a>,
+    token_program: AccountInfo<'a>,
+    associated_token_program: AccountInfo<'a>,
 }
 
 struct TokenInitializeAccountParams<'a> {
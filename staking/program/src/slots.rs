@@ -0,0 +1,119 @@
+//! Slot based durations and times, so they can't be mixed up with seconds
+
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use solana_program::clock::Slot;
+
+/// A number of slots, or a slot counted from genesis
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Slots(pub u64);
+
+/// A signed change to a number of slots
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SlotDelta(pub i64);
+
+impl Slots {
+    pub fn to_le_bytes(self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+
+    pub fn from_le_bytes(bytes: [u8; 8]) -> Self {
+        Self(u64::from_le_bytes(bytes))
+    }
+}
+
+impl SlotDelta {
+    pub fn to_le_bytes(self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+
+    pub fn from_le_bytes(bytes: [u8; 8]) -> Self {
+        Self(i64::from_le_bytes(bytes))
+    }
+}
+
+impl From<Slot> for Slots {
+    fn from(slots: Slot) -> Self {
+        Self(slots)
+    }
+}
+
+impl From<Slots> for Slot {
+    fn from(slots: Slots) -> Self {
+        slots.0
+    }
+}
+
+impl From<i64> for SlotDelta {
+    fn from(delta: i64) -> Self {
+        Self(delta)
+    }
+}
+
+impl From<SlotDelta> for i64 {
+    fn from(delta: SlotDelta) -> Self {
+        delta.0
+    }
+}
+
+impl FromStr for Slots {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+impl FromStr for SlotDelta {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+impl fmt::Display for Slots {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for SlotDelta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_and_display() {
+        assert_eq!("1000".parse::<Slots>(), Ok(Slots(1000)));
+        assert_eq!("-250".parse::<SlotDelta>(), Ok(SlotDelta(-250)));
+        assert!("-250".parse::<Slots>().is_err());
+        assert!("1.5".parse::<Slots>().is_err());
+        assert_eq!(Slots(1000).to_string(), "1000");
+        assert_eq!(SlotDelta(-250).to_string(), "-250");
+    }
+
+    #[test]
+    fn le_bytes_match_raw_values() {
+        for slots in [0, 1, 1000, u64::MAX] {
+            assert_eq!(Slots(slots).to_le_bytes(), slots.to_le_bytes());
+            assert_eq!(Slots::from_le_bytes(slots.to_le_bytes()), Slots(slots));
+            assert_eq!(Slot::from(Slots::from(slots)), slots);
+        }
+        for delta in [i64::MIN, -1, 0, 1, i64::MAX] {
+            assert_eq!(SlotDelta(delta).to_le_bytes(), delta.to_le_bytes());
+            assert_eq!(
+                SlotDelta::from_le_bytes(delta.to_le_bytes()),
+                SlotDelta(delta)
+            );
+            assert_eq!(i64::from(SlotDelta::from(delta)), delta);
+        }
+    }
+}
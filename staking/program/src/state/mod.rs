@@ -17,7 +17,8 @@ pub const PROGRAM_VERSION: u8 = 1;
 /// will have the version set to 0.
 pub const UNINITIALIZED_VERSION: u8 = 0;
 
-///pack coption of pubkey into buffer
+/// Pack an Option<Pubkey> into 33 bytes: a tag byte of 0 for None or 1 for Some, followed by
+/// the key. The key bytes are zeroed for None so a zeroed buffer and a packed None are the same.
 pub fn pack_option_key(src: &Option<Pubkey>, dst: &mut [u8; 1 + PUBKEY_BYTES]) {
     match src {
         Option::Some(key) => {
@@ -25,12 +26,12 @@ pub fn pack_option_key(src: &Option<Pubkey>, dst: &mut [u8; 1 + PUBKEY_BYTES]) {
             dst[1..].copy_from_slice(key.as_ref());
         }
         Option::None => {
-            dst[0] = 0;
+            *dst = [0; 1 + PUBKEY_BYTES];
         }
     }
 }
 
-///unpack coption pubkey from buffer
+/// Unpack an Option<Pubkey> packed by `pack_option_key`, an all-zero buffer is None
 pub fn unpack_option_key(src: &[u8; 1 + PUBKEY_BYTES]) -> Result<Option<Pubkey>, ProgramError> {
     match src[0] {
         0 => Ok(Option::None),
@@ -44,6 +45,27 @@ pub fn unpack_option_key(src: &[u8; 1 + PUBKEY_BYTES]) -> Result<Option<Pubkey>,
     }
 }
 
+/// Pack an Option<u8> into 2 bytes: a tag byte of 0 for None or 1 for Some, followed by the
+/// value, which is zeroed for None
+pub fn pack_option_u8(src: &Option<u8>, dst: &mut [u8; 2]) {
+    *dst = match src {
+        Option::Some(x) => [1, *x],
+        Option::None => [0; 2],
+    };
+}
+
+/// Unpack an Option<u8> packed by `pack_option_u8`, an all-zero buffer is None
+pub fn unpack_option_u8(src: &[u8; 2]) -> Result<Option<u8>, ProgramError> {
+    match src[0] {
+        0 => Ok(Option::None),
+        1 => Ok(Option::Some(src[1])),
+        _ => {
+            msg!("Option<u8> cannot be unpacked");
+            Err(ProgramError::InvalidAccountData)
+        }
+    }
+}
+
 ///pack coption of pubkey into buffer
 pub fn pack_option_decimal(src: &Option<Decimal>, dst: &mut [u8; 1 + Decimal::LEN]) {
     match src {
@@ -70,3 +92,45 @@ pub fn unpack_option_decimal(
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn option_key_round_trip() {
+        for key in [None, Some(Pubkey::default()), Some(Pubkey::new_unique())] {
+            let mut dst = [0xff; 1 + PUBKEY_BYTES];
+            pack_option_key(&key, &mut dst);
+            assert_eq!(unpack_option_key(&dst).unwrap(), key);
+        }
+
+        // a zeroed account reads back as None rather than the default pubkey
+        assert_eq!(unpack_option_key(&[0; 1 + PUBKEY_BYTES]).unwrap(), None);
+
+        // packing None clears any stale key
+        let mut dst = [0xff; 1 + PUBKEY_BYTES];
+        pack_option_key(&None, &mut dst);
+        assert_eq!(dst, [0; 1 + PUBKEY_BYTES]);
+
+        let mut invalid_tag = [0; 1 + PUBKEY_BYTES];
+        invalid_tag[0] = 2;
+        assert_eq!(
+            unpack_option_key(&invalid_tag),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+    #[test]
+    fn option_u8_round_trip() {
+        for value in [None, Some(0), Some(9), Some(u8::MAX)] {
+            let mut dst = [0xff; 2];
+            pack_option_u8(&value, &mut dst);
+            assert_eq!(unpack_option_u8(&dst).unwrap(), value);
+        }
+        assert_eq!(unpack_option_u8(&[0; 2]).unwrap(), None);
+        assert_eq!(
+            unpack_option_u8(&[2, 0]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
@@ -183,6 +183,15 @@ impl StakeAccount {
         self.start_rate = current_rate;
         Ok(reward_lamports)
     }
+
+    /// Reward and sub reward `claim_reward` would pay at the pool's current cumulative rate,
+    /// leaving the stake account untouched
+    pub fn pending_reward(
+        &self,
+        pool_rate: CumulativeRate,
+    ) -> Result<(u64, Option<u64>), ProgramError> {
+        self.clone().claim_reward(pool_rate)
+    }
 }
 impl Sealed for StakeAccount {}
 impl IsInitialized for StakeAccount {
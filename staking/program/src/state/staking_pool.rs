@@ -12,10 +12,11 @@ use solana_program::{msg, pubkey::Pubkey};
 
 use crate::error::StakingError;
 use crate::math::{Decimal, TryAdd, TryDiv, TryMul, TrySub};
+use crate::slots::{SlotDelta, Slots};
 use crate::solana_program::program_pack::{IsInitialized, Pack, Sealed};
 use crate::state::{
-    pack_option_decimal, pack_option_key, unpack_option_decimal, unpack_option_key,
-    PROGRAM_VERSION, UNINITIALIZED_VERSION,
+    pack_option_decimal, pack_option_key, pack_option_u8, unpack_option_decimal, unpack_option_key,
+    unpack_option_u8, PROGRAM_VERSION, UNINITIALIZED_VERSION,
 };
 
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -34,8 +35,17 @@ pub struct StakingPool {
     pub pool_size: u64,
     pub bump_seed_staking_program: u8,
     pub sub_reward_token_pool: Option<Pubkey>,
-    pub reserve_fields3: [u8; 32],
-    pub reserve_fields4: [u8; 29],
+    /// Owner proposed by the current owner, who must accept before taking control
+    pub pending_owner: Option<Pubkey>,
+    /// Decimals of the reward token mint, 0 for pools initialized before they were recorded
+    pub reward_token_decimals: u8,
+    /// Decimals of the sub reward token mint, if the pool has a sub reward
+    pub sub_reward_token_decimals: Option<u8>,
+    /// Reward tokens paid out to stakers by claims
+    pub total_distributed: u64,
+    /// Sub reward tokens paid out to stakers by claims, 0 if the pool has no sub reward
+    pub sub_total_distributed: u64,
+    pub reserve_fields4: [u8; 9],
 }
 
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
@@ -54,6 +64,11 @@ impl RatePerSlot {
         })
     }
 
+    /// Whether every reward is distributed at a rate that doesn't floor to zero
+    pub fn is_positive(&self) -> bool {
+        self.reward > Decimal::zero() && self.sub_reward != Some(Decimal::zero())
+    }
+
     pub fn try_floor_u64(&self) -> Result<(u64, Option<u64>), ProgramError> {
         Ok((
             self.reward.try_floor_u64()?,
@@ -137,24 +152,29 @@ impl StakingPool {
         admin_authority: Pubkey,
         reward_token_pool_pubkey: Pubkey,
         sub_reward_token_pool_pubkey: Option<Pubkey>,
-        duration: u64,
+        duration: Slots,
         supply: u64,
         sub_supply: Option<u64>,
-        earliest_reward_claim_time: Slot,
+        earliest_reward_claim_time: Slots,
         bump_seed_staking_program: u8,
     ) -> ProgramResult {
+        let duration = Slot::from(duration);
         if supply == 0 {
             Err(StakingError::InvalidSupplyError.into())
         } else if duration == 0 {
             Err(StakingError::InvalidDurationError.into())
         } else {
+            let rate_per_slot = RatePerSlot::init(supply, sub_supply, duration)?;
+            if !rate_per_slot.is_positive() {
+                return Err(StakingError::RewardRateTooLow.into());
+            }
             self.version = PROGRAM_VERSION;
             self.owner_authority = owner_authority;
             self.admin_authority = admin_authority;
             self.reward_token_pool = reward_token_pool_pubkey;
             self.duration = duration;
-            self.rate_per_slot = RatePerSlot::init(supply, sub_supply, duration)?;
-            self.earliest_reward_claim_time = earliest_reward_claim_time;
+            self.rate_per_slot = rate_per_slot;
+            self.earliest_reward_claim_time = earliest_reward_claim_time.into();
             self.bump_seed_staking_program = bump_seed_staking_program;
             self.sub_reward_token_pool = sub_reward_token_pool_pubkey;
             self.cumulative_rate.sub_reward = sub_supply.map(|_| Decimal::zero());
@@ -168,6 +188,10 @@ impl StakingPool {
             .ok_or(StakingError::InvalidCurrentTimeError)?;
 
         self.last_update = mark_time;
+        // nobody is staked, so rewards for the elapsed slots stay in the supply
+        if self.pool_size == 0 {
+            return Ok(self.cumulative_rate);
+        }
         self.cumulative_rate
             .accumulate_rate(&self.rate_per_slot, time_elapsed, self.pool_size)
     }
@@ -177,6 +201,21 @@ impl StakingPool {
         Ok(self.cumulative_rate)
     }
 
+    /// Adds the reward paid out by a claim to the distributed totals
+    pub fn record_distribution(&mut self, amount: u64, sub_amount: Option<u64>) -> ProgramResult {
+        self.total_distributed = self
+            .total_distributed
+            .checked_add(amount)
+            .ok_or(StakingError::MathOverflow)?;
+        if let Some(sub_amount) = sub_amount {
+            self.sub_total_distributed = self
+                .sub_total_distributed
+                .checked_add(sub_amount)
+                .ok_or(StakingError::MathOverflow)?;
+        }
+        Ok(())
+    }
+
     pub fn update_reward_supply(
         &mut self,
         amount: i64,
@@ -240,7 +279,12 @@ impl StakingPool {
         Ok(())
     }
 
-    pub fn extend_duration(&mut self, extend_amount: i64, current_time: Slot) -> ProgramResult {
+    pub fn extend_duration(
+        &mut self,
+        extend_amount: SlotDelta,
+        current_time: Slot,
+    ) -> ProgramResult {
+        let extend_amount = i64::from(extend_amount);
         if self.end_time == 0 {
             let duration = self.duration;
             self.duration = m! {
@@ -296,7 +340,9 @@ impl StakingPool {
             msg!("Cannot deposit zero amount");
             return Err(StakingError::StakeDepositsZero.into());
         }
-        if self.pool_size == 0u64 {
+        // mining starts with the first deposit and is not restarted if the pool later empties,
+        // otherwise the remaining supply would be paid out again over a full duration
+        if self.end_time == 0u64 {
             self.end_time = current_time
                 .checked_add(self.duration)
                 .ok_or(StakingError::MathOverflow)?;
@@ -356,7 +402,14 @@ impl Pack for StakingPool {
         + 1
         + Decimal::LEN
         + 1
-        + 61;
+        + PUBKEY_BYTES
+        + 1
+        + 1
+        + 1
+        + 1
+        + 8
+        + 8
+        + 9;
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let output = array_mut_ref![dst, 0, StakingPool::LEN];
@@ -377,6 +430,11 @@ impl Pack for StakingPool {
             sub_reward_token_pool,
             sub_rate_per_slot,
             sub_cumulative_rate,
+            pending_owner,
+            reward_token_decimals,
+            sub_reward_token_decimals,
+            total_distributed,
+            sub_total_distributed,
             _,
         ) = mut_array_refs![
             output,
@@ -395,7 +453,12 @@ impl Pack for StakingPool {
             PUBKEY_BYTES + 1,
             Decimal::LEN + 1,
             Decimal::LEN + 1,
-            61
+            PUBKEY_BYTES + 1,
+            1,
+            1 + 1,
+            8,
+            8,
+            9
         ];
         *version = self.version.to_le_bytes();
         owner_authority.copy_from_slice(self.owner_authority.as_ref());
@@ -412,6 +475,11 @@ impl Pack for StakingPool {
         *pool_size = self.pool_size.to_le_bytes();
         *bump_seed_staking_program = self.bump_seed_staking_program.to_le_bytes();
         pack_option_key(&self.sub_reward_token_pool, sub_reward_token_pool);
+        pack_option_key(&self.pending_owner, pending_owner);
+        *reward_token_decimals = self.reward_token_decimals.to_le_bytes();
+        pack_option_u8(&self.sub_reward_token_decimals, sub_reward_token_decimals);
+        *total_distributed = self.total_distributed.to_le_bytes();
+        *sub_total_distributed = self.sub_total_distributed.to_le_bytes();
     }
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let input = array_ref![src, 0, StakingPool::LEN];
@@ -432,6 +500,11 @@ impl Pack for StakingPool {
             sub_reward_token_pool,
             sub_rate_per_slot,
             sub_cumulative_rate,
+            pending_owner,
+            reward_token_decimals,
+            sub_reward_token_decimals,
+            total_distributed,
+            sub_total_distributed,
             _,
         ) = array_refs![
             input,
@@ -450,7 +523,12 @@ impl Pack for StakingPool {
             PUBKEY_BYTES + 1,
             Decimal::LEN + 1,
             Decimal::LEN + 1,
-            61
+            PUBKEY_BYTES + 1,
+            1,
+            1 + 1,
+            8,
+            8,
+            9
         ];
         let version = u8::from_le_bytes(*version);
         if version > PROGRAM_VERSION {
@@ -472,8 +550,11 @@ impl Pack for StakingPool {
         let pool_size = u64::from_le_bytes(*pool_size);
         let bump_seed_staking_program = u8::from_le_bytes(*bump_seed_staking_program);
         let sub_reward_token_pool = unpack_option_key(sub_reward_token_pool)?;
-
-        let reserve_field = [0; 32];
+        let pending_owner = unpack_option_key(pending_owner)?;
+        let reward_token_decimals = u8::from_le_bytes(*reward_token_decimals);
+        let sub_reward_token_decimals = unpack_option_u8(sub_reward_token_decimals)?;
+        let total_distributed = u64::from_le_bytes(*total_distributed);
+        let sub_total_distributed = u64::from_le_bytes(*sub_total_distributed);
         Ok(StakingPool {
             version,
             owner_authority,
@@ -494,8 +575,271 @@ impl Pack for StakingPool {
             pool_size,
             bump_seed_staking_program,
             sub_reward_token_pool,
-            reserve_fields3: reserve_field,
-            reserve_fields4: [0; 29],
+            pending_owner,
+            reward_token_decimals,
+            sub_reward_token_decimals,
+            total_distributed,
+            sub_total_distributed,
+            reserve_fields4: [0; 9],
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::WAD;
+
+    fn mining_pool(duration: u64, supply: u64) -> StakingPool {
+        let mut staking_pool = StakingPool::default();
+        staking_pool
+            .init(
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Some(Pubkey::new_unique()),
+                Slots(duration),
+                supply,
+                Some(supply * 2),
+                Slots(0),
+                0,
+            )
+            .unwrap();
+        staking_pool
+    }
+
+    fn distributed(staking_pool: &StakingPool, stake: u64) -> (u64, u64) {
+        let rate = staking_pool.cumulative_rate;
+        (
+            rate.reward.try_mul(stake).unwrap().try_floor_u64().unwrap(),
+            rate.sub_reward
+                .unwrap()
+                .try_mul(stake)
+                .unwrap()
+                .try_floor_u64()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn extend_and_reduce_duration_reconcile_to_supply() {
+        let mut staking_pool = mining_pool(1000, 100_000);
+        staking_pool.deposit(0, 10).unwrap();
+        assert_eq!(staking_pool.end_time, 1000);
+
+        staking_pool.claim_reward(500).unwrap();
+        assert_eq!(distributed(&staking_pool, 10), (50_000, 100_000));
+
+        // 50_000 left over the next 1000 slots
+        staking_pool.extend_duration(SlotDelta(500), 500).unwrap();
+        assert_eq!(staking_pool.end_time, 1500);
+        assert_eq!(staking_pool.duration, 1500);
+        assert_eq!(
+            staking_pool.rate_per_slot,
+            RatePerSlot {
+                reward: Decimal::from(50u64),
+                sub_reward: Some(Decimal::from(100u64)),
+            }
+        );
+
+        // 40_000 left over the next 500 slots
+        staking_pool.extend_duration(SlotDelta(-300), 700).unwrap();
+        assert_eq!(staking_pool.end_time, 1200);
+        assert_eq!(staking_pool.duration, 1200);
+        assert_eq!(
+            staking_pool.cumulative_rate,
+            CumulativeRate {
+                reward: Decimal::from(6000u64),
+                sub_reward: Some(Decimal::from(12_000u64)),
+            }
+        );
+        assert_eq!(
+            staking_pool.rate_per_slot,
+            RatePerSlot {
+                reward: Decimal::from(80u64),
+                sub_reward: Some(Decimal::from(160u64)),
+            }
+        );
+
+        staking_pool.claim_reward(2000).unwrap();
+        assert_eq!(staking_pool.last_update, 1200);
+        assert_eq!(distributed(&staking_pool, 10), (100_000, 200_000));
+    }
+
+    #[test]
+    fn duration_changes_never_over_distribute() {
+        let supply = 99_999;
+        let mut staking_pool = mining_pool(1000, supply);
+        let mut paid = (Decimal::zero(), Decimal::zero());
+        let mut accrue_to = |staking_pool: &mut StakingPool, current_time: Slot| {
+            let before = staking_pool.cumulative_rate;
+            let after = staking_pool.claim_reward(current_time).unwrap();
+            let pool_size = staking_pool.pool_size;
+            paid.0 = after
+                .reward
+                .try_sub(before.reward)
+                .and_then(|r| r.try_mul(pool_size))
+                .and_then(|r| r.try_add(paid.0))
+                .unwrap();
+            paid.1 = after
+                .sub_reward
+                .unwrap()
+                .try_sub(before.sub_reward.unwrap())
+                .and_then(|r| r.try_mul(pool_size))
+                .and_then(|r| r.try_add(paid.1))
+                .unwrap();
+        };
+
+        staking_pool.deposit(0, 7).unwrap();
+        accrue_to(&mut staking_pool, 333);
+        staking_pool.extend_duration(SlotDelta(777), 333).unwrap();
+        accrue_to(&mut staking_pool, 600);
+        staking_pool.deposit(600, 6).unwrap();
+        accrue_to(&mut staking_pool, 901);
+        staking_pool.extend_duration(SlotDelta(-555), 901).unwrap();
+        accrue_to(&mut staking_pool, 1000);
+        staking_pool.withdraw(1000, 5).unwrap();
+        accrue_to(&mut staking_pool, 5000);
+
+        // each change floors the remaining supply, losing less than one token
+        let reward = paid.0.try_floor_u64().unwrap();
+        let sub_reward = paid.1.try_floor_u64().unwrap();
+        assert!(reward <= supply && supply - reward <= 2);
+        assert!(sub_reward <= supply * 2 && supply * 2 - sub_reward <= 2);
+    }
+
+    #[test]
+    fn empty_pool_does_not_restart_mining() {
+        let mut staking_pool = mining_pool(1000, 100_000);
+        staking_pool.deposit(0, 10).unwrap();
+        staking_pool.withdraw(400, 10).unwrap();
+        assert_eq!(distributed(&staking_pool, 10), (40_000, 80_000));
+
+        // no stakers to accrue to, the rate is carried over to the remaining slots
+        staking_pool.extend_duration(SlotDelta(400), 500).unwrap();
+        assert_eq!(staking_pool.end_time, 1400);
+
+        staking_pool.deposit(600, 10).unwrap();
+        assert_eq!(staking_pool.end_time, 1400);
+        assert_eq!(staking_pool.last_update, 600);
+
+        staking_pool.claim_reward(5000).unwrap();
+        let (reward, sub_reward) = distributed(&staking_pool, 10);
+        assert!(reward <= 100_000);
+        assert!(sub_reward <= 200_000);
+    }
+
+    #[test]
+    fn total_distributed_sums_claims_within_supply() {
+        use crate::state::stake_account::StakeAccount;
+
+        let supply = 100_000;
+        let mut staking_pool = mining_pool(1000, supply);
+        let mut stake_accounts = [StakeAccount::default(), StakeAccount::default()];
+        let rate = staking_pool.deposit(0, 3).unwrap();
+        stake_accounts[0].deposit(rate, 3).unwrap();
+        let rate = staking_pool.deposit(100, 7).unwrap();
+        stake_accounts[1].deposit(rate, 7).unwrap();
+
+        let mut claimed = (0, 0);
+        for (i, current_time) in [250, 251, 600, 999, 1000, 4000].iter().enumerate() {
+            let stake_account = &mut stake_accounts[i % 2];
+            let (reward, sub_reward) = staking_pool
+                .claim_reward(*current_time)
+                .and_then(|rate| stake_account.claim_reward(rate))
+                .unwrap();
+            staking_pool
+                .record_distribution(reward, sub_reward)
+                .unwrap();
+            claimed = (claimed.0 + reward, claimed.1 + sub_reward.unwrap());
+            assert_eq!(
+                (
+                    staking_pool.total_distributed,
+                    staking_pool.sub_total_distributed
+                ),
+                claimed
+            );
+            assert!(staking_pool.total_distributed <= supply);
+            assert!(staking_pool.sub_total_distributed <= supply * 2);
+        }
+
+        // claiming again once mining ended pays out nothing more
+        for stake_account in stake_accounts.iter_mut() {
+            let (reward, sub_reward) = staking_pool
+                .claim_reward(5000)
+                .and_then(|rate| stake_account.claim_reward(rate))
+                .unwrap();
+            staking_pool
+                .record_distribution(reward, sub_reward)
+                .unwrap();
+        }
+        // only the fractions of a token floored away per claim are left undistributed
+        assert!(
+            staking_pool.total_distributed <= supply
+                && supply - staking_pool.total_distributed <= 2
+        );
+        assert!(
+            staking_pool.sub_total_distributed <= supply * 2
+                && supply * 2 - staking_pool.sub_total_distributed <= 2
+        );
+
+        let mut packed = [0; StakingPool::LEN];
+        StakingPool::pack(staking_pool.clone(), &mut packed).unwrap();
+        assert_eq!(StakingPool::unpack(&packed).unwrap(), staking_pool);
+    }
+
+    #[test]
+    fn pending_reward_matches_claim() {
+        use crate::state::stake_account::StakeAccount;
+
+        let mut staking_pool = mining_pool(1000, 100_000);
+        let mut stake_account = StakeAccount::default();
+        let rate = staking_pool.deposit(0, 3).unwrap();
+        stake_account.deposit(rate, 3).unwrap();
+        let rate = staking_pool.deposit(100, 7).unwrap();
+        let before = stake_account.clone();
+
+        // 100 slots of the whole reward rate, floored by the per-stake rate of a pool of 3
+        assert_eq!(
+            stake_account.pending_reward(rate),
+            Ok((9_999, Some(19_999)))
+        );
+        assert_eq!(stake_account, before);
+
+        for current_time in [333, 334, 999, 4000].iter() {
+            let rate = staking_pool.claim_reward(*current_time).unwrap();
+            let pending = stake_account.pending_reward(rate).unwrap();
+            assert_eq!(stake_account.claim_reward(rate), Ok(pending));
+            assert_eq!(stake_account.pending_reward(rate), Ok((0, Some(0))));
+        }
+    }
+
+    #[test]
+    fn init_rejects_reward_rate_flooring_to_zero() {
+        let init = |supply, sub_supply: Option<u64>, duration| {
+            StakingPool::default().init(
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                sub_supply.map(|_| Pubkey::new_unique()),
+                Slots(duration),
+                supply,
+                sub_supply,
+                Slots(0),
+                0,
+            )
+        };
+        // 1 token over 2 * 10^18 slots is below the smallest representable rate
+        assert_eq!(
+            init(1, None, 2 * WAD),
+            Err(StakingError::RewardRateTooLow.into())
+        );
+        assert_eq!(
+            init(1_000, Some(1), 2 * WAD),
+            Err(StakingError::RewardRateTooLow.into())
+        );
+        // while 1 token over 10^18 slots is exactly the smallest
+        assert_eq!(init(1, None, WAD), Ok(()));
+        assert_eq!(init(1, Some(1), WAD), Ok(()));
+    }
+}
@@ -6,6 +6,7 @@ use port_finance_staking::error::StakingError;
 use port_finance_staking::solana_program::instruction::InstructionError;
 use solana_program::pubkey::Pubkey;
 use solana_program_test::*;
+use solana_sdk::signature::{Keypair, Signer};
 use solana_sdk::transaction::TransactionError;
 
 #[tokio::test]
@@ -52,6 +53,144 @@ async fn test_change_owner_fail() {
     staking_pool.validate_state(&mut banks_client).await;
 }
 
+#[tokio::test]
+async fn test_propose_and_accept_owner() {
+    let mut test = staking_test!();
+
+    // limit to track compute unit increase
+    test.set_compute_max_units(50_000);
+    let mut staking_pool =
+        add_staking_pool(&mut test, spl_token::native_mint::id(), 1000, 100, None, 0);
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+    let old_owner = staking_pool.staking_pool.owner_authority;
+    let new_owner = Keypair::new();
+    staking_pool
+        .propose_new_owner(&mut banks_client, new_owner.pubkey(), &payer, true)
+        .await
+        .unwrap();
+    assert_eq!(staking_pool.staking_pool.owner_authority, old_owner);
+    assert_eq!(
+        staking_pool.staking_pool.pending_owner,
+        Some(new_owner.pubkey())
+    );
+    staking_pool.validate_state(&mut banks_client).await;
+
+    staking_pool
+        .accept_ownership(&mut banks_client, &new_owner, &payer)
+        .await
+        .unwrap();
+    assert_eq!(
+        staking_pool.staking_pool.owner_authority,
+        new_owner.pubkey()
+    );
+    assert_eq!(staking_pool.staking_pool.pending_owner, None);
+    staking_pool.validate_state(&mut banks_client).await;
+}
+
+#[tokio::test]
+async fn test_accept_owner_wrong_key_fail() {
+    let mut test = staking_test!();
+
+    // limit to track compute unit increase
+    test.set_compute_max_units(50_000);
+    let mut staking_pool =
+        add_staking_pool(&mut test, spl_token::native_mint::id(), 1000, 100, None, 0);
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+    let new_owner = Keypair::new();
+    staking_pool
+        .propose_new_owner(&mut banks_client, new_owner.pubkey(), &payer, true)
+        .await
+        .unwrap();
+
+    let err = staking_pool
+        .accept_ownership(&mut banks_client, &Keypair::new(), &payer)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::InvalidSigner as u32)
+        )
+    );
+    staking_pool.validate_state(&mut banks_client).await;
+}
+
+#[tokio::test]
+async fn test_overwrite_pending_owner() {
+    let mut test = staking_test!();
+
+    // limit to track compute unit increase
+    test.set_compute_max_units(50_000);
+    let mut staking_pool =
+        add_staking_pool(&mut test, spl_token::native_mint::id(), 1000, 100, None, 0);
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+    let first_owner = Keypair::new();
+    let second_owner = Keypair::new();
+    staking_pool
+        .propose_new_owner(&mut banks_client, first_owner.pubkey(), &payer, true)
+        .await
+        .unwrap();
+    staking_pool
+        .propose_new_owner(&mut banks_client, second_owner.pubkey(), &payer, true)
+        .await
+        .unwrap();
+    assert_eq!(
+        staking_pool.staking_pool.pending_owner,
+        Some(second_owner.pubkey())
+    );
+    staking_pool.validate_state(&mut banks_client).await;
+
+    let err = staking_pool
+        .accept_ownership(&mut banks_client, &first_owner, &payer)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::InvalidSigner as u32)
+        )
+    );
+
+    staking_pool
+        .accept_ownership(&mut banks_client, &second_owner, &payer)
+        .await
+        .unwrap();
+    assert_eq!(
+        staking_pool.staking_pool.owner_authority,
+        second_owner.pubkey()
+    );
+    staking_pool.validate_state(&mut banks_client).await;
+}
+
+#[tokio::test]
+async fn test_propose_owner_fail() {
+    let mut test = staking_test!();
+
+    // limit to track compute unit increase
+    test.set_compute_max_units(50_000);
+    let mut staking_pool =
+        add_staking_pool(&mut test, spl_token::native_mint::id(), 1000, 100, None, 0);
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+    let err = staking_pool
+        .propose_new_owner(&mut banks_client, Pubkey::new_unique(), &payer, false)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::InvalidSigner as u32)
+        )
+    );
+    staking_pool.validate_state(&mut banks_client).await;
+}
+
 #[tokio::test]
 async fn test_change_admin() {
     let mut test = staking_test!();
@@ -9,9 +9,14 @@ use port_finance_staking::solana_program::clock::Slot;
 use port_finance_staking::solana_program::instruction::InstructionError;
 use serde_yaml::from_str;
 use solana_program_test::*;
+use solana_sdk::account::Account;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, Signer};
-use solana_sdk::transaction::TransactionError;
+use solana_sdk::system_program;
+use solana_sdk::transaction::{Transaction, TransactionError};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account,
+};
 use spl_token::state::Account as Token;
 use spl_token::state::AccountState;
 use std::process::Command;
@@ -116,6 +121,186 @@ async fn claim_reward() {
     );
 }
 
+const ATA_AMOUNT: u64 = 10;
+const ATA_SLOT: Slot = 10;
+const ATA_ELAPSED: Slot = 100;
+const ATA_SUPPLY: u64 = 100;
+const ATA_DURATION: Slot = 1000;
+
+/// A stake account with rewards to claim and an owner holding lamports to pay for its
+/// associated token accounts
+async fn start_with_claimable_reward(
+    sub_supply: Option<u64>,
+) -> (ProgramTestContext, TestStakingPool, TestStakeAccount) {
+    let mut test = staking_test!();
+    test.set_compute_max_units(200000);
+
+    let mut staking_pool = add_staking_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        ATA_DURATION,
+        ATA_SUPPLY,
+        sub_supply,
+        0,
+    );
+    let mut stake_account: TestStakeAccount = add_stake_account(&mut test, staking_pool.pubkey);
+    test.add_account(
+        stake_account.owner.pubkey(),
+        Account::new(u32::MAX as u64, 0, &system_program::id()),
+    );
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(ATA_SLOT).unwrap();
+    let rate = staking_pool
+        .deposit(
+            &mut test_context.banks_client,
+            ATA_AMOUNT,
+            ATA_SLOT,
+            &test_context.payer,
+            None,
+            stake_account.pubkey,
+        )
+        .await
+        .unwrap();
+    stake_account.deposit(ATA_AMOUNT, rate).unwrap();
+    test_context.warp_to_slot(ATA_SLOT + ATA_ELAPSED).unwrap();
+
+    (test_context, staking_pool, stake_account)
+}
+
+#[tokio::test]
+async fn claim_reward_to_new_associated_token_account() {
+    let (mut test_context, mut staking_pool, mut stake_account) =
+        start_with_claimable_reward(Some(ATA_SUPPLY * 2)).await;
+    let ProgramTestContext {
+        ref mut banks_client,
+        ref payer,
+        ..
+    } = test_context;
+
+    let dest =
+        get_associated_token_address(&stake_account.owner.pubkey(), &spl_token::native_mint::id());
+    assert!(banks_client.get_account(dest).await.unwrap().is_none());
+
+    // reward and sub reward share a mint, so both land in the one associated token account
+    let rate = staking_pool
+        .claim_reward_to_associated_token_account(
+            banks_client,
+            ATA_SLOT + ATA_ELAPSED,
+            payer,
+            &stake_account.owner,
+            stake_account.pubkey,
+            spl_token::native_mint::id(),
+            Some(spl_token::native_mint::id()),
+        )
+        .await
+        .unwrap();
+
+    let claim_amount = stake_account.claim_reward(rate).unwrap();
+    staking_pool.validate_state(banks_client).await;
+    stake_account.validate_state(banks_client).await;
+    assert_eq!(
+        claim_amount,
+        (
+            ATA_SUPPLY * ATA_ELAPSED / ATA_DURATION,
+            Some(ATA_SUPPLY * 2 * ATA_ELAPSED / ATA_DURATION)
+        )
+    );
+    assert_eq!(
+        get_token_balance(banks_client, dest).await,
+        claim_amount.0 + claim_amount.1.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn claim_reward_to_existing_associated_token_account() {
+    let (mut test_context, mut staking_pool, mut stake_account) =
+        start_with_claimable_reward(None).await;
+    let ProgramTestContext {
+        ref mut banks_client,
+        ref payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[create_associated_token_account(
+            &payer.pubkey(),
+            &stake_account.owner.pubkey(),
+            &spl_token::native_mint::id(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let dest =
+        get_associated_token_address(&stake_account.owner.pubkey(), &spl_token::native_mint::id());
+    let dest_lamports = banks_client.get_balance(dest).await.unwrap();
+
+    let rate = staking_pool
+        .claim_reward_to_associated_token_account(
+            banks_client,
+            ATA_SLOT + ATA_ELAPSED,
+            payer,
+            &stake_account.owner,
+            stake_account.pubkey,
+            spl_token::native_mint::id(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    let claim_amount = stake_account.claim_reward(rate).unwrap();
+    stake_account.validate_state(banks_client).await;
+    assert_eq!(
+        claim_amount,
+        (ATA_SUPPLY * ATA_ELAPSED / ATA_DURATION, None)
+    );
+    assert_eq!(get_token_balance(banks_client, dest).await, claim_amount.0);
+    // the existing account is reused rather than created again
+    assert_eq!(
+        banks_client.get_balance(dest).await.unwrap(),
+        dest_lamports + claim_amount.0
+    );
+}
+
+#[tokio::test]
+async fn claim_reward_to_associated_token_account_wrong_destination() {
+    let (mut test_context, staking_pool, stake_account) = start_with_claimable_reward(None).await;
+    let ProgramTestContext {
+        ref mut banks_client,
+        ref payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let mut instruction =
+        port_finance_staking::instruction::claim_reward_to_associated_token_account(
+            port_finance_staking::id(),
+            stake_account.owner.pubkey(),
+            stake_account.pubkey,
+            staking_pool.pubkey,
+            staking_pool.staking_pool.reward_token_pool,
+            None,
+            spl_token::native_mint::id(),
+            None,
+        );
+    instruction.accounts[4].pubkey = Pubkey::new_unique();
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[payer, &stake_account.owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::InvalidAssociatedTokenAccount as u32)
+        )
+    );
+}
+
 #[tokio::test]
 async fn claim_reward_and_add_sub_reward() {
     let mut test = staking_test!();
@@ -847,3 +1032,302 @@ async fn claim_reward_random_test() {
         assert!(sub_tol < Decimal::from(1u64));
     }
 }
+
+#[tokio::test]
+async fn total_distributed_across_claims() {
+    let (mut test_context, mut staking_pool, mut stake_account) =
+        start_with_claimable_reward(Some(ATA_SUPPLY * 2)).await;
+
+    let reward_dest = create_token_account(
+        &mut test_context.banks_client,
+        spl_token::native_mint::id(),
+        &test_context.payer,
+        None,
+        None,
+    )
+    .await;
+    let sub_reward_dest = create_token_account(
+        &mut test_context.banks_client,
+        spl_token::native_mint::id(),
+        &test_context.payer,
+        None,
+        None,
+    )
+    .await;
+
+    let mut claimed = (0, 0);
+    for slot in [
+        ATA_SLOT + ATA_ELAPSED,
+        333,
+        777,
+        ATA_DURATION + 2 * ATA_SLOT,
+    ] {
+        test_context.warp_to_slot(slot).unwrap();
+        let ProgramTestContext {
+            ref mut banks_client,
+            ref payer,
+            ..
+        } = test_context;
+        let rate = staking_pool
+            .claim_reward(
+                banks_client,
+                slot,
+                payer,
+                &stake_account.owner,
+                stake_account.pubkey,
+                reward_dest,
+                Some(sub_reward_dest),
+            )
+            .await
+            .unwrap();
+        let claim_amount = stake_account.claim_reward(rate).unwrap();
+        claimed = (
+            claimed.0 + claim_amount.0,
+            claimed.1 + claim_amount.1.unwrap(),
+        );
+        staking_pool.validate_state(banks_client).await;
+        stake_account.validate_state(banks_client).await;
+
+        let state = staking_pool.get_state(banks_client).await;
+        assert_eq!(
+            (state.total_distributed, state.sub_total_distributed),
+            claimed
+        );
+        assert_eq!(
+            (
+                get_token_balance(banks_client, reward_dest).await,
+                get_token_balance(banks_client, sub_reward_dest).await
+            ),
+            claimed
+        );
+        assert!(state.total_distributed <= ATA_SUPPLY);
+        assert!(state.sub_total_distributed <= ATA_SUPPLY * 2);
+    }
+    assert_eq!(claimed, (ATA_SUPPLY, ATA_SUPPLY * 2));
+}
+
+#[tokio::test]
+async fn pending_reward_matches_claim() {
+    let (mut test_context, mut staking_pool, stake_account) =
+        start_with_claimable_reward(Some(ATA_SUPPLY * 2)).await;
+
+    let reward_dest = create_token_account(
+        &mut test_context.banks_client,
+        spl_token::native_mint::id(),
+        &test_context.payer,
+        None,
+        None,
+    )
+    .await;
+    let sub_reward_dest = create_token_account(
+        &mut test_context.banks_client,
+        spl_token::native_mint::id(),
+        &test_context.payer,
+        None,
+        None,
+    )
+    .await;
+
+    let mut claimed = (0, 0);
+    for slot in [ATA_SLOT + ATA_ELAPSED, 333, ATA_DURATION + 2 * ATA_SLOT] {
+        test_context.warp_to_slot(slot).unwrap();
+        let ProgramTestContext {
+            ref mut banks_client,
+            ref payer,
+            ..
+        } = test_context;
+        let stake_account_state = stake_account.get_state(banks_client).await;
+        let pool_rate = staking_pool
+            .get_state(banks_client)
+            .await
+            .claim_reward(slot)
+            .unwrap();
+        let (pending_reward, pending_sub_reward) =
+            stake_account_state.pending_reward(pool_rate).unwrap();
+        // querying leaves the stake account as it was
+        assert_eq!(
+            stake_account.get_state(banks_client).await,
+            stake_account_state
+        );
+
+        staking_pool
+            .claim_reward(
+                banks_client,
+                slot,
+                payer,
+                &stake_account.owner,
+                stake_account.pubkey,
+                reward_dest,
+                Some(sub_reward_dest),
+            )
+            .await
+            .unwrap();
+        claimed = (
+            claimed.0 + pending_reward,
+            claimed.1 + pending_sub_reward.unwrap(),
+        );
+        assert_eq!(
+            (
+                get_token_balance(banks_client, reward_dest).await,
+                get_token_balance(banks_client, sub_reward_dest).await
+            ),
+            claimed
+        );
+    }
+}
+
+#[tokio::test]
+async fn claim_reward_exceeding_reward_token_pool() {
+    let mut test = staking_test!();
+    test.set_compute_max_units(200000);
+
+    let mut staking_pool = add_staking_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        ATA_DURATION,
+        ATA_SUPPLY,
+        None,
+        0,
+    );
+    // the pool has been drained below what its stakers have earned
+    let (staking_program_derived, _) =
+        Pubkey::find_program_address(&[staking_pool.pubkey.as_ref()], &port_finance_staking::id());
+    test.add_packable_account(
+        staking_pool.staking_pool.reward_token_pool,
+        u32::MAX as u64,
+        &Token {
+            mint: spl_token::native_mint::id(),
+            owner: staking_program_derived,
+            amount: 1,
+            state: AccountState::Initialized,
+            ..Token::default()
+        },
+        &spl_token::id(),
+    );
+    let dest = Pubkey::new_unique();
+    test.add_packable_account(
+        dest,
+        u32::MAX as u64,
+        &Token {
+            mint: spl_token::native_mint::id(),
+            owner: Pubkey::new_unique(),
+            state: AccountState::Initialized,
+            ..Token::default()
+        },
+        &spl_token::id(),
+    );
+    let mut stake_account: TestStakeAccount = add_stake_account(&mut test, staking_pool.pubkey);
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(ATA_SLOT).unwrap();
+    let rate = staking_pool
+        .deposit(
+            &mut test_context.banks_client,
+            ATA_AMOUNT,
+            ATA_SLOT,
+            &test_context.payer,
+            None,
+            stake_account.pubkey,
+        )
+        .await
+        .unwrap();
+    stake_account.deposit(ATA_AMOUNT, rate).unwrap();
+    test_context.warp_to_slot(ATA_SLOT + ATA_ELAPSED).unwrap();
+
+    let ProgramTestContext {
+        ref mut banks_client,
+        ref payer,
+        ..
+    } = test_context;
+    assert_eq!(
+        staking_pool
+            .claim_reward(
+                banks_client,
+                ATA_SLOT + ATA_ELAPSED,
+                payer,
+                &stake_account.owner,
+                stake_account.pubkey,
+                dest,
+                None,
+            )
+            .await
+            .unwrap_err(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::RewardExceedsSupply as u32)
+        )
+    );
+    assert_eq!(
+        staking_pool.get_state(banks_client).await.total_distributed,
+        0
+    );
+}
+
+#[tokio::test]
+async fn claim_reward_to_wrong_mint() {
+    let mut test = staking_test!();
+    test.set_compute_max_units(200000);
+
+    let mut staking_pool = add_staking_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        ATA_DURATION,
+        ATA_SUPPLY,
+        None,
+        0,
+    );
+    // the reward is paid in wrapped SOL, which the token program won't move to another mint
+    let dest = Pubkey::new_unique();
+    test.add_packable_account(
+        dest,
+        u32::MAX as u64,
+        &Token {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            state: AccountState::Initialized,
+            ..Token::default()
+        },
+        &spl_token::id(),
+    );
+    let mut stake_account: TestStakeAccount = add_stake_account(&mut test, staking_pool.pubkey);
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(ATA_SLOT).unwrap();
+    let rate = staking_pool
+        .deposit(
+            &mut test_context.banks_client,
+            ATA_AMOUNT,
+            ATA_SLOT,
+            &test_context.payer,
+            None,
+            stake_account.pubkey,
+        )
+        .await
+        .unwrap();
+    stake_account.deposit(ATA_AMOUNT, rate).unwrap();
+    test_context.warp_to_slot(ATA_SLOT + ATA_ELAPSED).unwrap();
+
+    let ProgramTestContext {
+        ref mut banks_client,
+        ref payer,
+        ..
+    } = test_context;
+    assert_eq!(
+        staking_pool
+            .claim_reward(
+                banks_client,
+                ATA_SLOT + ATA_ELAPSED,
+                payer,
+                &stake_account.owner,
+                stake_account.pubkey,
+                dest,
+                None,
+            )
+            .await
+            .unwrap_err(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::TokenTransferFailed as u32)
+        )
+    );
+}
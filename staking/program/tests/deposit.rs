@@ -179,6 +179,60 @@ async fn deposit_no_authority() {
     );
 }
 
+#[tokio::test]
+async fn deposit_by_stake_account_owner() {
+    let mut test = staking_test!();
+    test.set_compute_max_units(8200);
+
+    const AMOUNT: u64 = 10;
+    const SLOT: Slot = 10;
+    const EARLIEST_CLAIM_SLOT: Slot = 0;
+    const SUPPLY: u64 = 100;
+    const DURATION: Slot = 1000;
+    let mut staking_pool = add_staking_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        DURATION,
+        SUPPLY,
+        None,
+        EARLIEST_CLAIM_SLOT,
+    );
+    let stake_account: TestStakeAccount = add_stake_account(&mut test, staking_pool.pubkey);
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(SLOT).unwrap();
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: _recent_blockhash,
+        ..
+    } = test_context;
+
+    // only the pool owner, which custodies the staked collateral, can credit a stake account
+    let stake_account_owner = Keypair::from_bytes(&stake_account.owner.to_bytes()).unwrap();
+    let err = staking_pool
+        .deposit(
+            &mut banks_client,
+            AMOUNT,
+            SLOT,
+            &payer,
+            Some(&stake_account_owner),
+            stake_account.pubkey,
+        )
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::InvalidSigner as u32)
+        )
+    );
+    stake_account.validate_state(&mut banks_client).await;
+}
+
 #[tokio::test]
 async fn deposit_does_not_match() {
     let mut test = staking_test!();
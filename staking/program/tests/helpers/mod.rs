@@ -18,6 +18,7 @@ use spl_token::state::{AccountState, Mint};
 
 use port_finance_staking::instruction::*;
 use port_finance_staking::math::TryMul;
+use port_finance_staking::slots::{SlotDelta, Slots};
 use port_finance_staking::solana_program::clock::Slot;
 use port_finance_staking::solana_program::instruction::InstructionError;
 use port_finance_staking::solana_program::program_error::ProgramError;
@@ -231,10 +232,10 @@ pub fn add_staking_pool(
             staking_pool_admin.pubkey(),
             reward_token_pool_pubkey,
             sub_reward_token_pool_pubkey,
-            duration,
+            Slots(duration),
             supply,
             sub_supply,
-            earliest_claim_time,
+            Slots(earliest_claim_time),
             bump_seed,
         )
         .unwrap();
@@ -328,8 +329,8 @@ impl TestStakingPool {
                     port_finance_staking::id(),
                     supply,
                     sub_supply,
-                    duration,
-                    earliest_reward_claim_time,
+                    Slots(duration),
+                    Slots(earliest_reward_claim_time),
                     supply_accounts_owner.pubkey(),
                     reward_supply_pubkey,
                     reward_pool_keypair.pubkey(),
@@ -372,13 +373,19 @@ impl TestStakingPool {
                 staking_pool_admin.pubkey(),
                 reward_pool_keypair.pubkey(),
                 sub_supply.map(|_| sub_reward_pool_keypair.pubkey()),
-                duration,
+                Slots(duration),
                 supply,
                 sub_supply,
-                earliest_reward_claim_time,
+                Slots(earliest_reward_claim_time),
                 bump_seed,
             )
             .unwrap();
+        staking_pool.reward_token_decimals =
+            get_mint_decimals(banks_client, reward_supply_mint_pubkey).await;
+        if let Some(sub_reward_supply_mint_pubkey) = sub_reward_supply_mint_pubkey {
+            staking_pool.sub_reward_token_decimals =
+                Some(get_mint_decimals(banks_client, sub_reward_supply_mint_pubkey).await);
+        }
 
         Ok(Self {
             name,
@@ -502,12 +509,13 @@ impl TestStakingPool {
         banks_client
             .process_transaction(transaction)
             .await
-            .map_err(|e| e.unwrap())
-            .map(|_| {
-                self.staking_pool
-                    .add_sub_reward(amount, current_slot, sub_reward_token_pool.pubkey())
-                    .unwrap()
-            })
+            .map_err(|e| e.unwrap())?;
+        self.staking_pool
+            .add_sub_reward(amount, current_slot, sub_reward_token_pool.pubkey())
+            .unwrap();
+        self.staking_pool.sub_reward_token_decimals =
+            Some(get_mint_decimals(banks_client, sub_reward_token_mint).await);
+        Ok(())
     }
 
     pub async fn claim_reward(
@@ -536,11 +544,80 @@ impl TestStakingPool {
 
         let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
         transaction.sign(&vec![payer, account_owner], recent_blockhash);
+        let balances_before = self.reward_token_pool_balances(banks_client).await;
         banks_client
             .process_transaction(transaction)
             .await
-            .map_err(|e| e.unwrap())
-            .map(|_| self.staking_pool.claim_reward(slot).unwrap())
+            .map_err(|e| e.unwrap())?;
+        self.record_claimed_reward(banks_client, balances_before)
+            .await;
+        Ok(self.staking_pool.claim_reward(slot).unwrap())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn claim_reward_to_associated_token_account(
+        &mut self,
+        banks_client: &mut BanksClient,
+        slot: Slot,
+        payer: &Keypair,
+        account_owner: &Keypair,
+        stake_account: Pubkey,
+        reward_token_mint: Pubkey,
+        sub_reward_token_mint: Option<Pubkey>,
+    ) -> Result<CumulativeRate, TransactionError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[claim_reward_to_associated_token_account(
+                port_finance_staking::id(),
+                account_owner.pubkey(),
+                stake_account,
+                self.pubkey,
+                self.staking_pool.reward_token_pool,
+                self.staking_pool.sub_reward_token_pool,
+                reward_token_mint,
+                sub_reward_token_mint,
+            )],
+            Some(&payer.pubkey()),
+        );
+
+        let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+        transaction.sign(&vec![payer, account_owner], recent_blockhash);
+        let balances_before = self.reward_token_pool_balances(banks_client).await;
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())?;
+        self.record_claimed_reward(banks_client, balances_before)
+            .await;
+        Ok(self.staking_pool.claim_reward(slot).unwrap())
+    }
+
+    async fn reward_token_pool_balances(
+        &self,
+        banks_client: &mut BanksClient,
+    ) -> (u64, Option<u64>) {
+        let balance = get_token_balance(banks_client, self.staking_pool.reward_token_pool).await;
+        let sub_balance = match self.staking_pool.sub_reward_token_pool {
+            Some(sub_reward_token_pool) => {
+                Some(get_token_balance(banks_client, sub_reward_token_pool).await)
+            }
+            None => None,
+        };
+        (balance, sub_balance)
+    }
+
+    /// Tracks a claim in the expected distributed totals by how much left the reward token pools
+    async fn record_claimed_reward(
+        &mut self,
+        banks_client: &mut BanksClient,
+        balances_before: (u64, Option<u64>),
+    ) {
+        let balances_after = self.reward_token_pool_balances(banks_client).await;
+        self.staking_pool
+            .record_distribution(
+                balances_before.0 - balances_after.0,
+                balances_before.1.zip(balances_after.1).map(|(b, a)| b - a),
+            )
+            .unwrap();
     }
 
     pub async fn change_owner(
@@ -572,7 +649,69 @@ impl TestStakingPool {
             .process_transaction(transaction)
             .await
             .map_err(|e| e.unwrap())
-            .map(|_| self.staking_pool.owner_authority = new_owner)
+            .map(|_| {
+                self.staking_pool.owner_authority = new_owner;
+                self.staking_pool.pending_owner = None;
+            })
+    }
+
+    pub async fn propose_new_owner(
+        &mut self,
+        banks_client: &mut BanksClient,
+        new_owner: Pubkey,
+        payer: &Keypair,
+        correct_owner: bool,
+    ) -> Result<(), TransactionError> {
+        let tmp_keypair = Keypair::new();
+        let current_owner = if correct_owner {
+            &self.staking_pool_owner
+        } else {
+            &tmp_keypair
+        };
+        let mut transaction = Transaction::new_with_payer(
+            &[propose_new_owner(
+                port_finance_staking::id(),
+                new_owner,
+                current_owner.pubkey(),
+                self.pubkey,
+            )],
+            Some(&payer.pubkey()),
+        );
+        let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
+        transaction.sign(&[&payer, current_owner], recent_blockhash);
+
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+            .map(|_| self.staking_pool.pending_owner = Some(new_owner))
+    }
+
+    pub async fn accept_ownership(
+        &mut self,
+        banks_client: &mut BanksClient,
+        pending_owner: &Keypair,
+        payer: &Keypair,
+    ) -> Result<(), TransactionError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[accept_ownership(
+                port_finance_staking::id(),
+                pending_owner.pubkey(),
+                self.pubkey,
+            )],
+            Some(&payer.pubkey()),
+        );
+        let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
+        transaction.sign(&[&payer, pending_owner], recent_blockhash);
+
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+            .map(|_| {
+                self.staking_pool.owner_authority = pending_owner.pubkey();
+                self.staking_pool.pending_owner = None;
+            })
     }
 
     pub async fn change_admin(
@@ -623,7 +762,7 @@ impl TestStakingPool {
         let mut transaction = Transaction::new_with_payer(
             &[change_duration(
                 port_finance_staking::id(),
-                amount,
+                SlotDelta(amount),
                 current_admin.pubkey(),
                 self.pubkey,
             )],
@@ -636,7 +775,11 @@ impl TestStakingPool {
             .process_transaction(transaction)
             .await
             .map_err(|e| e.unwrap())
-            .map(|_| self.staking_pool.extend_duration(amount, slot).unwrap())
+            .map(|_| {
+                self.staking_pool
+                    .extend_duration(SlotDelta(amount), slot)
+                    .unwrap()
+            })
     }
 
     pub async fn update_earliest_claim_time(
@@ -648,7 +791,7 @@ impl TestStakingPool {
         let mut transaction = Transaction::new_with_payer(
             &[update_earliest_reward_claim_time(
                 port_finance_staking::id(),
-                time,
+                Slots(time),
                 self.staking_pool_admin.pubkey(),
                 self.pubkey,
             )],
@@ -665,6 +808,32 @@ impl TestStakingPool {
             .map(|_| self.staking_pool.earliest_reward_claim_time = time)
     }
 
+    pub async fn advance_earliest_claim_time(
+        &mut self,
+        banks_client: &mut BanksClient,
+        time: Slot,
+        payer: &Keypair,
+    ) -> Result<(), TransactionError> {
+        let mut transaction = Transaction::new_with_payer(
+            &[advance_earliest_reward_claim_time(
+                port_finance_staking::id(),
+                Slots(time),
+                self.staking_pool_admin.pubkey(),
+                self.pubkey,
+            )],
+            Some(&payer.pubkey()),
+        );
+
+        let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+        transaction.sign(&[payer, &self.staking_pool_admin], recent_blockhash);
+
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|e| e.unwrap())
+            .map(|_| self.staking_pool.earliest_reward_claim_time = time)
+    }
+
     pub async fn change_reward_supply(
         &mut self,
         banks_client: &mut BanksClient,
@@ -899,6 +1068,12 @@ pub async fn mint_to(
     assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
 }
 
+pub async fn get_mint_decimals(banks_client: &mut BanksClient, pubkey: Pubkey) -> u8 {
+    let mint: Account = banks_client.get_account(pubkey).await.unwrap().unwrap();
+
+    Mint::unpack(&mint.data[..]).unwrap().decimals
+}
+
 pub async fn get_token_balance(banks_client: &mut BanksClient, pubkey: Pubkey) -> u64 {
     let token: Account = banks_client.get_account(pubkey).await.unwrap().unwrap();
 
@@ -6,12 +6,14 @@ use solana_program_test::tokio;
 use solana_sdk::signature::{Keypair, Signer};
 use solana_sdk::transaction::{Transaction, TransactionError};
 use spl_token::state::Account as Token;
-use spl_token::state::AccountState;
+use spl_token::state::{AccountState, Mint};
 
 use port_finance_staking::error::StakingError;
 use port_finance_staking::instruction::init_staking_pool;
-use port_finance_staking::math::TryMul;
+use port_finance_staking::math::{Decimal, TryMul};
+use port_finance_staking::slots::Slots;
 use port_finance_staking::solana_program::instruction::InstructionError;
+use port_finance_staking::state::staking_pool::StakingPool;
 
 use crate::helpers::*;
 
@@ -62,6 +64,7 @@ async fn test_success() {
     let sol_reward_supply =
         get_token_balance(&mut banks_client, staking_pool.reward_token_pool).await;
     assert_eq!(sol_reward_supply, SUPPLY);
+    assert_eq!(staking_pool.reward_token_decimals, 9);
 
     assert_eq!(
         staking_pool
@@ -118,6 +121,7 @@ async fn test_success_with_dual_reward() {
     let sol_reward_supply =
         get_token_balance(&mut banks_client, staking_pool.reward_token_pool).await;
     assert_eq!(sol_reward_supply, SUPPLY);
+    assert_eq!(staking_pool.reward_token_decimals, 9);
 
     assert_eq!(
         staking_pool
@@ -135,6 +139,7 @@ async fn test_success_with_dual_reward() {
     .await;
 
     assert_eq!(sub_sol_reward_supply, SUPPLY * 2);
+    assert_eq!(staking_pool.sub_reward_token_decimals, Some(9));
 
     assert_eq!(
         staking_pool
@@ -184,8 +189,8 @@ async fn test_already_initialized() {
             port_finance_staking::id(),
             SUPPLY,
             None,
-            1000,
-            0,
+            Slots(1000),
+            Slots(0),
             transfer_reward_token_authority.pubkey(),
             reward_token_supply_pubkey,
             reward_pool_keypair.pubkey(),
@@ -219,6 +224,96 @@ async fn test_already_initialized() {
     );
 }
 
+#[tokio::test]
+async fn test_invalid_reward_token_mint() {
+    let mut test = staking_test!();
+
+    test.set_compute_max_units(50000);
+
+    let transfer_reward_token_authority = Keypair::new();
+    const SUPPLY: u64 = 100;
+
+    // owned by the token program, but never initialized as a mint
+    let reward_token_mint_pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        reward_token_mint_pubkey,
+        u32::MAX as u64,
+        &Mint {
+            decimals: 6,
+            ..Mint::default()
+        },
+        &spl_token::id(),
+    );
+    let reward_token_supply_pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        reward_token_supply_pubkey,
+        u32::MAX as u64,
+        &Token {
+            mint: reward_token_mint_pubkey,
+            amount: SUPPLY,
+            owner: transfer_reward_token_authority.pubkey(),
+            state: AccountState::Initialized,
+            ..Token::default()
+        },
+        &spl_token::id(),
+    );
+    let reward_pool_pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        reward_pool_pubkey,
+        u32::MAX as u64,
+        &Token::default(),
+        &spl_token::id(),
+    );
+    let staking_pool_pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        staking_pool_pubkey,
+        u32::MAX as u64,
+        &StakingPool::default(),
+        &port_finance_staking::id(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[init_staking_pool(
+            port_finance_staking::id(),
+            SUPPLY,
+            None,
+            Slots(1000),
+            Slots(0),
+            transfer_reward_token_authority.pubkey(),
+            reward_token_supply_pubkey,
+            reward_pool_pubkey,
+            None,
+            None,
+            staking_pool_pubkey,
+            reward_token_mint_pubkey,
+            None,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &transfer_reward_token_authority],
+        recent_blockhash,
+    );
+
+    let err = banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap();
+
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::InvalidRewardTokenMint as u32)
+        )
+    );
+}
+
 #[tokio::test]
 async fn test_zero_supply() {
     let mut test = staking_test!();
@@ -312,3 +407,95 @@ async fn test_zero_duration() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_reward_rate_too_low() {
+    let mut test = staking_test!();
+
+    test.set_compute_max_units(8200);
+
+    let supply_accounts_owner = Keypair::new();
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+    // less than 10^-18 tokens per slot
+    const SUPPLY: u64 = 1;
+    const DURATION: Slot = 2_000_000_000_000_000_000;
+    const EARLIEST_REWARD_CLAIM_TIME: Slot = 0;
+
+    let sol_reward_supplier = create_and_mint_to_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        supply_accounts_owner.pubkey(),
+        SUPPLY,
+    )
+    .await;
+
+    let err = TestStakingPool::init(
+        "sol_staking_pool".to_owned(),
+        &mut banks_client,
+        sol_reward_supplier,
+        spl_token::native_mint::id(),
+        SUPPLY,
+        DURATION,
+        EARLIEST_REWARD_CLAIM_TIME,
+        &payer,
+        &supply_accounts_owner,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(StakingError::RewardRateTooLow as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_success_with_smallest_reward_rate() {
+    let mut test = staking_test!();
+
+    test.set_compute_max_units(38200);
+
+    let supply_accounts_owner = Keypair::new();
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+    // exactly 10^-18 tokens per slot
+    const SUPPLY: u64 = 1;
+    const DURATION: Slot = 1_000_000_000_000_000_000;
+    const EARLIEST_REWARD_CLAIM_TIME: Slot = 0;
+
+    let sol_reward_supplier = create_and_mint_to_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        supply_accounts_owner.pubkey(),
+        SUPPLY,
+    )
+    .await;
+
+    let sol_staking_pool = TestStakingPool::init(
+        "sol_staking_pool".to_owned(),
+        &mut banks_client,
+        sol_reward_supplier,
+        spl_token::native_mint::id(),
+        SUPPLY,
+        DURATION,
+        EARLIEST_REWARD_CLAIM_TIME,
+        &payer,
+        &supply_accounts_owner,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let staking_pool = sol_staking_pool.get_state(&mut banks_client).await;
+    assert!(staking_pool.rate_per_slot.reward > Decimal::zero());
+}
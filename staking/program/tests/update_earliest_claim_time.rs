@@ -0,0 +1,95 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+use helpers::*;
+use port_finance_staking::error::StakingError;
+use port_finance_staking::solana_program::instruction::InstructionError;
+use solana_program_test::*;
+use solana_sdk::transaction::TransactionError;
+
+#[tokio::test]
+async fn test_advance_earliest_claim_time() {
+    let mut test = staking_test!();
+
+    // limit to track compute unit increase
+    test.set_compute_max_units(50_000);
+    let mut staking_pool = add_staking_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        1000,
+        100,
+        None,
+        100,
+    );
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+    staking_pool
+        .advance_earliest_claim_time(&mut banks_client, 200, &payer)
+        .await
+        .unwrap();
+    assert_eq!(staking_pool.staking_pool.earliest_reward_claim_time, 200);
+    staking_pool.validate_state(&mut banks_client).await;
+
+    // setting the same time again is not a backward move
+    staking_pool
+        .advance_earliest_claim_time(&mut banks_client, 200, &payer)
+        .await
+        .unwrap();
+    staking_pool.validate_state(&mut banks_client).await;
+}
+
+#[tokio::test]
+async fn test_advance_earliest_claim_time_backward_fail() {
+    let mut test = staking_test!();
+
+    // limit to track compute unit increase
+    test.set_compute_max_units(50_000);
+    let mut staking_pool = add_staking_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        1000,
+        100,
+        None,
+        100,
+    );
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+    let err = staking_pool
+        .advance_earliest_claim_time(&mut banks_client, 99, &payer)
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::InvalidClaimTimeChange as u32)
+        )
+    );
+    assert_eq!(staking_pool.staking_pool.earliest_reward_claim_time, 100);
+    staking_pool.validate_state(&mut banks_client).await;
+}
+
+#[tokio::test]
+async fn test_update_earliest_claim_time_backward_override() {
+    let mut test = staking_test!();
+
+    // limit to track compute unit increase
+    test.set_compute_max_units(50_000);
+    let mut staking_pool = add_staking_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        1000,
+        100,
+        None,
+        100,
+    );
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+    staking_pool
+        .update_earliest_claim_time(&mut banks_client, 50, &payer)
+        .await
+        .unwrap();
+    assert_eq!(staking_pool.staking_pool.earliest_reward_claim_time, 50);
+    staking_pool.validate_state(&mut banks_client).await;
+}
@@ -202,6 +202,75 @@ async fn withdraw_no_authority() {
     );
 }
 
+#[tokio::test]
+async fn withdraw_by_stake_account_owner() {
+    let mut test = staking_test!();
+    test.set_compute_max_units(15200);
+
+    const AMOUNT: u64 = 10;
+    const SLOT: Slot = 10;
+    const EARLIEST_CLAIM_SLOT: Slot = 0;
+    const SUPPLY: u64 = 100;
+    const DURATION: Slot = 1000;
+    let mut staking_pool = add_staking_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        DURATION,
+        SUPPLY,
+        None,
+        EARLIEST_CLAIM_SLOT,
+    );
+    let mut stake_account: TestStakeAccount = add_stake_account(&mut test, staking_pool.pubkey);
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(SLOT).unwrap();
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: _recent_blockhash,
+        ..
+    } = test_context;
+
+    // the pool owner (the lending market authority when driven through CPI) deposits
+    let rate = staking_pool
+        .deposit(
+            &mut banks_client,
+            AMOUNT,
+            SLOT,
+            &payer,
+            None,
+            stake_account.pubkey,
+        )
+        .await
+        .unwrap();
+    stake_account.deposit(AMOUNT, rate).unwrap();
+
+    // the stake account owner cannot pull the stake out from under the pool owner
+    let stake_account_owner = Keypair::from_bytes(&stake_account.owner.to_bytes()).unwrap();
+    let err = staking_pool
+        .withdraw(
+            &mut banks_client,
+            AMOUNT,
+            SLOT,
+            &payer,
+            Some(&stake_account_owner),
+            stake_account.pubkey,
+        )
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakingError::InvalidSigner as u32)
+        )
+    );
+    staking_pool.validate_state(&mut banks_client).await;
+    stake_account.validate_state(&mut banks_client).await;
+}
+
 #[tokio::test]
 async fn withdraw_more_than_balance() {
     let mut test = staking_test!();
@@ -356,3 +425,83 @@ async fn withdraw_does_not_match() {
         )
     );
 }
+
+#[tokio::test]
+async fn withdraw_long_after_end_time() {
+    let mut test = staking_test!();
+    test.set_compute_max_units(15200);
+
+    const AMOUNT: u64 = 10;
+    const SLOT: Slot = 10;
+    const EARLIEST_CLAIM_SLOT: Slot = 0;
+    const SUPPLY: u64 = 100;
+    const DURATION: Slot = 1000;
+    let mut staking_pool = add_staking_pool(
+        &mut test,
+        spl_token::native_mint::id(),
+        DURATION,
+        SUPPLY,
+        Some(SUPPLY * 2),
+        EARLIEST_CLAIM_SLOT,
+    );
+    let mut stake_account: TestStakeAccount = add_stake_account(&mut test, staking_pool.pubkey);
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(SLOT).unwrap(); // clock.slot = 3
+    {
+        let ProgramTestContext {
+            ref mut banks_client,
+            ref payer,
+            ..
+        } = test_context;
+
+        let rate = staking_pool
+            .deposit(
+                banks_client,
+                AMOUNT,
+                SLOT,
+                payer,
+                None,
+                stake_account.pubkey,
+            )
+            .await
+            .unwrap();
+        stake_account.deposit(AMOUNT, rate).unwrap();
+    }
+
+    // accrual stops at end_time, however long after it the pool is next updated
+    for (slot, amount) in [(SLOT + 10 * DURATION, 4), (SLOT + 20 * DURATION, 6)] {
+        test_context.warp_to_slot(slot).unwrap();
+        let ProgramTestContext {
+            ref mut banks_client,
+            ref payer,
+            ..
+        } = test_context;
+        let rate = staking_pool
+            .withdraw(
+                banks_client,
+                amount,
+                slot,
+                payer,
+                None,
+                stake_account.pubkey,
+            )
+            .await
+            .unwrap();
+        stake_account.withdraw(amount, rate).unwrap();
+        staking_pool.validate_state(banks_client).await;
+        stake_account.validate_state(banks_client).await;
+
+        let pool = staking_pool.get_state(banks_client).await;
+        assert_eq!(pool.end_time, SLOT + DURATION);
+        assert_eq!(pool.last_update, SLOT + DURATION);
+        let unclaimed = stake_account
+            .get_state(banks_client)
+            .await
+            .unclaimed_reward_wads;
+        assert_eq!(
+            unclaimed.try_floor_u64().unwrap(),
+            (SUPPLY, Some(SUPPLY * 2))
+        );
+    }
+}
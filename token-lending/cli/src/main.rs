@@ -1,11 +1,13 @@
 use std::fmt::Display;
 
-use solana_clap_utils::input_parsers::pubkeys_of;
 use solana_client::rpc_config::RpcSendTransactionConfig;
-use solana_sdk::commitment_config::CommitmentLevel::Finalized;
 use solana_sdk::signature::read_keypair_file;
 
-use port_finance_variable_rate_lending::instruction::{refresh_obligation, update_reserve};
+use port_finance_variable_rate_lending::instruction::{
+    accept_lending_market_owner, migrate_obligation, migrate_reserve, refresh_obligation,
+    set_host_fee, set_liquidation_grace_slots, set_market_limits, set_min_liquidation_value,
+    set_reserve_wind_down, set_slots_per_year, unpause_reserve, update_reserve,
+};
 use port_finance_variable_rate_lending::instruction::{
     refresh_reserve, repay_obligation_liquidity,
 };
@@ -14,11 +16,15 @@ use {
         crate_description, crate_name, crate_version, value_t, App, AppSettings, Arg, ArgMatches,
         SubCommand,
     },
+    port_finance_staking::state::staking_pool::StakingPool,
     port_finance_variable_rate_lending::{
         self,
-        instruction::{init_lending_market, init_reserve},
-        math::{Decimal, WAD},
-        state::{LendingMarket, Reserve, ReserveConfig, ReserveFees},
+        instruction::{
+            build_init_reserve_transaction, build_local_market_transactions, init_lending_market,
+            InitReserveAccounts, LocalMarketAccounts,
+        },
+        math::{Decimal, Rate, SCALE, WAD},
+        state::{LendingMarket, Obligation, Reserve, ReserveConfig, ReserveFees},
     },
     solana_clap_utils::{
         fee_payer::fee_payer_arg,
@@ -27,15 +33,24 @@ use {
         keypair::signer_from_path,
     },
     solana_client::rpc_client::RpcClient,
-    solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey},
+    solana_program::{
+        instruction::Instruction,
+        program_option::COption,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        sysvar::{self, rent::Rent},
+    },
     solana_sdk::{
+        account::from_account,
         commitment_config::CommitmentConfig,
+        message::Message,
+        packet::PACKET_DATA_SIZE,
         signature::{Keypair, Signer},
         system_instruction,
         transaction::Transaction,
     },
     spl_token::{
-        instruction::{approve, revoke},
+        amount_to_ui_amount,
         state::{Account as Token, Mint},
         ui_amount_to_amount,
     },
@@ -49,6 +64,8 @@ struct Config {
     lending_program_id: Pubkey,
     verbose: bool,
     dry_run: bool,
+    commitment: CommitmentConfig,
+    skip_preflight: bool,
 }
 
 type Error = Box<dyn std::error::Error>;
@@ -68,36 +85,58 @@ where
     }
 }
 
-pub fn is_pubkey_or_none<T>(pubkey: T) -> Result<(), String>
+/// Parse a decimal string such as `0.0009` and scale it by WAD using integer math only, so the
+/// result is exact instead of carrying `f64` rounding error in the low bits
+pub fn parse_percent_to_wad(value: &str) -> Result<u64, String> {
+    let (integer, fraction) = match value.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (value, ""),
+    };
+    if (integer.is_empty() && fraction.is_empty())
+        || fraction.len() > SCALE
+        || !integer
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(format!("Invalid decimal: {}", value));
+    }
+
+    let integer = if integer.is_empty() {
+        0
+    } else {
+        integer
+            .parse::<u64>()
+            .map_err(|_| format!("Decimal out of range: {}", value))?
+    };
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        fraction
+            .parse::<u64>()
+            .map_err(|_| format!("Decimal out of range: {}", value))?
+            .checked_mul(10u64.pow((SCALE - fraction.len()) as u32))
+            .ok_or_else(|| format!("Decimal out of range: {}", value))?
+    };
+    integer
+        .checked_mul(WAD)
+        .and_then(|integer| integer.checked_add(fraction))
+        .ok_or_else(|| format!("Decimal out of range: {}", value))
+}
+
+pub fn is_percent_wad<T>(percent: T) -> Result<(), String>
 where
-    T: AsRef<str> + Display + Clone,
+    T: AsRef<str> + Display,
 {
-    if pubkey.as_ref() == "NONE" || is_pubkey(pubkey.clone()).is_ok() {
-        Ok(())
-    } else {
-        Err(format!(
-            "Unable to parse input amount as a pubkey or None, provided: {}",
-            pubkey
-        ))
-    }
+    parse_percent_to_wad(percent.as_ref())
+        .map(|_| ())
+        .map_err(|_| format!("Unable to parse input as a decimal, provided: {}", percent))
 }
 
-pub fn pubkeys_or_none_of(matches: &ArgMatches<'_>, name: &str) -> Option<Vec<COption<Pubkey>>> {
-    matches.values_of(name).map(|values| {
-        values
-            .map(|value| {
-                if value == "NONE" {
-                    COption::None
-                } else {
-                    COption::Some(value.parse::<Pubkey>().unwrap_or_else(|_| {
-                        read_keypair_file(value)
-                            .expect("read_keypair_file failed")
-                            .pubkey()
-                    }))
-                }
-            })
-            .collect()
-    })
+pub fn percent_wad_of(matches: &ArgMatches<'_>, name: &str) -> Option<u64> {
+    matches
+        .value_of(name)
+        .map(|value| parse_percent_to_wad(value).unwrap())
 }
 
 pub fn pubkey_or_none_of(matches: &ArgMatches<'_>, name: &str) -> Option<COption<Pubkey>> {
@@ -140,6 +179,15 @@ fn main() {
         "borrow_fee_wad",
         "flash_loan_fee_wad",
         "host_fee_percentage",
+        "max_accrual_slots",
+        "liquidation_bonus_ramp_slots",
+        "min_initial_deposit",
+        "max_price_staleness_slots",
+        "max_slot_liquidity_change_percent",
+        "max_oracle_deviation_bps",
+        "isolated_debt_ceiling",
+        "max_liquidation_bonus",
+        "min_liquidation_bonus",
     ]
     .into_iter()
     .map(build_u64_arg)
@@ -201,6 +249,7 @@ fn main() {
                 .global(true)
                 .help("Simulate transaction instead of executing"),
         )
+        .args(&send_transaction_args())
         .subcommand(
             SubCommand::with_name("update-reserve")
                 .about("Update the config of the reserve")
@@ -237,6 +286,40 @@ fn main() {
                         .takes_value(true)
                         .help("deposit staking pool")
                 )
+                .arg(
+                    Arg::with_name("borrowing_disabled")
+                        .long("borrowing_disabled")
+                        .value_name("BOOL")
+                        .possible_values(&["true", "false"])
+                        .takes_value(true)
+                        .help("Only accept deposits and collateral, disabling borrows from the reserve")
+                )
+                .arg(
+                    Arg::with_name("collateral_disabled")
+                        .long("collateral_disabled")
+                        .value_name("BOOL")
+                        .possible_values(&["true", "false"])
+                        .takes_value(true)
+                        .help("Only accept supply, giving deposits of the reserve no borrowing power")
+                )
+                .arg(
+                    Arg::with_name("borrow_fee")
+                        .long("borrow-fee")
+                        .validator(is_percent_wad)
+                        .value_name("DECIMAL_PERCENT")
+                        .takes_value(true)
+                        .conflicts_with("borrow_fee_wad")
+                        .help("Fee assessed on borrow, expressed as a percentage: [0, 1)"),
+                )
+                .arg(
+                    Arg::with_name("flash_loan_fee")
+                        .long("flash-loan-fee")
+                        .validator(is_percent_wad)
+                        .value_name("DECIMAL_PERCENT")
+                        .takes_value(true)
+                        .conflicts_with("flash_loan_fee_wad")
+                        .help("Fee assessed for flash loans, expressed as a percentage: [0, 1)"),
+                )
                 .args(&update_reserve_args)
         )
         .subcommand(
@@ -287,43 +370,102 @@ fn main() {
                         .help("Reserve to repay to")
                 )
                 .arg(
-                    Arg::with_name("all_reserves")
-                        .long("reserve")
+                    Arg::with_name("repay_obligation")
+                        .long("obligation")
                         .validator(is_pubkey)
                         .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .multiple(true)
-                        .help("All reserves to refresh should be in same order as oracles")
+                        .help("Obligation to repay")
                 )
                 .arg(
-                    Arg::with_name("all_oracles")
-                        .long("oracle")
-                        .validator(is_pubkey_or_none)
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
                         .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .multiple(true)
-                        .help("All oracle associated with reserves should be in same order as reserves")
+                        .help("Lending market repays to")
                 )
+        )
+        .subcommand(
+            SubCommand::with_name("show-reserve")
+                .about("Show the liquidity and collateral supply of a reserve")
                 .arg(
-                    Arg::with_name("repay_obligation")
-                        .long("obligation")
+                    Arg::with_name("reserve")
+                        .long("reserve")
                         .validator(is_pubkey)
                         .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .help("Obligation to repay")
+                        .help("Reserve to show"),
                 )
                 .arg(
-                    Arg::with_name("lending_market")
-                        .long("market")
+                    Arg::with_name("collateral_account")
+                        .long("collateral-account")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Collateral token account to show the redeemable amount of"),
+                )
+                .arg(
+                    Arg::with_name("borrow_amount")
+                        .long("borrow-amount")
+                        .validator(is_u64)
+                        .value_name("U64")
+                        .takes_value(true)
+                        .help("Liquidity amount to preview the utilization after borrowing"),
+                )
+                .arg(
+                    Arg::with_name("redeem_collateral")
+                        .long("redeem-collateral")
+                        .validator(is_u64)
+                        .value_name("U64")
+                        .takes_value(true)
+                        .help("Collateral amount to preview the utilization after redeeming"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show-obligation")
+                .about("Show the deposits and borrows of an obligation")
+                .arg(
+                    Arg::with_name("obligation")
+                        .long("obligation")
                         .validator(is_pubkey)
                         .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .help("Lending market repays to")
+                        .help("Obligation to show"),
+                )
+                .arg(
+                    Arg::with_name("collateral")
+                        .long("collateral")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Deposit reserve to show the liquidation price of"),
                 )
+                .arg(
+                    Arg::with_name("borrow")
+                        .long("borrow")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Borrow reserve to show the maximum borrow amount of"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("decode")
+                .about("Decode and show every field of a lending market, reserve or obligation")
+                .arg(
+                    Arg::with_name("account")
+                        .long("account")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Account to decode"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("create-market")
@@ -344,31 +486,42 @@ fn main() {
                         .takes_value(true)
                         .required(true)
                         .default_value("USD")
-                        .help("Currency market prices are quoted in"),
+                        .help("Currency market prices are quoted in, prefix a mint pubkey with `mint:`"),
+                )
+                .arg(
+                    Arg::with_name("quote_kind")
+                        .long("quote-kind")
+                        .value_name("KIND")
+                        .takes_value(true)
+                        .possible_values(&["symbol", "mint"])
+                        .default_value("symbol")
+                        .help("Whether the quote currency is a symbol or a mint pubkey"),
                 ),
         )
         .subcommand(
-            SubCommand::with_name("add-reserve")
-                .about("Add a reserve to a lending market")
-                // @TODO: use is_valid_signer
+            SubCommand::with_name("accept-market-owner")
+                .about("Accept ownership of a lending market proposed by its current owner")
                 .arg(
-                    Arg::with_name("lending_market_owner")
-                        .long("market-owner")
-                        .value_name("KEYPAIR")
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .help("Owner of the lending market"),
+                        .help("Lending market to take ownership of"),
                 )
-                // @TODO: use is_valid_signer
                 .arg(
-                    Arg::with_name("source_liquidity_owner")
-                        .long("source-owner")
-                        .validator(is_keypair)
+                    Arg::with_name("pending_owner")
+                        .long("pending-owner")
                         .value_name("KEYPAIR")
                         .takes_value(true)
                         .required(true)
-                        .help("Owner of the SPL Token account to deposit initial liquidity from"),
-                )
+                        .help("Pending owner of the lending market"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-market-limits")
+                .about("Set the policy limits of a lending market")
                 .arg(
                     Arg::with_name("lending_market")
                         .long("market")
@@ -376,133 +529,488 @@ fn main() {
                         .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .help("Lending market address"),
+                        .help("Lending market to update"),
                 )
                 .arg(
-                    Arg::with_name("source_liquidity")
-                        .long("source")
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("max_obligation_reserves")
+                        .long("max-obligation-reserves")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Maximum number of distinct deposit and borrow reserves per obligation, 0 for the full capacity"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-liquidation-grace")
+                .about("Set how long an obligation must be unhealthy before it can be liquidated")
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
                         .validator(is_pubkey)
                         .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .help("SPL Token account to deposit initial liquidity from"),
+                        .help("Lending market to update"),
                 )
-                // @TODO: use is_amount_or_all
                 .arg(
-                    Arg::with_name("liquidity_amount")
-                        .long("amount")
-                        .validator(is_amount)
-                        .value_name("DECIMAL_AMOUNT")
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .value_name("KEYPAIR")
                         .takes_value(true)
                         .required(true)
-                        .help("Initial amount of liquidity to deposit into the new reserve"),
+                        .help("Owner of the lending market"),
                 )
                 .arg(
-                    Arg::with_name("fixed_price")
-                        .long("fixed-price")
-                        .validator(is_amount)
-                        .value_name("DECIMAL_AMOUNT")
+                    Arg::with_name("liquidation_grace_slots")
+                        .long("slots")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
                         .takes_value(true)
-                        .help("Initial price for the given asset"),
-                )
+                        .required(true)
+                        .help("Slots an obligation must have been unhealthy for, 0 to allow immediate liquidation"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-slots-per-year")
+                .about("Set the slots per year the market's supply and borrow rates are annualized with")
                 .arg(
-                    Arg::with_name("pyth_price")
-                        .long("pyth-price")
+                    Arg::with_name("lending_market")
+                        .long("market")
                         .validator(is_pubkey)
                         .value_name("PUBKEY")
                         .takes_value(true)
-                        .required(false)
-                        .help("Pyth price account: https://pyth.network/developers/consumers/accounts"),
+                        .required(true)
+                        .help("Lending market to update"),
                 )
                 .arg(
-                    Arg::with_name("optimal_utilization_rate")
-                        .long("optimal-utilization-rate")
-                        .validator(is_parsable::<u8>)
-                        .value_name("INTEGER_PERCENT")
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .value_name("KEYPAIR")
                         .takes_value(true)
                         .required(true)
-                        .default_value("80")
-                        .help("Optimal utilization rate: [0, 100]"),
+                        .help("Owner of the lending market"),
                 )
                 .arg(
-                    Arg::with_name("loan_to_value_ratio")
-                        .long("loan-to-value-ratio")
-                        .validator(is_parsable::<u8>)
-                        .value_name("INTEGER_PERCENT")
+                    Arg::with_name("slots_per_year")
+                        .long("slots")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
                         .takes_value(true)
                         .required(true)
-                        .default_value("50")
-                        .help("Target ratio of the value of borrows to deposits: [0, 100)"),
-                )
+                        .help("Estimated slots per year, 0 for the program's default estimate"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate-obligation")
+                .about("Realloc an obligation created with the legacy layout to the current one")
                 .arg(
-                    Arg::with_name("liquidation_bonus")
-                        .long("liquidation-bonus")
-                        .validator(is_parsable::<u8>)
-                        .value_name("INTEGER_PERCENT")
+                    Arg::with_name("obligation")
+                        .long("obligation")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .default_value("5")
-                        .help("Bonus a liquidator gets when repaying part of an unhealthy obligation: [0, 100]"),
-                )
+                        .help("Obligation to migrate, the fee payer tops up its rent"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate-reserve")
+                .about("Realloc a reserve created with the legacy layout to the current one")
                 .arg(
-                    Arg::with_name("liquidation_threshold")
-                        .long("liquidation-threshold")
-                        .validator(is_parsable::<u8>)
-                        .value_name("INTEGER_PERCENT")
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .default_value("55")
-                        .help("Loan to value ratio at which an obligation can be liquidated: (LTV, 100]"),
-                )
+                        .help("Reserve to migrate, the fee payer tops up its rent"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bootstrap-local")
+                .about("Development only: against a local validator, create a liquidity mint, mint liquidity to the fee payer and create a lending market with a fixed price reserve of it")
                 .arg(
-                    Arg::with_name("min_borrow_rate")
-                        .long("min-borrow-rate")
+                    Arg::with_name("decimals")
+                        .long("decimals")
                         .validator(is_parsable::<u8>)
-                        .value_name("INTEGER_PERCENT")
+                        .value_name("INTEGER")
                         .takes_value(true)
                         .required(true)
-                        .default_value("0")
-                        .help("Min borrow APY: min <= optimal <= max"),
+                        .default_value("6")
+                        .help("Decimals of the liquidity mint"),
                 )
                 .arg(
-                    Arg::with_name("optimal_borrow_rate")
-                        .long("optimal-borrow-rate")
-                        .validator(is_parsable::<u8>)
-                        .value_name("INTEGER_PERCENT")
+                    Arg::with_name("mint_amount")
+                        .long("mint-amount")
+                        .validator(is_amount)
+                        .value_name("DECIMAL_AMOUNT")
                         .takes_value(true)
                         .required(true)
-                        .default_value("4")
-                        .help("Optimal (utilization) borrow APY: min <= optimal <= max"),
+                        .default_value("1000000")
+                        .help("Amount of liquidity minted to the fee payer"),
                 )
                 .arg(
-                    Arg::with_name("max_borrow_rate")
-                        .long("max-borrow-rate")
-                        .validator(is_parsable::<u8>)
-                        .value_name("INTEGER_PERCENT")
+                    Arg::with_name("liquidity_amount")
+                        .long("amount")
+                        .validator(is_amount)
+                        .value_name("DECIMAL_AMOUNT")
                         .takes_value(true)
                         .required(true)
-                        .default_value("30")
-                        .help("Max borrow APY: min <= optimal <= max"),
+                        .default_value("1000")
+                        .help("Initial amount of the minted liquidity to deposit into the reserve"),
                 )
                 .arg(
-                    Arg::with_name("borrow_fee")
-                        .long("borrow-fee")
-                        .validator(is_parsable::<f64>)
-                        .value_name("DECIMAL_PERCENT")
+                    Arg::with_name("fixed_price")
+                        .long("fixed-price")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
                         .takes_value(true)
                         .required(true)
-                        .default_value("0.0001")
-                        .help("Fee assessed on borrow, expressed as a percentage: [0, 1)"),
-                )
+                        .default_value("1")
+                        .help("Price of the liquidity in the lending market's quote currency, USD"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-min-liquidation-value")
+                .about("Set the smallest value a partial liquidation must repay")
                 .arg(
-                    Arg::with_name("flash_loan_fee")
-                        .long("flash-loan-fee")
-                        .validator(is_parsable::<f64>)
-                        .value_name("DECIMAL_PERCENT")
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .default_value("0.0009")
-                        .help("Fee assessed for flash loans, expressed as a percentage: [0, 1)"),
+                        .help("Lending market to update"),
+                )
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("min_liquidation_value")
+                        .long("value")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Minimum repaid value in the market's quote currency, 0 for no minimum"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unpause-reserve")
+                .about("Unpause a reserve paused because its oracle price was stale")
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve to unpause"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market of the reserve"),
+                )
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-reserve-wind-down")
+                .about("Wind down a reserve, blocking deposits and borrows while it is drained")
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve to wind down"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market of the reserve"),
+                )
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("wind_down")
+                        .long("wind-down")
+                        .value_name("BOOL")
+                        .possible_values(&["true", "false"])
+                        .takes_value(true)
+                        .default_value("true")
+                        .help("Whether the reserve is winding down, false to reopen it"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-host-fee")
+                .about("Set the percentage of a reserve's fees paid to hosts")
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve to update"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market of the reserve"),
+                )
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("host_fee_percentage")
+                        .long("percentage")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Host fee percentage, from 0 to 100"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-staking-pool")
+                .about("Point a reserve's collateral at another staking pool, or at none")
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve to update"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market of the reserve"),
+                )
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("staking_pool")
+                        .long("pool")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Staking pool for the reserve's collateral, or NONE to remove it"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("add-reserve")
+                .about("Add a reserve to a lending market")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("source_liquidity_owner")
+                        .long("source-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the SPL Token account to deposit initial liquidity from"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+                .arg(
+                    Arg::with_name("source_liquidity")
+                        .long("source")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to deposit initial liquidity from"),
+                )
+                // @TODO: use is_amount_or_all
+                .arg(
+                    Arg::with_name("liquidity_amount")
+                        .long("amount")
+                        .validator(is_amount)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Initial amount of liquidity to deposit into the new reserve"),
+                )
+                .arg(
+                    Arg::with_name("fixed_price")
+                        .long("fixed-price")
+                        .validator(is_amount)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .help("Initial price for the given asset"),
+                )
+                .arg(
+                    Arg::with_name("pyth_price")
+                        .long("pyth-price")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Pyth price account: https://pyth.network/developers/consumers/accounts"),
+                )
+                .arg(
+                    Arg::with_name("optimal_utilization_rate")
+                        .long("optimal-utilization-rate")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("80")
+                        .help("Optimal utilization rate: [0, 100]"),
+                )
+                .arg(
+                    Arg::with_name("loan_to_value_ratio")
+                        .long("loan-to-value-ratio")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("50")
+                        .help("Target ratio of the value of borrows to deposits: [0, 100)"),
+                )
+                .arg(
+                    Arg::with_name("liquidation_bonus")
+                        .long("liquidation-bonus")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("5")
+                        .help("Bonus a liquidator gets when repaying part of an unhealthy obligation: [0, 100]"),
+                )
+                .arg(
+                    Arg::with_name("liquidation_threshold")
+                        .long("liquidation-threshold")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("55")
+                        .help("Loan to value ratio at which an obligation can be liquidated: (LTV, 100]"),
+                )
+                .arg(
+                    Arg::with_name("min_borrow_rate")
+                        .long("min-borrow-rate")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help("Min borrow APY: min <= optimal <= max"),
+                )
+                .arg(
+                    Arg::with_name("optimal_borrow_rate")
+                        .long("optimal-borrow-rate")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("4")
+                        .help("Optimal (utilization) borrow APY: min <= optimal <= max"),
+                )
+                .arg(
+                    Arg::with_name("max_borrow_rate")
+                        .long("max-borrow-rate")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("30")
+                        .help("Max borrow APY: min <= optimal <= max"),
+                )
+                .arg(
+                    Arg::with_name("borrow_fee")
+                        .long("borrow-fee")
+                        .validator(is_percent_wad)
+                        .value_name("DECIMAL_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0.0001")
+                        .help("Fee assessed on borrow, expressed as a percentage: [0, 1)"),
+                )
+                .arg(
+                    Arg::with_name("flash_loan_fee")
+                        .long("flash-loan-fee")
+                        .validator(is_percent_wad)
+                        .value_name("DECIMAL_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0.0009")
+                        .help("Fee assessed for flash loans, expressed as a percentage: [0, 1)"),
                 )
                 .arg(
                     Arg::with_name("host_fee_percentage")
@@ -543,6 +1051,8 @@ fn main() {
         let lending_program_id = pubkey_of(&matches, "lending_program_id").unwrap();
         let verbose = matches.is_present("verbose");
         let dry_run = matches.is_present("dry_run");
+        let commitment = commitment_of(&matches);
+        let skip_preflight = skip_preflight_of(&matches);
 
         Config {
             rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
@@ -550,14 +1060,199 @@ fn main() {
             lending_program_id,
             verbose,
             dry_run,
+            commitment,
+            skip_preflight,
+        }
+    };
+
+    let _ = match matches.subcommand() {
+        ("create-market", Some(arg_matches)) => {
+            let lending_market_owner = pubkey_of(arg_matches, "lending_market_owner").unwrap();
+            match quote_currency_of(arg_matches, "quote_currency", "quote_kind") {
+                Ok(quote_currency) => {
+                    command_create_lending_market(&config, lending_market_owner, quote_currency)
+                }
+                Err(err) => Err(err.into()),
+            }
+        }
+        ("accept-market-owner", Some(arg_matches)) => {
+            let lending_market = pubkey_of(arg_matches, "lending_market").unwrap();
+            let mut wallet_manager = None;
+            let pending_owner = signer_from_path(
+                arg_matches,
+                arg_matches.value_of("pending_owner").unwrap(),
+                "pending_owner",
+                &mut wallet_manager,
+            )
+            .unwrap();
+            command_accept_market_owner(&config, lending_market, pending_owner)
+        }
+        ("set-market-limits", Some(arg_matches)) => {
+            let lending_market = pubkey_of(arg_matches, "lending_market").unwrap();
+            let max_obligation_reserves = value_of(arg_matches, "max_obligation_reserves").unwrap();
+            let mut wallet_manager = None;
+            let lending_market_owner = signer_from_path(
+                arg_matches,
+                arg_matches.value_of("lending_market_owner").unwrap(),
+                "lending_market_owner",
+                &mut wallet_manager,
+            )
+            .unwrap();
+            command_set_market_limits(
+                &config,
+                lending_market,
+                lending_market_owner,
+                max_obligation_reserves,
+            )
+        }
+        ("set-liquidation-grace", Some(arg_matches)) => {
+            let lending_market = pubkey_of(arg_matches, "lending_market").unwrap();
+            let liquidation_grace_slots = value_of(arg_matches, "liquidation_grace_slots").unwrap();
+            let mut wallet_manager = None;
+            let lending_market_owner = signer_from_path(
+                arg_matches,
+                arg_matches.value_of("lending_market_owner").unwrap(),
+                "lending_market_owner",
+                &mut wallet_manager,
+            )
+            .unwrap();
+            command_set_liquidation_grace_slots(
+                &config,
+                lending_market,
+                lending_market_owner,
+                liquidation_grace_slots,
+            )
+        }
+        ("set-slots-per-year", Some(arg_matches)) => {
+            let lending_market = pubkey_of(arg_matches, "lending_market").unwrap();
+            let slots_per_year = value_of(arg_matches, "slots_per_year").unwrap();
+            let mut wallet_manager = None;
+            let lending_market_owner = signer_from_path(
+                arg_matches,
+                arg_matches.value_of("lending_market_owner").unwrap(),
+                "lending_market_owner",
+                &mut wallet_manager,
+            )
+            .unwrap();
+            command_set_slots_per_year(
+                &config,
+                lending_market,
+                lending_market_owner,
+                slots_per_year,
+            )
+        }
+        ("migrate-obligation", Some(arg_matches)) => {
+            let obligation = pubkey_of(arg_matches, "obligation").unwrap();
+            command_migrate_obligation(&config, obligation)
+        }
+        ("migrate-reserve", Some(arg_matches)) => {
+            let reserve = pubkey_of(arg_matches, "reserve").unwrap();
+            command_migrate_reserve(&config, reserve)
+        }
+        ("bootstrap-local", Some(arg_matches)) => {
+            let decimals = value_of(arg_matches, "decimals").unwrap();
+            let mint_ui_amount = value_of(arg_matches, "mint_amount").unwrap();
+            let ui_amount = value_of(arg_matches, "liquidity_amount").unwrap();
+            let fixed_price: u64 = value_of(arg_matches, "fixed_price").unwrap();
+            command_bootstrap_local(
+                &config,
+                decimals,
+                mint_ui_amount,
+                ui_amount,
+                Decimal::from(fixed_price),
+            )
+        }
+        ("set-min-liquidation-value", Some(arg_matches)) => {
+            let lending_market = pubkey_of(arg_matches, "lending_market").unwrap();
+            let min_liquidation_value: u64 =
+                value_of(arg_matches, "min_liquidation_value").unwrap();
+            let mut wallet_manager = None;
+            let lending_market_owner = signer_from_path(
+                arg_matches,
+                arg_matches.value_of("lending_market_owner").unwrap(),
+                "lending_market_owner",
+                &mut wallet_manager,
+            )
+            .unwrap();
+            command_set_min_liquidation_value(
+                &config,
+                lending_market,
+                lending_market_owner,
+                Decimal::from(min_liquidation_value),
+            )
+        }
+        ("unpause-reserve", Some(arg_matches)) => {
+            let reserve = pubkey_of(arg_matches, "reserve").unwrap();
+            let lending_market = pubkey_of(arg_matches, "lending_market").unwrap();
+            let mut wallet_manager = None;
+            let lending_market_owner = signer_from_path(
+                arg_matches,
+                arg_matches.value_of("lending_market_owner").unwrap(),
+                "lending_market_owner",
+                &mut wallet_manager,
+            )
+            .unwrap();
+            command_unpause_reserve(&config, reserve, lending_market, lending_market_owner)
+        }
+        ("set-reserve-wind-down", Some(arg_matches)) => {
+            let reserve = pubkey_of(arg_matches, "reserve").unwrap();
+            let lending_market = pubkey_of(arg_matches, "lending_market").unwrap();
+            let wind_down = value_of(arg_matches, "wind_down").unwrap();
+            let mut wallet_manager = None;
+            let lending_market_owner = signer_from_path(
+                arg_matches,
+                arg_matches.value_of("lending_market_owner").unwrap(),
+                "lending_market_owner",
+                &mut wallet_manager,
+            )
+            .unwrap();
+            command_set_reserve_wind_down(
+                &config,
+                reserve,
+                lending_market,
+                lending_market_owner,
+                wind_down,
+            )
+        }
+        ("set-host-fee", Some(arg_matches)) => {
+            let reserve = pubkey_of(arg_matches, "reserve").unwrap();
+            let lending_market = pubkey_of(arg_matches, "lending_market").unwrap();
+            let host_fee_percentage = value_of(arg_matches, "host_fee_percentage").unwrap();
+            let mut wallet_manager = None;
+            let lending_market_owner = signer_from_path(
+                arg_matches,
+                arg_matches.value_of("lending_market_owner").unwrap(),
+                "lending_market_owner",
+                &mut wallet_manager,
+            )
+            .unwrap();
+            command_set_host_fee(
+                &config,
+                reserve,
+                lending_market,
+                lending_market_owner,
+                host_fee_percentage,
+            )
         }
-    };
-
-    let _ = match matches.subcommand() {
-        ("create-market", Some(arg_matches)) => {
-            let lending_market_owner = pubkey_of(arg_matches, "lending_market_owner").unwrap();
-            let quote_currency = quote_currency_of(arg_matches, "quote_currency").unwrap();
-            command_create_lending_market(&config, lending_market_owner, quote_currency)
+        ("set-staking-pool", Some(arg_matches)) => {
+            let reserve = pubkey_of(arg_matches, "reserve").unwrap();
+            let lending_market = pubkey_of(arg_matches, "lending_market").unwrap();
+            let staking_pool = pubkey_or_none_of(arg_matches, "staking_pool").unwrap();
+            let mut wallet_manager = None;
+            let lending_market_owner = signer_from_path(
+                arg_matches,
+                arg_matches.value_of("lending_market_owner").unwrap(),
+                "lending_market_owner",
+                &mut wallet_manager,
+            )
+            .unwrap();
+            command_set_staking_pool(
+                &config,
+                reserve,
+                lending_market,
+                lending_market_owner,
+                staking_pool,
+            )
         }
         ("update-reserve", Some(arg_matches)) => {
             let reserve = pubkey_of(arg_matches, "reserve").unwrap();
@@ -577,14 +1272,30 @@ fn main() {
             let min_borrow_rate = value_of(arg_matches, "min_borrow_rate");
             let optimal_borrow_rate = value_of(arg_matches, "optimal_borrow_rate");
             let max_borrow_rate = value_of(arg_matches, "max_borrow_rate");
-            let borrow_fee_wad = value_of(arg_matches, "borrow_fee_wad");
-            let flash_loan_fee_wad = value_of(arg_matches, "flash_loan_fee_wad");
+            let borrow_fee_wad = value_of(arg_matches, "borrow_fee_wad")
+                .or_else(|| percent_wad_of(arg_matches, "borrow_fee"));
+            let flash_loan_fee_wad = value_of(arg_matches, "flash_loan_fee_wad")
+                .or_else(|| percent_wad_of(arg_matches, "flash_loan_fee"));
             let host_fee_percentage = value_of(arg_matches, "host_fee_percentage");
             let deposit_staking_pool = pubkey_or_none_of(arg_matches, "deposit_staking_pool");
+            let max_accrual_slots = value_of(arg_matches, "max_accrual_slots");
+            let borrowing_disabled = value_of(arg_matches, "borrowing_disabled");
+            let collateral_disabled = value_of(arg_matches, "collateral_disabled");
+            let liquidation_bonus_ramp_slots =
+                value_of(arg_matches, "liquidation_bonus_ramp_slots");
+            let min_initial_deposit = value_of(arg_matches, "min_initial_deposit");
+            let max_price_staleness_slots = value_of(arg_matches, "max_price_staleness_slots");
+            let max_slot_liquidity_change_percent =
+                value_of(arg_matches, "max_slot_liquidity_change_percent");
+            let max_oracle_deviation_bps = value_of(arg_matches, "max_oracle_deviation_bps");
+            let isolated_debt_ceiling = value_of(arg_matches, "isolated_debt_ceiling");
+            let max_liquidation_bonus = value_of(arg_matches, "max_liquidation_bonus");
+            let min_liquidation_bonus = value_of(arg_matches, "min_liquidation_bonus");
             let mut old_config =
                 Reserve::unpack(&config.rpc_client.get_account(&reserve).unwrap().data)
                     .unwrap()
                     .config;
+            let current_config = old_config;
 
             old_config.optimal_utilization_rate =
                 optimal_utilization_rate.unwrap_or(old_config.optimal_utilization_rate);
@@ -606,11 +1317,34 @@ fn main() {
                 flash_loan_fee_wad.unwrap_or(old_config.fees.flash_loan_fee_wad);
             old_config.deposit_staking_pool =
                 deposit_staking_pool.unwrap_or(old_config.deposit_staking_pool);
+            old_config.max_accrual_slots =
+                max_accrual_slots.unwrap_or(old_config.max_accrual_slots);
+            old_config.borrowing_disabled =
+                borrowing_disabled.unwrap_or(old_config.borrowing_disabled);
+            old_config.collateral_disabled =
+                collateral_disabled.unwrap_or(old_config.collateral_disabled);
+            old_config.liquidation_bonus_ramp_slots =
+                liquidation_bonus_ramp_slots.unwrap_or(old_config.liquidation_bonus_ramp_slots);
+            old_config.min_initial_deposit =
+                min_initial_deposit.unwrap_or(old_config.min_initial_deposit);
+            old_config.max_price_staleness_slots =
+                max_price_staleness_slots.unwrap_or(old_config.max_price_staleness_slots);
+            old_config.max_slot_liquidity_change_percent = max_slot_liquidity_change_percent
+                .unwrap_or(old_config.max_slot_liquidity_change_percent);
+            old_config.max_oracle_deviation_bps =
+                max_oracle_deviation_bps.unwrap_or(old_config.max_oracle_deviation_bps);
+            old_config.isolated_debt_ceiling =
+                isolated_debt_ceiling.unwrap_or(old_config.isolated_debt_ceiling);
+            old_config.max_liquidation_bonus =
+                max_liquidation_bonus.unwrap_or(old_config.max_liquidation_bonus);
+            old_config.min_liquidation_bonus =
+                min_liquidation_bonus.unwrap_or(old_config.min_liquidation_bonus);
             command_update_reserve(
                 &config,
                 reserve,
                 lending_market,
                 lending_market_owner,
+                &current_config,
                 old_config,
             )
         }
@@ -647,13 +1381,10 @@ fn main() {
             let min_borrow_rate = value_of(arg_matches, "min_borrow_rate").unwrap();
             let optimal_borrow_rate = value_of(arg_matches, "optimal_borrow_rate").unwrap();
             let max_borrow_rate = value_of(arg_matches, "max_borrow_rate").unwrap();
-            let borrow_fee = value_of::<f64>(arg_matches, "borrow_fee").unwrap();
-            let flash_loan_fee = value_of::<f64>(arg_matches, "flash_loan_fee").unwrap();
+            let borrow_fee_wad = percent_wad_of(arg_matches, "borrow_fee").unwrap();
+            let flash_loan_fee_wad = percent_wad_of(arg_matches, "flash_loan_fee").unwrap();
             let host_fee_percentage = value_of(arg_matches, "host_fee_percentage").unwrap();
 
-            let borrow_fee_wad = (borrow_fee * WAD as f64) as u64;
-            let flash_loan_fee_wad = (flash_loan_fee * WAD as f64) as u64;
-
             if fixed_price.is_none() && pyth_price_pubkey.is_none() {
                 eprintln!("Supply at least one of `fixed_price` or `pyth_price_pubkey`");
                 exit(1);
@@ -681,6 +1412,22 @@ fn main() {
                         host_fee_percentage,
                     },
                     deposit_staking_pool: COption::None,
+                    use_twap: false,
+                    twap_window_slots: 0,
+                    borrow_fee_discount_staking_pool: COption::None,
+                    borrow_fee_discount_percentage: 0,
+                    max_accrual_slots: 0,
+                    borrowing_disabled: false,
+                    liquidation_bonus_ramp_slots: 0,
+                    min_initial_deposit: 0,
+                    max_price_staleness_slots: 0,
+                    max_slot_liquidity_change_percent: 0,
+                    max_oracle_deviation_bps: 0,
+                    isolated_debt_ceiling: 0,
+                    collateral_disabled: false,
+                    max_liquidation_bonus: 0,
+                    borrow_fee_discount_min_stake: 0,
+                    min_liquidation_bonus: 0,
                 },
                 source_liquidity_pubkey,
                 source_liquidity_owner_keypair,
@@ -697,16 +1444,6 @@ fn main() {
             let repay_reserve = pubkey_of(arg_matches, "repay_reserve").unwrap();
             let repay_obligation = pubkey_of(arg_matches, "repay_obligation").unwrap();
             let lending_market = pubkey_of(arg_matches, "lending_market").unwrap();
-            let reserves = pubkeys_of(arg_matches, "all_reserves").unwrap();
-            let oracles = pubkeys_or_none_of(arg_matches, "all_oracles").unwrap();
-            if reserves.len() != oracles.len() {
-                eprintln!(
-                    "Number of reserves should equal with the number of oracles, {} != {}",
-                    reserves.len(),
-                    oracles.len()
-                );
-                exit(1);
-            }
             command_repay_loan(
                 &config,
                 amount,
@@ -715,10 +1452,32 @@ fn main() {
                 dest_token,
                 repay_reserve,
                 repay_obligation,
-                reserves.into_iter().zip(oracles).collect(),
                 lending_market,
             )
         }
+        ("show-reserve", Some(arg_matches)) => {
+            let reserve = pubkey_of(arg_matches, "reserve").unwrap();
+            let collateral_account = pubkey_of(arg_matches, "collateral_account");
+            let borrow_amount = value_of(arg_matches, "borrow_amount");
+            let redeem_collateral = value_of(arg_matches, "redeem_collateral");
+            command_show_reserve(
+                &config,
+                reserve,
+                collateral_account,
+                borrow_amount,
+                redeem_collateral,
+            )
+        }
+        ("show-obligation", Some(arg_matches)) => {
+            let obligation = pubkey_of(arg_matches, "obligation").unwrap();
+            let collateral_reserve = pubkey_of(arg_matches, "collateral");
+            let borrow_reserve = pubkey_of(arg_matches, "borrow");
+            command_show_obligation(&config, obligation, collateral_reserve, borrow_reserve)
+        }
+        ("decode", Some(arg_matches)) => {
+            let account = pubkey_of(arg_matches, "account").unwrap();
+            command_decode(&config, account)
+        }
         _ => unreachable!(),
     }
     .map_err(|err| {
@@ -733,12 +1492,18 @@ fn command_update_reserve(
     reserve: Pubkey,
     lending_market: Pubkey,
     lending_market_owner: Box<dyn Signer>,
+    current_config: &ReserveConfig,
     reserve_config: ReserveConfig,
 ) -> CommandResult {
-    println!(
-        "update reserve {} with the config {:?}",
-        reserve, reserve_config
-    );
+    let changes = current_config.diff(&reserve_config);
+    if changes.is_empty() {
+        println!("reserve {} config is unchanged", reserve);
+        return Ok(());
+    }
+    println!("update reserve {} with the changes:", reserve);
+    for (field, old, new) in changes {
+        println!("  {}: {} -> {}", field, old, new);
+    }
     let mut transaction = Transaction::new_with_payer(
         &[update_reserve(
             config.lending_program_id,
@@ -796,13 +1561,445 @@ fn command_create_lending_market(
 
     let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
     transaction.sign(
-        &vec![config.fee_payer.as_ref(), &lending_market_keypair],
+        &vec![config.fee_payer.as_ref(), &lending_market_keypair],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_accept_market_owner(
+    config: &Config,
+    lending_market: Pubkey,
+    pending_owner: Box<dyn Signer>,
+) -> CommandResult {
+    println!(
+        "Accepting ownership of lending market {} as {}",
+        lending_market,
+        pending_owner.pubkey()
+    );
+    let mut transaction = Transaction::new_with_payer(
+        &[accept_lending_market_owner(
+            config.lending_program_id,
+            lending_market,
+            pending_owner.pubkey(),
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), pending_owner.as_ref()],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_set_market_limits(
+    config: &Config,
+    lending_market: Pubkey,
+    lending_market_owner: Box<dyn Signer>,
+    max_obligation_reserves: u8,
+) -> CommandResult {
+    println!(
+        "Limiting obligations in lending market {} to {} reserves",
+        lending_market, max_obligation_reserves
+    );
+    let mut transaction = Transaction::new_with_payer(
+        &[set_market_limits(
+            config.lending_program_id,
+            lending_market,
+            lending_market_owner.pubkey(),
+            max_obligation_reserves,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), lending_market_owner.as_ref()],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_set_liquidation_grace_slots(
+    config: &Config,
+    lending_market: Pubkey,
+    lending_market_owner: Box<dyn Signer>,
+    liquidation_grace_slots: u64,
+) -> CommandResult {
+    println!(
+        "Allowing liquidation in lending market {} after {} unhealthy slots",
+        lending_market, liquidation_grace_slots
+    );
+    let mut transaction = Transaction::new_with_payer(
+        &[set_liquidation_grace_slots(
+            config.lending_program_id,
+            lending_market,
+            lending_market_owner.pubkey(),
+            liquidation_grace_slots,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), lending_market_owner.as_ref()],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_set_slots_per_year(
+    config: &Config,
+    lending_market: Pubkey,
+    lending_market_owner: Box<dyn Signer>,
+    slots_per_year: u64,
+) -> CommandResult {
+    println!(
+        "Annualizing the rates of lending market {} over {} slots per year",
+        lending_market, slots_per_year
+    );
+    let mut transaction = Transaction::new_with_payer(
+        &[set_slots_per_year(
+            config.lending_program_id,
+            lending_market,
+            lending_market_owner.pubkey(),
+            slots_per_year,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), lending_market_owner.as_ref()],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_migrate_obligation(config: &Config, obligation: Pubkey) -> CommandResult {
+    println!("Migrating obligation {}", obligation);
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate_obligation(
+            config.lending_program_id,
+            obligation,
+            config.fee_payer.pubkey(),
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    transaction.sign(&vec![config.fee_payer.as_ref()], recent_blockhash);
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_migrate_reserve(config: &Config, reserve: Pubkey) -> CommandResult {
+    println!("Migrating reserve {}", reserve);
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate_reserve(
+            config.lending_program_id,
+            reserve,
+            config.fee_payer.pubkey(),
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    transaction.sign(&vec![config.fee_payer.as_ref()], recent_blockhash);
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_bootstrap_local(
+    config: &Config,
+    decimals: u8,
+    mint_ui_amount: f64,
+    ui_amount: f64,
+    fixed_price: Decimal,
+) -> CommandResult {
+    let url = config.rpc_client.url();
+    if !["localhost", "127.0.0.1", "0.0.0.0"]
+        .iter()
+        .any(|host| url.contains(host))
+    {
+        return Err(format!(
+            "bootstrap-local only runs against a local validator, not {}",
+            url
+        )
+        .into());
+    }
+
+    let liquidity_mint_keypair = Keypair::new();
+    let user_liquidity_keypair = Keypair::new();
+    let lending_market_keypair = Keypair::new();
+    let reserve_keypair = Keypair::new();
+    let collateral_mint_keypair = Keypair::new();
+    let collateral_supply_keypair = Keypair::new();
+    let liquidity_supply_keypair = Keypair::new();
+    let liquidity_fee_receiver_keypair = Keypair::new();
+    let user_collateral_keypair = Keypair::new();
+    let user_transfer_authority_keypair = Keypair::new();
+    let accounts = LocalMarketAccounts {
+        liquidity_mint: liquidity_mint_keypair.pubkey(),
+        user_liquidity: user_liquidity_keypair.pubkey(),
+        lending_market: lending_market_keypair.pubkey(),
+        reserve: InitReserveAccounts {
+            reserve: reserve_keypair.pubkey(),
+            collateral_mint: collateral_mint_keypair.pubkey(),
+            collateral_supply: collateral_supply_keypair.pubkey(),
+            liquidity_supply: liquidity_supply_keypair.pubkey(),
+            liquidity_fee_receiver: liquidity_fee_receiver_keypair.pubkey(),
+            user_collateral: user_collateral_keypair.pubkey(),
+            user_transfer_authority: user_transfer_authority_keypair.pubkey(),
+        },
+    };
+
+    let rent = from_account::<Rent, _>(&config.rpc_client.get_account(&sysvar::rent::id())?)
+        .ok_or("Failed to deserialize the rent sysvar")?;
+    let transactions = build_local_market_transactions(
+        config.lending_program_id,
+        config.fee_payer.pubkey(),
+        &rent,
+        parse_quote_currency("USD", QuoteKind::Symbol)?,
+        decimals,
+        ui_amount_to_amount(mint_ui_amount, decimals),
+        ui_amount_to_amount(ui_amount, decimals),
+        fixed_price,
+        ReserveConfig {
+            optimal_utilization_rate: 80,
+            loan_to_value_ratio: 50,
+            liquidation_bonus: 5,
+            liquidation_threshold: 55,
+            min_borrow_rate: 0,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 30,
+            fees: ReserveFees {
+                borrow_fee_wad: parse_percent_to_wad("0.0001")?,
+                flash_loan_fee_wad: parse_percent_to_wad("0.0009")?,
+                host_fee_percentage: 20,
+            },
+            ..ReserveConfig::default()
+        },
+        &accounts,
+    )?;
+    let signers: [Vec<&dyn Signer>; 3] = [
+        vec![
+            config.fee_payer.as_ref(),
+            &liquidity_mint_keypair,
+            &user_liquidity_keypair,
+            &lending_market_keypair,
+        ],
+        vec![
+            config.fee_payer.as_ref(),
+            &reserve_keypair,
+            &collateral_mint_keypair,
+            &collateral_supply_keypair,
+            &user_collateral_keypair,
+        ],
+        vec![
+            config.fee_payer.as_ref(),
+            &liquidity_supply_keypair,
+            &liquidity_fee_receiver_keypair,
+            &user_transfer_authority_keypair,
+        ],
+    ];
+
+    for (instructions, signers) in transactions.iter().zip(signers.iter()) {
+        let mut transaction =
+            Transaction::new_with_payer(instructions, Some(&config.fee_payer.pubkey()));
+        let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+        transaction.sign(signers, recent_blockhash);
+        send_transaction(config, transaction)?;
+    }
+
+    println!(
+        "Lending market owner {}\n\
+        Lending market {}\n\
+        Liquidity mint {}\n\
+        User liquidity {}\n\
+        Reserve {}\n\
+        Reserve liquidity supply {}\n\
+        Reserve liquidity fee receiver {}\n\
+        Reserve collateral mint {}\n\
+        Reserve collateral supply {}\n\
+        User collateral {}",
+        config.fee_payer.pubkey(),
+        accounts.lending_market,
+        accounts.liquidity_mint,
+        accounts.user_liquidity,
+        accounts.reserve.reserve,
+        accounts.reserve.liquidity_supply,
+        accounts.reserve.liquidity_fee_receiver,
+        accounts.reserve.collateral_mint,
+        accounts.reserve.collateral_supply,
+        accounts.reserve.user_collateral,
+    );
+    Ok(())
+}
+
+fn command_set_min_liquidation_value(
+    config: &Config,
+    lending_market: Pubkey,
+    lending_market_owner: Box<dyn Signer>,
+    min_liquidation_value: Decimal,
+) -> CommandResult {
+    println!(
+        "Requiring liquidations in lending market {} to repay a value of at least {}",
+        lending_market, min_liquidation_value
+    );
+    let mut transaction = Transaction::new_with_payer(
+        &[set_min_liquidation_value(
+            config.lending_program_id,
+            lending_market,
+            lending_market_owner.pubkey(),
+            min_liquidation_value,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), lending_market_owner.as_ref()],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_unpause_reserve(
+    config: &Config,
+    reserve: Pubkey,
+    lending_market: Pubkey,
+    lending_market_owner: Box<dyn Signer>,
+) -> CommandResult {
+    println!("Unpausing reserve {}", reserve);
+    let mut transaction = Transaction::new_with_payer(
+        &[unpause_reserve(
+            config.lending_program_id,
+            reserve,
+            lending_market,
+            lending_market_owner.pubkey(),
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), lending_market_owner.as_ref()],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_set_reserve_wind_down(
+    config: &Config,
+    reserve: Pubkey,
+    lending_market: Pubkey,
+    lending_market_owner: Box<dyn Signer>,
+    wind_down: bool,
+) -> CommandResult {
+    if wind_down {
+        println!("Winding down reserve {}", reserve);
+    } else {
+        println!("Reopening reserve {}", reserve);
+    }
+    let mut transaction = Transaction::new_with_payer(
+        &[set_reserve_wind_down(
+            config.lending_program_id,
+            wind_down,
+            reserve,
+            lending_market,
+            lending_market_owner.pubkey(),
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), lending_market_owner.as_ref()],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_set_host_fee(
+    config: &Config,
+    reserve: Pubkey,
+    lending_market: Pubkey,
+    lending_market_owner: Box<dyn Signer>,
+    host_fee_percentage: u8,
+) -> CommandResult {
+    println!(
+        "Setting the host fee of reserve {} to {}%",
+        reserve, host_fee_percentage
+    );
+    let mut transaction = Transaction::new_with_payer(
+        &[set_host_fee(
+            config.lending_program_id,
+            reserve,
+            lending_market,
+            lending_market_owner.pubkey(),
+            host_fee_percentage,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), lending_market_owner.as_ref()],
         recent_blockhash,
     );
     send_transaction(config, transaction)?;
     Ok(())
 }
 
+fn command_set_staking_pool(
+    config: &Config,
+    reserve: Pubkey,
+    lending_market: Pubkey,
+    lending_market_owner: Box<dyn Signer>,
+    staking_pool: COption<Pubkey>,
+) -> CommandResult {
+    if let COption::Some(staking_pool) = staking_pool {
+        let staking_pool_account = config
+            .rpc_client
+            .get_account(&staking_pool)
+            .map_err(|_| format!("Staking pool {} does not exist", staking_pool))?;
+        if staking_pool_account.owner != port_finance_staking::id() {
+            return Err(format!(
+                "Staking pool {} is owned by {}, not the staking program {}",
+                staking_pool,
+                staking_pool_account.owner,
+                port_finance_staking::id()
+            )
+            .into());
+        }
+        StakingPool::unpack(&staking_pool_account.data)
+            .map_err(|_| format!("{} is not an initialized staking pool", staking_pool))?;
+    }
+
+    let current_config = Reserve::unpack(&config.rpc_client.get_account(&reserve)?.data)?.config;
+    if current_config.deposit_staking_pool.is_some()
+        && current_config.deposit_staking_pool != staking_pool
+    {
+        println!(
+            "Warning: collateral already staked in {} stays there. Existing stake accounts \
+             have to be moved with MigrateStakeAccount before they earn from the new pool.",
+            current_config.deposit_staking_pool.unwrap()
+        );
+    }
+    command_update_reserve(
+        config,
+        reserve,
+        lending_market,
+        lending_market_owner,
+        &current_config,
+        ReserveConfig {
+            deposit_staking_pool: staking_pool,
+            ..current_config
+        },
+    )
+}
+
 #[allow(clippy::too_many_arguments)]
 fn command_add_reserve(
     config: &Config,
@@ -870,107 +2067,35 @@ fn command_add_reserve(
         );
     }
 
-    let reserve_balance = config
-        .rpc_client
-        .get_minimum_balance_for_rent_exemption(Reserve::LEN)?;
-    let collateral_mint_balance = config
-        .rpc_client
-        .get_minimum_balance_for_rent_exemption(Mint::LEN)?;
-    let token_account_balance = config
-        .rpc_client
-        .get_minimum_balance_for_rent_exemption(Token::LEN)?;
-    let collateral_supply_balance = token_account_balance;
-    let user_collateral_balance = token_account_balance;
-    let liquidity_supply_balance = token_account_balance;
-    let liquidity_fee_receiver_balance = token_account_balance;
-
-    let mut transaction_1 = Transaction::new_with_payer(
-        &[
-            create_account(
-                &config.fee_payer.pubkey(),
-                &reserve_keypair.pubkey(),
-                reserve_balance,
-                Reserve::LEN as u64,
-                &config.lending_program_id,
-            ),
-            create_account(
-                &config.fee_payer.pubkey(),
-                &collateral_mint_keypair.pubkey(),
-                collateral_mint_balance,
-                Mint::LEN as u64,
-                &spl_token::id(),
-            ),
-            create_account(
-                &config.fee_payer.pubkey(),
-                &collateral_supply_keypair.pubkey(),
-                collateral_supply_balance,
-                Token::LEN as u64,
-                &spl_token::id(),
-            ),
-            create_account(
-                &config.fee_payer.pubkey(),
-                &user_collateral_keypair.pubkey(),
-                user_collateral_balance,
-                Token::LEN as u64,
-                &spl_token::id(),
-            ),
-        ],
-        Some(&config.fee_payer.pubkey()),
-    );
-
-    let mut transaction_2 = Transaction::new_with_payer(
-        &[
-            create_account(
-                &config.fee_payer.pubkey(),
-                &liquidity_supply_keypair.pubkey(),
-                liquidity_supply_balance,
-                Token::LEN as u64,
-                &spl_token::id(),
-            ),
-            create_account(
-                &config.fee_payer.pubkey(),
-                &liquidity_fee_receiver_keypair.pubkey(),
-                liquidity_fee_receiver_balance,
-                Token::LEN as u64,
-                &spl_token::id(),
-            ),
-            approve(
-                &spl_token::id(),
-                &source_liquidity_pubkey,
-                &user_transfer_authority_keypair.pubkey(),
-                &source_liquidity_owner_keypair.pubkey(),
-                &[],
-                liquidity_amount,
-            )
-            .unwrap(),
-            init_reserve(
-                config.lending_program_id,
-                liquidity_amount,
-                fixed_price,
-                reserve_config,
-                source_liquidity_pubkey,
-                user_collateral_keypair.pubkey(),
-                reserve_keypair.pubkey(),
-                source_liquidity.mint,
-                liquidity_supply_keypair.pubkey(),
-                liquidity_fee_receiver_keypair.pubkey(),
-                collateral_mint_keypair.pubkey(),
-                collateral_supply_keypair.pubkey(),
-                lending_market_pubkey,
-                lending_market_owner_keypair.pubkey(),
-                user_transfer_authority_keypair.pubkey(),
-                pyth_price_pubkey,
-            ),
-            revoke(
-                &spl_token::id(),
-                &source_liquidity_pubkey,
-                &source_liquidity_owner_keypair.pubkey(),
-                &[],
-            )
-            .unwrap(),
-        ],
-        Some(&config.fee_payer.pubkey()),
-    );
+    let rent = from_account::<Rent, _>(&config.rpc_client.get_account(&sysvar::rent::id())?)
+        .ok_or("Failed to deserialize the rent sysvar")?;
+    let transactions = build_init_reserve_transaction(
+        config.lending_program_id,
+        config.fee_payer.pubkey(),
+        &rent,
+        liquidity_amount,
+        fixed_price,
+        reserve_config,
+        source_liquidity_pubkey,
+        source_liquidity_owner_keypair.pubkey(),
+        source_liquidity.mint,
+        lending_market_pubkey,
+        lending_market_owner_keypair.pubkey(),
+        pyth_price_pubkey,
+        &InitReserveAccounts {
+            reserve: reserve_keypair.pubkey(),
+            collateral_mint: collateral_mint_keypair.pubkey(),
+            collateral_supply: collateral_supply_keypair.pubkey(),
+            liquidity_supply: liquidity_supply_keypair.pubkey(),
+            liquidity_fee_receiver: liquidity_fee_receiver_keypair.pubkey(),
+            user_collateral: user_collateral_keypair.pubkey(),
+            user_transfer_authority: user_transfer_authority_keypair.pubkey(),
+        },
+    )?;
+    let mut transaction_1 =
+        Transaction::new_with_payer(&transactions[0], Some(&config.fee_payer.pubkey()));
+    let mut transaction_2 =
+        Transaction::new_with_payer(&transactions[1], Some(&config.fee_payer.pubkey()));
 
     let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
     transaction_1.sign(
@@ -1001,7 +2126,236 @@ fn command_add_reserve(
     Ok(())
 }
 
+fn command_show_reserve(
+    config: &Config,
+    reserve_pubkey: Pubkey,
+    collateral_account: Option<Pubkey>,
+    borrow_amount: Option<u64>,
+    redeem_collateral: Option<u64>,
+) -> CommandResult {
+    let reserve = Reserve::unpack(&config.rpc_client.get_account(&reserve_pubkey)?.data)?;
+    let lending_market =
+        LendingMarket::unpack(&config.rpc_client.get_account(&reserve.lending_market)?.data)?;
+    let slots_per_year = lending_market.slots_per_year();
+    let decimals = reserve.liquidity.mint_decimals;
+    let price = reserve.liquidity.market_price;
+    println!(
+        "Reserve {}\n\
+        Liquidity mint {}\n\
+        Market price {}\n\
+        Available liquidity {}\n\
+        Borrowed liquidity {}\n\
+        Total liquidity {}\n\
+        Protocol owned liquidity {}\n\
+        Collateral mint {}\n\
+        Collateral supply {}\n\
+        Redeemable collateral {}",
+        reserve_pubkey,
+        reserve.liquidity.mint_pubkey,
+        price,
+        format_amount(reserve.liquidity.available_amount, decimals, price),
+        format_amount(
+            reserve.liquidity.borrowed_amount_wads.try_ceil_u64()?,
+            decimals,
+            price
+        ),
+        format_amount(
+            reserve.liquidity.total_supply()?.try_floor_u64()?,
+            decimals,
+            price
+        ),
+        format_amount(reserve.protocol_owned_amount(), decimals, price),
+        reserve.collateral.mint_pubkey,
+        format_amount(
+            reserve.collateral.mint_total_supply,
+            decimals,
+            Decimal::zero()
+        ),
+        format_amount(
+            reserve.max_redeemable_collateral(reserve.collateral.mint_total_supply)?,
+            decimals,
+            Decimal::zero()
+        ),
+    );
+    // fees are transferred to the fee receiver when charged, and `WithdrawFee` sweeps its balance
+    let fee_receiver = Token::unpack(
+        &config
+            .rpc_client
+            .get_account(&reserve.liquidity.fee_receiver)?
+            .data,
+    )?;
+    println!(
+        "Fee receiver {}\n\
+        Pending protocol fees {}",
+        reserve.liquidity.fee_receiver,
+        format_amount(fee_receiver.amount, decimals, price),
+    );
+    println!(
+        "Supply APY {}\n\
+        Borrow APY {}\n\
+        Annualized over {} slots per year",
+        format_rate(reserve.supply_apy(slots_per_year)?),
+        format_rate(reserve.borrow_apy(slots_per_year)?),
+        slots_per_year,
+    );
+    if let Some(collateral_account) = collateral_account {
+        let collateral = Token::unpack(&config.rpc_client.get_account(&collateral_account)?.data)?;
+        println!(
+            "Redeemable from {} {} of {}",
+            collateral_account,
+            format_amount(
+                reserve.max_redeemable_collateral(collateral.amount)?,
+                decimals,
+                Decimal::zero()
+            ),
+            format_amount(collateral.amount, decimals, Decimal::zero()),
+        );
+    }
+    let utilization = reserve.liquidity.utilization_rate()?;
+    if let Some(borrow_amount) = borrow_amount {
+        println!(
+            "Utilization after borrowing {}: {} -> {}",
+            format_amount(borrow_amount, decimals, price),
+            format_rate(utilization),
+            format_rate(reserve.utilization_after_borrow(borrow_amount)?),
+        );
+    }
+    if let Some(redeem_collateral) = redeem_collateral {
+        println!(
+            "Utilization after redeeming {}: {} -> {}",
+            format_amount(redeem_collateral, decimals, Decimal::zero()),
+            format_rate(utilization),
+            format_rate(reserve.utilization_after_redeem(redeem_collateral)?),
+        );
+    }
+    Ok(())
+}
+
+fn command_decode(config: &Config, account_pubkey: Pubkey) -> CommandResult {
+    let account = config.rpc_client.get_account(&account_pubkey)?;
+    println!(
+        "Account {}\n\
+        Owner {}\n\
+        Length {}",
+        account_pubkey,
+        account.owner,
+        account.data.len(),
+    );
+    if account.owner != config.lending_program_id {
+        println!(
+            "Account is not owned by the lending program {}",
+            config.lending_program_id
+        );
+    }
+    println!("{}", decode_account(&account.data)?);
+    Ok(())
+}
+
+/// Unpack lending program account data as the account type of its length, formatting every field
+fn decode_account(data: &[u8]) -> Result<String, Error> {
+    Ok(match data.len() {
+        LendingMarket::LEN => format!("Lending market {:#?}", LendingMarket::unpack(data)?),
+        Reserve::LEN => format!("Reserve {:#?}", Reserve::unpack(data)?),
+        Obligation::LEN => format!("Obligation {:#?}", Obligation::unpack(data)?),
+        len => return Err(format!("No lending program account is {} bytes long", len).into()),
+    })
+}
+
 #[allow(clippy::too_many_arguments)]
+fn command_show_obligation(
+    config: &Config,
+    obligation_pubkey: Pubkey,
+    collateral_reserve: Option<Pubkey>,
+    borrow_reserve: Option<Pubkey>,
+) -> CommandResult {
+    let obligation = Obligation::unpack(&config.rpc_client.get_account(&obligation_pubkey)?.data)?;
+    println!(
+        "Obligation {}\n\
+        Owner {}\n\
+        Last update slot {}\n\
+        Deposited value {}\n\
+        Borrowed value {}\n\
+        Allowed borrow value {}\n\
+        Unhealthy borrow value {}",
+        obligation_pubkey,
+        obligation.owner,
+        obligation.last_update.slot,
+        obligation.deposited_value,
+        obligation.borrowed_value,
+        obligation.allowed_borrow_value,
+        obligation.unhealthy_borrow_value,
+    );
+    for collateral in &obligation.deposits {
+        let reserve = Reserve::unpack(
+            &config
+                .rpc_client
+                .get_account(&collateral.deposit_reserve)?
+                .data,
+        )?;
+        let liquidity_amount = reserve
+            .collateral_exchange_rate()?
+            .collateral_to_liquidity(collateral.deposited_amount)?;
+        println!(
+            "Deposit {} amount {} ({}) value {}",
+            collateral.deposit_reserve,
+            collateral.deposited_amount,
+            format_amount(
+                liquidity_amount,
+                reserve.liquidity.mint_decimals,
+                reserve.liquidity.market_price
+            ),
+            collateral.market_value
+        );
+    }
+    for liquidity in &obligation.borrows {
+        let reserve = Reserve::unpack(
+            &config
+                .rpc_client
+                .get_account(&liquidity.borrow_reserve)?
+                .data,
+        )?;
+        println!(
+            "Borrow {} amount {} ({}) value {}",
+            liquidity.borrow_reserve,
+            liquidity.borrowed_amount_wads,
+            format_amount(
+                liquidity.borrowed_amount_wads.try_ceil_u64()?,
+                reserve.liquidity.mint_decimals,
+                reserve.liquidity.market_price
+            ),
+            liquidity.market_value
+        );
+    }
+
+    if let Some(collateral_reserve) = collateral_reserve {
+        let reserve = Reserve::unpack(&config.rpc_client.get_account(&collateral_reserve)?.data)?;
+        println!(
+            "Liquidation price of {} {}",
+            collateral_reserve,
+            obligation.liquidation_price(&collateral_reserve, &reserve)?
+        );
+    }
+    // the borrow fee discount of staked obligation owners is not applied
+    if let Some(borrow_reserve) = borrow_reserve {
+        let reserve = Reserve::unpack(&config.rpc_client.get_account(&borrow_reserve)?.data)?;
+        let max_borrow_amount = obligation.max_borrow_amount(
+            reserve.liquidity.market_price,
+            reserve.liquidity.mint_decimals,
+            &reserve.config.fees,
+        )?;
+        println!(
+            "Max borrow amount of {} {}",
+            borrow_reserve,
+            format_amount(
+                max_borrow_amount,
+                reserve.liquidity.mint_decimals,
+                reserve.liquidity.market_price
+            )
+        );
+    }
+    Ok(())
+}
+
 fn command_repay_loan(
     config: &Config,
     amount: u64,
@@ -1010,12 +2364,16 @@ fn command_repay_loan(
     dest_token: Pubkey,
     repay_reserve: Pubkey,
     repay_obligation: Pubkey,
-    all_reserves_with_oracle: Vec<(Pubkey, COption<Pubkey>)>,
     lending_market: Pubkey,
 ) -> CommandResult {
+    let reserve = Reserve::unpack(&config.rpc_client.get_account(&repay_reserve)?.data)?;
     println!(
         "Repay Loan {}, from {} to {}",
-        amount,
+        format_amount(
+            amount,
+            reserve.liquidity.mint_decimals,
+            reserve.liquidity.market_price
+        ),
         source_wallet.pubkey(),
         dest_token
     );
@@ -1040,28 +2398,46 @@ fn command_repay_loan(
         amount
     );
 
-    let mut instructions: Vec<_> = all_reserves_with_oracle
-        .iter()
-        .map(|(r, o)| refresh_reserve(config.lending_program_id, *r, *o))
-        .collect();
-    instructions.push(refresh_obligation(
-        config.lending_program_id,
-        repay_obligation,
-        all_reserves_with_oracle.iter().map(|(r, _)| *r).collect(),
-    ));
-    instructions.push(repay_obligation_liquidity(
-        config.lending_program_id,
-        amount,
-        source_token,
-        dest_token,
-        repay_reserve,
-        repay_obligation,
-        lending_market,
-        source_wallet.pubkey(),
-    ));
+    let obligation = Obligation::unpack(&config.rpc_client.get_account(&repay_obligation)?.data)?;
+    let obligation_reserves = obligation.refresh_account_metas();
+
+    let mut reserve_refreshes = Vec::new();
+    let mut refreshed_reserves = Vec::new();
+    for reserve_pubkey in &obligation_reserves {
+        if refreshed_reserves.contains(reserve_pubkey) {
+            continue;
+        }
+        let reserve = Reserve::unpack(&config.rpc_client.get_account(reserve_pubkey)?.data)?;
+        reserve_refreshes.push(refresh_reserve(
+            config.lending_program_id,
+            *reserve_pubkey,
+            reserve.liquidity.oracle_pubkey,
+        ));
+        refreshed_reserves.push(*reserve_pubkey);
+    }
+    let action = vec![
+        refresh_obligation(
+            config.lending_program_id,
+            repay_obligation,
+            obligation_reserves,
+        ),
+        repay_obligation_liquidity(
+            config.lending_program_id,
+            amount,
+            source_token,
+            dest_token,
+            repay_reserve,
+            repay_obligation,
+            lending_market,
+            source_wallet.pubkey(),
+        ),
+    ];
+    let instructions =
+        refreshed_action_instructions(&config.fee_payer.pubkey(), reserve_refreshes, action)?;
+
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
     let mut transaction =
         Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
-    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
     transaction.sign(
         &vec![config.fee_payer.as_ref(), &source_wallet],
         recent_blockhash,
@@ -1070,6 +2446,54 @@ fn command_repay_loan(
     Ok(())
 }
 
+/// Size of `instructions` serialized as one transaction paid by `payer`, with its signatures
+fn transaction_size(instructions: &[Instruction], payer: &Pubkey) -> usize {
+    let message = Message::new(instructions, Some(payer));
+    // the signature count is a compact-u16, a single byte below 128 signatures
+    1 + message.header.num_required_signatures as usize * 64 + message.serialize().len()
+}
+
+/// The `reserve_refreshes` an `action` needs followed by the action, as one transaction. The
+/// action's obligation refresh only accepts reserves refreshed in the same slot, so the
+/// refreshes can't be sent in earlier transactions. An obligation with too many reserves for
+/// them to fit in a packet fails with a message to split it.
+fn refreshed_action_instructions(
+    payer: &Pubkey,
+    reserve_refreshes: Vec<Instruction>,
+    action: Vec<Instruction>,
+) -> Result<Vec<Instruction>, Error> {
+    let refresh_count = reserve_refreshes.len();
+    let instructions = [reserve_refreshes, action].concat();
+    let size = transaction_size(&instructions, payer);
+    if size > PACKET_DATA_SIZE {
+        return Err(format!(
+            "Refreshing the obligation's {} reserves takes a {} byte transaction, more than the \
+            {} byte limit. Move some of its deposits or borrows to another obligation with \
+            SplitObligation and try again",
+            refresh_count, size, PACKET_DATA_SIZE
+        )
+        .into());
+    }
+    Ok(instructions)
+}
+
+/// Render a raw token amount as a UI amount, followed by its approximate value in the quote
+/// currency when the price is known
+fn format_amount(amount: u64, decimals: u8, price: Decimal) -> String {
+    let ui_amount = amount_to_ui_amount(amount, decimals);
+    if price == Decimal::zero() {
+        return ui_amount.to_string();
+    }
+    let price: f64 = price.to_string().parse().unwrap_or_default();
+    format!("{} (~{:.2} quote)", ui_amount, ui_amount * price)
+}
+
+/// Render a rate as a percentage
+fn format_rate(rate: Rate) -> String {
+    let rate: f64 = rate.to_string().parse().unwrap_or_default();
+    format!("{:.2}%", rate * 100.0)
+}
+
 fn send_transaction(
     config: &Config,
     transaction: Transaction,
@@ -1082,31 +2506,375 @@ fn send_transaction(
             .rpc_client
             .send_and_confirm_transaction_with_spinner_and_config(
                 &transaction,
-                CommitmentConfig {
-                    commitment: Finalized,
-                },
-                RpcSendTransactionConfig {
-                    skip_preflight: true,
-                    ..RpcSendTransactionConfig::default()
-                },
+                config.commitment,
+                send_transaction_config(config.commitment, config.skip_preflight),
             )?;
         println!("Signature: {}", signature);
     }
     Ok(())
 }
 
-fn quote_currency_of(matches: &ArgMatches<'_>, name: &str) -> Option<[u8; 32]> {
-    if let Some(value) = matches.value_of(name) {
-        if value == "USD" {
-            Some(*b"USD\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0")
-        } else if value.len() <= 32 {
+fn send_transaction_args<'a, 'b>() -> [Arg<'a, 'b>; 3] {
+    [
+        Arg::with_name("commitment")
+            .long("commitment")
+            .value_name("LEVEL")
+            .takes_value(true)
+            .possible_values(&["processed", "confirmed", "finalized"])
+            .default_value("finalized")
+            .global(true)
+            .help("Commitment level to wait for after sending a transaction"),
+        Arg::with_name("skip_preflight")
+            .long("skip-preflight")
+            .takes_value(false)
+            .global(true)
+            .help("Skip the preflight simulation of sent transactions [default]"),
+        Arg::with_name("no_skip_preflight")
+            .long("no-skip-preflight")
+            .takes_value(false)
+            .conflicts_with("skip_preflight")
+            .global(true)
+            .help("Run the preflight simulation of sent transactions and report its errors"),
+    ]
+}
+
+fn commitment_of(matches: &ArgMatches<'_>) -> CommitmentConfig {
+    match matches.value_of("commitment") {
+        Some("processed") => CommitmentConfig::processed(),
+        Some("confirmed") => CommitmentConfig::confirmed(),
+        _ => CommitmentConfig::finalized(),
+    }
+}
+
+fn skip_preflight_of(matches: &ArgMatches<'_>) -> bool {
+    !matches.is_present("no_skip_preflight")
+}
+
+fn send_transaction_config(
+    commitment: CommitmentConfig,
+    skip_preflight: bool,
+) -> RpcSendTransactionConfig {
+    RpcSendTransactionConfig {
+        skip_preflight,
+        preflight_commitment: Some(commitment.commitment),
+        ..RpcSendTransactionConfig::default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum QuoteKind {
+    Symbol,
+    Mint,
+}
+
+fn quote_currency_of(
+    matches: &ArgMatches<'_>,
+    name: &str,
+    kind_name: &str,
+) -> Result<[u8; 32], String> {
+    let kind = match matches.value_of(kind_name) {
+        Some("mint") => QuoteKind::Mint,
+        _ => QuoteKind::Symbol,
+    };
+    parse_quote_currency(matches.value_of(name).unwrap(), kind)
+}
+
+/// A `mint:` prefix reads the value as a mint pubkey whatever `kind` is, so a symbol is never
+/// mistaken for a mint or the other way around
+fn parse_quote_currency(value: &str, kind: QuoteKind) -> Result<[u8; 32], String> {
+    let (value, kind) = match value.strip_prefix("mint:") {
+        Some(mint) => (mint, QuoteKind::Mint),
+        None => (value, kind),
+    };
+    match kind {
+        QuoteKind::Symbol => {
+            if value.is_empty() || value.len() > 32 {
+                return Err(format!(
+                    "Quote currency symbol must be 1 to 32 bytes, provided: {}",
+                    value
+                ));
+            }
             let mut bytes32 = [0u8; 32];
             bytes32[0..value.len()].clone_from_slice(value.as_bytes());
-            Some(bytes32)
-        } else {
-            Some(Pubkey::from_str(value).unwrap().to_bytes())
+            Ok(bytes32)
         }
-    } else {
-        None
+        QuoteKind::Mint => Pubkey::from_str(value)
+            .map(|mint| mint.to_bytes())
+            .map_err(|_| {
+                format!(
+                    "Quote currency mint is not a valid pubkey, provided: {}",
+                    value
+                )
+            }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use port_finance_variable_rate_lending::state::PROGRAM_VERSION;
+    use solana_sdk::hash::Hash;
+
+    fn send_transaction_config_from(args: &[&str]) -> (CommitmentConfig, RpcSendTransactionConfig) {
+        let matches = App::new("test")
+            .args(&send_transaction_args())
+            .get_matches_from(args);
+        let commitment = commitment_of(&matches);
+        (
+            commitment,
+            send_transaction_config(commitment, skip_preflight_of(&matches)),
+        )
+    }
+
+    #[test]
+    fn decode_account_by_length() {
+        let owner = Pubkey::new_unique();
+        let lending_market = LendingMarket {
+            version: PROGRAM_VERSION,
+            owner,
+            ..LendingMarket::default()
+        };
+        let mut data = vec![0; LendingMarket::LEN];
+        LendingMarket::pack(lending_market, &mut data).unwrap();
+        let decoded = decode_account(&data).unwrap();
+        assert!(decoded.starts_with("Lending market LendingMarket {"));
+        assert!(decoded.contains(&format!("owner: {:?},", owner)));
+
+        let reserve = Reserve {
+            version: PROGRAM_VERSION,
+            ..Reserve::default()
+        };
+        let mut data = vec![0; Reserve::LEN];
+        Reserve::pack(reserve, &mut data).unwrap();
+        assert!(decode_account(&data)
+            .unwrap()
+            .starts_with("Reserve Reserve {"));
+
+        let obligation = Obligation {
+            version: PROGRAM_VERSION,
+            owner,
+            ..Obligation::default()
+        };
+        let mut data = vec![0; Obligation::LEN];
+        Obligation::pack(obligation, &mut data).unwrap();
+        let decoded = decode_account(&data).unwrap();
+        assert!(decoded.starts_with("Obligation Obligation {"));
+        assert!(decoded.contains(&format!("owner: {:?},", owner)));
+
+        assert!(decode_account(&[0; 3]).is_err());
+    }
+
+    #[test]
+    fn send_transaction_config_flags() {
+        // defaults keep the finalized commitment and skip preflight
+        let (commitment, send_config) = send_transaction_config_from(&["test"]);
+        assert_eq!(commitment, CommitmentConfig::finalized());
+        assert!(send_config.skip_preflight);
+
+        let (commitment, send_config) = send_transaction_config_from(&[
+            "test",
+            "--commitment",
+            "confirmed",
+            "--no-skip-preflight",
+        ]);
+        assert_eq!(commitment, CommitmentConfig::confirmed());
+        assert!(!send_config.skip_preflight);
+        assert_eq!(
+            send_config.preflight_commitment,
+            Some(CommitmentConfig::confirmed().commitment)
+        );
+
+        let (_, send_config) = send_transaction_config_from(&["test", "--skip-preflight"]);
+        assert!(send_config.skip_preflight);
+    }
+
+    #[test]
+    fn format_amount_with_price() {
+        assert_eq!(
+            format_amount(1_500_000, 6, Decimal::from(2u64)),
+            "1.5 (~3.00 quote)"
+        );
+        assert_eq!(
+            format_amount(25, 2, Decimal::from_percent(40)),
+            "0.25 (~0.10 quote)"
+        );
+        assert_eq!(format_amount(0, 9, Decimal::from(40u64)), "0 (~0.00 quote)");
+    }
+
+    #[test]
+    fn format_amount_without_price() {
+        assert_eq!(format_amount(1_500_000, 6, Decimal::zero()), "1.5");
+        assert_eq!(format_amount(7, 0, Decimal::zero()), "7");
+    }
+
+    #[test]
+    fn format_rate_as_percent() {
+        assert_eq!(format_rate(Rate::from_percent(5)), "5.00%");
+        assert_eq!(
+            format_rate(Rate::from_scaled_val(77_884_150_000_000_000)),
+            "7.79%"
+        );
+        assert_eq!(format_rate(Rate::zero()), "0.00%");
+    }
+
+    #[test]
+    fn parse_quote_currency_symbol() {
+        assert_eq!(
+            parse_quote_currency("USD", QuoteKind::Symbol),
+            Ok(*b"USD\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0")
+        );
+        assert!(parse_quote_currency("", QuoteKind::Symbol).is_err());
+        assert!(parse_quote_currency(&"A".repeat(33), QuoteKind::Symbol).is_err());
+    }
+
+    #[test]
+    fn parse_quote_currency_mint() {
+        let mint = Pubkey::new_unique();
+        assert_eq!(
+            parse_quote_currency(&mint.to_string(), QuoteKind::Mint),
+            Ok(mint.to_bytes())
+        );
+        assert_eq!(
+            parse_quote_currency(&format!("mint:{}", mint), QuoteKind::Symbol),
+            Ok(mint.to_bytes())
+        );
+        // a 44 character pubkey is too long to be taken for a symbol
+        assert!(parse_quote_currency(&mint.to_string(), QuoteKind::Symbol).is_err());
+        assert!(parse_quote_currency("mint:USD", QuoteKind::Symbol).is_err());
+        assert!(parse_quote_currency("USD", QuoteKind::Mint).is_err());
+    }
+
+    #[test]
+    fn parse_quote_currency_ambiguous() {
+        // 32 characters that are both a valid symbol and the system program id
+        let ambiguous = "11111111111111111111111111111111";
+        let mut symbol = [0u8; 32];
+        symbol.copy_from_slice(ambiguous.as_bytes());
+        assert_eq!(
+            parse_quote_currency(ambiguous, QuoteKind::Symbol),
+            Ok(symbol)
+        );
+        assert_eq!(
+            parse_quote_currency(ambiguous, QuoteKind::Mint),
+            Ok([0u8; 32])
+        );
+        assert_eq!(
+            parse_quote_currency(&format!("mint:{}", ambiguous), QuoteKind::Symbol),
+            Ok([0u8; 32])
+        );
+
+        let quote_currency_from = |args: &[&str]| {
+            let matches = App::new("test")
+                .arg(
+                    Arg::with_name("quote_currency")
+                        .long("quote")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("quote_kind")
+                        .long("quote-kind")
+                        .takes_value(true)
+                        .possible_values(&["symbol", "mint"])
+                        .default_value("symbol"),
+                )
+                .get_matches_from(args);
+            quote_currency_of(&matches, "quote_currency", "quote_kind")
+        };
+        assert_eq!(
+            quote_currency_from(&["test", "--quote", ambiguous]),
+            Ok(symbol)
+        );
+        assert_eq!(
+            quote_currency_from(&["test", "--quote", ambiguous, "--quote-kind", "mint"]),
+            Ok([0u8; 32])
+        );
+    }
+
+    /// Refreshes of `reserve_count` reserves with an oracle each, and the refresh of an obligation
+    /// over them followed by a repayment signed by a transfer authority besides the fee payer
+    fn repay_instructions(
+        reserve_count: usize,
+        transfer_authority: &Pubkey,
+    ) -> (Vec<Instruction>, Vec<Instruction>) {
+        let program_id = Pubkey::new_unique();
+        let obligation = Pubkey::new_unique();
+        let reserves: Vec<Pubkey> = (0..reserve_count).map(|_| Pubkey::new_unique()).collect();
+        let reserve_refreshes = reserves
+            .iter()
+            .map(|reserve| {
+                refresh_reserve(program_id, *reserve, COption::Some(Pubkey::new_unique()))
+            })
+            .collect();
+        let action = vec![
+            refresh_obligation(program_id, obligation, reserves.clone()),
+            repay_obligation_liquidity(
+                program_id,
+                1,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                reserves[0],
+                obligation,
+                Pubkey::new_unique(),
+                *transfer_authority,
+            ),
+        ];
+        (reserve_refreshes, action)
+    }
+
+    #[test]
+    fn refreshed_action_instructions_fit_one_transaction() {
+        let payer = Keypair::new();
+        let transfer_authority = Keypair::new();
+        let (reserve_refreshes, action) = repay_instructions(3, &transfer_authority.pubkey());
+        let instructions = [reserve_refreshes.clone(), action.clone()].concat();
+        let result =
+            refreshed_action_instructions(&payer.pubkey(), reserve_refreshes, action).unwrap();
+        assert_eq!(result, instructions);
+
+        // the refreshes and the repayment sign and serialize as one transaction within a packet
+        let mut transaction = Transaction::new_with_payer(&result, Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &transfer_authority], Hash::default());
+        assert!(transaction.verify().is_ok());
+        assert_eq!(transaction.signatures.len(), 2);
+        assert!(transaction_size(&result, &payer.pubkey()) <= PACKET_DATA_SIZE);
+    }
+
+    #[test]
+    fn refreshed_action_instructions_too_many_reserves() {
+        let payer = Pubkey::new_unique();
+        let transfer_authority = Pubkey::new_unique();
+        let (reserve_refreshes, action) = repay_instructions(16, &transfer_authority);
+        let instructions = [reserve_refreshes.clone(), action.clone()].concat();
+        assert!(transaction_size(&instructions, &payer) > PACKET_DATA_SIZE);
+
+        let error = refreshed_action_instructions(&payer, reserve_refreshes, action)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("Refreshing the obligation's 16 reserves"));
+        assert!(error.contains("SplitObligation"));
+    }
+
+    #[test]
+    fn parse_percent_to_wad_exact() {
+        assert_eq!(parse_percent_to_wad("0.0001").unwrap(), 100_000_000_000_000);
+        assert_eq!(parse_percent_to_wad("0.0009").unwrap(), 900_000_000_000_000);
+        assert_eq!(
+            parse_percent_to_wad("0.1").unwrap(),
+            100_000_000_000_000_000
+        );
+        assert_eq!(parse_percent_to_wad(".3").unwrap(), 300_000_000_000_000_000);
+        assert_eq!(parse_percent_to_wad("1").unwrap(), WAD);
+        assert_eq!(parse_percent_to_wad("0.000000000000000001").unwrap(), 1);
+        assert_eq!(parse_percent_to_wad("0").unwrap(), 0);
+    }
+
+    #[test]
+    fn parse_percent_to_wad_invalid() {
+        assert!(parse_percent_to_wad("").is_err());
+        assert!(parse_percent_to_wad(".").is_err());
+        assert!(parse_percent_to_wad("-0.1").is_err());
+        assert!(parse_percent_to_wad("1e-4").is_err());
+        assert!(parse_percent_to_wad("0.0000000000000000001").is_err());
+        assert!(parse_percent_to_wad("19").is_err());
     }
 }
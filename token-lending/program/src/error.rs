@@ -170,6 +170,80 @@ pub enum LendingError {
     ///Invalid Fee account
     #[error("Invalid reserve fee account")]
     InvalidReserveFeeAccount,
+    /// Lending market has no pending owner
+    #[error("Lending market has no pending owner to accept")]
+    NoPendingMarketOwner,
+    /// Instructions preceding the current one are not the expected refreshes
+    #[error("Unexpected instruction order in transaction")]
+    UnexpectedInstructionOrder,
+    // 50
+    /// Liquidity mint has a freeze authority
+    #[error("Liquidity mint has a freeze authority")]
+    FreezableLiquidityMint,
+    /// Fee receiver does not match the reserve fee receiver
+    #[error("Fee receiver does not match the reserve liquidity fee receiver")]
+    WrongFeeReceiver,
+    /// Collateral mint does not match the reserve collateral mint
+    #[error("Collateral mint does not match the reserve collateral mint")]
+    WrongCollateralMint,
+    /// Liquidity supply does not match the reserve liquidity supply
+    #[error("Liquidity supply does not match the reserve liquidity supply")]
+    WrongLiquiditySupply,
+    /// Collateral supply does not match the reserve collateral supply
+    #[error("Collateral supply does not match the reserve collateral supply")]
+    WrongCollateralSupply,
+    /// Borrowing is disabled for the reserve
+    #[error("Borrowing is disabled for the reserve")]
+    BorrowingDisabled,
+    /// Deposit into an empty reserve is below its minimum initial deposit
+    #[error("Deposit into an empty reserve is below its minimum initial deposit")]
+    InitialDepositTooSmall,
+    /// Oracle price is older than the maximum price staleness
+    #[error("Oracle price is stale")]
+    OraclePriceStale,
+    /// Reserve is paused
+    #[error("Reserve is paused until the lending market owner unpauses it")]
+    ReservePaused,
+    /// Reserve has a staking pool but the stake accounts were not supplied
+    #[error("Reserve has a staking pool but the stake accounts are missing")]
+    MissingStakingAccounts,
+    /// Lending market does not allow conservative obligation refreshes
+    #[error("Lending market does not allow conservative obligation refreshes")]
+    ConservativeRefreshDisabled,
+    /// Moving deposits and borrows would leave an obligation unhealthy
+    #[error("Obligation would be unhealthy after moving deposits and borrows")]
+    ObligationUnhealthy,
+    /// Reserve collateral supply does not match its collateral mint supply
+    #[error("Reserve collateral supply does not match the collateral mint supply")]
+    CollateralSupplyMismatch,
+    /// Reserve liquidity moved in a slot would exceed the reserve's limit
+    #[error("Reserve liquidity moved in this slot would exceed the reserve's limit")]
+    RateLimited,
+    /// Reserve collateral mint is already initialized
+    #[error("Reserve collateral mint is already in use")]
+    CollateralMintInUse,
+    /// Obligation has not been unhealthy for the lending market's liquidation grace period
+    #[error("Obligation has not been unhealthy long enough to be liquidated")]
+    LiquidationNotYetAllowed,
+    /// Oracle price moved further from the reserve's previous price than the reserve allows.
+    /// No longer returned, `RefreshReserve` pauses the reserve instead.
+    #[error("Oracle price deviates too far from the previous price")]
+    OracleDeviationTooLarge,
+    /// An obligation or reserve belongs to a different lending market than the one provided
+    #[error("Obligation and reserves do not all belong to the lending market provided")]
+    LendingMarketMismatch,
+    /// Borrow would take the value of a reserve's borrows above its isolated debt ceiling
+    #[error("Borrow would exceed the reserve's isolated debt ceiling")]
+    DebtCeilingExceeded,
+    /// Obligation has more deposits and borrows than a refresh processes in one call
+    #[error("Obligation has too many reserves to refresh in one call")]
+    TooManyReserves,
+    /// Stake account belongs to a different staking pool than the reserve's
+    #[error("Stake account is not in the reserve's deposit staking pool")]
+    StakeAccountPoolMismatch,
+    /// Reserve is winding down
+    #[error("Reserve is winding down, deposits and borrows are blocked")]
+    ReserveWindingDown,
 }
 
 impl From<LendingError> for ProgramError {
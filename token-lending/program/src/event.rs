@@ -0,0 +1,128 @@
+//! Events logged for off-chain accounting
+
+use std::{fmt, str::FromStr};
+
+use solana_program::{msg, pubkey::Pubkey};
+
+use crate::math::Decimal;
+
+/// Version of the event log format, bumped whenever an event's fields change
+pub const EVENT_VERSION: u8 = 1;
+
+/// First field of a logged supply event
+pub const SUPPLY_EVENT_PREFIX: &str = "supply_event";
+
+/// Whether liquidity was supplied to or redeemed from a reserve
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SupplyAction {
+    /// Liquidity deposited and collateral minted
+    Deposit,
+    /// Collateral burned and liquidity redeemed
+    Redeem,
+}
+
+impl SupplyAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Deposit => "deposit",
+            Self::Redeem => "redeem",
+        }
+    }
+}
+
+/// Liquidity moved and collateral minted or burned by a deposit into or a redemption from a
+/// reserve
+#[derive(Clone, Debug, PartialEq)]
+pub struct SupplyEvent {
+    /// Deposit or redemption
+    pub action: SupplyAction,
+    /// Reserve the liquidity was supplied to or redeemed from
+    pub reserve: Pubkey,
+    /// Liquidity moved into or out of the reserve
+    pub liquidity_amount: u64,
+    /// Collateral minted or burned
+    pub collateral_amount: u64,
+    /// Liquidity one collateral token was worth when the amounts were converted
+    pub exchange_rate: Decimal,
+}
+
+impl SupplyEvent {
+    /// Logs the event as a single line `parse` reads back
+    pub fn log(&self) {
+        msg!("{}", self);
+    }
+
+    /// Parses a logged supply event of the current `EVENT_VERSION`, with or without the
+    /// runtime's `Program log: ` prefix
+    pub fn parse(log: &str) -> Option<Self> {
+        let mut fields = log.trim_start_matches("Program log: ").split(' ');
+        if fields.next()? != SUPPLY_EVENT_PREFIX || fields.next()? != format!("v{}", EVENT_VERSION)
+        {
+            return None;
+        }
+        let action = match fields.next()? {
+            "deposit" => SupplyAction::Deposit,
+            "redeem" => SupplyAction::Redeem,
+            _ => return None,
+        };
+        let event = Self {
+            action,
+            reserve: Pubkey::from_str(fields.next()?).ok()?,
+            liquidity_amount: fields.next()?.parse().ok()?,
+            collateral_amount: fields.next()?.parse().ok()?,
+            exchange_rate: Decimal::from_scaled_val(fields.next()?.parse().ok()?),
+        };
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(event)
+    }
+}
+
+impl fmt::Display for SupplyEvent {
+    /// Formats the exchange rate as its scaled value so it parses back exactly
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} v{} {} {} {} {} {}",
+            SUPPLY_EVENT_PREFIX,
+            EVENT_VERSION,
+            self.action.as_str(),
+            self.reserve,
+            self.liquidity_amount,
+            self.collateral_amount,
+            self.exchange_rate.to_scaled_val().map_err(|_| fmt::Error)?
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn supply_event_round_trip() {
+        let event = SupplyEvent {
+            action: SupplyAction::Redeem,
+            reserve: Pubkey::new_unique(),
+            liquidity_amount: 1_050,
+            collateral_amount: 1_000,
+            exchange_rate: Decimal::from_percent(105),
+        };
+        let log = format!("Program log: {}", event);
+        assert_eq!(SupplyEvent::parse(&log), Some(event.clone()));
+        assert_eq!(SupplyEvent::parse(&event.to_string()), Some(event.clone()));
+
+        // other versions and trailing fields are not read as the current event
+        let other_version = log.replace(
+            &format!("v{}", EVENT_VERSION),
+            &format!("v{}", EVENT_VERSION + 1),
+        );
+        assert_eq!(SupplyEvent::parse(&other_version), None);
+        assert_eq!(SupplyEvent::parse(&format!("{} 0", log)), None);
+        assert_eq!(
+            SupplyEvent::parse("Program log: Instruction: Deposit"),
+            None
+        );
+    }
+}
@@ -1,21 +1,30 @@
 //! Instruction types
 
-use std::{convert::TryInto, mem::size_of};
+use std::{collections::HashMap, convert::TryInto, mem::size_of};
 
 use arrayref::array_refs;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     msg,
     program_error::ProgramError,
+    program_pack::Pack,
     pubkey::{Pubkey, PUBKEY_BYTES},
-    sysvar,
+    system_instruction, system_program,
+    sysvar::{self, rent::Rent},
+};
+use spl_token::{
+    instruction::{approve, initialize_account, initialize_mint, mint_to, revoke},
+    solana_program::program_option::COption,
+    state::{Account as Token, Mint},
 };
-use spl_token::solana_program::program_option::COption;
 
 use crate::math::Decimal;
 use crate::{
     error::LendingError,
-    state::{pack_coption_key_compact, unpack_coption_key_compact, ReserveConfig, ReserveFees},
+    state::{
+        find_reserve_address, pack_coption_key_compact, unpack_coption_key_compact, LendingMarket,
+        Obligation, Reserve, ReserveConfig, ReserveFees,
+    },
 };
 
 /// Instructions supported by the lending program.
@@ -38,7 +47,8 @@ pub enum LendingInstruction {
     },
 
     // 1
-    /// Sets the new owner of a lending market.
+    /// Proposes a new owner of a lending market, replacing any pending proposal.
+    /// The current owner keeps control until the new owner sends `AcceptLendingMarketOwner`.
     ///
     /// Accounts expected by this instruction:
     ///
@@ -82,26 +92,27 @@ pub enum LendingInstruction {
     },
 
     // 3
-    /// Accrue interest and update market price of liquidity on a reserve.
+    /// Accrue interest and update market price of liquidity on a reserve. A reserve that was
+    /// already refreshed in the current slot and not marked stale since is left unchanged.
     ///
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Reserve account.
     ///   1. `[]` Clock sysvar.
     ///   2. `[]` Reserve liquidity oracle account.
-    ///             Must be the Pyth price account specified at InitReserve.
+    ///             Must be the price account specified at InitReserve, ignored for a fixed price.
     RefreshReserve,
 
     // 4
     /// Deposit liquidity into a reserve in exchange for collateral. Collateral represents a share
-    /// of the reserve liquidity pool.
+    /// of the reserve liquidity pool. Requires a refreshed reserve.
     ///
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Source liquidity token account.
     ///                     $authority can transfer $liquidity_amount.
     ///   1. `[writable]` Destination collateral token account.
-    ///   2. `[writable]` Reserve account.
+    ///   2. `[writable]` Reserve account - refreshed.
     ///   3. `[writable]` Reserve liquidity supply SPL Token account.
     ///   4. `[writable]` Reserve collateral SPL Token mint.
     ///   5. `[]` Lending market account.
@@ -151,7 +162,14 @@ pub enum LendingInstruction {
     // 7
     /// Refresh an obligation's accrued interest and collateral and liquidity prices. Requires
     /// refreshed reserves, as all obligation collateral deposit reserves in order, followed by all
-    /// liquidity borrow reserves in order.
+    /// liquidity borrow reserves in order. An obligation that was already refreshed in the
+    /// current slot and not marked stale since is left unchanged.
+    ///
+    /// If the lending market allows conservative refreshes, the obligation owner can append the
+    /// lending market account and themselves as a signer after the reserves, to refresh with stale
+    /// deposit reserves, such as ones with a broken oracle. Their collateral is valued at zero,
+    /// which can leave the obligation unhealthy, and borrow reserves must still be refreshed.
+    /// The obligation can't be liquidated until it is refreshed with all its reserves fresh.
     ///
     /// Accounts expected by this instruction:
     ///
@@ -227,6 +245,12 @@ pub enum LendingInstruction {
     ///   7. `[signer]` Obligation owner.
     ///   8. `[]` Clock sysvar.
     ///   9. `[]` Token program id.
+    ///   10. `[]` Deposit reserve account - refreshed, only if the obligation's borrow from the
+    ///                     borrow reserve is bound to collateral, the reserve of that collateral.
+    ///   .. `[optional]` Obligation owner's stake account in the borrow reserve's fee
+    ///                     discount staking pool, for a discounted borrow fee.
+    ///   .. `[]` Instructions sysvar, last - required if the lending market enforces
+    ///             strict instruction order.
     BorrowObligationLiquidity {
         /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power
         liquidity_amount: u64,
@@ -277,6 +301,8 @@ pub enum LendingInstruction {
     ///   14 `[writable, optional]` Deposit stake account.
     ///   15 `[writable, optional]` Deposit staking pool.
     ///   16 `[optional]` staking program id.
+    ///   .. `[]` Instructions sysvar, last - required if the lending market enforces
+    ///             strict instruction order.
     LiquidateObligation {
         /// Amount of liquidity to repay - u64::MAX for up to 100% of borrowed amount
         liquidity_amount: u64,
@@ -324,14 +350,15 @@ pub enum LendingInstruction {
     },
 
     // 14
-    /// Combines DepositReserveLiquidity and DepositObligationCollateral
+    /// Combines DepositReserveLiquidity and DepositObligationCollateral. Requires a refreshed
+    /// reserve, it is not refreshed by this instruction.
     ///
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Source liquidity token account.
     ///                     $authority can transfer $liquidity_amount.
     ///   1. `[writable]` Destination collateral token account.
-    ///   2. `[writable]` Reserve account.
+    ///   2. `[writable]` Reserve account - refreshed.
     ///   3. `[writable]` Reserve liquidity supply SPL Token account.
     ///   4. `[writable]` Reserve collateral SPL Token mint.
     ///   5. `[]` Lending market account.
@@ -345,6 +372,10 @@ pub enum LendingInstruction {
     ///   13 `[writable, optional]` Stake account.
     ///   14 `[writable, optional]` Staking pool.
     ///   15 `[optional]` staking program id.
+    ///
+    /// The staking accounts are required if the reserve has a deposit staking pool and must be
+    /// omitted otherwise; all three are passed in or none are. The stake account must be in
+    /// the reserve's staking pool and owned by the obligation owner.
     DepositReserveLiquidityAndObligationCollateral {
         /// Amount of liquidity to deposit in exchange
         liquidity_amount: u64,
@@ -368,6 +399,8 @@ pub enum LendingInstruction {
 
     ///17
     /// Withdraw fee from a reserve.
+    /// Fees are charged in whole tokens straight into the reserve fee account, so its whole
+    /// balance is swept and no fractional remainder is kept on the reserve.
     /// Accounts expected by this instruction:
     ///
     ///   0. `[]` Reserve account
@@ -379,6 +412,431 @@ pub enum LendingInstruction {
     ///   4. `[]` Rent sysvar.
     ///   5. `[]` Token program id.
     WithdrawFee,
+
+    // 18
+    /// Accepts ownership of a lending market proposed by `SetLendingMarketOwner`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Pending owner.
+    AcceptLendingMarketOwner,
+
+    // 19
+    /// Sets whether borrows and liquidations in a lending market must be immediately preceded by
+    /// `RefreshReserve` for every obligation reserve, in order, followed by `RefreshObligation`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetStrictInstructionOrder {
+        /// Whether strict instruction order is enforced
+        enabled: bool,
+    },
+
+    // 20
+    /// Binds a borrow to a single deposit of the same obligation, so that only that collateral
+    /// backs it, or removes the binding to borrow against cross collateral again.
+    ///
+    /// Collateral with borrows bound to it no longer backs any other borrow, which may reduce the
+    /// obligation's borrowing power. Takes effect on the next `RefreshObligation`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[signer]` Obligation owner.
+    ///   2. `[]` Borrow reserve account.
+    ///   3. `[]` Deposit reserve account - optional, omit to remove the binding.
+    BindBorrowCollateral,
+
+    // 21
+    /// Reallocs an obligation created with the legacy layout to the current `Obligation::LEN`,
+    /// leaving its borrows unbound to any collateral reserve and `became_unhealthy_slot` at 0.
+    /// Anyone can migrate any obligation, and obligations already at the current length are left
+    /// untouched. The payer tops the obligation up to the rent exempt balance of the new length.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[signer, writable]` Payer.
+    ///   2. `[]` Rent sysvar.
+    ///   3. `[]` System program.
+    MigrateObligation,
+
+    // 22
+    /// Combines DepositReserveLiquidityAndObligationCollateral, RefreshObligation and
+    /// BorrowObligationLiquidity. Requires refreshed reserves.
+    ///
+    /// Deposit reserves with a staking pool are not supported. In lending markets that enforce
+    /// strict instruction order, it must be immediately preceded by `RefreshReserve` for every
+    /// obligation reserve passed to it, in order, as it refreshes the obligation itself.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Destination collateral token account.
+    ///   2. `[writable]` Deposit reserve account - refreshed.
+    ///   3. `[writable]` Deposit reserve liquidity supply SPL Token account.
+    ///   4. `[writable]` Deposit reserve collateral SPL Token mint.
+    ///   5. `[]` Lending market account.
+    ///   6. `[]` Derived lending market authority.
+    ///   7. `[writable]` Destination deposit reserve collateral supply SPL Token account.
+    ///   8. `[writable]` Obligation account.
+    ///   9. `[signer]` Obligation owner.
+    ///   10 `[signer]` User transfer authority ($authority).
+    ///   11 `[]` Clock sysvar.
+    ///   12 `[]` Token program id.
+    ///   13 `[writable]` Source borrow reserve liquidity supply SPL Token account.
+    ///   14 `[writable]` Destination liquidity token account.
+    ///   15 `[writable]` Borrow reserve account - refreshed.
+    ///   16 `[writable]` Borrow reserve liquidity fee receiver account.
+    ///   .. `[]` Obligation deposit reserves followed by obligation borrow reserves, including
+    ///             the deposit reserve, as they are before the borrow - all refreshed.
+    ///   .. `[]` Instructions sysvar, last - required if the lending market enforces
+    ///             strict instruction order.
+    DepositAndBorrow {
+        /// Amount of liquidity to deposit in exchange for collateral tokens
+        liquidity_amount: u64,
+        /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power
+        borrow_amount: u64,
+    },
+
+    // 23
+    /// Sets whether reserves in a lending market may use a liquidity mint with a freeze
+    /// authority, which could freeze the reserve liquidity supply.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetAllowFreezableMint {
+        /// Whether freezable liquidity mints are allowed
+        allowed: bool,
+    },
+
+    // 24
+    /// Replaces the fee receiver of a reserve, so that fees can be swept by `WithdrawFee` again
+    /// if the reserve was initialized with a fee receiver that isn't usable.
+    ///
+    /// The new fee receiver must be an SPL Token account of the reserve liquidity mint owned by
+    /// the derived lending market authority, other than the reserve liquidity supply.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner.
+    ///   3. `[]` New reserve liquidity fee receiver account - $new_receiver.
+    SetReserveFeeReceiver {
+        /// The new reserve liquidity fee receiver
+        new_receiver: Pubkey,
+    },
+
+    // 25
+    /// Moves the stake of an obligation's collateral in a reserve from the staking pool the reserve
+    /// used before `UpdateReserve` last changed it to the reserve's current deposit staking pool.
+    /// The whole balance of the previous stake account is moved.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` Obligation account.
+    ///   1. `[]` Deposit reserve account.
+    ///   2. `[]` Lending market account.
+    ///   3. `[]` Derived lending market authority.
+    ///   4. `[signer]` Obligation owner.
+    ///   5. `[]` Clock sysvar.
+    ///   6. `[writable]` Stake account in the previous staking pool.
+    ///   7. `[writable]` Previous staking pool - the reserve's `previous_deposit_staking_pool`.
+    ///   8. `[writable]` Stake account in the reserve deposit staking pool.
+    ///   9. `[writable]` Reserve deposit staking pool.
+    ///   10. `[]` Staking program id.
+    MigrateStakeAccount,
+
+    // 26
+    /// Unpauses a reserve that `RefreshReserve` paused because its oracle price was stale or
+    /// moved further than the reserve's maximum oracle deviation.
+    ///
+    /// The reserve is marked stale, and paused again by the next `RefreshReserve` if the oracle
+    /// price is still stale, or moves too far from the price that paused the reserve.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner.
+    UnpauseReserve,
+
+    // 27
+    /// Sets policy limits of a lending market.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetMarketLimits {
+        /// Maximum number of distinct deposit and borrow reserves an obligation may have, at
+        /// most `MAX_REFRESH_RESERVES` - 0 for that maximum. Obligations already over
+        /// the limit keep their reserves but can't add new ones
+        max_obligation_reserves: u8,
+    },
+
+    // 28
+    /// Donate liquidity to a reserve without minting collateral, raising the value of existing
+    /// collateral. The donation is added to the reserve's protocol owned amount, which only
+    /// reports the liquidity donated so far and does not hold it back from depositors. Requires a
+    /// refreshed reserve with collateral outstanding.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account, $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Reserve account - refreshed.
+    ///   3. `[]` Lending market account.
+    ///   4. `[signer]` User transfer authority ($authority).
+    ///   5. `[]` Clock sysvar.
+    ///   6. `[]` Token program id.
+    DonateReserveLiquidity {
+        /// Amount of liquidity to donate
+        liquidity_amount: u64,
+    },
+
+    // 29
+    /// Sets whether obligation owners in a lending market may refresh their obligations with
+    /// stale deposit reserves valued at zero.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetAllowConservativeRefresh {
+        /// Whether conservative obligation refreshes are allowed
+        allowed: bool,
+    },
+
+    // 30
+    /// Moves a percentage of every deposit and borrow of an obligation to another obligation of
+    /// the same owner, rounding the collateral moved down. Both obligations are refreshed with
+    /// the reserves provided, which must include every reserve of either obligation, and neither
+    /// may be left unhealthy.
+    ///
+    /// Collateral stays staked on the owner's behalf, so stake accounts are not needed.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source obligation account.
+    ///   1. `[writable]` Destination obligation account.
+    ///   2. `[]` Lending market account.
+    ///   3. `[signer]` Obligation owner.
+    ///   4. `[]` Clock sysvar.
+    ///   5. `[]` Deposit and borrow reserves of both obligations, refreshed, in any order.
+    SplitObligation {
+        /// Percentage of the source obligation to move, from 1 to 99
+        split_percent: u8,
+    },
+
+    // 31
+    /// Moves every deposit and borrow of an obligation to another obligation of the same owner,
+    /// leaving the source obligation empty. Both obligations are refreshed with the reserves
+    /// provided, and the destination may not be left unhealthy.
+    ///
+    /// Borrows from the same reserve must be bound to the same collateral.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source obligation account.
+    ///   1. `[writable]` Destination obligation account.
+    ///   2. `[]` Lending market account.
+    ///   3. `[signer]` Obligation owner.
+    ///   4. `[]` Clock sysvar.
+    ///   5. `[]` Deposit and borrow reserves of both obligations, refreshed, in any order.
+    MergeObligation,
+
+    // 32
+    /// Sets how many slots an obligation must have been unhealthy for before it can be
+    /// liquidated.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetLiquidationGraceSlots {
+        /// Slots an obligation must have been unhealthy for, 0 to allow immediate liquidation
+        liquidation_grace_slots: u64,
+    },
+
+    // 33
+    /// Sets the percentage of a reserve's borrow and flash loan fees paid to the host fee
+    /// receiver, leaving the rest of the reserve config unchanged.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner.
+    SetHostFee {
+        /// Host fee percentage, from 0 to 100
+        percentage: u8,
+    },
+
+    // 34
+    /// Initializes a new lending market reserve at the address derived from the lending market
+    /// and the reserve liquidity mint, see `find_reserve_address`, so a lending market has at
+    /// most one such reserve per liquidity mint. The reserve account is created by the program.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account, $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Destination collateral token account - uninitialized.
+    ///   2. `[writable]` Reserve account - derived, not yet created.
+    ///   3. `[]` Reserve liquidity SPL Token mint.
+    ///   4. `[writable]` Reserve liquidity supply SPL Token account - uninitialized.
+    ///   5. `[writable]` Reserve liquidity fee receiver - uninitialized.
+    ///   6. `[writable]` Reserve collateral SPL Token mint - uninitialized.
+    ///   7. `[writable]` Reserve collateral token supply - uninitialized.
+    ///   8. `[]` Lending market account.
+    ///   9. `[]` Derived lending market authority.
+    ///   10. `[signer]` Lending market owner.
+    ///   11. `[signer]` User transfer authority ($authority).
+    ///   12. `[]` Clock sysvar.
+    ///   13. `[]` Rent sysvar.
+    ///   14. `[]` Token program id.
+    ///   15. `[writable, signer]` Funder of the reserve account rent.
+    ///   16. `[]` System program id.
+    ///   17. `[optional]` Reserve liquidity oracle price account, pyth or switchboard.
+    InitReservePda {
+        /// Initial amount of liquidity to deposit into the new reserve
+        liquidity_amount: u64,
+        /// Fixed the price for this reserve
+        fixed_price: COption<Decimal>,
+        /// Reserve configuration values
+        config: ReserveConfig,
+    },
+
+    // 35
+    /// Sets the smallest value a liquidation must repay, unless it fully closes the repaid borrow
+    /// or seizes all of the withdrawn collateral.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetMinLiquidationValue {
+        /// Minimum repaid value in the market's quote currency, 0 for no minimum
+        min_liquidation_value: Decimal,
+    },
+
+    // 36
+    /// Combines InitObligation and DepositAndBorrow to open a position from a fresh obligation
+    /// account, with a single deposit reserve and a single borrow reserve. Requires refreshed
+    /// reserves. In lending markets that enforce strict instruction order, it must be immediately
+    /// preceded by `RefreshReserve` for the deposit reserve.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///      $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Destination collateral token account.
+    ///   2. `[writable]` Deposit reserve account - refreshed.
+    ///   3. `[writable]` Deposit reserve liquidity supply SPL Token account.
+    ///   4. `[writable]` Deposit reserve collateral SPL Token mint.
+    ///   5. `[]` Lending market account.
+    ///   6. `[]` Derived lending market authority.
+    ///   7. `[writable]` Destination deposit reserve collateral supply SPL Token account.
+    ///   8. `[writable]` Obligation account - uninitialized.
+    ///   9. `[signer]` Obligation owner.
+    ///   10. `[signer]` User transfer authority ($authority).
+    ///   11. `[]` Clock sysvar.
+    ///   12. `[]` Token program id.
+    ///   13. `[writable]` Source borrow reserve liquidity supply SPL Token account.
+    ///   14. `[writable]` Destination liquidity token account.
+    ///   15. `[writable]` Borrow reserve account - refreshed.
+    ///   16. `[writable]` Borrow reserve liquidity fee receiver account.
+    ///   17. `[]` Rent sysvar.
+    ///   18. `[]` Instructions sysvar - required if the lending market enforces strict
+    ///             instruction order.
+    BootstrapObligation {
+        /// Amount of liquidity to deposit in exchange for collateral tokens
+        liquidity_amount: u64,
+        /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power
+        borrow_amount: u64,
+    },
+
+    // 37
+    /// Reallocs a reserve created with the legacy layout to the current `Reserve::LEN`, leaving
+    /// the fields appended since at their defaults. Anyone can migrate any reserve, and reserves
+    /// already at the current length are left untouched. The payer tops the reserve up to the
+    /// rent exempt balance of the new length.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[signer, writable]` Payer.
+    ///   2. `[]` Rent sysvar.
+    ///   3. `[]` System program.
+    MigrateReserve,
+
+    // 38
+    /// Sets the estimate of slots per year the market's rates are annualized with for display.
+    /// Interest keeps accruing per slot, so this doesn't change what borrowers owe.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetSlotsPerYear {
+        /// Estimated slots per year, 0 for `DEFAULT_SLOTS_PER_YEAR`, or between
+        /// `MIN_SLOTS_PER_YEAR` and `MAX_SLOTS_PER_YEAR`
+        slots_per_year: u64,
+    },
+
+    // 39
+    /// Combines WithdrawObligationCollateral and RedeemReserveCollateral, so a collateral
+    /// position is exited in one step without a collateral balance left behind. Requires a
+    /// refreshed obligation and reserve.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source withdraw reserve collateral supply SPL Token account.
+    ///   1. `[writable]` User collateral token account, the obligation owner can burn from it.
+    ///   2. `[writable]` Destination liquidity token account.
+    ///   3. `[writable]` Withdraw reserve account - refreshed.
+    ///   4. `[writable]` Reserve collateral SPL Token mint.
+    ///   5. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   6. `[writable]` Obligation account - refreshed.
+    ///   7. `[]` Lending market account.
+    ///   8. `[]` Derived lending market authority.
+    ///   9. `[signer]` Obligation owner.
+    ///   10. `[]` Clock sysvar.
+    ///   11. `[]` Token program id.
+    ///   12. `[writable, optional]` Stake account.
+    ///   13. `[writable, optional]` Staking pool.
+    ///   14. `[optional]` staking program id.
+    ///
+    /// The staking accounts are required if the reserve has a deposit staking pool and must be
+    /// omitted otherwise. The withdrawn collateral is unstaked from the stake account. It only
+    /// passes through the user collateral account, all of it is redeemed.
+    WithdrawObligationCollateralAndRedeem {
+        /// Amount of collateral tokens to withdraw and redeem - u64::MAX for up to 100% of
+        /// deposited amount
+        collateral_amount: u64,
+    },
+
+    // 40
+    /// Sets whether a reserve is winding down. A reserve winding down blocks deposits and
+    /// borrows, while repayments, redemptions, collateral withdrawals and liquidations go on, so
+    /// it can be drained before it is retired. Unlike a pause it is only lifted by the owner.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner.
+    SetReserveWindDown {
+        /// Whether the reserve is winding down
+        wind_down: bool,
+    },
 }
 
 impl LendingInstruction {
@@ -454,6 +912,97 @@ impl LendingInstruction {
                 Self::UpdateReserve { config }
             }
             17 => Self::WithdrawFee,
+            18 => Self::AcceptLendingMarketOwner,
+            19 => {
+                let (enabled, _rest) = Self::unpack_bool(rest)?;
+                Self::SetStrictInstructionOrder { enabled }
+            }
+            20 => Self::BindBorrowCollateral,
+            21 => Self::MigrateObligation,
+            22 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (borrow_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositAndBorrow {
+                    liquidity_amount,
+                    borrow_amount,
+                }
+            }
+            23 => {
+                let (allowed, _rest) = Self::unpack_bool(rest)?;
+                Self::SetAllowFreezableMint { allowed }
+            }
+            24 => {
+                let (new_receiver, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetReserveFeeReceiver { new_receiver }
+            }
+            25 => Self::MigrateStakeAccount,
+            26 => Self::UnpauseReserve,
+            27 => {
+                let (max_obligation_reserves, _rest) = Self::unpack_u8(rest)?;
+                Self::SetMarketLimits {
+                    max_obligation_reserves,
+                }
+            }
+            28 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DonateReserveLiquidity { liquidity_amount }
+            }
+            29 => {
+                let (allowed, _rest) = Self::unpack_bool(rest)?;
+                Self::SetAllowConservativeRefresh { allowed }
+            }
+            30 => {
+                let (split_percent, _rest) = Self::unpack_u8(rest)?;
+                Self::SplitObligation { split_percent }
+            }
+            31 => Self::MergeObligation,
+            32 => {
+                let (liquidation_grace_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::SetLiquidationGraceSlots {
+                    liquidation_grace_slots,
+                }
+            }
+            33 => {
+                let (percentage, _rest) = Self::unpack_u8(rest)?;
+                Self::SetHostFee { percentage }
+            }
+            34 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (fixed_price, rest) = Self::unpack_coption_decimal(rest)?;
+                let (config, _rest) = Self::unpack_reserve_config(rest)?;
+                Self::InitReservePda {
+                    liquidity_amount,
+                    fixed_price,
+                    config,
+                }
+            }
+            35 => {
+                let (min_liquidation_value, _rest) = Self::unpack_u128(rest)?;
+                Self::SetMinLiquidationValue {
+                    min_liquidation_value: Decimal::from_scaled_val(min_liquidation_value),
+                }
+            }
+            36 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (borrow_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::BootstrapObligation {
+                    liquidity_amount,
+                    borrow_amount,
+                }
+            }
+            37 => Self::MigrateReserve,
+            38 => {
+                let (slots_per_year, _rest) = Self::unpack_u64(rest)?;
+                Self::SetSlotsPerYear { slots_per_year }
+            }
+            39 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawObligationCollateralAndRedeem { collateral_amount }
+            }
+            40 => {
+                let (wind_down, _rest) = Self::unpack_bool(rest)?;
+                Self::SetReserveWindDown { wind_down }
+            }
             _ => {
                 msg!("Instruction cannot be unpacked");
                 return Err(LendingError::InstructionUnpackError.into());
@@ -475,6 +1024,47 @@ impl LendingInstruction {
         Ok((value, rest))
     }
 
+    fn unpack_u128(input: &[u8]) -> Result<(u128, &[u8]), ProgramError> {
+        if input.len() < 16 {
+            msg!("u128 cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(16);
+        let value = bytes
+            .get(..16)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u128::from_le_bytes)
+            .ok_or(LendingError::InstructionUnpackError)?;
+        Ok((value, rest))
+    }
+
+    fn unpack_bool(input: &[u8]) -> Result<(bool, &[u8]), ProgramError> {
+        let (value, rest) = Self::unpack_u8(input)?;
+        let value = match value {
+            0 => false,
+            1 => true,
+            _ => {
+                msg!("Boolean cannot be unpacked");
+                return Err(LendingError::InstructionUnpackError.into());
+            }
+        };
+        Ok((value, rest))
+    }
+
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        if input.len() < 2 {
+            msg!("u16 cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(2);
+        let value = bytes
+            .get(..2)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u16::from_le_bytes)
+            .ok_or(LendingError::InstructionUnpackError)?;
+        Ok((value, rest))
+    }
+
     fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
         if input.is_empty() {
             msg!("u8 cannot be unpacked");
@@ -523,14 +1113,17 @@ impl LendingInstruction {
             return Err(LendingError::InstructionUnpackError.into());
         }
         let (coption_decimal, rest) = input.split_at(20);
-        let (tag, decimal) = array_refs![coption_decimal.try_into().unwrap(), 4, 16];
+        let coption_decimal: &[u8; 20] = coption_decimal
+            .try_into()
+            .map_err(|_| LendingError::InstructionUnpackError)?;
         #[allow(clippy::ptr_offset_with_cast)]
+        let (tag, decimal) = array_refs![coption_decimal, 4, 16];
         match *tag {
             [0, 0, 0, 0] => Ok((COption::None, rest)),
             [1, 0, 0, 0] => Ok((COption::Some(Self::unpack_decimal(decimal)), rest)),
             _ => {
-                msg!("COption<Decimal> cannot be unpacked");
-                Err(ProgramError::InvalidAccountData)
+                msg!("COption<Decimal> tag is invalid");
+                Err(LendingError::InstructionUnpackError.into())
             }
         }
     }
@@ -558,6 +1151,22 @@ impl LendingInstruction {
         let (flash_loan_fee_wad, rest) = Self::unpack_u64(rest)?;
         let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
         let (deposit_staking_pool, rest) = Self::unpack_coption_key_compact(rest)?;
+        let (use_twap, rest) = Self::unpack_bool(rest)?;
+        let (twap_window_slots, rest) = Self::unpack_u64(rest)?;
+        let (borrow_fee_discount_staking_pool, rest) = Self::unpack_coption_key_compact(rest)?;
+        let (borrow_fee_discount_percentage, rest) = Self::unpack_u8(rest)?;
+        let (max_accrual_slots, rest) = Self::unpack_u64(rest)?;
+        let (borrowing_disabled, rest) = Self::unpack_bool(rest)?;
+        let (liquidation_bonus_ramp_slots, rest) = Self::unpack_u64(rest)?;
+        let (min_initial_deposit, rest) = Self::unpack_u64(rest)?;
+        let (max_price_staleness_slots, rest) = Self::unpack_u64(rest)?;
+        let (max_slot_liquidity_change_percent, rest) = Self::unpack_u8(rest)?;
+        let (max_oracle_deviation_bps, rest) = Self::unpack_u16(rest)?;
+        let (isolated_debt_ceiling, rest) = Self::unpack_u64(rest)?;
+        let (collateral_disabled, rest) = Self::unpack_bool(rest)?;
+        let (max_liquidation_bonus, rest) = Self::unpack_u8(rest)?;
+        let (borrow_fee_discount_min_stake, rest) = Self::unpack_u64(rest)?;
+        let (min_liquidation_bonus, rest) = Self::unpack_u8(rest)?;
         Ok((
             ReserveConfig {
                 optimal_utilization_rate,
@@ -573,6 +1182,22 @@ impl LendingInstruction {
                     host_fee_percentage,
                 },
                 deposit_staking_pool,
+                use_twap,
+                twap_window_slots,
+                borrow_fee_discount_staking_pool,
+                borrow_fee_discount_percentage,
+                max_accrual_slots,
+                borrowing_disabled,
+                liquidation_bonus_ramp_slots,
+                min_initial_deposit,
+                max_price_staleness_slots,
+                max_slot_liquidity_change_percent,
+                max_oracle_deviation_bps,
+                isolated_debt_ceiling,
+                collateral_disabled,
+                max_liquidation_bonus,
+                borrow_fee_discount_min_stake,
+                min_liquidation_bonus,
             },
             rest,
         ))
@@ -656,22 +1281,132 @@ impl LendingInstruction {
             Self::WithdrawFee => {
                 buf.push(17);
             }
-        }
-        buf
-    }
-
-    fn pack_coption_decimal(buf: &mut Vec<u8>, coption_decimal: COption<Decimal>) {
-        match coption_decimal {
-            COption::None => {
-                buf.extend_from_slice(&[0u8, 0u8, 0u8, 0u8]);
-                buf.extend_from_slice(
-                    &Decimal::zero()
-                        .to_scaled_val()
-                        .expect("Decimal cannot be packed")
-                        .to_le_bytes(),
-                );
+            Self::AcceptLendingMarketOwner => {
+                buf.push(18);
             }
-            COption::Some(decimal) => {
+            Self::SetStrictInstructionOrder { enabled } => {
+                buf.push(19);
+                buf.push(enabled as u8);
+            }
+            Self::BindBorrowCollateral => {
+                buf.push(20);
+            }
+            Self::MigrateObligation => {
+                buf.push(21);
+            }
+            Self::DepositAndBorrow {
+                liquidity_amount,
+                borrow_amount,
+            } => {
+                buf.push(22);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&borrow_amount.to_le_bytes());
+            }
+            Self::SetAllowFreezableMint { allowed } => {
+                buf.push(23);
+                buf.push(allowed as u8);
+            }
+            Self::SetReserveFeeReceiver { new_receiver } => {
+                buf.push(24);
+                buf.extend_from_slice(new_receiver.as_ref());
+            }
+            Self::MigrateStakeAccount => {
+                buf.push(25);
+            }
+            Self::UnpauseReserve => {
+                buf.push(26);
+            }
+            Self::SetMarketLimits {
+                max_obligation_reserves,
+            } => {
+                buf.push(27);
+                buf.push(max_obligation_reserves);
+            }
+            Self::DonateReserveLiquidity { liquidity_amount } => {
+                buf.push(28);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::SetAllowConservativeRefresh { allowed } => {
+                buf.push(29);
+                buf.push(allowed as u8);
+            }
+            Self::SplitObligation { split_percent } => {
+                buf.push(30);
+                buf.push(split_percent);
+            }
+            Self::MergeObligation => {
+                buf.push(31);
+            }
+            Self::SetLiquidationGraceSlots {
+                liquidation_grace_slots,
+            } => {
+                buf.push(32);
+                buf.extend_from_slice(&liquidation_grace_slots.to_le_bytes());
+            }
+            Self::SetHostFee { percentage } => {
+                buf.push(33);
+                buf.push(percentage);
+            }
+            Self::InitReservePda {
+                liquidity_amount,
+                fixed_price,
+                config,
+            } => {
+                buf.push(34);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                Self::pack_coption_decimal(&mut buf, fixed_price);
+                Self::pack_reserve_config(&mut buf, config);
+            }
+            Self::SetMinLiquidationValue {
+                min_liquidation_value,
+            } => {
+                buf.push(35);
+                buf.extend_from_slice(
+                    &min_liquidation_value
+                        .to_scaled_val()
+                        .expect("Decimal cannot be packed")
+                        .to_le_bytes(),
+                );
+            }
+            Self::BootstrapObligation {
+                liquidity_amount,
+                borrow_amount,
+            } => {
+                buf.push(36);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&borrow_amount.to_le_bytes());
+            }
+            Self::MigrateReserve => {
+                buf.push(37);
+            }
+            Self::SetSlotsPerYear { slots_per_year } => {
+                buf.push(38);
+                buf.extend_from_slice(&slots_per_year.to_le_bytes());
+            }
+            Self::WithdrawObligationCollateralAndRedeem { collateral_amount } => {
+                buf.push(39);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::SetReserveWindDown { wind_down } => {
+                buf.push(40);
+                buf.push(wind_down as u8);
+            }
+        }
+        buf
+    }
+
+    fn pack_coption_decimal(buf: &mut Vec<u8>, coption_decimal: COption<Decimal>) {
+        match coption_decimal {
+            COption::None => {
+                buf.extend_from_slice(&[0u8, 0u8, 0u8, 0u8]);
+                buf.extend_from_slice(
+                    &Decimal::zero()
+                        .to_scaled_val()
+                        .expect("Decimal cannot be packed")
+                        .to_le_bytes(),
+                );
+            }
+            COption::Some(decimal) => {
                 buf.extend_from_slice(&[1u8, 0u8, 0u8, 0u8]);
                 buf.extend_from_slice(
                     &decimal
@@ -699,6 +1434,22 @@ impl LendingInstruction {
                     host_fee_percentage,
                 },
             deposit_staking_pool,
+            use_twap,
+            twap_window_slots,
+            borrow_fee_discount_staking_pool,
+            borrow_fee_discount_percentage,
+            max_accrual_slots,
+            borrowing_disabled,
+            liquidation_bonus_ramp_slots,
+            min_initial_deposit,
+            max_price_staleness_slots,
+            max_slot_liquidity_change_percent,
+            max_oracle_deviation_bps,
+            isolated_debt_ceiling,
+            collateral_disabled,
+            max_liquidation_bonus,
+            borrow_fee_discount_min_stake,
+            min_liquidation_bonus,
         } = reserve_config;
         buf.extend_from_slice(&optimal_utilization_rate.to_le_bytes());
         buf.extend_from_slice(&loan_to_value_ratio.to_le_bytes());
@@ -713,6 +1464,23 @@ impl LendingInstruction {
         let mut coption_key_buf = [0u8; 33];
         pack_coption_key_compact(&deposit_staking_pool, &mut coption_key_buf);
         buf.extend_from_slice(&coption_key_buf);
+        buf.push(use_twap as u8);
+        buf.extend_from_slice(&twap_window_slots.to_le_bytes());
+        pack_coption_key_compact(&borrow_fee_discount_staking_pool, &mut coption_key_buf);
+        buf.extend_from_slice(&coption_key_buf);
+        buf.extend_from_slice(&borrow_fee_discount_percentage.to_le_bytes());
+        buf.extend_from_slice(&max_accrual_slots.to_le_bytes());
+        buf.push(borrowing_disabled as u8);
+        buf.extend_from_slice(&liquidation_bonus_ramp_slots.to_le_bytes());
+        buf.extend_from_slice(&min_initial_deposit.to_le_bytes());
+        buf.extend_from_slice(&max_price_staleness_slots.to_le_bytes());
+        buf.extend_from_slice(&max_slot_liquidity_change_percent.to_le_bytes());
+        buf.extend_from_slice(&max_oracle_deviation_bps.to_le_bytes());
+        buf.extend_from_slice(&isolated_debt_ceiling.to_le_bytes());
+        buf.push(collateral_disabled as u8);
+        buf.extend_from_slice(&max_liquidation_bonus.to_le_bytes());
+        buf.extend_from_slice(&borrow_fee_discount_min_stake.to_le_bytes());
+        buf.extend_from_slice(&min_liquidation_bonus.to_le_bytes());
     }
 }
 
@@ -755,6 +1523,73 @@ pub fn set_lending_market_owner(
     }
 }
 
+/// Creates an 'AcceptLendingMarketOwner' instruction.
+pub fn accept_lending_market_owner(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    pending_owner: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(pending_owner, true),
+        ],
+        data: LendingInstruction::AcceptLendingMarketOwner.pack(),
+    }
+}
+
+/// Creates a 'SetStrictInstructionOrder' instruction.
+pub fn set_strict_instruction_order(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    enabled: bool,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetStrictInstructionOrder { enabled }.pack(),
+    }
+}
+
+/// Creates a 'SetAllowFreezableMint' instruction.
+pub fn set_allow_freezable_mint(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    allowed: bool,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetAllowFreezableMint { allowed }.pack(),
+    }
+}
+
+/// Creates a 'SetAllowConservativeRefresh' instruction.
+pub fn set_allow_conservative_refresh(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    allowed: bool,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetAllowConservativeRefresh { allowed }.pack(),
+    }
+}
+
 /// Creates an 'InitReserve' instruction.
 #[allow(clippy::too_many_arguments)]
 pub fn init_reserve(
@@ -811,6 +1646,286 @@ pub fn init_reserve(
     }
 }
 
+/// Creates an `InitReservePda` instruction for the reserve at `find_reserve_address`
+#[allow(clippy::too_many_arguments)]
+pub fn init_reserve_pda(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    fixed_price: COption<Decimal>,
+    config: ReserveConfig,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_collateral_supply_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+    funder_pubkey: Pubkey,
+    coption_pyth_price_pubkey: COption<Pubkey>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let (reserve_pubkey, _bump_seed) = find_reserve_address(
+        &program_id,
+        &lending_market_pubkey,
+        &reserve_liquidity_mint_pubkey,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_collateral_pubkey, false),
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new_readonly(reserve_liquidity_mint_pubkey, false),
+        AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(reserve_collateral_supply_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(funder_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if let COption::Some(pyth_price_pubkey) = coption_pyth_price_pubkey {
+        accounts.push(AccountMeta::new_readonly(pyth_price_pubkey, false))
+    }
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::InitReservePda {
+            liquidity_amount,
+            fixed_price,
+            config,
+        }
+        .pack(),
+    }
+}
+
+/// Accounts `build_init_reserve_transaction` creates for a new reserve. The caller generates a
+/// keypair for each, and every keypair signs the transaction that creates its account.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InitReserveAccounts {
+    /// Reserve account, owned by the lending program
+    pub reserve: Pubkey,
+    /// Reserve collateral mint
+    pub collateral_mint: Pubkey,
+    /// Reserve collateral supply token account
+    pub collateral_supply: Pubkey,
+    /// Reserve liquidity supply token account
+    pub liquidity_supply: Pubkey,
+    /// Reserve liquidity fee receiver token account
+    pub liquidity_fee_receiver: Pubkey,
+    /// Token account receiving the collateral minted for the initial liquidity
+    pub user_collateral: Pubkey,
+    /// Authority approved to transfer the initial liquidity, signs the `InitReserve` instruction
+    pub user_transfer_authority: Pubkey,
+}
+
+/// Creates the instructions that add a reserve to a lending market, split into the
+/// transactions they must be sent in, in order:
+///
+///   1. Create the reserve, collateral mint, collateral supply and user collateral accounts.
+///      Signed by the payer and the new reserve, collateral mint, collateral supply and user
+///      collateral accounts.
+///   2. Create the liquidity supply and fee receiver accounts, approve the user transfer
+///      authority for `liquidity_amount`, `InitReserve`, then revoke the approval. Signed by
+///      the payer, the new liquidity supply and fee receiver accounts, the source liquidity
+///      owner, the lending market owner and the user transfer authority.
+///
+/// New accounts are funded by the payer with the rent-exempt minimum under `rent`.
+#[allow(clippy::too_many_arguments)]
+pub fn build_init_reserve_transaction(
+    program_id: Pubkey,
+    payer_pubkey: Pubkey,
+    rent: &Rent,
+    liquidity_amount: u64,
+    fixed_price: COption<Decimal>,
+    config: ReserveConfig,
+    source_liquidity_pubkey: Pubkey,
+    source_liquidity_owner_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    coption_pyth_price_pubkey: COption<Pubkey>,
+    accounts: &InitReserveAccounts,
+) -> Result<Vec<Vec<Instruction>>, ProgramError> {
+    let create_token_account = |pubkey: &Pubkey, space: usize| {
+        system_instruction::create_account(
+            &payer_pubkey,
+            pubkey,
+            rent.minimum_balance(space),
+            space as u64,
+            &spl_token::id(),
+        )
+    };
+
+    let create_accounts = vec![
+        system_instruction::create_account(
+            &payer_pubkey,
+            &accounts.reserve,
+            rent.minimum_balance(Reserve::LEN),
+            Reserve::LEN as u64,
+            &program_id,
+        ),
+        create_token_account(&accounts.collateral_mint, Mint::LEN),
+        create_token_account(&accounts.collateral_supply, Token::LEN),
+        create_token_account(&accounts.user_collateral, Token::LEN),
+    ];
+
+    let init_reserve = vec![
+        create_token_account(&accounts.liquidity_supply, Token::LEN),
+        create_token_account(&accounts.liquidity_fee_receiver, Token::LEN),
+        approve(
+            &spl_token::id(),
+            &source_liquidity_pubkey,
+            &accounts.user_transfer_authority,
+            &source_liquidity_owner_pubkey,
+            &[],
+            liquidity_amount,
+        )?,
+        init_reserve(
+            program_id,
+            liquidity_amount,
+            fixed_price,
+            config,
+            source_liquidity_pubkey,
+            accounts.user_collateral,
+            accounts.reserve,
+            reserve_liquidity_mint_pubkey,
+            accounts.liquidity_supply,
+            accounts.liquidity_fee_receiver,
+            accounts.collateral_mint,
+            accounts.collateral_supply,
+            lending_market_pubkey,
+            lending_market_owner_pubkey,
+            accounts.user_transfer_authority,
+            coption_pyth_price_pubkey,
+        ),
+        revoke(
+            &spl_token::id(),
+            &source_liquidity_pubkey,
+            &source_liquidity_owner_pubkey,
+            &[],
+        )?,
+    ];
+
+    Ok(vec![create_accounts, init_reserve])
+}
+
+/// Accounts `build_local_market_transactions` creates for a development lending market with a
+/// single reserve. The caller generates a keypair for each.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LocalMarketAccounts {
+    /// Liquidity mint, with the payer as mint authority
+    pub liquidity_mint: Pubkey,
+    /// Payer's token account the liquidity is minted to and the reserve liquidity taken from
+    pub user_liquidity: Pubkey,
+    /// Lending market, owned by the payer
+    pub lending_market: Pubkey,
+    /// Accounts of the reserve added to the lending market
+    pub reserve: InitReserveAccounts,
+}
+
+/// Creates the instructions that stand up a lending market for local development, split into
+/// the transactions they must be sent in, in order:
+///
+///   1. Create the liquidity mint and the payer's liquidity token account, mint
+///      `mint_amount` to it, then create the lending market owned by the payer. Signed by the
+///      payer and the new liquidity mint, user liquidity and lending market accounts.
+///   2. and 3. The transactions of `build_init_reserve_transaction`, adding a reserve of
+///      `liquidity_amount` of the minted liquidity at `fixed_price`, with the payer as source
+///      liquidity owner and lending market owner.
+#[allow(clippy::too_many_arguments)]
+pub fn build_local_market_transactions(
+    program_id: Pubkey,
+    payer_pubkey: Pubkey,
+    rent: &Rent,
+    quote_currency: [u8; 32],
+    liquidity_mint_decimals: u8,
+    mint_amount: u64,
+    liquidity_amount: u64,
+    fixed_price: Decimal,
+    config: ReserveConfig,
+    accounts: &LocalMarketAccounts,
+) -> Result<Vec<Vec<Instruction>>, ProgramError> {
+    let create_market = vec![
+        system_instruction::create_account(
+            &payer_pubkey,
+            &accounts.liquidity_mint,
+            rent.minimum_balance(Mint::LEN),
+            Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        initialize_mint(
+            &spl_token::id(),
+            &accounts.liquidity_mint,
+            &payer_pubkey,
+            None,
+            liquidity_mint_decimals,
+        )?,
+        system_instruction::create_account(
+            &payer_pubkey,
+            &accounts.user_liquidity,
+            rent.minimum_balance(Token::LEN),
+            Token::LEN as u64,
+            &spl_token::id(),
+        ),
+        initialize_account(
+            &spl_token::id(),
+            &accounts.user_liquidity,
+            &accounts.liquidity_mint,
+            &payer_pubkey,
+        )?,
+        mint_to(
+            &spl_token::id(),
+            &accounts.liquidity_mint,
+            &accounts.user_liquidity,
+            &payer_pubkey,
+            &[],
+            mint_amount,
+        )?,
+        system_instruction::create_account(
+            &payer_pubkey,
+            &accounts.lending_market,
+            rent.minimum_balance(LendingMarket::LEN),
+            LendingMarket::LEN as u64,
+            &program_id,
+        ),
+        init_lending_market(
+            program_id,
+            payer_pubkey,
+            quote_currency,
+            accounts.lending_market,
+        ),
+    ];
+
+    let mut transactions = vec![create_market];
+    transactions.extend(build_init_reserve_transaction(
+        program_id,
+        payer_pubkey,
+        rent,
+        liquidity_amount,
+        COption::Some(fixed_price),
+        config,
+        accounts.user_liquidity,
+        payer_pubkey,
+        accounts.liquidity_mint,
+        accounts.lending_market,
+        payer_pubkey,
+        COption::None,
+        &accounts.reserve,
+    )?);
+    Ok(transactions)
+}
+
 /// Creates a `RefreshReserve` instruction
 pub fn refresh_reserve(
     program_id: Pubkey,
@@ -949,6 +2064,65 @@ pub fn refresh_obligation(
     }
 }
 
+/// Creates a `RefreshObligation` instruction that values stale deposit reserves at zero.
+pub fn refresh_obligation_conservative(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    reserve_pubkeys: Vec<Pubkey>,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+) -> Instruction {
+    let mut instruction = refresh_obligation(program_id, obligation_pubkey, reserve_pubkeys);
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+    ]);
+    instruction
+}
+
+/// Creates the `RefreshReserve` instructions for every reserve of an obligation, each reserve
+/// once, followed by the `RefreshObligation` instruction with its reserves in the order the
+/// processor expects: deposit reserves, then borrow reserves.
+///
+/// `reserve_oracles` maps each reserve to its liquidity oracle. Reserves without an entry are
+/// refreshed without an oracle, like reserves with a fixed price.
+pub fn build_refresh_transaction(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation: &Obligation,
+    reserve_oracles: &HashMap<Pubkey, COption<Pubkey>>,
+) -> Vec<Instruction> {
+    let reserve_pubkeys: Vec<Pubkey> = obligation
+        .deposits
+        .iter()
+        .map(|collateral| collateral.deposit_reserve)
+        .chain(
+            obligation
+                .borrows
+                .iter()
+                .map(|liquidity| liquidity.borrow_reserve),
+        )
+        .collect();
+
+    let mut instructions: Vec<Instruction> = Vec::with_capacity(reserve_pubkeys.len() + 1);
+    for (index, reserve_pubkey) in reserve_pubkeys.iter().enumerate() {
+        if reserve_pubkeys[..index].contains(reserve_pubkey) {
+            continue;
+        }
+        let oracle_pubkey = reserve_oracles
+            .get(reserve_pubkey)
+            .cloned()
+            .unwrap_or(COption::None);
+        instructions.push(refresh_reserve(program_id, *reserve_pubkey, oracle_pubkey));
+    }
+    instructions.push(refresh_obligation(
+        program_id,
+        obligation_pubkey,
+        reserve_pubkeys,
+    ));
+    instructions
+}
+
 /// Creates a `DepositObligationCollateral` instruction.
 #[allow(clippy::too_many_arguments)]
 pub fn deposit_obligation_collateral(
@@ -1051,12 +2225,14 @@ pub fn borrow_obligation_liquidity(
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     obligation_owner_pubkey: Pubkey,
+    option_bound_deposit_reserve_pubkey: Option<Pubkey>,
+    option_stake_account_pubkey: Option<Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
     );
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new(source_liquidity_pubkey, false),
         AccountMeta::new(destination_liquidity_pubkey, false),
         AccountMeta::new(borrow_reserve_pubkey, false),
@@ -1068,6 +2244,16 @@ pub fn borrow_obligation_liquidity(
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
     ];
+    if let Some(bound_deposit_reserve_pubkey) = option_bound_deposit_reserve_pubkey {
+        accounts.push(AccountMeta::new_readonly(
+            bound_deposit_reserve_pubkey,
+            false,
+        ));
+    }
+    if let Some(stake_account_pubkey) = option_stake_account_pubkey {
+        accounts.push(AccountMeta::new_readonly(stake_account_pubkey, false));
+    }
+    accounts.push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
 
     Instruction {
         program_id,
@@ -1076,6 +2262,47 @@ pub fn borrow_obligation_liquidity(
     }
 }
 
+/// Creates a `BindBorrowCollateral` instruction.
+pub fn bind_borrow_collateral(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    deposit_reserve_pubkey: COption<Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(borrow_reserve_pubkey, false),
+    ];
+    if let COption::Some(deposit_reserve_pubkey) = deposit_reserve_pubkey {
+        accounts.push(AccountMeta::new_readonly(deposit_reserve_pubkey, false));
+    }
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::BindBorrowCollateral.pack(),
+    }
+}
+
+/// Creates a `MigrateObligation` instruction.
+pub fn migrate_obligation(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    payer_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(payer_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: LendingInstruction::MigrateObligation.pack(),
+    }
+}
+
 /// Creates a `RepayObligationLiquidity` instruction
 #[allow(clippy::too_many_arguments)]
 pub fn repay_obligation_liquidity(
@@ -1148,6 +2375,7 @@ pub fn liquidate_obligation(
         accounts.push(AccountMeta::new(staking_pool_pubkey, false));
         accounts.push(AccountMeta::new_readonly(port_finance_staking::id(), false));
     }
+    accounts.push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
     Instruction {
         program_id,
         accounts,
@@ -1246,6 +2474,142 @@ pub fn deposit_reserve_liquidity_and_obligation_collateral(
     }
 }
 
+/// Creates a `DepositAndBorrow` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_and_borrow(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    borrow_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    user_collateral_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    deposit_reserve_liquidity_supply_pubkey: Pubkey,
+    deposit_reserve_collateral_mint_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    destination_deposit_collateral_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+    borrow_reserve_liquidity_supply_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    obligation_reserve_pubkeys: Vec<Pubkey>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(user_collateral_pubkey, false),
+        AccountMeta::new(deposit_reserve_pubkey, false),
+        AccountMeta::new(deposit_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(deposit_reserve_collateral_mint_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new(destination_deposit_collateral_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(borrow_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(borrow_reserve_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
+    ];
+    accounts.extend(
+        obligation_reserve_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
+    accounts.push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::DepositAndBorrow {
+            liquidity_amount,
+            borrow_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `BootstrapObligation` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn bootstrap_obligation(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    borrow_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    user_collateral_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    deposit_reserve_liquidity_supply_pubkey: Pubkey,
+    deposit_reserve_collateral_mint_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    destination_deposit_collateral_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+    borrow_reserve_liquidity_supply_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(user_collateral_pubkey, false),
+            AccountMeta::new(deposit_reserve_pubkey, false),
+            AccountMeta::new(deposit_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(deposit_reserve_collateral_mint_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new(destination_deposit_collateral_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(borrow_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(borrow_reserve_pubkey, false),
+            AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+        data: LendingInstruction::BootstrapObligation {
+            liquidity_amount,
+            borrow_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `MigrateReserve` instruction.
+pub fn migrate_reserve(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    payer_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(payer_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: LendingInstruction::MigrateReserve.pack(),
+    }
+}
+
 /// Creates an `UpdateReserveConfig` instruction.
 #[allow(clippy::too_many_arguments)]
 pub fn update_reserve(
@@ -1304,3 +2668,573 @@ pub fn withdraw_fee(
         data: LendingInstruction::WithdrawFee.pack(),
     }
 }
+
+/// Creates a `SetReserveFeeReceiver` instruction.
+pub fn set_reserve_fee_receiver(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    new_receiver_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(new_receiver_pubkey, false),
+        ],
+        data: LendingInstruction::SetReserveFeeReceiver {
+            new_receiver: new_receiver_pubkey,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `MigrateStakeAccount` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn migrate_stake_account(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    old_stake_account_pubkey: Pubkey,
+    old_staking_pool_pubkey: Pubkey,
+    new_stake_account_pubkey: Pubkey,
+    new_staking_pool_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(obligation_pubkey, false),
+            AccountMeta::new_readonly(deposit_reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new(old_stake_account_pubkey, false),
+            AccountMeta::new(old_staking_pool_pubkey, false),
+            AccountMeta::new(new_stake_account_pubkey, false),
+            AccountMeta::new(new_staking_pool_pubkey, false),
+            AccountMeta::new_readonly(port_finance_staking::id(), false),
+        ],
+        data: LendingInstruction::MigrateStakeAccount.pack(),
+    }
+}
+
+/// Creates an `UnpauseReserve` instruction.
+pub fn unpause_reserve(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        ],
+        data: LendingInstruction::UnpauseReserve.pack(),
+    }
+}
+
+/// Creates a `SetMarketLimits` instruction.
+pub fn set_market_limits(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    max_obligation_reserves: u8,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetMarketLimits {
+            max_obligation_reserves,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `SetLiquidationGraceSlots` instruction.
+pub fn set_liquidation_grace_slots(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    liquidation_grace_slots: u64,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetLiquidationGraceSlots {
+            liquidation_grace_slots,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `SetMinLiquidationValue` instruction.
+pub fn set_min_liquidation_value(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    min_liquidation_value: Decimal,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetMinLiquidationValue {
+            min_liquidation_value,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `SetSlotsPerYear` instruction.
+pub fn set_slots_per_year(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    slots_per_year: u64,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetSlotsPerYear { slots_per_year }.pack(),
+    }
+}
+
+/// Creates a `WithdrawObligationCollateralAndRedeem` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_obligation_collateral_and_redeem(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    source_collateral_pubkey: Pubkey,
+    user_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    option_stake_account_pubkey: Option<Pubkey>,
+    option_staking_pool_pubkey: Option<Pubkey>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_collateral_pubkey, false),
+        AccountMeta::new(user_collateral_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(withdraw_reserve_pubkey, false),
+        AccountMeta::new(reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if let [Some(staking_account_pubkey), Some(staking_pool_pubkey)] =
+        [option_stake_account_pubkey, option_staking_pool_pubkey]
+    {
+        accounts.push(AccountMeta::new(staking_account_pubkey, false));
+        accounts.push(AccountMeta::new(staking_pool_pubkey, false));
+        accounts.push(AccountMeta::new_readonly(port_finance_staking::id(), false));
+    }
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::WithdrawObligationCollateralAndRedeem { collateral_amount }
+            .pack(),
+    }
+}
+
+/// Creates a `SetReserveWindDown` instruction.
+pub fn set_reserve_wind_down(
+    program_id: Pubkey,
+    wind_down: bool,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        ],
+        data: LendingInstruction::SetReserveWindDown { wind_down }.pack(),
+    }
+}
+
+/// Creates a `SetHostFee` instruction.
+pub fn set_host_fee(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    percentage: u8,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        ],
+        data: LendingInstruction::SetHostFee { percentage }.pack(),
+    }
+}
+
+/// Creates a `DonateReserveLiquidity` instruction.
+pub fn donate_reserve_liquidity(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::DonateReserveLiquidity { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a `SplitObligation` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn split_obligation(
+    program_id: Pubkey,
+    split_percent: u8,
+    source_obligation_pubkey: Pubkey,
+    destination_obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    reserve_pubkeys: Vec<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: move_obligation_accounts(
+            source_obligation_pubkey,
+            destination_obligation_pubkey,
+            lending_market_pubkey,
+            obligation_owner_pubkey,
+            reserve_pubkeys,
+        ),
+        data: LendingInstruction::SplitObligation { split_percent }.pack(),
+    }
+}
+
+/// Creates a `MergeObligation` instruction.
+pub fn merge_obligation(
+    program_id: Pubkey,
+    source_obligation_pubkey: Pubkey,
+    destination_obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    reserve_pubkeys: Vec<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: move_obligation_accounts(
+            source_obligation_pubkey,
+            destination_obligation_pubkey,
+            lending_market_pubkey,
+            obligation_owner_pubkey,
+            reserve_pubkeys,
+        ),
+        data: LendingInstruction::MergeObligation.pack(),
+    }
+}
+
+fn move_obligation_accounts(
+    source_obligation_pubkey: Pubkey,
+    destination_obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    reserve_pubkeys: Vec<Pubkey>,
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new(source_obligation_pubkey, false),
+        AccountMeta::new(destination_obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    accounts.extend(
+        reserve_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
+    accounts
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{ObligationCollateral, ObligationLiquidity};
+
+    #[test]
+    fn init_reserve_pda_uses_derived_reserve() {
+        let program_id = Pubkey::new_unique();
+        let lending_market = Pubkey::new_unique();
+        let liquidity_mint = Pubkey::new_unique();
+        let instruction = init_reserve_pda(
+            program_id,
+            42,
+            COption::None,
+            ReserveConfig::default(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            liquidity_mint,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            lending_market,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            COption::None,
+        );
+
+        let (reserve, bump_seed) =
+            find_reserve_address(&program_id, &lending_market, &liquidity_mint);
+        assert_eq!(instruction.accounts[2].pubkey, reserve);
+        assert_eq!(
+            Pubkey::create_program_address(
+                &[
+                    lending_market.as_ref(),
+                    liquidity_mint.as_ref(),
+                    &[bump_seed]
+                ],
+                &program_id
+            ),
+            Ok(reserve)
+        );
+        // one reserve per lending market and liquidity mint
+        assert_ne!(
+            find_reserve_address(&program_id, &lending_market, &Pubkey::new_unique()).0,
+            reserve
+        );
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.data),
+            Ok(LendingInstruction::InitReservePda {
+                liquidity_amount: 42,
+                fixed_price: COption::None,
+                config: ReserveConfig::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn unpack_coption_decimal_rejects_short_input() {
+        let input = [1u8; 19];
+        assert_eq!(
+            LendingInstruction::unpack_coption_decimal(&input),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn unpack_coption_decimal_rejects_invalid_tag() {
+        let mut input = [0u8; 20];
+        input[0] = 2;
+        assert_eq!(
+            LendingInstruction::unpack_coption_decimal(&input),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+
+        let mut input = [0u8; 20];
+        input[0] = 1;
+        input[3] = 1;
+        assert_eq!(
+            LendingInstruction::unpack_coption_decimal(&input),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn set_min_liquidation_value_round_trip() {
+        let instruction = LendingInstruction::SetMinLiquidationValue {
+            min_liquidation_value: Decimal::from_percent(150),
+        };
+        let packed = instruction.clone().pack();
+        assert_eq!(packed.len(), 1 + 16);
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+        assert_eq!(
+            LendingInstruction::unpack(&packed[..16]),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn set_slots_per_year_round_trip() {
+        let instruction = LendingInstruction::SetSlotsPerYear {
+            slots_per_year: 70_000_000,
+        };
+        let packed = instruction.clone().pack();
+        assert_eq!(packed.len(), 1 + 8);
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+        assert_eq!(
+            LendingInstruction::unpack(&packed[..8]),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn bootstrap_obligation_round_trip() {
+        let instruction = LendingInstruction::BootstrapObligation {
+            liquidity_amount: 100,
+            borrow_amount: u64::MAX,
+        };
+        let packed = instruction.clone().pack();
+        assert_eq!(packed.len(), 1 + 8 + 8);
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+        assert_eq!(
+            LendingInstruction::unpack(&packed[..9]),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn withdraw_obligation_collateral_and_redeem_round_trip() {
+        let instruction = LendingInstruction::WithdrawObligationCollateralAndRedeem {
+            collateral_amount: u64::MAX,
+        };
+        let packed = instruction.clone().pack();
+        assert_eq!(packed.len(), 1 + 8);
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+        assert_eq!(
+            LendingInstruction::unpack(&packed[..8]),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn set_reserve_wind_down_round_trip() {
+        for wind_down in [false, true] {
+            let instruction = LendingInstruction::SetReserveWindDown { wind_down };
+            let packed = instruction.clone().pack();
+            assert_eq!(packed, vec![40, wind_down as u8]);
+            assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+        }
+        assert_eq!(
+            LendingInstruction::unpack(&[40]),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn migrate_reserve_round_trip() {
+        let packed = LendingInstruction::MigrateReserve.pack();
+        assert_eq!(packed, vec![37]);
+        assert_eq!(
+            LendingInstruction::unpack(&packed).unwrap(),
+            LendingInstruction::MigrateReserve
+        );
+    }
+
+    #[test]
+    fn unpack_coption_decimal_round_trip() {
+        for value in [COption::None, COption::Some(Decimal::from(42u64))] {
+            let mut buf = Vec::new();
+            LendingInstruction::pack_coption_decimal(&mut buf, value);
+            buf.push(7);
+            let (unpacked, rest) = LendingInstruction::unpack_coption_decimal(&buf).unwrap();
+            assert_eq!(unpacked, value);
+            assert_eq!(rest, &[7]);
+        }
+    }
+
+    #[test]
+    fn build_refresh_transaction_orders_reserves() {
+        let program_id = Pubkey::new_unique();
+        let obligation_pubkey = Pubkey::new_unique();
+        let deposit_reserve = Pubkey::new_unique();
+        let deposit_oracle = Pubkey::new_unique();
+        let borrow_reserve = Pubkey::new_unique();
+        let obligation = Obligation {
+            deposits: vec![ObligationCollateral::new(deposit_reserve)],
+            borrows: vec![
+                ObligationLiquidity::new(borrow_reserve),
+                ObligationLiquidity::new(deposit_reserve),
+            ],
+            ..Obligation::default()
+        };
+        let reserve_oracles: HashMap<Pubkey, COption<Pubkey>> = [
+            (deposit_reserve, COption::Some(deposit_oracle)),
+            (borrow_reserve, COption::None),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let instructions =
+            build_refresh_transaction(program_id, obligation_pubkey, &obligation, &reserve_oracles);
+
+        // each reserve is refreshed once, before the obligation
+        assert_eq!(
+            instructions,
+            vec![
+                refresh_reserve(program_id, deposit_reserve, COption::Some(deposit_oracle)),
+                refresh_reserve(program_id, borrow_reserve, COption::None),
+                refresh_obligation(
+                    program_id,
+                    obligation_pubkey,
+                    vec![deposit_reserve, borrow_reserve, deposit_reserve],
+                ),
+            ]
+        );
+        let obligation_accounts: Vec<Pubkey> = instructions[2]
+            .accounts
+            .iter()
+            .map(|account| account.pubkey)
+            .collect();
+        assert_eq!(
+            obligation_accounts,
+            vec![
+                obligation_pubkey,
+                sysvar::clock::id(),
+                deposit_reserve,
+                borrow_reserve,
+                deposit_reserve,
+            ]
+        );
+    }
+}
@@ -4,8 +4,10 @@
 
 pub mod entrypoint;
 pub mod error;
+pub mod event;
 pub mod instruction;
 pub mod math;
+pub mod metrics;
 pub mod processor;
 pub mod pyth;
 pub mod state;
@@ -34,3 +34,24 @@ pub trait TryMul<RHS>: Sized {
     /// Multiply
     fn try_mul(self, rhs: RHS) -> Result<Self, ProgramError>;
 }
+
+/// Add, clamping at the maximum value instead of returning an error on overflow.
+/// Only for display and other best-effort paths, value-moving paths must use `TryAdd`
+pub trait SaturatingAdd: Sized {
+    /// Add
+    fn saturating_add(self, rhs: Self) -> Self;
+}
+
+/// Subtract, clamping at zero instead of returning an error on underflow.
+/// Only for display and other best-effort paths, value-moving paths must use `TrySub`
+pub trait SaturatingSub: Sized {
+    /// Subtract
+    fn saturating_sub(self, rhs: Self) -> Self;
+}
+
+/// Multiply, clamping at the maximum value instead of returning an error on overflow.
+/// Only for display and other best-effort paths, value-moving paths must use `TryMul`
+pub trait SaturatingMul<RHS>: Sized {
+    /// Multiply
+    fn saturating_mul(self, rhs: RHS) -> Self;
+}
@@ -40,6 +40,11 @@ impl Decimal {
         Self(U192::zero())
     }
 
+    /// Largest representable value, used as the saturation bound
+    pub fn max_value() -> Self {
+        Self(U192::MAX)
+    }
+
     // OPTIMIZE: use const slice when fixed in BPF toolchain
     fn wad() -> U192 {
         U192::from(WAD)
@@ -96,6 +101,36 @@ impl Decimal {
             .ok_or(LendingError::MathOverflow)?;
         Ok(u64::try_from(ceil_val).map_err(|_| LendingError::MathOverflow)?)
     }
+
+    /// Multiply by a rate, rounding up at the 18th decimal place instead of down
+    pub fn try_mul_ceil(self, rhs: Rate) -> Result<Self, ProgramError> {
+        Ok(Self(Self::ceil_div(
+            self.0
+                .checked_mul(Self::from(rhs).0)
+                .ok_or(LendingError::MathOverflow)?,
+            Self::wad(),
+        )?))
+    }
+
+    /// Divide by a decimal, rounding up at the 18th decimal place instead of down
+    pub fn try_div_ceil(self, rhs: Self) -> Result<Self, ProgramError> {
+        Ok(Self(Self::ceil_div(
+            self.0
+                .checked_mul(Self::wad())
+                .ok_or(LendingError::MathOverflow)?,
+            rhs.0,
+        )?))
+    }
+
+    fn ceil_div(numerator: U192, denominator: U192) -> Result<U192, ProgramError> {
+        if denominator.is_zero() {
+            return Err(LendingError::MathOverflow.into());
+        }
+        Ok(numerator
+            .checked_add(denominator - U192::from(1u64))
+            .ok_or(LendingError::MathOverflow)?
+            / denominator)
+    }
 }
 
 impl fmt::Display for Decimal {
@@ -210,6 +245,36 @@ impl TryMul<Decimal> for Decimal {
     }
 }
 
+impl SaturatingAdd for Decimal {
+    fn saturating_add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl SaturatingSub for Decimal {
+    fn saturating_sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl SaturatingMul<u64> for Decimal {
+    fn saturating_mul(self, rhs: u64) -> Self {
+        self.try_mul(rhs).unwrap_or_else(|_| Self::max_value())
+    }
+}
+
+impl SaturatingMul<Rate> for Decimal {
+    fn saturating_mul(self, rhs: Rate) -> Self {
+        self.try_mul(rhs).unwrap_or_else(|_| Self::max_value())
+    }
+}
+
+impl SaturatingMul<Decimal> for Decimal {
+    fn saturating_mul(self, rhs: Self) -> Self {
+        self.try_mul(rhs).unwrap_or_else(|_| Self::max_value())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -218,4 +283,90 @@ mod test {
     fn test_scaler() {
         assert_eq!(U192::exp10(SCALE), Decimal::wad());
     }
+
+    #[test]
+    fn saturating_add_sub() {
+        let max = Decimal::max_value();
+        assert_eq!(max.saturating_add(Decimal::one()), max);
+        assert_eq!(
+            max.try_add(Decimal::one()),
+            Err(LendingError::MathOverflow.into())
+        );
+
+        assert_eq!(
+            Decimal::zero().saturating_sub(Decimal::one()),
+            Decimal::zero()
+        );
+        assert_eq!(
+            Decimal::zero().try_sub(Decimal::one()),
+            Err(LendingError::MathOverflow.into())
+        );
+
+        assert_eq!(
+            Decimal::from(2u64).saturating_add(Decimal::one()),
+            Decimal::from(3u64)
+        );
+        assert_eq!(
+            Decimal::from(2u64).saturating_sub(Decimal::one()),
+            Decimal::one()
+        );
+    }
+
+    #[test]
+    fn saturating_mul() {
+        let max = Decimal::max_value();
+        assert_eq!(max.saturating_mul(2u64), max);
+        assert_eq!(max.try_mul(2u64), Err(LendingError::MathOverflow.into()));
+
+        assert_eq!(max.saturating_mul(Decimal::from(2u64)), max);
+        assert_eq!(
+            max.try_mul(Decimal::from(2u64)),
+            Err(LendingError::MathOverflow.into())
+        );
+
+        assert_eq!(max.saturating_mul(Rate::one()), max);
+        assert_eq!(
+            max.try_mul(Rate::one()),
+            Err(LendingError::MathOverflow.into())
+        );
+
+        assert_eq!(
+            Decimal::from(2u64).saturating_mul(3u64),
+            Decimal::from(6u64)
+        );
+        assert_eq!(
+            Decimal::from(2u64).saturating_mul(Rate::from_percent(50)),
+            Decimal::one()
+        );
+    }
+
+    #[test]
+    fn ceil_mul_div() {
+        let third = Decimal::one().try_div(3u64).unwrap();
+        assert_eq!(
+            third.try_mul(Rate::from_percent(50)),
+            Ok(Decimal(third.0 / 2))
+        );
+        assert_eq!(
+            third.try_mul_ceil(Rate::from_percent(50)),
+            Ok(Decimal(third.0 / 2 + 1))
+        );
+        assert_eq!(
+            Decimal::from(2u64).try_mul_ceil(Rate::from_percent(50)),
+            Ok(Decimal::one())
+        );
+
+        assert_eq!(
+            Decimal::one().try_div_ceil(Decimal::from(3u64)),
+            Ok(Decimal(third.0 + 1))
+        );
+        assert_eq!(
+            Decimal::from(6u64).try_div_ceil(Decimal::from(3u64)),
+            Ok(Decimal::from(2u64))
+        );
+        assert_eq!(
+            Decimal::one().try_div_ceil(Decimal::zero()),
+            Err(LendingError::MathOverflow.into())
+        );
+    }
 }
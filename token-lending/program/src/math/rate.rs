@@ -46,6 +46,11 @@ impl Rate {
         Self(U128::from(0))
     }
 
+    /// Largest representable value, used as the saturation bound
+    pub fn max_value() -> Self {
+        Self(U128::MAX)
+    }
+
     // OPTIMIZE: use const slice when fixed in BPF toolchain
     fn wad() -> U128 {
         U128::from(WAD)
@@ -61,6 +66,25 @@ impl Rate {
         Self(U128::from(percent as u64 * PERCENT_SCALER))
     }
 
+    /// Create per-slot rate from an annual percentage rate
+    pub fn from_apr_percent(apr: u8, slots_per_year: u64) -> Result<Self, ProgramError> {
+        Self::from_percent(apr).try_annual_to_slot_rate(slots_per_year)
+    }
+
+    /// Convert an annual rate to the rate applied each slot
+    pub fn try_annual_to_slot_rate(&self, slots_per_year: u64) -> Result<Self, ProgramError> {
+        Ok(Self(
+            self.0
+                .checked_div(U128::from(slots_per_year))
+                .ok_or(LendingError::MathOverflow)?,
+        ))
+    }
+
+    /// Convert a rate applied each slot to an annual rate, inverse of `try_annual_to_slot_rate`
+    pub fn try_slot_to_annual_rate(&self, slots_per_year: u64) -> Result<Self, ProgramError> {
+        self.try_mul(slots_per_year)
+    }
+
     /// Return raw scaled value
     #[allow(clippy::wrong_self_convention)]
     pub fn to_scaled_val(&self) -> u128 {
@@ -188,6 +212,30 @@ impl TryMul<Rate> for Rate {
     }
 }
 
+impl SaturatingAdd for Rate {
+    fn saturating_add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl SaturatingSub for Rate {
+    fn saturating_sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl SaturatingMul<u64> for Rate {
+    fn saturating_mul(self, rhs: u64) -> Self {
+        self.try_mul(rhs).unwrap_or_else(|_| Self::max_value())
+    }
+}
+
+impl SaturatingMul<Rate> for Rate {
+    fn saturating_mul(self, rhs: Self) -> Self {
+        self.try_mul(rhs).unwrap_or_else(|_| Self::max_value())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -196,4 +244,66 @@ mod test {
     fn checked_pow() {
         assert_eq!(Rate::one(), Rate::one().try_pow(u64::MAX).unwrap());
     }
+
+    #[test]
+    fn saturating_arithmetic() {
+        let max = Rate::max_value();
+        assert_eq!(max.saturating_add(Rate::one()), max);
+        assert_eq!(
+            max.try_add(Rate::one()),
+            Err(LendingError::MathOverflow.into())
+        );
+
+        assert_eq!(Rate::zero().saturating_sub(Rate::one()), Rate::zero());
+        assert_eq!(
+            Rate::zero().try_sub(Rate::one()),
+            Err(LendingError::MathOverflow.into())
+        );
+
+        assert_eq!(max.saturating_mul(2u64), max);
+        assert_eq!(max.try_mul(2u64), Err(LendingError::MathOverflow.into()));
+        assert_eq!(max.saturating_mul(Rate::from_percent(200)), max);
+        assert_eq!(
+            max.try_mul(Rate::from_percent(200)),
+            Err(LendingError::MathOverflow.into())
+        );
+
+        assert_eq!(
+            Rate::from_percent(50).saturating_mul(Rate::from_percent(50)),
+            Rate::from_percent(25)
+        );
+    }
+
+    #[test]
+    fn apr_to_slot_rate() {
+        // ~2.5 slots per second
+        let slots_per_year = 78_840_000;
+
+        assert_eq!(
+            Rate::from_apr_percent(30, slots_per_year),
+            Ok(Rate::from_scaled_val(3_805_175_038))
+        );
+        assert_eq!(Rate::from_apr_percent(0, slots_per_year), Ok(Rate::zero()));
+        assert_eq!(
+            Rate::from_apr_percent(30, 0),
+            Err(LendingError::MathOverflow.into())
+        );
+    }
+
+    #[test]
+    fn apr_round_trip() {
+        let slots_per_year = 78_840_000;
+        for apr in [1, 30, 100, u8::MAX] {
+            let slot_rate = Rate::from_apr_percent(apr, slots_per_year).unwrap();
+            let annual_rate = slot_rate.try_slot_to_annual_rate(slots_per_year).unwrap();
+
+            // truncation loses less than one scaled unit per slot
+            let loss = Rate::from_percent(apr).try_sub(annual_rate).unwrap();
+            assert!(loss < Rate::from_scaled_val(slots_per_year));
+            assert_eq!(
+                annual_rate.try_annual_to_slot_rate(slots_per_year),
+                Ok(slot_rate)
+            );
+        }
+    }
 }
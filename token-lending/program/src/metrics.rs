@@ -0,0 +1,137 @@
+//! Counters of the instructions processed and the errors they returned, for profiling the
+//! program natively, e.g. in a custom validator. They are only kept with the `metrics` feature
+//! and off-chain; otherwise recording is a no-op and every count reads as zero.
+
+use solana_program::program_error::ProgramError;
+
+use crate::error::LendingError;
+
+#[cfg(all(feature = "metrics", not(target_arch = "bpf")))]
+mod counters {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// One counter per instruction tag, and per custom error code below it
+    pub const LEN: usize = 256;
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicU64 = AtomicU64::new(0);
+
+    static INSTRUCTIONS: [AtomicU64; LEN] = [ZERO; LEN];
+    static ERRORS: [AtomicU64; LEN] = [ZERO; LEN];
+    static OTHER_ERRORS: AtomicU64 = ZERO;
+
+    pub fn increment_instruction(tag: u8) {
+        INSTRUCTIONS[tag as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_error(code: Option<u32>) {
+        match code.map(|code| code as usize).filter(|code| *code < LEN) {
+            Some(code) => ERRORS[code].fetch_add(1, Ordering::Relaxed),
+            None => OTHER_ERRORS.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub fn instruction(tag: u8) -> u64 {
+        INSTRUCTIONS[tag as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn error(code: u32) -> u64 {
+        ERRORS
+            .get(code as usize)
+            .map_or(0, |count| count.load(Ordering::Relaxed))
+    }
+
+    pub fn other_errors() -> u64 {
+        OTHER_ERRORS.load(Ordering::Relaxed)
+    }
+
+    pub fn reset() {
+        INSTRUCTIONS
+            .iter()
+            .chain(ERRORS.iter())
+            .chain(std::iter::once(&OTHER_ERRORS))
+            .for_each(|count| count.store(0, Ordering::Relaxed));
+    }
+}
+
+#[cfg(not(all(feature = "metrics", not(target_arch = "bpf"))))]
+mod counters {
+    pub fn increment_instruction(_tag: u8) {}
+
+    pub fn increment_error(_code: Option<u32>) {}
+
+    pub fn instruction(_tag: u8) -> u64 {
+        0
+    }
+
+    pub fn error(_code: u32) -> u64 {
+        0
+    }
+
+    pub fn other_errors() -> u64 {
+        0
+    }
+
+    pub fn reset() {}
+}
+
+/// Count an invocation of the instruction packed in `input`, and the error it returned if any
+pub fn record(input: &[u8], result: &Result<(), ProgramError>) {
+    if let Some(tag) = input.first() {
+        counters::increment_instruction(*tag);
+    }
+    match result {
+        Ok(()) => {}
+        Err(ProgramError::Custom(code)) => counters::increment_error(Some(*code)),
+        Err(_) => counters::increment_error(None),
+    }
+}
+
+/// Invocations of the instruction with tag `tag`, whether or not they succeeded
+pub fn instruction_count(tag: u8) -> u64 {
+    counters::instruction(tag)
+}
+
+/// Instructions that returned the custom error code of `error`
+pub fn error_count(error: LendingError) -> u64 {
+    counters::error(error as u32)
+}
+
+/// Instructions that returned a builtin program error, or a custom error code too large to be a
+/// `LendingError`
+pub fn other_error_count() -> u64 {
+    counters::other_errors()
+}
+
+/// Set every count back to zero
+pub fn reset() {
+    counters::reset()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_counts_only_with_metrics() {
+        let enabled = u64::from(cfg!(feature = "metrics"));
+        let tag = u8::MAX - 1;
+        let before = (
+            instruction_count(tag),
+            error_count(LendingError::ObligationStale),
+            other_error_count(),
+        );
+
+        record(&[tag, 1, 2], &Ok(()));
+        record(&[tag], &Err(LendingError::ObligationStale.into()));
+        record(&[tag], &Err(ProgramError::InvalidArgument));
+        record(&[], &Err(ProgramError::InvalidArgument));
+
+        assert_eq!(instruction_count(tag), before.0 + 3 * enabled);
+        assert_eq!(
+            error_count(LendingError::ObligationStale),
+            before.1 + enabled
+        );
+        assert_eq!(other_error_count(), before.2 + 2 * enabled);
+    }
+}
@@ -13,8 +13,16 @@ use solana_program::{
     program_error::{PrintProgramError, ProgramError},
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
-    sysvar::{clock::Clock, rent::Rent, Sysvar},
+    system_instruction, system_program,
+    sysvar::{
+        self,
+        clock::Clock,
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        rent::Rent,
+        Sysvar,
+    },
 };
+use spl_token::error::TokenError;
 use spl_token::solana_program::instruction::AccountMeta;
 use spl_token::solana_program::program_option::COption;
 use spl_token::state::{Account, Mint};
@@ -27,14 +35,16 @@ use port_finance_staking::state::stake_account::StakeAccount;
 
 use crate::{
     error::LendingError,
+    event::{SupplyAction, SupplyEvent},
     instruction::LendingInstruction,
-    math::{Decimal, Rate, TryAdd, TryDiv, TryMul, WAD},
-    pyth,
+    math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub},
+    metrics, pyth,
     state::{
-        CalculateBorrowResult, CalculateLiquidationResult, CalculateRepayResult,
-        InitLendingMarketParams, InitObligationParams, InitReserveParams, LendingMarket,
-        NewReserveCollateralParams, NewReserveLiquidityParams, Obligation, Reserve,
-        ReserveCollateral, ReserveConfig, ReserveLiquidity,
+        find_reserve_address, CalculateBorrowResult, CalculateLiquidationResult,
+        CalculateRepayResult, InitLendingMarketParams, InitObligationParams, InitReserveParams,
+        LendingMarket, NewReserveCollateralParams, NewReserveLiquidityParams, Obligation, Reserve,
+        ReserveCollateral, ReserveConfig, ReserveLiquidity, MAX_REFRESH_RESERVES,
+        MAX_SLOTS_PER_YEAR, MIN_SLOTS_PER_YEAR,
     },
 };
 use switchboard_v2::AggregatorAccountData;
@@ -72,6 +82,16 @@ pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     input: &[u8],
+) -> ProgramResult {
+    let result = process_lending_instruction(program_id, accounts, input);
+    metrics::record(input, &result);
+    result
+}
+
+fn process_lending_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
 ) -> ProgramResult {
     let instruction = LendingInstruction::unpack(input)?;
     match instruction {
@@ -88,7 +108,29 @@ pub fn process_instruction(
             config,
         } => {
             msg!("Instruction: Init Reserve");
-            process_init_reserve(program_id, liquidity_amount, fixed_price, config, accounts)
+            process_init_reserve(
+                program_id,
+                liquidity_amount,
+                fixed_price,
+                config,
+                accounts,
+                false,
+            )
+        }
+        LendingInstruction::InitReservePda {
+            liquidity_amount,
+            fixed_price,
+            config,
+        } => {
+            msg!("Instruction: Init Reserve PDA");
+            process_init_reserve(
+                program_id,
+                liquidity_amount,
+                fixed_price,
+                config,
+                accounts,
+                true,
+            )
         }
         LendingInstruction::InitObligation => {
             msg!("Instruction: Init Obligation");
@@ -154,6 +196,110 @@ pub fn process_instruction(
             msg!("Withdraw fee from reserve");
             process_withdraw_fee(program_id, accounts)
         }
+        LendingInstruction::AcceptLendingMarketOwner => {
+            msg!("Instruction: Accept Lending Market Owner");
+            process_accept_lending_market_owner(program_id, accounts)
+        }
+        LendingInstruction::SetStrictInstructionOrder { enabled } => {
+            msg!("Instruction: Set Strict Instruction Order");
+            process_set_strict_instruction_order(program_id, enabled, accounts)
+        }
+        LendingInstruction::BindBorrowCollateral => {
+            msg!("Instruction: Bind Borrow Collateral");
+            process_bind_borrow_collateral(program_id, accounts)
+        }
+        LendingInstruction::MigrateObligation => {
+            msg!("Instruction: Migrate Obligation");
+            process_migrate_obligation(program_id, accounts)
+        }
+        LendingInstruction::DepositAndBorrow {
+            liquidity_amount,
+            borrow_amount,
+        } => {
+            msg!("Instruction: Deposit And Borrow");
+            process_deposit_and_borrow(program_id, liquidity_amount, borrow_amount, accounts)
+        }
+        LendingInstruction::SetAllowFreezableMint { allowed } => {
+            msg!("Instruction: Set Allow Freezable Mint");
+            process_set_allow_freezable_mint(program_id, allowed, accounts)
+        }
+        LendingInstruction::SetReserveFeeReceiver { new_receiver } => {
+            msg!("Instruction: Set Reserve Fee Receiver");
+            process_set_reserve_fee_receiver(program_id, new_receiver, accounts)
+        }
+        LendingInstruction::MigrateStakeAccount => {
+            msg!("Instruction: Migrate Stake Account");
+            process_migrate_stake_account(program_id, accounts)
+        }
+        LendingInstruction::UnpauseReserve => {
+            msg!("Instruction: Unpause Reserve");
+            process_unpause_reserve(program_id, accounts)
+        }
+        LendingInstruction::SetMarketLimits {
+            max_obligation_reserves,
+        } => {
+            msg!("Instruction: Set Market Limits");
+            process_set_market_limits(program_id, max_obligation_reserves, accounts)
+        }
+        LendingInstruction::DonateReserveLiquidity { liquidity_amount } => {
+            msg!("Instruction: Donate Reserve Liquidity");
+            process_donate_reserve_liquidity(program_id, liquidity_amount, accounts)
+        }
+        LendingInstruction::SetAllowConservativeRefresh { allowed } => {
+            msg!("Instruction: Set Allow Conservative Refresh");
+            process_set_allow_conservative_refresh(program_id, allowed, accounts)
+        }
+        LendingInstruction::SetLiquidationGraceSlots {
+            liquidation_grace_slots,
+        } => {
+            msg!("Instruction: Set Liquidation Grace Slots");
+            process_set_liquidation_grace_slots(program_id, liquidation_grace_slots, accounts)
+        }
+        LendingInstruction::SetMinLiquidationValue {
+            min_liquidation_value,
+        } => {
+            msg!("Instruction: Set Min Liquidation Value");
+            process_set_min_liquidation_value(program_id, min_liquidation_value, accounts)
+        }
+        LendingInstruction::BootstrapObligation {
+            liquidity_amount,
+            borrow_amount,
+        } => {
+            msg!("Instruction: Bootstrap Obligation");
+            process_bootstrap_obligation(program_id, liquidity_amount, borrow_amount, accounts)
+        }
+        LendingInstruction::MigrateReserve => {
+            msg!("Instruction: Migrate Reserve");
+            process_migrate_reserve(program_id, accounts)
+        }
+        LendingInstruction::SetSlotsPerYear { slots_per_year } => {
+            msg!("Instruction: Set Slots Per Year");
+            process_set_slots_per_year(program_id, slots_per_year, accounts)
+        }
+        LendingInstruction::WithdrawObligationCollateralAndRedeem { collateral_amount } => {
+            msg!("Instruction: Withdraw Obligation Collateral and Redeem");
+            process_withdraw_obligation_collateral_and_redeem(
+                program_id,
+                collateral_amount,
+                accounts,
+            )
+        }
+        LendingInstruction::SetReserveWindDown { wind_down } => {
+            msg!("Instruction: Set Reserve Wind Down");
+            process_set_reserve_wind_down(program_id, wind_down, accounts)
+        }
+        LendingInstruction::SetHostFee { percentage } => {
+            msg!("Instruction: Set Host Fee");
+            process_set_host_fee(program_id, percentage, accounts)
+        }
+        LendingInstruction::SplitObligation { split_percent } => {
+            msg!("Instruction: Split Obligation");
+            process_split_obligation(program_id, split_percent, accounts)
+        }
+        LendingInstruction::MergeObligation => {
+            msg!("Instruction: Merge Obligation");
+            process_merge_obligation(program_id, accounts)
+        }
     }
 }
 
@@ -196,38 +342,209 @@ fn process_set_lending_market_owner(
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_owner_info = next_account_info(account_info_iter)?;
 
+    let mut lending_market =
+        unpack_owned_lending_market(program_id, lending_market_info, lending_market_owner_info)?;
+
+    lending_market.pending_owner = COption::Some(new_owner);
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_accept_lending_market_owner(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let pending_owner_info = next_account_info(account_info_iter)?;
+
     let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &lending_market.owner != lending_market_owner_info.key {
-        msg!("Lending market owner does not match the lending market owner provided");
+    let pending_owner = match lending_market.pending_owner {
+        COption::Some(pending_owner) => pending_owner,
+        COption::None => {
+            msg!("Lending market has no pending owner");
+            return Err(LendingError::NoPendingMarketOwner.into());
+        }
+    };
+    if &pending_owner != pending_owner_info.key {
+        msg!("Lending market pending owner does not match the pending owner provided");
         return Err(LendingError::InvalidMarketOwner.into());
     }
-    if !lending_market_owner_info.is_signer {
-        msg!("Lending market owner provided must be a signer");
+    if !pending_owner_info.is_signer {
+        msg!("Lending market pending owner provided must be a signer");
         return Err(LendingError::InvalidSigner.into());
     }
 
-    lending_market.owner = new_owner;
+    lending_market.owner = pending_owner;
+    lending_market.pending_owner = COption::None;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_strict_instruction_order(
+    program_id: &Pubkey,
+    enabled: bool,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market =
+        unpack_owned_lending_market(program_id, lending_market_info, lending_market_owner_info)?;
+
+    lending_market.strict_instruction_order = enabled;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_allow_freezable_mint(
+    program_id: &Pubkey,
+    allowed: bool,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market =
+        unpack_owned_lending_market(program_id, lending_market_info, lending_market_owner_info)?;
+
+    lending_market.allow_freezable_mint = allowed;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_allow_conservative_refresh(
+    program_id: &Pubkey,
+    allowed: bool,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market =
+        unpack_owned_lending_market(program_id, lending_market_info, lending_market_owner_info)?;
+
+    lending_market.allow_conservative_refresh = allowed;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_liquidation_grace_slots(
+    program_id: &Pubkey,
+    liquidation_grace_slots: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market =
+        unpack_owned_lending_market(program_id, lending_market_info, lending_market_owner_info)?;
+
+    lending_market.liquidation_grace_slots = liquidation_grace_slots;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_min_liquidation_value(
+    program_id: &Pubkey,
+    min_liquidation_value: Decimal,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market =
+        unpack_owned_lending_market(program_id, lending_market_info, lending_market_owner_info)?;
+
+    lending_market.min_liquidation_value = min_liquidation_value;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_slots_per_year(
+    program_id: &Pubkey,
+    slots_per_year: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if slots_per_year != 0 && !(MIN_SLOTS_PER_YEAR..=MAX_SLOTS_PER_YEAR).contains(&slots_per_year) {
+        msg!(
+            "Slots per year must be 0 for the default, or between {} and {}",
+            MIN_SLOTS_PER_YEAR,
+            MAX_SLOTS_PER_YEAR
+        );
+        return Err(LendingError::InvalidConfig.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market =
+        unpack_owned_lending_market(program_id, lending_market_info, lending_market_owner_info)?;
+
+    lending_market.slots_per_year = slots_per_year;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_market_limits(
+    program_id: &Pubkey,
+    max_obligation_reserves: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if max_obligation_reserves as usize > MAX_REFRESH_RESERVES {
+        msg!(
+            "Max obligation reserves must be at most {}",
+            MAX_REFRESH_RESERVES
+        );
+        return Err(LendingError::InvalidConfig.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market =
+        unpack_owned_lending_market(program_id, lending_market_info, lending_market_owner_info)?;
+
+    lending_market.max_obligation_reserves = max_obligation_reserves;
     LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
 
     Ok(())
 }
 
+/// Initializes a reserve, creating the reserve account at its derived address first if
+/// `create_derived_reserve` is set
 fn process_init_reserve(
     program_id: &Pubkey,
     liquidity_amount: u64,
     fixed_price: COption<Decimal>,
     config: ReserveConfig,
     accounts: &[AccountInfo],
+    create_derived_reserve: bool,
 ) -> ProgramResult {
     if liquidity_amount == 0 {
         msg!("Reserve must be initialized with liquidity");
         return Err(LendingError::InvalidAmount.into());
     }
-    validate_reserve_config(config)?;
+    config.validate()?;
 
     let account_info_iter = &mut accounts.iter().peekable();
     let source_liquidity_info = next_account_info(account_info_iter)?;
@@ -247,6 +564,28 @@ fn process_init_reserve(
     let rent = &Rent::from_account_info(rent_info)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
+    if create_derived_reserve {
+        let funder_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        create_derived_reserve_account(
+            program_id,
+            reserve_info,
+            lending_market_info,
+            reserve_liquidity_mint_info,
+            funder_info,
+            system_program_info,
+            rent,
+        )?;
+    }
+
+    assert_writable(reserve_info, "Reserve")?;
+    assert_writable(reserve_liquidity_supply_info, "Reserve liquidity supply")?;
+    assert_writable(
+        reserve_liquidity_fee_receiver_info,
+        "Reserve liquidity fee receiver",
+    )?;
+    assert_writable(reserve_collateral_supply_info, "Reserve collateral supply")?;
+
     assert_rent_exempt(rent, reserve_info)?;
     let mut reserve = assert_uninitialized::<Reserve>(reserve_info)?;
     if reserve_info.owner != program_id {
@@ -299,12 +638,16 @@ fn process_init_reserve(
             if is_pyth_program(oracle_price_info.owner) {
                 (
                     COption::Some(*oracle_price_info.key),
-                    get_pyth_price(oracle_price_info, clock)?,
+                    get_pyth_price(oracle_price_info, clock, price_staleness_slots(&config))?,
                 )
             } else if is_switchbaord_program(oracle_price_info.owner) {
                 (
                     COption::Some(*oracle_price_info.key),
-                    get_switchboard_price(oracle_price_info, clock)?,
+                    get_switchboard_price(
+                        oracle_price_info,
+                        clock,
+                        price_staleness_slots(&config),
+                    )?,
                 )
             } else {
                 msg!("Oracle owner is {}, not correct", oracle_price_info.owner);
@@ -330,6 +673,41 @@ fn process_init_reserve(
         msg!("Reserve liquidity mint is not owned by the token program provided");
         return Err(LendingError::InvalidTokenOwner.into());
     }
+    // Market values are denominated in the quote currency, so a reserve of the quote currency
+    // mint itself is always worth exactly one unit of it
+    if lending_market.quote_currency == reserve_liquidity_mint_info.key.to_bytes()
+        && (reserve_liquidity_oracle_pubkey.is_some()
+            || reserve_liquidity_market_price != Decimal::one())
+    {
+        msg!("Reserve of the lending market quote currency must have a fixed price of one");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    // Oracle prices are in USD, a market quoted in anything else can only use fixed prices
+    if reserve_liquidity_oracle_pubkey.is_some() && !lending_market.quotes_in_usd() {
+        msg!("Oracle prices are in USD, a reserve of a market not quoted in USD must have a fixed price");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    if reserve_liquidity_mint.freeze_authority.is_some() {
+        if !lending_market.allow_freezable_mint {
+            msg!("Reserve liquidity mint has a freeze authority, which the lending market does not allow");
+            return Err(LendingError::FreezableLiquidityMint.into());
+        }
+        msg!("Warning: reserve liquidity mint has a freeze authority, reserve liquidity can be frozen");
+    }
+
+    // The collateral mint is initialized below with the lending market authority as its mint
+    // authority, so a mint that is already initialized may be backing another reserve
+    if reserve_collateral_mint_info.owner != token_program_id.key {
+        msg!("Reserve collateral mint is not owned by the token program provided");
+        return Err(LendingError::InvalidTokenOwner.into());
+    }
+    let reserve_collateral_mint =
+        Mint::unpack_unchecked(&reserve_collateral_mint_info.data.borrow())
+            .map_err(|_| LendingError::InvalidTokenMint)?;
+    if reserve_collateral_mint.is_initialized {
+        msg!("Reserve collateral mint is already initialized and may belong to another reserve");
+        return Err(LendingError::CollateralMintInUse.into());
+    }
 
     reserve.init(InitReserveParams {
         current_slot: clock.slot,
@@ -349,6 +727,9 @@ fn process_init_reserve(
         config,
     });
 
+    let exchange_rate = reserve
+        .collateral_exchange_rate()?
+        .decimal_collateral_to_liquidity(Decimal::one())?;
     let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
@@ -410,82 +791,141 @@ fn process_init_reserve(
         token_program: token_program_id.clone(),
     })?;
 
+    SupplyEvent {
+        action: SupplyAction::Deposit,
+        reserve: *reserve_info.key,
+        liquidity_amount,
+        collateral_amount,
+        exchange_rate,
+    }
+    .log();
+
     Ok(())
 }
 
-fn validate_reserve_config(config: ReserveConfig) -> ProgramResult {
-    if config.optimal_utilization_rate > 100 {
-        msg!("Optimal utilization rate must be in range [0, 100]");
-        return Err(LendingError::InvalidConfig.into());
-    }
-    if config.loan_to_value_ratio >= 100 {
-        msg!("Loan to value ratio must be in range [0, 100)");
-        return Err(LendingError::InvalidConfig.into());
-    }
-    if config.liquidation_bonus > 100 {
-        msg!("Liquidation bonus must be in range [0, 100]");
-        return Err(LendingError::InvalidConfig.into());
-    }
-    if config.liquidation_threshold <= config.loan_to_value_ratio
-        || config.liquidation_threshold > 100
-    {
-        msg!(
-            "Liquidation threshold must be in range (LTV {}, 100]",
-            config.loan_to_value_ratio
-        );
-        return Err(LendingError::InvalidConfig.into());
+/// Create the reserve account at the address derived from the lending market and liquidity mint.
+/// An account already owned by the program there is a reserve initialized before.
+fn create_derived_reserve_account<'a>(
+    program_id: &Pubkey,
+    reserve_info: &AccountInfo<'a>,
+    lending_market_info: &AccountInfo<'a>,
+    reserve_liquidity_mint_info: &AccountInfo<'a>,
+    funder_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    rent: &Rent,
+) -> ProgramResult {
+    let (reserve_pubkey, bump_seed) = find_reserve_address(
+        program_id,
+        lending_market_info.key,
+        reserve_liquidity_mint_info.key,
+    );
+    if &reserve_pubkey != reserve_info.key {
+        msg!("Reserve provided is not the address derived from the lending market and liquidity mint");
+        return Err(LendingError::InvalidAccountInput.into());
     }
-    if config.optimal_borrow_rate < config.min_borrow_rate {
-        msg!("Optimal borrow rate must be >= min borrow rate");
-        return Err(LendingError::InvalidConfig.into());
+    if reserve_info.owner == program_id {
+        msg!("Reserve already exists for this lending market and liquidity mint");
+        return Err(LendingError::AlreadyInitialized.into());
     }
-    if config.optimal_borrow_rate > config.max_borrow_rate {
-        msg!("Optimal borrow rate must be <= max borrow rate");
-        return Err(LendingError::InvalidConfig.into());
+    if system_program_info.key != &system_program::id() {
+        msg!("System program provided is not the system program");
+        return Err(LendingError::InvalidAccountInput.into());
     }
-    if config.fees.borrow_fee_wad >= WAD {
-        msg!("Borrow fee must be in range [0, 1_000_000_000_000_000_000)");
-        return Err(LendingError::InvalidConfig.into());
+    if !funder_info.is_signer {
+        msg!("Reserve funder provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
     }
-    if config.fees.host_fee_percentage > 100 {
-        msg!("Host fee percentage must be in range [0, 100]");
-        return Err(LendingError::InvalidConfig.into());
+
+    // create_account fails if anyone sent lamports to the address first, so top up the rent and
+    // allocate and assign the account separately
+    let rent_shortfall = rent
+        .minimum_balance(Reserve::LEN)
+        .saturating_sub(reserve_info.lamports());
+    if rent_shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(funder_info.key, reserve_info.key, rent_shortfall),
+            &[
+                funder_info.clone(),
+                reserve_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
     }
 
-    Ok(())
+    let reserve_signer_seeds: &[&[u8]] = &[
+        lending_market_info.key.as_ref(),
+        reserve_liquidity_mint_info.key.as_ref(),
+        &[bump_seed],
+    ];
+    invoke_signed(
+        &system_instruction::allocate(reserve_info.key, Reserve::LEN as u64),
+        &[reserve_info.clone(), system_program_info.clone()],
+        &[reserve_signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(reserve_info.key, program_id),
+        &[reserve_info.clone(), system_program_info.clone()],
+        &[reserve_signer_seeds],
+    )
 }
 
 fn process_refresh_reserve(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter().peekable();
+    let account_info_iter = &mut accounts.iter();
     let reserve_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
+    assert_writable(reserve_info, "Reserve")?;
+
     let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
     if reserve_info.owner != program_id {
         msg!("Reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    // a second refresh in the same slot is a no-op, so interleaved oracle prices cannot be
+    // applied more than once per slot
+    let already_refreshed = reserve.last_update.is_updated_in(clock.slot);
+    // a fixed price reserve has no oracle, any oracle account passed after the clock is ignored
     if let COption::Some(reserve_liquidity_oracle_pubkey) = reserve.liquidity.oracle_pubkey {
         let reserve_liquidity_oracle_info = next_account_info(account_info_iter)?;
         if &reserve_liquidity_oracle_pubkey != reserve_liquidity_oracle_info.key {
             msg!("Reserve liquidity oracle does not match the reserve liquidity oracle provided");
-            return Err(LendingError::InvalidAccountInput.into());
+            return Err(LendingError::InvalidOracleConfig.into());
+        }
+        if already_refreshed {
+            return Ok(());
         }
 
         // @TODO: sanity check https://git.io/JOCcb
-        reserve.liquidity.market_price = if is_pyth_program(reserve_liquidity_oracle_info.owner) {
-            get_pyth_price(reserve_liquidity_oracle_info, clock)
+        let stale_after_slots = price_staleness_slots(&reserve.config);
+        let spot_price = if is_pyth_program(reserve_liquidity_oracle_info.owner) {
+            get_pyth_price(reserve_liquidity_oracle_info, clock, stale_after_slots)
         } else if is_switchbaord_program(reserve_liquidity_oracle_info.owner) {
-            get_switchboard_price(reserve_liquidity_oracle_info, clock)
+            get_switchboard_price(reserve_liquidity_oracle_info, clock, stale_after_slots)
         } else {
             Err(LendingError::InvalidAccountInput.into())
-        }?;
-    } else if account_info_iter.peek().is_some() {
-        msg!("Reserve liquidity oracle cannot be provided when reserve liquidity is the quote currency");
-        return Err(LendingError::InvalidAccountInput.into());
+        };
+        match spot_price {
+            // keep the last price and pause, so that repayments and redemptions which need a
+            // refreshed reserve still go through
+            Err(err)
+                if err == LendingError::OraclePriceStale.into()
+                    && reserve.config.max_price_staleness_slots != 0 =>
+            {
+                msg!("Reserve oracle price is stale, pausing borrows and liquidations");
+                reserve.is_paused = true;
+            }
+            spot_price => reserve.update_market_price(spot_price?, clock.slot)?,
+        }
+    } else if already_refreshed {
+        return Ok(());
     }
 
     reserve.accrue_interest(clock.slot)?;
+    // deposits and redemptions need the reserve refreshed in their slot, so the liquidity moved
+    // since the last refresh in an earlier slot no longer counts against the per slot limit
+    if reserve.last_update.slot != clock.slot {
+        reserve.slot_liquidity_change_bps = 0;
+    }
     reserve.last_update.update_slot(clock.slot);
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
@@ -547,6 +987,9 @@ fn _deposit_reserve_liquidity<'a>(
     clock: &Clock,
     token_program_id: &AccountInfo<'a>,
 ) -> Result<u64, ProgramError> {
+    assert_writable(reserve_info, "Reserve")?;
+    assert_writable(reserve_liquidity_supply_info, "Reserve liquidity supply")?;
+
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         msg!("Lending market provided is not owned by the lending program");
@@ -568,11 +1011,11 @@ fn _deposit_reserve_liquidity<'a>(
     }
     if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
         msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongLiquiditySupply.into());
     }
     if &reserve.collateral.mint_pubkey != reserve_collateral_mint_info.key {
         msg!("Reserve collateral mint does not match the reserve collateral mint provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongCollateralMint.into());
     }
     if &reserve.liquidity.supply_pubkey == source_liquidity_info.key {
         msg!("Reserve liquidity supply cannot be used as the source liquidity provided");
@@ -586,6 +1029,10 @@ fn _deposit_reserve_liquidity<'a>(
         msg!("Reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if reserve.is_wind_down {
+        msg!("Reserve is winding down and no longer accepts deposits");
+        return Err(LendingError::ReserveWindingDown.into());
+    }
 
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
@@ -600,6 +1047,9 @@ fn _deposit_reserve_liquidity<'a>(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
+    let exchange_rate = reserve
+        .collateral_exchange_rate()?
+        .decimal_collateral_to_liquidity(Decimal::one())?;
     let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
     reserve.last_update.mark_stale();
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
@@ -622,33 +1072,117 @@ fn _deposit_reserve_liquidity<'a>(
         token_program: token_program_id.clone(),
     })?;
 
+    SupplyEvent {
+        action: SupplyAction::Deposit,
+        reserve: *reserve_info.key,
+        liquidity_amount,
+        collateral_amount,
+        exchange_rate,
+    }
+    .log();
+
     Ok(collateral_amount)
 }
 
-fn process_redeem_reserve_collateral(
+fn process_donate_reserve_liquidity(
     program_id: &Pubkey,
-    collateral_amount: u64,
+    liquidity_amount: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    if collateral_amount == 0 {
-        msg!("Collateral amount provided cannot be zero");
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
         return Err(LendingError::InvalidAmount.into());
     }
 
     let account_info_iter = &mut accounts.iter();
-    let source_collateral_info = next_account_info(account_info_iter)?;
-    let destination_liquidity_info = next_account_info(account_info_iter)?;
-    let reserve_info = next_account_info(account_info_iter)?;
-    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let source_liquidity_info = next_account_info(account_info_iter)?;
     let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
-    let lending_market_authority_info = next_account_info(account_info_iter)?;
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
-    _redeem_reserve_collateral(
-        program_id,
+    assert_writable(reserve_info, "Reserve")?;
+    assert_writable(reserve_liquidity_supply_info, "Reserve liquidity supply")?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
+        return Err(LendingError::WrongLiquiditySupply.into());
+    }
+    if &reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Reserve liquidity supply cannot be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.last_update.is_stale(clock.slot)? {
+        msg!("Reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+    // with no collateral outstanding the donation would go to whoever deposits first
+    if reserve.collateral.mint_total_supply == 0 {
+        msg!("Reserve has no collateral outstanding to receive the donation");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    reserve.liquidity.donate(liquidity_amount)?;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: reserve_liquidity_supply_info.clone(),
+        amount: liquidity_amount,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+fn process_redeem_reserve_collateral(
+    program_id: &Pubkey,
+    collateral_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if collateral_amount == 0 {
+        msg!("Collateral amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_collateral_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    _redeem_reserve_collateral(
+        program_id,
         collateral_amount,
         source_collateral_info,
         destination_liquidity_info,
@@ -678,6 +1212,9 @@ fn _redeem_reserve_collateral<'a>(
     clock: &Clock,
     token_program_id: &AccountInfo<'a>,
 ) -> ProgramResult {
+    assert_writable(reserve_info, "Reserve")?;
+    assert_writable(reserve_liquidity_supply_info, "Reserve liquidity supply")?;
+
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         msg!("Lending market provided is not owned by the lending program");
@@ -699,7 +1236,7 @@ fn _redeem_reserve_collateral<'a>(
     }
     if &reserve.collateral.mint_pubkey != reserve_collateral_mint_info.key {
         msg!("Reserve collateral mint does not match the reserve collateral mint provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongCollateralMint.into());
     }
     if &reserve.collateral.supply_pubkey == source_collateral_info.key {
         msg!("Reserve collateral supply cannot be used as the source collateral provided");
@@ -707,7 +1244,7 @@ fn _redeem_reserve_collateral<'a>(
     }
     if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
         msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongLiquiditySupply.into());
     }
     if &reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
         msg!("Reserve liquidity supply cannot be used as the destination liquidity provided");
@@ -731,6 +1268,9 @@ fn _redeem_reserve_collateral<'a>(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
+    let exchange_rate = reserve
+        .collateral_exchange_rate()?
+        .decimal_collateral_to_liquidity(Decimal::one())?;
     let liquidity_amount = reserve.redeem_collateral(collateral_amount)?;
     reserve.last_update.mark_stale();
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
@@ -753,6 +1293,15 @@ fn _redeem_reserve_collateral<'a>(
         token_program: token_program_id.clone(),
     })?;
 
+    SupplyEvent {
+        action: SupplyAction::Redeem,
+        reserve: *reserve_info.key,
+        liquidity_amount,
+        collateral_amount,
+        exchange_rate,
+    }
+    .log();
+
     Ok(())
 }
 
@@ -766,6 +1315,8 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
     let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
+    assert_writable(obligation_info, "Obligation")?;
+
     assert_rent_exempt(rent, obligation_info)?;
     let mut obligation = assert_uninitialized::<Obligation>(obligation_info)?;
     if obligation_info.owner != program_id {
@@ -805,16 +1356,54 @@ fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) ->
     let obligation_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
+    assert_writable(obligation_info, "Obligation")?;
+
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
         msg!("Obligation provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    // reserves are not refreshed twice in a slot either, so the values computed by a full refresh
+    // earlier in the slot still hold. A conservative refresh that valued stale collateral at zero
+    // can be redone in full, and the reserves provided are still checked either way.
+    let refreshed_in_slot = obligation.last_update.is_updated_in(clock.slot)
+        && !obligation.has_zero_valued_collateral();
+
+    obligation.check_refresh_reserve_count()?;
+
+    let reserve_count = obligation.deposits.len() + obligation.borrows.len();
+    let conservative_refresh = match accounts.get(2 + reserve_count..) {
+        Some([lending_market_info, obligation_owner_info]) => {
+            if lending_market_info.owner != program_id {
+                msg!("Lending market provided is not owned by the lending program");
+                return Err(LendingError::InvalidAccountOwner.into());
+            }
+            if &obligation.lending_market != lending_market_info.key {
+                msg!("Obligation lending market does not match the lending market provided");
+                return Err(LendingError::InvalidAccountInput.into());
+            }
+            let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+            if !lending_market.allow_conservative_refresh {
+                msg!("Lending market does not allow conservative obligation refreshes");
+                return Err(LendingError::ConservativeRefreshDisabled.into());
+            }
+            if &obligation.owner != obligation_owner_info.key {
+                msg!("Obligation owner does not match the obligation owner provided");
+                return Err(LendingError::InvalidObligationOwner.into());
+            }
+            if !obligation_owner_info.is_signer {
+                msg!("Obligation owner provided must be a signer");
+                return Err(LendingError::InvalidSigner.into());
+            }
+            true
+        }
+        _ => false,
+    };
 
     let mut deposited_value = Decimal::zero();
     let mut borrowed_value = Decimal::zero();
-    let mut allowed_borrow_value = Decimal::zero();
-    let mut unhealthy_borrow_value = Decimal::zero();
+    let mut collateral_values = Vec::with_capacity(obligation.deposits.len());
+    let mut borrow_reserve_paused = false;
 
     for (index, collateral) in obligation.deposits.iter_mut().enumerate() {
         let deposit_reserve_info = next_account_info(account_info_iter)?;
@@ -832,37 +1421,43 @@ fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) ->
             );
             return Err(LendingError::InvalidAccountInput.into());
         }
+        if refreshed_in_slot {
+            continue;
+        }
 
         let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
         if deposit_reserve.last_update.is_stale(clock.slot)? {
+            if !conservative_refresh {
+                msg!(
+                    "Deposit reserve provided for collateral {} is stale and must be refreshed in the current slot",
+                    index
+                );
+                return Err(LendingError::ReserveStale.into());
+            }
+            // never in the owner's favor, the collateral can't be borrowed against or withdrawn
+            // until its reserve can be refreshed again
             msg!(
-                "Deposit reserve provided for collateral {} is stale and must be refreshed in the current slot",
+                "Deposit reserve provided for collateral {} is stale, valuing it at zero",
                 index
             );
-            return Err(LendingError::ReserveStale.into());
+            collateral.market_value = Decimal::zero();
+            collateral_values.push((Decimal::zero(), Decimal::zero()));
+            continue;
         }
 
-        // @TODO: add lookup table https://git.io/JOCYq
-        let decimals = 10u64
-            .checked_pow(deposit_reserve.liquidity.mint_decimals as u32)
-            .ok_or(LendingError::MathOverflow)?;
-
-        let market_value = deposit_reserve
-            .collateral_exchange_rate()?
-            .decimal_collateral_to_liquidity(collateral.deposited_amount.into())?
-            .try_mul(deposit_reserve.liquidity.market_price)?
-            .try_div(decimals)?;
+        let market_value =
+            packable_value(deposit_reserve.collateral_market_value(collateral.deposited_amount)?)?;
         collateral.market_value = market_value;
 
-        let loan_to_value_rate = Rate::from_percent(deposit_reserve.config.loan_to_value_ratio);
+        let loan_to_value_rate = deposit_reserve.loan_to_value_rate();
         let liquidation_threshold_rate =
             Rate::from_percent(deposit_reserve.config.liquidation_threshold);
 
         deposited_value = deposited_value.try_add(market_value)?;
-        allowed_borrow_value =
-            allowed_borrow_value.try_add(market_value.try_mul(loan_to_value_rate)?)?;
-        unhealthy_borrow_value =
-            unhealthy_borrow_value.try_add(market_value.try_mul(liquidation_threshold_rate)?)?;
+        collateral_values.push((
+            market_value.try_mul(loan_to_value_rate)?,
+            market_value.try_mul(liquidation_threshold_rate)?,
+        ));
     }
 
     for (index, liquidity) in obligation.borrows.iter_mut().enumerate() {
@@ -881,6 +1476,9 @@ fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) ->
             );
             return Err(LendingError::InvalidAccountInput.into());
         }
+        if refreshed_in_slot {
+            continue;
+        }
 
         let borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
         if borrow_reserve.last_update.is_stale(clock.slot)? {
@@ -892,30 +1490,41 @@ fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) ->
         }
         // @TODO: add deposit difference to staking pool, consider change staking amount from u64 to Decimal
         liquidity.accrue_interest(borrow_reserve.liquidity.cumulative_borrow_rate_wads)?;
+        borrow_reserve_paused |= borrow_reserve.is_paused;
 
-        // @TODO: add lookup table https://git.io/JOCYq
-        let decimals = 10u64
-            .checked_pow(borrow_reserve.liquidity.mint_decimals as u32)
-            .ok_or(LendingError::MathOverflow)?;
-
-        let market_value = liquidity
-            .borrowed_amount_wads
-            .try_mul(borrow_reserve.liquidity.market_price)?
-            .try_div(decimals)?;
+        let market_value =
+            packable_value(borrow_reserve.liquidity_market_value(liquidity.borrowed_amount_wads)?)?;
         liquidity.market_value = market_value;
 
         borrowed_value = borrowed_value.try_add(market_value)?;
     }
 
+    if conservative_refresh {
+        // lending market and obligation owner, checked above
+        account_info_iter.nth(1);
+    }
     if account_info_iter.peek().is_some() {
         msg!("Too many obligation deposit or borrow reserves provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    if refreshed_in_slot {
+        return Ok(());
+    }
 
-    obligation.deposited_value = deposited_value;
-    obligation.borrowed_value = borrowed_value;
-    obligation.allowed_borrow_value = allowed_borrow_value;
-    obligation.unhealthy_borrow_value = unhealthy_borrow_value;
+    let (mut allowed_borrow_value, unhealthy_borrow_value) =
+        obligation.calculate_borrowing_power(&collateral_values)?;
+    if borrow_reserve_paused {
+        // debt in a paused reserve is valued at its last trusted price, which may understate it,
+        // so nothing can be borrowed or withdrawn against the obligation until it is unpaused
+        msg!("Obligation borrows from a paused reserve, it has no borrowing power");
+        allowed_borrow_value = Decimal::zero();
+    }
+
+    obligation.deposited_value = packable_value(deposited_value)?;
+    obligation.borrowed_value = packable_value(borrowed_value)?;
+    obligation.allowed_borrow_value = packable_value(allowed_borrow_value)?;
+    obligation.unhealthy_borrow_value = packable_value(unhealthy_borrow_value)?;
+    obligation.update_unhealthy_slot(clock.slot);
 
     obligation.last_update.update_slot(clock.slot);
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
@@ -947,9 +1556,19 @@ fn process_deposit_obligation_collateral(
     let clock = &Clock::from_account_info(clock_info)?;
     let token_program_id = next_account_info(account_info_iter)?;
     let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
-    if account_info_iter.peek().is_some() ^ deposit_reserve.config.deposit_staking_pool.is_some() {
-        msg!("This reserve has corresponded staking pool, a stake pool and stake account must be passed in when depositing");
-        return Err(LendingError::InvalidStakingPool.into());
+    match (
+        deposit_reserve.config.deposit_staking_pool.is_some(),
+        account_info_iter.peek().is_some(),
+    ) {
+        (true, false) => {
+            msg!("This reserve has corresponded staking pool, a stake pool and stake account must be passed in when depositing");
+            return Err(LendingError::MissingStakingAccounts.into());
+        }
+        (false, true) => {
+            msg!("This reserve has no staking pool, a stake pool and stake account must not be passed in when depositing");
+            return Err(LendingError::InvalidStakingPool.into());
+        }
+        _ => {}
     }
     deposit_obligation_collateral(
         program_id,
@@ -1010,6 +1629,12 @@ fn deposit_obligation_collateral<'a>(
     clock: &Clock,
     token_program_id: &AccountInfo<'a>,
 ) -> ProgramResult {
+    assert_writable(obligation_info, "Obligation")?;
+    assert_writable(
+        destination_collateral_info,
+        "Deposit reserve collateral supply",
+    )?;
+
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         msg!("Lending market provided is not owned by the lending program");
@@ -1027,7 +1652,7 @@ fn deposit_obligation_collateral<'a>(
     }
     if &deposit_reserve.lending_market != lending_market_info.key {
         msg!("Deposit reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if &deposit_reserve.collateral.supply_pubkey == source_collateral_info.key {
         msg!("Deposit reserve collateral supply cannot be used as the source collateral provided");
@@ -1037,13 +1662,17 @@ fn deposit_obligation_collateral<'a>(
         msg!(
             "Deposit reserve collateral supply must be used as the destination collateral provided"
         );
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongCollateralSupply.into());
     }
     if deposit_reserve.last_update.is_stale(clock.slot)? {
         msg!("Deposit reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
-    if deposit_reserve.config.loan_to_value_ratio == 0 {
+    if deposit_reserve.is_paused {
+        msg!("Deposit reserve is paused, its collateral gives no borrowing power");
+        return Err(LendingError::ReservePaused.into());
+    }
+    if deposit_reserve.loan_to_value_rate() == Rate::zero() {
         msg!("Deposit reserve has collateral disabled for borrowing");
         return Err(LendingError::ReserveCollateralDisabled.into());
     }
@@ -1055,7 +1684,7 @@ fn deposit_obligation_collateral<'a>(
     }
     if &obligation.lending_market != lending_market_info.key {
         msg!("Obligation lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if &obligation.owner != obligation_owner_info.key {
         msg!("Obligation owner does not match the obligation owner provided");
@@ -1080,8 +1709,20 @@ fn deposit_obligation_collateral<'a>(
     }
 
     obligation
-        .find_or_add_collateral_to_deposits(*deposit_reserve_info.key)?
+        .find_or_add_collateral_to_deposits(
+            *deposit_reserve_info.key,
+            lending_market.obligation_reserve_limit(),
+        )?
         .deposit(collateral_amount)?;
+    let (collateral, collateral_index) =
+        obligation.find_collateral_in_deposits(*deposit_reserve_info.key)?;
+    let market_value = deposit_reserve.collateral_market_value(collateral.deposited_amount)?;
+    obligation.revalue_collateral(
+        collateral_index,
+        market_value,
+        deposit_reserve.loan_to_value_rate(),
+        Rate::from_percent(deposit_reserve.config.liquidation_threshold),
+    )?;
     obligation.last_update.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
@@ -1180,9 +1821,19 @@ fn process_withdraw_obligation_collateral(
 
     let reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
 
-    if account_info_iter.peek().is_some() ^ reserve.config.deposit_staking_pool.is_some() {
-        msg!("This reserve has corresponded staking pool, a stake pool and stake account must be passed in when withdrawing");
-        return Err(LendingError::InvalidStakingPool.into());
+    match (
+        reserve.config.deposit_staking_pool.is_some(),
+        account_info_iter.peek().is_some(),
+    ) {
+        (true, false) => {
+            msg!("This reserve has corresponded staking pool, a stake pool and stake account must be passed in when withdrawing");
+            return Err(LendingError::MissingStakingAccounts.into());
+        }
+        (false, true) => {
+            msg!("This reserve has no staking pool, a stake pool and stake account must not be passed in when withdrawing");
+            return Err(LendingError::InvalidStakingPool.into());
+        }
+        _ => {}
     }
 
     let withdraw_amount = withdraw_obligation_collateral(
@@ -1228,6 +1879,104 @@ fn process_withdraw_obligation_collateral(
     }
 }
 
+fn process_withdraw_obligation_collateral_and_redeem(
+    program_id: &Pubkey,
+    collateral_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if collateral_amount == 0 {
+        msg!("Collateral amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    let account_info_iter = &mut accounts.iter().peekable();
+    let source_collateral_info = next_account_info(account_info_iter)?;
+    let user_collateral_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let clock_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(clock_info)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+
+    match (
+        reserve.config.deposit_staking_pool.is_some(),
+        account_info_iter.peek().is_some(),
+    ) {
+        (true, false) => {
+            msg!("This reserve has corresponded staking pool, a stake pool and stake account must be passed in when withdrawing");
+            return Err(LendingError::MissingStakingAccounts.into());
+        }
+        (false, true) => {
+            msg!("This reserve has no staking pool, a stake pool and stake account must not be passed in when withdrawing");
+            return Err(LendingError::InvalidStakingPool.into());
+        }
+        _ => {}
+    }
+
+    let withdraw_amount = withdraw_obligation_collateral(
+        program_id,
+        collateral_amount,
+        source_collateral_info,
+        user_collateral_info,
+        withdraw_reserve_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        obligation_owner_info,
+        clock,
+        token_program_id,
+    )?;
+
+    if account_info_iter.peek().is_some() {
+        let stake_account_info = next_account_info(account_info_iter)?;
+        let staking_pool_info = next_account_info(account_info_iter)?;
+        let staking_program_id = next_account_info(account_info_iter)?;
+        if reserve
+            .config
+            .deposit_staking_pool
+            .map_or(true, |k| k != *staking_pool_info.key)
+        {
+            msg!("Invalid staking pool, not the one corresponded to the reserve");
+            return Err(LendingError::InvalidStakingPool.into());
+        }
+        withdraw_from_staking_program(
+            program_id,
+            withdraw_amount,
+            lending_market_info,
+            lending_market_authority_info,
+            clock_info,
+            stake_account_info,
+            staking_pool_info,
+            staking_program_id,
+            *obligation_owner_info.key,
+        )?;
+    }
+
+    // the withdrawn collateral is redeemed straight out of the user's account, so nothing is
+    // left behind in it
+    _redeem_reserve_collateral(
+        program_id,
+        withdraw_amount,
+        user_collateral_info,
+        destination_liquidity_info,
+        withdraw_reserve_info,
+        reserve_collateral_mint_info,
+        reserve_liquidity_supply_info,
+        lending_market_info,
+        lending_market_authority_info,
+        obligation_owner_info,
+        clock,
+        token_program_id,
+    )
+}
+
 #[allow(clippy::too_many_arguments)]
 fn withdraw_obligation_collateral<'a>(
     program_id: &Pubkey,
@@ -1242,6 +1991,9 @@ fn withdraw_obligation_collateral<'a>(
     clock: &Clock,
     token_program_id: &AccountInfo<'a>,
 ) -> Result<u64, ProgramError> {
+    assert_writable(obligation_info, "Obligation")?;
+    assert_writable(source_collateral_info, "Withdraw reserve collateral supply")?;
+
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         msg!("Lending market provided is not owned by the lending program");
@@ -1259,11 +2011,11 @@ fn withdraw_obligation_collateral<'a>(
     }
     if &withdraw_reserve.lending_market != lending_market_info.key {
         msg!("Withdraw reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if &withdraw_reserve.collateral.supply_pubkey != source_collateral_info.key {
         msg!("Withdraw reserve collateral supply must be used as the source collateral provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongCollateralSupply.into());
     }
     if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
         msg!("Withdraw reserve collateral supply cannot be used as the destination collateral provided");
@@ -1281,7 +2033,7 @@ fn withdraw_obligation_collateral<'a>(
     }
     if &obligation.lending_market != lending_market_info.key {
         msg!("Obligation lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if &obligation.owner != obligation_owner_info.key {
         msg!("Obligation owner does not match the obligation owner provided");
@@ -1326,9 +2078,8 @@ fn withdraw_obligation_collateral<'a>(
         msg!("Obligation deposited value is zero");
         return Err(LendingError::ObligationDepositsZero.into());
     } else {
-        let max_withdraw_value = obligation.max_withdraw_value(Rate::from_percent(
-            withdraw_reserve.config.loan_to_value_ratio,
-        ))?;
+        let max_withdraw_value =
+            obligation.max_withdraw_value(withdraw_reserve.loan_to_value_rate())?;
         if max_withdraw_value == Decimal::zero() {
             msg!("Maximum withdraw value is zero");
             return Err(LendingError::WithdrawTooLarge.into());
@@ -1356,9 +2107,37 @@ fn withdraw_obligation_collateral<'a>(
             msg!("Withdraw amount is too small to transfer collateral");
             return Err(LendingError::WithdrawTooSmall.into());
         }
+
+        // recheck the obligation's health with the collateral removed, independently of how
+        // the withdraw amount was derived above
+        let withdraw_value = collateral
+            .market_value
+            .try_mul(Decimal::from(withdraw_amount).try_div(collateral.deposited_amount)?)?;
+        let withdraw_borrow_value =
+            withdraw_value.try_mul(withdraw_reserve.loan_to_value_rate())?;
+        if obligation.borrowed_value.try_add(withdraw_borrow_value)?
+            > obligation.allowed_borrow_value
+        {
+            msg!(
+                "Obligation borrowed value would exceed its allowed borrow value after withdrawing"
+            );
+            return Err(LendingError::WithdrawTooLarge.into());
+        }
         withdraw_amount
     };
 
+    let market_value = withdraw_reserve.collateral_market_value(
+        obligation.deposits[collateral_index]
+            .deposited_amount
+            .checked_sub(withdraw_amount)
+            .ok_or(LendingError::MathOverflow)?,
+    )?;
+    obligation.revalue_collateral(
+        collateral_index,
+        market_value,
+        withdraw_reserve.loan_to_value_rate(),
+        Rate::from_percent(withdraw_reserve.config.liquidation_threshold),
+    )?;
     obligation.withdraw(withdraw_amount, collateral_index)?;
     obligation.last_update.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
@@ -1447,6 +2226,7 @@ fn process_borrow_obligation_liquidity(
         return Err(LendingError::InvalidAmount.into());
     }
 
+    let (accounts, instructions_info) = split_instructions_sysvar(accounts);
     let account_info_iter = &mut accounts.iter();
     let source_liquidity_info = next_account_info(account_info_iter)?;
     let destination_liquidity_info = next_account_info(account_info_iter)?;
@@ -1459,6 +2239,14 @@ fn process_borrow_obligation_liquidity(
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
+    assert_writable(borrow_reserve_info, "Borrow reserve")?;
+    assert_writable(source_liquidity_info, "Borrow reserve liquidity supply")?;
+    assert_writable(
+        borrow_reserve_liquidity_fee_receiver_info,
+        "Borrow reserve liquidity fee receiver",
+    )?;
+    assert_writable(obligation_info, "Obligation")?;
+
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         msg!("Lending market provided is not owned by the lending program");
@@ -1476,11 +2264,11 @@ fn process_borrow_obligation_liquidity(
     }
     if &borrow_reserve.lending_market != lending_market_info.key {
         msg!("Borrow reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if &borrow_reserve.liquidity.supply_pubkey != source_liquidity_info.key {
         msg!("Borrow reserve liquidity supply must be used as the source liquidity provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongLiquiditySupply.into());
     }
     if &borrow_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
         msg!(
@@ -1490,12 +2278,24 @@ fn process_borrow_obligation_liquidity(
     }
     if &borrow_reserve.liquidity.fee_receiver != borrow_reserve_liquidity_fee_receiver_info.key {
         msg!("Borrow reserve liquidity fee receiver does not match the borrow reserve liquidity fee receiver provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongFeeReceiver.into());
     }
     if borrow_reserve.last_update.is_stale(clock.slot)? {
         msg!("Borrow reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if borrow_reserve.config.borrowing_disabled {
+        msg!("Borrow reserve only accepts deposits, borrowing is disabled");
+        return Err(LendingError::BorrowingDisabled.into());
+    }
+    if borrow_reserve.is_paused {
+        msg!("Borrow reserve is paused");
+        return Err(LendingError::ReservePaused.into());
+    }
+    if borrow_reserve.is_wind_down {
+        msg!("Borrow reserve is winding down and no longer lends liquidity");
+        return Err(LendingError::ReserveWindingDown.into());
+    }
 
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
@@ -1504,7 +2304,7 @@ fn process_borrow_obligation_liquidity(
     }
     if &obligation.lending_market != lending_market_info.key {
         msg!("Obligation lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if &obligation.owner != obligation_owner_info.key {
         msg!("Obligation owner does not match the obligation owner provided");
@@ -1526,6 +2326,14 @@ fn process_borrow_obligation_liquidity(
         msg!("Obligation deposits have zero value");
         return Err(LendingError::ObligationDepositsZero.into());
     }
+    if lending_market.strict_instruction_order {
+        assert_refreshed_immediately_before(
+            program_id,
+            instructions_info,
+            obligation_info.key,
+            obligation.deposits.len() + obligation.borrows.len(),
+        )?;
+    }
 
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
@@ -1540,7 +2348,41 @@ fn process_borrow_obligation_liquidity(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    let remaining_borrow_value = obligation.remaining_borrow_value()?;
+    // a borrow bound to collateral can't draw on any other collateral, even while the other
+    // collateral would cover the obligation as a whole
+    let bound_collateral_reserve = obligation
+        .find_liquidity_in_borrows(*borrow_reserve_info.key)
+        .map_or(COption::None, |(liquidity, _)| liquidity.collateral_reserve);
+    let remaining_bound_borrow_value = match bound_collateral_reserve {
+        COption::Some(collateral_reserve) => {
+            let deposit_reserve_info = next_account_info(account_info_iter)?;
+            if deposit_reserve_info.key != &collateral_reserve {
+                msg!("Deposit reserve provided does not match the collateral reserve the borrow is bound to");
+                return Err(LendingError::InvalidAccountInput.into());
+            }
+            if deposit_reserve_info.owner != program_id {
+                msg!("Deposit reserve provided is not owned by the lending program");
+                return Err(LendingError::InvalidAccountOwner.into());
+            }
+            let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
+            if deposit_reserve.last_update.is_stale(clock.slot)? {
+                msg!("Deposit reserve is stale and must be refreshed in the current slot");
+                return Err(LendingError::ReserveStale.into());
+            }
+            let (collateral, _) = obligation.find_collateral_in_deposits(collateral_reserve)?;
+            let allowed_value = collateral
+                .market_value
+                .try_mul(deposit_reserve.loan_to_value_rate())?;
+            Some(obligation.remaining_bound_borrow_value(&collateral_reserve, allowed_value)?)
+        }
+        COption::None => None,
+    };
+    let stake_account_info = next_account_info(account_info_iter).ok();
+
+    let mut remaining_borrow_value = obligation.remaining_borrow_value()?;
+    if let Some(remaining_bound_borrow_value) = remaining_bound_borrow_value {
+        remaining_borrow_value = remaining_borrow_value.min(remaining_bound_borrow_value);
+    }
     if remaining_borrow_value == Decimal::zero() {
         msg!("Remaining borrow value is zero");
         return Err(LendingError::BorrowTooLarge.into());
@@ -1551,28 +2393,59 @@ fn process_borrow_obligation_liquidity(
         receive_amount,
         borrow_fee,
         host_fee: _,
-    } = borrow_reserve.calculate_borrow(liquidity_amount, remaining_borrow_value)?;
+    } = borrow_reserve.calculate_borrow(
+        liquidity_amount,
+        remaining_borrow_value,
+        borrow_fee_discount_percentage(&borrow_reserve, &obligation.owner, stake_account_info),
+    )?;
 
     if receive_amount == 0 {
         msg!("Borrow amount is too small to receive liquidity after fees");
         return Err(LendingError::BorrowTooSmall.into());
     }
 
+    // recheck the obligation's health with the new borrow added, independently of how the
+    // borrow amount was derived above
+    // @TODO: add lookup table https://git.io/JOCYq
+    let decimals = 10u64
+        .checked_pow(borrow_reserve.liquidity.mint_decimals as u32)
+        .ok_or(LendingError::MathOverflow)?;
+    let borrow_value = borrow_amount
+        .try_mul(borrow_reserve.liquidity.market_price)?
+        .try_div(decimals)?;
+    if obligation.borrowed_value.try_add(borrow_value)? > obligation.allowed_borrow_value {
+        msg!("Obligation borrowed value would exceed its allowed borrow value after borrowing");
+        return Err(LendingError::BorrowTooLarge.into());
+    }
+    if let Some(remaining_bound_borrow_value) = remaining_bound_borrow_value {
+        if borrow_value > remaining_bound_borrow_value {
+            msg!("Borrow value would exceed the allowed borrow value of the collateral it is bound to");
+            return Err(LendingError::BorrowTooLarge.into());
+        }
+    }
+    borrow_reserve.check_isolated_debt_ceiling(borrow_amount)?;
+
     let cumulative_borrow_rate_wads = borrow_reserve.liquidity.cumulative_borrow_rate_wads;
 
     borrow_reserve.liquidity.borrow(borrow_amount)?;
     borrow_reserve.last_update.mark_stale();
-    Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
 
-    let obligation_liquidity =
-        obligation.find_or_add_liquidity_to_borrows(*borrow_reserve_info.key)?;
+    let obligation_liquidity = obligation.find_or_add_liquidity_to_borrows(
+        *borrow_reserve_info.key,
+        lending_market.obligation_reserve_limit(),
+    )?;
     if obligation_liquidity.cumulative_borrow_rate_wads == Decimal::zero() {
         obligation_liquidity.cumulative_borrow_rate_wads = cumulative_borrow_rate_wads;
     }
 
     obligation_liquidity.borrow(borrow_amount)?;
+    let (liquidity, liquidity_index) =
+        obligation.find_liquidity_in_borrows(*borrow_reserve_info.key)?;
+    let market_value = borrow_reserve.liquidity_market_value(liquidity.borrowed_amount_wads)?;
+    obligation.revalue_liquidity(liquidity_index, market_value)?;
     obligation.last_update.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+    Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
 
     if borrow_fee > 0 {
         spl_token_transfer(TokenTransferParams {
@@ -1595,7 +2468,336 @@ fn process_borrow_obligation_liquidity(
     })
 }
 
-#[inline(never)] // avoid stack frame limit
+fn process_bind_borrow_collateral(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter).ok();
+
+    assert_writable(obligation_info, "Obligation")?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let (_, liquidity_index) = obligation.find_liquidity_in_borrows(*borrow_reserve_info.key)?;
+    let collateral_reserve = match deposit_reserve_info {
+        Some(deposit_reserve_info) => {
+            obligation.find_collateral_in_deposits(*deposit_reserve_info.key)?;
+            COption::Some(*deposit_reserve_info.key)
+        }
+        None => COption::None,
+    };
+
+    obligation.borrows[liquidity_index].collateral_reserve = collateral_reserve;
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_migrate_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    migrate_account::<Obligation>(program_id, accounts, "Obligation")
+}
+
+fn process_migrate_reserve(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    migrate_account::<Reserve>(program_id, accounts, "Reserve")
+}
+
+/// Realloc an account in a legacy layout to the current length and repack it. Unpacking reads the
+/// legacy layout, so the fields appended since are packed with their defaults.
+fn migrate_account<T: Pack + IsInitialized>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    name: &str,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let account_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    assert_writable(account_info, name)?;
+    if account_info.owner != program_id {
+        msg!("{} provided is not owned by the lending program", name);
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if account_info.data_len() == T::LEN {
+        msg!("{} is already migrated", name);
+        return Ok(());
+    }
+    if system_program_info.key != &system_program::id() {
+        msg!("System program provided is not the system program");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if !payer_info.is_signer {
+        msg!("Payer provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let unpacked = T::unpack(&account_info.data.borrow())?;
+
+    let rent_shortfall = rent
+        .minimum_balance(T::LEN)
+        .saturating_sub(account_info.lamports());
+    if rent_shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer_info.key, account_info.key, rent_shortfall),
+            &[
+                payer_info.clone(),
+                account_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+
+    account_info.realloc(T::LEN, true)?;
+    T::pack(unpacked, &mut account_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_split_obligation(
+    program_id: &Pubkey,
+    split_percent: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if split_percent == 0 || split_percent >= 100 {
+        msg!("Split percentage must be between 1 and 99");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_obligation_info = next_account_info(account_info_iter)?;
+    let destination_obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let reserve_infos = account_info_iter.as_slice();
+
+    let (lending_market, mut source_obligation, mut destination_obligation) =
+        unpack_obligation_pair(
+            program_id,
+            source_obligation_info,
+            destination_obligation_info,
+            lending_market_info,
+            obligation_owner_info,
+        )?;
+
+    source_obligation.split_off(
+        &mut destination_obligation,
+        Rate::from_percent(split_percent),
+        lending_market.obligation_reserve_limit(),
+    )?;
+    revalue_moved_obligation(program_id, &mut source_obligation, reserve_infos, clock)?;
+    revalue_moved_obligation(
+        program_id,
+        &mut destination_obligation,
+        reserve_infos,
+        clock,
+    )?;
+
+    Obligation::pack(
+        source_obligation,
+        &mut source_obligation_info.data.borrow_mut(),
+    )?;
+    Obligation::pack(
+        destination_obligation,
+        &mut destination_obligation_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_merge_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let source_obligation_info = next_account_info(account_info_iter)?;
+    let destination_obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let reserve_infos = account_info_iter.as_slice();
+
+    let (lending_market, mut source_obligation, mut destination_obligation) =
+        unpack_obligation_pair(
+            program_id,
+            source_obligation_info,
+            destination_obligation_info,
+            lending_market_info,
+            obligation_owner_info,
+        )?;
+
+    destination_obligation.merge(
+        &mut source_obligation,
+        lending_market.obligation_reserve_limit(),
+    )?;
+    revalue_moved_obligation(program_id, &mut source_obligation, reserve_infos, clock)?;
+    revalue_moved_obligation(
+        program_id,
+        &mut destination_obligation,
+        reserve_infos,
+        clock,
+    )?;
+
+    Obligation::pack(
+        source_obligation,
+        &mut source_obligation_info.data.borrow_mut(),
+    )?;
+    Obligation::pack(
+        destination_obligation,
+        &mut destination_obligation_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+/// Unpack two distinct obligations of the same lending market and owner, for the owner to move
+/// deposits and borrows between them
+fn unpack_obligation_pair<'a>(
+    program_id: &Pubkey,
+    source_obligation_info: &AccountInfo<'a>,
+    destination_obligation_info: &AccountInfo<'a>,
+    lending_market_info: &AccountInfo,
+    obligation_owner_info: &AccountInfo,
+) -> Result<(LendingMarket, Obligation, Obligation), ProgramError> {
+    assert_writable(source_obligation_info, "Source obligation")?;
+    assert_writable(destination_obligation_info, "Destination obligation")?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if source_obligation_info.key == destination_obligation_info.key {
+        msg!("Source and destination obligations must be different");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let mut obligations = Vec::with_capacity(2);
+    for obligation_info in [source_obligation_info, destination_obligation_info] {
+        let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+        if obligation_info.owner != program_id {
+            msg!("Obligation provided is not owned by the lending program");
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        if &obligation.lending_market != lending_market_info.key {
+            msg!("Obligation lending market does not match the lending market provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &obligation.owner != obligation_owner_info.key {
+            msg!("Obligation owner does not match the obligation owner provided");
+            return Err(LendingError::InvalidObligationOwner.into());
+        }
+        obligations.push(obligation);
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let destination_obligation = obligations.pop().unwrap();
+    let source_obligation = obligations.pop().unwrap();
+    Ok((lending_market, source_obligation, destination_obligation))
+}
+
+/// Refresh an obligation whose deposits and borrows were just moved, from refreshed reserves
+/// found by key among `reserve_infos`, and check it is still healthy
+fn revalue_moved_obligation(
+    program_id: &Pubkey,
+    obligation: &mut Obligation,
+    reserve_infos: &[AccountInfo],
+    clock: &Clock,
+) -> ProgramResult {
+    let find_reserve = |reserve_pubkey: &Pubkey| -> Result<Reserve, ProgramError> {
+        let reserve_info = match reserve_infos.iter().find(|info| info.key == reserve_pubkey) {
+            Some(reserve_info) => reserve_info,
+            None => {
+                msg!("Obligation reserve {} was not provided", reserve_pubkey);
+                return Err(LendingError::InvalidAccountInput.into());
+            }
+        };
+        if reserve_info.owner != program_id {
+            msg!(
+                "Obligation reserve {} is not owned by the lending program",
+                reserve_pubkey
+            );
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+        if reserve.last_update.is_stale(clock.slot)? {
+            msg!(
+                "Obligation reserve {} is stale and must be refreshed in the current slot",
+                reserve_pubkey
+            );
+            return Err(LendingError::ReserveStale.into());
+        }
+        Ok(reserve)
+    };
+
+    let mut deposited_value = Decimal::zero();
+    let mut borrowed_value = Decimal::zero();
+    let mut collateral_values = Vec::with_capacity(obligation.deposits.len());
+    let mut borrow_reserve_paused = false;
+
+    for collateral in obligation.deposits.iter_mut() {
+        let deposit_reserve = find_reserve(&collateral.deposit_reserve)?;
+        let market_value = deposit_reserve.collateral_market_value(collateral.deposited_amount)?;
+        collateral.market_value = market_value;
+
+        deposited_value = deposited_value.try_add(market_value)?;
+        collateral_values.push((
+            market_value.try_mul(deposit_reserve.loan_to_value_rate())?,
+            market_value.try_mul(Rate::from_percent(
+                deposit_reserve.config.liquidation_threshold,
+            ))?,
+        ));
+    }
+
+    for liquidity in obligation.borrows.iter_mut() {
+        let borrow_reserve = find_reserve(&liquidity.borrow_reserve)?;
+        liquidity.accrue_interest(borrow_reserve.liquidity.cumulative_borrow_rate_wads)?;
+        borrow_reserve_paused |= borrow_reserve.is_paused;
+        let market_value = borrow_reserve.liquidity_market_value(liquidity.borrowed_amount_wads)?;
+        liquidity.market_value = market_value;
+
+        borrowed_value = borrowed_value.try_add(market_value)?;
+    }
+
+    let (mut allowed_borrow_value, unhealthy_borrow_value) =
+        obligation.calculate_borrowing_power(&collateral_values)?;
+    if borrow_reserve_paused {
+        // same as refreshing, debt in a paused reserve leaves the obligation no borrowing power
+        allowed_borrow_value = Decimal::zero();
+    }
+
+    obligation.deposited_value = deposited_value;
+    obligation.borrowed_value = borrowed_value;
+    obligation.allowed_borrow_value = allowed_borrow_value;
+    obligation.unhealthy_borrow_value = unhealthy_borrow_value;
+    if obligation.is_unhealthy() {
+        msg!("Obligation would be unhealthy after moving its deposits and borrows");
+        return Err(LendingError::ObligationUnhealthy.into());
+    }
+    obligation.update_unhealthy_slot(clock.slot);
+
+    obligation.last_update.update_slot(clock.slot);
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
 fn process_repay_obligation_liquidity(
     program_id: &Pubkey,
     liquidity_amount: u64,
@@ -1616,6 +2818,10 @@ fn process_repay_obligation_liquidity(
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
+    assert_writable(repay_reserve_info, "Repay reserve")?;
+    assert_writable(destination_liquidity_info, "Repay reserve liquidity supply")?;
+    assert_writable(obligation_info, "Obligation")?;
+
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         msg!("Lending market provided is not owned by the lending program");
@@ -1633,7 +2839,7 @@ fn process_repay_obligation_liquidity(
     }
     if &repay_reserve.lending_market != lending_market_info.key {
         msg!("Repay reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
         msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
@@ -1641,7 +2847,7 @@ fn process_repay_obligation_liquidity(
     }
     if &repay_reserve.liquidity.supply_pubkey != destination_liquidity_info.key {
         msg!("Repay reserve liquidity supply must be used as the destination liquidity provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongLiquiditySupply.into());
     }
     if repay_reserve.last_update.is_stale(clock.slot)? {
         msg!("Repay reserve is stale and must be refreshed in the current slot");
@@ -1655,7 +2861,7 @@ fn process_repay_obligation_liquidity(
     }
     if &obligation.lending_market != lending_market_info.key {
         msg!("Obligation lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if obligation.last_update.is_stale(clock.slot)? {
         msg!("Obligation is stale and must be refreshed in the current slot");
@@ -1679,14 +2885,17 @@ fn process_repay_obligation_liquidity(
         return Err(LendingError::RepayTooSmall.into());
     }
 
-    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
-    repay_reserve.last_update.mark_stale();
-    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
-
+    let market_value = repay_reserve
+        .liquidity_market_value(liquidity.borrowed_amount_wads.try_sub(settle_amount)?)?;
+    obligation.revalue_liquidity(liquidity_index, market_value)?;
     obligation.repay(settle_amount, liquidity_index)?;
     obligation.last_update.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
+    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+    repay_reserve.last_update.mark_stale();
+    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+
     spl_token_transfer(TokenTransferParams {
         source: source_liquidity_info.clone(),
         destination: destination_liquidity_info.clone(),
@@ -1708,6 +2917,7 @@ fn process_liquidate_obligation(
         return Err(LendingError::InvalidAmount.into());
     }
 
+    let (accounts, instructions_info) = split_instructions_sysvar(accounts);
     let account_info_iter = &mut accounts.iter().peekable();
     let source_liquidity_info = next_account_info(account_info_iter)?;
     let destination_collateral_info = next_account_info(account_info_iter)?;
@@ -1723,6 +2933,17 @@ fn process_liquidate_obligation(
     let clock = &Clock::from_account_info(clock_info)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
+    assert_writable(repay_reserve_info, "Repay reserve")?;
+    assert_writable(
+        repay_reserve_liquidity_supply_info,
+        "Repay reserve liquidity supply",
+    )?;
+    assert_writable(
+        withdraw_reserve_collateral_supply_info,
+        "Withdraw reserve collateral supply",
+    )?;
+    assert_writable(obligation_info, "Obligation")?;
+
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         msg!("Lending market provided is not owned by the lending program");
@@ -1740,11 +2961,11 @@ fn process_liquidate_obligation(
     }
     if &repay_reserve.lending_market != lending_market_info.key {
         msg!("Repay reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
         msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongLiquiditySupply.into());
     }
     if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
         msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
@@ -1760,6 +2981,10 @@ fn process_liquidate_obligation(
         msg!("Repay reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if repay_reserve.is_paused {
+        msg!("Repay reserve is paused, liquidations are blocked");
+        return Err(LendingError::ReservePaused.into());
+    }
 
     let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
     if withdraw_reserve_info.owner != program_id {
@@ -1768,11 +2993,11 @@ fn process_liquidate_obligation(
     }
     if &withdraw_reserve.lending_market != lending_market_info.key {
         msg!("Withdraw reserve lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
         msg!("Withdraw reserve collateral supply does not match the withdraw reserve collateral supply provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongCollateralSupply.into());
     }
     if &withdraw_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
         msg!("Withdraw reserve liquidity supply cannot be used as the source liquidity provided");
@@ -1786,6 +3011,10 @@ fn process_liquidate_obligation(
         msg!("Withdraw reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if withdraw_reserve.is_paused {
+        msg!("Withdraw reserve is paused, liquidations are blocked");
+        return Err(LendingError::ReservePaused.into());
+    }
 
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
@@ -1794,12 +3023,18 @@ fn process_liquidate_obligation(
     }
     if &obligation.lending_market != lending_market_info.key {
         msg!("Obligation lending market does not match the lending market provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::LendingMarketMismatch.into());
     }
     if obligation.last_update.is_stale(clock.slot)? {
         msg!("Obligation is stale and must be refreshed in the current slot");
         return Err(LendingError::ObligationStale.into());
     }
+    // a conservative refresh undervalues the obligation in its owner's disfavor, which must not
+    // leave it open to liquidation
+    if obligation.has_zero_valued_collateral() {
+        msg!("Obligation has collateral valued at zero and must be refreshed with all its deposit reserves fresh");
+        return Err(LendingError::ObligationStale.into());
+    }
     if obligation.deposited_value == Decimal::zero() {
         msg!("Obligation deposited value is zero");
         return Err(LendingError::ObligationDepositsZero.into());
@@ -1812,16 +3047,41 @@ fn process_liquidate_obligation(
         msg!("Obligation is healthy and cannot be liquidated");
         return Err(LendingError::ObligationHealthy.into());
     }
+    let unhealthy_slots = clock.slot.saturating_sub(obligation.became_unhealthy_slot);
+    if unhealthy_slots < lending_market.liquidation_grace_slots {
+        msg!(
+            "Obligation has been unhealthy for {} slots, it can be liquidated after {}",
+            unhealthy_slots,
+            lending_market.liquidation_grace_slots
+        );
+        return Err(LendingError::LiquidationNotYetAllowed.into());
+    }
+    if lending_market.strict_instruction_order {
+        assert_refreshed_immediately_before(
+            program_id,
+            instructions_info,
+            obligation_info.key,
+            obligation.deposits.len() + obligation.borrows.len(),
+        )?;
+    }
 
-    let (liquidity, liquidity_index) =
-        obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
+    let (liquidity, liquidity_index) = obligation
+        .find_liquidity_in_borrows(*repay_reserve_info.key)
+        .map_err(|_| {
+            msg!("Obligation has no borrow from the repay reserve provided");
+            LendingError::ObligationLiquidityEmpty
+        })?;
     if liquidity.market_value == Decimal::zero() {
         msg!("Obligation borrow value is zero");
         return Err(LendingError::ObligationLiquidityEmpty.into());
     }
 
-    let (collateral, collateral_index) =
-        obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
+    let (collateral, collateral_index) = obligation
+        .find_collateral_in_deposits(*withdraw_reserve_info.key)
+        .map_err(|_| {
+            msg!("Obligation has no deposit in the withdraw reserve provided");
+            LendingError::ObligationCollateralEmpty
+        })?;
     if collateral.market_value == Decimal::zero() {
         msg!("Obligation deposit value is zero");
         return Err(LendingError::ObligationCollateralEmpty.into());
@@ -1840,14 +3100,22 @@ fn process_liquidate_obligation(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    if withdraw_reserve.config.deposit_staking_pool.is_some() ^ account_info_iter.peek().is_some() {
-        msg!(
-            "This reserve has corresponded staking pool, \
-        a stake pool and stake account must be passed in when depositing"
-        );
-        return Err(LendingError::InvalidStakingPool.into());
+    match (
+        withdraw_reserve.config.deposit_staking_pool.is_some(),
+        account_info_iter.peek().is_some(),
+    ) {
+        (true, false) => {
+            msg!("This reserve has corresponded staking pool, a stake pool and stake account must be passed in when liquidating");
+            return Err(LendingError::MissingStakingAccounts.into());
+        }
+        (false, true) => {
+            msg!("This reserve has no staking pool, a stake pool and stake account must not be passed in when liquidating");
+            return Err(LendingError::InvalidStakingPool.into());
+        }
+        _ => {}
     }
 
+    let max_liquidation_amount = obligation.max_liquidation_amount(liquidity)?;
     let CalculateLiquidationResult {
         settle_amount,
         repay_amount,
@@ -1857,6 +3125,7 @@ fn process_liquidate_obligation(
         &obligation,
         liquidity,
         collateral,
+        clock.slot,
     )?;
 
     if repay_amount == 0 {
@@ -1867,6 +3136,22 @@ fn process_liquidate_obligation(
         msg!("Liquidation is too small to receive collateral");
         return Err(LendingError::LiquidationTooSmall.into());
     }
+    // a liquidation closing out the borrow or the collateral, or repaying as much as the close
+    // factor allows, can't be any larger, so only smaller ones are held to the market minimum
+    if settle_amount < liquidity.borrowed_amount_wads
+        && settle_amount < max_liquidation_amount
+        && withdraw_amount < collateral.deposited_amount
+    {
+        let liquidation_value = repay_reserve.liquidity_market_value(settle_amount)?;
+        if liquidation_value < lending_market.min_liquidation_value {
+            msg!(
+                "Liquidation repays a value of {}, below the market minimum of {}",
+                liquidation_value,
+                lending_market.min_liquidation_value
+            );
+            return Err(LendingError::LiquidationTooSmall.into());
+        }
+    }
 
     repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
     repay_reserve.last_update.mark_stale();
@@ -1948,6 +3233,13 @@ fn process_flash_loan(
     let token_program_id = next_account_info(account_info_iter)?;
     let flash_loan_receiver_program_id = next_account_info(account_info_iter)?;
 
+    assert_writable(reserve_info, "Reserve")?;
+    assert_writable(source_liquidity_info, "Reserve liquidity supply")?;
+    assert_writable(
+        reserve_liquidity_fee_receiver_info,
+        "Reserve liquidity fee receiver",
+    )?;
+
     if program_id == flash_loan_receiver_program_id.key {
         msg!("Lending program cannot be used as the flash loan receiver program provided");
         return Err(LendingError::InvalidFlashLoanReceiverProgram.into());
@@ -1986,11 +3278,11 @@ fn process_flash_loan(
     }
     if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
         msg!("Reserve liquidity supply must be used as the source liquidity provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongLiquiditySupply.into());
     }
     if &reserve.liquidity.fee_receiver != reserve_liquidity_fee_receiver_info.key {
         msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
-        return Err(LendingError::InvalidAccountInput.into());
+        return Err(LendingError::WrongFeeReceiver.into());
     }
 
     // @FIXME: if u64::MAX is flash loaned, fees should be inclusive as with ordinary borrows
@@ -2134,13 +3426,48 @@ fn process_deposit_reserve_liquidity_and_obligation_collateral(
 
     let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
 
-    if reserve.config.deposit_staking_pool.is_some() ^ account_info_iter.peek().is_some() {
-        msg!(
-            "This reserve has corresponded staking pool, \
-        a stake pool and stake account must be passed in when depositing"
-        );
-        return Err(LendingError::InvalidStakingPool.into());
-    }
+    match (
+        reserve.config.deposit_staking_pool.is_some(),
+        account_info_iter.peek().is_some(),
+    ) {
+        (true, false) => {
+            msg!("This reserve has corresponded staking pool, a stake pool and stake account must be passed in when depositing");
+            return Err(LendingError::MissingStakingAccounts.into());
+        }
+        (false, true) => {
+            msg!("This reserve has no staking pool, a stake pool and stake account must not be passed in when depositing");
+            return Err(LendingError::InvalidStakingPool.into());
+        }
+        _ => {}
+    }
+
+    // validate the stake account before any tokens move, rather than after the deposit
+    let staking_accounts = if account_info_iter.peek().is_some() {
+        let (stake_account_info, staking_pool_info, staking_program_id) = match (
+            account_info_iter.next(),
+            account_info_iter.next(),
+            account_info_iter.next(),
+        ) {
+            (Some(stake_account_info), Some(staking_pool_info), Some(staking_program_id)) => {
+                (stake_account_info, staking_pool_info, staking_program_id)
+            }
+            _ => {
+                msg!("A stake account, staking pool and staking program must all be passed in when depositing");
+                return Err(LendingError::MissingStakingAccounts.into());
+            }
+        };
+
+        let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+        validate_stake_account(
+            stake_account_info,
+            staking_pool_info,
+            reserve.config.deposit_staking_pool,
+            &obligation.owner,
+        )?;
+        Some((stake_account_info, staking_pool_info, staking_program_id))
+    } else {
+        None
+    };
 
     let collateral_amount = _deposit_reserve_liquidity(
         program_id,
@@ -2159,7 +3486,6 @@ fn process_deposit_reserve_liquidity_and_obligation_collateral(
 
     reserve = Reserve::unpack(&reserve_info.data.borrow())?;
     reserve.last_update.update_slot(clock.slot);
-    let reserve_deposit_pool = reserve.config.deposit_staking_pool;
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
     deposit_obligation_collateral(
@@ -2177,15 +3503,7 @@ fn process_deposit_reserve_liquidity_and_obligation_collateral(
         token_program_id,
     )?;
 
-    if account_info_iter.peek().is_some() {
-        let stake_account_info = next_account_info(account_info_iter)?;
-        let staking_pool_info = next_account_info(account_info_iter)?;
-        let staking_program_id = next_account_info(account_info_iter)?;
-
-        if reserve_deposit_pool.map_or(true, |k| k != *staking_pool_info.key) {
-            msg!("Invalid staking pool, not the one corresponded to the reserve");
-            return Err(LendingError::InvalidStakingPool.into());
-        }
+    if let Some((stake_account_info, staking_pool_info, staking_program_id)) = staking_accounts {
         deposit_to_staking_program(
             program_id,
             collateral_amount,
@@ -2202,12 +3520,152 @@ fn process_deposit_reserve_liquidity_and_obligation_collateral(
     }
 }
 
-fn process_update_reserve(
+/// Check that a stake account passed in alongside a deposit is in the reserve's deposit staking
+/// pool and owned by the obligation owner
+fn validate_stake_account(
+    stake_account_info: &AccountInfo,
+    staking_pool_info: &AccountInfo,
+    deposit_staking_pool: COption<Pubkey>,
+    obligation_owner: &Pubkey,
+) -> ProgramResult {
+    if deposit_staking_pool.map_or(true, |k| k != *staking_pool_info.key) {
+        msg!("Invalid staking pool, not the one corresponded to the reserve");
+        return Err(LendingError::InvalidStakingPool.into());
+    }
+
+    let stake_account = StakeAccount::unpack(&stake_account_info.data.borrow())?;
+    if stake_account.pool_pubkey != *staking_pool_info.key {
+        msg!("Stake account is not in the reserve deposit staking pool");
+        return Err(LendingError::StakeAccountPoolMismatch.into());
+    }
+    if stake_account.owner != *obligation_owner {
+        msg!("Stake account owner does not match the obligation owner");
+        return Err(LendingError::InvalidStakeAccount.into());
+    }
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_deposit_and_borrow(
     program_id: &Pubkey,
-    config: ReserveConfig,
+    liquidity_amount: u64,
+    borrow_amount: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    validate_reserve_config(config)?;
+    if liquidity_amount == 0 || borrow_amount == 0 {
+        msg!("Liquidity amount and borrow amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let (accounts, instructions_info) = split_instructions_sysvar(accounts);
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let user_collateral_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let destination_collateral_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+
+    process_deposit_reserve_liquidity_and_obligation_collateral(
+        program_id,
+        liquidity_amount,
+        &[
+            source_liquidity_info.clone(),
+            user_collateral_info.clone(),
+            deposit_reserve_info.clone(),
+            deposit_reserve_liquidity_supply_info.clone(),
+            deposit_reserve_collateral_mint_info.clone(),
+            lending_market_info.clone(),
+            lending_market_authority_info.clone(),
+            destination_collateral_info.clone(),
+            obligation_info.clone(),
+            obligation_owner_info.clone(),
+            user_transfer_authority_info.clone(),
+            clock_info.clone(),
+            token_program_id.clone(),
+        ],
+    )?;
+
+    let mut refresh_obligation_accounts = vec![obligation_info.clone(), clock_info.clone()];
+    refresh_obligation_accounts.extend(account_info_iter.cloned());
+    process_refresh_obligation(program_id, &refresh_obligation_accounts)?;
+
+    let mut borrow_accounts = vec![
+        borrow_reserve_liquidity_supply_info.clone(),
+        destination_liquidity_info.clone(),
+        borrow_reserve_info.clone(),
+        borrow_reserve_liquidity_fee_receiver_info.clone(),
+        obligation_info.clone(),
+        lending_market_info.clone(),
+        lending_market_authority_info.clone(),
+        obligation_owner_info.clone(),
+        clock_info.clone(),
+        token_program_id.clone(),
+    ];
+    borrow_accounts.extend(instructions_info.cloned());
+    process_borrow_obligation_liquidity(program_id, borrow_amount, &borrow_accounts)
+}
+
+fn process_bootstrap_obligation(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    borrow_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 || borrow_amount == 0 {
+        msg!("Liquidity amount and borrow amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let (accounts, instructions_info) = split_instructions_sysvar(accounts);
+    let account_info_iter = &mut accounts.iter();
+    let mut deposit_and_borrow_accounts = (0..17)
+        .map(|_| next_account_info(account_info_iter).cloned())
+        .collect::<Result<Vec<_>, _>>()?;
+    let rent_info = next_account_info(account_info_iter)?;
+
+    process_init_obligation(
+        program_id,
+        &[
+            // obligation, lending market, obligation owner, clock and token program
+            deposit_and_borrow_accounts[8].clone(),
+            deposit_and_borrow_accounts[5].clone(),
+            deposit_and_borrow_accounts[9].clone(),
+            deposit_and_borrow_accounts[11].clone(),
+            rent_info.clone(),
+            deposit_and_borrow_accounts[12].clone(),
+        ],
+    )?;
+
+    // the fresh obligation only holds the deposit reserve when refreshed before the borrow
+    let deposit_reserve_info = deposit_and_borrow_accounts[2].clone();
+    deposit_and_borrow_accounts.push(deposit_reserve_info);
+    deposit_and_borrow_accounts.extend(instructions_info.cloned());
+    process_deposit_and_borrow(
+        program_id,
+        liquidity_amount,
+        borrow_amount,
+        &deposit_and_borrow_accounts,
+    )
+}
+
+fn process_update_reserve(
+    program_id: &Pubkey,
+    config: ReserveConfig,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    config.validate()?;
 
     let account_info_iter = &mut accounts.iter();
     let reserve_info = next_account_info(account_info_iter)?;
@@ -2218,6 +3676,8 @@ fn process_update_reserve(
     let rent = &Rent::from_account_info(rent_info)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
+    assert_writable(reserve_info, "Reserve")?;
+
     assert_rent_exempt(rent, reserve_info)?;
     let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
 
@@ -2261,6 +3721,9 @@ fn process_update_reserve(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
+    if reserve.config.deposit_staking_pool != config.deposit_staking_pool {
+        reserve.previous_deposit_staking_pool = reserve.config.deposit_staking_pool;
+    }
     reserve.config = config;
     msg!("Updated reserve config.");
 
@@ -2281,6 +3744,8 @@ fn process_withdraw_fee(program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
     let rent = &Rent::from_account_info(rent_info)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
+    assert_writable(reserve_fee_token_info, "Reserve liquidity fee receiver")?;
+
     assert_rent_exempt(rent, reserve_info)?;
     let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
 
@@ -2341,6 +3806,332 @@ fn process_withdraw_fee(program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
     })
 }
 
+fn process_unpause_reserve(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    assert_writable(reserve_info, "Reserve")?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    reserve.is_paused = false;
+    // the oracle price must be checked again before the reserve can be used
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_reserve_wind_down(
+    program_id: &Pubkey,
+    wind_down: bool,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    assert_writable(reserve_info, "Reserve")?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    reserve.is_wind_down = wind_down;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_host_fee(
+    program_id: &Pubkey,
+    percentage: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if percentage > 100 {
+        msg!("Host fee percentage must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    assert_writable(reserve_info, "Reserve")?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    reserve.config.fees.host_fee_percentage = percentage;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_reserve_fee_receiver(
+    program_id: &Pubkey,
+    new_receiver: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let new_receiver_info = next_account_info(account_info_iter)?;
+
+    assert_writable(reserve_info, "Reserve")?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    if new_receiver_info.key != &new_receiver {
+        msg!("New fee receiver does not match the fee receiver account provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if new_receiver == reserve.liquidity.supply_pubkey {
+        msg!("Reserve liquidity supply cannot be used as the fee receiver");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if new_receiver_info.owner != &lending_market.token_program_id {
+        msg!("New fee receiver is not owned by the lending market token program");
+        return Err(LendingError::InvalidTokenOwner.into());
+    }
+    let new_receiver_account = Account::unpack(&new_receiver_info.data.borrow())
+        .map_err(|_| LendingError::InvalidTokenAccount)?;
+    if new_receiver_account.mint != reserve.liquidity.mint_pubkey {
+        msg!("New fee receiver mint does not match the reserve liquidity mint");
+        return Err(LendingError::InvalidTokenMint.into());
+    }
+    let lending_market_authority_pubkey = Pubkey::create_program_address(
+        &[
+            lending_market_info.key.as_ref(),
+            &[lending_market.bump_seed],
+        ],
+        program_id,
+    )?;
+    if new_receiver_account.owner != lending_market_authority_pubkey {
+        msg!("New fee receiver must be owned by the derived lending market authority");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    reserve.liquidity.fee_receiver = new_receiver;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_migrate_stake_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let clock_info = next_account_info(account_info_iter)?;
+    let old_stake_account_info = next_account_info(account_info_iter)?;
+    let old_staking_pool_info = next_account_info(account_info_iter)?;
+    let new_stake_account_info = next_account_info(account_info_iter)?;
+    let new_staking_pool_info = next_account_info(account_info_iter)?;
+    let staking_program_id = next_account_info(account_info_iter)?;
+
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
+    if deposit_reserve_info.owner != program_id {
+        msg!("Deposit reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &deposit_reserve.lending_market != lending_market_info.key {
+        msg!("Deposit reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let (collateral, _collateral_index) =
+        obligation.find_collateral_in_deposits(*deposit_reserve_info.key)?;
+    if collateral.deposited_amount == 0 {
+        msg!("Collateral deposited amount is zero");
+        return Err(LendingError::ObligationCollateralEmpty.into());
+    }
+
+    if deposit_reserve
+        .config
+        .deposit_staking_pool
+        .map_or(true, |k| k != *new_staking_pool_info.key)
+    {
+        msg!("Invalid staking pool, not the one corresponded to the reserve");
+        return Err(LendingError::InvalidStakingPool.into());
+    }
+    if old_staking_pool_info.key == new_staking_pool_info.key {
+        msg!("Stake account is already in the staking pool corresponded to the reserve");
+        return Err(LendingError::InvalidStakingPool.into());
+    }
+    if deposit_reserve.previous_deposit_staking_pool != COption::Some(*old_staking_pool_info.key) {
+        msg!("Previous staking pool provided is not the one the reserve used before");
+        return Err(LendingError::InvalidStakingPool.into());
+    }
+    if staking_program_id.key != &port_finance_staking::id() {
+        msg!("Staking program provided is not the Port Finance staking program");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    // The stake account only holds what was staked before the pool changed, deposits since
+    // then are already staked in the new pool
+    if old_stake_account_info.owner != staking_program_id.key {
+        msg!("Stake account in the previous staking pool is not owned by the staking program");
+        return Err(LendingError::InvalidStakeAccount.into());
+    }
+    let old_stake_account = StakeAccount::unpack(&old_stake_account_info.data.borrow())?;
+    if &old_stake_account.pool_pubkey != old_staking_pool_info.key {
+        msg!("Stake account provided is not in the previous staking pool");
+        return Err(LendingError::InvalidStakeAccount.into());
+    }
+    if old_stake_account.deposited_amount == 0 {
+        msg!("Stake account in the previous staking pool is empty");
+        return Err(LendingError::InvalidStakeAccount.into());
+    }
+    let stake_amount = old_stake_account.deposited_amount;
+
+    withdraw_from_staking_program(
+        program_id,
+        stake_amount,
+        lending_market_info,
+        lending_market_authority_info,
+        clock_info,
+        old_stake_account_info,
+        old_staking_pool_info,
+        staking_program_id,
+        *obligation_owner_info.key,
+    )?;
+    deposit_to_staking_program(
+        program_id,
+        stake_amount,
+        lending_market_info,
+        lending_market_authority_info,
+        clock_info,
+        new_stake_account_info,
+        new_staking_pool_info,
+        staking_program_id,
+        *obligation_owner_info.key,
+    )
+}
+
+fn assert_writable(account_info: &AccountInfo, name: &str) -> ProgramResult {
+    if !account_info.is_writable {
+        msg!("{} provided must be writable", name);
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    Ok(())
+}
+
 fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
     if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
         msg!(&rent.minimum_balance(account_info.data_len()).to_string());
@@ -2350,6 +4141,192 @@ fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult
     }
 }
 
+/// Borrow fee discount the obligation owner is entitled to, zero unless the stake account is
+/// the owner's non-empty stake account in the reserve's fee discount staking pool, holding at
+/// least the reserve's minimum stake for the discount
+fn borrow_fee_discount_percentage(
+    reserve: &Reserve,
+    obligation_owner: &Pubkey,
+    stake_account_info: Option<&AccountInfo>,
+) -> u8 {
+    let (staking_pool, stake_account_info) = match (
+        reserve.config.borrow_fee_discount_staking_pool,
+        stake_account_info,
+    ) {
+        (COption::Some(staking_pool), Some(stake_account_info)) => {
+            (staking_pool, stake_account_info)
+        }
+        _ => return 0,
+    };
+    if stake_account_info.owner != &port_finance_staking::id() {
+        msg!("Stake account is not owned by the staking program, no borrow fee discount");
+        return 0;
+    }
+    match StakeAccount::unpack(&stake_account_info.data.borrow()) {
+        Ok(stake_account)
+            if stake_account.pool_pubkey == staking_pool
+                && &stake_account.owner == obligation_owner
+                && stake_account.deposited_amount > 0
+                && stake_account.deposited_amount
+                    >= reserve.config.borrow_fee_discount_min_stake =>
+        {
+            reserve.config.borrow_fee_discount_percentage
+        }
+        _ => {
+            msg!("Stake account does not qualify for a borrow fee discount");
+            0
+        }
+    }
+}
+
+/// Splits a trailing instructions sysvar account off the accounts provided
+fn split_instructions_sysvar<'a, 'b>(
+    accounts: &'a [AccountInfo<'b>],
+) -> (&'a [AccountInfo<'b>], Option<&'a AccountInfo<'b>>) {
+    match accounts.split_last() {
+        Some((last, rest)) if sysvar::instructions::check_id(last.key) => (rest, Some(last)),
+        _ => (accounts, None),
+    }
+}
+
+/// Ensures the instructions immediately preceding the current one refresh every reserve of the
+/// obligation, in order, followed by the obligation itself. `DepositAndBorrow` and
+/// `BootstrapObligation` refresh the obligation themselves, so they only need to be preceded by
+/// the refresh of every reserve passed to them, in order. Only the first `reserve_count` accounts
+/// after the fixed ones are reserves, like the lending market and obligation owner that follow
+/// them in a conservative refresh.
+fn assert_refreshed_immediately_before(
+    program_id: &Pubkey,
+    instructions_info: Option<&AccountInfo>,
+    obligation_pubkey: &Pubkey,
+    reserve_count: usize,
+) -> ProgramResult {
+    let instructions_info = match instructions_info {
+        Some(instructions_info) => instructions_info,
+        None => {
+            msg!("Instructions sysvar must be provided when strict instruction order is enforced");
+            return Err(LendingError::UnexpectedInstructionOrder.into());
+        }
+    };
+
+    let current_index = load_current_index_checked(instructions_info)? as usize;
+    let current = load_instruction_at_checked(current_index, instructions_info)?;
+    let composite_reserve_metas: Option<Vec<AccountMeta>> = if &current.program_id == program_id {
+        match LendingInstruction::unpack(&current.data) {
+            // refresh accounts follow the 17 accounts of the deposit and the borrow
+            Ok(LendingInstruction::DepositAndBorrow { .. }) => {
+                Some(current.accounts.iter().skip(17).cloned().collect())
+            }
+            // a bootstrapped obligation only holds the deposit reserve when refreshed
+            Ok(LendingInstruction::BootstrapObligation { .. }) => {
+                Some(current.accounts.iter().skip(2).take(1).cloned().collect())
+            }
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    // composite instructions refresh the obligation themselves, so only its reserves must be
+    // refreshed immediately before them
+    let (refresh_obligation_index, reserve_metas) = match composite_reserve_metas {
+        Some(reserve_metas) => (current_index, reserve_metas),
+        None => {
+            let refresh_obligation_index = match current_index.checked_sub(1) {
+                Some(index) => index,
+                None => {
+                    msg!("Obligation must be refreshed immediately before the current instruction");
+                    return Err(LendingError::UnexpectedInstructionOrder.into());
+                }
+            };
+            let refresh_obligation =
+                load_instruction_at_checked(refresh_obligation_index, instructions_info)?;
+            if &refresh_obligation.program_id != program_id
+                || !matches!(
+                    LendingInstruction::unpack(&refresh_obligation.data),
+                    Ok(LendingInstruction::RefreshObligation)
+                )
+                || refresh_obligation.accounts.first().map(|meta| &meta.pubkey)
+                    != Some(obligation_pubkey)
+            {
+                msg!("Obligation must be refreshed immediately before the current instruction");
+                return Err(LendingError::UnexpectedInstructionOrder.into());
+            }
+            // obligation refresh accounts are the obligation and clock, followed by its reserves
+            (
+                refresh_obligation_index,
+                refresh_obligation
+                    .accounts
+                    .iter()
+                    .skip(2)
+                    .cloned()
+                    .collect(),
+            )
+        }
+    };
+
+    // reserves may repeat when the same reserve is both deposited to and borrowed from
+    let mut reserve_pubkeys: Vec<Pubkey> = vec![];
+    for meta in reserve_metas.iter().take(reserve_count) {
+        if !reserve_pubkeys.contains(&meta.pubkey) {
+            reserve_pubkeys.push(meta.pubkey);
+        }
+    }
+    let first_refresh_reserve_index =
+        match refresh_obligation_index.checked_sub(reserve_pubkeys.len()) {
+            Some(index) => index,
+            None => {
+                msg!("Obligation reserves must be refreshed immediately before the obligation");
+                return Err(LendingError::UnexpectedInstructionOrder.into());
+            }
+        };
+    for (offset, reserve_pubkey) in reserve_pubkeys.iter().enumerate() {
+        let refresh_reserve =
+            load_instruction_at_checked(first_refresh_reserve_index + offset, instructions_info)?;
+        if &refresh_reserve.program_id != program_id
+            || !matches!(
+                LendingInstruction::unpack(&refresh_reserve.data),
+                Ok(LendingInstruction::RefreshReserve)
+            )
+            || refresh_reserve.accounts.first().map(|meta| &meta.pubkey) != Some(reserve_pubkey)
+        {
+            msg!("Obligation reserves must be refreshed immediately before the obligation");
+            return Err(LendingError::UnexpectedInstructionOrder.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Values are summed in 192 bits but packed in 128, so a value too large to pack fails with
+/// `MathOverflow` instead of panicking when the account is packed
+fn packable_value(value: Decimal) -> Result<Decimal, ProgramError> {
+    value.to_scaled_val()?;
+    Ok(value)
+}
+
+/// Unpacks a lending market for an instruction that must be signed by its owner
+fn unpack_owned_lending_market(
+    program_id: &Pubkey,
+    lending_market_info: &AccountInfo,
+    lending_market_owner_info: &AccountInfo,
+) -> Result<LendingMarket, ProgramError> {
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    Ok(lending_market)
+}
+
 fn assert_uninitialized<T: Pack + IsInitialized>(
     account_info: &AccountInfo,
 ) -> Result<T, ProgramError> {
@@ -2366,15 +4343,26 @@ fn unpack_mint(data: &[u8]) -> Result<Mint, LendingError> {
     Mint::unpack(data).map_err(|_| LendingError::InvalidTokenMint)
 }
 
+/// Number of slots after which an oracle price is stale, unless the reserve configures its own
+/// maximum price staleness
+const ORACLE_STALE_AFTER_SLOTS_ELAPSED: u64 = 240;
+
+fn price_staleness_slots(config: &ReserveConfig) -> u64 {
+    match config.max_price_staleness_slots {
+        0 => ORACLE_STALE_AFTER_SLOTS_ELAPSED,
+        slots => slots,
+    }
+}
+
 fn get_switchboard_price(
     switchboard_feed_account: &AccountInfo,
     clock: &Clock,
+    stale_after_slots: u64,
 ) -> Result<Decimal, ProgramError> {
     if is_switchbaord_program_v2(switchboard_feed_account.owner) {
-        return get_switchboard_price_v2(switchboard_feed_account, clock);
+        return get_switchboard_price_v2(switchboard_feed_account, clock, stale_after_slots);
     }
 
-    const STALE_AFTER_SLOTS_ELAPSED: u64 = 240;
     let account_buf = switchboard_feed_account.try_borrow_data()?;
     if account_buf.len() == 0 {
         msg!("The provided account is empty.");
@@ -2390,17 +4378,15 @@ fn get_switchboard_price(
             e
         })?;
 
-        round_result
+        let slots_elapsed = round_result
             .round_open_slot
-            .and_then(|slot| {
-                let slots_elapsed = clock.slot.checked_sub(slot)?;
-                if slots_elapsed >= STALE_AFTER_SLOTS_ELAPSED {
-                    msg!("Oracle price is stale");
-                    return None;
-                }
-                round_result.result
-            })
-            .ok_or(LendingError::InvalidOracleConfig)
+            .and_then(|slot| clock.slot.checked_sub(slot))
+            .ok_or(LendingError::InvalidOracleConfig)?;
+        if slots_elapsed >= stale_after_slots {
+            msg!("Oracle price is stale");
+            return Err(LendingError::OraclePriceStale.into());
+        }
+        round_result.result.ok_or(LendingError::InvalidOracleConfig)
     } else if account_buf[0] == SwitchboardAccountType::TYPE_AGGREGATOR_RESULT_PARSE_OPTIMIZED as u8
     {
         let feed_data = FastRoundResultAccountData::deserialize(&account_buf).unwrap();
@@ -2420,17 +4406,16 @@ fn get_switchboard_price(
 fn get_switchboard_price_v2(
     switchboard_feed_info: &AccountInfo,
     clock: &Clock,
+    stale_after_slots: u64,
 ) -> Result<Decimal, ProgramError> {
-    const STALE_AFTER_SLOTS_ELAPSED: u64 = 240;
-
     let feed = AggregatorAccountData::new(switchboard_feed_info)?;
     let slots_elapsed = clock
         .slot
         .checked_sub(feed.latest_confirmed_round.round_open_slot)
         .ok_or(LendingError::MathOverflow)?;
-    if slots_elapsed >= STALE_AFTER_SLOTS_ELAPSED {
+    if slots_elapsed >= stale_after_slots {
         msg!("Switchboard oracle price is stale");
-        return Err(LendingError::InvalidOracleConfig.into());
+        return Err(LendingError::OraclePriceStale.into());
     }
 
     let price_switchboard_desc = feed.get_result()?;
@@ -2443,9 +4428,11 @@ fn get_switchboard_price_v2(
     price.try_div(exp)
 }
 
-fn get_pyth_price(pyth_price_info: &AccountInfo, clock: &Clock) -> Result<Decimal, ProgramError> {
-    const STALE_AFTER_SLOTS_ELAPSED: u64 = 240;
-
+fn get_pyth_price(
+    pyth_price_info: &AccountInfo,
+    clock: &Clock,
+    stale_after_slots: u64,
+) -> Result<Decimal, ProgramError> {
     let pyth_price_data = pyth_price_info.try_borrow_data()?;
     let pyth_price = pyth::load::<pyth::Price>(&pyth_price_data)
         .map_err(|_| ProgramError::InvalidAccountData)?;
@@ -2464,9 +4451,9 @@ fn get_pyth_price(pyth_price_info: &AccountInfo, clock: &Clock) -> Result<Decima
         .slot
         .checked_sub(pyth_price.valid_slot)
         .ok_or(LendingError::MathOverflow)?;
-    if slots_elapsed >= STALE_AFTER_SLOTS_ELAPSED {
+    if slots_elapsed >= stale_after_slots {
         msg!("Oracle price is stale");
-        return Err(LendingError::InvalidOracleConfig.into());
+        return Err(LendingError::OraclePriceStale.into());
     }
 
     let price: u64 = pyth_price.agg.price.try_into().map_err(|_| {
@@ -2515,8 +4502,16 @@ fn spl_token_init_account(params: TokenInitializeAccountParams<'_>) -> ProgramRe
         mint.key,
         owner.key,
     )?;
+    let account_key = *account.key;
     let result = invoke(&ix, &[account, mint, owner, rent, token_program]);
-    result.map_err(|_| LendingError::TokenInitializeAccountFailed.into())
+    result.map_err(|error| {
+        msg!(
+            "Initializing token account {} failed: {}",
+            account_key,
+            describe_token_error(&error)
+        );
+        LendingError::TokenInitializeAccountFailed.into()
+    })
 }
 
 /// Issue a spl_token `InitializeMint` instruction.
@@ -2536,8 +4531,16 @@ fn spl_token_init_mint(params: TokenInitializeMintParams<'_, '_>) -> ProgramResu
         None,
         decimals,
     )?;
+    let mint_key = *mint.key;
     let result = invoke(&ix, &[mint, rent, token_program]);
-    result.map_err(|_| LendingError::TokenInitializeMintFailed.into())
+    result.map_err(|error| {
+        msg!(
+            "Initializing mint {} failed: {}",
+            mint_key,
+            describe_token_error(&error)
+        );
+        LendingError::TokenInitializeMintFailed.into()
+    })
 }
 
 /// Invoke signed unless signers seeds are empty
@@ -2565,6 +4568,7 @@ fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult {
         amount,
         authority_signer_seeds,
     } = params;
+    let (source_key, destination_key) = (*source.key, *destination.key);
     let result = invoke_optionally_signed(
         &spl_token::instruction::transfer(
             token_program.key,
@@ -2577,7 +4581,16 @@ fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult {
         &[source, destination, authority, token_program],
         authority_signer_seeds,
     );
-    result.map_err(|_| LendingError::TokenTransferFailed.into())
+    result.map_err(|error| {
+        msg!(
+            "Transfer of {} from {} to {} failed: {}",
+            amount,
+            source_key,
+            destination_key,
+            describe_token_error(&error)
+        );
+        LendingError::TokenTransferFailed.into()
+    })
 }
 
 /// Issue a spl_token `MintTo` instruction.
@@ -2590,6 +4603,7 @@ fn spl_token_mint_to(params: TokenMintToParams<'_, '_>) -> ProgramResult {
         amount,
         authority_signer_seeds,
     } = params;
+    let destination_key = *destination.key;
     let result = invoke_optionally_signed(
         &spl_token::instruction::mint_to(
             token_program.key,
@@ -2602,7 +4616,15 @@ fn spl_token_mint_to(params: TokenMintToParams<'_, '_>) -> ProgramResult {
         &[mint, destination, authority, token_program],
         authority_signer_seeds,
     );
-    result.map_err(|_| LendingError::TokenMintToFailed.into())
+    result.map_err(|error| {
+        msg!(
+            "Minting {} to {} failed: {}",
+            amount,
+            destination_key,
+            describe_token_error(&error)
+        );
+        LendingError::TokenMintToFailed.into()
+    })
 }
 
 /// Issue a spl_token `Burn` instruction.
@@ -2616,6 +4638,7 @@ fn spl_token_burn(params: TokenBurnParams<'_, '_>) -> ProgramResult {
         amount,
         authority_signer_seeds,
     } = params;
+    let source_key = *source.key;
     let result = invoke_optionally_signed(
         &spl_token::instruction::burn(
             token_program.key,
@@ -2628,7 +4651,26 @@ fn spl_token_burn(params: TokenBurnParams<'_, '_>) -> ProgramResult {
         &[source, mint, authority, token_program],
         authority_signer_seeds,
     );
-    result.map_err(|_| LendingError::TokenBurnFailed.into())
+    result.map_err(|error| {
+        msg!(
+            "Burning {} from {} failed: {}",
+            amount,
+            source_key,
+            describe_token_error(&error)
+        );
+        LendingError::TokenBurnFailed.into()
+    })
+}
+
+/// Describe an error returned by the token program, naming the SPL Token error for its custom
+/// error codes
+fn describe_token_error(error: &ProgramError) -> String {
+    match error {
+        ProgramError::Custom(code) => TokenError::from_u32(*code)
+            .map(|token_error| token_error.to_string())
+            .unwrap_or_else(|| error.to_string()),
+        _ => error.to_string(),
+    }
 }
 
 struct TokenInitializeMintParams<'a: 'b, 'b> {
@@ -39,6 +39,11 @@ impl LastUpdate {
         self.stale = true;
     }
 
+    /// Check if updated in the given slot and not marked stale since
+    pub fn is_updated_in(&self, slot: Slot) -> bool {
+        !self.stale && self.slot == slot
+    }
+
     /// Check if marked stale or last update slot is too long ago
     pub fn is_stale(&self, slot: Slot) -> Result<bool, ProgramError> {
         Ok(self.stale || self.slots_elapsed(slot)? >= STALE_AFTER_SLOTS_ELAPSED)
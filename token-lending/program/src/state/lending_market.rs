@@ -7,6 +7,10 @@ use solana_program::{
     pubkey::{Pubkey, PUBKEY_BYTES},
 };
 
+/// Quote currency of a market priced in USD, the unit of Pyth and Switchboard prices
+pub const USD_QUOTE_CURRENCY: [u8; 32] =
+    *b"USD\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+
 /// Lending market state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct LendingMarket {
@@ -21,6 +25,27 @@ pub struct LendingMarket {
     pub quote_currency: [u8; 32],
     /// Token program id
     pub token_program_id: Pubkey,
+    /// Proposed owner, who must accept before taking control of the market
+    pub pending_owner: COption<Pubkey>,
+    /// Require borrows and liquidations to be immediately preceded by the refreshes they rely on
+    pub strict_instruction_order: bool,
+    /// Allow reserves whose liquidity mint has a freeze authority
+    pub allow_freezable_mint: bool,
+    /// Maximum number of distinct deposit and borrow reserves an obligation may have,
+    /// 0 for the `MAX_REFRESH_RESERVES` a refresh can process
+    pub max_obligation_reserves: u8,
+    /// Allow obligation owners to refresh with stale deposit reserves valued at zero, so that
+    /// a broken oracle doesn't keep them from repaying or withdrawing other collateral
+    pub allow_conservative_refresh: bool,
+    /// Slots an obligation must have been unhealthy for before it can be liquidated, so a
+    /// single noisy oracle price can't get it liquidated
+    pub liquidation_grace_slots: u64,
+    /// Smallest value in the quote currency a liquidation must repay unless it fully closes the
+    /// repaid borrow or seizes all of the withdrawn collateral, 0 for no minimum
+    pub min_liquidation_value: Decimal,
+    /// Slots per year used to annualize per-slot rates for display, 0 for
+    /// `DEFAULT_SLOTS_PER_YEAR`. Interest accrues per slot regardless of this estimate.
+    pub slots_per_year: u64,
 }
 
 impl LendingMarket {
@@ -39,6 +64,27 @@ impl LendingMarket {
         self.quote_currency = params.quote_currency;
         self.token_program_id = params.token_program_id;
     }
+
+    /// Estimated slots per year of this market, to annualize per-slot rates with
+    pub fn slots_per_year(&self) -> u64 {
+        match self.slots_per_year {
+            0 => DEFAULT_SLOTS_PER_YEAR,
+            slots_per_year => slots_per_year,
+        }
+    }
+
+    /// Whether market prices are quoted in USD, so reserves can be priced by an oracle
+    pub fn quotes_in_usd(&self) -> bool {
+        self.quote_currency == USD_QUOTE_CURRENCY
+    }
+
+    /// Number of distinct deposit and borrow reserves an obligation may have in this market
+    pub fn obligation_reserve_limit(&self) -> usize {
+        match self.max_obligation_reserves {
+            0 => MAX_REFRESH_RESERVES,
+            max_obligation_reserves => max_obligation_reserves as usize,
+        }
+    }
 }
 
 /// Initialize a lending market
@@ -61,29 +107,97 @@ impl IsInitialized for LendingMarket {
     }
 }
 
-const LENDING_MARKET_LEN: usize = 258; // 1 + 1 + 32 + 32 + 32 + 160
+const LENDING_MARKET_LEN: usize = 258; // 1 + 1 + 32 + 32 + 32 + 33 + 1 + 1 + 1 + 1 + 8 + 16 + 8 + 91
 impl Pack for LendingMarket {
     const LEN: usize = LENDING_MARKET_LEN;
 
     fn pack_into_slice(&self, output: &mut [u8]) {
         let output = array_mut_ref![output, 0, LENDING_MARKET_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
-        let (version, bump_seed, owner, quote_currency, token_program_id, _padding) =
-            mut_array_refs![output, 1, 1, PUBKEY_BYTES, 32, PUBKEY_BYTES, 160];
+        let (
+            version,
+            bump_seed,
+            owner,
+            quote_currency,
+            token_program_id,
+            pending_owner,
+            strict_instruction_order,
+            allow_freezable_mint,
+            max_obligation_reserves,
+            allow_conservative_refresh,
+            liquidation_grace_slots,
+            min_liquidation_value,
+            slots_per_year,
+            _padding,
+        ) = mut_array_refs![
+            output,
+            1,
+            1,
+            PUBKEY_BYTES,
+            32,
+            PUBKEY_BYTES,
+            1 + PUBKEY_BYTES,
+            1,
+            1,
+            1,
+            1,
+            8,
+            16,
+            8,
+            91
+        ];
 
         *version = self.version.to_le_bytes();
         *bump_seed = self.bump_seed.to_le_bytes();
         owner.copy_from_slice(self.owner.as_ref());
         quote_currency.copy_from_slice(self.quote_currency.as_ref());
         token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        pack_coption_key_compact(&self.pending_owner, pending_owner);
+        pack_bool(self.strict_instruction_order, strict_instruction_order);
+        pack_bool(self.allow_freezable_mint, allow_freezable_mint);
+        *max_obligation_reserves = self.max_obligation_reserves.to_le_bytes();
+        pack_bool(self.allow_conservative_refresh, allow_conservative_refresh);
+        *liquidation_grace_slots = self.liquidation_grace_slots.to_le_bytes();
+        pack_decimal(self.min_liquidation_value, min_liquidation_value);
+        *slots_per_year = self.slots_per_year.to_le_bytes();
     }
 
     /// Unpacks a byte buffer into a [LendingMarketInfo](struct.LendingMarketInfo.html)
     fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
         let input = array_ref![input, 0, LENDING_MARKET_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
-        let (version, bump_seed, owner, quote_currency, token_program_id, _padding) =
-            array_refs![input, 1, 1, PUBKEY_BYTES, 32, PUBKEY_BYTES, 160];
+        let (
+            version,
+            bump_seed,
+            owner,
+            quote_currency,
+            token_program_id,
+            pending_owner,
+            strict_instruction_order,
+            allow_freezable_mint,
+            max_obligation_reserves,
+            allow_conservative_refresh,
+            liquidation_grace_slots,
+            min_liquidation_value,
+            slots_per_year,
+            _padding,
+        ) = array_refs![
+            input,
+            1,
+            1,
+            PUBKEY_BYTES,
+            32,
+            PUBKEY_BYTES,
+            1 + PUBKEY_BYTES,
+            1,
+            1,
+            1,
+            1,
+            8,
+            16,
+            8,
+            91
+        ];
 
         let version = u8::from_le_bytes(*version);
         if version > PROGRAM_VERSION {
@@ -97,6 +211,14 @@ impl Pack for LendingMarket {
             owner: Pubkey::new_from_array(*owner),
             quote_currency: *quote_currency,
             token_program_id: Pubkey::new_from_array(*token_program_id),
+            pending_owner: unpack_coption_key_compact(pending_owner)?,
+            strict_instruction_order: unpack_bool(strict_instruction_order)?,
+            allow_freezable_mint: unpack_bool(allow_freezable_mint)?,
+            max_obligation_reserves: u8::from_le_bytes(*max_obligation_reserves),
+            allow_conservative_refresh: unpack_bool(allow_conservative_refresh)?,
+            liquidation_grace_slots: u64::from_le_bytes(*liquidation_grace_slots),
+            min_liquidation_value: unpack_decimal(min_liquidation_value),
+            slots_per_year: u64::from_le_bytes(*slots_per_year),
         })
     }
 }
@@ -3,14 +3,17 @@
 mod last_update;
 mod lending_market;
 mod obligation;
+pub mod offsets;
 mod reserve;
+mod reserve_snapshot;
 
 pub use last_update::*;
 pub use lending_market::*;
 pub use obligation::*;
 pub use reserve::*;
+pub use reserve_snapshot::*;
 
-use crate::math::{Decimal, WAD};
+use crate::math::{Decimal, Rate, U128, WAD};
 use arrayref::{array_refs, mut_array_refs};
 use solana_program::{
     clock::{DEFAULT_TICKS_PER_SECOND, DEFAULT_TICKS_PER_SLOT, SECONDS_PER_DAY},
@@ -37,6 +40,16 @@ pub const UNINITIALIZED_VERSION: u8 = 0;
 pub const SLOTS_PER_YEAR: u64 =
     DEFAULT_TICKS_PER_SECOND / DEFAULT_TICKS_PER_SLOT * SECONDS_PER_DAY * 365;
 
+/// Estimate of the slots actually produced per year, with slots averaging 500ms rather than the
+/// nominal 400ms of `SLOTS_PER_YEAR`. Used to annualize rates unless a market sets its own.
+pub const DEFAULT_SLOTS_PER_YEAR: u64 = SECONDS_PER_DAY * 365 * 2;
+
+/// Fewest slots per year a market can estimate, with slots averaging 1.6s
+pub const MIN_SLOTS_PER_YEAR: u64 = SLOTS_PER_YEAR / 4;
+
+/// Most slots per year a market can estimate, with slots averaging 200ms
+pub const MAX_SLOTS_PER_YEAR: u64 = SLOTS_PER_YEAR * 2;
+
 // Helpers
 fn pack_decimal(decimal: Decimal, dst: &mut [u8; 16]) {
     *dst = decimal
@@ -49,6 +62,14 @@ fn unpack_decimal(src: &[u8; 16]) -> Decimal {
     Decimal::from_scaled_val(u128::from_le_bytes(*src))
 }
 
+fn pack_rate(rate: Rate, dst: &mut [u8; 16]) {
+    *dst = rate.to_scaled_val().to_le_bytes();
+}
+
+fn unpack_rate(src: &[u8; 16]) -> Rate {
+    Rate(U128::from(u128::from_le_bytes(*src)))
+}
+
 fn pack_bool(boolean: bool, dst: &mut [u8; 1]) {
     *dst = (boolean as u8).to_le_bytes()
 }
@@ -93,7 +114,8 @@ fn unpack_coption_key(src: &[u8; 4 + PUBKEY_BYTES]) -> Result<COption<Pubkey>, P
     }
 }
 
-///pack coption of pubkey into buffer
+/// Pack a COption<Pubkey> into 33 bytes: a tag byte of 0 for None or 1 for Some, followed by
+/// the key. The key bytes are zeroed for None so a zeroed buffer and a packed None are the same.
 pub fn pack_coption_key_compact(src: &COption<Pubkey>, dst: &mut [u8; 1 + PUBKEY_BYTES]) {
     match src {
         COption::Some(key) => {
@@ -101,12 +123,12 @@ pub fn pack_coption_key_compact(src: &COption<Pubkey>, dst: &mut [u8; 1 + PUBKEY
             dst[1..].copy_from_slice(key.as_ref());
         }
         COption::None => {
-            dst[0] = 0;
+            *dst = [0; 1 + PUBKEY_BYTES];
         }
     }
 }
 
-///unpack coption pubkey from buffer
+/// Unpack a COption<Pubkey> packed by `pack_coption_key_compact`, an all-zero buffer is None
 pub fn unpack_coption_key_compact(
     src: &[u8; 1 + PUBKEY_BYTES],
 ) -> Result<COption<Pubkey>, ProgramError> {
@@ -133,4 +155,35 @@ mod test {
             INITIAL_COLLATERAL_RATE
         );
     }
+
+    #[test]
+    fn coption_key_compact_round_trip() {
+        for key in [
+            COption::None,
+            COption::Some(Pubkey::default()),
+            COption::Some(Pubkey::new_unique()),
+        ] {
+            let mut dst = [0xff; 1 + PUBKEY_BYTES];
+            pack_coption_key_compact(&key, &mut dst);
+            assert_eq!(unpack_coption_key_compact(&dst).unwrap(), key);
+        }
+
+        // a zeroed account reads back as None rather than the default pubkey
+        assert_eq!(
+            unpack_coption_key_compact(&[0; 1 + PUBKEY_BYTES]).unwrap(),
+            COption::None
+        );
+
+        // packing None clears any stale key
+        let mut dst = [0xff; 1 + PUBKEY_BYTES];
+        pack_coption_key_compact(&COption::None, &mut dst);
+        assert_eq!(dst, [0; 1 + PUBKEY_BYTES]);
+
+        let mut invalid_tag = [0; 1 + PUBKEY_BYTES];
+        invalid_tag[0] = 2;
+        assert_eq!(
+            unpack_coption_key_compact(&invalid_tag),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
 }
@@ -1,7 +1,7 @@
 use super::*;
 use crate::{
     error::LendingError,
-    math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub},
+    math::{Decimal, Rate, SaturatingSub, TryAdd, TryDiv, TryMul, TrySub},
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_program::{
@@ -20,6 +20,13 @@ use std::{
 /// Max number of collateral and liquidity reserve accounts combined for an obligation
 pub const MAX_OBLIGATION_RESERVES: usize = 10;
 
+/// Max number of deposit and borrow reserves `RefreshObligation` processes in one call, so an
+/// obligation that would not refresh within the compute budget fails with a clear error instead
+/// of running out of compute part way through. Kept below `MAX_OBLIGATION_RESERVES` to leave
+/// headroom for the instruction that follows the refresh in the same transaction; obligations
+/// can't add reserves past it, only ones that already hold more than it fail to refresh
+pub const MAX_REFRESH_RESERVES: usize = 8;
+
 /// Lending market obligation state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Obligation {
@@ -43,6 +50,8 @@ pub struct Obligation {
     pub allowed_borrow_value: Decimal,
     /// The dangerous borrow value at the weighted average liquidation threshold
     pub unhealthy_borrow_value: Decimal,
+    /// Slot a refresh first found the obligation unhealthy, 0 while it is healthy
+    pub became_unhealthy_slot: Slot,
 }
 
 impl Obligation {
@@ -63,6 +72,35 @@ impl Obligation {
         self.borrows = params.borrows;
     }
 
+    /// Reserves to pass to `RefreshObligation`, deposit reserves followed by borrow reserves in
+    /// the order the obligation stores them
+    pub fn refresh_account_metas(&self) -> Vec<Pubkey> {
+        self.deposits
+            .iter()
+            .map(|collateral| collateral.deposit_reserve)
+            .chain(
+                self.borrows
+                    .iter()
+                    .map(|liquidity| liquidity.borrow_reserve),
+            )
+            .collect()
+    }
+
+    /// Fail with `TooManyReserves` if a refresh would have to process more than
+    /// `MAX_REFRESH_RESERVES` deposit and borrow reserves
+    pub fn check_refresh_reserve_count(&self) -> ProgramResult {
+        let reserve_count = self.deposits.len() + self.borrows.len();
+        if reserve_count > MAX_REFRESH_RESERVES {
+            msg!(
+                "Obligation has {} deposits and borrows, more than the {} a refresh can process. Repay or withdraw some of them, or move them to another obligation",
+                reserve_count,
+                MAX_REFRESH_RESERVES
+            );
+            return Err(LendingError::TooManyReserves.into());
+        }
+        Ok(())
+    }
+
     /// Calculate the current ratio of borrowed value to deposited value
     pub fn loan_to_value(&self) -> Result<Decimal, ProgramError> {
         self.borrowed_value.try_div(self.deposited_value)
@@ -111,6 +149,236 @@ impl Obligation {
         self.allowed_borrow_value.try_sub(self.borrowed_value)
     }
 
+    /// Calculate the maximum liquidity value that the borrows bound to the collateral of
+    /// `deposit_reserve` can add, given the allowed borrow value of that collateral alone
+    pub fn remaining_bound_borrow_value(
+        &self,
+        deposit_reserve: &Pubkey,
+        allowed_value: Decimal,
+    ) -> Result<Decimal, ProgramError> {
+        let bound_value = self
+            .borrows
+            .iter()
+            .filter(|liquidity| liquidity.collateral_reserve == COption::Some(*deposit_reserve))
+            .try_fold(Decimal::zero(), |sum, liquidity| {
+                sum.try_add(liquidity.market_value)
+            })?;
+        Ok(allowed_value.saturating_sub(bound_value))
+    }
+
+    /// Calculate the largest amount of liquidity that can be borrowed from a reserve, net of the
+    /// borrow fee, while staying within the allowed borrow value. Deposit and borrow values must
+    /// be refreshed, and `borrow_fees` must have any borrow fee discount already applied.
+    pub fn max_borrow_amount(
+        &self,
+        borrow_reserve_price: Decimal,
+        mint_decimals: u8,
+        borrow_fees: &ReserveFees,
+    ) -> Result<u64, ProgramError> {
+        let remaining_borrow_value = self
+            .allowed_borrow_value
+            .saturating_sub(self.borrowed_value);
+        if remaining_borrow_value == Decimal::zero() || borrow_reserve_price == Decimal::zero() {
+            return Ok(0);
+        }
+        let decimals = 10u64
+            .checked_pow(mint_decimals as u32)
+            .ok_or(LendingError::MathOverflow)?;
+
+        // the same check as `Reserve::calculate_borrow` makes for a borrow of `receive_amount`
+        let within_borrow_value = |receive_amount: u64| -> Result<bool, ProgramError> {
+            let borrow_amount = Decimal::from(receive_amount);
+            let borrow_fee =
+                match borrow_fees.calculate_borrow_fees(borrow_amount, FeeCalculation::Exclusive) {
+                    Ok((borrow_fee, _)) => borrow_fee,
+                    Err(error) if error == LendingError::BorrowTooSmall.into() => return Ok(false),
+                    Err(error) => return Err(error),
+                };
+            let borrow_value = borrow_amount
+                .try_add(borrow_fee.into())?
+                .try_mul(borrow_reserve_price)?
+                .try_div(decimals)?;
+            Ok(borrow_value <= remaining_borrow_value)
+        };
+
+        // start from the amount whose proportional fee uses up the remaining borrow value, then
+        // step to the exact amount, which differs only by the minimum fee and fee rounding
+        let borrow_fee_rate = Rate::from_scaled_val(borrow_fees.borrow_fee_wad);
+        let mut max_borrow_amount = remaining_borrow_value
+            .try_mul(decimals)?
+            .try_div(borrow_reserve_price)?
+            .try_div(Rate::one().try_add(borrow_fee_rate)?)?
+            .try_floor_u64()?;
+        while max_borrow_amount > 0 && !within_borrow_value(max_borrow_amount)? {
+            max_borrow_amount -= 1;
+        }
+        while within_borrow_value(max_borrow_amount + 1)? {
+            max_borrow_amount += 1;
+        }
+        Ok(max_borrow_amount)
+    }
+
+    /// Calculate the allowed and unhealthy borrow values from the loan to value and liquidation
+    /// threshold values of each deposit, in deposit order.
+    ///
+    /// Borrows without a collateral binding are backed by all collateral not bound to any borrow.
+    /// Collateral with borrows bound to it only backs those borrows, up to their market value;
+    /// any excess is not available to other borrows.
+    pub fn calculate_borrowing_power(
+        &self,
+        collateral_values: &[(Decimal, Decimal)],
+    ) -> Result<(Decimal, Decimal), ProgramError> {
+        let mut allowed_borrow_value = Decimal::zero();
+        let mut unhealthy_borrow_value = Decimal::zero();
+        for (collateral, (allowed_value, unhealthy_value)) in
+            self.deposits.iter().zip(collateral_values)
+        {
+            let mut bound_borrows = self.borrows.iter().filter(|liquidity| {
+                liquidity.collateral_reserve == COption::Some(collateral.deposit_reserve)
+            });
+            match bound_borrows.next() {
+                None => {
+                    allowed_borrow_value = allowed_borrow_value.try_add(*allowed_value)?;
+                    unhealthy_borrow_value = unhealthy_borrow_value.try_add(*unhealthy_value)?;
+                }
+                Some(first) => {
+                    let bound_value = bound_borrows
+                        .try_fold(first.market_value, |sum, liquidity| {
+                            sum.try_add(liquidity.market_value)
+                        })?;
+                    allowed_borrow_value =
+                        allowed_borrow_value.try_add((*allowed_value).min(bound_value))?;
+                    unhealthy_borrow_value =
+                        unhealthy_borrow_value.try_add((*unhealthy_value).min(bound_value))?;
+                }
+            }
+        }
+        Ok((allowed_borrow_value, unhealthy_borrow_value))
+    }
+
+    /// Set the market value of the collateral at `collateral_index` after a deposit or withdraw,
+    /// moving the deposited, allowed and unhealthy borrow values by the change so they reflect it
+    /// before the next refresh. Value added to collateral bound to a borrow is only counted
+    /// towards borrowing power by the next refresh.
+    pub fn revalue_collateral(
+        &mut self,
+        collateral_index: usize,
+        market_value: Decimal,
+        loan_to_value_rate: Rate,
+        liquidation_threshold_rate: Rate,
+    ) -> ProgramResult {
+        let collateral = &mut self.deposits[collateral_index];
+        let previous_value = collateral.market_value;
+        collateral.market_value = market_value;
+        let deposit_reserve = COption::Some(collateral.deposit_reserve);
+        let is_bound = self
+            .borrows
+            .iter()
+            .any(|liquidity| liquidity.collateral_reserve == deposit_reserve);
+
+        if market_value >= previous_value {
+            let added_value = market_value.try_sub(previous_value)?;
+            self.deposited_value = self.deposited_value.try_add(added_value)?;
+            if !is_bound {
+                self.allowed_borrow_value = self
+                    .allowed_borrow_value
+                    .try_add(added_value.try_mul(loan_to_value_rate)?)?;
+                self.unhealthy_borrow_value = self
+                    .unhealthy_borrow_value
+                    .try_add(added_value.try_mul(liquidation_threshold_rate)?)?;
+            }
+        } else {
+            let removed_value = previous_value.try_sub(market_value)?;
+            self.deposited_value = self.deposited_value.saturating_sub(removed_value);
+            self.allowed_borrow_value = self
+                .allowed_borrow_value
+                .saturating_sub(removed_value.try_mul(loan_to_value_rate)?);
+            self.unhealthy_borrow_value = self
+                .unhealthy_borrow_value
+                .saturating_sub(removed_value.try_mul(liquidation_threshold_rate)?);
+        }
+        Ok(())
+    }
+
+    /// Set the market value of the liquidity at `liquidity_index` after a borrow or repay,
+    /// moving the borrowed value by the change so it reflects it before the next refresh
+    pub fn revalue_liquidity(
+        &mut self,
+        liquidity_index: usize,
+        market_value: Decimal,
+    ) -> ProgramResult {
+        let liquidity = &mut self.borrows[liquidity_index];
+        let previous_value = liquidity.market_value;
+        liquidity.market_value = market_value;
+        self.borrowed_value = if market_value >= previous_value {
+            self.borrowed_value
+                .try_add(market_value.try_sub(previous_value)?)?
+        } else {
+            self.borrowed_value
+                .saturating_sub(previous_value.try_sub(market_value)?)
+        };
+        Ok(())
+    }
+
+    /// Whether any deposited collateral was valued at zero by the last refresh, as a conservative
+    /// refresh does for collateral in a stale reserve
+    pub fn has_zero_valued_collateral(&self) -> bool {
+        self.deposits.iter().any(|collateral| {
+            collateral.deposited_amount > 0 && collateral.market_value == Decimal::zero()
+        })
+    }
+
+    /// Whether the borrowed value has reached the unhealthy borrow value, as of the last refresh
+    pub fn is_unhealthy(&self) -> bool {
+        !self.borrows.is_empty() && self.borrowed_value >= self.unhealthy_borrow_value
+    }
+
+    /// Record the slot the obligation became unhealthy, or clear it once healthy again
+    pub fn update_unhealthy_slot(&mut self, current_slot: Slot) {
+        if !self.is_unhealthy() {
+            self.became_unhealthy_slot = 0;
+        } else if self.became_unhealthy_slot == 0 {
+            self.became_unhealthy_slot = current_slot;
+        }
+    }
+
+    /// Calculate the price of the liquidity in `collateral_reserve` at which the obligation
+    /// becomes liquidatable, holding the values of its other deposits and borrows at those of
+    /// the last refresh. Assumes the collateral is not bound to a borrow. Returns zero if the
+    /// other deposits alone leave the obligation unhealthy.
+    pub fn liquidation_price(
+        &self,
+        collateral_reserve: &Pubkey,
+        reserve: &Reserve,
+    ) -> Result<Decimal, ProgramError> {
+        let (collateral, _) = self.find_collateral_in_deposits(*collateral_reserve)?;
+        let liquidation_threshold = Rate::from_percent(reserve.config.liquidation_threshold);
+        let other_unhealthy_value = self
+            .unhealthy_borrow_value
+            .try_sub(collateral.market_value.try_mul(liquidation_threshold)?)?;
+        if self.borrowed_value <= other_unhealthy_value {
+            return Ok(Decimal::zero());
+        }
+        let liquidation_value = self
+            .borrowed_value
+            .try_sub(other_unhealthy_value)?
+            .try_div(liquidation_threshold)?;
+
+        let liquidity_amount = reserve
+            .collateral_exchange_rate()?
+            .decimal_collateral_to_liquidity(collateral.deposited_amount.into())?;
+        if liquidity_amount == Decimal::zero() {
+            msg!("Obligation deposit amount is zero");
+            return Err(LendingError::ObligationCollateralEmpty.into());
+        }
+        let decimals = 10u64
+            .checked_pow(reserve.liquidity.mint_decimals as u32)
+            .ok_or(LendingError::MathOverflow)?;
+        liquidation_value
+            .try_mul(decimals)?
+            .try_div(liquidity_amount)
+    }
+
     /// Calculate the maximum liquidation amount for a given liquidity
     pub fn max_liquidation_amount(
         &self,
@@ -124,6 +392,82 @@ impl Obligation {
         liquidity.borrowed_amount_wads.try_mul(max_liquidation_pct)
     }
 
+    /// Move `split_rate` of every deposit and borrow to `destination`, rounding the collateral
+    /// moved down. Market values are left for the next refresh of both obligations.
+    pub fn split_off(
+        &mut self,
+        destination: &mut Obligation,
+        split_rate: Rate,
+        max_reserves: usize,
+    ) -> ProgramResult {
+        for collateral in self.deposits.iter_mut() {
+            let split_amount = Decimal::from(collateral.deposited_amount)
+                .try_mul(split_rate)?
+                .try_floor_u64()?;
+            if split_amount == 0 {
+                continue;
+            }
+            collateral.withdraw(split_amount)?;
+            destination
+                .find_or_add_collateral_to_deposits(collateral.deposit_reserve, max_reserves)?
+                .deposit(split_amount)?;
+        }
+        for liquidity in self.borrows.iter_mut() {
+            let split_amount = liquidity.borrowed_amount_wads.try_mul(split_rate)?;
+            if split_amount == Decimal::zero() {
+                continue;
+            }
+            liquidity.repay(split_amount)?;
+            destination.add_moved_liquidity(liquidity, split_amount, max_reserves)?;
+        }
+        self.deposits
+            .retain(|collateral| collateral.deposited_amount > 0);
+        self.borrows
+            .retain(|liquidity| liquidity.borrowed_amount_wads > Decimal::zero());
+        Ok(())
+    }
+
+    /// Move every deposit and borrow of `source` to this obligation, leaving `source` empty.
+    /// Market values are left for the next refresh.
+    pub fn merge(&mut self, source: &mut Obligation, max_reserves: usize) -> ProgramResult {
+        for collateral in source.deposits.drain(..) {
+            self.find_or_add_collateral_to_deposits(collateral.deposit_reserve, max_reserves)?
+                .deposit(collateral.deposited_amount)?;
+        }
+        for liquidity in source.borrows.drain(..) {
+            self.add_moved_liquidity(&liquidity, liquidity.borrowed_amount_wads, max_reserves)?;
+        }
+        Ok(())
+    }
+
+    /// Add `borrow_amount` of liquidity moved from another obligation's `moved` borrow, keeping
+    /// its collateral binding and accruing interest to the later of the two borrow rates
+    fn add_moved_liquidity(
+        &mut self,
+        moved: &ObligationLiquidity,
+        borrow_amount: Decimal,
+        max_reserves: usize,
+    ) -> ProgramResult {
+        let liquidity =
+            self.find_or_add_liquidity_to_borrows(moved.borrow_reserve, max_reserves)?;
+        let borrow_amount = if liquidity.borrowed_amount_wads == Decimal::zero() {
+            liquidity.cumulative_borrow_rate_wads = moved.cumulative_borrow_rate_wads;
+            liquidity.collateral_reserve = moved.collateral_reserve;
+            borrow_amount
+        } else if liquidity.collateral_reserve != moved.collateral_reserve {
+            msg!("Borrows from the same reserve cannot be bound to different collateral");
+            return Err(LendingError::InvalidObligationLiquidity.into());
+        } else if liquidity.cumulative_borrow_rate_wads <= moved.cumulative_borrow_rate_wads {
+            liquidity.accrue_interest(moved.cumulative_borrow_rate_wads)?;
+            borrow_amount
+        } else {
+            borrow_amount
+                .try_mul(liquidity.cumulative_borrow_rate_wads)?
+                .try_div(moved.cumulative_borrow_rate_wads)?
+        };
+        liquidity.borrow(borrow_amount)
+    }
+
     /// Find collateral by deposit reserve
     pub fn find_collateral_in_deposits(
         &self,
@@ -139,18 +483,20 @@ impl Obligation {
         Ok((&self.deposits[collateral_index], collateral_index))
     }
 
-    /// Find or add collateral by deposit reserve
+    /// Find or add collateral by deposit reserve, adding at most `max_reserves` distinct deposit
+    /// and borrow reserves
     pub fn find_or_add_collateral_to_deposits(
         &mut self,
         deposit_reserve: Pubkey,
+        max_reserves: usize,
     ) -> Result<&mut ObligationCollateral, ProgramError> {
         if let Some(collateral_index) = self._find_collateral_index_in_deposits(deposit_reserve) {
             return Ok(&mut self.deposits[collateral_index]);
         }
-        if self.deposits.len() + self.borrows.len() >= MAX_OBLIGATION_RESERVES {
+        if self.deposits.len() + self.borrows.len() >= max_reserves.min(MAX_REFRESH_RESERVES) {
             msg!(
                 "Obligation cannot have more than {} deposits and borrows combined",
-                MAX_OBLIGATION_RESERVES
+                max_reserves.min(MAX_REFRESH_RESERVES)
             );
             return Err(LendingError::ObligationReserveLimit.into());
         }
@@ -180,18 +526,20 @@ impl Obligation {
         Ok((&self.borrows[liquidity_index], liquidity_index))
     }
 
-    /// Find or add liquidity by borrow reserve
+    /// Find or add liquidity by borrow reserve, adding at most `max_reserves` distinct deposit
+    /// and borrow reserves
     pub fn find_or_add_liquidity_to_borrows(
         &mut self,
         borrow_reserve: Pubkey,
+        max_reserves: usize,
     ) -> Result<&mut ObligationLiquidity, ProgramError> {
         if let Some(liquidity_index) = self._find_liquidity_index_in_borrows(borrow_reserve) {
             return Ok(&mut self.borrows[liquidity_index]);
         }
-        if self.deposits.len() + self.borrows.len() >= MAX_OBLIGATION_RESERVES {
+        if self.deposits.len() + self.borrows.len() >= max_reserves.min(MAX_REFRESH_RESERVES) {
             msg!(
                 "Obligation cannot have more than {} deposits and borrows combined",
-                MAX_OBLIGATION_RESERVES
+                max_reserves.min(MAX_REFRESH_RESERVES)
             );
             return Err(LendingError::ObligationReserveLimit.into());
         }
@@ -279,6 +627,9 @@ pub struct ObligationLiquidity {
     pub borrowed_amount_wads: Decimal,
     /// Liquidity market value in quote currency
     pub market_value: Decimal,
+    /// Deposit reserve whose collateral alone backs this borrow, or none to borrow against
+    /// all cross collateral
+    pub collateral_reserve: COption<Pubkey>,
 }
 
 impl ObligationLiquidity {
@@ -289,6 +640,7 @@ impl ObligationLiquidity {
             cumulative_borrow_rate_wads: Decimal::zero(),
             borrowed_amount_wads: Decimal::zero(),
             market_value: Decimal::zero(),
+            collateral_reserve: COption::None,
         }
     }
 
@@ -313,13 +665,14 @@ impl ObligationLiquidity {
             }
             Ordering::Equal => {}
             Ordering::Greater => {
+                // Rounded up, see `Reserve::accrue_interest`
                 let compounded_interest_rate: Rate = cumulative_borrow_rate_wads
-                    .try_div(self.cumulative_borrow_rate_wads)?
+                    .try_div_ceil(self.cumulative_borrow_rate_wads)?
                     .try_into()?;
 
                 self.borrowed_amount_wads = self
                     .borrowed_amount_wads
-                    .try_mul(compounded_interest_rate)?;
+                    .try_mul_ceil(compounded_interest_rate)?;
                 self.cumulative_borrow_rate_wads = cumulative_borrow_rate_wads;
             }
         }
@@ -328,13 +681,29 @@ impl ObligationLiquidity {
     }
 }
 
-const OBLIGATION_COLLATERAL_LEN: usize = 56; // 32 + 8 + 16
-const OBLIGATION_LIQUIDITY_LEN: usize = 80; // 32 + 16 + 16 + 16
-const OBLIGATION_LEN: usize = 916; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 1 + 1 + (56 * 1) + (80 * 9)
-                                   // @TODO: break this up by obligation / collateral / liquidity https://git.io/JOCca
+pub(crate) const OBLIGATION_COLLATERAL_LEN: usize = 56; // 32 + 8 + 16
+pub(crate) const OBLIGATION_LIQUIDITY_LEN: usize = 113; // 32 + 16 + 16 + 16 + 33
+const OBLIGATION_LEN: usize = 1221; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 1 + 1 + (56 * 1) + (113 * 9) + 8
+                                    // @TODO: break this up by obligation / collateral / liquidity https://git.io/JOCca
+
+/// Length of obligations created before borrows could be bound to a collateral reserve and
+/// before `became_unhealthy_slot` was tracked. `MigrateObligation` reallocs them to
+/// `Obligation::LEN`.
+pub const LEGACY_OBLIGATION_LEN: usize = 916; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 1 + 1 + (56 * 1) + (80 * 9)
+const LEGACY_OBLIGATION_LIQUIDITY_LEN: usize = 80; // 32 + 16 + 16 + 16
+
 impl Pack for Obligation {
     const LEN: usize = OBLIGATION_LEN;
 
+    /// Also unpacks the legacy layout, so obligations stay readable until they are migrated
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        match input.len() {
+            OBLIGATION_LEN => Self::unpack_from_slice(input),
+            LEGACY_OBLIGATION_LEN => Self::unpack_legacy(input),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let output = array_mut_ref![dst, 0, OBLIGATION_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
@@ -351,6 +720,7 @@ impl Pack for Obligation {
             deposits_len,
             borrows_len,
             data_flat,
+            became_unhealthy_slot,
         ) = mut_array_refs![
             output,
             1,
@@ -364,7 +734,8 @@ impl Pack for Obligation {
             16,
             1,
             1,
-            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1)),
+            8
         ];
 
         // obligation
@@ -379,6 +750,7 @@ impl Pack for Obligation {
         pack_decimal(self.unhealthy_borrow_value, unhealthy_borrow_value);
         *deposits_len = u8::try_from(self.deposits.len()).unwrap().to_le_bytes();
         *borrows_len = u8::try_from(self.borrows.len()).unwrap().to_le_bytes();
+        *became_unhealthy_slot = self.became_unhealthy_slot.to_le_bytes();
 
         let mut offset = 0;
 
@@ -398,8 +770,13 @@ impl Pack for Obligation {
         for liquidity in &self.borrows {
             let borrows_flat = array_mut_ref![data_flat, offset, OBLIGATION_LIQUIDITY_LEN];
             #[allow(clippy::ptr_offset_with_cast)]
-            let (borrow_reserve, cumulative_borrow_rate_wads, borrowed_amount_wads, market_value) =
-                mut_array_refs![borrows_flat, PUBKEY_BYTES, 16, 16, 16];
+            let (
+                borrow_reserve,
+                cumulative_borrow_rate_wads,
+                borrowed_amount_wads,
+                market_value,
+                collateral_reserve,
+            ) = mut_array_refs![borrows_flat, PUBKEY_BYTES, 16, 16, 16, 1 + PUBKEY_BYTES];
             borrow_reserve.copy_from_slice(liquidity.borrow_reserve.as_ref());
             pack_decimal(
                 liquidity.cumulative_borrow_rate_wads,
@@ -407,6 +784,7 @@ impl Pack for Obligation {
             );
             pack_decimal(liquidity.borrowed_amount_wads, borrowed_amount_wads);
             pack_decimal(liquidity.market_value, market_value);
+            pack_coption_key_compact(&liquidity.collateral_reserve, collateral_reserve);
             offset += OBLIGATION_LIQUIDITY_LEN;
         }
     }
@@ -428,6 +806,7 @@ impl Pack for Obligation {
             deposits_len,
             borrows_len,
             data_flat,
+            became_unhealthy_slot,
         ) = array_refs![
             input,
             1,
@@ -441,7 +820,8 @@ impl Pack for Obligation {
             16,
             1,
             1,
-            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1)),
+            8
         ];
 
         let version = u8::from_le_bytes(*version);
@@ -471,6 +851,105 @@ impl Pack for Obligation {
         for _ in 0..borrows_len {
             let borrows_flat = array_ref![data_flat, offset, OBLIGATION_LIQUIDITY_LEN];
             #[allow(clippy::ptr_offset_with_cast)]
+            let (
+                borrow_reserve,
+                cumulative_borrow_rate_wads,
+                borrowed_amount_wads,
+                market_value,
+                collateral_reserve,
+            ) = array_refs![borrows_flat, PUBKEY_BYTES, 16, 16, 16, 1 + PUBKEY_BYTES];
+            borrows.push(ObligationLiquidity {
+                borrow_reserve: Pubkey::new(borrow_reserve),
+                cumulative_borrow_rate_wads: unpack_decimal(cumulative_borrow_rate_wads),
+                borrowed_amount_wads: unpack_decimal(borrowed_amount_wads),
+                market_value: unpack_decimal(market_value),
+                collateral_reserve: unpack_coption_key_compact(collateral_reserve)?,
+            });
+            offset += OBLIGATION_LIQUIDITY_LEN;
+        }
+
+        Ok(Self {
+            version,
+            last_update: LastUpdate {
+                slot: u64::from_le_bytes(*last_update_slot),
+                stale: unpack_bool(last_update_stale)?,
+            },
+            lending_market: Pubkey::new_from_array(*lending_market),
+            owner: Pubkey::new_from_array(*owner),
+            deposits,
+            borrows,
+            deposited_value: unpack_decimal(deposited_value),
+            borrowed_value: unpack_decimal(borrowed_value),
+            allowed_borrow_value: unpack_decimal(allowed_borrow_value),
+            unhealthy_borrow_value: unpack_decimal(unhealthy_borrow_value),
+            became_unhealthy_slot: u64::from_le_bytes(*became_unhealthy_slot),
+        })
+    }
+}
+
+impl Obligation {
+    /// Unpacks an obligation in the legacy layout, leaving borrows unbound to any collateral
+    /// reserve and `became_unhealthy_slot` at 0
+    fn unpack_legacy(src: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![src, 0, LEGACY_OBLIGATION_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            version,
+            last_update_slot,
+            last_update_stale,
+            lending_market,
+            owner,
+            deposited_value,
+            borrowed_value,
+            allowed_borrow_value,
+            unhealthy_borrow_value,
+            deposits_len,
+            borrows_len,
+            data_flat,
+        ) = array_refs![
+            input,
+            1,
+            8,
+            1,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            16,
+            16,
+            16,
+            16,
+            1,
+            1,
+            OBLIGATION_COLLATERAL_LEN
+                + (LEGACY_OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+        ];
+
+        let version = u8::from_le_bytes(*version);
+        if version > PROGRAM_VERSION {
+            msg!("Obligation version does not match lending program version");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let deposits_len = u8::from_le_bytes(*deposits_len);
+        let borrows_len = u8::from_le_bytes(*borrows_len);
+        let mut deposits = Vec::with_capacity(deposits_len as usize + 1);
+        let mut borrows = Vec::with_capacity(borrows_len as usize + 1);
+
+        let mut offset = 0;
+        for _ in 0..deposits_len {
+            let deposits_flat = array_ref![data_flat, offset, OBLIGATION_COLLATERAL_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (deposit_reserve, deposited_amount, market_value) =
+                array_refs![deposits_flat, PUBKEY_BYTES, 8, 16];
+            deposits.push(ObligationCollateral {
+                deposit_reserve: Pubkey::new(deposit_reserve),
+                deposited_amount: u64::from_le_bytes(*deposited_amount),
+                market_value: unpack_decimal(market_value),
+            });
+            offset += OBLIGATION_COLLATERAL_LEN;
+        }
+        for _ in 0..borrows_len {
+            let borrows_flat = array_ref![data_flat, offset, LEGACY_OBLIGATION_LIQUIDITY_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
             let (borrow_reserve, cumulative_borrow_rate_wads, borrowed_amount_wads, market_value) =
                 array_refs![borrows_flat, PUBKEY_BYTES, 16, 16, 16];
             borrows.push(ObligationLiquidity {
@@ -478,8 +957,9 @@ impl Pack for Obligation {
                 cumulative_borrow_rate_wads: unpack_decimal(cumulative_borrow_rate_wads),
                 borrowed_amount_wads: unpack_decimal(borrowed_amount_wads),
                 market_value: unpack_decimal(market_value),
+                collateral_reserve: COption::None,
             });
-            offset += OBLIGATION_LIQUIDITY_LEN;
+            offset += LEGACY_OBLIGATION_LIQUIDITY_LEN;
         }
 
         Ok(Self {
@@ -496,6 +976,7 @@ impl Pack for Obligation {
             borrowed_value: unpack_decimal(borrowed_value),
             allowed_borrow_value: unpack_decimal(allowed_borrow_value),
             unhealthy_borrow_value: unpack_decimal(unhealthy_borrow_value),
+            became_unhealthy_slot: 0,
         })
     }
 }
@@ -539,6 +1020,214 @@ mod test {
         );
     }
 
+    #[test]
+    fn refresh_account_metas_order() {
+        let deposit_reserves = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let borrow_reserves = [Pubkey::new_unique(), deposit_reserves[1]];
+        let obligation = Obligation {
+            deposits: deposit_reserves
+                .iter()
+                .map(|reserve| ObligationCollateral::new(*reserve))
+                .collect(),
+            borrows: borrow_reserves
+                .iter()
+                .map(|reserve| ObligationLiquidity::new(*reserve))
+                .collect(),
+            ..Obligation::default()
+        };
+        assert_eq!(
+            obligation.refresh_account_metas(),
+            vec![
+                deposit_reserves[0],
+                deposit_reserves[1],
+                borrow_reserves[0],
+                borrow_reserves[1],
+            ]
+        );
+    }
+
+    #[test]
+    fn update_unhealthy_slot() {
+        let mut obligation = Obligation {
+            borrows: vec![ObligationLiquidity::new(Pubkey::new_unique())],
+            borrowed_value: Decimal::from(90u64),
+            unhealthy_borrow_value: Decimal::from(100u64),
+            ..Obligation::default()
+        };
+        obligation.update_unhealthy_slot(10);
+        assert_eq!(obligation.became_unhealthy_slot, 0);
+
+        obligation.borrowed_value = Decimal::from(100u64);
+        obligation.update_unhealthy_slot(20);
+        assert_eq!(obligation.became_unhealthy_slot, 20);
+
+        // stays at the slot it first became unhealthy
+        obligation.update_unhealthy_slot(30);
+        assert_eq!(obligation.became_unhealthy_slot, 20);
+
+        obligation.borrowed_value = Decimal::from(99u64);
+        obligation.update_unhealthy_slot(40);
+        assert_eq!(obligation.became_unhealthy_slot, 0);
+    }
+
+    #[test]
+    fn has_zero_valued_collateral() {
+        let mut collateral = ObligationCollateral::new(Pubkey::new_unique());
+        collateral.deposit(100).unwrap();
+        let mut obligation = Obligation {
+            deposits: vec![collateral, ObligationCollateral::new(Pubkey::new_unique())],
+            ..Obligation::default()
+        };
+        assert!(obligation.has_zero_valued_collateral());
+
+        // an empty deposit has no value to begin with
+        obligation.deposits[0].market_value = Decimal::from(100u64);
+        assert!(!obligation.has_zero_valued_collateral());
+    }
+
+    #[test]
+    fn find_or_add_reserve_limit() {
+        let mut obligation = Obligation::default();
+        let deposit_reserve = Pubkey::new_unique();
+        let borrow_reserve = Pubkey::new_unique();
+        obligation
+            .find_or_add_collateral_to_deposits(deposit_reserve, 2)
+            .unwrap();
+        obligation
+            .find_or_add_liquidity_to_borrows(borrow_reserve, 2)
+            .unwrap();
+
+        // new reserves beyond the limit are rejected
+        assert_eq!(
+            obligation
+                .find_or_add_collateral_to_deposits(Pubkey::new_unique(), 2)
+                .unwrap_err(),
+            LendingError::ObligationReserveLimit.into()
+        );
+        assert_eq!(
+            obligation
+                .find_or_add_liquidity_to_borrows(Pubkey::new_unique(), 2)
+                .unwrap_err(),
+            LendingError::ObligationReserveLimit.into()
+        );
+
+        // existing reserves are still found at the limit
+        assert!(obligation
+            .find_or_add_collateral_to_deposits(deposit_reserve, 2)
+            .is_ok());
+        assert!(obligation
+            .find_or_add_liquidity_to_borrows(borrow_reserve, 2)
+            .is_ok());
+        assert_eq!(obligation.deposits.len() + obligation.borrows.len(), 2);
+
+        // the limit never exceeds what a refresh can process
+        let mut obligation = Obligation::default();
+        for _ in 0..MAX_REFRESH_RESERVES {
+            obligation
+                .find_or_add_collateral_to_deposits(Pubkey::new_unique(), usize::MAX)
+                .unwrap();
+        }
+        assert_eq!(
+            obligation
+                .find_or_add_collateral_to_deposits(Pubkey::new_unique(), usize::MAX)
+                .unwrap_err(),
+            LendingError::ObligationReserveLimit.into()
+        );
+    }
+
+    #[test]
+    fn borrowing_power_cross_and_bound() {
+        let sol_reserve = Pubkey::new_unique();
+        let usdc_reserve = Pubkey::new_unique();
+        let mut obligation = Obligation {
+            deposits: vec![
+                ObligationCollateral::new(sol_reserve),
+                ObligationCollateral::new(usdc_reserve),
+            ],
+            borrows: vec![ObligationLiquidity {
+                market_value: Decimal::from(30u64),
+                ..ObligationLiquidity::new(Pubkey::new_unique())
+            }],
+            ..Obligation::default()
+        };
+        let collateral_values = [
+            (Decimal::from(50u64), Decimal::from(60u64)),
+            (Decimal::from(40u64), Decimal::from(45u64)),
+        ];
+
+        assert_eq!(
+            obligation.calculate_borrowing_power(&collateral_values),
+            Ok((Decimal::from(90u64), Decimal::from(105u64)))
+        );
+
+        // bound collateral only counts up to the value of the borrows bound to it
+        obligation.borrows[0].collateral_reserve = COption::Some(sol_reserve);
+        assert_eq!(
+            obligation.calculate_borrowing_power(&collateral_values),
+            Ok((Decimal::from(70u64), Decimal::from(75u64)))
+        );
+
+        // a bound borrow larger than its collateral does not draw on the rest
+        obligation.borrows[0].market_value = Decimal::from(55u64);
+        assert_eq!(
+            obligation.calculate_borrowing_power(&collateral_values),
+            Ok((Decimal::from(90u64), Decimal::from(100u64)))
+        );
+
+        // a binding to collateral that is no longer deposited adds no borrowing power
+        obligation.borrows[0].collateral_reserve = COption::Some(Pubkey::new_unique());
+        assert_eq!(
+            obligation.calculate_borrowing_power(&collateral_values),
+            Ok((Decimal::from(90u64), Decimal::from(105u64)))
+        );
+    }
+
+    #[test]
+    fn remaining_bound_borrow_value() {
+        let sol_reserve = Pubkey::new_unique();
+        let usdc_reserve = Pubkey::new_unique();
+        let mut obligation = Obligation {
+            deposits: vec![
+                ObligationCollateral::new(sol_reserve),
+                ObligationCollateral::new(usdc_reserve),
+            ],
+            borrows: vec![
+                ObligationLiquidity {
+                    market_value: Decimal::from(30u64),
+                    collateral_reserve: COption::Some(sol_reserve),
+                    ..ObligationLiquidity::new(Pubkey::new_unique())
+                },
+                ObligationLiquidity {
+                    market_value: Decimal::from(15u64),
+                    collateral_reserve: COption::Some(sol_reserve),
+                    ..ObligationLiquidity::new(Pubkey::new_unique())
+                },
+                ObligationLiquidity {
+                    market_value: Decimal::from(100u64),
+                    ..ObligationLiquidity::new(Pubkey::new_unique())
+                },
+            ],
+            ..Obligation::default()
+        };
+
+        // only the borrows bound to the collateral count against it
+        assert_eq!(
+            obligation.remaining_bound_borrow_value(&sol_reserve, Decimal::from(50u64)),
+            Ok(Decimal::from(5u64))
+        );
+        assert_eq!(
+            obligation.remaining_bound_borrow_value(&usdc_reserve, Decimal::from(50u64)),
+            Ok(Decimal::from(50u64))
+        );
+
+        // bound borrows already past the collateral's allowed value leave nothing
+        obligation.borrows[1].market_value = Decimal::from(25u64);
+        assert_eq!(
+            obligation.remaining_bound_borrow_value(&sol_reserve, Decimal::from(50u64)),
+            Ok(Decimal::zero())
+        );
+    }
+
     // Creates rates (r1, r2) where 0 < r1 <= r2 <= 100*r1
     prop_compose! {
         fn cumulative_rates()(rate in 1..=u128::MAX)(
@@ -631,4 +1320,394 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn liquidation_price() {
+        let sol_reserve_pubkey = Pubkey::new_unique();
+        let mut sol_reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                mint_decimals: 9,
+                available_amount: 100_000_000_000,
+                market_price: Decimal::from(20u64),
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 100_000_000_000,
+                ..ReserveCollateral::default()
+            },
+            config: ReserveConfig {
+                liquidation_threshold: 80,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        // 100 SOL at 20 USDC backing 1000 USDC
+        let obligation = Obligation {
+            deposits: vec![ObligationCollateral {
+                deposited_amount: 100_000_000_000,
+                market_value: Decimal::from(2_000u64),
+                ..ObligationCollateral::new(sol_reserve_pubkey)
+            }],
+            borrows: vec![ObligationLiquidity {
+                market_value: Decimal::from(1_000u64),
+                ..ObligationLiquidity::new(Pubkey::new_unique())
+            }],
+            borrowed_value: Decimal::from(1_000u64),
+            unhealthy_borrow_value: Decimal::from(1_600u64),
+            ..Obligation::default()
+        };
+
+        // 1000 USDC / 80% -> 1250 USDC / 100 SOL -> 12.5 USDC
+        let liquidation_price = obligation
+            .liquidation_price(&sol_reserve_pubkey, &sol_reserve)
+            .unwrap();
+        assert_eq!(
+            liquidation_price,
+            Decimal::from(25u64).try_div(2u64).unwrap()
+        );
+
+        // recompute the unhealthy borrow value the way a refresh would at a given price
+        let mut is_liquidatable = |market_price| {
+            sol_reserve.liquidity.market_price = market_price;
+            let market_value = sol_reserve
+                .collateral_exchange_rate()
+                .unwrap()
+                .decimal_collateral_to_liquidity(obligation.deposits[0].deposited_amount.into())
+                .unwrap()
+                .try_mul(sol_reserve.liquidity.market_price)
+                .unwrap()
+                .try_div(1_000_000_000u64)
+                .unwrap();
+            let unhealthy_borrow_value = market_value.try_mul(Rate::from_percent(80)).unwrap();
+            obligation.borrowed_value >= unhealthy_borrow_value
+        };
+        assert!(is_liquidatable(liquidation_price));
+        assert!(!is_liquidatable(
+            liquidation_price
+                .try_add(Decimal::from_scaled_val(1_000_000))
+                .unwrap()
+        ));
+
+        // a collateral the obligation hasn't deposited has no liquidation price
+        assert_eq!(
+            obligation.liquidation_price(&Pubkey::new_unique(), &sol_reserve),
+            Err(LendingError::InvalidObligationCollateral.into())
+        );
+    }
+
+    #[test]
+    fn max_borrow_amount_net_of_fees() {
+        let obligation = Obligation {
+            allowed_borrow_value: Decimal::from(100u64),
+            borrowed_value: Decimal::from(40u64),
+            ..Obligation::default()
+        };
+        let mut fees = ReserveFees {
+            borrow_fee_wad: 0,
+            flash_loan_fee_wad: 0,
+            host_fee_percentage: 0,
+        };
+        assert_eq!(
+            obligation.max_borrow_amount(Decimal::one(), 0, &fees),
+            Ok(60)
+        );
+
+        // 1% is under the minimum fee of one token each to the owner and host
+        fees.borrow_fee_wad = 10_000_000_000_000_000;
+        fees.host_fee_percentage = 20;
+        assert_eq!(
+            obligation.max_borrow_amount(Decimal::one(), 0, &fees),
+            Ok(58)
+        );
+
+        // nothing can be borrowed past the allowed borrow value
+        let unhealthy = Obligation {
+            borrowed_value: Decimal::from(101u64),
+            ..obligation.clone()
+        };
+        assert_eq!(unhealthy.max_borrow_amount(Decimal::one(), 0, &fees), Ok(0));
+    }
+
+    proptest! {
+        #[test]
+        fn max_borrow_amount_matches_calculate_borrow(
+            remaining_borrow_value in 1..=1_000_000_000u64,
+            market_price in 1..=1_000u64,
+            mint_decimals in 0..=9u8,
+            borrow_fee_wad in 0..=WAD / 10,
+            host_fee_percentage in 0..=100u8,
+        ) {
+            let obligation = Obligation {
+                allowed_borrow_value: Decimal::from(remaining_borrow_value),
+                ..Obligation::default()
+            };
+            let reserve = Reserve {
+                liquidity: ReserveLiquidity {
+                    mint_decimals,
+                    market_price: Decimal::from(market_price),
+                    ..ReserveLiquidity::default()
+                },
+                config: ReserveConfig {
+                    fees: ReserveFees {
+                        borrow_fee_wad,
+                        flash_loan_fee_wad: 0,
+                        host_fee_percentage,
+                    },
+                    ..ReserveConfig::default()
+                },
+                ..Reserve::default()
+            };
+            let max_borrow_value = obligation.remaining_borrow_value()?;
+            let max_borrow_amount = obligation.max_borrow_amount(
+                reserve.liquidity.market_price,
+                mint_decimals,
+                &reserve.config.fees,
+            )?;
+
+            if max_borrow_amount > 0 {
+                reserve.calculate_borrow(max_borrow_amount, max_borrow_value, 0)?;
+            }
+            prop_assert!(reserve
+                .calculate_borrow(max_borrow_amount + 1, max_borrow_value, 0)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn revalue_collateral_and_liquidity() {
+        let deposit_reserve = Pubkey::new_unique();
+        let bound_deposit_reserve = Pubkey::new_unique();
+        let borrow_reserve = Pubkey::new_unique();
+        let mut obligation = Obligation {
+            deposits: vec![
+                ObligationCollateral {
+                    market_value: Decimal::from(100u64),
+                    ..ObligationCollateral::new(deposit_reserve)
+                },
+                ObligationCollateral {
+                    market_value: Decimal::from(100u64),
+                    ..ObligationCollateral::new(bound_deposit_reserve)
+                },
+            ],
+            borrows: vec![ObligationLiquidity {
+                market_value: Decimal::from(40u64),
+                collateral_reserve: COption::Some(bound_deposit_reserve),
+                ..ObligationLiquidity::new(borrow_reserve)
+            }],
+            deposited_value: Decimal::from(200u64),
+            borrowed_value: Decimal::from(40u64),
+            allowed_borrow_value: Decimal::from(90u64),
+            unhealthy_borrow_value: Decimal::from(100u64),
+            ..Obligation::default()
+        };
+        let loan_to_value_rate = Rate::from_percent(50);
+        let liquidation_threshold_rate = Rate::from_percent(60);
+
+        // adding to unbound collateral adds borrowing power
+        obligation
+            .revalue_collateral(
+                0,
+                Decimal::from(150u64),
+                loan_to_value_rate,
+                liquidation_threshold_rate,
+            )
+            .unwrap();
+        assert_eq!(obligation.deposits[0].market_value, Decimal::from(150u64));
+        assert_eq!(obligation.deposited_value, Decimal::from(250u64));
+        assert_eq!(obligation.allowed_borrow_value, Decimal::from(115u64));
+        assert_eq!(obligation.unhealthy_borrow_value, Decimal::from(130u64));
+
+        // adding to bound collateral leaves borrowing power for the next refresh
+        obligation
+            .revalue_collateral(
+                1,
+                Decimal::from(200u64),
+                loan_to_value_rate,
+                liquidation_threshold_rate,
+            )
+            .unwrap();
+        assert_eq!(obligation.deposited_value, Decimal::from(350u64));
+        assert_eq!(obligation.allowed_borrow_value, Decimal::from(115u64));
+        assert_eq!(obligation.unhealthy_borrow_value, Decimal::from(130u64));
+
+        // removing collateral always takes its borrowing power with it
+        obligation
+            .revalue_collateral(
+                1,
+                Decimal::from(100u64),
+                loan_to_value_rate,
+                liquidation_threshold_rate,
+            )
+            .unwrap();
+        assert_eq!(obligation.deposited_value, Decimal::from(250u64));
+        assert_eq!(obligation.allowed_borrow_value, Decimal::from(65u64));
+        assert_eq!(obligation.unhealthy_borrow_value, Decimal::from(70u64));
+
+        obligation
+            .revalue_liquidity(0, Decimal::from(60u64))
+            .unwrap();
+        assert_eq!(obligation.borrows[0].market_value, Decimal::from(60u64));
+        assert_eq!(obligation.borrowed_value, Decimal::from(60u64));
+        obligation.revalue_liquidity(0, Decimal::zero()).unwrap();
+        assert_eq!(obligation.borrowed_value, Decimal::zero());
+    }
+
+    #[test]
+    fn split_off_and_merge() {
+        let deposit_reserve = Pubkey::new_unique();
+        let borrow_reserve = Pubkey::new_unique();
+        let mut obligation = Obligation {
+            deposits: vec![ObligationCollateral {
+                deposited_amount: 1_001,
+                ..ObligationCollateral::new(deposit_reserve)
+            }],
+            borrows: vec![ObligationLiquidity {
+                cumulative_borrow_rate_wads: Decimal::one(),
+                borrowed_amount_wads: Decimal::from(500u64),
+                collateral_reserve: COption::Some(deposit_reserve),
+                ..ObligationLiquidity::new(borrow_reserve)
+            }],
+            ..Obligation::default()
+        };
+
+        // collateral is rounded down in the source's favor
+        let mut split = Obligation::default();
+        obligation
+            .split_off(&mut split, Rate::from_percent(50), MAX_OBLIGATION_RESERVES)
+            .unwrap();
+        assert_eq!(obligation.deposits[0].deposited_amount, 501);
+        assert_eq!(split.deposits[0].deposited_amount, 500);
+        assert_eq!(
+            obligation.borrows[0].borrowed_amount_wads,
+            Decimal::from(250u64)
+        );
+        assert_eq!(split.borrows[0].borrowed_amount_wads, Decimal::from(250u64));
+        assert_eq!(split.borrows[0].cumulative_borrow_rate_wads, Decimal::one());
+        assert_eq!(
+            split.borrows[0].collateral_reserve,
+            COption::Some(deposit_reserve)
+        );
+
+        // interest accrued on one half is kept when merging back
+        obligation.borrows[0]
+            .accrue_interest(Decimal::from(2u64))
+            .unwrap();
+        obligation
+            .merge(&mut split, MAX_OBLIGATION_RESERVES)
+            .unwrap();
+        assert!(split.deposits.is_empty() && split.borrows.is_empty());
+        assert_eq!(obligation.deposits[0].deposited_amount, 1_001);
+        assert_eq!(
+            obligation.borrows[0].borrowed_amount_wads,
+            Decimal::from(1_000u64)
+        );
+        assert_eq!(
+            obligation.borrows[0].cumulative_borrow_rate_wads,
+            Decimal::from(2u64)
+        );
+
+        // borrows from the same reserve can't merge with different collateral bindings
+        let mut unbound = Obligation {
+            borrows: vec![ObligationLiquidity {
+                cumulative_borrow_rate_wads: Decimal::from(2u64),
+                borrowed_amount_wads: Decimal::one(),
+                ..ObligationLiquidity::new(borrow_reserve)
+            }],
+            ..Obligation::default()
+        };
+        assert_eq!(
+            obligation.merge(&mut unbound, MAX_OBLIGATION_RESERVES),
+            Err(LendingError::InvalidObligationLiquidity.into())
+        );
+    }
+
+    #[test]
+    fn unpack_legacy_obligation() {
+        let obligation = Obligation {
+            version: PROGRAM_VERSION,
+            last_update: LastUpdate {
+                slot: 41,
+                stale: true,
+            },
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![ObligationCollateral {
+                deposited_amount: 1_000,
+                market_value: Decimal::from(2_000u64),
+                ..ObligationCollateral::new(Pubkey::new_unique())
+            }],
+            borrows: vec![
+                ObligationLiquidity {
+                    cumulative_borrow_rate_wads: Decimal::from(2u64),
+                    borrowed_amount_wads: Decimal::from(500u64),
+                    market_value: Decimal::from(500u64),
+                    ..ObligationLiquidity::new(Pubkey::new_unique())
+                },
+                ObligationLiquidity {
+                    cumulative_borrow_rate_wads: Decimal::one(),
+                    borrowed_amount_wads: Decimal::from(100u64),
+                    market_value: Decimal::from(300u64),
+                    ..ObligationLiquidity::new(Pubkey::new_unique())
+                },
+            ],
+            deposited_value: Decimal::from(2_000u64),
+            borrowed_value: Decimal::from(800u64),
+            allowed_borrow_value: Decimal::from(1_500u64),
+            unhealthy_borrow_value: Decimal::from(1_600u64),
+            became_unhealthy_slot: 0,
+        };
+
+        // the legacy layout is the current one without the collateral binding at the end of
+        // each borrow and without the trailing became_unhealthy_slot
+        let mut packed = [0u8; OBLIGATION_LEN];
+        Obligation::pack(obligation.clone(), &mut packed).unwrap();
+        let deposits_end = offsets::obligation::DATA_FLAT + OBLIGATION_COLLATERAL_LEN;
+        let mut legacy = packed[..deposits_end].to_vec();
+        for i in 0..obligation.borrows.len() {
+            let borrow = deposits_end + i * OBLIGATION_LIQUIDITY_LEN;
+            legacy.extend_from_slice(&packed[borrow..borrow + LEGACY_OBLIGATION_LIQUIDITY_LEN]);
+        }
+        legacy.resize(LEGACY_OBLIGATION_LEN, 0);
+
+        let unpacked = Obligation::unpack(&legacy).unwrap();
+        assert_eq!(unpacked, obligation);
+
+        // migrated obligations keep their deposits and borrows
+        let mut migrated = [0u8; OBLIGATION_LEN];
+        Obligation::pack(unpacked, &mut migrated).unwrap();
+        assert_eq!(migrated, packed);
+
+        assert_eq!(
+            Obligation::unpack(&legacy[..LEGACY_OBLIGATION_LEN - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            Obligation::unpack(&[0u8; LEGACY_OBLIGATION_LEN]),
+            Err(ProgramError::UninitializedAccount)
+        );
+    }
+
+    #[test]
+    fn check_refresh_reserve_count() {
+        let mut obligation = Obligation {
+            deposits: vec![
+                ObligationCollateral::new(Pubkey::new_unique());
+                MAX_REFRESH_RESERVES / 2
+            ],
+            borrows: vec![
+                ObligationLiquidity::new(Pubkey::new_unique());
+                MAX_REFRESH_RESERVES - MAX_REFRESH_RESERVES / 2
+            ],
+            ..Obligation::default()
+        };
+        assert_eq!(obligation.check_refresh_reserve_count(), Ok(()));
+
+        obligation
+            .borrows
+            .push(ObligationLiquidity::new(Pubkey::new_unique()));
+        assert_eq!(
+            obligation.check_refresh_reserve_count(),
+            Err(LendingError::TooManyReserves.into())
+        );
+    }
 }
@@ -0,0 +1,672 @@
+//! Byte offsets of the fields of packed accounts, for reading single fields without
+//! unpacking the whole account. Values are little-endian and decimals are WAD-scaled `u128`s.
+
+/// Lending market account layout
+pub mod lending_market {
+    use crate::state::LendingMarket;
+    use solana_program::{program_pack::Pack, pubkey::PUBKEY_BYTES};
+
+    /// Length of a lending market account
+    pub const LEN: usize = LendingMarket::LEN;
+
+    /// Version of the struct
+    pub const VERSION: usize = 0;
+    /// Bump seed for the derived lending market authority
+    pub const BUMP_SEED: usize = VERSION + 1;
+    /// Owner authority
+    pub const OWNER: usize = BUMP_SEED + 1;
+    /// Currency market prices are quoted in
+    pub const QUOTE_CURRENCY: usize = OWNER + PUBKEY_BYTES;
+    /// Token program id
+    pub const TOKEN_PROGRAM_ID: usize = QUOTE_CURRENCY + 32;
+    /// Pending owner, as a compact `COption<Pubkey>`
+    pub const PENDING_OWNER: usize = TOKEN_PROGRAM_ID + PUBKEY_BYTES;
+    /// Whether strict instruction order is enforced
+    pub const STRICT_INSTRUCTION_ORDER: usize = PENDING_OWNER + (1 + PUBKEY_BYTES);
+    /// Whether freezable liquidity mints are allowed
+    pub const ALLOW_FREEZABLE_MINT: usize = STRICT_INSTRUCTION_ORDER + 1;
+    /// Maximum number of distinct reserves per obligation, 0 for the full capacity
+    pub const MAX_OBLIGATION_RESERVES: usize = ALLOW_FREEZABLE_MINT + 1;
+    /// Whether conservative obligation refreshes are allowed
+    pub const ALLOW_CONSERVATIVE_REFRESH: usize = MAX_OBLIGATION_RESERVES + 1;
+    /// Slots an obligation must be unhealthy for before it can be liquidated
+    pub const LIQUIDATION_GRACE_SLOTS: usize = ALLOW_CONSERVATIVE_REFRESH + 1;
+    /// Minimum value a partial liquidation must repay, in the quote currency
+    pub const MIN_LIQUIDATION_VALUE: usize = LIQUIDATION_GRACE_SLOTS + 8;
+    /// Slots per year rates are annualized with, 0 for the default estimate
+    pub const SLOTS_PER_YEAR: usize = MIN_LIQUIDATION_VALUE + 16;
+}
+
+/// Reserve account layout
+pub mod reserve {
+    use crate::state::{Reserve, PRICE_HISTORY_LEN, PRICE_SAMPLE_LEN};
+    use solana_program::{program_pack::Pack, pubkey::PUBKEY_BYTES};
+
+    /// Length of a reserve account
+    pub const LEN: usize = Reserve::LEN;
+
+    /// Version of the struct
+    pub const VERSION: usize = 0;
+    /// Last slot when supply and rates updated
+    pub const LAST_UPDATE_SLOT: usize = VERSION + 1;
+    /// True when marked stale
+    pub const LAST_UPDATE_STALE: usize = LAST_UPDATE_SLOT + 8;
+    /// Lending market address
+    pub const LENDING_MARKET: usize = LAST_UPDATE_STALE + 1;
+    /// Reserve liquidity mint address
+    pub const LIQUIDITY_MINT_PUBKEY: usize = LENDING_MARKET + PUBKEY_BYTES;
+    /// Reserve liquidity mint decimals
+    pub const LIQUIDITY_MINT_DECIMALS: usize = LIQUIDITY_MINT_PUBKEY + PUBKEY_BYTES;
+    /// Reserve liquidity supply address
+    pub const LIQUIDITY_SUPPLY_PUBKEY: usize = LIQUIDITY_MINT_DECIMALS + 1;
+    /// Reserve liquidity fee receiver address
+    pub const LIQUIDITY_FEE_RECEIVER: usize = LIQUIDITY_SUPPLY_PUBKEY + PUBKEY_BYTES;
+    /// Reserve liquidity oracle account, as a `COption<Pubkey>`
+    pub const LIQUIDITY_ORACLE_PUBKEY: usize = LIQUIDITY_FEE_RECEIVER + PUBKEY_BYTES;
+    /// Reserve liquidity available
+    pub const LIQUIDITY_AVAILABLE_AMOUNT: usize = LIQUIDITY_ORACLE_PUBKEY + (4 + PUBKEY_BYTES);
+    /// Reserve liquidity borrowed
+    pub const LIQUIDITY_BORROWED_AMOUNT_WADS: usize = LIQUIDITY_AVAILABLE_AMOUNT + 8;
+    /// Reserve liquidity cumulative borrow rate
+    pub const LIQUIDITY_CUMULATIVE_BORROW_RATE_WADS: usize = LIQUIDITY_BORROWED_AMOUNT_WADS + 16;
+    /// Reserve liquidity market price in quote currency
+    pub const LIQUIDITY_MARKET_PRICE: usize = LIQUIDITY_CUMULATIVE_BORROW_RATE_WADS + 16;
+    /// Reserve collateral mint address
+    pub const COLLATERAL_MINT_PUBKEY: usize = LIQUIDITY_MARKET_PRICE + 16;
+    /// Reserve collateral mint supply
+    pub const COLLATERAL_MINT_TOTAL_SUPPLY: usize = COLLATERAL_MINT_PUBKEY + PUBKEY_BYTES;
+    /// Reserve collateral supply address
+    pub const COLLATERAL_SUPPLY_PUBKEY: usize = COLLATERAL_MINT_TOTAL_SUPPLY + 8;
+    /// Optimal utilization rate
+    pub const CONFIG_OPTIMAL_UTILIZATION_RATE: usize = COLLATERAL_SUPPLY_PUBKEY + PUBKEY_BYTES;
+    /// Loan to value ratio
+    pub const CONFIG_LOAN_TO_VALUE_RATIO: usize = CONFIG_OPTIMAL_UTILIZATION_RATE + 1;
+    /// Liquidation bonus
+    pub const CONFIG_LIQUIDATION_BONUS: usize = CONFIG_LOAN_TO_VALUE_RATIO + 1;
+    /// Liquidation threshold
+    pub const CONFIG_LIQUIDATION_THRESHOLD: usize = CONFIG_LIQUIDATION_BONUS + 1;
+    /// Min borrow APY
+    pub const CONFIG_MIN_BORROW_RATE: usize = CONFIG_LIQUIDATION_THRESHOLD + 1;
+    /// Optimal borrow APY
+    pub const CONFIG_OPTIMAL_BORROW_RATE: usize = CONFIG_MIN_BORROW_RATE + 1;
+    /// Max borrow APY
+    pub const CONFIG_MAX_BORROW_RATE: usize = CONFIG_OPTIMAL_BORROW_RATE + 1;
+    /// Borrow fee
+    pub const CONFIG_FEES_BORROW_FEE_WAD: usize = CONFIG_MAX_BORROW_RATE + 1;
+    /// Flash loan fee
+    pub const CONFIG_FEES_FLASH_LOAN_FEE_WAD: usize = CONFIG_FEES_BORROW_FEE_WAD + 8;
+    /// Host fee percentage
+    pub const CONFIG_FEES_HOST_FEE_PERCENTAGE: usize = CONFIG_FEES_FLASH_LOAN_FEE_WAD + 8;
+    /// Deposit staking pool, as a compact `COption<Pubkey>`
+    pub const CONFIG_DEPOSIT_STAKING_POOL: usize = CONFIG_FEES_HOST_FEE_PERCENTAGE + 1;
+    /// Whether the TWAP is used
+    pub const CONFIG_USE_TWAP: usize = CONFIG_DEPOSIT_STAKING_POOL + (1 + PUBKEY_BYTES);
+    /// TWAP window
+    pub const CONFIG_TWAP_WINDOW_SLOTS: usize = CONFIG_USE_TWAP + 1;
+    /// Index of the next price sample
+    pub const LIQUIDITY_PRICE_HISTORY_NEXT_INDEX: usize = CONFIG_TWAP_WINDOW_SLOTS + 8;
+    /// Number of price samples recorded
+    pub const LIQUIDITY_PRICE_HISTORY_LEN: usize = LIQUIDITY_PRICE_HISTORY_NEXT_INDEX + 1;
+    /// Price samples, each a slot followed by a price
+    pub const LIQUIDITY_PRICE_HISTORY_SAMPLES: usize = LIQUIDITY_PRICE_HISTORY_LEN + 1;
+    /// Borrow fee discount staking pool, as a compact `COption<Pubkey>`
+    pub const CONFIG_BORROW_FEE_DISCOUNT_STAKING_POOL: usize =
+        LIQUIDITY_PRICE_HISTORY_SAMPLES + (PRICE_SAMPLE_LEN * PRICE_HISTORY_LEN);
+    /// Borrow fee discount percentage
+    pub const CONFIG_BORROW_FEE_DISCOUNT_PERCENTAGE: usize =
+        CONFIG_BORROW_FEE_DISCOUNT_STAKING_POOL + (1 + PUBKEY_BYTES);
+    /// Maximum slots accrued over in a single refresh
+    pub const CONFIG_MAX_ACCRUAL_SLOTS: usize = CONFIG_BORROW_FEE_DISCOUNT_PERCENTAGE + 1;
+    /// Whether borrowing is disabled
+    pub const CONFIG_BORROWING_DISABLED: usize = CONFIG_MAX_ACCRUAL_SLOTS + 8;
+    /// Liquidation bonus ramp
+    pub const CONFIG_LIQUIDATION_BONUS_RAMP_SLOTS: usize = CONFIG_BORROWING_DISABLED + 1;
+    /// Reserve liquidity cumulative supply rate
+    pub const LIQUIDITY_CUMULATIVE_SUPPLY_RATE_WADS: usize =
+        CONFIG_LIQUIDATION_BONUS_RAMP_SLOTS + 8;
+    /// Minimum initial deposit
+    pub const CONFIG_MIN_INITIAL_DEPOSIT: usize = LIQUIDITY_CUMULATIVE_SUPPLY_RATE_WADS + 16;
+    /// Maximum oracle price staleness
+    pub const CONFIG_MAX_PRICE_STALENESS_SLOTS: usize = CONFIG_MIN_INITIAL_DEPOSIT + 8;
+    /// Whether the reserve is paused
+    pub const IS_PAUSED: usize = CONFIG_MAX_PRICE_STALENESS_SLOTS + 8;
+    /// Liquidity borrow rate interest was last accrued at
+    pub const LIQUIDITY_BORROW_RATE: usize = IS_PAUSED + 1;
+    /// Maximum liquidity moved in a single slot
+    pub const CONFIG_MAX_SLOT_LIQUIDITY_CHANGE_PERCENT: usize = LIQUIDITY_BORROW_RATE + 16;
+    /// Liquidity moved in the current slot, as a `u16` in basis points
+    pub const SLOT_LIQUIDITY_CHANGE_BPS: usize = CONFIG_MAX_SLOT_LIQUIDITY_CHANGE_PERCENT + 1;
+    /// Maximum oracle price move in a refresh, as a `u16` in basis points
+    pub const CONFIG_MAX_ORACLE_DEVIATION_BPS: usize = SLOT_LIQUIDITY_CHANGE_BPS + 2;
+    /// Isolated debt ceiling in quote currency
+    pub const CONFIG_ISOLATED_DEBT_CEILING: usize = CONFIG_MAX_ORACLE_DEVIATION_BPS + 2;
+    /// Liquidity donated to the reserve
+    pub const LIQUIDITY_PROTOCOL_OWNED_AMOUNT: usize = CONFIG_ISOLATED_DEBT_CEILING + 8;
+    /// Whether the reserve's deposits give no borrowing power
+    pub const CONFIG_COLLATERAL_DISABLED: usize = LIQUIDITY_PROTOCOL_OWNED_AMOUNT + 8;
+    /// Liquidation bonus for an obligation whose borrows are worth its deposits
+    pub const CONFIG_MAX_LIQUIDATION_BONUS: usize = CONFIG_COLLATERAL_DISABLED + 1;
+    /// Whether the reserve is winding down
+    pub const IS_WIND_DOWN: usize = CONFIG_MAX_LIQUIDATION_BONUS + 1;
+    /// Least stake for the borrow fee discount
+    pub const CONFIG_BORROW_FEE_DISCOUNT_MIN_STAKE: usize = IS_WIND_DOWN + 1;
+    /// Deposit staking pool before the last change, as a compact `COption<Pubkey>`
+    pub const PREVIOUS_DEPOSIT_STAKING_POOL: usize = CONFIG_BORROW_FEE_DISCOUNT_MIN_STAKE + 8;
+    /// Liquidation bonus for an obligation that just became unhealthy
+    pub const CONFIG_MIN_LIQUIDATION_BONUS: usize = PREVIOUS_DEPOSIT_STAKING_POOL + 33;
+}
+
+/// Obligation account layout
+pub mod obligation {
+    use crate::state::{
+        Obligation, MAX_OBLIGATION_RESERVES, OBLIGATION_COLLATERAL_LEN, OBLIGATION_LIQUIDITY_LEN,
+    };
+    use solana_program::{program_pack::Pack, pubkey::PUBKEY_BYTES};
+
+    /// Length of an obligation account
+    pub const LEN: usize = Obligation::LEN;
+
+    /// Version of the struct
+    pub const VERSION: usize = 0;
+    /// Last slot when the obligation was refreshed
+    pub const LAST_UPDATE_SLOT: usize = VERSION + 1;
+    /// True when marked stale
+    pub const LAST_UPDATE_STALE: usize = LAST_UPDATE_SLOT + 8;
+    /// Lending market address
+    pub const LENDING_MARKET: usize = LAST_UPDATE_STALE + 1;
+    /// Owner authority
+    pub const OWNER: usize = LENDING_MARKET + PUBKEY_BYTES;
+    /// Market value of deposits
+    pub const DEPOSITED_VALUE: usize = OWNER + PUBKEY_BYTES;
+    /// Market value of borrows
+    pub const BORROWED_VALUE: usize = DEPOSITED_VALUE + 16;
+    /// Maximum borrow value
+    pub const ALLOWED_BORROW_VALUE: usize = BORROWED_VALUE + 16;
+    /// Borrow value at which the obligation is unhealthy
+    pub const UNHEALTHY_BORROW_VALUE: usize = ALLOWED_BORROW_VALUE + 16;
+    /// Number of deposits
+    pub const DEPOSITS_LEN: usize = UNHEALTHY_BORROW_VALUE + 16;
+    /// Number of borrows
+    pub const BORROWS_LEN: usize = DEPOSITS_LEN + 1;
+    /// Deposits followed by borrows, see [`obligation_collateral`] and [`obligation_liquidity`]
+    pub const DATA_FLAT: usize = BORROWS_LEN + 1;
+    /// Slot the obligation became unhealthy
+    pub const BECAME_UNHEALTHY_SLOT: usize = DATA_FLAT
+        + (OBLIGATION_COLLATERAL_LEN + OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1));
+}
+
+/// Layout of a deposit in [`obligation::DATA_FLAT`], relative to the start of the deposit.
+/// Deposits are stored first, so deposit `i` starts at
+/// `obligation::DATA_FLAT + i * obligation_collateral::LEN`.
+pub mod obligation_collateral {
+    use crate::state::OBLIGATION_COLLATERAL_LEN;
+    use solana_program::pubkey::PUBKEY_BYTES;
+
+    /// Length of a packed deposit
+    pub const LEN: usize = OBLIGATION_COLLATERAL_LEN;
+
+    /// Deposit reserve address
+    pub const DEPOSIT_RESERVE: usize = 0;
+    /// Collateral deposited
+    pub const DEPOSITED_AMOUNT: usize = DEPOSIT_RESERVE + PUBKEY_BYTES;
+    /// Market value of the collateral
+    pub const MARKET_VALUE: usize = DEPOSITED_AMOUNT + 8;
+}
+
+/// Layout of a borrow in [`obligation::DATA_FLAT`], relative to the start of the borrow.
+/// Borrows follow the deposits, so borrow `i` starts at
+/// `obligation::DATA_FLAT + deposits_len * obligation_collateral::LEN + i * obligation_liquidity::LEN`.
+pub mod obligation_liquidity {
+    use crate::state::OBLIGATION_LIQUIDITY_LEN;
+    use solana_program::pubkey::PUBKEY_BYTES;
+
+    /// Length of a packed borrow
+    pub const LEN: usize = OBLIGATION_LIQUIDITY_LEN;
+
+    /// Borrow reserve address
+    pub const BORROW_RESERVE: usize = 0;
+    /// Cumulative borrow rate when last refreshed
+    pub const CUMULATIVE_BORROW_RATE_WADS: usize = BORROW_RESERVE + PUBKEY_BYTES;
+    /// Liquidity borrowed
+    pub const BORROWED_AMOUNT_WADS: usize = CUMULATIVE_BORROW_RATE_WADS + 16;
+    /// Market value of the borrow
+    pub const MARKET_VALUE: usize = BORROWED_AMOUNT_WADS + 16;
+    /// Collateral reserve the borrow is bound to, as a compact `COption<Pubkey>`
+    pub const COLLATERAL_RESERVE: usize = MARKET_VALUE + 16;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        math::{Decimal, Rate},
+        state::{
+            LastUpdate, LendingMarket, Obligation, ObligationCollateral, ObligationLiquidity,
+            Reserve, ReserveCollateral, ReserveConfig, ReserveFees, ReserveLiquidity,
+            PROGRAM_VERSION,
+        },
+    };
+    use solana_program::{
+        program_option::COption,
+        program_pack::Pack,
+        pubkey::{Pubkey, PUBKEY_BYTES},
+    };
+    use std::convert::TryInto;
+
+    fn read_u64(data: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn read_decimal(data: &[u8], offset: usize) -> Decimal {
+        Decimal::from_scaled_val(u128::from_le_bytes(
+            data[offset..offset + 16].try_into().unwrap(),
+        ))
+    }
+
+    fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+        Pubkey::new(&data[offset..offset + 32])
+    }
+
+    #[test]
+    fn lending_market_offsets() {
+        let market = LendingMarket {
+            version: PROGRAM_VERSION,
+            bump_seed: 254,
+            owner: Pubkey::new_unique(),
+            quote_currency: [7; 32],
+            token_program_id: Pubkey::new_unique(),
+            pending_owner: COption::Some(Pubkey::new_unique()),
+            strict_instruction_order: true,
+            allow_freezable_mint: true,
+            max_obligation_reserves: 4,
+            allow_conservative_refresh: true,
+            liquidation_grace_slots: 5,
+            min_liquidation_value: Decimal::from_percent(250),
+            slots_per_year: 70_000_000,
+        };
+        let mut data = [0u8; lending_market::LEN];
+        LendingMarket::pack(market.clone(), &mut data).unwrap();
+
+        assert_eq!(data[lending_market::VERSION], PROGRAM_VERSION);
+        assert_eq!(data[lending_market::BUMP_SEED], 254);
+        assert_eq!(read_pubkey(&data, lending_market::OWNER), market.owner);
+        assert_eq!(
+            data[lending_market::QUOTE_CURRENCY..lending_market::QUOTE_CURRENCY + 32],
+            [7; 32]
+        );
+        assert_eq!(
+            read_pubkey(&data, lending_market::TOKEN_PROGRAM_ID),
+            market.token_program_id
+        );
+        assert_eq!(data[lending_market::PENDING_OWNER], 1);
+        assert_eq!(
+            COption::Some(read_pubkey(&data, lending_market::PENDING_OWNER + 1)),
+            market.pending_owner
+        );
+        assert_eq!(data[lending_market::STRICT_INSTRUCTION_ORDER], 1);
+        assert_eq!(data[lending_market::ALLOW_FREEZABLE_MINT], 1);
+        assert_eq!(data[lending_market::MAX_OBLIGATION_RESERVES], 4);
+        assert_eq!(data[lending_market::ALLOW_CONSERVATIVE_REFRESH], 1);
+        assert_eq!(read_u64(&data, lending_market::LIQUIDATION_GRACE_SLOTS), 5);
+        assert_eq!(
+            read_decimal(&data, lending_market::MIN_LIQUIDATION_VALUE),
+            Decimal::from_percent(250)
+        );
+        assert_eq!(read_u64(&data, lending_market::SLOTS_PER_YEAR), 70_000_000);
+        // followed by 91 bytes of padding
+        assert_eq!(lending_market::SLOTS_PER_YEAR + 8 + 91, lending_market::LEN);
+    }
+
+    #[test]
+    fn reserve_offsets() {
+        let reserve = Reserve {
+            version: PROGRAM_VERSION,
+            last_update: LastUpdate {
+                slot: 11,
+                stale: true,
+            },
+            lending_market: Pubkey::new_unique(),
+            liquidity: ReserveLiquidity {
+                mint_pubkey: Pubkey::new_unique(),
+                mint_decimals: 9,
+                supply_pubkey: Pubkey::new_unique(),
+                fee_receiver: Pubkey::new_unique(),
+                oracle_pubkey: COption::Some(Pubkey::new_unique()),
+                available_amount: 12,
+                borrowed_amount_wads: Decimal::from(13u64),
+                cumulative_borrow_rate_wads: Decimal::from(14u64),
+                market_price: Decimal::from(15u64),
+                cumulative_supply_rate_wads: Decimal::from(16u64),
+                borrow_rate: Rate::from_percent(27),
+                protocol_owned_amount: 30,
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_pubkey: Pubkey::new_unique(),
+                mint_total_supply: 17,
+                supply_pubkey: Pubkey::new_unique(),
+            },
+            config: ReserveConfig {
+                optimal_utilization_rate: 80,
+                loan_to_value_ratio: 50,
+                liquidation_bonus: 5,
+                liquidation_threshold: 55,
+                min_borrow_rate: 1,
+                optimal_borrow_rate: 10,
+                max_borrow_rate: 100,
+                fees: ReserveFees {
+                    borrow_fee_wad: 18,
+                    flash_loan_fee_wad: 19,
+                    host_fee_percentage: 20,
+                },
+                deposit_staking_pool: COption::Some(Pubkey::new_unique()),
+                use_twap: true,
+                twap_window_slots: 21,
+                borrow_fee_discount_staking_pool: COption::Some(Pubkey::new_unique()),
+                borrow_fee_discount_percentage: 22,
+                max_accrual_slots: 23,
+                borrowing_disabled: true,
+                liquidation_bonus_ramp_slots: 24,
+                min_initial_deposit: 25,
+                max_price_staleness_slots: 26,
+                max_slot_liquidity_change_percent: 27,
+                max_oracle_deviation_bps: 29,
+                isolated_debt_ceiling: 0x30_0031,
+                collateral_disabled: true,
+                max_liquidation_bonus: 31,
+                borrow_fee_discount_min_stake: 32,
+                min_liquidation_bonus: 33,
+            },
+            is_paused: true,
+            slot_liquidity_change_bps: 28,
+            is_wind_down: true,
+            previous_deposit_staking_pool: COption::Some(Pubkey::new_unique()),
+        };
+        let mut data = [0u8; reserve::LEN];
+        Reserve::pack(reserve.clone(), &mut data).unwrap();
+
+        assert_eq!(data[reserve::VERSION], PROGRAM_VERSION);
+        assert_eq!(read_u64(&data, reserve::LAST_UPDATE_SLOT), 11);
+        assert_eq!(data[reserve::LAST_UPDATE_STALE], 1);
+        assert_eq!(
+            read_pubkey(&data, reserve::LENDING_MARKET),
+            reserve.lending_market
+        );
+
+        let liquidity = &reserve.liquidity;
+        assert_eq!(
+            read_pubkey(&data, reserve::LIQUIDITY_MINT_PUBKEY),
+            liquidity.mint_pubkey
+        );
+        assert_eq!(data[reserve::LIQUIDITY_MINT_DECIMALS], 9);
+        assert_eq!(
+            read_pubkey(&data, reserve::LIQUIDITY_SUPPLY_PUBKEY),
+            liquidity.supply_pubkey
+        );
+        assert_eq!(
+            read_pubkey(&data, reserve::LIQUIDITY_FEE_RECEIVER),
+            liquidity.fee_receiver
+        );
+        assert_eq!(
+            COption::Some(read_pubkey(&data, reserve::LIQUIDITY_ORACLE_PUBKEY + 4)),
+            liquidity.oracle_pubkey
+        );
+        assert_eq!(read_u64(&data, reserve::LIQUIDITY_AVAILABLE_AMOUNT), 12);
+        assert_eq!(
+            read_decimal(&data, reserve::LIQUIDITY_BORROWED_AMOUNT_WADS),
+            liquidity.borrowed_amount_wads
+        );
+        assert_eq!(
+            read_decimal(&data, reserve::LIQUIDITY_CUMULATIVE_BORROW_RATE_WADS),
+            liquidity.cumulative_borrow_rate_wads
+        );
+        assert_eq!(
+            read_decimal(&data, reserve::LIQUIDITY_MARKET_PRICE),
+            liquidity.market_price
+        );
+        assert_eq!(
+            read_decimal(&data, reserve::LIQUIDITY_CUMULATIVE_SUPPLY_RATE_WADS),
+            liquidity.cumulative_supply_rate_wads
+        );
+
+        assert_eq!(
+            read_pubkey(&data, reserve::COLLATERAL_MINT_PUBKEY),
+            reserve.collateral.mint_pubkey
+        );
+        assert_eq!(read_u64(&data, reserve::COLLATERAL_MINT_TOTAL_SUPPLY), 17);
+        assert_eq!(
+            read_pubkey(&data, reserve::COLLATERAL_SUPPLY_PUBKEY),
+            reserve.collateral.supply_pubkey
+        );
+
+        let config = &reserve.config;
+        assert_eq!(data[reserve::CONFIG_OPTIMAL_UTILIZATION_RATE], 80);
+        assert_eq!(data[reserve::CONFIG_LOAN_TO_VALUE_RATIO], 50);
+        assert_eq!(data[reserve::CONFIG_LIQUIDATION_BONUS], 5);
+        assert_eq!(data[reserve::CONFIG_LIQUIDATION_THRESHOLD], 55);
+        assert_eq!(data[reserve::CONFIG_MIN_BORROW_RATE], 1);
+        assert_eq!(data[reserve::CONFIG_OPTIMAL_BORROW_RATE], 10);
+        assert_eq!(data[reserve::CONFIG_MAX_BORROW_RATE], 100);
+        assert_eq!(read_u64(&data, reserve::CONFIG_FEES_BORROW_FEE_WAD), 18);
+        assert_eq!(read_u64(&data, reserve::CONFIG_FEES_FLASH_LOAN_FEE_WAD), 19);
+        assert_eq!(data[reserve::CONFIG_FEES_HOST_FEE_PERCENTAGE], 20);
+        assert_eq!(
+            COption::Some(read_pubkey(&data, reserve::CONFIG_DEPOSIT_STAKING_POOL + 1)),
+            config.deposit_staking_pool
+        );
+        assert_eq!(data[reserve::CONFIG_USE_TWAP], 1);
+        assert_eq!(read_u64(&data, reserve::CONFIG_TWAP_WINDOW_SLOTS), 21);
+        assert_eq!(
+            COption::Some(read_pubkey(
+                &data,
+                reserve::CONFIG_BORROW_FEE_DISCOUNT_STAKING_POOL + 1
+            )),
+            config.borrow_fee_discount_staking_pool
+        );
+        assert_eq!(data[reserve::CONFIG_BORROW_FEE_DISCOUNT_PERCENTAGE], 22);
+        assert_eq!(read_u64(&data, reserve::CONFIG_MAX_ACCRUAL_SLOTS), 23);
+        assert_eq!(data[reserve::CONFIG_BORROWING_DISABLED], 1);
+        assert_eq!(
+            read_u64(&data, reserve::CONFIG_LIQUIDATION_BONUS_RAMP_SLOTS),
+            24
+        );
+        assert_eq!(read_u64(&data, reserve::CONFIG_MIN_INITIAL_DEPOSIT), 25);
+        assert_eq!(
+            read_u64(&data, reserve::CONFIG_MAX_PRICE_STALENESS_SLOTS),
+            26
+        );
+        assert_eq!(data[reserve::IS_PAUSED], 1);
+        assert_eq!(
+            read_decimal(&data, reserve::LIQUIDITY_BORROW_RATE),
+            Decimal::from(liquidity.borrow_rate)
+        );
+        assert_eq!(data[reserve::CONFIG_MAX_SLOT_LIQUIDITY_CHANGE_PERCENT], 27);
+        assert_eq!(
+            u16::from_le_bytes(
+                data[reserve::SLOT_LIQUIDITY_CHANGE_BPS..reserve::SLOT_LIQUIDITY_CHANGE_BPS + 2]
+                    .try_into()
+                    .unwrap()
+            ),
+            28
+        );
+        assert_eq!(
+            u16::from_le_bytes(
+                data[reserve::CONFIG_MAX_ORACLE_DEVIATION_BPS
+                    ..reserve::CONFIG_MAX_ORACLE_DEVIATION_BPS + 2]
+                    .try_into()
+                    .unwrap()
+            ),
+            29
+        );
+        assert_eq!(
+            read_u64(&data, reserve::CONFIG_ISOLATED_DEBT_CEILING),
+            0x30_0031
+        );
+        assert_eq!(
+            read_u64(&data, reserve::LIQUIDITY_PROTOCOL_OWNED_AMOUNT),
+            30
+        );
+        assert_eq!(data[reserve::CONFIG_COLLATERAL_DISABLED], 1);
+        assert_eq!(data[reserve::CONFIG_MAX_LIQUIDATION_BONUS], 31);
+        assert_eq!(data[reserve::IS_WIND_DOWN], 1);
+        assert_eq!(
+            read_u64(&data, reserve::CONFIG_BORROW_FEE_DISCOUNT_MIN_STAKE),
+            32
+        );
+        assert_eq!(data[reserve::PREVIOUS_DEPOSIT_STAKING_POOL], 1);
+        assert_eq!(
+            COption::Some(read_pubkey(
+                &data,
+                reserve::PREVIOUS_DEPOSIT_STAKING_POOL + 1
+            )),
+            reserve.previous_deposit_staking_pool
+        );
+        assert_eq!(data[reserve::CONFIG_MIN_LIQUIDATION_BONUS], 33);
+        // followed by 82 bytes of padding
+        assert_eq!(
+            reserve::PREVIOUS_DEPOSIT_STAKING_POOL + (1 + PUBKEY_BYTES) + 82,
+            reserve::LEN
+        );
+    }
+
+    #[test]
+    fn reserve_price_history_offsets() {
+        let mut reserve = Reserve::default();
+        reserve
+            .liquidity
+            .price_history
+            .record(31, Decimal::from(32u64), 0);
+        reserve
+            .liquidity
+            .price_history
+            .record(33, Decimal::from(34u64), 0);
+        let mut data = [0u8; reserve::LEN];
+        Reserve::pack(reserve, &mut data).unwrap();
+
+        assert_eq!(data[reserve::LIQUIDITY_PRICE_HISTORY_NEXT_INDEX], 2);
+        assert_eq!(data[reserve::LIQUIDITY_PRICE_HISTORY_LEN], 2);
+        let second_sample = reserve::LIQUIDITY_PRICE_HISTORY_SAMPLES + 24;
+        assert_eq!(read_u64(&data, second_sample), 33);
+        assert_eq!(read_decimal(&data, second_sample + 8), Decimal::from(34u64));
+    }
+
+    #[test]
+    fn obligation_offsets() {
+        let deposit_reserves = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let obligation = Obligation {
+            version: PROGRAM_VERSION,
+            last_update: LastUpdate {
+                slot: 41,
+                stale: true,
+            },
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![
+                ObligationCollateral {
+                    deposit_reserve: deposit_reserves[0],
+                    deposited_amount: 42,
+                    market_value: Decimal::from(43u64),
+                },
+                ObligationCollateral {
+                    deposit_reserve: deposit_reserves[1],
+                    deposited_amount: 44,
+                    market_value: Decimal::from(45u64),
+                },
+            ],
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve: Pubkey::new_unique(),
+                cumulative_borrow_rate_wads: Decimal::from(46u64),
+                borrowed_amount_wads: Decimal::from(47u64),
+                market_value: Decimal::from(48u64),
+                collateral_reserve: COption::Some(deposit_reserves[1]),
+            }],
+            deposited_value: Decimal::from(49u64),
+            borrowed_value: Decimal::from(50u64),
+            allowed_borrow_value: Decimal::from(51u64),
+            unhealthy_borrow_value: Decimal::from(52u64),
+            became_unhealthy_slot: 53,
+        };
+        let mut data = [0u8; obligation::LEN];
+        Obligation::pack(obligation.clone(), &mut data).unwrap();
+
+        assert_eq!(data[obligation::VERSION], PROGRAM_VERSION);
+        assert_eq!(read_u64(&data, obligation::LAST_UPDATE_SLOT), 41);
+        assert_eq!(data[obligation::LAST_UPDATE_STALE], 1);
+        assert_eq!(
+            read_pubkey(&data, obligation::LENDING_MARKET),
+            obligation.lending_market
+        );
+        assert_eq!(read_pubkey(&data, obligation::OWNER), obligation.owner);
+        assert_eq!(
+            read_decimal(&data, obligation::DEPOSITED_VALUE),
+            obligation.deposited_value
+        );
+        assert_eq!(
+            read_decimal(&data, obligation::BORROWED_VALUE),
+            obligation.borrowed_value
+        );
+        assert_eq!(
+            read_decimal(&data, obligation::ALLOWED_BORROW_VALUE),
+            obligation.allowed_borrow_value
+        );
+        assert_eq!(
+            read_decimal(&data, obligation::UNHEALTHY_BORROW_VALUE),
+            obligation.unhealthy_borrow_value
+        );
+        assert_eq!(data[obligation::DEPOSITS_LEN], 2);
+        assert_eq!(data[obligation::BORROWS_LEN], 1);
+        assert_eq!(read_u64(&data, obligation::BECAME_UNHEALTHY_SLOT), 53);
+        assert_eq!(
+            obligation::BECAME_UNHEALTHY_SLOT + 8,
+            obligation::LEN,
+            "obligation layout has no padding"
+        );
+
+        let deposit = obligation::DATA_FLAT + obligation_collateral::LEN;
+        assert_eq!(
+            read_pubkey(&data, deposit + obligation_collateral::DEPOSIT_RESERVE),
+            deposit_reserves[1]
+        );
+        assert_eq!(
+            read_u64(&data, deposit + obligation_collateral::DEPOSITED_AMOUNT),
+            44
+        );
+        assert_eq!(
+            read_decimal(&data, deposit + obligation_collateral::MARKET_VALUE),
+            Decimal::from(45u64)
+        );
+
+        let borrow = obligation::DATA_FLAT + 2 * obligation_collateral::LEN;
+        let liquidity = &obligation.borrows[0];
+        assert_eq!(
+            read_pubkey(&data, borrow + obligation_liquidity::BORROW_RESERVE),
+            liquidity.borrow_reserve
+        );
+        assert_eq!(
+            read_decimal(
+                &data,
+                borrow + obligation_liquidity::CUMULATIVE_BORROW_RATE_WADS
+            ),
+            liquidity.cumulative_borrow_rate_wads
+        );
+        assert_eq!(
+            read_decimal(&data, borrow + obligation_liquidity::BORROWED_AMOUNT_WADS),
+            liquidity.borrowed_amount_wads
+        );
+        assert_eq!(
+            read_decimal(&data, borrow + obligation_liquidity::MARKET_VALUE),
+            liquidity.market_value
+        );
+        assert_eq!(
+            COption::Some(read_pubkey(
+                &data,
+                borrow + obligation_liquidity::COLLATERAL_RESERVE + 1
+            )),
+            liquidity.collateral_reserve
+        );
+        assert_eq!(
+            obligation_liquidity::COLLATERAL_RESERVE + 33,
+            obligation_liquidity::LEN
+        );
+    }
+}
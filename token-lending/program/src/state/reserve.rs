@@ -17,7 +17,7 @@ use spl_token::solana_program::program_option::COption;
 
 use crate::{
     error::LendingError,
-    math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub},
+    math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub, WAD},
 };
 
 use super::*;
@@ -28,6 +28,32 @@ pub const LIQUIDATION_CLOSE_FACTOR: u8 = 50;
 /// Obligation borrow amount that is small enough to close out
 pub const LIQUIDATION_CLOSE_AMOUNT: u64 = 2;
 
+/// Number of price samples kept on a reserve for the time-weighted average price
+pub const PRICE_HISTORY_LEN: usize = 4;
+
+/// Default maximum number of slots interest is accrued over in a single refresh
+pub const DEFAULT_MAX_ACCRUAL_SLOTS: u64 = SLOTS_PER_YEAR;
+
+/// Absolute ceiling on the annual borrow rate interest accrues at, whatever the reserve config
+/// allows, so a mis-set max borrow rate can't drain borrowers
+pub const MAX_BORROW_RATE_CEILING_PERCENT: u8 = 200;
+
+/// Basis points in one
+const BPS_SCALER: u64 = 10_000;
+
+/// Address of the reserve `InitReservePda` creates for a lending market and liquidity mint, with
+/// its bump seed
+pub fn find_reserve_address(
+    program_id: &Pubkey,
+    lending_market: &Pubkey,
+    liquidity_mint: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[lending_market.as_ref(), liquidity_mint.as_ref()],
+        program_id,
+    )
+}
+
 /// Lending market reserve state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Reserve {
@@ -43,6 +69,20 @@ pub struct Reserve {
     pub collateral: ReserveCollateral,
     /// Reserve configuration values
     pub config: ReserveConfig,
+    /// Set by `RefreshReserve` when the oracle price is older than the reserve's maximum price
+    /// staleness or moved further than its maximum oracle deviation, blocking borrows and
+    /// liquidations until the lending market owner unpauses it.
+    /// Obligations borrowing from a paused reserve have no borrowing power when refreshed.
+    pub is_paused: bool,
+    /// Liquidity deposited and redeemed since the reserve was first refreshed in the current
+    /// slot, in basis points of the total liquidity supply at each deposit or redemption
+    pub slot_liquidity_change_bps: u16,
+    /// Set by the lending market owner to retire the reserve, blocking deposits and borrows
+    /// while repayments, redemptions and collateral withdrawals drain it
+    pub is_wind_down: bool,
+    /// Deposit staking pool the reserve used before `UpdateReserve` last changed it, the only
+    /// pool `MigrateStakeAccount` moves stake out of
+    pub previous_deposit_staking_pool: COption<Pubkey>,
 }
 
 impl Reserve {
@@ -65,6 +105,16 @@ impl Reserve {
 
     /// Record deposited liquidity and return amount of collateral tokens to mint
     pub fn deposit_liquidity(&mut self, liquidity_amount: u64) -> Result<u64, ProgramError> {
+        if self.collateral.mint_total_supply == 0
+            && liquidity_amount < self.config.min_initial_deposit
+        {
+            msg!(
+                "Deposit into an empty reserve must be at least {} liquidity",
+                self.config.min_initial_deposit
+            );
+            return Err(LendingError::InitialDepositTooSmall.into());
+        }
+        self.record_liquidity_change(liquidity_amount)?;
         let collateral_amount = self
             .collateral_exchange_rate()?
             .liquidity_to_collateral(liquidity_amount)?;
@@ -80,6 +130,7 @@ impl Reserve {
         let collateral_exchange_rate = self.collateral_exchange_rate()?;
         let liquidity_amount =
             collateral_exchange_rate.collateral_to_liquidity(collateral_amount)?;
+        self.record_liquidity_change(liquidity_amount)?;
 
         self.collateral.burn(collateral_amount)?;
         self.liquidity.withdraw(liquidity_amount)?;
@@ -87,6 +138,100 @@ impl Reserve {
         Ok(liquidity_amount)
     }
 
+    /// Most of `user_collateral` that can be redeemed, capped at the collateral worth the
+    /// reserve's available liquidity since borrowed liquidity can't be withdrawn
+    pub fn max_redeemable_collateral(&self, user_collateral: u64) -> Result<u64, ProgramError> {
+        let available_collateral = self
+            .collateral_exchange_rate()?
+            .liquidity_to_collateral(self.liquidity.available_amount)?;
+        Ok(user_collateral.min(available_collateral))
+    }
+
+    /// Utilization the reserve would have after borrowing `amount_to_borrow` plus the borrow
+    /// fee, before any borrow fee discount, without changing the reserve
+    pub fn utilization_after_borrow(&self, amount_to_borrow: u64) -> Result<Rate, ProgramError> {
+        let borrow_amount = Decimal::from(amount_to_borrow);
+        let (borrow_fee, _) = self
+            .config
+            .fees
+            .calculate_borrow_fees(borrow_amount, FeeCalculation::Exclusive)?;
+        let mut liquidity = self.liquidity.clone();
+        liquidity.borrow(borrow_amount.try_add(borrow_fee.into())?)?;
+        liquidity.utilization_rate()
+    }
+
+    /// Utilization the reserve would have after redeeming `collateral_amount`, without changing
+    /// the reserve
+    pub fn utilization_after_redeem(&self, collateral_amount: u64) -> Result<Rate, ProgramError> {
+        let liquidity_amount = self
+            .collateral_exchange_rate()?
+            .collateral_to_liquidity(collateral_amount)?;
+        let mut liquidity = self.liquidity.clone();
+        liquidity.withdraw(liquidity_amount)?;
+        liquidity.utilization_rate()
+    }
+
+    /// Liquidity donated to the reserve so far, a reported figure only. No collateral was minted
+    /// for it, so the donations raised the value of the collateral already outstanding.
+    pub fn protocol_owned_amount(&self) -> u64 {
+        self.liquidity.protocol_owned_amount
+    }
+
+    /// Count liquidity deposited or redeemed against the reserve's limit on liquidity moved per
+    /// slot, so the collateral exchange rate can't be swung by a large deposit and redemption
+    /// within one slot. Nothing is counted against a reserve without liquidity.
+    fn record_liquidity_change(&mut self, liquidity_amount: u64) -> ProgramResult {
+        if self.config.max_slot_liquidity_change_percent == 0 {
+            return Ok(());
+        }
+        let total_liquidity = self.liquidity.total_supply()?;
+        if total_liquidity == Decimal::zero() {
+            return Ok(());
+        }
+        let change_bps = Decimal::from(liquidity_amount)
+            .try_mul(BPS_SCALER)?
+            .try_div(total_liquidity)?
+            .try_ceil_u64()?;
+        let slot_liquidity_change_bps = u64::from(self.slot_liquidity_change_bps)
+            .saturating_add(change_bps)
+            .min(u16::MAX.into());
+        let max_change_bps = u64::from(self.config.max_slot_liquidity_change_percent) * 100;
+        if slot_liquidity_change_bps > max_change_bps {
+            msg!(
+                "Liquidity moved this slot would be {} bps of the reserve, above the limit of {} bps",
+                slot_liquidity_change_bps,
+                max_change_bps
+            );
+            return Err(LendingError::RateLimited.into());
+        }
+        self.slot_liquidity_change_bps = slot_liquidity_change_bps as u16;
+        Ok(())
+    }
+
+    /// Check the collateral supply recorded by the reserve matches the supply of its collateral
+    /// mint, which only the reserve mints and burns. A mismatch means accounting drifted.
+    pub fn check_collateral_invariant(&self, collateral_mint_supply: u64) -> ProgramResult {
+        if self.collateral.mint_total_supply != collateral_mint_supply {
+            msg!(
+                "Reserve collateral supply {} does not match the collateral mint supply {}",
+                self.collateral.mint_total_supply,
+                collateral_mint_supply
+            );
+            return Err(LendingError::CollateralSupplyMismatch.into());
+        }
+        Ok(())
+    }
+
+    /// Share of the value of deposited collateral that can be borrowed against, zero when the
+    /// reserve has collateral disabled or is paused, as its price can no longer be trusted
+    pub fn loan_to_value_rate(&self) -> Rate {
+        if self.config.collateral_disabled || self.is_paused {
+            Rate::zero()
+        } else {
+            Rate::from_percent(self.config.loan_to_value_ratio)
+        }
+    }
+
     /// Calculate the current borrow rate
     pub fn current_borrow_rate(&self) -> Result<Rate, ProgramError> {
         let utilization_rate = self.liquidity.utilization_rate()?;
@@ -123,29 +268,198 @@ impl Reserve {
         }
     }
 
+    /// Borrow rate interest accrues at, the current borrow rate capped at
+    /// `MAX_BORROW_RATE_CEILING_PERCENT`
+    pub fn effective_borrow_rate(&self) -> Result<Rate, ProgramError> {
+        Ok(self
+            .current_borrow_rate()?
+            .min(Rate::from_percent(MAX_BORROW_RATE_CEILING_PERCENT)))
+    }
+
+    /// Borrow rate interest was last accrued at, without recomputing it from the utilization
+    pub fn last_borrow_rate(&self) -> Rate {
+        self.liquidity.borrow_rate
+    }
+
+    /// Annual percentage yield paid by borrowers at the effective borrow rate, compounding the
+    /// per-slot rate interest accrues at over `slots_per_year` slots
+    pub fn borrow_apy(&self, slots_per_year: u64) -> Result<Rate, ProgramError> {
+        let slot_rate = self
+            .effective_borrow_rate()?
+            .try_annual_to_slot_rate(SLOTS_PER_YEAR)?;
+        compound_slot_rate(slot_rate, slots_per_year)
+    }
+
+    /// Annual percentage yield earned by suppliers, the borrow APY over `slots_per_year` slots
+    /// scaled by the current utilization. All interest accrues to suppliers, the reserve keeps
+    /// no share of it.
+    pub fn supply_apy(&self, slots_per_year: u64) -> Result<Rate, ProgramError> {
+        supplier_apy(
+            self.borrow_apy(slots_per_year)?,
+            self.liquidity.utilization_rate()?,
+            Rate::zero(),
+        )
+    }
+
     /// Collateral exchange rate
     pub fn collateral_exchange_rate(&self) -> Result<CollateralExchangeRate, ProgramError> {
         let total_liquidity = self.liquidity.total_supply()?;
         self.collateral.exchange_rate(total_liquidity)
     }
 
-    /// Update borrow rate and accrue interest
+    /// Update borrow rate and accrue interest, over at most the configured maximum number of
+    /// slots so that a reserve left unrefreshed for a long time doesn't compound without bound,
+    /// and at most at `MAX_BORROW_RATE_CEILING_PERCENT`
+    ///
+    /// Interest is rounded in favor of suppliers: accrued borrows and the cumulative borrow rate
+    /// are rounded up, obligations accrue at a rate rounded up from it, repayments are rounded
+    /// up to whole tokens and redeemed collateral is rounded down, so the liquidity suppliers can
+    /// redeem never exceeds what borrowers actually repay.
     pub fn accrue_interest(&mut self, current_slot: Slot) -> ProgramResult {
-        let slots_elapsed = self.last_update.slots_elapsed(current_slot)?;
+        let mut slots_elapsed = self.last_update.slots_elapsed(current_slot)?;
+        let max_accrual_slots = match self.config.max_accrual_slots {
+            0 => DEFAULT_MAX_ACCRUAL_SLOTS,
+            max_accrual_slots => max_accrual_slots,
+        };
+        if slots_elapsed > max_accrual_slots {
+            msg!(
+                "Reserve was last refreshed {} slots ago, accruing interest over {} slots",
+                slots_elapsed,
+                max_accrual_slots
+            );
+            slots_elapsed = max_accrual_slots;
+        }
         if slots_elapsed > 0 {
-            let current_borrow_rate = self.current_borrow_rate()?;
+            let effective_borrow_rate = self.effective_borrow_rate()?;
+            if self.current_borrow_rate()? > effective_borrow_rate {
+                msg!(
+                    "Borrow rate exceeds the program ceiling, accruing interest at {}%",
+                    MAX_BORROW_RATE_CEILING_PERCENT
+                );
+            }
+            self.liquidity
+                .compound_interest(effective_borrow_rate, slots_elapsed)?;
+        }
+        Ok(())
+    }
+
+    /// Record a spot price and update the market price, using the time-weighted average price
+    /// over the configured window if enabled
+    pub fn update_market_price(
+        &mut self,
+        spot_price: Decimal,
+        current_slot: Slot,
+    ) -> ProgramResult {
+        let deviates = self.exceeds_oracle_deviation(spot_price)?;
+        self.liquidity.price_history.record(
+            current_slot,
+            spot_price,
+            self.config.twap_window_slots / PRICE_HISTORY_LEN as u64,
+        );
+        if deviates {
+            // the sample is still recorded, so once unpaused the next price is compared to it
+            msg!("Pausing borrows and liquidations, keeping the previous market price");
+            self.is_paused = true;
+            return Ok(());
+        }
+        self.liquidity.market_price = if self.config.use_twap {
             self.liquidity
-                .compound_interest(current_borrow_rate, slots_elapsed)?;
+                .price_history
+                .time_weighted_average(current_slot, self.config.twap_window_slots)?
+                .unwrap_or(spot_price)
+        } else {
+            spot_price
+        };
+        Ok(())
+    }
+
+    /// Whether a spot price moved further from the reserve's previous price, the latest recorded
+    /// sample or the market price before any sample, than the reserve config allows
+    fn exceeds_oracle_deviation(&self, spot_price: Decimal) -> Result<bool, ProgramError> {
+        if self.config.max_oracle_deviation_bps == 0 {
+            return Ok(false);
+        }
+        let previous_price = self
+            .liquidity
+            .price_history
+            .latest()
+            .map_or(self.liquidity.market_price, |sample| sample.price);
+        if previous_price == Decimal::zero() {
+            return Ok(false);
+        }
+        let price_change = if spot_price > previous_price {
+            spot_price.try_sub(previous_price)?
+        } else {
+            previous_price.try_sub(spot_price)?
+        };
+        let deviation_bps = price_change.try_mul(BPS_SCALER)?.try_div(previous_price)?;
+        if deviation_bps > Decimal::from(u64::from(self.config.max_oracle_deviation_bps)) {
+            msg!(
+                "Oracle price {} moved {} bps from the previous price {}, above the limit of {} bps",
+                spot_price,
+                deviation_bps,
+                previous_price,
+                self.config.max_oracle_deviation_bps
+            );
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Reject a borrow that would take the market value of all of the reserve's borrows, across
+    /// every obligation, above its isolated debt ceiling
+    pub fn check_isolated_debt_ceiling(&self, borrow_amount: Decimal) -> ProgramResult {
+        if self.config.isolated_debt_ceiling == 0 {
+            return Ok(());
+        }
+        let debt_ceiling = Decimal::from(self.config.isolated_debt_ceiling);
+        let borrowed_value = self
+            .liquidity_market_value(self.liquidity.borrowed_amount_wads.try_add(borrow_amount)?)?;
+        if borrowed_value > debt_ceiling {
+            msg!(
+                "Reserve borrows would be worth {}, above the isolated debt ceiling of {}",
+                borrowed_value,
+                debt_ceiling
+            );
+            return Err(LendingError::DebtCeilingExceeded.into());
         }
         Ok(())
     }
 
-    /// Borrow liquidity up to a maximum market value
+    /// Market value of an amount of the reserve's collateral, at the refreshed market price
+    pub fn collateral_market_value(&self, collateral_amount: u64) -> Result<Decimal, ProgramError> {
+        let liquidity_amount = self
+            .collateral_exchange_rate()?
+            .decimal_collateral_to_liquidity(collateral_amount.into())?;
+        self.liquidity_market_value(liquidity_amount)
+    }
+
+    /// Market value of an amount of the reserve's liquidity, at the refreshed market price
+    pub fn liquidity_market_value(
+        &self,
+        liquidity_amount: Decimal,
+    ) -> Result<Decimal, ProgramError> {
+        // @TODO: add lookup table https://git.io/JOCYq
+        let decimals = 10u64
+            .checked_pow(self.liquidity.mint_decimals as u32)
+            .ok_or(LendingError::MathOverflow)?;
+        liquidity_amount
+            .try_mul(self.liquidity.market_price)?
+            .try_div(decimals)
+    }
+
+    /// Borrow liquidity up to a maximum market value, discounting the borrow fee by
+    /// `borrow_fee_discount_percentage`
     pub fn calculate_borrow(
         &self,
         amount_to_borrow: u64,
         max_borrow_value: Decimal,
+        borrow_fee_discount_percentage: u8,
     ) -> Result<CalculateBorrowResult, ProgramError> {
+        let fees = self
+            .config
+            .fees
+            .discount_borrow_fee(borrow_fee_discount_percentage)?;
         // @TODO: add lookup table https://git.io/JOCYq
         let decimals = 10u64
             .checked_pow(self.liquidity.mint_decimals as u32)
@@ -155,10 +469,8 @@ impl Reserve {
                 .try_mul(decimals)?
                 .try_div(self.liquidity.market_price)?
                 .min(self.liquidity.available_amount.into());
-            let (borrow_fee, host_fee) = self
-                .config
-                .fees
-                .calculate_borrow_fees(borrow_amount, FeeCalculation::Inclusive)?;
+            let (borrow_fee, host_fee) =
+                fees.calculate_borrow_fees(borrow_amount, FeeCalculation::Inclusive)?;
             let receive_amount = borrow_amount
                 .try_floor_u64()?
                 .checked_sub(borrow_fee)
@@ -173,10 +485,8 @@ impl Reserve {
         } else {
             let receive_amount = amount_to_borrow;
             let borrow_amount = Decimal::from(receive_amount);
-            let (borrow_fee, host_fee) = self
-                .config
-                .fees
-                .calculate_borrow_fees(borrow_amount, FeeCalculation::Exclusive)?;
+            let (borrow_fee, host_fee) =
+                fees.calculate_borrow_fees(borrow_amount, FeeCalculation::Exclusive)?;
 
             let borrow_amount = borrow_amount.try_add(borrow_fee.into())?;
             let borrow_value = borrow_amount
@@ -215,6 +525,65 @@ impl Reserve {
         })
     }
 
+    /// Liquidation bonus for an unhealthy obligation before the ramp, for this reserve as the
+    /// withdrawn collateral. With `min_liquidation_bonus` and `max_liquidation_bonus` both 0 it is
+    /// the flat `liquidation_bonus`. Otherwise it scales linearly with the health ratio
+    /// `borrowed_value / unhealthy_borrow_value`, from `min_liquidation_bonus` at 1 to
+    /// `max_liquidation_bonus` at `100 / liquidation_threshold`, where the borrows are worth as
+    /// much as collateral of this reserve alone.
+    pub fn health_liquidation_bonus(&self, obligation: &Obligation) -> Result<Rate, ProgramError> {
+        if self.config.min_liquidation_bonus == 0 && self.config.max_liquidation_bonus == 0 {
+            return Ok(Rate::from_percent(self.config.liquidation_bonus));
+        }
+        let min_bonus = Rate::from_percent(self.config.min_liquidation_bonus);
+        if self.config.max_liquidation_bonus <= self.config.min_liquidation_bonus
+            || obligation.borrowed_value <= obligation.unhealthy_borrow_value
+        {
+            return Ok(min_bonus);
+        }
+        let max_bonus = Rate::from_percent(self.config.max_liquidation_bonus);
+        let max_health_ratio =
+            Decimal::from(100u64).try_div(self.config.liquidation_threshold as u64)?;
+        if obligation.unhealthy_borrow_value == Decimal::zero()
+            || max_health_ratio <= Decimal::one()
+        {
+            return Ok(max_bonus);
+        }
+
+        let health_ratio = obligation
+            .borrowed_value
+            .try_div(obligation.unhealthy_borrow_value)?;
+        if health_ratio >= max_health_ratio {
+            return Ok(max_bonus);
+        }
+        let unhealthy_depth = health_ratio
+            .try_sub(Decimal::one())?
+            .try_div(max_health_ratio.try_sub(Decimal::one())?)?;
+        min_bonus.try_add(
+            max_bonus
+                .try_sub(min_bonus)?
+                .try_mul(Rate::try_from(unhealthy_depth)?)?,
+        )
+    }
+
+    /// Liquidation bonus for an unhealthy obligation, ramping linearly from zero to its
+    /// `health_liquidation_bonus` over the configured number of slots after it became unhealthy
+    pub fn liquidation_bonus(
+        &self,
+        obligation: &Obligation,
+        current_slot: Slot,
+    ) -> Result<Rate, ProgramError> {
+        let liquidation_bonus = self.health_liquidation_bonus(obligation)?;
+        let ramp_slots = self.config.liquidation_bonus_ramp_slots;
+        let unhealthy_slots = current_slot.saturating_sub(obligation.became_unhealthy_slot);
+        if unhealthy_slots >= ramp_slots {
+            return Ok(liquidation_bonus);
+        }
+        liquidation_bonus
+            .try_mul(unhealthy_slots)?
+            .try_div(ramp_slots)
+    }
+
     /// Liquidate some or all of an unhealthy obligation
     pub fn calculate_liquidation(
         &self,
@@ -222,8 +591,11 @@ impl Reserve {
         obligation: &Obligation,
         liquidity: &ObligationLiquidity,
         collateral: &ObligationCollateral,
+        current_slot: Slot,
     ) -> Result<CalculateLiquidationResult, ProgramError> {
-        let bonus_rate = Rate::from_percent(self.config.liquidation_bonus).try_add(Rate::one())?;
+        let bonus_rate = self
+            .liquidation_bonus(obligation, current_slot)?
+            .try_add(Rate::one())?;
 
         let max_amount = if amount_to_liquidate == u64::MAX {
             liquidity.borrowed_amount_wads
@@ -370,6 +742,17 @@ pub struct ReserveLiquidity {
     pub cumulative_borrow_rate_wads: Decimal,
     /// Reserve liquidity market price in quote currency
     pub market_price: Decimal,
+    /// Recent spot prices used for the time-weighted average price
+    pub price_history: PriceHistory,
+    /// Reserve liquidity cumulative supply rate, growth of the total supply from accrued
+    /// interest. Zero for reserves created before it was tracked, read as one.
+    pub cumulative_supply_rate_wads: Decimal,
+    /// Borrow rate interest was last accrued at. Zero until the first accrual.
+    pub borrow_rate: Rate,
+    /// Liquidity donated to the reserve so far, reported but not set aside. The donations are
+    /// part of the available amount and, with no collateral minted for them, are redeemable by
+    /// the collateral outstanding like any other liquidity.
+    pub protocol_owned_amount: u64,
 }
 
 impl ReserveLiquidity {
@@ -385,6 +768,10 @@ impl ReserveLiquidity {
             borrowed_amount_wads: Decimal::zero(),
             cumulative_borrow_rate_wads: Decimal::one(),
             market_price: params.market_price,
+            price_history: PriceHistory::default(),
+            cumulative_supply_rate_wads: Decimal::one(),
+            borrow_rate: Rate::zero(),
+            protocol_owned_amount: 0,
         }
     }
 
@@ -402,6 +789,17 @@ impl ReserveLiquidity {
         Ok(())
     }
 
+    /// Add donated liquidity to the available amount, raising the value of the existing
+    /// collateral, and report it in the protocol owned amount
+    pub fn donate(&mut self, liquidity_amount: u64) -> ProgramResult {
+        self.deposit(liquidity_amount)?;
+        self.protocol_owned_amount = self
+            .protocol_owned_amount
+            .checked_add(liquidity_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        Ok(())
+    }
+
     /// Remove liquidity from available amount
     pub fn withdraw(&mut self, liquidity_amount: u64) -> ProgramResult {
         if liquidity_amount > self.available_amount {
@@ -465,18 +863,56 @@ impl ReserveLiquidity {
         current_borrow_rate: Rate,
         slots_elapsed: u64,
     ) -> ProgramResult {
-        let slot_interest_rate = current_borrow_rate.try_div(SLOTS_PER_YEAR)?;
+        let slot_interest_rate = current_borrow_rate.try_annual_to_slot_rate(SLOTS_PER_YEAR)?;
         let compounded_interest_rate = Rate::one()
             .try_add(slot_interest_rate)?
             .try_pow(slots_elapsed)?;
+        // Round accrued interest up so that borrows never grow slower than the rate implies,
+        // any rounding is paid by borrowers rather than lost by suppliers
         self.cumulative_borrow_rate_wads = self
             .cumulative_borrow_rate_wads
-            .try_mul(compounded_interest_rate)?;
+            .try_mul_ceil(compounded_interest_rate)?;
+        let previous_total_supply = self.total_supply()?;
         self.borrowed_amount_wads = self
             .borrowed_amount_wads
-            .try_mul(compounded_interest_rate)?;
+            .try_mul_ceil(compounded_interest_rate)?;
+        if previous_total_supply != Decimal::zero() {
+            let supply_growth = self.total_supply()?.try_div(previous_total_supply)?;
+            self.cumulative_supply_rate_wads =
+                self.cumulative_supply_rate().try_mul(supply_growth)?;
+        }
+        self.borrow_rate = current_borrow_rate;
         Ok(())
     }
+
+    /// Cumulative supply rate, the growth of a unit of supplied liquidity from accrued interest
+    pub fn cumulative_supply_rate(&self) -> Decimal {
+        if self.cumulative_supply_rate_wads == Decimal::zero() {
+            Decimal::one()
+        } else {
+            self.cumulative_supply_rate_wads
+        }
+    }
+}
+
+/// Annual percentage yield earned by suppliers when borrowers pay `borrow_apy` on `utilization`
+/// of the supply, after the `reserve_factor` share of interest kept by the reserve
+pub fn supplier_apy(
+    borrow_apy: Rate,
+    utilization: Rate,
+    reserve_factor: Rate,
+) -> Result<Rate, ProgramError> {
+    borrow_apy
+        .try_mul(utilization)?
+        .try_mul(Rate::one().try_sub(reserve_factor)?)
+}
+
+/// Growth over `slots` slots of a rate compounded every slot
+fn compound_slot_rate(slot_rate: Rate, slots: u64) -> Result<Rate, ProgramError> {
+    Rate::one()
+        .try_add(slot_rate)?
+        .try_pow(slots)?
+        .try_sub(Rate::one())
 }
 
 /// Create a new reserve liquidity
@@ -495,6 +931,104 @@ pub struct NewReserveLiquidityParams {
     pub market_price: Decimal,
 }
 
+/// Spot price observed at a slot
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PriceSample {
+    /// Slot the price was observed at
+    pub slot: Slot,
+    /// Spot price in quote currency
+    pub price: Decimal,
+}
+
+/// Ring buffer of the most recent spot prices of a reserve
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PriceHistory {
+    /// Price samples, the oldest one is overwritten first
+    pub samples: [PriceSample; PRICE_HISTORY_LEN],
+    /// Index the next sample is written to
+    pub next_index: u8,
+    /// Number of samples recorded, at most `PRICE_HISTORY_LEN`
+    pub len: u8,
+}
+
+impl PriceHistory {
+    /// Record a spot price, replacing the latest sample if it was taken in the same slot. A price
+    /// observed less than `sample_interval` slots after the latest sample is not recorded, so
+    /// refreshing in consecutive slots can't flush the history faster than once per interval.
+    pub fn record(&mut self, slot: Slot, price: Decimal, sample_interval: Slot) {
+        let sample = PriceSample { slot, price };
+        if let Some(latest) = self.latest_index() {
+            let latest_slot = self.samples[latest].slot;
+            if latest_slot == slot {
+                self.samples[latest] = sample;
+                return;
+            }
+            if slot.saturating_sub(latest_slot) < sample_interval {
+                return;
+            }
+        }
+        self.samples[self.next_index as usize] = sample;
+        self.next_index = ((self.next_index as usize + 1) % PRICE_HISTORY_LEN) as u8;
+        self.len = (self.len as usize + 1).min(PRICE_HISTORY_LEN) as u8;
+    }
+
+    /// Samples in chronological order
+    pub fn samples(&self) -> impl Iterator<Item = &PriceSample> {
+        let len = self.len as usize;
+        let start = (self.next_index as usize + PRICE_HISTORY_LEN - len) % PRICE_HISTORY_LEN;
+        (0..len).map(move |i| &self.samples[(start + i) % PRICE_HISTORY_LEN])
+    }
+
+    /// Average of the recorded prices over the last `window` slots, each weighted by the number
+    /// of slots it was in effect. The sample taken at `current_slot` has no weight yet, so a
+    /// price spike only moves the average as slots pass. Returns `None` if no sample has weight.
+    pub fn time_weighted_average(
+        &self,
+        current_slot: Slot,
+        window: Slot,
+    ) -> Result<Option<Decimal>, ProgramError> {
+        let window_start = current_slot.saturating_sub(window);
+        let mut weighted_sum = Decimal::zero();
+        let mut total_slots = 0u64;
+
+        let mut samples = self.samples().peekable();
+        while let Some(sample) = samples.next() {
+            let end = samples
+                .peek()
+                .map_or(current_slot, |next| next.slot)
+                .min(current_slot);
+            let start = sample.slot.max(window_start);
+            if end <= start {
+                continue;
+            }
+            let slots = end - start;
+            weighted_sum = weighted_sum.try_add(sample.price.try_mul(slots)?)?;
+            total_slots = total_slots
+                .checked_add(slots)
+                .ok_or(LendingError::MathOverflow)?;
+        }
+
+        if total_slots == 0 {
+            Ok(None)
+        } else {
+            weighted_sum.try_div(total_slots).map(Some)
+        }
+    }
+
+    /// Most recently recorded sample
+    pub fn latest(&self) -> Option<&PriceSample> {
+        self.latest_index().map(|index| &self.samples[index])
+    }
+
+    fn latest_index(&self) -> Option<usize> {
+        if self.len == 0 {
+            None
+        } else {
+            Some((self.next_index as usize + PRICE_HISTORY_LEN - 1) % PRICE_HISTORY_LEN)
+        }
+    }
+}
+
 /// Reserve collateral
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct ReserveCollateral {
@@ -606,7 +1140,8 @@ pub struct ReserveConfig {
     /// Target ratio of the value of borrows to deposits, as a percentage
     /// 0 if use as collateral is disabled
     pub loan_to_value_ratio: u8,
-    /// Bonus a liquidator gets when repaying part of an unhealthy obligation, as a percentage
+    /// Bonus a liquidator gets when repaying part of an unhealthy obligation, as a percentage.
+    /// Used while `min_liquidation_bonus` and `max_liquidation_bonus` are both 0.
     pub liquidation_bonus: u8,
     /// Loan to value ratio at which an obligation can be liquidated, as a percentage
     pub liquidation_threshold: u8,
@@ -614,12 +1149,286 @@ pub struct ReserveConfig {
     pub min_borrow_rate: u8,
     /// Optimal (utilization) borrow APY
     pub optimal_borrow_rate: u8,
-    /// Max borrow APY
+    /// Max borrow APY, interest never accrues above `MAX_BORROW_RATE_CEILING_PERCENT`
     pub max_borrow_rate: u8,
     /// Program owner fees assessed, separate from gains due to interest accrual
     pub fees: ReserveFees,
     /// corresponded staking pool pubkey of deposit
     pub deposit_staking_pool: COption<Pubkey>,
+    /// Price collateral and borrows with the time-weighted average price instead of spot
+    pub use_twap: bool,
+    /// Number of slots the time-weighted average price is taken over, sampling the spot price at
+    /// most once every `PRICE_HISTORY_LEN`th of the window
+    pub twap_window_slots: u64,
+    /// Staking pool whose stakers get a discount on the borrow fee
+    pub borrow_fee_discount_staking_pool: COption<Pubkey>,
+    /// Discount on the borrow fee for stakers of the discount staking pool, as a percentage
+    pub borrow_fee_discount_percentage: u8,
+    /// Maximum number of slots interest is accrued over in a single refresh
+    /// 0 for the default of `DEFAULT_MAX_ACCRUAL_SLOTS`
+    pub max_accrual_slots: u64,
+    /// Liquidity cannot be borrowed from the reserve, it can only be deposited and used as
+    /// collateral
+    pub borrowing_disabled: bool,
+    /// Number of slots the liquidation bonus ramps up over from zero after an obligation
+    /// becomes unhealthy, 0 for the full bonus immediately
+    pub liquidation_bonus_ramp_slots: u64,
+    /// Minimum liquidity deposited while the reserve has no collateral minted, so a tiny first
+    /// deposit can't be used to skew the exchange rate for later depositors. 0 for no minimum.
+    pub min_initial_deposit: u64,
+    /// Number of slots after which the oracle price is stale and `RefreshReserve` pauses the
+    /// reserve instead of failing. 0 to fail refreshes on the oracle's default staleness instead.
+    pub max_price_staleness_slots: u64,
+    /// Maximum liquidity deposited and redeemed in a single slot, as a percentage of the total
+    /// liquidity supply. 0 for no limit.
+    pub max_slot_liquidity_change_percent: u8,
+    /// Maximum move of the oracle price from the reserve's previous price in a single refresh,
+    /// in basis points. A larger move pauses the reserve at its previous market price until the
+    /// lending market owner unpauses it. 0 for no limit.
+    pub max_oracle_deviation_bps: u16,
+    /// Maximum market value of all borrows of the reserve liquidity across every obligation, in
+    /// the lending market's quote currency. Borrows that would exceed it fail. 0 for no ceiling.
+    pub isolated_debt_ceiling: u64,
+    /// Deposits of the reserve give obligations no borrowing power, the liquidity can still be
+    /// supplied to earn interest and redeemed
+    pub collateral_disabled: bool,
+    /// Liquidation bonus, as a percentage, for an obligation whose borrows are worth as much as
+    /// its collateral. The bonus scales up to it from `min_liquidation_bonus` with how far the
+    /// obligation is past unhealthy, equal to it for a flat bonus.
+    pub max_liquidation_bonus: u8,
+    /// Least amount staked in the discount staking pool for a stake account to get the borrow
+    /// fee discount
+    pub borrow_fee_discount_min_stake: u64,
+    /// Liquidation bonus, as a percentage, for an obligation that just became unhealthy. Both it
+    /// and `max_liquidation_bonus` 0 for a flat `liquidation_bonus`.
+    pub min_liquidation_bonus: u8,
+}
+
+impl ReserveConfig {
+    /// Fields that differ from `other`, as (field, old value, new value)
+    pub fn diff(&self, other: &ReserveConfig) -> Vec<(&'static str, String, String)> {
+        fn display_pubkey(key: &COption<Pubkey>) -> String {
+            match key {
+                COption::Some(key) => key.to_string(),
+                COption::None => "none".to_string(),
+            }
+        }
+
+        let mut changes = Vec::new();
+        macro_rules! compare {
+            ($name:expr, $old:expr, $new:expr, $display:expr) => {
+                if $old != $new {
+                    changes.push(($name, $display(&$old), $display(&$new)));
+                }
+            };
+            ($name:expr, $old:expr, $new:expr) => {
+                compare!($name, $old, $new, ToString::to_string)
+            };
+        }
+
+        compare!(
+            "optimal_utilization_rate",
+            self.optimal_utilization_rate,
+            other.optimal_utilization_rate
+        );
+        compare!(
+            "loan_to_value_ratio",
+            self.loan_to_value_ratio,
+            other.loan_to_value_ratio
+        );
+        compare!(
+            "liquidation_bonus",
+            self.liquidation_bonus,
+            other.liquidation_bonus
+        );
+        compare!(
+            "liquidation_threshold",
+            self.liquidation_threshold,
+            other.liquidation_threshold
+        );
+        compare!(
+            "min_borrow_rate",
+            self.min_borrow_rate,
+            other.min_borrow_rate
+        );
+        compare!(
+            "optimal_borrow_rate",
+            self.optimal_borrow_rate,
+            other.optimal_borrow_rate
+        );
+        compare!(
+            "max_borrow_rate",
+            self.max_borrow_rate,
+            other.max_borrow_rate
+        );
+        compare!(
+            "borrow_fee_wad",
+            self.fees.borrow_fee_wad,
+            other.fees.borrow_fee_wad
+        );
+        compare!(
+            "flash_loan_fee_wad",
+            self.fees.flash_loan_fee_wad,
+            other.fees.flash_loan_fee_wad
+        );
+        compare!(
+            "host_fee_percentage",
+            self.fees.host_fee_percentage,
+            other.fees.host_fee_percentage
+        );
+        compare!(
+            "deposit_staking_pool",
+            self.deposit_staking_pool,
+            other.deposit_staking_pool,
+            display_pubkey
+        );
+        compare!("use_twap", self.use_twap, other.use_twap);
+        compare!(
+            "twap_window_slots",
+            self.twap_window_slots,
+            other.twap_window_slots
+        );
+        compare!(
+            "borrow_fee_discount_staking_pool",
+            self.borrow_fee_discount_staking_pool,
+            other.borrow_fee_discount_staking_pool,
+            display_pubkey
+        );
+        compare!(
+            "borrow_fee_discount_percentage",
+            self.borrow_fee_discount_percentage,
+            other.borrow_fee_discount_percentage
+        );
+        compare!(
+            "max_accrual_slots",
+            self.max_accrual_slots,
+            other.max_accrual_slots
+        );
+        compare!(
+            "borrowing_disabled",
+            self.borrowing_disabled,
+            other.borrowing_disabled
+        );
+        compare!(
+            "liquidation_bonus_ramp_slots",
+            self.liquidation_bonus_ramp_slots,
+            other.liquidation_bonus_ramp_slots
+        );
+        compare!(
+            "min_initial_deposit",
+            self.min_initial_deposit,
+            other.min_initial_deposit
+        );
+        compare!(
+            "max_price_staleness_slots",
+            self.max_price_staleness_slots,
+            other.max_price_staleness_slots
+        );
+        compare!(
+            "max_slot_liquidity_change_percent",
+            self.max_slot_liquidity_change_percent,
+            other.max_slot_liquidity_change_percent
+        );
+        compare!(
+            "max_oracle_deviation_bps",
+            self.max_oracle_deviation_bps,
+            other.max_oracle_deviation_bps
+        );
+        compare!(
+            "isolated_debt_ceiling",
+            self.isolated_debt_ceiling,
+            other.isolated_debt_ceiling
+        );
+        compare!(
+            "collateral_disabled",
+            self.collateral_disabled,
+            other.collateral_disabled
+        );
+        compare!(
+            "max_liquidation_bonus",
+            self.max_liquidation_bonus,
+            other.max_liquidation_bonus
+        );
+        compare!(
+            "borrow_fee_discount_min_stake",
+            self.borrow_fee_discount_min_stake,
+            other.borrow_fee_discount_min_stake
+        );
+        compare!(
+            "min_liquidation_bonus",
+            self.min_liquidation_bonus,
+            other.min_liquidation_bonus
+        );
+        changes
+    }
+
+    /// Check the config values are in range, naming the first that isn't
+    pub fn validate(&self) -> ProgramResult {
+        if self.optimal_utilization_rate > 100 {
+            msg!("Optimal utilization rate must be in range [0, 100]");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.loan_to_value_ratio >= 100 {
+            msg!("Loan to value ratio must be in range [0, 100)");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.liquidation_bonus > 100 {
+            msg!("Liquidation bonus must be in range [0, 100]");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.min_liquidation_bonus > self.max_liquidation_bonus
+            || self.max_liquidation_bonus > 100
+        {
+            msg!(
+                "Max liquidation bonus must be in range [min liquidation bonus {}, 100]",
+                self.min_liquidation_bonus
+            );
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.liquidation_threshold <= self.loan_to_value_ratio
+            || self.liquidation_threshold > 100
+        {
+            msg!(
+                "Liquidation threshold must be in range (LTV {}, 100]",
+                self.loan_to_value_ratio
+            );
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.optimal_borrow_rate < self.min_borrow_rate {
+            msg!("Optimal borrow rate must be >= min borrow rate");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.optimal_borrow_rate > self.max_borrow_rate {
+            msg!("Optimal borrow rate must be <= max borrow rate");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.fees.borrow_fee_wad >= WAD {
+            msg!("Borrow fee must be in range [0, 1_000_000_000_000_000_000)");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.fees.flash_loan_fee_wad >= WAD {
+            msg!("Flash loan fee must be in range [0, 1_000_000_000_000_000_000)");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.fees.host_fee_percentage > 100 {
+            msg!("Host fee percentage must be in range [0, 100]");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.borrow_fee_discount_percentage > 100 {
+            msg!("Borrow fee discount percentage must be in range [0, 100]");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.use_twap && self.twap_window_slots == 0 {
+            msg!("TWAP window must be greater than zero slots when TWAP is enabled");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.max_slot_liquidity_change_percent > 100 {
+            msg!("Max slot liquidity change percentage must be in range [0, 100]");
+            return Err(LendingError::InvalidConfig.into());
+        }
+
+        Ok(())
+    }
 }
 
 /// Additional fee information on a reserve
@@ -636,23 +1445,47 @@ pub struct ReserveFees {
     /// 0.01% (1 basis point) = 100_000_000_000_000
     /// 0.00001% (Aave borrow fee) = 100_000_000_000
     pub borrow_fee_wad: u64,
-    /// Fee for flash loan, expressed as a Wad.
+    /// Fee for flash loan, expressed as a Wad. Must be below 10^18.
     pub flash_loan_fee_wad: u64,
     /// Amount of fee going to host account, if provided in liquidate and repay
     pub host_fee_percentage: u8,
 }
 
 impl ReserveFees {
+    /// Fees with the borrow fee reduced by a percentage
+    pub fn discount_borrow_fee(&self, discount_percentage: u8) -> Result<Self, ProgramError> {
+        if discount_percentage == 0 {
+            return Ok(*self);
+        }
+        let remaining_percentage = 100u8
+            .checked_sub(discount_percentage)
+            .ok_or(LendingError::MathOverflow)?;
+        let borrow_fee_wad = Rate::from_scaled_val(self.borrow_fee_wad)
+            .try_mul(Rate::from_percent(remaining_percentage))?
+            .to_scaled_val();
+        Ok(Self {
+            borrow_fee_wad: u64::try_from(borrow_fee_wad)
+                .map_err(|_| LendingError::MathOverflow)?,
+            ..*self
+        })
+    }
+
     /// Calculate the owner and host fees on borrow
     pub fn calculate_borrow_fees(
         &self,
         borrow_amount: Decimal,
         fee_calculation: FeeCalculation,
     ) -> Result<(u64, u64), ProgramError> {
-        self.calculate_fees(borrow_amount, self.borrow_fee_wad, fee_calculation)
+        self.calculate_fees(
+            borrow_amount,
+            self.borrow_fee_wad,
+            fee_calculation,
+            Decimal::try_round_u64,
+        )
     }
 
-    /// Calculate the owner and host fees on flash loan
+    /// Calculate the owner and host fees on flash loan, rounding the fee up so a fractional fee
+    /// can't be rounded away
     pub fn calculate_flash_loan_fees(
         &self,
         flash_loan_amount: Decimal,
@@ -661,6 +1494,7 @@ impl ReserveFees {
             flash_loan_amount,
             self.flash_loan_fee_wad,
             FeeCalculation::Exclusive,
+            Decimal::try_ceil_u64,
         )
     }
 
@@ -669,6 +1503,7 @@ impl ReserveFees {
         amount: Decimal,
         fee_wad: u64,
         fee_calculation: FeeCalculation,
+        round_fee: fn(&Decimal) -> Result<u64, ProgramError>,
     ) -> Result<(u64, u64), ProgramError> {
         let borrow_fee_rate = Rate::from_scaled_val(fee_wad);
         let host_fee_rate = Rate::from_percent(self.host_fee_percentage);
@@ -697,7 +1532,7 @@ impl ReserveFees {
                 return Err(LendingError::BorrowTooSmall.into());
             }
 
-            let borrow_fee = borrow_fee_decimal.try_round_u64()?;
+            let borrow_fee = round_fee(&borrow_fee_decimal)?;
             let host_fee = if need_to_assess_host_fee {
                 borrow_fee_decimal
                     .try_mul(host_fee_rate)?
@@ -729,10 +1564,30 @@ impl IsInitialized for Reserve {
     }
 }
 
-const RESERVE_LEN: usize = 575; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + (4 + 32) + 8 + 16 + 16 + 16 + 32 + 8 + 32 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 33 + 33 + 182
+pub(crate) const PRICE_SAMPLE_LEN: usize = 24; // 8 + 16
+const RESERVE_LEN: usize = 714; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + (4 + 32) + 8 + 16 + 16 + 16 + 32 + 8 + 32 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 33 + 1 + 8 + (1 + 1 + 24 * 4) + 33 + 1 + 8 + 1 + 8 + 16 + 8 + 8 + 1 + 16 + 1 + 2 + 2 + 8 + 8 + 1 + 1 + 1 + 8 + 33 + 1 + 81
+
+/// Length of reserves created before the protocol owned amount was tracked, once the padding of
+/// the original layout had run out. The current layout only appends fields to it, so
+/// `MigrateReserve` reallocs them to `Reserve::LEN` leaving the appended fields zeroed.
+pub const LEGACY_RESERVE_LEN: usize = 575;
+
 impl Pack for Reserve {
     const LEN: usize = RESERVE_LEN;
 
+    /// Also unpacks the legacy layout, so reserves stay readable until they are migrated
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        match input.len() {
+            RESERVE_LEN => Self::unpack_from_slice(input),
+            LEGACY_RESERVE_LEN => {
+                let mut extended = [0u8; RESERVE_LEN];
+                extended[..LEGACY_RESERVE_LEN].copy_from_slice(input);
+                Self::unpack_from_slice(&extended)
+            }
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
     // @TODO: break this up by reserve / liquidity / collateral / config https://git.io/JOCca
     fn pack_into_slice(&self, output: &mut [u8]) {
         let output = array_mut_ref![output, 0, RESERVE_LEN];
@@ -765,6 +1620,32 @@ impl Pack for Reserve {
             config_fees_flash_loan_fee_wad,
             config_fees_host_fee_percentage,
             config_deposit_staking_pool,
+            config_use_twap,
+            config_twap_window_slots,
+            liquidity_price_history_next_index,
+            liquidity_price_history_len,
+            liquidity_price_history_samples,
+            config_borrow_fee_discount_staking_pool,
+            config_borrow_fee_discount_percentage,
+            config_max_accrual_slots,
+            config_borrowing_disabled,
+            config_liquidation_bonus_ramp_slots,
+            liquidity_cumulative_supply_rate_wads,
+            config_min_initial_deposit,
+            config_max_price_staleness_slots,
+            is_paused,
+            liquidity_borrow_rate,
+            config_max_slot_liquidity_change_percent,
+            slot_liquidity_change_bps,
+            config_max_oracle_deviation_bps,
+            config_isolated_debt_ceiling,
+            liquidity_protocol_owned_amount,
+            config_collateral_disabled,
+            config_max_liquidation_bonus,
+            is_wind_down,
+            config_borrow_fee_discount_min_stake,
+            previous_deposit_staking_pool,
+            config_min_liquidation_bonus,
             _padding,
         ) = mut_array_refs![
             output,
@@ -795,7 +1676,33 @@ impl Pack for Reserve {
             8,
             1,
             33,
-            215
+            1,
+            8,
+            1,
+            1,
+            PRICE_SAMPLE_LEN * PRICE_HISTORY_LEN,
+            33,
+            1,
+            8,
+            1,
+            8,
+            16,
+            8,
+            8,
+            1,
+            16,
+            1,
+            2,
+            2,
+            8,
+            8,
+            1,
+            1,
+            1,
+            8,
+            33,
+            1,
+            81
         ];
 
         // reserve
@@ -820,6 +1727,30 @@ impl Pack for Reserve {
             liquidity_cumulative_borrow_rate_wads,
         );
         pack_decimal(self.liquidity.market_price, liquidity_market_price);
+        pack_decimal(
+            self.liquidity.cumulative_supply_rate_wads,
+            liquidity_cumulative_supply_rate_wads,
+        );
+        *config_min_initial_deposit = self.config.min_initial_deposit.to_le_bytes();
+        *config_max_price_staleness_slots = self.config.max_price_staleness_slots.to_le_bytes();
+        pack_bool(self.is_paused, is_paused);
+        pack_rate(self.liquidity.borrow_rate, liquidity_borrow_rate);
+        *config_max_slot_liquidity_change_percent =
+            self.config.max_slot_liquidity_change_percent.to_le_bytes();
+        *slot_liquidity_change_bps = self.slot_liquidity_change_bps.to_le_bytes();
+        *config_max_oracle_deviation_bps = self.config.max_oracle_deviation_bps.to_le_bytes();
+        *config_isolated_debt_ceiling = self.config.isolated_debt_ceiling.to_le_bytes();
+        *liquidity_protocol_owned_amount = self.liquidity.protocol_owned_amount.to_le_bytes();
+        pack_bool(self.config.collateral_disabled, config_collateral_disabled);
+        *config_max_liquidation_bonus = self.config.max_liquidation_bonus.to_le_bytes();
+        pack_bool(self.is_wind_down, is_wind_down);
+        *config_borrow_fee_discount_min_stake =
+            self.config.borrow_fee_discount_min_stake.to_le_bytes();
+        pack_coption_key_compact(
+            &self.previous_deposit_staking_pool,
+            previous_deposit_staking_pool,
+        );
+        *config_min_liquidation_bonus = self.config.min_liquidation_bonus.to_le_bytes();
 
         // collateral
         collateral_mint_pubkey.copy_from_slice(self.collateral.mint_pubkey.as_ref());
@@ -841,6 +1772,33 @@ impl Pack for Reserve {
             &self.config.deposit_staking_pool,
             config_deposit_staking_pool,
         );
+        pack_bool(self.config.use_twap, config_use_twap);
+        *config_twap_window_slots = self.config.twap_window_slots.to_le_bytes();
+        pack_coption_key_compact(
+            &self.config.borrow_fee_discount_staking_pool,
+            config_borrow_fee_discount_staking_pool,
+        );
+        *config_borrow_fee_discount_percentage =
+            self.config.borrow_fee_discount_percentage.to_le_bytes();
+        *config_max_accrual_slots = self.config.max_accrual_slots.to_le_bytes();
+        pack_bool(self.config.borrowing_disabled, config_borrowing_disabled);
+        *config_liquidation_bonus_ramp_slots =
+            self.config.liquidation_bonus_ramp_slots.to_le_bytes();
+
+        // price history
+        let price_history = &self.liquidity.price_history;
+        *liquidity_price_history_next_index = price_history.next_index.to_le_bytes();
+        *liquidity_price_history_len = price_history.len.to_le_bytes();
+        for (sample, dst) in price_history
+            .samples
+            .iter()
+            .zip(liquidity_price_history_samples.chunks_exact_mut(PRICE_SAMPLE_LEN))
+        {
+            let dst = array_mut_ref![dst, 0, PRICE_SAMPLE_LEN];
+            let (slot, price) = mut_array_refs![dst, 8, 16];
+            *slot = sample.slot.to_le_bytes();
+            pack_decimal(sample.price, price);
+        }
     }
 
     /// Unpacks a byte buffer into a [ReserveInfo](struct.ReserveInfo.html).
@@ -875,6 +1833,32 @@ impl Pack for Reserve {
             config_fees_flash_loan_fee_wad,
             config_fees_host_fee_percentage,
             config_deposit_staking_pool,
+            config_use_twap,
+            config_twap_window_slots,
+            liquidity_price_history_next_index,
+            liquidity_price_history_len,
+            liquidity_price_history_samples,
+            config_borrow_fee_discount_staking_pool,
+            config_borrow_fee_discount_percentage,
+            config_max_accrual_slots,
+            config_borrowing_disabled,
+            config_liquidation_bonus_ramp_slots,
+            liquidity_cumulative_supply_rate_wads,
+            config_min_initial_deposit,
+            config_max_price_staleness_slots,
+            is_paused,
+            liquidity_borrow_rate,
+            config_max_slot_liquidity_change_percent,
+            slot_liquidity_change_bps,
+            config_max_oracle_deviation_bps,
+            config_isolated_debt_ceiling,
+            liquidity_protocol_owned_amount,
+            config_collateral_disabled,
+            config_max_liquidation_bonus,
+            is_wind_down,
+            config_borrow_fee_discount_min_stake,
+            previous_deposit_staking_pool,
+            config_min_liquidation_bonus,
             _padding,
         ) = array_refs![
             input,
@@ -905,32 +1889,86 @@ impl Pack for Reserve {
             8,
             1,
             33,
-            215
-        ];
-
-        let version = u8::from_le_bytes(*version);
-        if version > PROGRAM_VERSION {
-            msg!("Reserve version does not match lending program version");
-            return Err(ProgramError::InvalidAccountData);
-        }
-
-        Ok(Self {
-            version,
-            last_update: LastUpdate {
-                slot: u64::from_le_bytes(*last_update_slot),
-                stale: unpack_bool(last_update_stale)?,
-            },
-            lending_market: Pubkey::new_from_array(*lending_market),
-            liquidity: ReserveLiquidity {
-                mint_pubkey: Pubkey::new_from_array(*liquidity_mint_pubkey),
-                mint_decimals: u8::from_le_bytes(*liquidity_mint_decimals),
-                supply_pubkey: Pubkey::new_from_array(*liquidity_supply_pubkey),
-                fee_receiver: Pubkey::new_from_array(*liquidity_fee_receiver),
+            1,
+            8,
+            1,
+            1,
+            PRICE_SAMPLE_LEN * PRICE_HISTORY_LEN,
+            33,
+            1,
+            8,
+            1,
+            8,
+            16,
+            8,
+            8,
+            1,
+            16,
+            1,
+            2,
+            2,
+            8,
+            8,
+            1,
+            1,
+            1,
+            8,
+            33,
+            1,
+            81
+        ];
+
+        let version = u8::from_le_bytes(*version);
+        if version > PROGRAM_VERSION {
+            msg!("Reserve version does not match lending program version");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut price_history = PriceHistory {
+            next_index: u8::from_le_bytes(*liquidity_price_history_next_index),
+            len: u8::from_le_bytes(*liquidity_price_history_len),
+            ..PriceHistory::default()
+        };
+        if price_history.next_index as usize >= PRICE_HISTORY_LEN
+            || price_history.len as usize > PRICE_HISTORY_LEN
+        {
+            msg!("Reserve price history is invalid");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        for (sample, src) in price_history
+            .samples
+            .iter_mut()
+            .zip(liquidity_price_history_samples.chunks_exact(PRICE_SAMPLE_LEN))
+        {
+            let src = array_ref![src, 0, PRICE_SAMPLE_LEN];
+            let (slot, price) = array_refs![src, 8, 16];
+            *sample = PriceSample {
+                slot: u64::from_le_bytes(*slot),
+                price: unpack_decimal(price),
+            };
+        }
+
+        Ok(Self {
+            version,
+            last_update: LastUpdate {
+                slot: u64::from_le_bytes(*last_update_slot),
+                stale: unpack_bool(last_update_stale)?,
+            },
+            lending_market: Pubkey::new_from_array(*lending_market),
+            liquidity: ReserveLiquidity {
+                mint_pubkey: Pubkey::new_from_array(*liquidity_mint_pubkey),
+                mint_decimals: u8::from_le_bytes(*liquidity_mint_decimals),
+                supply_pubkey: Pubkey::new_from_array(*liquidity_supply_pubkey),
+                fee_receiver: Pubkey::new_from_array(*liquidity_fee_receiver),
                 oracle_pubkey: unpack_coption_key(liquidity_oracle_pubkey)?,
                 available_amount: u64::from_le_bytes(*liquidity_available_amount),
                 borrowed_amount_wads: unpack_decimal(liquidity_borrowed_amount_wads),
                 cumulative_borrow_rate_wads: unpack_decimal(liquidity_cumulative_borrow_rate_wads),
                 market_price: unpack_decimal(liquidity_market_price),
+                price_history,
+                cumulative_supply_rate_wads: unpack_decimal(liquidity_cumulative_supply_rate_wads),
+                borrow_rate: unpack_rate(liquidity_borrow_rate),
+                protocol_owned_amount: u64::from_le_bytes(*liquidity_protocol_owned_amount),
             },
             collateral: ReserveCollateral {
                 mint_pubkey: Pubkey::new_from_array(*collateral_mint_pubkey),
@@ -951,7 +1989,39 @@ impl Pack for Reserve {
                     host_fee_percentage: u8::from_le_bytes(*config_fees_host_fee_percentage),
                 },
                 deposit_staking_pool: unpack_coption_key_compact(config_deposit_staking_pool)?,
+                use_twap: unpack_bool(config_use_twap)?,
+                twap_window_slots: u64::from_le_bytes(*config_twap_window_slots),
+                borrow_fee_discount_staking_pool: unpack_coption_key_compact(
+                    config_borrow_fee_discount_staking_pool,
+                )?,
+                borrow_fee_discount_percentage: u8::from_le_bytes(
+                    *config_borrow_fee_discount_percentage,
+                ),
+                max_accrual_slots: u64::from_le_bytes(*config_max_accrual_slots),
+                borrowing_disabled: unpack_bool(config_borrowing_disabled)?,
+                liquidation_bonus_ramp_slots: u64::from_le_bytes(
+                    *config_liquidation_bonus_ramp_slots,
+                ),
+                min_initial_deposit: u64::from_le_bytes(*config_min_initial_deposit),
+                max_price_staleness_slots: u64::from_le_bytes(*config_max_price_staleness_slots),
+                max_slot_liquidity_change_percent: u8::from_le_bytes(
+                    *config_max_slot_liquidity_change_percent,
+                ),
+                max_oracle_deviation_bps: u16::from_le_bytes(*config_max_oracle_deviation_bps),
+                isolated_debt_ceiling: u64::from_le_bytes(*config_isolated_debt_ceiling),
+                collateral_disabled: unpack_bool(config_collateral_disabled)?,
+                max_liquidation_bonus: u8::from_le_bytes(*config_max_liquidation_bonus),
+                borrow_fee_discount_min_stake: u64::from_le_bytes(
+                    *config_borrow_fee_discount_min_stake,
+                ),
+                min_liquidation_bonus: u8::from_le_bytes(*config_min_liquidation_bonus),
             },
+            is_paused: unpack_bool(is_paused)?,
+            slot_liquidity_change_bps: u16::from_le_bytes(*slot_liquidity_change_bps),
+            is_wind_down: unpack_bool(is_wind_down)?,
+            previous_deposit_staking_pool: unpack_coption_key_compact(
+                previous_deposit_staking_pool,
+            )?,
         })
     }
 }
@@ -1266,6 +2336,134 @@ mod test {
 
     }
 
+    #[test]
+    fn collateral_invariant_tracks_mint_and_burn() {
+        let mut reserve = Reserve::default();
+        let mut collateral_mint_supply = 0;
+        reserve
+            .check_collateral_invariant(collateral_mint_supply)
+            .unwrap();
+
+        // what the processor mints and burns for deposits and redeems
+        collateral_mint_supply += reserve.deposit_liquidity(1_000).unwrap();
+        reserve
+            .check_collateral_invariant(collateral_mint_supply)
+            .unwrap();
+        reserve.liquidity.borrow(Decimal::from(400u64)).unwrap();
+        reserve
+            .check_collateral_invariant(collateral_mint_supply)
+            .unwrap();
+        reserve.redeem_collateral(500).unwrap();
+        collateral_mint_supply -= 500;
+        reserve
+            .check_collateral_invariant(collateral_mint_supply)
+            .unwrap();
+
+        assert_eq!(
+            reserve.check_collateral_invariant(collateral_mint_supply + 1),
+            Err(LendingError::CollateralSupplyMismatch.into())
+        );
+    }
+
+    #[test]
+    fn slot_liquidity_change_limit() {
+        let mut reserve = Reserve::default();
+        reserve.config.max_slot_liquidity_change_percent = 10;
+        // the first deposit into an empty reserve isn't limited
+        reserve.deposit_liquidity(1_000).unwrap();
+        assert_eq!(reserve.slot_liquidity_change_bps, 0);
+
+        // deposits and redeems both count against the limit
+        reserve.deposit_liquidity(50).unwrap();
+        assert_eq!(reserve.slot_liquidity_change_bps, 500);
+        reserve.redeem_collateral(50).unwrap();
+        assert_eq!(reserve.slot_liquidity_change_bps, 977);
+        assert_eq!(
+            reserve.deposit_liquidity(10),
+            Err(LendingError::RateLimited.into())
+        );
+        assert_eq!(reserve.slot_liquidity_change_bps, 977);
+
+        // refreshing the reserve in a later slot resets the limit
+        reserve.slot_liquidity_change_bps = 0;
+        reserve.deposit_liquidity(10).unwrap();
+
+        reserve.config.max_slot_liquidity_change_percent = 0;
+        reserve.deposit_liquidity(10_000).unwrap();
+    }
+
+    #[test]
+    fn calculate_borrow_fee_in_whole_tokens() {
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 1_000_000,
+                market_price: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    borrow_fee_wad: 15_000_000_000_000_000, // 1.5%
+                    flash_loan_fee_wad: 0,
+                    host_fee_percentage: 0,
+                },
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+
+        // (amount to borrow, fee rounded from 1.5% of it)
+        for (amount, fee) in [(1_000, 15), (1_030, 15), (1_100, 17), (1_234_567, 18_519)] {
+            let result = reserve
+                .calculate_borrow(amount, Decimal::from(u64::MAX), 0)
+                .unwrap();
+            assert_eq!(result.receive_amount, amount);
+            assert_eq!(result.borrow_fee, fee);
+            // the debt is exactly what is received plus the whole-token fee sent to the fee
+            // receiver, so no fractional fee is left over to accumulate on the reserve
+            assert_eq!(result.borrow_amount, Decimal::from(amount + fee));
+        }
+
+        // borrowing the maximum takes the whole-token fee out of the whole tokens borrowed
+        let result = reserve
+            .calculate_borrow(u64::MAX, Decimal::from(1_234u64).try_div(10u64).unwrap(), 0)
+            .unwrap();
+        assert_eq!(result.borrow_fee, 2);
+        assert_eq!(
+            result.receive_amount + result.borrow_fee,
+            result.borrow_amount.try_floor_u64().unwrap()
+        );
+    }
+
+    #[test]
+    fn flash_loan_fee_rounds_up() {
+        let fees = ReserveFees {
+            borrow_fee_wad: 3_000_000_000_000_000,     // 0.3%
+            flash_loan_fee_wad: 3_000_000_000_000_000, // 0.3%
+            host_fee_percentage: 0,
+        };
+
+        // (amount, flash loan fee rounded up from 0.3% of it)
+        for (amount, fee) in [(2u64, 1), (100, 1), (1_000, 3), (1_001, 4), (1_100, 4)] {
+            let (total_fee, host_fee) = fees
+                .calculate_flash_loan_fees(Decimal::from(amount))
+                .unwrap();
+            assert_eq!(total_fee, fee);
+            assert_eq!(host_fee, 0);
+        }
+
+        // borrow fees are still rounded to the nearest token
+        let (total_fee, _) = fees
+            .calculate_borrow_fees(Decimal::from(1_100u64), FeeCalculation::Exclusive)
+            .unwrap();
+        assert_eq!(total_fee, 3);
+
+        // a loan too small to pay the minimum fee is rejected rather than made for free
+        assert_eq!(
+            fees.calculate_flash_loan_fees(Decimal::one()).unwrap_err(),
+            LendingError::BorrowTooSmall.into()
+        );
+    }
+
     #[test]
     fn borrow_fee_calculation_min_host() {
         let fees = ReserveFees {
@@ -1354,4 +2552,998 @@ mod test {
         assert_eq!(total_fee, 10); // 1% of 1000
         assert_eq!(host_fee, 0); // 0 host fee
     }
+
+    #[test]
+    fn twap_dampens_price_spike() {
+        let mut reserve = Reserve {
+            config: ReserveConfig {
+                use_twap: true,
+                twap_window_slots: 100,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let spot_price = Decimal::from(10u64);
+        let spike_price = Decimal::from(100u64);
+
+        reserve.update_market_price(spot_price, 0).unwrap();
+        assert_eq!(reserve.liquidity.market_price, spot_price);
+        reserve.update_market_price(spot_price, 50).unwrap();
+        assert_eq!(reserve.liquidity.market_price, spot_price);
+
+        // the spike has no weight in the slot it is observed
+        reserve.update_market_price(spike_price, 75).unwrap();
+        assert_eq!(reserve.liquidity.market_price, spot_price);
+
+        // one slot later it only contributes a single slot of the window
+        reserve.update_market_price(spot_price, 76).unwrap();
+        let expected = Decimal::from(10u64 * 75 + 100)
+            .try_div(Decimal::from(76u64))
+            .unwrap();
+        assert_eq!(reserve.liquidity.market_price, expected);
+        assert!(reserve.liquidity.market_price < spike_price);
+
+        // spot pricing follows the spike
+        reserve.config.use_twap = false;
+        reserve.update_market_price(spike_price, 77).unwrap();
+        assert_eq!(reserve.liquidity.market_price, spike_price);
+    }
+
+    #[test]
+    fn twap_samples_once_per_interval() {
+        let mut reserve = Reserve {
+            config: ReserveConfig {
+                use_twap: true,
+                twap_window_slots: 100,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let spot_price = Decimal::from(10u64);
+        let spike_price = Decimal::from(100u64);
+        let sample_interval = 100 / PRICE_HISTORY_LEN as u64;
+
+        for slot in (0..100).step_by(sample_interval as usize) {
+            reserve.update_market_price(spot_price, slot).unwrap();
+        }
+        assert_eq!(
+            reserve.liquidity.price_history.len as usize,
+            PRICE_HISTORY_LEN
+        );
+
+        // refreshing in consecutive slots only records the first spike, the rest of the window
+        // is still priced by the older samples
+        for slot in 100..100 + PRICE_HISTORY_LEN as u64 {
+            reserve.update_market_price(spike_price, slot).unwrap();
+        }
+        let samples: Vec<Slot> = reserve
+            .liquidity
+            .price_history
+            .samples()
+            .map(|sample| sample.slot)
+            .collect();
+        assert_eq!(samples, vec![25, 50, 75, 100]);
+        let current_slot = 100 + PRICE_HISTORY_LEN as u64 - 1;
+        let expected = Decimal::from(10u64 * 75 + 100 * 3)
+            .try_div(Decimal::from(78u64))
+            .unwrap();
+        assert_eq!(reserve.liquidity.market_price, expected);
+        assert_eq!(
+            reserve
+                .liquidity
+                .price_history
+                .time_weighted_average(current_slot, 100)
+                .unwrap(),
+            Some(expected)
+        );
+
+        // the next sample is recorded once the interval has passed
+        reserve
+            .update_market_price(spot_price, 100 + sample_interval)
+            .unwrap();
+        assert_eq!(
+            reserve.liquidity.price_history.latest().unwrap().slot,
+            100 + sample_interval
+        );
+    }
+
+    #[test]
+    fn oracle_deviation_limit() {
+        let mut reserve = Reserve {
+            config: ReserveConfig {
+                max_oracle_deviation_bps: 1_000,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        // the first price has nothing to deviate from
+        reserve
+            .update_market_price(Decimal::from(100u64), 0)
+            .unwrap();
+
+        // a 10% move in either direction is within the limit
+        reserve
+            .update_market_price(Decimal::from(110u64), 1)
+            .unwrap();
+        reserve
+            .update_market_price(Decimal::from(99u64), 2)
+            .unwrap();
+        assert_eq!(reserve.liquidity.market_price, Decimal::from(99u64));
+
+        assert!(!reserve.is_paused);
+
+        // a larger jump pauses the reserve and leaves the previous price in place
+        reserve
+            .update_market_price(Decimal::from(109u64), 3)
+            .unwrap();
+        assert!(reserve.is_paused);
+        assert_eq!(reserve.liquidity.market_price, Decimal::from(99u64));
+        assert_eq!(
+            reserve.liquidity.price_history.latest().unwrap().price,
+            Decimal::from(109u64)
+        );
+
+        // the next price is compared to the jump, the reserve stays paused until unpaused
+        reserve
+            .update_market_price(Decimal::from(110u64), 4)
+            .unwrap();
+        assert!(reserve.is_paused);
+        assert_eq!(reserve.liquidity.market_price, Decimal::from(110u64));
+
+        reserve.config.max_oracle_deviation_bps = 0;
+        reserve
+            .update_market_price(Decimal::from(1_000u64), 5)
+            .unwrap();
+        assert_eq!(reserve.liquidity.market_price, Decimal::from(1_000u64));
+    }
+
+    #[test]
+    fn twap_window() {
+        let mut history = PriceHistory::default();
+        history.record(0, Decimal::from(100u64), 0);
+        history.record(90, Decimal::from(10u64), 0);
+
+        // only the last 10 slots of the first sample fall in the window
+        let twap = history.time_weighted_average(100, 20).unwrap().unwrap();
+        assert_eq!(twap, Decimal::from(55u64));
+
+        let twap = history.time_weighted_average(100, 5).unwrap().unwrap();
+        assert_eq!(twap, Decimal::from(10u64));
+
+        assert_eq!(history.time_weighted_average(0, 20).unwrap(), None);
+    }
+
+    #[test]
+    fn price_history_ring_buffer() {
+        let mut history = PriceHistory::default();
+        for slot in 1..=6u64 {
+            history.record(slot, Decimal::from(slot), 1);
+        }
+        // same slot replaces the latest sample
+        history.record(6, Decimal::from(60u64), 1);
+
+        assert_eq!(history.len as usize, PRICE_HISTORY_LEN);
+        let samples: Vec<(Slot, Decimal)> = history.samples().map(|s| (s.slot, s.price)).collect();
+        assert_eq!(
+            samples,
+            vec![
+                (3, Decimal::from(3u64)),
+                (4, Decimal::from(4u64)),
+                (5, Decimal::from(5u64)),
+                (6, Decimal::from(60u64)),
+            ]
+        );
+
+        let mut reserve = Reserve::default();
+        reserve.liquidity.price_history = history;
+        reserve.config.use_twap = true;
+        reserve.config.twap_window_slots = 30;
+        let mut packed = [0u8; Reserve::LEN];
+        Reserve::pack(reserve.clone(), &mut packed).unwrap();
+        assert_eq!(Reserve::unpack_from_slice(&packed).unwrap(), reserve);
+    }
+
+    #[test]
+    fn borrow_fee_discount() {
+        let fees = ReserveFees {
+            borrow_fee_wad: 10_000_000_000_000_000, // 1%
+            flash_loan_fee_wad: 0,
+            host_fee_percentage: 20,
+        };
+
+        assert_eq!(fees.discount_borrow_fee(0).unwrap(), fees);
+        let discounted = fees.discount_borrow_fee(50).unwrap();
+        assert_eq!(discounted.borrow_fee_wad, 5_000_000_000_000_000);
+        assert_eq!(discounted.host_fee_percentage, 20);
+        assert_eq!(fees.discount_borrow_fee(100).unwrap().borrow_fee_wad, 0);
+        assert!(fees.discount_borrow_fee(101).is_err());
+
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                mint_decimals: 0,
+                available_amount: 10_000,
+                market_price: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                fees,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let max_borrow_value = Decimal::from(u64::MAX);
+        let full = reserve
+            .calculate_borrow(1_000, max_borrow_value, 0)
+            .unwrap();
+        assert_eq!(full.borrow_fee, 10);
+        let discounted = reserve
+            .calculate_borrow(1_000, max_borrow_value, 50)
+            .unwrap();
+        assert_eq!(discounted.borrow_fee, 5);
+        assert_eq!(discounted.receive_amount, 1_000);
+    }
+
+    #[test]
+    fn reserve_config_diff() {
+        let old = ReserveConfig {
+            max_borrow_rate: 30,
+            ..ReserveConfig::default()
+        };
+        assert!(old.diff(&old).is_empty());
+
+        let new = ReserveConfig {
+            max_borrow_rate: 50,
+            ..old
+        };
+        assert_eq!(
+            old.diff(&new),
+            vec![("max_borrow_rate", "30".to_string(), "50".to_string())]
+        );
+
+        let pool = Pubkey::new_unique();
+        let new = ReserveConfig {
+            fees: ReserveFees {
+                borrow_fee_wad: 1,
+                ..old.fees
+            },
+            deposit_staking_pool: COption::Some(pool),
+            ..old
+        };
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                ("borrow_fee_wad", "0".to_string(), "1".to_string()),
+                ("deposit_staking_pool", "none".to_string(), pool.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reserve_config_validate_fees() {
+        let config = ReserveConfig {
+            loan_to_value_ratio: 50,
+            liquidation_threshold: 55,
+            ..ReserveConfig::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
+
+        let with_fees = |fees: ReserveFees| ReserveConfig { fees, ..config };
+        assert_eq!(
+            with_fees(ReserveFees {
+                borrow_fee_wad: WAD - 1,
+                flash_loan_fee_wad: WAD - 1,
+                host_fee_percentage: 100,
+            })
+            .validate(),
+            Ok(())
+        );
+        for fees in [
+            ReserveFees {
+                borrow_fee_wad: WAD,
+                ..config.fees
+            },
+            ReserveFees {
+                flash_loan_fee_wad: WAD,
+                ..config.fees
+            },
+            ReserveFees {
+                host_fee_percentage: 101,
+                ..config.fees
+            },
+        ] {
+            assert_eq!(
+                with_fees(fees).validate(),
+                Err(LendingError::InvalidConfig.into())
+            );
+        }
+    }
+
+    #[test]
+    fn accrue_interest_max_accrual_slots() {
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                borrowed_amount_wads: Decimal::from(1_000_000u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                max_borrow_rate: 100,
+                max_accrual_slots: 1_000,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let borrow_rate = reserve.current_borrow_rate().unwrap();
+        let compounded = |slots_elapsed| {
+            let mut liquidity = reserve.liquidity.clone();
+            liquidity
+                .compound_interest(borrow_rate, slots_elapsed)
+                .unwrap();
+            liquidity
+        };
+
+        // gaps within the cap accrue exactly
+        let mut normal = reserve.clone();
+        normal.accrue_interest(500).unwrap();
+        assert_eq!(normal.liquidity, compounded(500));
+
+        // longer gaps accrue the cap
+        let mut capped = reserve.clone();
+        capped.accrue_interest(10_000).unwrap();
+        assert_eq!(capped.liquidity, compounded(1_000));
+
+        // the default cap applies when none is configured, even for extreme gaps
+        let mut extreme = reserve.clone();
+        extreme.config.max_accrual_slots = 0;
+        extreme.accrue_interest(u64::MAX).unwrap();
+        assert_eq!(extreme.liquidity, compounded(DEFAULT_MAX_ACCRUAL_SLOTS));
+    }
+
+    #[test]
+    fn accrue_interest_borrow_rate_ceiling() {
+        // fully utilized, so the borrow rate is the configured max
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                borrowed_amount_wads: Decimal::from(1_000_000u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                max_borrow_rate: u8::MAX,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        assert_eq!(
+            reserve.current_borrow_rate().unwrap(),
+            Rate::from_percent(u8::MAX)
+        );
+        let compounded = |borrow_rate| {
+            let mut liquidity = reserve.liquidity.clone();
+            liquidity.compound_interest(borrow_rate, 1_000).unwrap();
+            liquidity
+        };
+
+        let mut clamped = reserve.clone();
+        clamped.accrue_interest(1_000).unwrap();
+        assert_eq!(
+            clamped.liquidity,
+            compounded(Rate::from_percent(MAX_BORROW_RATE_CEILING_PERCENT))
+        );
+        assert!(
+            clamped.liquidity.borrowed_amount_wads
+                < compounded(Rate::from_percent(u8::MAX)).borrowed_amount_wads
+        );
+
+        // the borrow APY is quoted at the rate interest actually accrues at
+        assert_eq!(
+            reserve.effective_borrow_rate().unwrap(),
+            Rate::from_percent(MAX_BORROW_RATE_CEILING_PERCENT)
+        );
+        let mut at_ceiling = reserve.clone();
+        at_ceiling.config.max_borrow_rate = MAX_BORROW_RATE_CEILING_PERCENT;
+        assert_eq!(
+            reserve.borrow_apy(SLOTS_PER_YEAR),
+            at_ceiling.borrow_apy(SLOTS_PER_YEAR)
+        );
+
+        // rates under the ceiling accrue as configured
+        let mut unclamped = reserve.clone();
+        unclamped.config.max_borrow_rate = MAX_BORROW_RATE_CEILING_PERCENT;
+        unclamped.accrue_interest(1_000).unwrap();
+        assert_eq!(
+            unclamped.liquidity,
+            compounded(Rate::from_percent(MAX_BORROW_RATE_CEILING_PERCENT))
+        );
+    }
+
+    #[test]
+    fn accrue_interest_stores_borrow_rate() {
+        let config = ReserveConfig {
+            optimal_utilization_rate: 80,
+            min_borrow_rate: 2,
+            optimal_borrow_rate: 10,
+            max_borrow_rate: 100,
+            ..ReserveConfig::default()
+        };
+
+        // empty, below optimal, at optimal, above optimal and fully utilized
+        for available_amount in [0u64, 100, 250, 400, 1_000_000] {
+            let mut reserve = Reserve {
+                liquidity: ReserveLiquidity {
+                    available_amount,
+                    borrowed_amount_wads: Decimal::from(1_000u64),
+                    cumulative_borrow_rate_wads: Decimal::one(),
+                    ..ReserveLiquidity::default()
+                },
+                config,
+                ..Reserve::default()
+            };
+            assert_eq!(reserve.last_borrow_rate(), Rate::zero());
+
+            // no slots elapsed, nothing accrued
+            reserve.accrue_interest(0).unwrap();
+            assert_eq!(reserve.last_borrow_rate(), Rate::zero());
+
+            // the stored rate is the one interest was accrued at, before the accrual moved it
+            let borrow_rate = reserve.current_borrow_rate().unwrap();
+            reserve.accrue_interest(100).unwrap();
+            assert_eq!(reserve.last_borrow_rate(), borrow_rate);
+
+            // and it follows the utilization on the next accrual
+            reserve.liquidity.available_amount /= 2;
+            let borrow_rate = reserve.current_borrow_rate().unwrap();
+            reserve.accrue_interest(200).unwrap();
+            assert_eq!(reserve.last_borrow_rate(), borrow_rate);
+        }
+    }
+
+    #[test]
+    fn isolated_debt_ceiling() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                mint_decimals: 6,
+                borrowed_amount_wads: Decimal::from(4_000_000_000u64),
+                market_price: Decimal::from(2u64),
+                ..ReserveLiquidity::default()
+            },
+            ..Reserve::default()
+        };
+
+        // no ceiling
+        assert_eq!(
+            reserve.check_isolated_debt_ceiling(Decimal::from(u64::MAX / 2)),
+            Ok(())
+        );
+
+        // 8_000 of borrows already, up to 10_000 allowed
+        reserve.config.isolated_debt_ceiling = 10_000;
+        assert_eq!(
+            reserve.check_isolated_debt_ceiling(Decimal::from(1_000_000_000u64)),
+            Ok(())
+        );
+        assert_eq!(
+            reserve.check_isolated_debt_ceiling(Decimal::from(1_000_000_001u64)),
+            Err(LendingError::DebtCeilingExceeded.into())
+        );
+
+        // the ceiling is on value, so a price rise leaves less room
+        reserve.liquidity.market_price = Decimal::from(3u64);
+        assert_eq!(
+            reserve.check_isolated_debt_ceiling(Decimal::from(1u64)),
+            Err(LendingError::DebtCeilingExceeded.into())
+        );
+    }
+
+    #[test]
+    fn accrued_interest_never_lost_to_rounding() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                cumulative_borrow_rate_wads: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                optimal_utilization_rate: 80,
+                min_borrow_rate: 3,
+                optimal_borrow_rate: 17,
+                max_borrow_rate: 150,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let deposit_amount = 1_000_003;
+        let collateral_amount = reserve.deposit_liquidity(deposit_amount).unwrap();
+
+        let borrow_amount = 700_001;
+        reserve
+            .liquidity
+            .borrow(Decimal::from(borrow_amount))
+            .unwrap();
+        let mut liquidity = ObligationLiquidity::new(Pubkey::new_unique());
+        liquidity.cumulative_borrow_rate_wads = reserve.liquidity.cumulative_borrow_rate_wads;
+        liquidity.borrow(Decimal::from(borrow_amount)).unwrap();
+
+        // many short accruals, each one rounding
+        let mut slot = 0;
+        for _ in 0..1_000 {
+            slot += 13;
+            reserve.accrue_interest(slot).unwrap();
+            reserve.last_update.update_slot(slot);
+            liquidity
+                .accrue_interest(reserve.liquidity.cumulative_borrow_rate_wads)
+                .unwrap();
+            assert!(liquidity.borrowed_amount_wads >= reserve.liquidity.borrowed_amount_wads);
+        }
+
+        // the borrower repays principal and interest in full
+        let settle_amount = liquidity.borrowed_amount_wads;
+        let repay_amount = settle_amount.try_ceil_u64().unwrap();
+        assert!(repay_amount > borrow_amount);
+        reserve
+            .liquidity
+            .repay(repay_amount, settle_amount)
+            .unwrap();
+        assert_eq!(reserve.liquidity.borrowed_amount_wads, Decimal::zero());
+
+        // and suppliers redeem no more than what was repaid
+        let redeemed_amount = reserve.redeem_collateral(collateral_amount).unwrap();
+        assert!(redeemed_amount > deposit_amount);
+        assert!(redeemed_amount - deposit_amount <= repay_amount - borrow_amount);
+        assert_eq!(
+            reserve.liquidity.available_amount,
+            deposit_amount + repay_amount - borrow_amount - redeemed_amount
+        );
+    }
+
+    #[test]
+    fn liquidation_bonus_ramp() {
+        let reserve = Reserve {
+            config: ReserveConfig {
+                liquidation_bonus: 10,
+                liquidation_bonus_ramp_slots: 100,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let obligation = Obligation {
+            became_unhealthy_slot: 1_000,
+            ..Obligation::default()
+        };
+        let bonus = |current_slot| {
+            reserve
+                .liquidation_bonus(&obligation, current_slot)
+                .unwrap()
+        };
+
+        // the bonus ramps up linearly once the obligation becomes unhealthy
+        assert_eq!(bonus(1_000), Rate::zero());
+        assert_eq!(bonus(1_050), Rate::from_percent(5));
+        assert_eq!(bonus(1_100), Rate::from_percent(10));
+        assert_eq!(bonus(5_000), Rate::from_percent(10));
+
+        // no ramp pays the full bonus immediately
+        let mut instant = reserve.clone();
+        instant.config.liquidation_bonus_ramp_slots = 0;
+        assert_eq!(
+            instant.liquidation_bonus(&obligation, 1_000).unwrap(),
+            Rate::from_percent(10)
+        );
+    }
+
+    #[test]
+    fn liquidation_bonus_scales_with_health() {
+        let reserve = Reserve {
+            config: ReserveConfig {
+                liquidation_bonus: 10,
+                liquidation_threshold: 80,
+                min_liquidation_bonus: 5,
+                max_liquidation_bonus: 15,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let obligation = |borrowed_value: u64| Obligation {
+            deposited_value: Decimal::from(100u64),
+            unhealthy_borrow_value: Decimal::from(80u64),
+            borrowed_value: Decimal::from(borrowed_value),
+            ..Obligation::default()
+        };
+        let bonus = |reserve: &Reserve, borrowed_value| {
+            reserve
+                .health_liquidation_bonus(&obligation(borrowed_value))
+                .unwrap()
+        };
+
+        // the bonus interpolates between the bounds as the health ratio goes from 1 at mild
+        // unhealthiness to 100 / liquidation threshold = 1.25 at severe unhealthiness
+        assert_eq!(bonus(&reserve, 80), Rate::from_percent(5));
+        assert_eq!(bonus(&reserve, 84), Rate::from_percent(7));
+        assert_eq!(bonus(&reserve, 96), Rate::from_percent(13));
+        assert_eq!(bonus(&reserve, 100), Rate::from_percent(15));
+        assert_eq!(bonus(&reserve, 150), Rate::from_percent(15));
+        assert!(bonus(&reserve, 84) < bonus(&reserve, 96));
+
+        // min == max is a flat bonus however unhealthy the obligation is
+        let mut flat = reserve.clone();
+        flat.config.min_liquidation_bonus = 5;
+        flat.config.max_liquidation_bonus = 5;
+        for borrowed_value in [80, 84, 96, 150] {
+            assert_eq!(bonus(&flat, borrowed_value), Rate::from_percent(5));
+        }
+
+        // with both unset the legacy `liquidation_bonus` applies, and a flat config at the same
+        // value reproduces it
+        let mut legacy = reserve.clone();
+        legacy.config.min_liquidation_bonus = 0;
+        legacy.config.max_liquidation_bonus = 0;
+        let mut flat_legacy = reserve.clone();
+        flat_legacy.config.min_liquidation_bonus = 10;
+        flat_legacy.config.max_liquidation_bonus = 10;
+        for borrowed_value in [80, 84, 96, 150] {
+            assert_eq!(bonus(&legacy, borrowed_value), Rate::from_percent(10));
+            assert_eq!(
+                bonus(&flat_legacy, borrowed_value),
+                bonus(&legacy, borrowed_value)
+            );
+        }
+
+        // the ramp applies to the scaled bonus
+        let mut ramped = reserve.clone();
+        ramped.config.liquidation_bonus_ramp_slots = 100;
+        assert_eq!(
+            ramped.liquidation_bonus(&obligation(96), 50).unwrap(),
+            Rate::from_percent(13).try_div(2).unwrap()
+        );
+    }
+
+    #[test]
+    fn cumulative_supply_rate_tracks_exchange_rate() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 4_000_000,
+                borrowed_amount_wads: Decimal::from(6_000_000u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 10_000_000,
+                ..ReserveCollateral::default()
+            },
+            config: ReserveConfig {
+                optimal_utilization_rate: 80,
+                optimal_borrow_rate: 10,
+                max_borrow_rate: 100,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        // reserves created before the index was tracked start from one
+        assert_eq!(reserve.liquidity.cumulative_supply_rate(), Decimal::one());
+
+        let mut previous_supply_rate = Decimal::one();
+        for slot in [1, 100, 10_000, 1_000_000] {
+            reserve.accrue_interest(slot).unwrap();
+            reserve.last_update.update_slot(slot);
+
+            let supply_rate = reserve.liquidity.cumulative_supply_rate();
+            assert!(supply_rate > previous_supply_rate);
+            previous_supply_rate = supply_rate;
+
+            // a collateral token minted at the start is worth the index in liquidity
+            let liquidity_per_collateral = reserve
+                .collateral_exchange_rate()
+                .unwrap()
+                .decimal_collateral_to_liquidity(Decimal::one())
+                .unwrap();
+            let difference = if supply_rate > liquidity_per_collateral {
+                supply_rate.try_sub(liquidity_per_collateral).unwrap()
+            } else {
+                liquidity_per_collateral.try_sub(supply_rate).unwrap()
+            };
+            assert!(difference < Decimal::from_scaled_val(1_000_000));
+        }
+    }
+
+    #[test]
+    fn apy_scales_with_slots_per_year() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 4_000_000,
+                borrowed_amount_wads: Decimal::from(6_000_000u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                optimal_utilization_rate: 80,
+                optimal_borrow_rate: 10,
+                max_borrow_rate: 100,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let borrow_rate = reserve.current_borrow_rate().unwrap();
+
+        // 7.5% compounded every slot of the year is e^0.075 - 1, about 7.79%
+        assert_eq!(borrow_rate, Rate::from_scaled_val(75_000_000_000_000_000));
+        let borrow_apy = reserve.borrow_apy(SLOTS_PER_YEAR).unwrap();
+        assert!(borrow_apy > Rate::from_scaled_val(77_880_000_000_000_000));
+        assert!(borrow_apy < Rate::from_scaled_val(77_890_000_000_000_000));
+        let supply_apy = reserve.supply_apy(SLOTS_PER_YEAR).unwrap();
+        assert!(supply_apy < borrow_apy);
+        assert!(supply_apy > borrow_rate.try_mul(Rate::from_percent(60)).unwrap());
+
+        // slower slots mean fewer slots of interest a year
+        let slow_borrow_apy = reserve.borrow_apy(SLOTS_PER_YEAR / 2).unwrap();
+        let slow_supply_apy = reserve.supply_apy(SLOTS_PER_YEAR / 2).unwrap();
+        // half a year of slots earns e^0.0375 - 1, about 3.82%
+        assert!(slow_borrow_apy > Rate::from_scaled_val(38_210_000_000_000_000));
+        assert!(slow_borrow_apy < Rate::from_scaled_val(38_220_000_000_000_000));
+        assert!(slow_borrow_apy < borrow_apy);
+        assert!(slow_supply_apy < supply_apy);
+        // a year of the fast slots is two of the slow ones
+        let compounded = Rate::one()
+            .try_add(slow_borrow_apy)
+            .unwrap()
+            .try_pow(2)
+            .unwrap()
+            .try_sub(Rate::one())
+            .unwrap();
+        let difference = if compounded > borrow_apy {
+            compounded.try_sub(borrow_apy).unwrap()
+        } else {
+            borrow_apy.try_sub(compounded).unwrap()
+        };
+        assert!(difference < Rate::from_scaled_val(1_000_000_000));
+
+        // the estimate only changes the display, interest still accrues per slot
+        let mut accrued = reserve.clone();
+        accrued.accrue_interest(SLOTS_PER_YEAR).unwrap();
+        let growth = accrued
+            .liquidity
+            .cumulative_borrow_rate_wads
+            .try_sub(Decimal::one())
+            .unwrap();
+        assert!(growth >= Decimal::from(borrow_apy));
+        assert!(
+            growth
+                < Decimal::from(borrow_apy)
+                    .try_add(Decimal::from_scaled_val(1_000_000_000))
+                    .unwrap()
+        );
+
+        reserve.liquidity.borrowed_amount_wads = Decimal::zero();
+        assert_eq!(reserve.supply_apy(SLOTS_PER_YEAR), Ok(Rate::zero()));
+        assert_eq!(reserve.borrow_apy(SLOTS_PER_YEAR), Ok(Rate::zero()));
+    }
+
+    #[test]
+    fn supplier_apy_scales_borrow_apy() {
+        let borrow_apy = Rate::from_percent(10);
+        for (utilization, reserve_factor, expected) in [
+            (0, 0, Rate::zero()),
+            (0, 20, Rate::zero()),
+            (50, 0, Rate::from_percent(5)),
+            (50, 10, Rate::from_scaled_val(45_000_000_000_000_000)),
+            (80, 20, Rate::from_scaled_val(64_000_000_000_000_000)),
+            (100, 0, borrow_apy),
+            (100, 100, Rate::zero()),
+        ] {
+            assert_eq!(
+                supplier_apy(
+                    borrow_apy,
+                    Rate::from_percent(utilization),
+                    Rate::from_percent(reserve_factor)
+                ),
+                Ok(expected)
+            );
+        }
+        assert_eq!(
+            supplier_apy(borrow_apy, Rate::one(), Rate::from_percent(101)),
+            Err(LendingError::MathOverflow.into())
+        );
+
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 4_000_000,
+                borrowed_amount_wads: Decimal::from(6_000_000u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                optimal_utilization_rate: 80,
+                optimal_borrow_rate: 10,
+                max_borrow_rate: 100,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        assert_eq!(
+            reserve.supply_apy(SLOTS_PER_YEAR),
+            reserve
+                .borrow_apy(SLOTS_PER_YEAR)
+                .unwrap()
+                .try_mul(Rate::from_percent(60))
+        );
+
+        reserve.liquidity.borrowed_amount_wads = Decimal::zero();
+        reserve.config.min_borrow_rate = 5;
+        assert!(reserve.borrow_apy(SLOTS_PER_YEAR).unwrap() > Rate::zero());
+        assert_eq!(reserve.supply_apy(SLOTS_PER_YEAR), Ok(Rate::zero()));
+    }
+
+    #[test]
+    fn donation_is_protocol_owned() {
+        let mut reserve = Reserve::default();
+        let user_collateral = reserve.deposit_liquidity(1_000_000).unwrap();
+        let exchange_rate = reserve.collateral_exchange_rate().unwrap();
+
+        reserve.liquidity.donate(100_000).unwrap();
+        assert_eq!(reserve.protocol_owned_amount(), 100_000);
+        assert_eq!(reserve.liquidity.available_amount, 1_100_000);
+
+        // no collateral is minted for the donation, it raises the value of the existing collateral
+        assert_eq!(reserve.collateral.mint_total_supply, user_collateral);
+        assert!(reserve.collateral_exchange_rate().unwrap().0 < exchange_rate.0);
+        assert_eq!(
+            reserve
+                .collateral_exchange_rate()
+                .unwrap()
+                .collateral_to_liquidity(user_collateral)
+                .unwrap(),
+            1_100_000
+        );
+
+        reserve.liquidity.donate(50_000).unwrap();
+        assert_eq!(reserve.protocol_owned_amount(), 150_000);
+    }
+
+    #[test]
+    fn max_redeemable_collateral_caps_at_available_liquidity() {
+        let mut reserve = Reserve::default();
+        let user_collateral = reserve.deposit_liquidity(1_000_000).unwrap();
+
+        // nothing borrowed, the whole balance can be redeemed
+        assert_eq!(
+            reserve.max_redeemable_collateral(user_collateral).unwrap(),
+            user_collateral
+        );
+
+        // 60% utilized with interest accrued, only the available 40% can be
+        reserve.liquidity.borrow(Decimal::from(600_000u64)).unwrap();
+        reserve.liquidity.borrowed_amount_wads = reserve
+            .liquidity
+            .borrowed_amount_wads
+            .try_mul(Rate::from_percent(110))
+            .unwrap();
+        let available_collateral = reserve
+            .collateral_exchange_rate()
+            .unwrap()
+            .liquidity_to_collateral(reserve.liquidity.available_amount)
+            .unwrap();
+        assert!(available_collateral < 400_000);
+        let max_redeemable = reserve.max_redeemable_collateral(user_collateral).unwrap();
+        assert_eq!(max_redeemable, available_collateral);
+        assert_eq!(reserve.max_redeemable_collateral(10).unwrap(), 10);
+
+        assert!(reserve.redeem_collateral(max_redeemable).unwrap() <= 400_000);
+    }
+
+    #[test]
+    fn utilization_after_action_matches_reserve() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                market_price: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    borrow_fee_wad: 10_000_000_000_000_000, // 1%
+                    flash_loan_fee_wad: 0,
+                    host_fee_percentage: 0,
+                },
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let collateral_amount = reserve.deposit_liquidity(1_000_000).unwrap();
+
+        let simulated = reserve.utilization_after_borrow(300_000).unwrap();
+        let borrow = reserve
+            .calculate_borrow(300_000, Decimal::from(u64::MAX), 0)
+            .unwrap();
+        reserve.liquidity.borrow(borrow.borrow_amount).unwrap();
+        assert_eq!(simulated, reserve.liquidity.utilization_rate().unwrap());
+        assert_eq!(simulated, Rate::from_scaled_val(303_000_000_000_000_000));
+
+        let simulated = reserve.utilization_after_redeem(500_000).unwrap();
+        reserve.redeem_collateral(500_000).unwrap();
+        assert_eq!(simulated, reserve.liquidity.utilization_rate().unwrap());
+
+        // neither can take more than the available liquidity
+        assert_eq!(
+            reserve.utilization_after_borrow(1_000_000),
+            Err(LendingError::InsufficientLiquidity.into())
+        );
+        assert_eq!(
+            reserve.utilization_after_redeem(collateral_amount),
+            Err(LendingError::InsufficientLiquidity.into())
+        );
+    }
+
+    #[test]
+    fn min_initial_deposit_limits_donation_attack() {
+        const DONATION: u64 = 1_000_000;
+        const VICTIM_DEPOSIT: u64 = 999_999;
+
+        // liquidity credited to the reserve without minting collateral, e.g. a donation
+        let donate = |reserve: &mut Reserve| {
+            reserve.liquidity.available_amount += DONATION;
+        };
+
+        // without a minimum, a single unit first deposit lets the donation round the next
+        // deposit down to nothing
+        let mut reserve = Reserve::default();
+        assert_eq!(reserve.deposit_liquidity(1).unwrap(), 1);
+        donate(&mut reserve);
+        assert_eq!(reserve.deposit_liquidity(VICTIM_DEPOSIT).unwrap(), 0);
+
+        let mut reserve = Reserve {
+            config: ReserveConfig {
+                min_initial_deposit: 1_000_000,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        assert_eq!(
+            reserve.deposit_liquidity(1),
+            Err(LendingError::InitialDepositTooSmall.into())
+        );
+        assert_eq!(reserve.deposit_liquidity(1_000_000).unwrap(), 1_000_000);
+        donate(&mut reserve);
+
+        // the next depositor loses at most rounding dust
+        let collateral_amount = reserve.deposit_liquidity(VICTIM_DEPOSIT).unwrap();
+        let redeemable = reserve
+            .collateral_exchange_rate()
+            .unwrap()
+            .collateral_to_liquidity(collateral_amount)
+            .unwrap();
+        assert!(VICTIM_DEPOSIT - redeemable <= 2);
+
+        // the minimum only applies while the reserve is empty
+        assert!(reserve.deposit_liquidity(10).unwrap() > 0);
+    }
+
+    #[test]
+    fn unpack_legacy_reserve() {
+        let mut reserve = Reserve {
+            version: PROGRAM_VERSION,
+            lending_market: Pubkey::new_unique(),
+            ..Reserve::default()
+        };
+        reserve.liquidity.available_amount = 1_000;
+        reserve.collateral.mint_total_supply = 900;
+
+        // the legacy layout is the current one without the appended fields, which unpack as zero
+        let mut packed = [0u8; Reserve::LEN];
+        Reserve::pack(reserve.clone(), &mut packed).unwrap();
+        let legacy = &packed[..LEGACY_RESERVE_LEN];
+
+        let unpacked = Reserve::unpack(legacy).unwrap();
+        assert_eq!(unpacked, reserve);
+
+        // migrated reserves keep their liquidity and collateral
+        let mut migrated = [0u8; Reserve::LEN];
+        Reserve::pack(unpacked, &mut migrated).unwrap();
+        assert_eq!(migrated, packed);
+
+        assert_eq!(
+            Reserve::unpack(&legacy[..LEGACY_RESERVE_LEN - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            Reserve::unpack(&[0u8; LEGACY_RESERVE_LEN]),
+            Err(ProgramError::UninitializedAccount)
+        );
+    }
 }
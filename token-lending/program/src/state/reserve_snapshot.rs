@@ -0,0 +1,279 @@
+use solana_program::{clock::Slot, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{error::LendingError, math::Decimal};
+
+use super::*;
+
+/// Plain data copy of a reserve's state for off-chain simulation, with decimal values stored
+/// as their scaled WAD representation
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReserveSnapshot {
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Last slot when supply and rates updated
+    pub last_update_slot: Slot,
+    /// True when marked stale
+    pub stale: bool,
+    /// Reserve liquidity mint address
+    pub liquidity_mint_pubkey: Pubkey,
+    /// Reserve liquidity mint decimals
+    pub liquidity_mint_decimals: u8,
+    /// Reserve liquidity supply address
+    pub liquidity_supply_pubkey: Pubkey,
+    /// Reserve liquidity fee receiver address
+    pub liquidity_fee_receiver: Pubkey,
+    /// Reserve liquidity oracle account
+    pub liquidity_oracle_pubkey: Option<Pubkey>,
+    /// Reserve liquidity available
+    pub available_amount: u64,
+    /// Reserve liquidity borrowed
+    pub borrowed_amount_wads: u128,
+    /// Reserve liquidity cumulative borrow rate
+    pub cumulative_borrow_rate_wads: u128,
+    /// Reserve liquidity market price in quote currency
+    pub market_price_wads: u128,
+    /// Reserve liquidity cumulative supply rate
+    pub cumulative_supply_rate_wads: u128,
+    /// Liquidity donated to the reserve
+    pub protocol_owned_amount: u64,
+    /// Reserve collateral mint address
+    pub collateral_mint_pubkey: Pubkey,
+    /// Reserve collateral mint supply
+    pub collateral_mint_total_supply: u64,
+    /// Reserve collateral supply address
+    pub collateral_supply_pubkey: Pubkey,
+    /// Optimal utilization rate, as a percentage
+    pub optimal_utilization_rate: u8,
+    /// Target ratio of the value of borrows to deposits, as a percentage
+    pub loan_to_value_ratio: u8,
+    /// Bonus a liquidator gets when repaying part of an unhealthy obligation, as a percentage
+    pub liquidation_bonus: u8,
+    /// Loan to value ratio at which an obligation can be liquidated, as a percentage
+    pub liquidation_threshold: u8,
+    /// Min borrow APY
+    pub min_borrow_rate: u8,
+    /// Optimal (utilization) borrow APY
+    pub optimal_borrow_rate: u8,
+    /// Max borrow APY
+    pub max_borrow_rate: u8,
+    /// Fee assessed on `BorrowObligationLiquidity`, expressed as a Wad
+    pub borrow_fee_wad: u64,
+    /// Fee for flash loan, expressed as a Wad
+    pub flash_loan_fee_wad: u64,
+    /// Amount of fee going to host account, if provided in liquidate and repay
+    pub host_fee_percentage: u8,
+    /// Maximum number of slots interest is accrued over in a single refresh
+    pub max_accrual_slots: u64,
+    /// Liquidity cannot be borrowed from the reserve
+    pub borrowing_disabled: bool,
+    /// Number of slots the liquidation bonus ramps up over after an obligation becomes unhealthy
+    pub liquidation_bonus_ramp_slots: u64,
+    /// Minimum liquidity deposited while the reserve has no collateral minted
+    pub min_initial_deposit: u64,
+    /// Number of slots after which the oracle price is stale and refreshes pause the reserve
+    pub max_price_staleness_slots: u64,
+    /// Borrows and liquidations are blocked until the reserve is unpaused
+    pub is_paused: bool,
+    /// Maximum liquidity deposited and redeemed in a single slot, as a percentage
+    pub max_slot_liquidity_change_percent: u8,
+    /// Liquidity deposited and redeemed in the current slot, in basis points
+    pub slot_liquidity_change_bps: u16,
+    /// Maximum move of the oracle price from the previous price in a refresh, in basis points
+    pub max_oracle_deviation_bps: u16,
+    /// Maximum value of all borrows of the reserve liquidity, in quote currency
+    pub isolated_debt_ceiling: u64,
+    /// Deposits of the reserve give obligations no borrowing power
+    pub collateral_disabled: bool,
+    /// Liquidation bonus for an obligation whose borrows are worth its deposits, as a percentage
+    pub max_liquidation_bonus: u8,
+    /// Liquidation bonus for an obligation that just became unhealthy, as a percentage
+    pub min_liquidation_bonus: u8,
+    /// Deposits and borrows are blocked while the reserve is drained
+    pub is_wind_down: bool,
+}
+
+impl ReserveSnapshot {
+    /// Accrue interest over `slots` slots the same way a refresh would on-chain
+    pub fn simulate_accrue(&mut self, slots: u64) -> Result<(), ProgramError> {
+        let mut reserve = self.to_reserve();
+        let current_slot = self
+            .last_update_slot
+            .checked_add(slots)
+            .ok_or(LendingError::MathOverflow)?;
+        reserve.accrue_interest(current_slot)?;
+        reserve.last_update.update_slot(current_slot);
+
+        self.last_update_slot = reserve.last_update.slot;
+        self.stale = reserve.last_update.stale;
+        self.borrowed_amount_wads = reserve.liquidity.borrowed_amount_wads.to_scaled_val()?;
+        self.cumulative_borrow_rate_wads = reserve
+            .liquidity
+            .cumulative_borrow_rate_wads
+            .to_scaled_val()?;
+        self.cumulative_supply_rate_wads = reserve
+            .liquidity
+            .cumulative_supply_rate_wads
+            .to_scaled_val()?;
+        Ok(())
+    }
+
+    fn to_reserve(&self) -> Reserve {
+        Reserve {
+            version: PROGRAM_VERSION,
+            last_update: LastUpdate {
+                slot: self.last_update_slot,
+                stale: self.stale,
+            },
+            lending_market: self.lending_market,
+            liquidity: ReserveLiquidity {
+                mint_pubkey: self.liquidity_mint_pubkey,
+                mint_decimals: self.liquidity_mint_decimals,
+                supply_pubkey: self.liquidity_supply_pubkey,
+                fee_receiver: self.liquidity_fee_receiver,
+                oracle_pubkey: self.liquidity_oracle_pubkey.into(),
+                available_amount: self.available_amount,
+                borrowed_amount_wads: Decimal::from_scaled_val(self.borrowed_amount_wads),
+                cumulative_borrow_rate_wads: Decimal::from_scaled_val(
+                    self.cumulative_borrow_rate_wads,
+                ),
+                market_price: Decimal::from_scaled_val(self.market_price_wads),
+                cumulative_supply_rate_wads: Decimal::from_scaled_val(
+                    self.cumulative_supply_rate_wads,
+                ),
+                protocol_owned_amount: self.protocol_owned_amount,
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_pubkey: self.collateral_mint_pubkey,
+                mint_total_supply: self.collateral_mint_total_supply,
+                supply_pubkey: self.collateral_supply_pubkey,
+            },
+            config: ReserveConfig {
+                optimal_utilization_rate: self.optimal_utilization_rate,
+                loan_to_value_ratio: self.loan_to_value_ratio,
+                liquidation_bonus: self.liquidation_bonus,
+                liquidation_threshold: self.liquidation_threshold,
+                min_borrow_rate: self.min_borrow_rate,
+                optimal_borrow_rate: self.optimal_borrow_rate,
+                max_borrow_rate: self.max_borrow_rate,
+                fees: ReserveFees {
+                    borrow_fee_wad: self.borrow_fee_wad,
+                    flash_loan_fee_wad: self.flash_loan_fee_wad,
+                    host_fee_percentage: self.host_fee_percentage,
+                },
+                max_accrual_slots: self.max_accrual_slots,
+                borrowing_disabled: self.borrowing_disabled,
+                liquidation_bonus_ramp_slots: self.liquidation_bonus_ramp_slots,
+                min_initial_deposit: self.min_initial_deposit,
+                max_price_staleness_slots: self.max_price_staleness_slots,
+                max_slot_liquidity_change_percent: self.max_slot_liquidity_change_percent,
+                max_oracle_deviation_bps: self.max_oracle_deviation_bps,
+                isolated_debt_ceiling: self.isolated_debt_ceiling,
+                collateral_disabled: self.collateral_disabled,
+                max_liquidation_bonus: self.max_liquidation_bonus,
+                min_liquidation_bonus: self.min_liquidation_bonus,
+                ..ReserveConfig::default()
+            },
+            is_paused: self.is_paused,
+            slot_liquidity_change_bps: self.slot_liquidity_change_bps,
+            is_wind_down: self.is_wind_down,
+            previous_deposit_staking_pool: COption::None,
+        }
+    }
+}
+
+impl Reserve {
+    /// Export the reserve state for off-chain simulation
+    pub fn snapshot(&self) -> ReserveSnapshot {
+        let scaled_val = |decimal: Decimal| {
+            decimal
+                .to_scaled_val()
+                .expect("Decimal cannot be snapshotted")
+        };
+        ReserveSnapshot {
+            lending_market: self.lending_market,
+            last_update_slot: self.last_update.slot,
+            stale: self.last_update.stale,
+            liquidity_mint_pubkey: self.liquidity.mint_pubkey,
+            liquidity_mint_decimals: self.liquidity.mint_decimals,
+            liquidity_supply_pubkey: self.liquidity.supply_pubkey,
+            liquidity_fee_receiver: self.liquidity.fee_receiver,
+            liquidity_oracle_pubkey: self.liquidity.oracle_pubkey.into(),
+            available_amount: self.liquidity.available_amount,
+            borrowed_amount_wads: scaled_val(self.liquidity.borrowed_amount_wads),
+            cumulative_borrow_rate_wads: scaled_val(self.liquidity.cumulative_borrow_rate_wads),
+            market_price_wads: scaled_val(self.liquidity.market_price),
+            cumulative_supply_rate_wads: scaled_val(self.liquidity.cumulative_supply_rate_wads),
+            protocol_owned_amount: self.liquidity.protocol_owned_amount,
+            collateral_mint_pubkey: self.collateral.mint_pubkey,
+            collateral_mint_total_supply: self.collateral.mint_total_supply,
+            collateral_supply_pubkey: self.collateral.supply_pubkey,
+            optimal_utilization_rate: self.config.optimal_utilization_rate,
+            loan_to_value_ratio: self.config.loan_to_value_ratio,
+            liquidation_bonus: self.config.liquidation_bonus,
+            liquidation_threshold: self.config.liquidation_threshold,
+            min_borrow_rate: self.config.min_borrow_rate,
+            optimal_borrow_rate: self.config.optimal_borrow_rate,
+            max_borrow_rate: self.config.max_borrow_rate,
+            borrow_fee_wad: self.config.fees.borrow_fee_wad,
+            flash_loan_fee_wad: self.config.fees.flash_loan_fee_wad,
+            host_fee_percentage: self.config.fees.host_fee_percentage,
+            max_accrual_slots: self.config.max_accrual_slots,
+            borrowing_disabled: self.config.borrowing_disabled,
+            liquidation_bonus_ramp_slots: self.config.liquidation_bonus_ramp_slots,
+            min_initial_deposit: self.config.min_initial_deposit,
+            max_price_staleness_slots: self.config.max_price_staleness_slots,
+            is_paused: self.is_paused,
+            max_slot_liquidity_change_percent: self.config.max_slot_liquidity_change_percent,
+            slot_liquidity_change_bps: self.slot_liquidity_change_bps,
+            max_oracle_deviation_bps: self.config.max_oracle_deviation_bps,
+            isolated_debt_ceiling: self.config.isolated_debt_ceiling,
+            collateral_disabled: self.config.collateral_disabled,
+            max_liquidation_bonus: self.config.max_liquidation_bonus,
+            min_liquidation_bonus: self.config.min_liquidation_bonus,
+            is_wind_down: self.is_wind_down,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn simulate_accrue_matches_accrue_interest() {
+        let reserve = Reserve {
+            last_update: LastUpdate {
+                slot: 100,
+                stale: false,
+            },
+            liquidity: ReserveLiquidity {
+                available_amount: 4_000_000,
+                borrowed_amount_wads: Decimal::from(6_000_000u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                market_price: Decimal::from(20u64),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                optimal_utilization_rate: 80,
+                min_borrow_rate: 2,
+                optimal_borrow_rate: 10,
+                max_borrow_rate: 100,
+                max_accrual_slots: 5_000,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+
+        for slots in [0, 1, 1_000, 5_000, 1_000_000] {
+            let mut expected = reserve.clone();
+            expected.accrue_interest(100 + slots).unwrap();
+            expected.last_update.update_slot(100 + slots);
+
+            let mut snapshot = reserve.snapshot();
+            snapshot.simulate_accrue(slots).unwrap();
+            assert_eq!(snapshot, expected.snapshot());
+        }
+    }
+}
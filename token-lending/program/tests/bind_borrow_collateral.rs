@@ -0,0 +1,437 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        bind_borrow_collateral, borrow_obligation_liquidity, refresh_obligation, refresh_reserve,
+    },
+    math::{Decimal, Rate, TryAdd, TryMul},
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+use solana_program::{instruction::Instruction, program_option::COption};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+const LOAN_TO_VALUE_RATIO: u8 = 50;
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC * INITIAL_COLLATERAL_RATIO;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = LOAN_TO_VALUE_RATIO;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            liquidity_amount: 2 * USDC_DEPOSIT_AMOUNT_FRACTIONAL / INITIAL_COLLATERAL_RATIO,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[
+                (&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS),
+                (&usdc_test_reserve, USDC_DEPOSIT_AMOUNT_FRACTIONAL),
+            ],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let refresh_instructions = [
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            sol_test_reserve.pubkey,
+            COption::Some(sol_oracle.price_pubkey),
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            COption::Some(usdc_oracle.price_pubkey),
+        ),
+        refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            vec![
+                sol_test_reserve.pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.pubkey,
+            ],
+        ),
+    ];
+    let with_refresh = |instruction: Instruction| {
+        let mut instructions = vec![instruction];
+        instructions.extend_from_slice(&refresh_instructions);
+        instructions
+    };
+
+    // cross collateral: every deposit backs the borrow
+    let mut transaction = Transaction::new_with_payer(&refresh_instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    let loan_to_value_rate = Rate::from_percent(LOAN_TO_VALUE_RATIO);
+    let sol_allowed_value = obligation.deposits[0]
+        .market_value
+        .try_mul(loan_to_value_rate)
+        .unwrap();
+    let usdc_allowed_value = obligation.deposits[1]
+        .market_value
+        .try_mul(loan_to_value_rate)
+        .unwrap();
+    let cross_allowed_borrow_value = obligation.allowed_borrow_value;
+    assert_eq!(
+        cross_allowed_borrow_value,
+        sol_allowed_value.try_add(usdc_allowed_value).unwrap()
+    );
+    assert!(obligation.borrowed_value < sol_allowed_value);
+
+    // bound to SOL: the SOL deposit only backs the borrow up to its value
+    let mut transaction = Transaction::new_with_payer(
+        &with_refresh(bind_borrow_collateral(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            user_accounts_owner.pubkey(),
+            usdc_test_reserve.pubkey,
+            COption::Some(sol_test_reserve.pubkey),
+        )),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.borrows[0].collateral_reserve,
+        COption::Some(sol_test_reserve.pubkey)
+    );
+    assert_eq!(
+        obligation.allowed_borrow_value,
+        usdc_allowed_value
+            .try_add(obligation.borrowed_value)
+            .unwrap()
+    );
+    assert!(obligation.allowed_borrow_value < cross_allowed_borrow_value);
+
+    // unbound: back to cross collateral
+    let mut transaction = Transaction::new_with_payer(
+        &with_refresh(bind_borrow_collateral(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            user_accounts_owner.pubkey(),
+            usdc_test_reserve.pubkey,
+            COption::None,
+        )),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.borrows[0].collateral_reserve, COption::None);
+    assert_eq!(obligation.allowed_borrow_value, cross_allowed_borrow_value);
+}
+
+#[tokio::test]
+async fn test_invalid_collateral() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[bind_borrow_collateral(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            user_accounts_owner.pubkey(),
+            usdc_test_reserve.pubkey,
+            COption::Some(Pubkey::new_unique()),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidObligationCollateral as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_bound_borrow_too_large() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = LOAN_TO_VALUE_RATIO;
+
+    // 10 SOL at $20 allows borrowing $100 against it alone
+    const SOL_BOUND_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BOUND_BORROW_AMOUNT_FRACTIONAL: u64 = 60 * FRACTIONAL_TO_USDC;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_BOUND_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL / INITIAL_COLLATERAL_RATIO,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL / 2,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[
+                (&sol_test_reserve, SOL_BOUND_DEPOSIT_AMOUNT_LAMPORTS),
+                (&usdc_test_reserve, USDC_DEPOSIT_AMOUNT_FRACTIONAL),
+            ],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL / 2)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let refresh_instructions = [
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            sol_test_reserve.pubkey,
+            COption::Some(sol_oracle.price_pubkey),
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            COption::Some(usdc_oracle.price_pubkey),
+        ),
+        refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            vec![
+                sol_test_reserve.pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.pubkey,
+            ],
+        ),
+    ];
+
+    let mut instructions = vec![bind_borrow_collateral(
+        port_finance_variable_rate_lending::id(),
+        test_obligation.pubkey,
+        user_accounts_owner.pubkey(),
+        usdc_test_reserve.pubkey,
+        COption::Some(sol_test_reserve.pubkey),
+    )];
+    instructions.extend_from_slice(&refresh_instructions);
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // the USDC deposit would cover the borrow, but the borrow is bound to the SOL deposit alone
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    let sol_allowed_value = obligation.deposits[0]
+        .market_value
+        .try_mul(Rate::from_percent(LOAN_TO_VALUE_RATIO))
+        .unwrap();
+    assert!(obligation.borrowed_value < sol_allowed_value);
+    let usdc_bound_borrow_value =
+        Decimal::from(USDC_BOUND_BORROW_AMOUNT_FRACTIONAL / FRACTIONAL_TO_USDC);
+    assert!(
+        obligation
+            .borrowed_value
+            .try_add(usdc_bound_borrow_value)
+            .unwrap()
+            > sol_allowed_value
+    );
+    assert!(
+        obligation
+            .borrowed_value
+            .try_add(usdc_bound_borrow_value)
+            .unwrap()
+            < obligation.allowed_borrow_value
+    );
+
+    let borrow = |bound_deposit_reserve_pubkey| {
+        let mut instructions = refresh_instructions.to_vec();
+        instructions.push(borrow_obligation_liquidity(
+            port_finance_variable_rate_lending::id(),
+            USDC_BOUND_BORROW_AMOUNT_FRACTIONAL,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            usdc_test_reserve.user_liquidity_pubkey,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.liquidity_fee_receiver_pubkey,
+            test_obligation.pubkey,
+            lending_market.pubkey,
+            test_obligation.owner,
+            Some(bound_deposit_reserve_pubkey),
+            None,
+        ));
+        let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    assert_eq!(
+        banks_client
+            .process_transaction(borrow(sol_test_reserve.pubkey))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::BorrowTooLarge as u32)
+        )
+    );
+
+    // the reserve of the bound collateral must be provided
+    assert_eq!(
+        banks_client
+            .process_transaction(borrow(usdc_test_reserve.pubkey))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
@@ -0,0 +1,140 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    instruction::{build_local_market_transactions, InitReserveAccounts, LocalMarketAccounts},
+    math::Decimal,
+    processor::process_instruction,
+    state::{LendingMarket, Reserve, INITIAL_COLLATERAL_RATIO},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    program_option::COption,
+    program_pack::Pack,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+const QUOTE_CURRENCY: [u8; 32] = *b"USD\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+
+#[tokio::test]
+async fn test_success() {
+    let test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    const MINT_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const RESERVE_AMOUNT: u64 = 100 * FRACTIONAL_TO_USDC;
+
+    let liquidity_mint_keypair = Keypair::new();
+    let user_liquidity_keypair = Keypair::new();
+    let lending_market_keypair = Keypair::new();
+    let reserve_keypair = Keypair::new();
+    let collateral_mint_keypair = Keypair::new();
+    let collateral_supply_keypair = Keypair::new();
+    let liquidity_supply_keypair = Keypair::new();
+    let liquidity_fee_receiver_keypair = Keypair::new();
+    let user_collateral_keypair = Keypair::new();
+    let user_transfer_authority_keypair = Keypair::new();
+    let accounts = LocalMarketAccounts {
+        liquidity_mint: liquidity_mint_keypair.pubkey(),
+        user_liquidity: user_liquidity_keypair.pubkey(),
+        lending_market: lending_market_keypair.pubkey(),
+        reserve: InitReserveAccounts {
+            reserve: reserve_keypair.pubkey(),
+            collateral_mint: collateral_mint_keypair.pubkey(),
+            collateral_supply: collateral_supply_keypair.pubkey(),
+            liquidity_supply: liquidity_supply_keypair.pubkey(),
+            liquidity_fee_receiver: liquidity_fee_receiver_keypair.pubkey(),
+            user_collateral: user_collateral_keypair.pubkey(),
+            user_transfer_authority: user_transfer_authority_keypair.pubkey(),
+        },
+    };
+
+    let rent = banks_client.get_rent().await.unwrap();
+    let transactions = build_local_market_transactions(
+        port_finance_variable_rate_lending::id(),
+        payer.pubkey(),
+        &rent,
+        QUOTE_CURRENCY,
+        6,
+        MINT_AMOUNT,
+        RESERVE_AMOUNT,
+        Decimal::one(),
+        TEST_RESERVE_CONFIG,
+        &accounts,
+    )
+    .unwrap();
+
+    let signers = [
+        vec![
+            &payer,
+            &liquidity_mint_keypair,
+            &user_liquidity_keypair,
+            &lending_market_keypair,
+        ],
+        vec![
+            &payer,
+            &reserve_keypair,
+            &collateral_mint_keypair,
+            &collateral_supply_keypair,
+            &user_collateral_keypair,
+        ],
+        vec![
+            &payer,
+            &liquidity_supply_keypair,
+            &liquidity_fee_receiver_keypair,
+            &user_transfer_authority_keypair,
+        ],
+    ];
+    assert_eq!(transactions.len(), signers.len());
+    for (instructions, signers) in transactions.iter().zip(signers.iter()) {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        transaction.sign(signers, recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    let lending_market = LendingMarket::unpack(
+        &banks_client
+            .get_account(accounts.lending_market)
+            .await
+            .unwrap()
+            .unwrap()
+            .data,
+    )
+    .unwrap();
+    assert_eq!(lending_market.owner, payer.pubkey());
+    assert_eq!(lending_market.quote_currency, QUOTE_CURRENCY);
+
+    let reserve = Reserve::unpack(
+        &banks_client
+            .get_account(accounts.reserve.reserve)
+            .await
+            .unwrap()
+            .unwrap()
+            .data,
+    )
+    .unwrap();
+    assert_eq!(reserve.lending_market, accounts.lending_market);
+    assert_eq!(reserve.liquidity.mint_pubkey, accounts.liquidity_mint);
+    assert_eq!(reserve.liquidity.mint_decimals, 6);
+    assert_eq!(reserve.liquidity.oracle_pubkey, COption::None);
+    assert_eq!(reserve.liquidity.market_price, Decimal::one());
+    assert_eq!(reserve.liquidity.available_amount, RESERVE_AMOUNT);
+    assert_eq!(reserve.config, TEST_RESERVE_CONFIG);
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, accounts.user_liquidity).await,
+        MINT_AMOUNT - RESERVE_AMOUNT
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, accounts.reserve.user_collateral).await,
+        RESERVE_AMOUNT * INITIAL_COLLATERAL_RATIO
+    );
+}
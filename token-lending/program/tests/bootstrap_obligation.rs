@@ -0,0 +1,300 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{bootstrap_obligation, refresh_reserve},
+    processor::process_instruction,
+    state::{Obligation, INITIAL_COLLATERAL_RATIO},
+};
+use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    system_instruction::create_account,
+    transaction::{Transaction, TransactionError},
+};
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000_000 * FRACTIONAL_TO_USDC;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+struct BootstrapAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+}
+
+fn setup() -> (ProgramTest, BootstrapAccounts) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS * INITIAL_COLLATERAL_RATIO,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    (
+        test,
+        BootstrapAccounts {
+            user_accounts_owner,
+            lending_market,
+            sol_test_reserve,
+            usdc_test_reserve,
+        },
+    )
+}
+
+fn bootstrap_instructions(
+    accounts: &BootstrapAccounts,
+    obligation_pubkey: Pubkey,
+    liquidity_amount: u64,
+    borrow_amount: u64,
+) -> Vec<Instruction> {
+    vec![
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            accounts.sol_test_reserve.pubkey,
+            accounts.sol_test_reserve.liquidity_oracle_pubkey,
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            accounts.usdc_test_reserve.pubkey,
+            accounts.usdc_test_reserve.liquidity_oracle_pubkey,
+        ),
+        bootstrap_obligation(
+            port_finance_variable_rate_lending::id(),
+            liquidity_amount,
+            borrow_amount,
+            accounts.sol_test_reserve.user_liquidity_pubkey,
+            accounts.sol_test_reserve.user_collateral_pubkey,
+            accounts.sol_test_reserve.pubkey,
+            accounts.sol_test_reserve.liquidity_supply_pubkey,
+            accounts.sol_test_reserve.collateral_mint_pubkey,
+            accounts.lending_market.pubkey,
+            accounts.sol_test_reserve.collateral_supply_pubkey,
+            obligation_pubkey,
+            accounts.user_accounts_owner.pubkey(),
+            accounts.user_accounts_owner.pubkey(),
+            accounts.usdc_test_reserve.liquidity_supply_pubkey,
+            accounts.usdc_test_reserve.user_liquidity_pubkey,
+            accounts.usdc_test_reserve.pubkey,
+            accounts.usdc_test_reserve.liquidity_fee_receiver_pubkey,
+        ),
+    ]
+}
+
+/// Creates a fresh obligation account and bootstraps it in the same transaction
+async fn bootstrap(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    accounts: &BootstrapAccounts,
+    obligation_keypair: &Keypair,
+    borrow_amount: u64,
+) -> Result<(), TransactionError> {
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut instructions = vec![create_account(
+        &payer.pubkey(),
+        &obligation_keypair.pubkey(),
+        rent.minimum_balance(Obligation::LEN),
+        Obligation::LEN as u64,
+        &port_finance_variable_rate_lending::id(),
+    )];
+    instructions.extend(bootstrap_instructions(
+        accounts,
+        obligation_keypair.pubkey(),
+        SOL_DEPOSIT_AMOUNT_LAMPORTS,
+        borrow_amount,
+    ));
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    transaction.sign(
+        &[payer, obligation_keypair, &accounts.user_accounts_owner],
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    let obligation_keypair = Keypair::new();
+    bootstrap(
+        &mut banks_client,
+        &payer,
+        &accounts,
+        &obligation_keypair,
+        USDC_BORROW_AMOUNT_FRACTIONAL,
+    )
+    .await
+    .unwrap();
+
+    let obligation_account = banks_client
+        .get_account(obligation_keypair.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let obligation = Obligation::unpack(&obligation_account.data).unwrap();
+    assert_eq!(obligation.lending_market, accounts.lending_market.pubkey);
+    assert_eq!(obligation.owner, accounts.user_accounts_owner.pubkey());
+    assert_eq!(obligation.deposits.len(), 1);
+    assert_eq!(
+        obligation.deposits[0].deposit_reserve,
+        accounts.sol_test_reserve.pubkey
+    );
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS * INITIAL_COLLATERAL_RATIO
+    );
+    assert_eq!(obligation.borrows.len(), 1);
+    assert_eq!(
+        obligation.borrows[0].borrow_reserve,
+        accounts.usdc_test_reserve.pubkey
+    );
+    assert!(obligation.borrows[0].borrowed_amount_wads >= USDC_BORROW_AMOUNT_FRACTIONAL.into());
+
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            accounts.usdc_test_reserve.user_liquidity_pubkey,
+        )
+        .await,
+        USDC_BORROW_AMOUNT_FRACTIONAL
+    );
+}
+
+#[tokio::test]
+async fn test_borrow_too_large() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    let obligation_keypair = Keypair::new();
+    assert_eq!(
+        bootstrap(
+            &mut banks_client,
+            &payer,
+            &accounts,
+            &obligation_keypair,
+            USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+        )
+        .await
+        .unwrap_err(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::BorrowTooLarge as u32)
+        )
+    );
+
+    // no partial obligation was left behind, and nothing was deposited
+    assert!(banks_client
+        .get_account(obligation_keypair.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            accounts.sol_test_reserve.user_liquidity_pubkey
+        )
+        .await,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS
+    );
+}
+
+#[tokio::test]
+async fn test_fail_already_initialized() {
+    let (mut test, accounts) = setup();
+    let test_obligation = add_obligation(
+        &mut test,
+        &accounts.lending_market,
+        &accounts.user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &bootstrap_instructions(
+            &accounts,
+            test_obligation.pubkey,
+            SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            USDC_BORROW_AMOUNT_FRACTIONAL,
+        ),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::AlreadyInitialized as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_zero_amount() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    let obligation_keypair = Keypair::new();
+    assert_eq!(
+        bootstrap(&mut banks_client, &payer, &accounts, &obligation_keypair, 0)
+            .await
+            .unwrap_err(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::InvalidAmount as u32)
+        )
+    );
+}
@@ -5,6 +5,8 @@ use std::u64;
 use solana_program_test::*;
 use solana_sdk::{
     instruction::InstructionError,
+    program_option::COption,
+    pubkey::Pubkey,
     signature::{Keypair, Signer},
     transaction::{Transaction, TransactionError},
 };
@@ -15,7 +17,7 @@ use port_finance_variable_rate_lending::{
     instruction::{borrow_obligation_liquidity, refresh_obligation},
     math::Decimal,
     processor::process_instruction,
-    state::{FeeCalculation, INITIAL_COLLATERAL_RATIO},
+    state::{FeeCalculation, ReserveFees, INITIAL_COLLATERAL_RATIO},
 };
 
 mod helpers;
@@ -111,6 +113,8 @@ async fn test_borrow_usdc_fixed_amount() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                None,
+                None,
             ),
         ],
         Some(&payer.pubkey()),
@@ -257,6 +261,8 @@ async fn test_borrow_sol_max_amount() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                None,
+                None,
             ),
         ],
         Some(&payer.pubkey()),
@@ -386,6 +392,8 @@ async fn test_borrow_too_large() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                None,
+                None,
             ),
         ],
         Some(&payer.pubkey()),
@@ -406,3 +414,646 @@ async fn test_borrow_too_large() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_borrow_updates_obligation_values() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 500 * FRACTIONAL_TO_USDC;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // the borrow is valued without another refresh, and the obligation is still healthy
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    let liquidity = &obligation.borrows[0];
+    let borrow_value = usdc_reserve
+        .liquidity_market_value(liquidity.borrowed_amount_wads)
+        .unwrap();
+    assert!(borrow_value > Decimal::zero());
+    assert_eq!(liquidity.market_value, borrow_value);
+    assert_eq!(obligation.borrowed_value, borrow_value);
+    assert!(obligation.borrowed_value <= obligation.allowed_borrow_value);
+    assert!(!obligation.is_unhealthy());
+    assert!(obligation.last_update.stale);
+}
+
+#[tokio::test]
+async fn test_borrow_at_borrow_limit() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // borrowing the fee-exclusive amount adds a fee of 100, landing exactly on the allowed
+    // borrow value of 100 SOL at 50% loan to value
+    const USDC_TOTAL_BORROW_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const FEE_AMOUNT: u64 = 100;
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = USDC_TOTAL_BORROW_FRACTIONAL - FEE_AMOUNT;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_TOTAL_BORROW_FRACTIONAL;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let borrow = |liquidity_amount| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                refresh_obligation(
+                    port_finance_variable_rate_lending::id(),
+                    test_obligation.pubkey,
+                    vec![sol_test_reserve.pubkey],
+                ),
+                borrow_obligation_liquidity(
+                    port_finance_variable_rate_lending::id(),
+                    liquidity_amount,
+                    usdc_test_reserve.liquidity_supply_pubkey,
+                    usdc_test_reserve.user_liquidity_pubkey,
+                    usdc_test_reserve.pubkey,
+                    usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                    test_obligation.pubkey,
+                    lending_market.pubkey,
+                    test_obligation.owner,
+                    None,
+                    None,
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    // one unit beyond the limit is rejected
+    assert_eq!(
+        banks_client
+            .process_transaction(borrow(USDC_BORROW_AMOUNT_FRACTIONAL + 1))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::BorrowTooLarge as u32)
+        )
+    );
+
+    banks_client
+        .process_transaction(borrow(USDC_BORROW_AMOUNT_FRACTIONAL))
+        .await
+        .unwrap();
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        Decimal::from(USDC_TOTAL_BORROW_FRACTIONAL)
+    );
+}
+
+#[tokio::test]
+async fn test_borrow_max_borrow_amount() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 10_000 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    reserve_config.fees = ReserveFees {
+        borrow_fee_wad: 10_000_000_000_000_000, // 1%
+        host_fee_percentage: 20,
+        ..reserve_config.fees
+    };
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            vec![sol_test_reserve.pubkey],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    let max_borrow_amount = test_obligation
+        .get_state(&mut banks_client)
+        .await
+        .max_borrow_amount(
+            usdc_reserve.liquidity.market_price,
+            usdc_reserve.liquidity.mint_decimals,
+            &usdc_reserve.config.fees,
+        )
+        .unwrap();
+    assert!(max_borrow_amount > 0);
+
+    let borrow = |liquidity_amount| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                refresh_obligation(
+                    port_finance_variable_rate_lending::id(),
+                    test_obligation.pubkey,
+                    vec![sol_test_reserve.pubkey],
+                ),
+                borrow_obligation_liquidity(
+                    port_finance_variable_rate_lending::id(),
+                    liquidity_amount,
+                    usdc_test_reserve.liquidity_supply_pubkey,
+                    usdc_test_reserve.user_liquidity_pubkey,
+                    usdc_test_reserve.pubkey,
+                    usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                    test_obligation.pubkey,
+                    lending_market.pubkey,
+                    test_obligation.owner,
+                    None,
+                    None,
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    // the fee on one more unit takes the borrow past the allowed borrow value
+    assert_eq!(
+        banks_client
+            .process_transaction(borrow(max_borrow_amount + 1))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::BorrowTooLarge as u32)
+        )
+    );
+
+    banks_client
+        .process_transaction(borrow(max_borrow_amount))
+        .await
+        .unwrap();
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.borrowed_value <= obligation.allowed_borrow_value);
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await,
+        max_borrow_amount
+    );
+}
+
+#[tokio::test]
+async fn test_borrow_wrong_fee_receiver() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                Pubkey::new_unique(),
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+
+    // check that transaction fails
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::WrongFeeReceiver as u32)
+        )
+    );
+}
+
+enum StakeAccountArg {
+    NoStakeAccount,
+    DiscountPool,
+    DiscountPoolBelowMinStake,
+    OtherPool,
+}
+
+/// Least stake in the discount staking pool for the borrow fee discount
+const BORROW_FEE_DISCOUNT_MIN_STAKE: u64 = 1_000;
+
+/// Borrow 1000 USDC with a 1% borrow fee and a 50% discount for stakers of at least
+/// `BORROW_FEE_DISCOUNT_MIN_STAKE`, returning the fee paid
+async fn borrow_fee_with_stake_account(stake_account_arg: StakeAccountArg) -> u64 {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let discount_staking_pool = add_staking_pool(&mut test, Pubkey::new_unique(), 0);
+    let other_staking_pool = add_staking_pool(&mut test, Pubkey::new_unique(), 0);
+    let stake_account = match stake_account_arg {
+        StakeAccountArg::NoStakeAccount => None,
+        StakeAccountArg::DiscountPool => Some(add_stake_account(
+            &mut test,
+            discount_staking_pool.staking_pool_pubkey,
+            &user_accounts_owner,
+            BORROW_FEE_DISCOUNT_MIN_STAKE,
+        )),
+        StakeAccountArg::DiscountPoolBelowMinStake => Some(add_stake_account(
+            &mut test,
+            discount_staking_pool.staking_pool_pubkey,
+            &user_accounts_owner,
+            1,
+        )),
+        StakeAccountArg::OtherPool => Some(add_stake_account(
+            &mut test,
+            other_staking_pool.staking_pool_pubkey,
+            &user_accounts_owner,
+            1,
+        )),
+    };
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    reserve_config.fees = ReserveFees {
+        borrow_fee_wad: 10_000_000_000_000_000, // 1%
+        host_fee_percentage: 0,
+        ..reserve_config.fees
+    };
+    reserve_config.borrow_fee_discount_staking_pool =
+        COption::Some(discount_staking_pool.staking_pool_pubkey);
+    reserve_config.borrow_fee_discount_percentage = 50;
+    reserve_config.borrow_fee_discount_min_stake = BORROW_FEE_DISCOUNT_MIN_STAKE;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                None,
+                stake_account.map(|stake_account| stake_account.pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    get_token_balance(
+        &mut banks_client,
+        usdc_test_reserve.liquidity_fee_receiver_pubkey,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_borrow_fee_no_stake_account() {
+    let fee = borrow_fee_with_stake_account(StakeAccountArg::NoStakeAccount).await;
+    assert_eq!(fee, 10 * FRACTIONAL_TO_USDC);
+}
+
+#[tokio::test]
+async fn test_borrow_fee_discount_for_staker() {
+    let fee = borrow_fee_with_stake_account(StakeAccountArg::DiscountPool).await;
+    assert_eq!(fee, 5 * FRACTIONAL_TO_USDC);
+}
+
+#[tokio::test]
+async fn test_borrow_fee_stake_below_min_stake() {
+    let fee = borrow_fee_with_stake_account(StakeAccountArg::DiscountPoolBelowMinStake).await;
+    assert_eq!(fee, 10 * FRACTIONAL_TO_USDC);
+}
+
+#[tokio::test]
+async fn test_borrow_fee_non_qualifying_stake_account() {
+    let fee = borrow_fee_with_stake_account(StakeAccountArg::OtherPool).await;
+    assert_eq!(fee, 10 * FRACTIONAL_TO_USDC);
+}
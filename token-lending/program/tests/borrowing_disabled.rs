@@ -0,0 +1,172 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{borrow_obligation_liquidity, deposit_reserve_liquidity, refresh_obligation},
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+
+mod helpers;
+
+#[tokio::test]
+async fn test_borrowing_disabled() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            user_liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: ReserveConfig {
+                borrowing_disabled: true,
+                ..TEST_RESERVE_CONFIG
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the collateral only asset cannot be borrowed
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                LAMPORTS_TO_SOL,
+                sol_test_reserve.liquidity_supply_pubkey,
+                sol_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::BorrowingDisabled as u32)
+        )
+    );
+
+    // deposits into it still work
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_reserve_liquidity(
+            port_finance_variable_rate_lending::id(),
+            SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            sol_test_reserve.user_liquidity_pubkey,
+            sol_test_reserve.user_collateral_pubkey,
+            sol_test_reserve.pubkey,
+            sol_test_reserve.liquidity_supply_pubkey,
+            sol_test_reserve.collateral_mint_pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // and it can back borrows from other reserves
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.borrows.len(), 1);
+    assert_eq!(
+        obligation.borrows[0].borrow_reserve,
+        usdc_test_reserve.pubkey
+    );
+}
@@ -0,0 +1,312 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, deposit_obligation_collateral, deposit_reserve_liquidity,
+        redeem_reserve_collateral, refresh_obligation, refresh_reserve,
+    },
+    math::Decimal,
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+
+mod helpers;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const SOL_BORROWED_AMOUNT_LAMPORTS: u64 = 50 * LAMPORTS_TO_SOL;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+struct CollateralDisabledAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+/// A SOL reserve with collateral disabled and outstanding borrows, so it pays its suppliers
+/// interest, and an obligation that deposited into it before collateral was disabled
+fn setup() -> (ProgramTest, CollateralDisabledAccounts) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            user_liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            borrow_amount: SOL_BORROWED_AMOUNT_LAMPORTS,
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: ReserveConfig {
+                collateral_disabled: true,
+                ..TEST_RESERVE_CONFIG
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        CollateralDisabledAccounts {
+            user_accounts_owner,
+            lending_market,
+            sol_test_reserve,
+            usdc_test_reserve,
+            test_obligation,
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_fail_borrow_against_disabled_collateral() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                accounts.test_obligation.pubkey,
+                vec![accounts.sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                accounts.usdc_test_reserve.liquidity_supply_pubkey,
+                accounts.usdc_test_reserve.user_liquidity_pubkey,
+                accounts.usdc_test_reserve.pubkey,
+                accounts.usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                accounts.test_obligation.pubkey,
+                accounts.lending_market.pubkey,
+                accounts.test_obligation.owner,
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::BorrowTooLarge as u32)
+        )
+    );
+
+    // the deposit is still valued, it just can't be borrowed against
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            accounts.test_obligation.pubkey,
+            vec![accounts.sol_test_reserve.pubkey],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.deposited_value > Decimal::zero());
+    assert_eq!(obligation.allowed_borrow_value, Decimal::zero());
+}
+
+#[tokio::test]
+async fn test_fail_deposit_obligation_collateral() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_obligation_collateral(
+            port_finance_variable_rate_lending::id(),
+            SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            accounts.sol_test_reserve.user_collateral_pubkey,
+            accounts.sol_test_reserve.collateral_supply_pubkey,
+            accounts.sol_test_reserve.pubkey,
+            accounts.test_obligation.pubkey,
+            accounts.lending_market.pubkey,
+            accounts.test_obligation.owner,
+            accounts.user_accounts_owner.pubkey(),
+            None,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ReserveCollateralDisabled as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_supply_earns_interest_and_redeems() {
+    let (test, accounts) = setup();
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(2).unwrap();
+    let sol_test_reserve = &accounts.sol_test_reserve;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                sol_test_reserve.pubkey,
+                sol_test_reserve.liquidity_oracle_pubkey,
+            ),
+            deposit_reserve_liquidity(
+                port_finance_variable_rate_lending::id(),
+                SOL_DEPOSIT_AMOUNT_LAMPORTS,
+                sol_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.liquidity_supply_pubkey,
+                sol_test_reserve.collateral_mint_pubkey,
+                accounts.lending_market.pubkey,
+                accounts.user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &accounts.user_accounts_owner],
+        test_context.last_blockhash,
+    );
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let collateral_amount = get_token_balance(
+        &mut test_context.banks_client,
+        sol_test_reserve.user_collateral_pubkey,
+    )
+    .await;
+
+    test_context.warp_to_slot(1_000).unwrap();
+    let recent_blockhash = test_context
+        .banks_client
+        .get_latest_blockhash()
+        .await
+        .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                sol_test_reserve.pubkey,
+                sol_test_reserve.liquidity_oracle_pubkey,
+            ),
+            redeem_reserve_collateral(
+                port_finance_variable_rate_lending::id(),
+                collateral_amount,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.collateral_mint_pubkey,
+                sol_test_reserve.liquidity_supply_pubkey,
+                accounts.lending_market.pubkey,
+                accounts.user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &accounts.user_accounts_owner],
+        recent_blockhash,
+    );
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // the collateral redeems for more liquidity than was supplied
+    let reserve = sol_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert!(
+        reserve
+            .collateral_exchange_rate()
+            .unwrap()
+            .collateral_to_liquidity(SOL_DEPOSIT_AMOUNT_LAMPORTS)
+            .unwrap()
+            > SOL_DEPOSIT_AMOUNT_LAMPORTS / INITIAL_COLLATERAL_RATIO
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut test_context.banks_client,
+            sol_test_reserve.user_collateral_pubkey
+        )
+        .await,
+        0
+    );
+    assert!(
+        get_token_balance(
+            &mut test_context.banks_client,
+            sol_test_reserve.user_liquidity_pubkey
+        )
+        .await
+            > SOL_DEPOSIT_AMOUNT_LAMPORTS
+    );
+}
@@ -0,0 +1,175 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    instruction::{
+        borrow_obligation_liquidity, redeem_reserve_collateral, refresh_obligation, refresh_reserve,
+    },
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token::instruction::approve;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_REDEEM_COLLATERAL_AMOUNT: u64 = 50 * FRACTIONAL_TO_USDC * INITIAL_COLLATERAL_RATIO;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+#[tokio::test]
+async fn test_deposit_redeem_and_borrow() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    // the collateral mint supply matches the collateral minted for the reserve's liquidity
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            user_liquidity_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    usdc_test_reserve
+        .validate_collateral_supply(&mut banks_client)
+        .await;
+
+    lending_market
+        .deposit(
+            &mut banks_client,
+            &user_accounts_owner,
+            &payer,
+            &usdc_test_reserve,
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+        )
+        .await;
+    usdc_test_reserve
+        .validate_collateral_supply(&mut banks_client)
+        .await;
+
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserve.user_collateral_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                USDC_REDEEM_COLLATERAL_AMOUNT,
+            )
+            .unwrap(),
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_oracle_pubkey,
+            ),
+            redeem_reserve_collateral(
+                port_finance_variable_rate_lending::id(),
+                USDC_REDEEM_COLLATERAL_AMOUNT,
+                usdc_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                lending_market.pubkey,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+    usdc_test_reserve
+        .validate_collateral_supply(&mut banks_client)
+        .await;
+
+    // borrowing moves liquidity without minting or burning collateral
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_oracle_pubkey,
+            ),
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+    usdc_test_reserve
+        .validate_collateral_supply(&mut banks_client)
+        .await;
+}
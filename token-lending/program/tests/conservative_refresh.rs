@@ -0,0 +1,498 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, liquidate_obligation, refresh_obligation,
+        refresh_obligation_conservative, refresh_reserve, repay_obligation_liquidity,
+        set_allow_conservative_refresh,
+    },
+    math::{Decimal, Rate, TryAdd, TryMul},
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+const SOL_DEPOSIT_AMOUNT: u64 = 10;
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 =
+    SOL_DEPOSIT_AMOUNT * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+// $150, within the $200 allowed against both deposits but not the $100 against the healthy one
+const USDC_EXTRA_BORROW_AMOUNT_FRACTIONAL: u64 = 150 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+struct ConservativeRefreshAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    broken_sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+/// An obligation borrowing USDC against two SOL deposits, one of which is in a reserve that can't
+/// be refreshed, as if its oracle were broken
+fn setup(usdc_borrow_amount: u64) -> (ProgramTest, ConservativeRefreshAccounts) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+    let broken_sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: usdc_borrow_amount,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[
+                (&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS),
+                (&broken_sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS),
+            ],
+            borrows: &[(&usdc_test_reserve, usdc_borrow_amount)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        ConservativeRefreshAccounts {
+            user_accounts_owner,
+            lending_market,
+            sol_test_reserve,
+            broken_sol_test_reserve,
+            usdc_test_reserve,
+            test_obligation,
+        },
+    )
+}
+
+fn reserve_pubkeys(accounts: &ConservativeRefreshAccounts) -> Vec<Pubkey> {
+    vec![
+        accounts.sol_test_reserve.pubkey,
+        accounts.broken_sol_test_reserve.pubkey,
+        accounts.usdc_test_reserve.pubkey,
+    ]
+}
+
+fn allow_instruction(accounts: &ConservativeRefreshAccounts) -> Instruction {
+    set_allow_conservative_refresh(
+        port_finance_variable_rate_lending::id(),
+        accounts.lending_market.pubkey,
+        accounts.lending_market.owner.pubkey(),
+        true,
+    )
+}
+
+fn refresh_instruction(accounts: &ConservativeRefreshAccounts) -> Instruction {
+    refresh_obligation_conservative(
+        port_finance_variable_rate_lending::id(),
+        accounts.test_obligation.pubkey,
+        reserve_pubkeys(accounts),
+        accounts.lending_market.pubkey,
+        accounts.test_obligation.owner,
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (test, accounts) = setup(USDC_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[allow_instruction(&accounts), refresh_instruction(&accounts)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let lending_market = accounts.lending_market.get_state(&mut banks_client).await;
+    assert!(lending_market.allow_conservative_refresh);
+
+    // only the healthy deposit counts towards the obligation's value
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    let healthy_value = obligation.deposits[0].market_value;
+    assert!(healthy_value > Decimal::zero());
+    assert_eq!(obligation.deposits[1].market_value, Decimal::zero());
+    assert_eq!(obligation.deposited_value, healthy_value);
+    assert_eq!(
+        obligation.allowed_borrow_value,
+        healthy_value
+            .try_mul(Rate::from_percent(TEST_RESERVE_CONFIG.loan_to_value_ratio))
+            .unwrap()
+    );
+    assert!(obligation.borrowed_value > Decimal::zero());
+}
+
+#[tokio::test]
+async fn test_full_refresh_after_conservative_refresh() {
+    let (test, accounts) = setup(USDC_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // a conservative refresh can't leave the broken deposit valued at zero for the rest of the
+    // slot, a full refresh once its reserve is refreshed values it again
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            allow_instruction(&accounts),
+            refresh_instruction(&accounts),
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                accounts.broken_sol_test_reserve.pubkey,
+                accounts.broken_sol_test_reserve.liquidity_oracle_pubkey,
+            ),
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                accounts.test_obligation.pubkey,
+                reserve_pubkeys(&accounts),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.deposits[1].market_value > Decimal::zero());
+    assert_eq!(
+        obligation.deposited_value,
+        obligation.deposits[0]
+            .market_value
+            .try_add(obligation.deposits[1].market_value)
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_fail_refresh_again_with_wrong_reserves() {
+    let (test, accounts) = setup(USDC_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // a refresh that is a no-op after a full refresh in the same slot still checks its reserves
+    let mut wrong_reserve_pubkeys = reserve_pubkeys(&accounts);
+    wrong_reserve_pubkeys.swap(0, 1);
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                accounts.broken_sol_test_reserve.pubkey,
+                accounts.broken_sol_test_reserve.liquidity_oracle_pubkey,
+            ),
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                accounts.test_obligation.pubkey,
+                reserve_pubkeys(&accounts),
+            ),
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                accounts.test_obligation.pubkey,
+                wrong_reserve_pubkeys,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_without_conservative_refresh() {
+    let (test, accounts) = setup(USDC_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // a regular refresh needs every reserve refreshed
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            accounts.test_obligation.pubkey,
+            reserve_pubkeys(&accounts),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ReserveStale as u32)
+        )
+    );
+
+    // a conservative refresh needs the lending market to allow it
+    let mut transaction =
+        Transaction::new_with_payer(&[refresh_instruction(&accounts)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ConservativeRefreshDisabled as u32)
+        )
+    );
+
+    // and only the obligation owner can choose it
+    let not_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            allow_instruction(&accounts),
+            refresh_obligation_conservative(
+                port_finance_variable_rate_lending::id(),
+                accounts.test_obligation.pubkey,
+                reserve_pubkeys(&accounts),
+                accounts.lending_market.pubkey,
+                not_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &accounts.lending_market.owner, &not_owner],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidObligationOwner as u32)
+        )
+    );
+
+    accounts
+        .test_obligation
+        .validate_state(&mut banks_client)
+        .await;
+}
+
+#[tokio::test]
+async fn test_borrow_against_zero_valued_collateral() {
+    let (test, accounts) = setup(USDC_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            allow_instruction(&accounts),
+            refresh_instruction(&accounts),
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                USDC_EXTRA_BORROW_AMOUNT_FRACTIONAL,
+                accounts.usdc_test_reserve.liquidity_supply_pubkey,
+                accounts.usdc_test_reserve.user_liquidity_pubkey,
+                accounts.usdc_test_reserve.pubkey,
+                accounts.usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                accounts.test_obligation.pubkey,
+                accounts.lending_market.pubkey,
+                accounts.test_obligation.owner,
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::BorrowTooLarge as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_repay() {
+    let (test, accounts) = setup(USDC_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            allow_instruction(&accounts),
+            refresh_instruction(&accounts),
+            repay_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                accounts.usdc_test_reserve.user_liquidity_pubkey,
+                accounts.usdc_test_reserve.liquidity_supply_pubkey,
+                accounts.usdc_test_reserve.pubkey,
+                accounts.test_obligation.pubkey,
+                accounts.lending_market.pubkey,
+                accounts.user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.borrows.is_empty());
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            accounts.usdc_test_reserve.user_liquidity_pubkey
+        )
+        .await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_fail_liquidate_after_conservative_refresh() {
+    // unhealthy once the broken deposit is valued at zero
+    let (test, accounts) = setup(USDC_EXTRA_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            allow_instruction(&accounts),
+            refresh_instruction(&accounts),
+            liquidate_obligation(
+                port_finance_variable_rate_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                accounts.usdc_test_reserve.user_liquidity_pubkey,
+                accounts.sol_test_reserve.user_collateral_pubkey,
+                accounts.usdc_test_reserve.pubkey,
+                accounts.usdc_test_reserve.liquidity_supply_pubkey,
+                accounts.sol_test_reserve.pubkey,
+                accounts.sol_test_reserve.collateral_supply_pubkey,
+                accounts.test_obligation.pubkey,
+                accounts.lending_market.pubkey,
+                accounts.user_accounts_owner.pubkey(),
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::ObligationStale as u32)
+        )
+    );
+}
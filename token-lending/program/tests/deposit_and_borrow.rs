@@ -0,0 +1,288 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{deposit_and_borrow, refresh_reserve, set_strict_instruction_order},
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+use solana_program::instruction::Instruction;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000_000 * FRACTIONAL_TO_USDC;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+struct DepositAndBorrowAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+fn setup() -> (ProgramTest, DepositAndBorrowAccounts) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS * INITIAL_COLLATERAL_RATIO,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+
+    (
+        test,
+        DepositAndBorrowAccounts {
+            user_accounts_owner,
+            lending_market,
+            sol_test_reserve,
+            usdc_test_reserve,
+            test_obligation,
+        },
+    )
+}
+
+fn deposit_and_borrow_instructions(
+    accounts: &DepositAndBorrowAccounts,
+    borrow_amount: u64,
+) -> Vec<Instruction> {
+    vec![
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            accounts.sol_test_reserve.pubkey,
+            accounts.sol_test_reserve.liquidity_oracle_pubkey,
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            accounts.usdc_test_reserve.pubkey,
+            accounts.usdc_test_reserve.liquidity_oracle_pubkey,
+        ),
+        deposit_and_borrow(
+            port_finance_variable_rate_lending::id(),
+            SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            borrow_amount,
+            accounts.sol_test_reserve.user_liquidity_pubkey,
+            accounts.sol_test_reserve.user_collateral_pubkey,
+            accounts.sol_test_reserve.pubkey,
+            accounts.sol_test_reserve.liquidity_supply_pubkey,
+            accounts.sol_test_reserve.collateral_mint_pubkey,
+            accounts.lending_market.pubkey,
+            accounts.sol_test_reserve.collateral_supply_pubkey,
+            accounts.test_obligation.pubkey,
+            accounts.user_accounts_owner.pubkey(),
+            accounts.user_accounts_owner.pubkey(),
+            accounts.usdc_test_reserve.liquidity_supply_pubkey,
+            accounts.usdc_test_reserve.user_liquidity_pubkey,
+            accounts.usdc_test_reserve.pubkey,
+            accounts.usdc_test_reserve.liquidity_fee_receiver_pubkey,
+            vec![accounts.sol_test_reserve.pubkey],
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &deposit_and_borrow_instructions(&accounts, USDC_BORROW_AMOUNT_FRACTIONAL),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.deposits.len(), 1);
+    assert_eq!(
+        obligation.deposits[0].deposit_reserve,
+        accounts.sol_test_reserve.pubkey
+    );
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS * INITIAL_COLLATERAL_RATIO
+    );
+    assert_eq!(obligation.borrows.len(), 1);
+    assert_eq!(
+        obligation.borrows[0].borrow_reserve,
+        accounts.usdc_test_reserve.pubkey
+    );
+
+    let user_liquidity_balance = get_token_balance(
+        &mut banks_client,
+        accounts.usdc_test_reserve.user_liquidity_pubkey,
+    )
+    .await;
+    assert!(user_liquidity_balance > 0);
+}
+
+#[tokio::test]
+async fn test_borrow_too_large() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &deposit_and_borrow_instructions(&accounts, USDC_RESERVE_LIQUIDITY_FRACTIONAL),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::BorrowTooLarge as u32)
+        )
+    );
+
+    // nothing was deposited
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.deposits.is_empty());
+    assert!(obligation.borrows.is_empty());
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            accounts.sol_test_reserve.user_liquidity_pubkey
+        )
+        .await,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS
+    );
+}
+
+/// Enable strict ordering, so that the reserves passed to `DepositAndBorrow` must be refreshed
+/// immediately before it.
+fn strict_deposit_and_borrow_instructions(accounts: &DepositAndBorrowAccounts) -> Vec<Instruction> {
+    let mut instructions = vec![set_strict_instruction_order(
+        port_finance_variable_rate_lending::id(),
+        accounts.lending_market.pubkey,
+        accounts.lending_market.owner.pubkey(),
+        true,
+    )];
+    instructions.extend(deposit_and_borrow_instructions(
+        accounts,
+        USDC_BORROW_AMOUNT_FRACTIONAL,
+    ));
+    instructions
+}
+
+#[tokio::test]
+async fn test_strict_instruction_order() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // refresh the borrow reserve first, so the deposit reserve passed to the composite is
+    // refreshed immediately before it
+    let mut instructions = strict_deposit_and_borrow_instructions(&accounts);
+    instructions.swap(1, 2);
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.deposits.len(), 1);
+    assert_eq!(obligation.borrows.len(), 1);
+    assert!(
+        get_token_balance(
+            &mut banks_client,
+            accounts.usdc_test_reserve.user_liquidity_pubkey,
+        )
+        .await
+            > 0
+    );
+}
+
+#[tokio::test]
+async fn test_strict_instruction_order_interleaved_refresh() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the borrow reserve refresh sits between the deposit reserve refresh and the composite
+    let mut transaction = Transaction::new_with_payer(
+        &strict_deposit_and_borrow_instructions(&accounts),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::UnexpectedInstructionOrder as u32)
+        )
+    );
+}
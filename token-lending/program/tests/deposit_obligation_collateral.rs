@@ -634,7 +634,7 @@ async fn test_deposit_and_collateral_success_with_liquidity_reward_fail() {
             .unwrap(),
         TransactionError::InstructionError(
             2,
-            InstructionError::Custom(LendingError::InvalidStakingPool as u32),
+            InstructionError::Custom(LendingError::MissingStakingAccounts as u32),
         )
     );
 }
@@ -1215,7 +1215,520 @@ async fn test_fail_with_liquidity_reward2() {
             .unwrap(),
         TransactionError::InstructionError(
             1,
-            InstructionError::Custom(LendingError::InvalidStakingPool as u32),
+            InstructionError::Custom(LendingError::MissingStakingAccounts as u32),
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_stale_reserve() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the collateral can't be valued without a refreshed exchange rate
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_obligation_collateral(
+            port_finance_variable_rate_lending::id(),
+            SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            sol_test_reserve.user_collateral_pubkey,
+            sol_test_reserve.collateral_supply_pubkey,
+            sol_test_reserve.pubkey,
+            test_obligation.pubkey,
+            lending_market.pubkey,
+            test_obligation.owner,
+            user_transfer_authority.pubkey(),
+            None,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &vec![&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ReserveStale as u32)
+        )
+    );
+
+    // the combined instruction doesn't refresh the reserve either
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_reserve_liquidity_and_obligation_collateral(
+            port_finance_variable_rate_lending::id(),
+            SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            sol_test_reserve.user_liquidity_pubkey,
+            sol_test_reserve.user_collateral_pubkey,
+            sol_test_reserve.pubkey,
+            sol_test_reserve.liquidity_supply_pubkey,
+            sol_test_reserve.collateral_mint_pubkey,
+            sol_test_reserve.lending_market_pubkey,
+            sol_test_reserve.collateral_supply_pubkey,
+            test_obligation.pubkey,
+            test_obligation.owner,
+            user_transfer_authority.pubkey(),
+            None,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &vec![&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ReserveStale as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_missing_staking_accounts() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+
+    let lending_market = add_lending_market(&mut test);
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market.pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &port_finance_variable_rate_lending::id(),
+    );
+    let staking_pool = add_staking_pool(&mut test, lending_market_authority_pubkey, 0);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: ReserveConfig {
+                deposit_staking_pool: COption::Some(staking_pool.staking_pool_pubkey),
+                ..TEST_RESERVE_CONFIG
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the reserve has a staking pool, so the deposit must not skip the rewards
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_obligation_collateral(
+            port_finance_variable_rate_lending::id(),
+            SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            sol_test_reserve.user_collateral_pubkey,
+            sol_test_reserve.collateral_supply_pubkey,
+            sol_test_reserve.pubkey,
+            test_obligation.pubkey,
+            lending_market.pubkey,
+            test_obligation.owner,
+            user_transfer_authority.pubkey(),
+            None,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::MissingStakingAccounts as u32)
         )
     );
+
+    test_obligation.validate_state(&mut banks_client).await;
+}
+
+#[tokio::test]
+async fn test_deposit_and_collateral_fail_missing_staking_accounts() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+
+    let lending_market = add_lending_market(&mut test);
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market.pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &port_finance_variable_rate_lending::id(),
+    );
+    let staking_pool = add_staking_pool(&mut test, lending_market_authority_pubkey, 0);
+    let stake_account: TestStakeAccount = add_stake_account(
+        &mut test,
+        staking_pool.staking_pool_pubkey,
+        &user_accounts_owner,
+        0,
+    );
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: ReserveConfig {
+                deposit_staking_pool: COption::Some(staking_pool.staking_pool_pubkey),
+                ..TEST_RESERVE_CONFIG
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let deposit = |option_stake_account_pubkey, option_staking_pool_pubkey| {
+        deposit_reserve_liquidity_and_obligation_collateral(
+            port_finance_variable_rate_lending::id(),
+            SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            sol_test_reserve.user_liquidity_pubkey,
+            sol_test_reserve.user_collateral_pubkey,
+            sol_test_reserve.pubkey,
+            sol_test_reserve.liquidity_supply_pubkey,
+            sol_test_reserve.collateral_mint_pubkey,
+            sol_test_reserve.lending_market_pubkey,
+            sol_test_reserve.collateral_supply_pubkey,
+            test_obligation.pubkey,
+            test_obligation.owner,
+            user_transfer_authority.pubkey(),
+            option_stake_account_pubkey,
+            option_staking_pool_pubkey,
+        )
+    };
+
+    // the stake account alone, without its staking pool and the staking program
+    let mut partial_staking_accounts = deposit(
+        Some(stake_account.pubkey),
+        Some(staking_pool.staking_pool_pubkey),
+    );
+    partial_staking_accounts.accounts.truncate(14);
+
+    // the reserve has a staking pool, so the deposit must not skip the rewards
+    for instruction in [deposit(None, None), partial_staking_accounts] {
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(
+            &[&payer, &user_accounts_owner, &user_transfer_authority],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(LendingError::MissingStakingAccounts as u32)
+            )
+        );
+    }
+
+    sol_test_reserve.validate_state(&mut banks_client).await;
+    test_obligation.validate_state(&mut banks_client).await;
+}
+
+#[tokio::test]
+async fn test_fail_unexpected_staking_accounts() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+
+    let lending_market = add_lending_market(&mut test);
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market.pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &port_finance_variable_rate_lending::id(),
+    );
+    let staking_pool = add_staking_pool(&mut test, lending_market_authority_pubkey, 0);
+    let stake_account: TestStakeAccount = add_stake_account(
+        &mut test,
+        staking_pool.staking_pool_pubkey,
+        &user_accounts_owner,
+        0,
+    );
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the reserve has no staking pool, so stake accounts must not be passed in
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_obligation_collateral(
+            port_finance_variable_rate_lending::id(),
+            SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            sol_test_reserve.user_collateral_pubkey,
+            sol_test_reserve.collateral_supply_pubkey,
+            sol_test_reserve.pubkey,
+            test_obligation.pubkey,
+            lending_market.pubkey,
+            test_obligation.owner,
+            user_transfer_authority.pubkey(),
+            Some(stake_account.pubkey),
+            Some(staking_pool.staking_pool_pubkey),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidStakingPool as u32)
+        )
+    );
+
+    test_obligation.validate_state(&mut banks_client).await;
+}
+
+#[tokio::test]
+async fn test_deposit_and_collateral_fail_stake_account_mismatch() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+
+    let lending_market = add_lending_market(&mut test);
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market.pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &port_finance_variable_rate_lending::id(),
+    );
+    let staking_pool = add_staking_pool(&mut test, lending_market_authority_pubkey, 0);
+    let other_staking_pool = add_staking_pool(&mut test, lending_market_authority_pubkey, 0);
+
+    let staking_acc_owner = Keypair::new();
+    let others_stake_account: TestStakeAccount = add_stake_account(
+        &mut test,
+        staking_pool.staking_pool_pubkey,
+        &staking_acc_owner,
+        0,
+    );
+    let other_pool_stake_account: TestStakeAccount = add_stake_account(
+        &mut test,
+        other_staking_pool.staking_pool_pubkey,
+        &user_accounts_owner,
+        0,
+    );
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: ReserveConfig {
+                deposit_staking_pool: COption::Some(staking_pool.staking_pool_pubkey),
+                ..TEST_RESERVE_CONFIG
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // (stake account, staking pool, expected error), all rejected before any tokens move
+    let cases = [
+        (
+            others_stake_account.pubkey,
+            staking_pool.staking_pool_pubkey,
+            LendingError::InvalidStakeAccount,
+        ),
+        (
+            other_pool_stake_account.pubkey,
+            staking_pool.staking_pool_pubkey,
+            LendingError::StakeAccountPoolMismatch,
+        ),
+        (
+            other_pool_stake_account.pubkey,
+            other_staking_pool.staking_pool_pubkey,
+            LendingError::InvalidStakingPool,
+        ),
+    ];
+    for (stake_account_pubkey, staking_pool_pubkey, error) in cases {
+        let mut transaction = Transaction::new_with_payer(
+            &[deposit_reserve_liquidity_and_obligation_collateral(
+                port_finance_variable_rate_lending::id(),
+                SOL_DEPOSIT_AMOUNT_LAMPORTS,
+                sol_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.liquidity_supply_pubkey,
+                sol_test_reserve.collateral_mint_pubkey,
+                sol_test_reserve.lending_market_pubkey,
+                sol_test_reserve.collateral_supply_pubkey,
+                test_obligation.pubkey,
+                test_obligation.owner,
+                user_transfer_authority.pubkey(),
+                Some(stake_account_pubkey),
+                Some(staking_pool_pubkey),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &[&payer, &user_accounts_owner, &user_transfer_authority],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+
+    sol_test_reserve.validate_state(&mut banks_client).await;
+    test_obligation.validate_state(&mut banks_client).await;
 }
@@ -1,10 +1,17 @@
 #![cfg(feature = "test-bpf")]
 
 use solana_program_test::*;
-use solana_sdk::signature::Keypair;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
 
 use helpers::*;
-use port_finance_variable_rate_lending::processor::process_instruction;
+use port_finance_variable_rate_lending::{
+    error::LendingError, instruction::deposit_reserve_liquidity, processor::process_instruction,
+};
+use spl_token::instruction::approve;
 
 mod helpers;
 
@@ -65,3 +72,144 @@ async fn test_success() {
         init_liquidity + 100 * FRACTIONAL_TO_USDC
     )
 }
+
+#[tokio::test]
+async fn test_read_only_reserve() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+            liquidity_amount: 10_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut instruction = deposit_reserve_liquidity(
+        port_finance_variable_rate_lending::id(),
+        100 * FRACTIONAL_TO_USDC,
+        usdc_test_reserve.user_liquidity_pubkey,
+        usdc_test_reserve.user_collateral_pubkey,
+        usdc_test_reserve.pubkey,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.collateral_mint_pubkey,
+        lending_market.pubkey,
+        user_accounts_owner.pubkey(),
+    );
+    let reserve_meta = instruction
+        .accounts
+        .iter_mut()
+        .find(|meta| meta.pubkey == usdc_test_reserve.pubkey)
+        .unwrap();
+    reserve_meta.is_writable = false;
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_insufficient_approval() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+            liquidity_amount: 10_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the transfer authority is approved for less than the deposit
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserve.user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                10 * FRACTIONAL_TO_USDC,
+            )
+            .unwrap(),
+            deposit_reserve_liquidity(
+                port_finance_variable_rate_lending::id(),
+                100 * FRACTIONAL_TO_USDC,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                lending_market.pubkey,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+
+    // the token program's insufficient funds error is reported as the failed transfer
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::TokenTransferFailed as u32)
+        )
+    );
+}
@@ -0,0 +1,218 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{donate_reserve_liquidity, redeem_reserve_collateral, refresh_reserve},
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+const USDC_DONATION_FRACTIONAL: u64 = 5 * FRACTIONAL_TO_USDC;
+const COLLATERAL_AMOUNT: u64 = USDC_RESERVE_LIQUIDITY_FRACTIONAL * INITIAL_COLLATERAL_RATIO;
+
+fn setup(liquidity_amount: u64) -> (ProgramTest, Keypair, TestLendingMarket, TestReserve) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: liquidity_amount * INITIAL_COLLATERAL_RATIO,
+            liquidity_amount,
+            user_liquidity_amount: USDC_DONATION_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    (test, user_accounts_owner, lending_market, usdc_test_reserve)
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (test, user_accounts_owner, lending_market, usdc_test_reserve) =
+        setup(USDC_RESERVE_LIQUIDITY_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    let initial_liquidity = initial_reserve
+        .collateral_exchange_rate()
+        .unwrap()
+        .collateral_to_liquidity(COLLATERAL_AMOUNT)
+        .unwrap();
+    assert_eq!(initial_liquidity, USDC_RESERVE_LIQUIDITY_FRACTIONAL);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[donate_reserve_liquidity(
+            port_finance_variable_rate_lending::id(),
+            USDC_DONATION_FRACTIONAL,
+            usdc_test_reserve.user_liquidity_pubkey,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // no collateral is minted, so the existing collateral is worth the donation more
+    let reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        reserve.liquidity.available_amount,
+        initial_reserve.liquidity.available_amount + USDC_DONATION_FRACTIONAL
+    );
+    assert_eq!(
+        reserve.collateral.mint_total_supply,
+        initial_reserve.collateral.mint_total_supply
+    );
+    assert_eq!(
+        reserve.protocol_owned_amount(),
+        initial_reserve.protocol_owned_amount() + USDC_DONATION_FRACTIONAL
+    );
+    assert_eq!(
+        reserve
+            .collateral_exchange_rate()
+            .unwrap()
+            .collateral_to_liquidity(COLLATERAL_AMOUNT)
+            .unwrap(),
+        USDC_RESERVE_LIQUIDITY_FRACTIONAL + USDC_DONATION_FRACTIONAL
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await,
+        reserve.liquidity.available_amount
+    );
+
+    // redeeming all the collateral returns the subsidized amount
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_oracle_pubkey,
+            ),
+            redeem_reserve_collateral(
+                port_finance_variable_rate_lending::id(),
+                COLLATERAL_AMOUNT,
+                usdc_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await,
+        USDC_RESERVE_LIQUIDITY_FRACTIONAL + USDC_DONATION_FRACTIONAL
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_donation() {
+    let (test, user_accounts_owner, lending_market, usdc_test_reserve) =
+        setup(USDC_RESERVE_LIQUIDITY_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // (amount, reserve liquidity supply, expected error)
+    let cases = [
+        (
+            0,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            LendingError::InvalidAmount,
+        ),
+        (
+            USDC_DONATION_FRACTIONAL,
+            Pubkey::new_unique(),
+            LendingError::WrongLiquiditySupply,
+        ),
+    ];
+    for (amount, reserve_liquidity_supply_pubkey, error) in cases {
+        let mut transaction = Transaction::new_with_payer(
+            &[donate_reserve_liquidity(
+                port_finance_variable_rate_lending::id(),
+                amount,
+                usdc_test_reserve.user_liquidity_pubkey,
+                reserve_liquidity_supply_pubkey,
+                usdc_test_reserve.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+
+    usdc_test_reserve.validate_state(&mut banks_client).await;
+}
+
+#[tokio::test]
+async fn test_no_collateral_outstanding() {
+    let (test, user_accounts_owner, lending_market, usdc_test_reserve) = setup(0);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[donate_reserve_liquidity(
+            port_finance_variable_rate_lending::id(),
+            USDC_DONATION_FRACTIONAL,
+            usdc_test_reserve.user_liquidity_pubkey,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAmount as u32)
+        )
+    );
+}
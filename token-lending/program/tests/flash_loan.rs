@@ -228,3 +228,186 @@ async fn test_failure() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_success_minimal_amount() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // 0.3% of the loan is under a token, so the minimum fee of one token each to the owner and
+    // host is charged instead of nothing
+    const FLASH_LOAN_AMOUNT: u64 = 100;
+    const FEE_AMOUNT: u64 = 2;
+    const HOST_FEE_AMOUNT: u64 = 1;
+    const RESERVE_LIQUIDITY_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+    let receiver_program_account = Keypair::new();
+    let receiver_program_id = receiver_program_account.pubkey();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.fees.flash_loan_fee_wad = 3_000_000_000_000_000;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: RESERVE_LIQUIDITY_AMOUNT,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let program_owned_token_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        FEE_AMOUNT,
+        &usdc_mint.pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the receiver repays exactly the loan plus the fee
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan(
+            port_finance_variable_rate_lending::id(),
+            FLASH_LOAN_AMOUNT,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            program_owned_token_account,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.liquidity_fee_receiver_pubkey,
+            usdc_test_reserve.liquidity_host_pubkey,
+            lending_market.pubkey,
+            receiver_program_id,
+            vec![AccountMeta::new_readonly(receiver_authority_pubkey, false)],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    let (total_fee, host_fee) = usdc_reserve
+        .config
+        .fees
+        .calculate_flash_loan_fees(FLASH_LOAN_AMOUNT.into())
+        .unwrap();
+    assert_eq!(total_fee, FEE_AMOUNT);
+    assert_eq!(host_fee, HOST_FEE_AMOUNT);
+
+    let liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    assert_eq!(liquidity_supply, RESERVE_LIQUIDITY_AMOUNT);
+
+    let token_balance = get_token_balance(&mut banks_client, program_owned_token_account).await;
+    assert_eq!(token_balance, 0);
+
+    let fee_balance = get_token_balance(
+        &mut banks_client,
+        usdc_test_reserve.liquidity_fee_receiver_pubkey,
+    )
+    .await;
+    assert_eq!(fee_balance, FEE_AMOUNT - HOST_FEE_AMOUNT);
+
+    let host_fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_host_pubkey).await;
+    assert_eq!(host_fee_balance, HOST_FEE_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_fail_zero_amount() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const RESERVE_LIQUIDITY_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+    let receiver_program_account = Keypair::new();
+    let receiver_program_id = receiver_program_account.pubkey();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.fees.flash_loan_fee_wad = 3_000_000_000_000_000;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: RESERVE_LIQUIDITY_AMOUNT,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let program_owned_token_account =
+        add_account_for_program(&mut test, &receiver_authority_pubkey, 0, &usdc_mint.pubkey);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan(
+            port_finance_variable_rate_lending::id(),
+            0,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            program_owned_token_account,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.liquidity_fee_receiver_pubkey,
+            usdc_test_reserve.liquidity_host_pubkey,
+            lending_market.pubkey,
+            receiver_program_id,
+            vec![AccountMeta::new_readonly(receiver_authority_pubkey, false)],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAmount as u32)
+        )
+    );
+}
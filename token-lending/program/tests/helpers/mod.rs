@@ -7,6 +7,7 @@ use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey
 use solana_program_test::*;
 use solana_sdk::{
     account::Account,
+    clock::Clock,
     signature::{read_keypair_file, Keypair, Signer},
     system_instruction::create_account,
     transaction::{Transaction, TransactionError},
@@ -20,6 +21,7 @@ use switchboard_program::{
     SwitchboardAccountType,
 };
 
+use port_finance_staking::slots::Slots;
 use port_finance_staking::state::staking_pool::RatePerSlot;
 use port_finance_variable_rate_lending::math::TryDiv;
 use port_finance_variable_rate_lending::{
@@ -65,6 +67,22 @@ pub const TEST_RESERVE_CONFIG: ReserveConfig = ReserveConfig {
         host_fee_percentage: 20,
     },
     deposit_staking_pool: COption::None,
+    use_twap: false,
+    twap_window_slots: 0,
+    borrow_fee_discount_staking_pool: COption::None,
+    borrow_fee_discount_percentage: 0,
+    max_accrual_slots: 0,
+    borrowing_disabled: false,
+    liquidation_bonus_ramp_slots: 0,
+    min_initial_deposit: 0,
+    max_price_staleness_slots: 0,
+    max_slot_liquidity_change_percent: 0,
+    max_oracle_deviation_bps: 0,
+    isolated_debt_ceiling: 0,
+    collateral_disabled: false,
+    max_liquidation_bonus: 0,
+    borrow_fee_discount_min_stake: 0,
+    min_liquidation_bonus: 0,
 };
 
 pub const SOL_PYTH_PRODUCT: &str = "3Mnn2fX6rQyUsyELYms1sBJyChWofzSNRoqYzvgMVz5E";
@@ -75,7 +93,7 @@ pub const SRM_PYTH_PRICE: &str = "992moaMQKs32GKZ9dxi8keyM2bUmbrwBZpK4p2K6X5Vs";
 
 pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
 
-trait AddPacked {
+pub trait AddPacked {
     fn add_packable_account<T: Pack>(
         &mut self,
         pubkey: Pubkey,
@@ -102,6 +120,7 @@ impl AddPacked for ProgramTest {
 pub struct TestStakingPool {
     pub staking_pool_pubkey: Pubkey,
     pub staking_pool: port_finance_staking::state::staking_pool::StakingPool,
+    pub reward_token_mint_pubkey: Pubkey,
 }
 
 impl TestStakingPool {
@@ -156,10 +175,10 @@ pub fn add_staking_pool(
             Pubkey::new_unique(),
             reward_token_pool_pubkey,
             None,
-            1000,
+            Slots(1000),
             100,
             None,
-            0,
+            Slots(0),
             bump_seed,
         )
         .unwrap();
@@ -174,6 +193,7 @@ pub fn add_staking_pool(
     TestStakingPool {
         staking_pool_pubkey,
         staking_pool,
+        reward_token_mint_pubkey: token_mint_pubkey,
     }
 }
 
@@ -226,6 +246,13 @@ pub fn add_stake_account<'a, 'b>(
 }
 
 pub fn add_lending_market(test: &mut ProgramTest) -> TestLendingMarket {
+    add_lending_market_with_quote_currency(test, QUOTE_CURRENCY)
+}
+
+pub fn add_lending_market_with_quote_currency(
+    test: &mut ProgramTest,
+    quote_currency: [u8; 32],
+) -> TestLendingMarket {
     let lending_market_pubkey = Pubkey::new_unique();
     let (lending_market_authority, bump_seed) = Pubkey::find_program_address(
         &[lending_market_pubkey.as_ref()],
@@ -241,7 +268,7 @@ pub fn add_lending_market(test: &mut ProgramTest) -> TestLendingMarket {
         &LendingMarket::new(InitLendingMarketParams {
             bump_seed,
             owner: lending_market_owner.pubkey(),
-            quote_currency: QUOTE_CURRENCY,
+            quote_currency,
             token_program_id: spl_token::id(),
         }),
         &port_finance_variable_rate_lending::id(),
@@ -251,7 +278,7 @@ pub fn add_lending_market(test: &mut ProgramTest) -> TestLendingMarket {
         pubkey: lending_market_pubkey,
         owner: lending_market_owner,
         authority: lending_market_authority,
-        quote_currency: QUOTE_CURRENCY,
+        quote_currency,
     }
 }
 
@@ -360,6 +387,8 @@ pub struct AddReserveArgs {
     pub collateral_amount: u64,
     pub mark_fresh: bool,
     pub slots_elapsed: u64,
+    /// Price the reserve at a fixed price instead of the oracle
+    pub fixed_price: COption<Decimal>,
 }
 
 pub fn add_reserve(
@@ -381,8 +410,14 @@ pub fn add_reserve(
         collateral_amount,
         mark_fresh,
         slots_elapsed,
+        fixed_price,
     } = args;
 
+    let (liquidity_oracle_pubkey, market_price) = match fixed_price {
+        COption::Some(fixed_price) => (COption::None, fixed_price),
+        COption::None => (COption::Some(oracle.price_pubkey), oracle.price),
+    };
+
     let is_native = if liquidity_mint_pubkey == spl_token::native_mint::id() {
         COption::Some(1)
     } else {
@@ -478,8 +513,8 @@ pub fn add_reserve(
             mint_decimals: liquidity_mint_decimals,
             supply_pubkey: liquidity_supply_pubkey,
             fee_receiver: liquidity_fee_receiver_pubkey,
-            oracle_pubkey: COption::Some(oracle.price_pubkey),
-            market_price: oracle.price,
+            oracle_pubkey: liquidity_oracle_pubkey,
+            market_price,
         }),
         collateral: ReserveCollateral::new(NewReserveCollateralParams {
             mint_pubkey: collateral_mint_pubkey,
@@ -550,12 +585,12 @@ pub fn add_reserve(
         liquidity_supply_pubkey,
         liquidity_fee_receiver_pubkey,
         liquidity_host_pubkey,
-        liquidity_oracle_pubkey: COption::Some(oracle.price_pubkey),
+        liquidity_oracle_pubkey,
         collateral_mint_pubkey,
         collateral_supply_pubkey,
         user_liquidity_pubkey,
         user_collateral_pubkey,
-        market_price: oracle.price,
+        market_price,
     }
 }
 
@@ -852,6 +887,8 @@ impl TestLendingMarket {
                 obligation.pubkey,
                 self.pubkey,
                 obligation.owner,
+                None,
+                None,
             )],
             Some(&payer.pubkey()),
         );
@@ -1102,6 +1139,17 @@ impl TestReserve {
         assert!(reserve.liquidity.available_amount > 0);
         assert!(reserve.collateral.mint_total_supply > 0);
     }
+
+    /// Check the reserve's recorded collateral supply against its collateral mint
+    pub async fn validate_collateral_supply(&self, banks_client: &mut BanksClient) {
+        let reserve = self.get_state(banks_client).await;
+        let collateral_mint_supply =
+            get_mint_supply(banks_client, self.collateral_mint_pubkey).await;
+        assert_eq!(
+            reserve.check_collateral_invariant(collateral_mint_supply),
+            Ok(())
+        );
+    }
 }
 
 #[derive(Debug)]
@@ -1265,6 +1313,29 @@ pub fn add_usdc_mint(test: &mut ProgramTest) -> TestMint {
     }
 }
 
+pub fn add_freezable_mint(test: &mut ProgramTest) -> TestMint {
+    let authority = Keypair::new();
+    let pubkey = Pubkey::new_unique();
+    let decimals = 6;
+    test.add_packable_account(
+        pubkey,
+        u32::MAX as u64,
+        &Mint {
+            is_initialized: true,
+            mint_authority: COption::Some(authority.pubkey()),
+            freeze_authority: COption::Some(authority.pubkey()),
+            decimals,
+            ..Mint::default()
+        },
+        &spl_token::id(),
+    );
+    TestMint {
+        pubkey,
+        authority,
+        decimals,
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct TestOracle {
     pub price_pubkey: Pubkey,
@@ -1531,6 +1602,51 @@ pub async fn mint_to(
     assert_matches!(banks_client.process_transaction(transaction).await, Ok(()));
 }
 
+/// Warp `slots` past the current slot and refresh the reserve there, accruing interest for
+/// exactly the slots since its last update. Returns the refreshed reserve.
+pub async fn advance_and_refresh(
+    test_context: &mut ProgramTestContext,
+    slots: u64,
+    reserve: &TestReserve,
+) -> Reserve {
+    let clock = test_context
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap();
+    test_context.warp_to_slot(clock.slot + slots).unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            reserve.pubkey,
+            reserve.liquidity_oracle_pubkey,
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    let recent_blockhash = test_context
+        .banks_client
+        .get_latest_blockhash()
+        .await
+        .unwrap();
+    transaction.sign(&[&test_context.payer], recent_blockhash);
+    assert_matches!(
+        test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await,
+        Ok(())
+    );
+
+    reserve.get_state(&mut test_context.banks_client).await
+}
+
+pub async fn get_mint_supply(banks_client: &mut BanksClient, pubkey: Pubkey) -> u64 {
+    let mint: Account = banks_client.get_account(pubkey).await.unwrap().unwrap();
+
+    Mint::unpack(&mint.data[..]).unwrap().supply
+}
+
 pub async fn get_token_balance(banks_client: &mut BanksClient, pubkey: Pubkey) -> u64 {
     let token: Account = banks_client.get_account(pubkey).await.unwrap().unwrap();
 
@@ -6,18 +6,30 @@ use helpers::*;
 use port_finance_variable_rate_lending::math::Decimal;
 use port_finance_variable_rate_lending::{
     error::LendingError,
-    instruction::init_reserve,
+    instruction::{
+        build_init_reserve_transaction, init_reserve, init_reserve_pda, set_allow_freezable_mint,
+        InitReserveAccounts,
+    },
     processor::process_instruction,
-    state::{ReserveFees, INITIAL_COLLATERAL_RATIO},
+    state::{
+        find_reserve_address, Reserve, ReserveFees, INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION,
+    },
 };
 use solana_program_test::*;
 use solana_sdk::{
+    account::Account,
     instruction::InstructionError,
+    program_pack::Pack,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
+    system_instruction::create_account,
     transaction::{Transaction, TransactionError},
 };
 use spl_token::solana_program::program_option::COption;
+use spl_token::{
+    instruction::approve,
+    state::{Account as Token, Mint},
+};
 
 #[tokio::test]
 async fn test_success() {
@@ -277,6 +289,225 @@ async fn test_success_switchboard_v2() {
     );
 }
 
+#[tokio::test]
+async fn test_quote_currency_reserve_with_oracle() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market =
+        add_lending_market_with_quote_currency(&mut test, usdc_mint.pubkey.to_bytes());
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        Some(&usdc_mint.authority),
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    // the quote currency is worth exactly one of itself, an oracle price cannot be used
+    assert_eq!(
+        TestReserve::init(
+            "usdc".to_owned(),
+            &mut banks_client,
+            &lending_market,
+            &COption::Some(usdc_oracle),
+            RESERVE_AMOUNT,
+            COption::None,
+            TEST_RESERVE_CONFIG,
+            usdc_mint.pubkey,
+            user_liquidity_account,
+            &payer,
+            &user_accounts_owner,
+        )
+        .await
+        .err(),
+        Some(TransactionError::InstructionError(
+            8,
+            InstructionError::Custom(LendingError::InvalidOracleConfig as u32)
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_oracle_reserve_in_non_usd_market() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    // market quoted in SOL rather than USD
+    let lending_market =
+        add_lending_market_with_quote_currency(&mut test, spl_token::native_mint::id().to_bytes());
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        Some(&usdc_mint.authority),
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    // the oracle prices USDC in USD, which would be taken for a price in SOL
+    assert_eq!(
+        TestReserve::init(
+            "usdc".to_owned(),
+            &mut banks_client,
+            &lending_market,
+            &COption::Some(usdc_oracle),
+            RESERVE_AMOUNT,
+            COption::None,
+            TEST_RESERVE_CONFIG,
+            usdc_mint.pubkey,
+            user_liquidity_account,
+            &payer,
+            &user_accounts_owner,
+        )
+        .await
+        .err(),
+        Some(TransactionError::InstructionError(
+            8,
+            InstructionError::Custom(LendingError::InvalidOracleConfig as u32)
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_freezable_mint() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let freezable_mint = add_freezable_mint(&mut test);
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        freezable_mint.pubkey,
+        Some(&freezable_mint.authority),
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    assert_eq!(
+        TestReserve::init(
+            "usdc".to_owned(),
+            &mut banks_client,
+            &lending_market,
+            &COption::Some(usdc_oracle),
+            RESERVE_AMOUNT,
+            COption::None,
+            TEST_RESERVE_CONFIG,
+            freezable_mint.pubkey,
+            user_liquidity_account,
+            &payer,
+            &user_accounts_owner,
+        )
+        .await
+        .err(),
+        Some(TransactionError::InstructionError(
+            8,
+            InstructionError::Custom(LendingError::FreezableLiquidityMint as u32)
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_freezable_mint_allowed() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let freezable_mint = add_freezable_mint(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_allow_freezable_mint(
+            port_finance_variable_rate_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            true,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+    assert!(
+        lending_market
+            .get_state(&mut banks_client)
+            .await
+            .allow_freezable_mint
+    );
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        freezable_mint.pubkey,
+        Some(&freezable_mint.authority),
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    let usdc_reserve = TestReserve::init(
+        "usdc".to_owned(),
+        &mut banks_client,
+        &lending_market,
+        &COption::Some(usdc_oracle),
+        RESERVE_AMOUNT,
+        COption::None,
+        TEST_RESERVE_CONFIG,
+        freezable_mint.pubkey,
+        user_liquidity_account,
+        &payer,
+        &user_accounts_owner,
+    )
+    .await
+    .unwrap();
+
+    usdc_reserve.validate_state(&mut banks_client).await;
+}
+
 #[tokio::test]
 async fn test_already_initialized() {
     let mut test = ProgramTest::new(
@@ -345,6 +576,91 @@ async fn test_already_initialized() {
     );
 }
 
+#[tokio::test]
+async fn test_collateral_mint_in_use() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 42,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    let rent = banks_client.get_rent().await.unwrap();
+
+    // a second reserve reusing the first reserve's collateral mint
+    let reserve_keypair = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &reserve_keypair.pubkey(),
+                rent.minimum_balance(Reserve::LEN),
+                Reserve::LEN as u64,
+                &port_finance_variable_rate_lending::id(),
+            ),
+            init_reserve(
+                port_finance_variable_rate_lending::id(),
+                42,
+                COption::None,
+                usdc_test_reserve.config,
+                usdc_test_reserve.user_liquidity_pubkey,
+                Pubkey::new_unique(),
+                reserve_keypair.pubkey(),
+                usdc_test_reserve.liquidity_mint_pubkey,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                usdc_test_reserve.collateral_mint_pubkey,
+                Pubkey::new_unique(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                user_transfer_authority.pubkey(),
+                COption::Some(usdc_oracle.price_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &reserve_keypair,
+            &lending_market.owner,
+            &user_transfer_authority,
+        ],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::CollateralMintInUse as u32)
+        )
+    );
+}
+
 #[tokio::test]
 async fn test_invalid_fees() {
     let mut test = ProgramTest::new(
@@ -496,3 +812,411 @@ async fn test_fixed_price() {
         RESERVE_AMOUNT * INITIAL_COLLATERAL_RATIO
     );
 }
+
+#[tokio::test]
+async fn test_success_pda() {
+    init_reserve_at_derived_address(0).await;
+}
+
+#[tokio::test]
+async fn test_success_pda_prefunded() {
+    // anyone can send lamports to the derived address before the reserve is created there
+    init_reserve_at_derived_address(1_000_000).await;
+}
+
+/// Initialize a SOL reserve at its derived address, holding `prefunded_lamports` beforehand
+async fn init_reserve_at_derived_address(prefunded_lamports: u64) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+
+    let (reserve_pubkey, _bump_seed) = find_reserve_address(
+        &port_finance_variable_rate_lending::id(),
+        &lending_market.pubkey,
+        &spl_token::native_mint::id(),
+    );
+    if prefunded_lamports > 0 {
+        test.add_account(
+            reserve_pubkey,
+            Account {
+                lamports: prefunded_lamports,
+                ..Account::default()
+            },
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    let rent = banks_client.get_rent().await.unwrap();
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let sol_user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    let token_accounts = [
+        Keypair::new(), // user collateral
+        Keypair::new(), // liquidity supply
+        Keypair::new(), // liquidity fee receiver
+        Keypair::new(), // collateral supply
+    ];
+    let collateral_mint = Keypair::new();
+    let mut instructions = vec![
+        approve(
+            &spl_token::id(),
+            &sol_user_liquidity_account,
+            &user_transfer_authority.pubkey(),
+            &user_accounts_owner.pubkey(),
+            &[],
+            RESERVE_AMOUNT,
+        )
+        .unwrap(),
+        create_account(
+            &payer.pubkey(),
+            &collateral_mint.pubkey(),
+            rent.minimum_balance(Mint::LEN),
+            Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+    ];
+    instructions.extend(token_accounts.iter().map(|keypair| {
+        create_account(
+            &payer.pubkey(),
+            &keypair.pubkey(),
+            rent.minimum_balance(Token::LEN),
+            Token::LEN as u64,
+            &spl_token::id(),
+        )
+    }));
+    instructions.push(init_reserve_pda(
+        port_finance_variable_rate_lending::id(),
+        RESERVE_AMOUNT,
+        COption::None,
+        TEST_RESERVE_CONFIG,
+        sol_user_liquidity_account,
+        token_accounts[0].pubkey(),
+        spl_token::native_mint::id(),
+        token_accounts[1].pubkey(),
+        token_accounts[2].pubkey(),
+        collateral_mint.pubkey(),
+        token_accounts[3].pubkey(),
+        lending_market.pubkey,
+        lending_market.owner.pubkey(),
+        user_transfer_authority.pubkey(),
+        payer.pubkey(),
+        COption::Some(sol_oracle.price_pubkey),
+    ));
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    let mut signers = vec![
+        &payer,
+        &user_accounts_owner,
+        &user_transfer_authority,
+        &lending_market.owner,
+        &collateral_mint,
+    ];
+    signers.extend(token_accounts.iter());
+    transaction.sign(&signers, recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // the reserve can be found from the lending market and liquidity mint alone
+    let reserve_account = banks_client
+        .get_account(reserve_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        reserve_account.owner,
+        port_finance_variable_rate_lending::id()
+    );
+    assert_eq!(
+        reserve_account.lamports,
+        rent.minimum_balance(Reserve::LEN).max(prefunded_lamports)
+    );
+    let reserve = Reserve::unpack(&reserve_account.data).unwrap();
+    assert_eq!(reserve.lending_market, lending_market.pubkey);
+    assert_eq!(reserve.liquidity.mint_pubkey, spl_token::native_mint::id());
+    assert_eq!(reserve.liquidity.available_amount, RESERVE_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_pda_already_initialized() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_transfer_authority = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_mint = add_usdc_mint(&mut test);
+
+    // a reserve already initialized at the derived address
+    let (reserve_pubkey, _bump_seed) = find_reserve_address(
+        &port_finance_variable_rate_lending::id(),
+        &lending_market.pubkey,
+        &usdc_mint.pubkey,
+    );
+    test.add_packable_account(
+        reserve_pubkey,
+        u32::MAX as u64,
+        &Reserve {
+            version: PROGRAM_VERSION,
+            lending_market: lending_market.pubkey,
+            ..Reserve::default()
+        },
+        &port_finance_variable_rate_lending::id(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[init_reserve_pda(
+            port_finance_variable_rate_lending::id(),
+            42,
+            COption::Some(Decimal::one()),
+            TEST_RESERVE_CONFIG,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            usdc_mint.pubkey,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            user_transfer_authority.pubkey(),
+            payer.pubkey(),
+            COption::None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &lending_market.owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::AlreadyInitialized as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_pda_wrong_address() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_transfer_authority = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_mint = add_usdc_mint(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut instruction = init_reserve_pda(
+        port_finance_variable_rate_lending::id(),
+        42,
+        COption::Some(Decimal::one()),
+        TEST_RESERVE_CONFIG,
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        usdc_mint.pubkey,
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        lending_market.pubkey,
+        lending_market.owner.pubkey(),
+        user_transfer_authority.pubkey(),
+        payer.pubkey(),
+        COption::None,
+    );
+    // a reserve address that isn't derived from the lending market and liquidity mint
+    instruction.accounts[2].pubkey = Pubkey::new_unique();
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(
+        &[&payer, &lending_market.owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_build_init_reserve_transaction() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let sol_user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    let reserve_keypair = Keypair::new();
+    let collateral_mint_keypair = Keypair::new();
+    let collateral_supply_keypair = Keypair::new();
+    let liquidity_supply_keypair = Keypair::new();
+    let liquidity_fee_receiver_keypair = Keypair::new();
+    let user_collateral_keypair = Keypair::new();
+    let user_transfer_authority_keypair = Keypair::new();
+    let accounts = InitReserveAccounts {
+        reserve: reserve_keypair.pubkey(),
+        collateral_mint: collateral_mint_keypair.pubkey(),
+        collateral_supply: collateral_supply_keypair.pubkey(),
+        liquidity_supply: liquidity_supply_keypair.pubkey(),
+        liquidity_fee_receiver: liquidity_fee_receiver_keypair.pubkey(),
+        user_collateral: user_collateral_keypair.pubkey(),
+        user_transfer_authority: user_transfer_authority_keypair.pubkey(),
+    };
+
+    let rent = banks_client.get_rent().await.unwrap();
+    let transactions = build_init_reserve_transaction(
+        port_finance_variable_rate_lending::id(),
+        payer.pubkey(),
+        &rent,
+        RESERVE_AMOUNT,
+        COption::None,
+        TEST_RESERVE_CONFIG,
+        sol_user_liquidity_account,
+        user_accounts_owner.pubkey(),
+        spl_token::native_mint::id(),
+        lending_market.pubkey,
+        lending_market.owner.pubkey(),
+        COption::Some(sol_oracle.price_pubkey),
+        &accounts,
+    )
+    .unwrap();
+    assert_eq!(transactions.len(), 2);
+
+    let mut transaction_1 = Transaction::new_with_payer(&transactions[0], Some(&payer.pubkey()));
+    transaction_1.sign(
+        &vec![
+            &payer,
+            &reserve_keypair,
+            &collateral_mint_keypair,
+            &collateral_supply_keypair,
+            &user_collateral_keypair,
+        ],
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction_1)
+        .await
+        .unwrap();
+
+    let mut transaction_2 = Transaction::new_with_payer(&transactions[1], Some(&payer.pubkey()));
+    transaction_2.sign(
+        &vec![
+            &payer,
+            &liquidity_supply_keypair,
+            &liquidity_fee_receiver_keypair,
+            &user_accounts_owner,
+            &lending_market.owner,
+            &user_transfer_authority_keypair,
+        ],
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction_2)
+        .await
+        .unwrap();
+
+    let reserve_account = banks_client
+        .get_account(accounts.reserve)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        reserve_account.owner,
+        port_finance_variable_rate_lending::id()
+    );
+    let reserve = Reserve::unpack(&reserve_account.data).unwrap();
+    assert_eq!(reserve.version, PROGRAM_VERSION);
+    assert_eq!(reserve.lending_market, lending_market.pubkey);
+    assert_eq!(reserve.liquidity.mint_pubkey, spl_token::native_mint::id());
+    assert_eq!(reserve.liquidity.supply_pubkey, accounts.liquidity_supply);
+    assert_eq!(
+        reserve.liquidity.fee_receiver,
+        accounts.liquidity_fee_receiver
+    );
+    assert_eq!(
+        reserve.liquidity.oracle_pubkey,
+        COption::Some(sol_oracle.price_pubkey)
+    );
+    assert_eq!(reserve.collateral.mint_pubkey, accounts.collateral_mint);
+    assert_eq!(reserve.collateral.supply_pubkey, accounts.collateral_supply);
+    assert_eq!(reserve.config, TEST_RESERVE_CONFIG);
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, accounts.liquidity_supply).await,
+        RESERVE_AMOUNT
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, accounts.user_collateral).await,
+        RESERVE_AMOUNT * INITIAL_COLLATERAL_RATIO
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, sol_user_liquidity_account).await,
+        0
+    );
+
+    // the approval is revoked once the reserve is initialized
+    let user_liquidity = Token::unpack(
+        &banks_client
+            .get_account(sol_user_liquidity_account)
+            .await
+            .unwrap()
+            .unwrap()
+            .data,
+    )
+    .unwrap();
+    assert_eq!(user_liquidity.delegate, COption::None);
+}
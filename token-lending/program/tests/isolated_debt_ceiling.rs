@@ -0,0 +1,315 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, refresh_obligation, refresh_reserve,
+        repay_obligation_liquidity,
+    },
+    math::Decimal,
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+use solana_program::instruction::Instruction;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    program_option::COption,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::instruction::approve;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 1_000 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 20_000 * FRACTIONAL_TO_USDC;
+/// Ceiling of 10_000 USDC, in the quote currency
+const ISOLATED_DEBT_CEILING: u64 = 10_000;
+const USDC_CEILING_FRACTIONAL: u64 = 10_000 * FRACTIONAL_TO_USDC;
+/// Already borrowed by the first obligation
+const USDC_FIRST_BORROW_FRACTIONAL: u64 = 6_000 * FRACTIONAL_TO_USDC;
+
+struct DebtCeilingAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    first_obligation: TestObligation,
+    second_obligation: TestObligation,
+}
+
+/// Two obligations with SOL collateral borrowing USDC, priced at one, from a reserve with a
+/// debt ceiling. The first one already borrowed, the second one hasn't.
+fn setup() -> (ProgramTest, DebtCeilingAccounts) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.fees.borrow_fee_wad = 0;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            user_liquidity_amount: USDC_FIRST_BORROW_FRACTIONAL,
+            borrow_amount: USDC_FIRST_BORROW_FRACTIONAL,
+            config: ReserveConfig {
+                isolated_debt_ceiling: ISOLATED_DEBT_CEILING,
+                ..reserve_config
+            },
+            mark_fresh: true,
+            fixed_price: COption::Some(Decimal::one()),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let first_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_FIRST_BORROW_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+    let second_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        DebtCeilingAccounts {
+            user_accounts_owner,
+            lending_market,
+            sol_test_reserve,
+            usdc_test_reserve,
+            first_obligation,
+            second_obligation,
+        },
+    )
+}
+
+fn borrow_instructions(
+    accounts: &DebtCeilingAccounts,
+    obligation: &TestObligation,
+    amount: u64,
+) -> Vec<Instruction> {
+    let mut obligation_reserves = vec![accounts.sol_test_reserve.pubkey];
+    if obligation.pubkey == accounts.first_obligation.pubkey {
+        obligation_reserves.push(accounts.usdc_test_reserve.pubkey);
+    }
+    vec![
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            accounts.sol_test_reserve.pubkey,
+            accounts.sol_test_reserve.liquidity_oracle_pubkey,
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            accounts.usdc_test_reserve.pubkey,
+            accounts.usdc_test_reserve.liquidity_oracle_pubkey,
+        ),
+        refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            obligation.pubkey,
+            obligation_reserves,
+        ),
+        borrow_obligation_liquidity(
+            port_finance_variable_rate_lending::id(),
+            amount,
+            accounts.usdc_test_reserve.liquidity_supply_pubkey,
+            accounts.usdc_test_reserve.user_liquidity_pubkey,
+            accounts.usdc_test_reserve.pubkey,
+            accounts.usdc_test_reserve.liquidity_fee_receiver_pubkey,
+            obligation.pubkey,
+            accounts.lending_market.pubkey,
+            obligation.owner,
+            None,
+            None,
+        ),
+    ]
+}
+
+async fn borrow(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    accounts: &DebtCeilingAccounts,
+    obligation: &TestObligation,
+    amount: u64,
+) -> Result<(), TransactionError> {
+    let mut transaction = Transaction::new_with_payer(
+        &borrow_instructions(accounts, obligation, amount),
+        Some(&payer.pubkey()),
+    );
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    transaction.sign(&[payer, &accounts.user_accounts_owner], recent_blockhash);
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+fn debt_ceiling_exceeded() -> TransactionError {
+    TransactionError::InstructionError(
+        3,
+        InstructionError::Custom(LendingError::DebtCeilingExceeded as u32),
+    )
+}
+
+#[tokio::test]
+async fn test_borrow_up_to_ceiling() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    // the second obligation takes the rest of the room
+    borrow(
+        &mut banks_client,
+        &payer,
+        &accounts,
+        &accounts.second_obligation,
+        USDC_CEILING_FRACTIONAL - USDC_FIRST_BORROW_FRACTIONAL,
+    )
+    .await
+    .unwrap();
+
+    let usdc_reserve = accounts
+        .usdc_test_reserve
+        .get_state(&mut banks_client)
+        .await;
+    assert_eq!(
+        usdc_reserve.liquidity.borrowed_amount_wads,
+        Decimal::from(USDC_CEILING_FRACTIONAL)
+    );
+
+    // so the first obligation can't borrow any more
+    assert_eq!(
+        borrow(
+            &mut banks_client,
+            &payer,
+            &accounts,
+            &accounts.first_obligation,
+            1,
+        )
+        .await
+        .unwrap_err(),
+        debt_ceiling_exceeded()
+    );
+}
+
+#[tokio::test]
+async fn test_fail_borrow_above_ceiling() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    assert_eq!(
+        borrow(
+            &mut banks_client,
+            &payer,
+            &accounts,
+            &accounts.second_obligation,
+            USDC_CEILING_FRACTIONAL - USDC_FIRST_BORROW_FRACTIONAL + 1,
+        )
+        .await
+        .unwrap_err(),
+        debt_ceiling_exceeded()
+    );
+}
+
+#[tokio::test]
+async fn test_repay_frees_ceiling() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    const REPAY_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &accounts.usdc_test_reserve.user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &accounts.user_accounts_owner.pubkey(),
+                &[],
+                REPAY_AMOUNT_FRACTIONAL,
+            )
+            .unwrap(),
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                accounts.first_obligation.pubkey,
+                vec![
+                    accounts.sol_test_reserve.pubkey,
+                    accounts.usdc_test_reserve.pubkey,
+                ],
+            ),
+            repay_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                REPAY_AMOUNT_FRACTIONAL,
+                accounts.usdc_test_reserve.user_liquidity_pubkey,
+                accounts.usdc_test_reserve.liquidity_supply_pubkey,
+                accounts.usdc_test_reserve.pubkey,
+                accounts.first_obligation.pubkey,
+                accounts.lending_market.pubkey,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.user_accounts_owner,
+            &user_transfer_authority,
+        ],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // the repaid amount is room for the second obligation
+    borrow(
+        &mut banks_client,
+        &payer,
+        &accounts,
+        &accounts.second_obligation,
+        USDC_CEILING_FRACTIONAL - USDC_FIRST_BORROW_FRACTIONAL + REPAY_AMOUNT_FRACTIONAL,
+    )
+    .await
+    .unwrap();
+}
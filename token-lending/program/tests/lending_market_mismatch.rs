@@ -0,0 +1,197 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{borrow_obligation_liquidity, deposit_obligation_collateral, refresh_obligation},
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+struct TwoMarkets {
+    user_accounts_owner: Keypair,
+    market_a: TestLendingMarket,
+    market_b: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+/// An obligation with SOL collateral in market A, and a USDC reserve in market B
+fn setup() -> (ProgramTest, TwoMarkets) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let market_a = add_lending_market(&mut test);
+    let market_b = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &market_a,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &market_b,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &market_a,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        TwoMarkets {
+            user_accounts_owner,
+            market_a,
+            market_b,
+            sol_test_reserve,
+            usdc_test_reserve,
+            test_obligation,
+        },
+    )
+}
+
+async fn cross_market_borrow(lending_market: fn(&TwoMarkets) -> Pubkey) {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                accounts.test_obligation.pubkey,
+                vec![accounts.sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                accounts.usdc_test_reserve.liquidity_supply_pubkey,
+                accounts.usdc_test_reserve.user_liquidity_pubkey,
+                accounts.usdc_test_reserve.pubkey,
+                accounts.usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                accounts.test_obligation.pubkey,
+                lending_market(&accounts),
+                accounts.test_obligation.owner,
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::LendingMarketMismatch as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_cross_market_borrow_with_obligation_market() {
+    // the borrow reserve doesn't belong to the obligation's market
+    cross_market_borrow(|accounts| accounts.market_a.pubkey).await;
+}
+
+#[tokio::test]
+async fn test_fail_cross_market_borrow_with_reserve_market() {
+    // the obligation doesn't belong to the borrow reserve's market
+    cross_market_borrow(|accounts| accounts.market_b.pubkey).await;
+}
+
+#[tokio::test]
+async fn test_fail_cross_market_deposit_collateral() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_obligation_collateral(
+            port_finance_variable_rate_lending::id(),
+            USDC_BORROW_AMOUNT_FRACTIONAL,
+            accounts.usdc_test_reserve.user_collateral_pubkey,
+            accounts.usdc_test_reserve.collateral_supply_pubkey,
+            accounts.usdc_test_reserve.pubkey,
+            accounts.test_obligation.pubkey,
+            accounts.market_a.pubkey,
+            accounts.test_obligation.owner,
+            user_transfer_authority.pubkey(),
+            None,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.user_accounts_owner,
+            &user_transfer_authority,
+        ],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::LendingMarketMismatch as u32)
+        )
+    );
+}
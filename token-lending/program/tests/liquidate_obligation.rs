@@ -681,7 +681,7 @@ async fn test_fail() {
             .unwrap(),
         TransactionError::InstructionError(
             2,
-            InstructionError::Custom(LendingError::InvalidStakingPool as u32),
+            InstructionError::Custom(LendingError::MissingStakingAccounts as u32),
         )
     );
 }
@@ -827,3 +827,135 @@ async fn test_fail2() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_fail_reserves_not_in_obligation() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // 100 SOL collateral
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    // 100 SOL * 80% LTV -> 80 SOL * 20 USDC -> 1600 USDC borrow
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_600 * FRACTIONAL_TO_USDC;
+    // 1600 USDC * 50% -> 800 USDC liquidation
+    const USDC_LIQUIDATION_AMOUNT_FRACTIONAL: u64 = USDC_BORROW_AMOUNT_FRACTIONAL / 2;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+    reserve_config.liquidation_threshold = 80;
+    reserve_config.liquidation_bonus = 10;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            user_liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let liquidate = |repay_reserve: &TestReserve, withdraw_reserve: &TestReserve| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                refresh_obligation(
+                    port_finance_variable_rate_lending::id(),
+                    test_obligation.pubkey,
+                    vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+                ),
+                liquidate_obligation(
+                    port_finance_variable_rate_lending::id(),
+                    USDC_LIQUIDATION_AMOUNT_FRACTIONAL,
+                    repay_reserve.user_liquidity_pubkey,
+                    withdraw_reserve.user_collateral_pubkey,
+                    repay_reserve.pubkey,
+                    repay_reserve.liquidity_supply_pubkey,
+                    withdraw_reserve.pubkey,
+                    withdraw_reserve.collateral_supply_pubkey,
+                    test_obligation.pubkey,
+                    lending_market.pubkey,
+                    user_transfer_authority.pubkey(),
+                    None,
+                    None,
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_transfer_authority], recent_blockhash);
+        transaction
+    };
+
+    // the obligation has no SOL deposit in the USDC reserve to seize
+    assert_eq!(
+        banks_client
+            .process_transaction(liquidate(&usdc_test_reserve, &usdc_test_reserve))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ObligationCollateralEmpty as u32),
+        )
+    );
+
+    // the obligation has not borrowed from the SOL reserve
+    assert_eq!(
+        banks_client
+            .process_transaction(liquidate(&sol_test_reserve, &sol_test_reserve))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ObligationLiquidityEmpty as u32),
+        )
+    );
+}
@@ -0,0 +1,311 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::instruction::approve;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        liquidate_obligation, refresh_obligation, refresh_reserve, set_liquidation_grace_slots,
+    },
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+
+mod helpers;
+
+// 100 SOL collateral
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+// 100 SOL * 80% LTV -> 80 SOL * 20 USDC -> 1600 USDC borrow
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_600 * FRACTIONAL_TO_USDC;
+// 1600 USDC * 50% -> 800 USDC liquidation
+const USDC_LIQUIDATION_AMOUNT_FRACTIONAL: u64 = USDC_BORROW_AMOUNT_FRACTIONAL / 2;
+const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+const LIQUIDATION_GRACE_SLOTS: u64 = 2;
+
+struct GraceAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+impl GraceAccounts {
+    fn refresh_obligation(&self) -> Instruction {
+        refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            self.test_obligation.pubkey,
+            vec![self.sol_test_reserve.pubkey, self.usdc_test_reserve.pubkey],
+        )
+    }
+
+    fn liquidate_obligation(&self, user_transfer_authority: &Keypair) -> Instruction {
+        liquidate_obligation(
+            port_finance_variable_rate_lending::id(),
+            USDC_LIQUIDATION_AMOUNT_FRACTIONAL,
+            self.usdc_test_reserve.user_liquidity_pubkey,
+            self.sol_test_reserve.user_collateral_pubkey,
+            self.usdc_test_reserve.pubkey,
+            self.usdc_test_reserve.liquidity_supply_pubkey,
+            self.sol_test_reserve.pubkey,
+            self.sol_test_reserve.collateral_supply_pubkey,
+            self.test_obligation.pubkey,
+            self.lending_market.pubkey,
+            user_transfer_authority.pubkey(),
+            None,
+            None,
+        )
+    }
+}
+
+/// An obligation that is unhealthy as soon as it is refreshed
+fn setup() -> (ProgramTest, GraceAccounts) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+    reserve_config.liquidation_threshold = 80;
+    reserve_config.liquidation_bonus = 10;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        GraceAccounts {
+            user_accounts_owner,
+            lending_market,
+            sol_test_reserve,
+            usdc_test_reserve,
+            test_obligation,
+        },
+    )
+}
+
+async fn set_grace_slots(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    lending_market: &TestLendingMarket,
+    recent_blockhash: solana_sdk::hash::Hash,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[set_liquidation_grace_slots(
+            port_finance_variable_rate_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            LIQUIDATION_GRACE_SLOTS,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let market = lending_market.get_state(banks_client).await;
+    assert_eq!(market.liquidation_grace_slots, LIQUIDATION_GRACE_SLOTS);
+}
+
+#[tokio::test]
+async fn test_fail_within_grace_period() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_grace_slots(
+        &mut banks_client,
+        &payer,
+        &accounts.lending_market,
+        recent_blockhash,
+    )
+    .await;
+
+    // the refresh marks the obligation unhealthy in this very slot
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            accounts.refresh_obligation(),
+            accounts.liquidate_obligation(&user_transfer_authority),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_transfer_authority], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::LiquidationNotYetAllowed as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_success_after_grace_period() {
+    let (test, accounts) = setup();
+    let mut test_context = test.start_with_context().await;
+    let payer = Keypair::from_bytes(&test_context.payer.to_bytes()).unwrap();
+    set_grace_slots(
+        &mut test_context.banks_client,
+        &payer,
+        &accounts.lending_market,
+        test_context.last_blockhash,
+    )
+    .await;
+
+    let mut transaction =
+        Transaction::new_with_payer(&[accounts.refresh_obligation()], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], test_context.last_blockhash);
+    assert!(test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_ok());
+    let became_unhealthy_slot = accounts
+        .test_obligation
+        .get_state(&mut test_context.banks_client)
+        .await
+        .became_unhealthy_slot;
+    assert_ne!(became_unhealthy_slot, 0);
+
+    test_context
+        .warp_to_slot(became_unhealthy_slot + LIQUIDATION_GRACE_SLOTS)
+        .unwrap();
+
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &accounts.usdc_test_reserve.user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &accounts.user_accounts_owner.pubkey(),
+                &[],
+                USDC_LIQUIDATION_AMOUNT_FRACTIONAL,
+            )
+            .unwrap(),
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                accounts.usdc_test_reserve.pubkey,
+                accounts.usdc_test_reserve.liquidity_oracle_pubkey,
+            ),
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                accounts.sol_test_reserve.pubkey,
+                accounts.sol_test_reserve.liquidity_oracle_pubkey,
+            ),
+            accounts.refresh_obligation(),
+            accounts.liquidate_obligation(&user_transfer_authority),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.user_accounts_owner,
+            &user_transfer_authority,
+        ],
+        test_context.last_blockhash,
+    );
+    assert!(test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_ok());
+
+    let obligation = accounts
+        .test_obligation
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        (USDC_BORROW_AMOUNT_FRACTIONAL - USDC_LIQUIDATION_AMOUNT_FRACTIONAL).into()
+    );
+}
+
+#[tokio::test]
+async fn test_fail_set_grace_slots_wrong_owner() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let not_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_liquidation_grace_slots(
+            port_finance_variable_rate_lending::id(),
+            accounts.lending_market.pubkey,
+            not_owner.pubkey(),
+            LIQUIDATION_GRACE_SLOTS,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &not_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
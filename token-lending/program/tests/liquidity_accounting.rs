@@ -0,0 +1,261 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::Instruction,
+    program_option::COption,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token::instruction::approve;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    instruction::{
+        borrow_obligation_liquidity, deposit_reserve_liquidity, redeem_reserve_collateral,
+        refresh_obligation, refresh_reserve, repay_obligation_liquidity,
+    },
+    math::{Decimal, TryAdd},
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+
+mod helpers;
+
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_COLLATERAL_AMOUNT: u64 =
+    USDC_RESERVE_LIQUIDITY_FRACTIONAL * INITIAL_COLLATERAL_RATIO;
+const OBLIGATION_COLLATERAL_AMOUNT: u64 = USDC_RESERVE_COLLATERAL_AMOUNT / 2;
+
+#[derive(Clone, Copy, Debug)]
+enum Operation {
+    Deposit(u64),
+    Borrow(u64),
+    Repay(u64),
+    Redeem(u64),
+}
+
+/// Borrowing from a reserve against collateral from the same reserve, then repaying, redeeming and
+/// depositing. The reserve's liquidity accounting must match its supply account after each step.
+#[tokio::test]
+async fn test_liquidity_accounting_matches_supply() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            collateral_amount: USDC_RESERVE_COLLATERAL_AMOUNT,
+            user_liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            fixed_price: COption::Some(Decimal::one()),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&usdc_test_reserve, OBLIGATION_COLLATERAL_AMOUNT)],
+            mark_fresh: true,
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+    check_liquidity_accounting(
+        &mut banks_client,
+        &usdc_test_reserve,
+        &test_obligation,
+        None,
+    )
+    .await;
+
+    let operations = [
+        Operation::Deposit(100 * FRACTIONAL_TO_USDC),
+        Operation::Borrow(200 * FRACTIONAL_TO_USDC),
+        Operation::Repay(50 * FRACTIONAL_TO_USDC),
+        Operation::Redeem(50 * FRACTIONAL_TO_USDC * INITIAL_COLLATERAL_RATIO),
+        Operation::Borrow(10 * FRACTIONAL_TO_USDC),
+        Operation::Repay(u64::MAX),
+        Operation::Deposit(FRACTIONAL_TO_USDC),
+    ];
+    for (index, operation) in operations.iter().enumerate() {
+        let obligation = test_obligation.get_state(&mut banks_client).await;
+        let obligation_reserves = obligation
+            .deposits
+            .iter()
+            .map(|collateral| collateral.deposit_reserve)
+            .chain(
+                obligation
+                    .borrows
+                    .iter()
+                    .map(|liquidity| liquidity.borrow_reserve),
+            )
+            .collect();
+        let refresh_instructions = vec![
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_oracle_pubkey,
+            ),
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                obligation_reserves,
+            ),
+        ];
+
+        let user_transfer_authority = Keypair::new();
+        let approve_instruction = |source: &_, amount| {
+            approve(
+                &spl_token::id(),
+                source,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                amount,
+            )
+            .unwrap()
+        };
+        let operation_instruction: Vec<Instruction> = match *operation {
+            Operation::Deposit(liquidity_amount) => vec![
+                approve_instruction(&usdc_test_reserve.user_liquidity_pubkey, liquidity_amount),
+                deposit_reserve_liquidity(
+                    port_finance_variable_rate_lending::id(),
+                    liquidity_amount,
+                    usdc_test_reserve.user_liquidity_pubkey,
+                    usdc_test_reserve.user_collateral_pubkey,
+                    usdc_test_reserve.pubkey,
+                    usdc_test_reserve.liquidity_supply_pubkey,
+                    usdc_test_reserve.collateral_mint_pubkey,
+                    lending_market.pubkey,
+                    user_transfer_authority.pubkey(),
+                ),
+            ],
+            Operation::Borrow(liquidity_amount) => vec![
+                // keeps the transfer authority a signer of every transaction
+                approve_instruction(&usdc_test_reserve.user_liquidity_pubkey, 0),
+                borrow_obligation_liquidity(
+                    port_finance_variable_rate_lending::id(),
+                    liquidity_amount,
+                    usdc_test_reserve.liquidity_supply_pubkey,
+                    usdc_test_reserve.user_liquidity_pubkey,
+                    usdc_test_reserve.pubkey,
+                    usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                    test_obligation.pubkey,
+                    lending_market.pubkey,
+                    test_obligation.owner,
+                    None,
+                    None,
+                ),
+            ],
+            Operation::Repay(liquidity_amount) => vec![
+                approve_instruction(&usdc_test_reserve.user_liquidity_pubkey, liquidity_amount),
+                repay_obligation_liquidity(
+                    port_finance_variable_rate_lending::id(),
+                    liquidity_amount,
+                    usdc_test_reserve.user_liquidity_pubkey,
+                    usdc_test_reserve.liquidity_supply_pubkey,
+                    usdc_test_reserve.pubkey,
+                    test_obligation.pubkey,
+                    lending_market.pubkey,
+                    user_transfer_authority.pubkey(),
+                ),
+            ],
+            Operation::Redeem(collateral_amount) => vec![
+                approve_instruction(&usdc_test_reserve.user_collateral_pubkey, collateral_amount),
+                redeem_reserve_collateral(
+                    port_finance_variable_rate_lending::id(),
+                    collateral_amount,
+                    usdc_test_reserve.user_collateral_pubkey,
+                    usdc_test_reserve.user_liquidity_pubkey,
+                    usdc_test_reserve.pubkey,
+                    usdc_test_reserve.collateral_mint_pubkey,
+                    usdc_test_reserve.liquidity_supply_pubkey,
+                    lending_market.pubkey,
+                    user_transfer_authority.pubkey(),
+                ),
+            ],
+        };
+
+        let mut transaction = Transaction::new_with_payer(
+            &[refresh_instructions, operation_instruction].concat(),
+            Some(&payer.pubkey()),
+        );
+        let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+        transaction.sign(
+            &[&payer, &user_accounts_owner, &user_transfer_authority],
+            recent_blockhash,
+        );
+        assert!(
+            banks_client.process_transaction(transaction).await.is_ok(),
+            "operation {} ({:?}) failed",
+            index,
+            operation
+        );
+
+        check_liquidity_accounting(
+            &mut banks_client,
+            &usdc_test_reserve,
+            &test_obligation,
+            Some((index, *operation)),
+        )
+        .await;
+    }
+}
+
+/// Available liquidity plus borrows recorded by the reserve must equal the liquidity supply
+/// balance plus the obligation's outstanding borrows, the only borrows from the reserve
+async fn check_liquidity_accounting(
+    banks_client: &mut BanksClient,
+    reserve: &TestReserve,
+    obligation: &TestObligation,
+    operation: Option<(usize, Operation)>,
+) {
+    let reserve_state = reserve.get_state(banks_client).await;
+    let obligation_state = obligation.get_state(banks_client).await;
+    let supply_balance = get_token_balance(banks_client, reserve.liquidity_supply_pubkey).await;
+
+    let outstanding_borrows = obligation_state
+        .borrows
+        .iter()
+        .try_fold(Decimal::zero(), |total, liquidity| {
+            total.try_add(liquidity.borrowed_amount_wads)
+        })
+        .unwrap();
+
+    let recorded = reserve_state.liquidity.available_amount
+        + reserve_state
+            .liquidity
+            .borrowed_amount_wads
+            .try_ceil_u64()
+            .unwrap();
+    let expected = supply_balance + outstanding_borrows.try_ceil_u64().unwrap();
+    assert_eq!(
+        recorded,
+        expected,
+        "reserve liquidity accounting diverged from the supply account after {}",
+        operation.map_or("setup".to_string(), |(index, operation)| format!(
+            "operation {} ({:?})",
+            index, operation
+        ))
+    );
+}
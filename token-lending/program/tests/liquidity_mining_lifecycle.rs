@@ -0,0 +1,278 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token::{
+    instruction::approve,
+    state::{Account as Token, AccountState},
+};
+
+use helpers::*;
+use port_finance_staking::instruction::claim_reward;
+use port_finance_staking::solana_program::program_option::COption;
+use port_finance_staking::solana_program::pubkey::PUBKEY_BYTES;
+use port_finance_variable_rate_lending::{
+    instruction::{
+        deposit_reserve_liquidity_and_obligation_collateral, refresh_obligation, refresh_reserve,
+        withdraw_obligation_collateral,
+    },
+    math::Decimal,
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+
+mod helpers;
+
+// `add_staking_pool` funds the pool with 100 reward tokens paid out over 1000 slots
+const REWARD_SUPPLY: u64 = 100;
+const REWARD_DURATION: u64 = 1000;
+
+#[tokio::test]
+async fn test_deposit_claim_withdraw_reward_lifecycle() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+    test.prefer_bpf(false);
+    test.add_program(
+        "port_finance_staking",
+        port_finance_staking::id(),
+        processor!(port_finance_staking::processor::process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+
+    let lending_market = add_lending_market(&mut test);
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market.pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &port_finance_variable_rate_lending::id(),
+    );
+    let staking_pool = add_staking_pool(&mut test, lending_market_authority_pubkey, 0);
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: ReserveConfig {
+                deposit_staking_pool: COption::Some(staking_pool.staking_pool_pubkey),
+                ..TEST_RESERVE_CONFIG
+            },
+            fixed_price: COption::Some(Decimal::from(20u64)),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+    let stake_account: TestStakeAccount = add_stake_account(
+        &mut test,
+        staking_pool.staking_pool_pubkey,
+        &user_accounts_owner,
+        0,
+    );
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+
+    let reward_destination_pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        reward_destination_pubkey,
+        u32::MAX as u64,
+        &Token {
+            mint: staking_pool.reward_token_mint_pubkey,
+            owner: user_accounts_owner.pubkey(),
+            state: AccountState::Initialized,
+            ..Token::default()
+        },
+        &spl_token::id(),
+    );
+
+    let mut test_context = test.start_with_context().await;
+    let payer = Keypair::from_bytes(&test_context.payer.to_bytes()).unwrap();
+    let reward_token_pool = staking_pool.staking_pool.reward_token_pool;
+
+    // depositing collateral stakes it and starts the reward period
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &sol_test_reserve.user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            )
+            .unwrap(),
+            approve(
+                &spl_token::id(),
+                &sol_test_reserve.user_collateral_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            )
+            .unwrap(),
+            deposit_reserve_liquidity_and_obligation_collateral(
+                port_finance_variable_rate_lending::id(),
+                SOL_DEPOSIT_AMOUNT_LAMPORTS,
+                sol_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.liquidity_supply_pubkey,
+                sol_test_reserve.collateral_mint_pubkey,
+                sol_test_reserve.lending_market_pubkey,
+                sol_test_reserve.collateral_supply_pubkey,
+                test_obligation.pubkey,
+                test_obligation.owner,
+                user_transfer_authority.pubkey(),
+                Some(stake_account.pubkey),
+                Some(staking_pool.staking_pool_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        test_context.last_blockhash,
+    );
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let pool = staking_pool.get_state(&mut test_context.banks_client).await;
+    let stake = stake_account
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(pool.pool_size, SOL_DEPOSIT_AMOUNT_LAMPORTS);
+    assert_eq!(stake.deposited_amount, SOL_DEPOSIT_AMOUNT_LAMPORTS);
+    let start_slot = pool.last_update;
+    assert_eq!(pool.end_time, start_slot + REWARD_DURATION);
+
+    let claim = |recent_blockhash| {
+        let mut transaction = Transaction::new_with_payer(
+            &[claim_reward(
+                port_finance_staking::id(),
+                user_accounts_owner.pubkey(),
+                stake_account.pubkey,
+                staking_pool.staking_pool_pubkey,
+                reward_token_pool,
+                None,
+                reward_destination_pubkey,
+                None,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    // the only staker earns the whole reward rate while staked
+    test_context.warp_to_slot(start_slot + 400).unwrap();
+    test_context
+        .banks_client
+        .process_transaction(claim(test_context.last_blockhash))
+        .await
+        .unwrap();
+    let claimed =
+        get_token_balance(&mut test_context.banks_client, reward_destination_pubkey).await;
+    let expected = REWARD_SUPPLY * 400 / REWARD_DURATION;
+    assert!(claimed <= expected && expected - claimed <= 1);
+    assert_eq!(
+        get_token_balance(&mut test_context.banks_client, reward_token_pool).await,
+        REWARD_SUPPLY - claimed
+    );
+
+    // withdrawing all of the collateral unstakes it
+    test_context.warp_to_slot(start_slot + 600).unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                sol_test_reserve.pubkey,
+                COption::None,
+            ),
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            withdraw_obligation_collateral(
+                port_finance_variable_rate_lending::id(),
+                SOL_DEPOSIT_AMOUNT_LAMPORTS,
+                sol_test_reserve.collateral_supply_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                Some(stake_account.pubkey),
+                Some(staking_pool.staking_pool_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], test_context.last_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let pool = staking_pool.get_state(&mut test_context.banks_client).await;
+    let stake = stake_account
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(pool.pool_size, 0);
+    assert_eq!(stake.deposited_amount, 0);
+    assert_eq!(
+        get_token_balance(
+            &mut test_context.banks_client,
+            sol_test_reserve.user_collateral_pubkey
+        )
+        .await,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS
+    );
+
+    // rewards earned until the withdrawal can still be claimed, nothing accrues afterwards
+    test_context.warp_to_slot(start_slot + 900).unwrap();
+    test_context
+        .banks_client
+        .process_transaction(claim(test_context.last_blockhash))
+        .await
+        .unwrap();
+    let claimed =
+        get_token_balance(&mut test_context.banks_client, reward_destination_pubkey).await;
+    let expected = REWARD_SUPPLY * 600 / REWARD_DURATION;
+    assert!(claimed <= expected && expected - claimed <= 1);
+
+    let stake = stake_account
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(
+        stake.unclaimed_reward_wads.reward.try_floor_u64().unwrap(),
+        0
+    );
+
+    // the rewards for the slots nobody was staked stay in the pool
+    assert_eq!(
+        get_token_balance(&mut test_context.banks_client, reward_token_pool).await,
+        REWARD_SUPPLY - claimed
+    );
+}
@@ -70,6 +70,22 @@ async fn test_success() {
             host_fee_percentage: 20,
         },
         deposit_staking_pool: COption::None,
+        use_twap: false,
+        twap_window_slots: 0,
+        borrow_fee_discount_staking_pool: COption::None,
+        borrow_fee_discount_percentage: 0,
+        max_accrual_slots: 0,
+        borrowing_disabled: false,
+        liquidation_bonus_ramp_slots: 0,
+        min_initial_deposit: 0,
+        max_price_staleness_slots: 0,
+        max_slot_liquidity_change_percent: 0,
+        max_oracle_deviation_bps: 0,
+        isolated_debt_ceiling: 0,
+        collateral_disabled: false,
+        max_liquidation_bonus: 0,
+        borrow_fee_discount_min_stake: 0,
+        min_liquidation_bonus: 0,
     };
 
     // oracle price doesn't matter so using usdc oracle for ease of computation
@@ -106,6 +122,22 @@ async fn test_success() {
             host_fee_percentage: 20,
         },
         deposit_staking_pool: COption::None,
+        use_twap: false,
+        twap_window_slots: 0,
+        borrow_fee_discount_staking_pool: COption::None,
+        borrow_fee_discount_percentage: 0,
+        max_accrual_slots: 0,
+        borrowing_disabled: false,
+        liquidation_bonus_ramp_slots: 0,
+        min_initial_deposit: 0,
+        max_price_staleness_slots: 0,
+        max_slot_liquidity_change_percent: 0,
+        max_oracle_deviation_bps: 0,
+        isolated_debt_ceiling: 0,
+        collateral_disabled: false,
+        max_liquidation_bonus: 0,
+        borrow_fee_discount_min_stake: 0,
+        min_liquidation_bonus: 0,
     };
     let usdc_mint = add_usdc_mint(&mut test);
     let usdc_oracle = add_usdc_pyth_oracle(&mut test);
@@ -234,6 +266,8 @@ async fn test_success() {
                 obligation_pubkey,
                 lending_market.pubkey,
                 user_accounts_owner_pubkey,
+                None,
+                None,
             ),
             // 11
             refresh_reserve(
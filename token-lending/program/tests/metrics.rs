@@ -0,0 +1,109 @@
+#![cfg(all(feature = "test-bpf", feature = "metrics"))]
+
+use solana_program::{instruction::Instruction, program_option::COption};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{refresh_reserve, set_slots_per_year},
+    math::Decimal,
+    metrics,
+    processor::process_instruction,
+};
+
+mod helpers;
+
+const REFRESH_RESERVE_TAG: u8 = 3;
+const SET_SLOTS_PER_YEAR_TAG: u8 = 36;
+
+#[tokio::test]
+async fn test_counts_instructions_and_errors() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            fixed_price: COption::Some(Decimal::one()),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    metrics::reset();
+
+    let refresh = refresh_reserve(
+        port_finance_variable_rate_lending::id(),
+        usdc_test_reserve.pubkey,
+        COption::None,
+    );
+    let mut transaction =
+        Transaction::new_with_payer(&[refresh.clone(), refresh], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let not_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_slots_per_year(
+            port_finance_variable_rate_lending::id(),
+            lending_market.pubkey,
+            not_owner.pubkey(),
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &not_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[Instruction {
+            program_id: port_finance_variable_rate_lending::id(),
+            accounts: vec![],
+            data: vec![u8::MAX],
+        }],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    assert_eq!(metrics::instruction_count(REFRESH_RESERVE_TAG), 2);
+    assert_eq!(metrics::instruction_count(SET_SLOTS_PER_YEAR_TAG), 1);
+    assert_eq!(metrics::instruction_count(u8::MAX), 1);
+    assert_eq!(metrics::error_count(LendingError::InvalidMarketOwner), 1);
+    assert_eq!(
+        metrics::error_count(LendingError::InstructionUnpackError),
+        1
+    );
+    assert_eq!(metrics::error_count(LendingError::ReserveStale), 0);
+    assert_eq!(metrics::other_error_count(), 0);
+}
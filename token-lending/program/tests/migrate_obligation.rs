@@ -0,0 +1,199 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey, rent::Rent};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::migrate_obligation,
+    math::Decimal,
+    processor::process_instruction,
+    state::{
+        offsets::{obligation, obligation_collateral, obligation_liquidity},
+        InitObligationParams, LastUpdate, Obligation, ObligationCollateral, ObligationLiquidity,
+        LEGACY_OBLIGATION_LEN,
+    },
+};
+
+mod helpers;
+
+fn test_obligation() -> Obligation {
+    let mut obligation = Obligation::new(InitObligationParams {
+        current_slot: 1,
+        lending_market: Pubkey::new_unique(),
+        owner: Pubkey::new_unique(),
+        deposits: vec![ObligationCollateral {
+            deposited_amount: 1_000,
+            ..ObligationCollateral::new(Pubkey::new_unique())
+        }],
+        borrows: vec![ObligationLiquidity {
+            cumulative_borrow_rate_wads: Decimal::one(),
+            borrowed_amount_wads: Decimal::from(500u64),
+            ..ObligationLiquidity::new(Pubkey::new_unique())
+        }],
+    });
+    obligation.last_update = LastUpdate::new(1);
+    obligation
+}
+
+/// Packs an obligation in the layout used before borrows carried a collateral binding and
+/// before the trailing `became_unhealthy_slot`
+fn pack_legacy_obligation(obligation: &Obligation) -> Vec<u8> {
+    let mut packed = vec![0u8; Obligation::LEN];
+    Obligation::pack(obligation.clone(), &mut packed).unwrap();
+
+    let deposits_end =
+        obligation::DATA_FLAT + obligation.deposits.len() * obligation_collateral::LEN;
+    let mut legacy = packed[..deposits_end].to_vec();
+    for i in 0..obligation.borrows.len() {
+        let borrow = deposits_end + i * obligation_liquidity::LEN;
+        legacy
+            .extend_from_slice(&packed[borrow..borrow + obligation_liquidity::COLLATERAL_RESERVE]);
+    }
+    legacy.resize(LEGACY_OBLIGATION_LEN, 0);
+    legacy
+}
+
+fn add_legacy_obligation(test: &mut ProgramTest, obligation: &Obligation, owner: Pubkey) -> Pubkey {
+    let obligation_pubkey = Pubkey::new_unique();
+    test.add_account(
+        obligation_pubkey,
+        Account {
+            lamports: Rent::default().minimum_balance(LEGACY_OBLIGATION_LEN),
+            data: pack_legacy_obligation(obligation),
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    obligation_pubkey
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let obligation = test_obligation();
+    let obligation_pubkey = add_legacy_obligation(
+        &mut test,
+        &obligation,
+        port_finance_variable_rate_lending::id(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate_obligation(
+            port_finance_variable_rate_lending::id(),
+            obligation_pubkey,
+            payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation_account = banks_client
+        .get_account(obligation_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(obligation_account.data.len(), Obligation::LEN);
+    assert!(Rent::default().is_exempt(obligation_account.lamports, Obligation::LEN));
+
+    let migrated = Obligation::unpack(&obligation_account.data).unwrap();
+    assert_eq!(migrated, obligation);
+    assert_eq!(migrated.borrows[0].collateral_reserve, COption::None);
+    assert_eq!(migrated.became_unhealthy_slot, 0);
+}
+
+#[tokio::test]
+async fn test_already_migrated() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let obligation = Obligation {
+        became_unhealthy_slot: 7,
+        ..test_obligation()
+    };
+    let obligation_pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        obligation_pubkey,
+        u32::MAX as u64,
+        &obligation,
+        &port_finance_variable_rate_lending::id(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate_obligation(
+            port_finance_variable_rate_lending::id(),
+            obligation_pubkey,
+            payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation_account = banks_client
+        .get_account(obligation_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(obligation_account.lamports, u32::MAX as u64);
+    assert_eq!(
+        Obligation::unpack(&obligation_account.data).unwrap(),
+        obligation
+    );
+}
+
+#[tokio::test]
+async fn test_fail_invalid_account_owner() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let obligation_pubkey =
+        add_legacy_obligation(&mut test, &test_obligation(), Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate_obligation(
+            port_finance_variable_rate_lending::id(),
+            obligation_pubkey,
+            payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountOwner as u32)
+        )
+    );
+}
@@ -0,0 +1,172 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program::{program_pack::Pack, pubkey::Pubkey, rent::Rent};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::migrate_reserve,
+    processor::process_instruction,
+    state::{LastUpdate, Reserve, LEGACY_RESERVE_LEN, PROGRAM_VERSION},
+};
+
+mod helpers;
+
+fn test_reserve() -> Reserve {
+    let mut reserve = Reserve {
+        version: PROGRAM_VERSION,
+        last_update: LastUpdate::new(1),
+        lending_market: Pubkey::new_unique(),
+        ..Reserve::default()
+    };
+    reserve.liquidity.mint_pubkey = Pubkey::new_unique();
+    reserve.liquidity.available_amount = 1_000;
+    reserve.collateral.mint_pubkey = Pubkey::new_unique();
+    reserve.collateral.mint_total_supply = 1_000;
+    reserve
+}
+
+/// Packs a reserve in the layout used before the protocol owned amount was appended
+fn add_legacy_reserve(test: &mut ProgramTest, reserve: &Reserve, owner: Pubkey) -> Pubkey {
+    let mut packed = vec![0u8; Reserve::LEN];
+    Reserve::pack(reserve.clone(), &mut packed).unwrap();
+    packed.truncate(LEGACY_RESERVE_LEN);
+
+    let reserve_pubkey = Pubkey::new_unique();
+    test.add_account(
+        reserve_pubkey,
+        Account {
+            lamports: Rent::default().minimum_balance(LEGACY_RESERVE_LEN),
+            data: packed,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    reserve_pubkey
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let reserve = test_reserve();
+    let reserve_pubkey = add_legacy_reserve(
+        &mut test,
+        &reserve,
+        port_finance_variable_rate_lending::id(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate_reserve(
+            port_finance_variable_rate_lending::id(),
+            reserve_pubkey,
+            payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let reserve_account = banks_client
+        .get_account(reserve_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reserve_account.data.len(), Reserve::LEN);
+    assert!(Rent::default().is_exempt(reserve_account.lamports, Reserve::LEN));
+
+    let migrated = Reserve::unpack(&reserve_account.data).unwrap();
+    assert_eq!(migrated, reserve);
+    assert_eq!(migrated.liquidity.protocol_owned_amount, 0);
+    assert!(!migrated.is_wind_down);
+}
+
+#[tokio::test]
+async fn test_already_migrated() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let reserve = Reserve {
+        is_wind_down: true,
+        ..test_reserve()
+    };
+    let reserve_pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        reserve_pubkey,
+        u32::MAX as u64,
+        &reserve,
+        &port_finance_variable_rate_lending::id(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate_reserve(
+            port_finance_variable_rate_lending::id(),
+            reserve_pubkey,
+            payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let reserve_account = banks_client
+        .get_account(reserve_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reserve_account.lamports, u32::MAX as u64);
+    assert_eq!(Reserve::unpack(&reserve_account.data).unwrap(), reserve);
+}
+
+#[tokio::test]
+async fn test_fail_invalid_account_owner() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let reserve_pubkey = add_legacy_reserve(&mut test, &test_reserve(), Pubkey::new_unique());
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate_reserve(
+            port_finance_variable_rate_lending::id(),
+            reserve_pubkey,
+            payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountOwner as u32)
+        )
+    );
+}
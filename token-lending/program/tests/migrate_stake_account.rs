@@ -0,0 +1,263 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    program_option::COption,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{migrate_stake_account, update_reserve},
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+
+mod helpers;
+
+#[tokio::test]
+async fn test_migrate_stake_account() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+    test.prefer_bpf(false);
+    test.add_program(
+        "port_finance_staking",
+        port_finance_staking::id(),
+        processor!(port_finance_staking::processor::process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let old_staking_pool = add_staking_pool(
+        &mut test,
+        lending_market.authority,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS,
+    );
+    let new_staking_pool = add_staking_pool(&mut test, lending_market.authority, 0);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: ReserveConfig {
+                deposit_staking_pool: COption::Some(old_staking_pool.staking_pool_pubkey),
+                ..TEST_RESERVE_CONFIG
+            },
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let old_stake_account = add_stake_account(
+        &mut test,
+        old_staking_pool.staking_pool_pubkey,
+        &user_accounts_owner,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS,
+    );
+    let new_stake_account = add_stake_account(
+        &mut test,
+        new_staking_pool.staking_pool_pubkey,
+        &user_accounts_owner,
+        0,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let migrate = |recent_blockhash| {
+        let mut transaction = Transaction::new_with_payer(
+            &[migrate_stake_account(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                sol_test_reserve.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                old_stake_account.pubkey,
+                old_staking_pool.staking_pool_pubkey,
+                new_stake_account.pubkey,
+                new_staking_pool.staking_pool_pubkey,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    // the reserve still uses the old staking pool
+    assert_eq!(
+        banks_client
+            .process_transaction(migrate(recent_blockhash))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidStakingPool as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[update_reserve(
+            port_finance_variable_rate_lending::id(),
+            ReserveConfig {
+                deposit_staking_pool: COption::Some(new_staking_pool.staking_pool_pubkey),
+                ..sol_test_reserve.config
+            },
+            sol_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let reserve = sol_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        reserve.previous_deposit_staking_pool,
+        COption::Some(old_staking_pool.staking_pool_pubkey)
+    );
+
+    // a fresh blockhash keeps the retry from matching the rejected transaction
+    let recent_blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    banks_client
+        .process_transaction(migrate(recent_blockhash))
+        .await
+        .unwrap();
+
+    let old_pool = old_staking_pool.get_state(&mut banks_client).await;
+    let new_pool = new_staking_pool.get_state(&mut banks_client).await;
+    assert_eq!(old_pool.pool_size, 0);
+    assert_eq!(new_pool.pool_size, SOL_DEPOSIT_AMOUNT_LAMPORTS);
+
+    let old_stake = old_stake_account.get_state(&mut banks_client).await;
+    let new_stake = new_stake_account.get_state(&mut banks_client).await;
+    assert_eq!(old_stake.deposited_amount, 0);
+    assert_eq!(new_stake.deposited_amount, SOL_DEPOSIT_AMOUNT_LAMPORTS);
+}
+
+#[tokio::test]
+async fn test_fail_pool_not_previously_used() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+    test.prefer_bpf(false);
+    test.add_program(
+        "port_finance_staking",
+        port_finance_staking::id(),
+        processor!(port_finance_staking::processor::process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    // a pool of the same lending market the reserve never used
+    let other_staking_pool = add_staking_pool(
+        &mut test,
+        lending_market.authority,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS,
+    );
+    let staking_pool = add_staking_pool(&mut test, lending_market.authority, 0);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: ReserveConfig {
+                deposit_staking_pool: COption::Some(staking_pool.staking_pool_pubkey),
+                ..TEST_RESERVE_CONFIG
+            },
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let other_stake_account = add_stake_account(
+        &mut test,
+        other_staking_pool.staking_pool_pubkey,
+        &user_accounts_owner,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS,
+    );
+    let stake_account = add_stake_account(
+        &mut test,
+        staking_pool.staking_pool_pubkey,
+        &user_accounts_owner,
+        0,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate_stake_account(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            sol_test_reserve.pubkey,
+            lending_market.pubkey,
+            test_obligation.owner,
+            other_stake_account.pubkey,
+            other_staking_pool.staking_pool_pubkey,
+            stake_account.pubkey,
+            staking_pool.staking_pool_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+
+    // stake the reserve never had in the other pool can't be moved into its pool
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidStakingPool as u32)
+        )
+    );
+}
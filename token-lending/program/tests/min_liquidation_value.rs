@@ -0,0 +1,408 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::instruction::approve;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        liquidate_obligation, refresh_obligation, refresh_reserve, set_min_liquidation_value,
+    },
+    math::Decimal,
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+
+mod helpers;
+
+// 100 SOL collateral
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+// 100 SOL * 80% LTV -> 80 SOL * 20 USDC -> 1600 USDC borrow
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_600 * FRACTIONAL_TO_USDC;
+// 1600 USDC * 50% -> 800 USDC, the most a single liquidation may repay
+const USDC_LIQUIDATION_AMOUNT_FRACTIONAL: u64 = USDC_BORROW_AMOUNT_FRACTIONAL / 2;
+const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+// 800 USDC at $1
+const MIN_LIQUIDATION_VALUE: u64 = 800;
+
+struct LiquidationAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+impl LiquidationAccounts {
+    fn refresh_obligation(&self) -> Instruction {
+        refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            self.test_obligation.pubkey,
+            vec![self.sol_test_reserve.pubkey, self.usdc_test_reserve.pubkey],
+        )
+    }
+
+    fn liquidate_obligation(
+        &self,
+        liquidity_amount: u64,
+        user_transfer_authority: &Keypair,
+    ) -> Instruction {
+        liquidate_obligation(
+            port_finance_variable_rate_lending::id(),
+            liquidity_amount,
+            self.usdc_test_reserve.user_liquidity_pubkey,
+            self.sol_test_reserve.user_collateral_pubkey,
+            self.usdc_test_reserve.pubkey,
+            self.usdc_test_reserve.liquidity_supply_pubkey,
+            self.sol_test_reserve.pubkey,
+            self.sol_test_reserve.collateral_supply_pubkey,
+            self.test_obligation.pubkey,
+            self.lending_market.pubkey,
+            user_transfer_authority.pubkey(),
+            None,
+            None,
+        )
+    }
+
+    /// Refreshes everything the liquidation relies on and liquidates, approving the repayment
+    fn refresh_and_liquidate(
+        &self,
+        liquidity_amount: u64,
+        user_transfer_authority: &Keypair,
+    ) -> Vec<Instruction> {
+        vec![
+            approve(
+                &spl_token::id(),
+                &self.usdc_test_reserve.user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &self.user_accounts_owner.pubkey(),
+                &[],
+                liquidity_amount,
+            )
+            .unwrap(),
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                self.usdc_test_reserve.pubkey,
+                self.usdc_test_reserve.liquidity_oracle_pubkey,
+            ),
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                self.sol_test_reserve.pubkey,
+                self.sol_test_reserve.liquidity_oracle_pubkey,
+            ),
+            self.refresh_obligation(),
+            self.liquidate_obligation(liquidity_amount, user_transfer_authority),
+        ]
+    }
+}
+
+/// An obligation that is unhealthy as soon as it is refreshed
+fn setup(deposit_amount: u64, borrow_amount: u64) -> (ProgramTest, LiquidationAccounts) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+    reserve_config.liquidation_threshold = 80;
+    reserve_config.liquidation_bonus = 10;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount,
+            user_liquidity_amount: borrow_amount,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, deposit_amount)],
+            borrows: &[(&usdc_test_reserve, borrow_amount)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        LiquidationAccounts {
+            user_accounts_owner,
+            lending_market,
+            sol_test_reserve,
+            usdc_test_reserve,
+            test_obligation,
+        },
+    )
+}
+
+async fn set_min_value(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    lending_market: &TestLendingMarket,
+    recent_blockhash: solana_sdk::hash::Hash,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[set_min_liquidation_value(
+            port_finance_variable_rate_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            Decimal::from(MIN_LIQUIDATION_VALUE),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let market = lending_market.get_state(banks_client).await;
+    assert_eq!(
+        market.min_liquidation_value,
+        Decimal::from(MIN_LIQUIDATION_VALUE)
+    );
+}
+
+#[tokio::test]
+async fn test_fail_below_minimum() {
+    let (test, accounts) = setup(SOL_DEPOSIT_AMOUNT_LAMPORTS, USDC_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_min_value(
+        &mut banks_client,
+        &payer,
+        &accounts.lending_market,
+        recent_blockhash,
+    )
+    .await;
+
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            accounts.refresh_obligation(),
+            accounts.liquidate_obligation(
+                USDC_LIQUIDATION_AMOUNT_FRACTIONAL - FRACTIONAL_TO_USDC,
+                &user_transfer_authority,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_transfer_authority], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::LiquidationTooSmall as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_success_at_minimum() {
+    let (test, accounts) = setup(SOL_DEPOSIT_AMOUNT_LAMPORTS, USDC_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_min_value(
+        &mut banks_client,
+        &payer,
+        &accounts.lending_market,
+        recent_blockhash,
+    )
+    .await;
+
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &accounts
+            .refresh_and_liquidate(USDC_LIQUIDATION_AMOUNT_FRACTIONAL, &user_transfer_authority),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.user_accounts_owner,
+            &user_transfer_authority,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        (USDC_BORROW_AMOUNT_FRACTIONAL - USDC_LIQUIDATION_AMOUNT_FRACTIONAL).into()
+    );
+}
+
+#[tokio::test]
+async fn test_success_full_close_below_minimum() {
+    // a borrow of 1 fractional USDC backed by 62 lamports, worth far less than the minimum,
+    // can only ever be liquidated by closing it out
+    let (test, accounts) = setup(62 * INITIAL_COLLATERAL_RATIO, 1);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_min_value(
+        &mut banks_client,
+        &payer,
+        &accounts.lending_market,
+        recent_blockhash,
+    )
+    .await;
+
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &accounts.refresh_and_liquidate(u64::MAX, &user_transfer_authority),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.user_accounts_owner,
+            &user_transfer_authority,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.borrows.is_empty());
+}
+
+#[tokio::test]
+async fn test_fail_set_min_liquidation_value_wrong_owner() {
+    let (test, accounts) = setup(SOL_DEPOSIT_AMOUNT_LAMPORTS, USDC_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let not_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_min_liquidation_value(
+            port_finance_variable_rate_lending::id(),
+            accounts.lending_market.pubkey,
+            not_owner.pubkey(),
+            Decimal::zero(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &not_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_success_set_min_liquidation_value() {
+    let (test, accounts) = setup(SOL_DEPOSIT_AMOUNT_LAMPORTS, USDC_BORROW_AMOUNT_FRACTIONAL);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_min_value(
+        &mut banks_client,
+        &payer,
+        &accounts.lending_market,
+        recent_blockhash,
+    )
+    .await;
+
+    // setting it back to 0 disables the minimum
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_min_liquidation_value(
+            port_finance_variable_rate_lending::id(),
+            accounts.lending_market.pubkey,
+            accounts.lending_market.owner.pubkey(),
+            Decimal::zero(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let market = accounts.lending_market.get_state(&mut banks_client).await;
+    assert_eq!(market.min_liquidation_value, Decimal::zero());
+}
+
+#[tokio::test]
+async fn test_success_close_factor_below_minimum() {
+    // 60 SOL * 20 USDC * 80% -> 960 USDC, unhealthy with a 1000 USDC borrow of which the close
+    // factor only allows 500 USDC, below the minimum, to be repaid at once
+    const USDC_SMALL_BORROW_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    let (test, accounts) = setup(
+        60 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO,
+        USDC_SMALL_BORROW_AMOUNT_FRACTIONAL,
+    );
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    set_min_value(
+        &mut banks_client,
+        &payer,
+        &accounts.lending_market,
+        recent_blockhash,
+    )
+    .await;
+
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &accounts.refresh_and_liquidate(u64::MAX, &user_transfer_authority),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.user_accounts_owner,
+            &user_transfer_authority,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        (USDC_SMALL_BORROW_AMOUNT_FRACTIONAL / 2).into()
+    );
+}
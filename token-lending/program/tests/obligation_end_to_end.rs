@@ -177,6 +177,8 @@ async fn test_success() {
                 obligation_pubkey,
                 lending_market.pubkey,
                 user_accounts_owner_pubkey,
+                None,
+                None,
             ),
             // 8
             refresh_reserve(
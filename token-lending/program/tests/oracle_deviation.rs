@@ -0,0 +1,115 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    instruction::refresh_reserve,
+    math::{Decimal, TryDiv, TryMul},
+    processor::process_instruction,
+    state::ReserveConfig,
+};
+
+mod helpers;
+
+const SOL_RESERVE_LIQUIDITY_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL;
+const MAX_ORACLE_DEVIATION_BPS: u16 = 1_000;
+
+/// A SOL reserve whose previous price is `previous_price_percent` of the current oracle price
+fn setup(previous_price_percent: u64) -> (ProgramTest, TestReserve, TestOracle) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let previous_price = sol_oracle
+        .price
+        .try_mul(previous_price_percent)
+        .unwrap()
+        .try_div(100)
+        .unwrap();
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &TestOracle {
+            price: previous_price,
+            ..sol_oracle
+        },
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: SOL_RESERVE_LIQUIDITY_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: ReserveConfig {
+                max_oracle_deviation_bps: MAX_ORACLE_DEVIATION_BPS,
+                ..TEST_RESERVE_CONFIG
+            },
+            ..AddReserveArgs::default()
+        },
+    );
+
+    (test, sol_test_reserve, sol_oracle)
+}
+
+#[tokio::test]
+async fn test_success_within_deviation() {
+    // 5% below the oracle price
+    let (test, sol_test_reserve, sol_oracle) = setup(95);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            sol_test_reserve.pubkey,
+            sol_test_reserve.liquidity_oracle_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let reserve = sol_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(reserve.liquidity.market_price, sol_oracle.price);
+    assert!(!reserve.last_update.stale);
+}
+
+#[tokio::test]
+async fn test_price_jump_pauses_reserve() {
+    // the oracle price doubled since the previous refresh
+    let (test, sol_test_reserve, sol_oracle) = setup(50);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            sol_test_reserve.pubkey,
+            sol_test_reserve.liquidity_oracle_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // the refresh goes through at the previous price, so repayments and redemptions still can
+    let reserve = sol_test_reserve.get_state(&mut banks_client).await;
+    assert!(reserve.is_paused);
+    assert!(!reserve.last_update.stale);
+    assert_eq!(
+        reserve.liquidity.market_price,
+        sol_test_reserve.market_price
+    );
+    assert_ne!(reserve.liquidity.market_price, Decimal::zero());
+    assert_eq!(
+        reserve.liquidity.price_history.latest().unwrap().price,
+        sol_oracle.price
+    );
+}
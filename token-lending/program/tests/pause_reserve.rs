@@ -0,0 +1,599 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    program_option::COption,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, liquidate_obligation, refresh_obligation, refresh_reserve,
+        repay_obligation_liquidity, unpause_reserve, withdraw_obligation_collateral,
+    },
+    math::Decimal,
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+
+mod helpers;
+
+const MAX_PRICE_STALENESS_SLOTS: u64 = 100;
+const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC * INITIAL_COLLATERAL_RATIO;
+const SOL_BORROW_AMOUNT_LAMPORTS: u64 = LAMPORTS_TO_SOL;
+
+struct PausedReserveTest {
+    test_context: ProgramTestContext,
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_oracle: TestOracle,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+/// An obligation borrowing SOL against USDC, with the SOL oracle price stale by the time the
+/// test starts
+async fn setup() -> PausedReserveTest {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: SOL_BORROW_AMOUNT_LAMPORTS,
+            liquidity_amount: 10 * SOL_BORROW_AMOUNT_LAMPORTS,
+            user_liquidity_amount: SOL_BORROW_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: ReserveConfig {
+                max_price_staleness_slots: MAX_PRICE_STALENESS_SLOTS,
+                ..TEST_RESERVE_CONFIG
+            },
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            fixed_price: COption::Some(Decimal::one()),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&usdc_test_reserve, USDC_DEPOSIT_AMOUNT_FRACTIONAL)],
+            borrows: &[(&sol_test_reserve, SOL_BORROW_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    // the oracle price was published at slot 0
+    let mut test_context = test.start_with_context().await;
+    test_context
+        .warp_to_slot(MAX_PRICE_STALENESS_SLOTS + 50)
+        .unwrap();
+
+    PausedReserveTest {
+        test_context,
+        user_accounts_owner,
+        lending_market,
+        sol_oracle,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+    }
+}
+
+fn refresh_instructions(test: &PausedReserveTest) -> Vec<solana_sdk::instruction::Instruction> {
+    vec![
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            test.sol_test_reserve.pubkey,
+            COption::Some(test.sol_oracle.price_pubkey),
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            test.usdc_test_reserve.pubkey,
+            COption::None,
+        ),
+        refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            test.test_obligation.pubkey,
+            vec![test.usdc_test_reserve.pubkey, test.sol_test_reserve.pubkey],
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn test_stale_oracle_pauses_reserve() {
+    let mut test = setup().await;
+    let payer = Keypair::from_bytes(&test.test_context.payer.to_bytes()).unwrap();
+    let recent_blockhash = test.test_context.last_blockhash;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            test.sol_test_reserve.pubkey,
+            COption::Some(test.sol_oracle.price_pubkey),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    test.test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // the refresh succeeds with the last known price
+    let sol_reserve = test
+        .sol_test_reserve
+        .get_state(&mut test.test_context.banks_client)
+        .await;
+    assert!(sol_reserve.is_paused);
+    assert_eq!(sol_reserve.liquidity.market_price, test.sol_oracle.price);
+    assert_eq!(sol_reserve.last_update.slot, MAX_PRICE_STALENESS_SLOTS + 50);
+
+    // borrows are blocked
+    let mut instructions = refresh_instructions(&test);
+    instructions.push(borrow_obligation_liquidity(
+        port_finance_variable_rate_lending::id(),
+        1,
+        test.sol_test_reserve.liquidity_supply_pubkey,
+        test.sol_test_reserve.user_liquidity_pubkey,
+        test.sol_test_reserve.pubkey,
+        test.sol_test_reserve.liquidity_fee_receiver_pubkey,
+        test.test_obligation.pubkey,
+        test.lending_market.pubkey,
+        test.test_obligation.owner,
+        None,
+        None,
+    ));
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &test.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        test.test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::ReservePaused as u32)
+        )
+    );
+
+    // and so are liquidations
+    let mut instructions = refresh_instructions(&test);
+    instructions.push(liquidate_obligation(
+        port_finance_variable_rate_lending::id(),
+        1,
+        test.sol_test_reserve.user_liquidity_pubkey,
+        test.usdc_test_reserve.user_collateral_pubkey,
+        test.sol_test_reserve.pubkey,
+        test.sol_test_reserve.liquidity_supply_pubkey,
+        test.usdc_test_reserve.pubkey,
+        test.usdc_test_reserve.collateral_supply_pubkey,
+        test.test_obligation.pubkey,
+        test.lending_market.pubkey,
+        test.user_accounts_owner.pubkey(),
+        None,
+        None,
+    ));
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &test.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        test.test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::ReservePaused as u32)
+        )
+    );
+
+    // only the lending market owner can unpause
+    let mut transaction = Transaction::new_with_payer(
+        &[unpause_reserve(
+            port_finance_variable_rate_lending::id(),
+            test.sol_test_reserve.pubkey,
+            test.lending_market.pubkey,
+            test.user_accounts_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &test.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        test.test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[unpause_reserve(
+            port_finance_variable_rate_lending::id(),
+            test.sol_test_reserve.pubkey,
+            test.lending_market.pubkey,
+            test.lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &test.lending_market.owner], recent_blockhash);
+    test.test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let sol_reserve = test
+        .sol_test_reserve
+        .get_state(&mut test.test_context.banks_client)
+        .await;
+    assert!(!sol_reserve.is_paused);
+
+    // the next refresh pauses it again while the oracle price is still stale
+    let recent_blockhash = test
+        .test_context
+        .banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            test.sol_test_reserve.pubkey,
+            COption::Some(test.sol_oracle.price_pubkey),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    test.test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let sol_reserve = test
+        .sol_test_reserve
+        .get_state(&mut test.test_context.banks_client)
+        .await;
+    assert!(sol_reserve.is_paused);
+}
+
+#[tokio::test]
+async fn test_repay_paused_reserve() {
+    let mut test = setup().await;
+    let payer = Keypair::from_bytes(&test.test_context.payer.to_bytes()).unwrap();
+    let recent_blockhash = test.test_context.last_blockhash;
+
+    let mut instructions = refresh_instructions(&test);
+    instructions.push(repay_obligation_liquidity(
+        port_finance_variable_rate_lending::id(),
+        SOL_BORROW_AMOUNT_LAMPORTS,
+        test.sol_test_reserve.user_liquidity_pubkey,
+        test.sol_test_reserve.liquidity_supply_pubkey,
+        test.sol_test_reserve.pubkey,
+        test.test_obligation.pubkey,
+        test.lending_market.pubkey,
+        test.user_accounts_owner.pubkey(),
+    ));
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &test.user_accounts_owner], recent_blockhash);
+    test.test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let sol_reserve = test
+        .sol_test_reserve
+        .get_state(&mut test.test_context.banks_client)
+        .await;
+    assert!(sol_reserve.is_paused);
+    let obligation = test
+        .test_obligation
+        .get_state(&mut test.test_context.banks_client)
+        .await;
+    assert_eq!(obligation.borrows.len(), 0);
+}
+
+#[tokio::test]
+async fn test_paused_borrow_reserve_blocks_withdraw() {
+    let mut test = setup().await;
+    let payer = Keypair::from_bytes(&test.test_context.payer.to_bytes()).unwrap();
+    let recent_blockhash = test.test_context.last_blockhash;
+
+    let mut transaction =
+        Transaction::new_with_payer(&refresh_instructions(&test), Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    test.test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // the debt is valued at the last known price, which can't be trusted to be high enough
+    let obligation = test
+        .test_obligation
+        .get_state(&mut test.test_context.banks_client)
+        .await;
+    assert!(obligation.borrowed_value > Decimal::zero());
+    assert!(obligation.unhealthy_borrow_value > Decimal::zero());
+    assert_eq!(obligation.allowed_borrow_value, Decimal::zero());
+
+    // so even a tiny withdrawal of the collateral that isn't paused fails
+    let mut instructions = refresh_instructions(&test);
+    instructions.push(withdraw_obligation_collateral(
+        port_finance_variable_rate_lending::id(),
+        1,
+        test.usdc_test_reserve.collateral_supply_pubkey,
+        test.usdc_test_reserve.user_collateral_pubkey,
+        test.usdc_test_reserve.pubkey,
+        test.test_obligation.pubkey,
+        test.lending_market.pubkey,
+        test.test_obligation.owner,
+        None,
+        None,
+    ));
+    let recent_blockhash = test
+        .test_context
+        .banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &test.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        test.test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::WithdrawTooLarge as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_stale_oracle_fails_refresh_without_max_staleness() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: LAMPORTS_TO_SOL,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    // past the default oracle staleness
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(300).unwrap();
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            sol_test_reserve.pubkey,
+            COption::Some(sol_oracle.price_pubkey),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::OraclePriceStale as u32)
+        )
+    );
+
+    let sol_reserve = sol_test_reserve.get_state(&mut banks_client).await;
+    assert!(!sol_reserve.is_paused);
+}
+
+#[tokio::test]
+async fn test_paused_collateral_gives_no_borrowing_power() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    // the collateral reserve is the one with the stale oracle
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: ReserveConfig {
+                max_price_staleness_slots: MAX_PRICE_STALENESS_SLOTS,
+                ..TEST_RESERVE_CONFIG
+            },
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            fixed_price: COption::Some(Decimal::one()),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    // the oracle price was published at slot 0
+    let mut test_context = test.start_with_context().await;
+    test_context
+        .warp_to_slot(MAX_PRICE_STALENESS_SLOTS + 50)
+        .unwrap();
+    let payer = Keypair::from_bytes(&test_context.payer.to_bytes()).unwrap();
+    let recent_blockhash = test_context.last_blockhash;
+
+    let refresh = vec![
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            sol_test_reserve.pubkey,
+            COption::Some(sol_oracle.price_pubkey),
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            COption::None,
+        ),
+        refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            vec![sol_test_reserve.pubkey],
+        ),
+    ];
+    let mut transaction = Transaction::new_with_payer(&refresh, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // the paused collateral still counts towards health, but not towards borrowing power
+    let sol_reserve = sol_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert!(sol_reserve.is_paused);
+    let obligation = test_obligation
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert!(obligation.deposited_value > Decimal::zero());
+    assert!(obligation.unhealthy_borrow_value > Decimal::zero());
+    assert_eq!(obligation.allowed_borrow_value, Decimal::zero());
+
+    // so borrowing from the reserve that isn't paused fails
+    let mut instructions = refresh;
+    instructions.push(borrow_obligation_liquidity(
+        port_finance_variable_rate_lending::id(),
+        USDC_BORROW_AMOUNT_FRACTIONAL,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.user_liquidity_pubkey,
+        usdc_test_reserve.pubkey,
+        usdc_test_reserve.liquidity_fee_receiver_pubkey,
+        test_obligation.pubkey,
+        lending_market.pubkey,
+        test_obligation.owner,
+        None,
+        None,
+    ));
+    let recent_blockhash = test_context
+        .banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::BorrowTooLarge as u32)
+        )
+    );
+}
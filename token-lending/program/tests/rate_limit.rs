@@ -0,0 +1,202 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{deposit_reserve_liquidity, redeem_reserve_collateral, refresh_reserve},
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+use spl_token::instruction::approve;
+
+mod helpers;
+
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 10_000 * FRACTIONAL_TO_USDC;
+const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 600 * FRACTIONAL_TO_USDC;
+const USDC_REDEEM_COLLATERAL_AMOUNT: u64 =
+    USDC_DEPOSIT_AMOUNT_FRACTIONAL * INITIAL_COLLATERAL_RATIO;
+const MAX_SLOT_LIQUIDITY_CHANGE_PERCENT: u8 = 10;
+
+fn add_rate_limited_reserve(
+    test: &mut ProgramTest,
+    lending_market: &TestLendingMarket,
+    user_accounts_owner: &Keypair,
+) -> TestReserve {
+    let usdc_mint = add_usdc_mint(test);
+    let usdc_oracle = add_usdc_pyth_oracle(test);
+    add_reserve(
+        test,
+        lending_market,
+        &usdc_oracle,
+        user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL * INITIAL_COLLATERAL_RATIO,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            user_liquidity_amount: 2 * USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            config: ReserveConfig {
+                max_slot_liquidity_change_percent: MAX_SLOT_LIQUIDITY_CHANGE_PERCENT,
+                ..TEST_RESERVE_CONFIG
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_fail_deposit_above_limit() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_test_reserve =
+        add_rate_limited_reserve(&mut test, &lending_market, &user_accounts_owner);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // 12% of the reserve's liquidity in one deposit
+    let mut transaction = Transaction::new_with_payer(
+        &[deposit_reserve_liquidity(
+            port_finance_variable_rate_lending::id(),
+            2 * USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            usdc_test_reserve.user_liquidity_pubkey,
+            usdc_test_reserve.user_collateral_pubkey,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            usdc_test_reserve.collateral_mint_pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::RateLimited as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_deposit_and_redeem_across_slots() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_test_reserve =
+        add_rate_limited_reserve(&mut test, &lending_market, &user_accounts_owner);
+
+    let mut test_context = test.start_with_context().await;
+    let payer = Keypair::from_bytes(&test_context.payer.to_bytes()).unwrap();
+
+    lending_market
+        .deposit(
+            &mut test_context.banks_client,
+            &user_accounts_owner,
+            &payer,
+            &usdc_test_reserve,
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+        )
+        .await;
+
+    let redeem_instructions = |user_transfer_authority: &Keypair| {
+        vec![
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserve.user_collateral_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                USDC_REDEEM_COLLATERAL_AMOUNT,
+            )
+            .unwrap(),
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_oracle_pubkey,
+            ),
+            redeem_reserve_collateral(
+                port_finance_variable_rate_lending::id(),
+                USDC_REDEEM_COLLATERAL_AMOUNT,
+                usdc_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                lending_market.pubkey,
+                user_transfer_authority.pubkey(),
+            ),
+        ]
+    };
+
+    // redeeming the deposit in the same slot moves too much liquidity
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &redeem_instructions(&user_transfer_authority),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        test_context.last_blockhash,
+    );
+    assert_eq!(
+        test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::RateLimited as u32)
+        )
+    );
+
+    // but is allowed once the reserve is refreshed in a later slot
+    test_context.warp_to_slot(3).unwrap();
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &redeem_instructions(&user_transfer_authority),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        test_context.last_blockhash,
+    );
+    assert!(test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_ok());
+
+    let reserve = usdc_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(
+        reserve.liquidity.available_amount,
+        USDC_RESERVE_LIQUIDITY_FRACTIONAL
+    );
+}
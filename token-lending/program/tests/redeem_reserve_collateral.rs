@@ -4,14 +4,15 @@ mod helpers;
 
 use helpers::*;
 use port_finance_variable_rate_lending::{
-    instruction::redeem_reserve_collateral, processor::process_instruction,
+    error::LendingError, instruction::redeem_reserve_collateral, processor::process_instruction,
     state::INITIAL_COLLATERAL_RATIO,
 };
 use solana_program_test::*;
 use solana_sdk::{
+    instruction::InstructionError,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 use spl_token::instruction::approve;
 
@@ -85,3 +86,208 @@ async fn test_success() {
     );
     assert!(banks_client.process_transaction(transaction).await.is_ok());
 }
+
+#[tokio::test]
+async fn test_wrong_collateral_mint() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+    const COLLATERAL_AMOUNT: u64 = USDC_RESERVE_LIQUIDITY_FRACTIONAL * INITIAL_COLLATERAL_RATIO;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: COLLATERAL_AMOUNT,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[redeem_reserve_collateral(
+            port_finance_variable_rate_lending::id(),
+            COLLATERAL_AMOUNT,
+            usdc_test_reserve.user_collateral_pubkey,
+            usdc_test_reserve.user_liquidity_pubkey,
+            usdc_test_reserve.pubkey,
+            Pubkey::new_unique(),
+            usdc_test_reserve.liquidity_supply_pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::WrongCollateralMint as u32)
+        )
+    );
+}
+
+const PARTIAL_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+const PARTIAL_BORROW_AMOUNT_FRACTIONAL: u64 = 6 * FRACTIONAL_TO_USDC;
+const PARTIAL_COLLATERAL_AMOUNT: u64 =
+    PARTIAL_RESERVE_LIQUIDITY_FRACTIONAL * INITIAL_COLLATERAL_RATIO;
+
+/// A reserve with 60% of its liquidity borrowed, and a user holding all of its collateral
+fn add_partially_utilized_reserve(
+    test: &mut ProgramTest,
+    lending_market: &TestLendingMarket,
+    user_accounts_owner: &Keypair,
+) -> TestReserve {
+    let usdc_mint = add_usdc_mint(test);
+    let usdc_oracle = add_usdc_pyth_oracle(test);
+    add_reserve(
+        test,
+        lending_market,
+        &usdc_oracle,
+        user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: PARTIAL_COLLATERAL_AMOUNT,
+            liquidity_amount: PARTIAL_RESERVE_LIQUIDITY_FRACTIONAL,
+            borrow_amount: PARTIAL_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_success_max_redeemable_collateral() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_test_reserve =
+        add_partially_utilized_reserve(&mut test, &lending_market, &user_accounts_owner);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    let max_redeemable = reserve
+        .max_redeemable_collateral(PARTIAL_COLLATERAL_AMOUNT)
+        .unwrap();
+    assert_eq!(
+        max_redeemable,
+        (PARTIAL_RESERVE_LIQUIDITY_FRACTIONAL - PARTIAL_BORROW_AMOUNT_FRACTIONAL)
+            * INITIAL_COLLATERAL_RATIO
+    );
+
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserve.user_collateral_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                max_redeemable,
+            )
+            .unwrap(),
+            redeem_reserve_collateral(
+                port_finance_variable_rate_lending::id(),
+                max_redeemable,
+                usdc_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                lending_market.pubkey,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(reserve.liquidity.available_amount, 0);
+}
+
+#[tokio::test]
+async fn test_fail_redeem_above_max_redeemable_collateral() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_test_reserve =
+        add_partially_utilized_reserve(&mut test, &lending_market, &user_accounts_owner);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    let max_redeemable = reserve
+        .max_redeemable_collateral(PARTIAL_COLLATERAL_AMOUNT)
+        .unwrap();
+
+    // collateral worth one more unit of liquidity than is available
+    let user_transfer_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[redeem_reserve_collateral(
+            port_finance_variable_rate_lending::id(),
+            max_redeemable + INITIAL_COLLATERAL_RATIO,
+            usdc_test_reserve.user_collateral_pubkey,
+            usdc_test_reserve.user_liquidity_pubkey,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.collateral_mint_pubkey,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            lending_market.pubkey,
+            user_transfer_authority.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_transfer_authority], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InsufficientLiquidity as u32)
+        )
+    );
+}
@@ -6,17 +6,19 @@ use helpers::*;
 use port_finance_variable_rate_lending::math::{Rate, TryAdd, TryMul};
 use port_finance_variable_rate_lending::state::SLOTS_PER_YEAR;
 use port_finance_variable_rate_lending::{
-    instruction::{refresh_obligation, refresh_reserve},
+    error::LendingError,
+    instruction::{refresh_obligation, refresh_reserve, update_reserve},
     math::{Decimal, TryDiv},
     processor::process_instruction,
-    state::INITIAL_COLLATERAL_RATIO,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO, MAX_REFRESH_RESERVES},
 };
 use solana_program_test::*;
 use solana_sdk::program_option::COption;
 use solana_sdk::{
+    instruction::InstructionError,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 
 #[tokio::test]
@@ -165,3 +167,784 @@ async fn test_success() {
     assert_eq!(sol_reserve.liquidity.market_price, collateral_price,);
     assert_eq!(usdc_reserve.liquidity.market_price, liquidity_price,);
 }
+
+#[tokio::test]
+async fn test_non_usd_quote_currency() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT: u64 = 100;
+    const USDC_BORROW_AMOUNT: u64 = 1_000;
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 =
+        SOL_DEPOSIT_AMOUNT * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = USDC_BORROW_AMOUNT * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    // market quoted in SOL rather than USD
+    let lending_market =
+        add_lending_market_with_quote_currency(&mut test, spl_token::native_mint::id().to_bytes());
+
+    // 1 USDC = 0.02 SOL
+    let usdc_price_in_sol = Decimal::one().try_div(50u64).unwrap();
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            fixed_price: COption::Some(Decimal::one()),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            fixed_price: COption::Some(usdc_price_in_sol),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                sol_test_reserve.pubkey,
+                COption::None,
+            ),
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                usdc_test_reserve.pubkey,
+                COption::None,
+            ),
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+
+    // values are in SOL, the market quote currency, not USD
+    assert_eq!(
+        obligation.deposited_value,
+        Decimal::from(SOL_DEPOSIT_AMOUNT)
+    );
+    assert_eq!(
+        obligation.borrowed_value,
+        obligation.borrows[0]
+            .borrowed_amount_wads
+            .try_mul(usdc_reserve.liquidity.market_price)
+            .unwrap()
+            .try_div(FRACTIONAL_TO_USDC)
+            .unwrap()
+    );
+    assert_eq!(usdc_reserve.liquidity.market_price, usdc_price_in_sol);
+    assert!(obligation.borrowed_value >= Decimal::from(20u64));
+    assert!(obligation.borrowed_value < Decimal::from(21u64));
+}
+
+#[tokio::test]
+async fn test_refresh_account_metas() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 =
+        1_000 * FRACTIONAL_TO_USDC * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            fixed_price: COption::Some(Decimal::from(20u64)),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            fixed_price: COption::Some(Decimal::one()),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[
+                (&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS),
+                (&usdc_test_reserve, USDC_DEPOSIT_AMOUNT_FRACTIONAL),
+            ],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    let reserve_pubkeys = obligation.refresh_account_metas();
+    assert_eq!(
+        reserve_pubkeys,
+        vec![
+            sol_test_reserve.pubkey,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.pubkey,
+        ]
+    );
+
+    let refresh_reserves = [sol_test_reserve.pubkey, usdc_test_reserve.pubkey]
+        .iter()
+        .map(|reserve| {
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                *reserve,
+                COption::None,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut instructions = refresh_reserves.clone();
+    instructions.push(refresh_obligation(
+        port_finance_variable_rate_lending::id(),
+        test_obligation.pubkey,
+        reserve_pubkeys.iter().rev().copied().collect(),
+    ));
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+
+    let mut instructions = refresh_reserves;
+    instructions.push(refresh_obligation(
+        port_finance_variable_rate_lending::id(),
+        test_obligation.pubkey,
+        reserve_pubkeys,
+    ));
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.deposited_value,
+        Decimal::from(100u64 * 20 + 1_000)
+    );
+}
+
+#[tokio::test]
+async fn test_became_unhealthy_slot() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // 100 SOL collateral
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    // 100 SOL * 80% liquidation threshold -> 80 SOL * 20 USDC -> 1600 USDC unhealthy
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_700 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+    reserve_config.liquidation_threshold = 80;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    let payer = Keypair::from_bytes(&test_context.payer.to_bytes()).unwrap();
+
+    let refresh = [
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            COption::Some(usdc_oracle.price_pubkey),
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            sol_test_reserve.pubkey,
+            COption::Some(sol_oracle.price_pubkey),
+        ),
+        refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+        ),
+    ];
+
+    // the first unhealthy refresh records the slot
+    test_context.warp_to_slot(3).unwrap();
+    let mut transaction = Transaction::new_with_payer(&refresh, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], test_context.last_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let obligation = test_obligation
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(obligation.became_unhealthy_slot, 3);
+
+    // later unhealthy refreshes keep it
+    test_context.warp_to_slot(5).unwrap();
+    let mut transaction = Transaction::new_with_payer(&refresh, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], test_context.last_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let obligation = test_obligation
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(obligation.became_unhealthy_slot, 3);
+
+    // a refresh that finds it healthy again resets it
+    test_context.warp_to_slot(7).unwrap();
+    let mut instructions = vec![update_reserve(
+        port_finance_variable_rate_lending::id(),
+        ReserveConfig {
+            liquidation_threshold: 90,
+            ..sol_test_reserve.config
+        },
+        sol_test_reserve.pubkey,
+        lending_market.pubkey,
+        lending_market.owner.pubkey(),
+    )];
+    instructions.extend_from_slice(&refresh);
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(
+        &[&payer, &lending_market.owner],
+        test_context.last_blockhash,
+    );
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let obligation = test_obligation
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(obligation.became_unhealthy_slot, 0);
+}
+
+#[tokio::test]
+async fn test_refresh_twice_in_same_slot() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    // Configure reserve to a fixed borrow rate of 1%
+    let reserve_config = ReserveConfig {
+        min_borrow_rate: 1,
+        optimal_borrow_rate: 1,
+        optimal_utilization_rate: 100,
+        ..TEST_RESERVE_CONFIG
+    };
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: reserve_config,
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: reserve_config,
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    let payer = Keypair::from_bytes(&test_context.payer.to_bytes()).unwrap();
+
+    let refresh = [
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            COption::Some(usdc_oracle.price_pubkey),
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            sol_test_reserve.pubkey,
+            COption::Some(sol_oracle.price_pubkey),
+        ),
+        refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+        ),
+    ];
+
+    test_context.warp_to_slot(3).unwrap();
+    let mut transaction = Transaction::new_with_payer(&refresh, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], test_context.last_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let usdc_reserve = usdc_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    let obligation = test_obligation
+        .get_state(&mut test_context.banks_client)
+        .await;
+
+    // a second refresh in the same slot leaves the reserves and the obligation unchanged
+    let recent_blockhash = test_context
+        .banks_client
+        .get_new_latest_blockhash(&test_context.last_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(&refresh, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    assert_eq!(
+        usdc_test_reserve
+            .get_state(&mut test_context.banks_client)
+            .await
+            .snapshot(),
+        usdc_reserve.snapshot()
+    );
+    assert_eq!(
+        test_obligation
+            .get_state(&mut test_context.banks_client)
+            .await,
+        obligation
+    );
+
+    // a refresh in the next slot accrues interest again
+    test_context.warp_to_slot(4).unwrap();
+    let mut transaction = Transaction::new_with_payer(&refresh, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], test_context.last_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let next_usdc_reserve = usdc_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    let next_obligation = test_obligation
+        .get_state(&mut test_context.banks_client)
+        .await;
+
+    assert_eq!(next_usdc_reserve.last_update.slot, 4);
+    assert!(
+        next_usdc_reserve.liquidity.cumulative_borrow_rate_wads
+            > usdc_reserve.liquidity.cumulative_borrow_rate_wads
+    );
+    assert_eq!(next_obligation.last_update.slot, 4);
+    assert!(
+        next_obligation.borrows[0].borrowed_amount_wads
+            > obligation.borrows[0].borrowed_amount_wads
+    );
+}
+
+#[tokio::test]
+async fn test_success_at_refresh_reserve_limit() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let test_reserves: Vec<TestReserve> = (0..MAX_REFRESH_RESERVES)
+        .map(|_| {
+            add_reserve(
+                &mut test,
+                &lending_market,
+                &usdc_oracle,
+                &user_accounts_owner,
+                AddReserveArgs {
+                    liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+                    liquidity_mint_decimals: usdc_mint.decimals,
+                    liquidity_mint_pubkey: usdc_mint.pubkey,
+                    config: TEST_RESERVE_CONFIG,
+                    mark_fresh: true,
+                    ..AddReserveArgs::default()
+                },
+            )
+        })
+        .collect();
+    let (deposit_reserves, borrow_reserves) = test_reserves.split_at(MAX_REFRESH_RESERVES / 2);
+    let deposits: Vec<(&TestReserve, u64)> = deposit_reserves
+        .iter()
+        .map(|test_reserve| (test_reserve, 10 * FRACTIONAL_TO_USDC))
+        .collect();
+    let borrows: Vec<(&TestReserve, u64)> = borrow_reserves
+        .iter()
+        .map(|test_reserve| (test_reserve, FRACTIONAL_TO_USDC))
+        .collect();
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &deposits,
+            borrows: &borrows,
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            test_reserves
+                .iter()
+                .map(|test_reserve| test_reserve.pubkey)
+                .collect(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.deposits.len() + obligation.borrows.len(),
+        MAX_REFRESH_RESERVES
+    );
+    assert!(!obligation.last_update.stale);
+    assert!(obligation.allowed_borrow_value > obligation.borrowed_value);
+}
+
+#[tokio::test]
+async fn test_fail_over_refresh_reserve_limit() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    // an obligation that picked up more reserves than a refresh can process, e.g. before the
+    // limit was lowered below `MAX_OBLIGATION_RESERVES`
+    let test_reserves: Vec<TestReserve> = (0..MAX_REFRESH_RESERVES + 1)
+        .map(|_| {
+            add_reserve(
+                &mut test,
+                &lending_market,
+                &usdc_oracle,
+                &user_accounts_owner,
+                AddReserveArgs {
+                    liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+                    liquidity_mint_decimals: usdc_mint.decimals,
+                    liquidity_mint_pubkey: usdc_mint.pubkey,
+                    config: TEST_RESERVE_CONFIG,
+                    mark_fresh: true,
+                    ..AddReserveArgs::default()
+                },
+            )
+        })
+        .collect();
+    let deposits: Vec<(&TestReserve, u64)> = test_reserves
+        .iter()
+        .map(|test_reserve| (test_reserve, 10 * FRACTIONAL_TO_USDC))
+        .collect();
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &deposits,
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            test_reserves
+                .iter()
+                .map(|test_reserve| test_reserve.pubkey)
+                .collect(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::TooManyReserves as u32)
+        )
+    );
+}
+
+/// Refresh an obligation borrowing `u64::MAX` tokens from a reserve at each of the `prices`
+async fn refresh_extreme_borrows(prices: &[u64]) -> TransactionError {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let test_reserves: Vec<TestReserve> = prices
+        .iter()
+        .map(|price| {
+            add_reserve(
+                &mut test,
+                &lending_market,
+                &usdc_oracle,
+                &user_accounts_owner,
+                AddReserveArgs {
+                    liquidity_mint_decimals: 0,
+                    liquidity_mint_pubkey: usdc_mint.pubkey,
+                    config: TEST_RESERVE_CONFIG,
+                    mark_fresh: true,
+                    fixed_price: COption::Some(Decimal::from(*price)),
+                    ..AddReserveArgs::default()
+                },
+            )
+        })
+        .collect();
+    let borrows: Vec<(&TestReserve, u64)> = test_reserves
+        .iter()
+        .map(|test_reserve| (test_reserve, u64::MAX))
+        .collect();
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            borrows: &borrows,
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            test_obligation.pubkey,
+            test_reserves
+                .iter()
+                .map(|test_reserve| test_reserve.pubkey)
+                .collect(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap_err()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_fail_borrow_market_value_overflow() {
+    // u64::MAX tokens at 20 is too large to pack
+    assert_eq!(
+        refresh_extreme_borrows(&[20]).await,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::MathOverflow as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_borrowed_value_overflow() {
+    // each borrow can be packed, but not their sum
+    assert_eq!(
+        refresh_extreme_borrows(&[10, 10]).await,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::MathOverflow as u32)
+        )
+    );
+}
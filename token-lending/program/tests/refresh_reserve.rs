@@ -3,13 +3,15 @@
 use solana_program_test::*;
 use solana_sdk::program_option::COption;
 use solana_sdk::{
+    instruction::InstructionError,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 
 use helpers::*;
 use port_finance_variable_rate_lending::{
+    error::LendingError,
     instruction::refresh_reserve,
     math::{Decimal, Rate, TryAdd, TryDiv, TryMul},
     processor::process_instruction,
@@ -312,3 +314,278 @@ async fn test_success_switchboard_parsed() {
         sol_test_reserve.market_price
     );
 }
+
+#[tokio::test]
+async fn test_max_accrual_slots() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+    const BORROW_AMOUNT: u64 = 100;
+    const MAX_ACCRUAL_SLOTS: u64 = 100;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    // Configure reserve to a fixed borrow rate of 1%
+    const BORROW_RATE: u8 = 1;
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.min_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_utilization_rate = 100;
+    reserve_config.max_accrual_slots = MAX_ACCRUAL_SLOTS;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: BORROW_AMOUNT,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: reserve_config,
+            fixed_price: COption::Some(Decimal::one()),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    // last refreshed at slot 1, far more than the maximum accrual slots ago
+    let mut test_context = test.start_with_context().await;
+    test_context
+        .warp_to_slot(100 * MAX_ACCRUAL_SLOTS + 1)
+        .unwrap();
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            COption::None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+
+    let slot_rate = Rate::from_percent(BORROW_RATE)
+        .try_div(SLOTS_PER_YEAR)
+        .unwrap();
+    let compound_rate = Rate::one()
+        .try_add(slot_rate)
+        .unwrap()
+        .try_pow(MAX_ACCRUAL_SLOTS)
+        .unwrap();
+    assert_eq!(
+        usdc_reserve.liquidity.cumulative_borrow_rate_wads,
+        compound_rate.into()
+    );
+    assert_eq!(
+        usdc_reserve.liquidity.borrowed_amount_wads,
+        Decimal::from(BORROW_AMOUNT).try_mul(compound_rate).unwrap()
+    );
+    assert_eq!(usdc_reserve.last_update.slot, 100 * MAX_ACCRUAL_SLOTS + 1);
+}
+
+#[tokio::test]
+async fn test_advance_and_refresh_accrues_interest() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+    const BORROW_AMOUNT: u64 = 100 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    // Configure reserve to a fixed borrow rate of 10%
+    const BORROW_RATE: u8 = 10;
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.min_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_utilization_rate = 100;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: BORROW_AMOUNT,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: reserve_config,
+            fixed_price: COption::Some(Decimal::one()),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    let initial_reserve = usdc_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+
+    let slot_rate = Rate::from_percent(BORROW_RATE)
+        .try_div(SLOTS_PER_YEAR)
+        .unwrap();
+    let mut last_update_slot = initial_reserve.last_update.slot;
+    let mut borrowed_amount_wads = initial_reserve.liquidity.borrowed_amount_wads;
+    for slots in [10, 50] {
+        let usdc_reserve = advance_and_refresh(&mut test_context, slots, &usdc_test_reserve).await;
+
+        let elapsed_slots = usdc_reserve.last_update.slot - last_update_slot;
+        assert!(elapsed_slots >= slots);
+        let compound_rate = Rate::one()
+            .try_add(slot_rate)
+            .unwrap()
+            .try_pow(elapsed_slots)
+            .unwrap();
+        let expected_borrowed_amount_wads =
+            borrowed_amount_wads.try_mul_ceil(compound_rate).unwrap();
+        assert_eq!(
+            usdc_reserve.liquidity.borrowed_amount_wads,
+            expected_borrowed_amount_wads
+        );
+        assert!(expected_borrowed_amount_wads > borrowed_amount_wads);
+
+        last_update_slot = usdc_reserve.last_update.slot;
+        borrowed_amount_wads = usdc_reserve.liquidity.borrowed_amount_wads;
+    }
+}
+
+#[tokio::test]
+async fn test_fail_wrong_oracle() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // a valid price account, but not the one the reserve was configured with
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            COption::Some(sol_oracle.price_pubkey),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidOracleConfig as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            COption::Some(usdc_oracle.price_pubkey),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        usdc_reserve.liquidity.market_price,
+        usdc_test_reserve.market_price
+    );
+}
+
+#[tokio::test]
+async fn test_fixed_price_ignores_oracle() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            fixed_price: COption::Some(Decimal::one()),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            COption::Some(sol_oracle.price_pubkey),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(usdc_reserve.liquidity.market_price, Decimal::one());
+    assert_eq!(usdc_reserve.liquidity.oracle_pubkey, COption::None);
+}
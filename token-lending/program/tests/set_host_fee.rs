@@ -0,0 +1,143 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError, instruction::set_host_fee, processor::process_instruction,
+    state::ReserveFees,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+fn setup() -> (ProgramTest, TestLendingMarket, TestReserve) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    (test, lending_market, usdc_test_reserve)
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (test, lending_market, usdc_test_reserve) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_host_fee(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            55,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // only the host fee changed
+    let reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        reserve.config.fees,
+        ReserveFees {
+            host_fee_percentage: 55,
+            ..TEST_RESERVE_CONFIG.fees
+        }
+    );
+    assert_eq!(
+        TEST_RESERVE_CONFIG.diff(&reserve.config),
+        vec![(
+            "host_fee_percentage",
+            TEST_RESERVE_CONFIG.fees.host_fee_percentage.to_string(),
+            "55".to_string()
+        )]
+    );
+}
+
+#[tokio::test]
+async fn test_fail_percentage_above_100() {
+    let (test, lending_market, usdc_test_reserve) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_host_fee(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            101,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidConfig as u32)
+        )
+    );
+
+    let reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(reserve.config, TEST_RESERVE_CONFIG);
+}
+
+#[tokio::test]
+async fn test_fail_wrong_owner() {
+    let (test, lending_market, usdc_test_reserve) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let not_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_host_fee(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            not_owner.pubkey(),
+            10,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &not_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
@@ -5,10 +5,13 @@ mod helpers;
 use helpers::*;
 use port_finance_variable_rate_lending::{
     error::LendingError,
-    instruction::{set_lending_market_owner, LendingInstruction},
+    instruction::{accept_lending_market_owner, set_lending_market_owner, LendingInstruction},
     processor::process_instruction,
 };
-use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+};
 use solana_program_test::*;
 use solana_sdk::{
     instruction::InstructionError,
@@ -31,13 +34,165 @@ async fn test_success() {
     let lending_market = add_lending_market(&mut test);
     let (mut banks_client, payer, recent_blockhash) = test.start().await;
 
+    let new_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_lending_market_owner(
+            port_finance_variable_rate_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            new_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let lending_market_info = lending_market.get_state(&mut banks_client).await;
+    assert_eq!(lending_market_info.owner, lending_market.owner.pubkey());
+    assert_eq!(
+        lending_market_info.pending_owner,
+        COption::Some(new_owner.pubkey())
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[accept_lending_market_owner(
+            port_finance_variable_rate_lending::id(),
+            lending_market.pubkey,
+            new_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &new_owner], recent_blockhash);
+
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let lending_market_info = lending_market.get_state(&mut banks_client).await;
+    assert_eq!(lending_market_info.owner, new_owner.pubkey());
+    assert_eq!(lending_market_info.pending_owner, COption::None);
+}
+
+#[tokio::test]
+async fn test_accept_by_wrong_key() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
     let new_owner = Pubkey::new_unique();
+    let wrong_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_lending_market_owner(
+                port_finance_variable_rate_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                new_owner,
+            ),
+            accept_lending_market_owner(
+                port_finance_variable_rate_lending::id(),
+                lending_market.pubkey,
+                wrong_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &lending_market.owner, &wrong_owner],
+        recent_blockhash,
+    );
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_old_owner_retains_control_until_accepted() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let new_owner = Keypair::new();
     let mut transaction = Transaction::new_with_payer(
         &[set_lending_market_owner(
             port_finance_variable_rate_lending::id(),
             lending_market.pubkey,
             lending_market.owner.pubkey(),
-            new_owner,
+            new_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    // the proposed owner cannot act as the owner before accepting
+    let mut transaction = Transaction::new_with_payer(
+        &[set_lending_market_owner(
+            port_finance_variable_rate_lending::id(),
+            lending_market.pubkey,
+            new_owner.pubkey(),
+            new_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &new_owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+
+    // the old owner can still replace the proposal
+    let replacement_owner = Pubkey::new_unique();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_lending_market_owner(
+            port_finance_variable_rate_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            replacement_owner,
         )],
         Some(&payer.pubkey()),
     );
@@ -51,7 +206,11 @@ async fn test_success() {
         .unwrap();
 
     let lending_market_info = lending_market.get_state(&mut banks_client).await;
-    assert_eq!(lending_market_info.owner, new_owner);
+    assert_eq!(lending_market_info.owner, lending_market.owner.pubkey());
+    assert_eq!(
+        lending_market_info.pending_owner,
+        COption::Some(replacement_owner)
+    );
 }
 
 #[tokio::test]
@@ -0,0 +1,295 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{deposit_obligation_collateral, set_market_limits},
+    processor::process_instruction,
+    state::{INITIAL_COLLATERAL_RATIO, MAX_REFRESH_RESERVES},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+struct MarketLimitsAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    other_sol_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+/// An obligation with one deposit and one borrow, and a second SOL reserve it has not used yet
+fn setup() -> (ProgramTest, MarketLimitsAccounts) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+    let other_sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        MarketLimitsAccounts {
+            user_accounts_owner,
+            lending_market,
+            sol_test_reserve,
+            other_sol_test_reserve,
+            test_obligation,
+        },
+    )
+}
+
+fn deposit_instruction(
+    accounts: &MarketLimitsAccounts,
+    reserve: &TestReserve,
+) -> solana_program::instruction::Instruction {
+    deposit_obligation_collateral(
+        port_finance_variable_rate_lending::id(),
+        SOL_DEPOSIT_AMOUNT_LAMPORTS,
+        reserve.user_collateral_pubkey,
+        reserve.collateral_supply_pubkey,
+        reserve.pubkey,
+        accounts.test_obligation.pubkey,
+        accounts.lending_market.pubkey,
+        accounts.test_obligation.owner,
+        accounts.user_accounts_owner.pubkey(),
+        None,
+        None,
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_market_limits(
+            port_finance_variable_rate_lending::id(),
+            accounts.lending_market.pubkey,
+            accounts.lending_market.owner.pubkey(),
+            2,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let lending_market = accounts.lending_market.get_state(&mut banks_client).await;
+    assert_eq!(lending_market.max_obligation_reserves, 2);
+    assert_eq!(lending_market.obligation_reserve_limit(), 2);
+}
+
+#[tokio::test]
+async fn test_invalid_limit() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_market_limits(
+            port_finance_variable_rate_lending::id(),
+            accounts.lending_market.pubkey,
+            accounts.lending_market.owner.pubkey(),
+            MAX_REFRESH_RESERVES as u8 + 1,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.lending_market.owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidConfig as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_market_limits(
+            port_finance_variable_rate_lending::id(),
+            accounts.lending_market.pubkey,
+            accounts.user_accounts_owner.pubkey(),
+            2,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_obligation_reserve_limit() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the obligation already uses two reserves, so a third is beyond the limit
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_market_limits(
+                port_finance_variable_rate_lending::id(),
+                accounts.lending_market.pubkey,
+                accounts.lending_market.owner.pubkey(),
+                2,
+            ),
+            deposit_instruction(&accounts, &accounts.other_sol_test_reserve),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ObligationReserveLimit as u32)
+        )
+    );
+
+    // reserves already in the obligation still work at the limit
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_market_limits(
+                port_finance_variable_rate_lending::id(),
+                accounts.lending_market.pubkey,
+                accounts.lending_market.owner.pubkey(),
+                2,
+            ),
+            deposit_instruction(&accounts, &accounts.sol_test_reserve),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // raising the limit lets the obligation add the reserve up to the new limit
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_market_limits(
+                port_finance_variable_rate_lending::id(),
+                accounts.lending_market.pubkey,
+                accounts.lending_market.owner.pubkey(),
+                3,
+            ),
+            deposit_instruction(&accounts, &accounts.other_sol_test_reserve),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.deposits.len(), 2);
+    assert_eq!(obligation.borrows.len(), 1);
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        2 * SOL_DEPOSIT_AMOUNT_LAMPORTS
+    );
+}
@@ -0,0 +1,301 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, refresh_obligation, set_reserve_fee_receiver, withdraw_fee,
+    },
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+
+mod helpers;
+
+#[tokio::test]
+async fn test_set_reserve_fee_receiver() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+    const FEE_AMOUNT: u64 = 100_000;
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.fees.borrow_fee_wad = 10_000_000_000_000_000; // 1%
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let new_fee_receiver =
+        add_account_for_program(&mut test, &lending_market.authority, 0, &usdc_mint.pubkey);
+    let old_fee_receiver = usdc_test_reserve.liquidity_fee_receiver_pubkey;
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_reserve_fee_receiver(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            new_fee_receiver,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(usdc_reserve.liquidity.fee_receiver, new_fee_receiver);
+
+    let borrow = |fee_receiver| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                refresh_obligation(
+                    port_finance_variable_rate_lending::id(),
+                    test_obligation.pubkey,
+                    vec![sol_test_reserve.pubkey],
+                ),
+                borrow_obligation_liquidity(
+                    port_finance_variable_rate_lending::id(),
+                    USDC_BORROW_AMOUNT_FRACTIONAL,
+                    usdc_test_reserve.liquidity_supply_pubkey,
+                    usdc_test_reserve.user_liquidity_pubkey,
+                    usdc_test_reserve.pubkey,
+                    fee_receiver,
+                    test_obligation.pubkey,
+                    lending_market.pubkey,
+                    test_obligation.owner,
+                    None,
+                    None,
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    assert_eq!(
+        banks_client
+            .process_transaction(borrow(old_fee_receiver))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::WrongFeeReceiver as u32)
+        )
+    );
+
+    banks_client
+        .process_transaction(borrow(new_fee_receiver))
+        .await
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, new_fee_receiver).await,
+        FEE_AMOUNT
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, old_fee_receiver).await,
+        0
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_fee(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            old_fee_receiver,
+            usdc_test_reserve.user_liquidity_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidReserveFeeAccount as u32)
+        )
+    );
+
+    let user_liquidity_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await;
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_fee(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            new_fee_receiver,
+            usdc_test_reserve.user_liquidity_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, new_fee_receiver).await,
+        0
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await,
+        user_liquidity_balance + FEE_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn test_set_reserve_fee_receiver_invalid() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 42,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let new_fee_receiver =
+        add_account_for_program(&mut test, &lending_market.authority, 0, &usdc_mint.pubkey);
+    let wrong_mint_fee_receiver = add_account_for_program(
+        &mut test,
+        &lending_market.authority,
+        0,
+        &spl_token::native_mint::id(),
+    );
+    let wrong_owner_fee_receiver = add_account_for_program(
+        &mut test,
+        &user_accounts_owner.pubkey(),
+        0,
+        &usdc_mint.pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    for (new_receiver, owner, error) in [
+        (
+            new_fee_receiver,
+            &user_accounts_owner,
+            LendingError::InvalidMarketOwner,
+        ),
+        (
+            wrong_mint_fee_receiver,
+            &lending_market.owner,
+            LendingError::InvalidTokenMint,
+        ),
+        (
+            wrong_owner_fee_receiver,
+            &lending_market.owner,
+            LendingError::InvalidAccountInput,
+        ),
+        (
+            usdc_test_reserve.liquidity_supply_pubkey,
+            &lending_market.owner,
+            LendingError::InvalidAccountInput,
+        ),
+    ] {
+        let mut transaction = Transaction::new_with_payer(
+            &[set_reserve_fee_receiver(
+                port_finance_variable_rate_lending::id(),
+                usdc_test_reserve.pubkey,
+                lending_market.pubkey,
+                owner.pubkey(),
+                new_receiver,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, owner], recent_blockhash);
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        usdc_reserve.liquidity.fee_receiver,
+        usdc_test_reserve.liquidity_fee_receiver_pubkey
+    );
+}
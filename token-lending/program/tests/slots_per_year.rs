@@ -0,0 +1,202 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{refresh_reserve, set_slots_per_year},
+    math::{Decimal, Rate, TryAdd, TryDiv, TryMul},
+    processor::process_instruction,
+    state::{DEFAULT_SLOTS_PER_YEAR, MAX_SLOTS_PER_YEAR, MIN_SLOTS_PER_YEAR, SLOTS_PER_YEAR},
+};
+
+mod helpers;
+
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const BORROW_AMOUNT: u64 = 100;
+// Fixed borrow rate of 1%
+const BORROW_RATE: u8 = 1;
+// Slots twice as slow as the nominal 400ms
+const SLOW_SLOTS_PER_YEAR: u64 = SLOTS_PER_YEAR / 2;
+
+fn setup() -> (ProgramTest, TestLendingMarket, TestReserve) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.min_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_utilization_rate = 100;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: BORROW_AMOUNT,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: reserve_config,
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddReserveArgs::default()
+        },
+    );
+
+    (test, lending_market, usdc_test_reserve)
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (test, lending_market, usdc_test_reserve) = setup();
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let market = lending_market.get_state(&mut banks_client).await;
+    assert_eq!(market.slots_per_year(), DEFAULT_SLOTS_PER_YEAR);
+    let reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    let default_borrow_apy = reserve.borrow_apy(market.slots_per_year()).unwrap();
+    let default_supply_apy = reserve.supply_apy(market.slots_per_year()).unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_slots_per_year(
+                port_finance_variable_rate_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                SLOW_SLOTS_PER_YEAR,
+            ),
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_oracle_pubkey,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let market = lending_market.get_state(&mut banks_client).await;
+    assert_eq!(market.slots_per_year, SLOW_SLOTS_PER_YEAR);
+    assert_eq!(market.slots_per_year(), SLOW_SLOTS_PER_YEAR);
+
+    // fewer slots a year compound to lower displayed rates
+    let reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert!(reserve.borrow_apy(market.slots_per_year()).unwrap() < default_borrow_apy);
+    assert!(reserve.supply_apy(market.slots_per_year()).unwrap() < default_supply_apy);
+
+    // while the slot elapsed still accrues at the nominal per-slot rate
+    let slot_rate = Rate::from_percent(BORROW_RATE)
+        .try_div(SLOTS_PER_YEAR)
+        .unwrap();
+    let compound_rate = Rate::one().try_add(slot_rate).unwrap();
+    assert_eq!(
+        reserve.liquidity.cumulative_borrow_rate_wads,
+        compound_rate.into()
+    );
+    assert_eq!(
+        reserve.liquidity.borrowed_amount_wads,
+        Decimal::from(BORROW_AMOUNT).try_mul(compound_rate).unwrap()
+    );
+
+    // setting it back to 0 restores the default estimate
+    let mut transaction = Transaction::new_with_payer(
+        &[set_slots_per_year(
+            port_finance_variable_rate_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let market = lending_market.get_state(&mut banks_client).await;
+    assert_eq!(market.slots_per_year(), DEFAULT_SLOTS_PER_YEAR);
+}
+
+#[tokio::test]
+async fn test_fail_wrong_owner() {
+    let (test, lending_market, _usdc_test_reserve) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let not_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_slots_per_year(
+            port_finance_variable_rate_lending::id(),
+            lending_market.pubkey,
+            not_owner.pubkey(),
+            SLOW_SLOTS_PER_YEAR,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &not_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_fail_out_of_range() {
+    let (test, lending_market, _usdc_test_reserve) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    for slots_per_year in [MIN_SLOTS_PER_YEAR - 1, MAX_SLOTS_PER_YEAR + 1] {
+        let mut transaction = Transaction::new_with_payer(
+            &[set_slots_per_year(
+                port_finance_variable_rate_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                slots_per_year,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(LendingError::InvalidConfig as u32)
+            )
+        );
+    }
+
+    let market = lending_market.get_state(&mut banks_client).await;
+    assert_eq!(market.slots_per_year, 0);
+}
@@ -0,0 +1,430 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{merge_obligation, split_obligation},
+    math::{Decimal, TryAdd},
+    processor::process_instruction,
+    state::{Obligation, INITIAL_COLLATERAL_RATIO},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 3 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+// more than the whole source obligation could back
+const USDC_UNBACKED_BORROW_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 10_000 * FRACTIONAL_TO_USDC;
+
+struct MoveObligationAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    /// Healthy obligation borrowing USDC against SOL
+    source_obligation: TestObligation,
+    /// Another healthy obligation of the same owner, with the same positions
+    other_obligation: TestObligation,
+    empty_obligation: TestObligation,
+    /// Obligation of the same owner with a borrow and no collateral
+    unbacked_obligation: TestObligation,
+}
+
+fn setup() -> (ProgramTest, MoveObligationAccounts) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL
+                + USDC_UNBACKED_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let source_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+    let other_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+    let empty_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+    let unbacked_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            borrows: &[(&usdc_test_reserve, USDC_UNBACKED_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        MoveObligationAccounts {
+            user_accounts_owner,
+            lending_market,
+            sol_test_reserve,
+            usdc_test_reserve,
+            source_obligation,
+            other_obligation,
+            empty_obligation,
+            unbacked_obligation,
+        },
+    )
+}
+
+fn reserve_pubkeys(accounts: &MoveObligationAccounts) -> Vec<Pubkey> {
+    vec![
+        accounts.sol_test_reserve.pubkey,
+        accounts.usdc_test_reserve.pubkey,
+    ]
+}
+
+fn split_instruction(
+    accounts: &MoveObligationAccounts,
+    split_percent: u8,
+    destination_obligation: &TestObligation,
+) -> Instruction {
+    split_obligation(
+        port_finance_variable_rate_lending::id(),
+        split_percent,
+        accounts.source_obligation.pubkey,
+        destination_obligation.pubkey,
+        accounts.lending_market.pubkey,
+        accounts.user_accounts_owner.pubkey(),
+        reserve_pubkeys(accounts),
+    )
+}
+
+fn assert_healthy(obligation: &Obligation) {
+    assert!(obligation.borrowed_value > Decimal::zero());
+    assert!(obligation.borrowed_value <= obligation.allowed_borrow_value);
+    assert_eq!(obligation.became_unhealthy_slot, 0);
+}
+
+#[tokio::test]
+async fn test_split_in_half() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[split_instruction(&accounts, 50, &accounts.empty_obligation)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let source = accounts
+        .source_obligation
+        .get_state(&mut banks_client)
+        .await;
+    let split = accounts.empty_obligation.get_state(&mut banks_client).await;
+
+    assert_eq!(
+        source.deposits[0].deposited_amount,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS / 2
+    );
+    assert_eq!(
+        split.deposits[0].deposited_amount,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS / 2
+    );
+    assert_eq!(
+        source.borrows[0].borrowed_amount_wads,
+        Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL / 2)
+    );
+    assert_eq!(
+        split.borrows[0].borrowed_amount_wads,
+        Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL / 2)
+    );
+
+    // both halves are refreshed, each with half the value and borrowing power
+    assert_eq!(source.deposited_value, split.deposited_value);
+    assert_eq!(source.borrowed_value, split.borrowed_value);
+    assert_eq!(source.allowed_borrow_value, split.allowed_borrow_value);
+    assert_eq!(source.unhealthy_borrow_value, split.unhealthy_borrow_value);
+    assert_healthy(&source);
+    assert_healthy(&split);
+}
+
+#[tokio::test]
+async fn test_merge() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[merge_obligation(
+            port_finance_variable_rate_lending::id(),
+            accounts.other_obligation.pubkey,
+            accounts.source_obligation.pubkey,
+            accounts.lending_market.pubkey,
+            accounts.user_accounts_owner.pubkey(),
+            reserve_pubkeys(&accounts),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let emptied = accounts.other_obligation.get_state(&mut banks_client).await;
+    assert!(emptied.deposits.is_empty());
+    assert!(emptied.borrows.is_empty());
+    assert_eq!(emptied.deposited_value, Decimal::zero());
+    assert_eq!(emptied.borrowed_value, Decimal::zero());
+
+    let merged = accounts
+        .source_obligation
+        .get_state(&mut banks_client)
+        .await;
+    assert_eq!(
+        merged.deposits[0].deposited_amount,
+        2 * SOL_DEPOSIT_AMOUNT_LAMPORTS
+    );
+    assert_eq!(
+        merged.borrows[0].borrowed_amount_wads,
+        Decimal::from(2 * USDC_BORROW_AMOUNT_FRACTIONAL)
+    );
+    assert_healthy(&merged);
+
+    // splitting the merged obligation in half gives back the values of either one
+    let mut transaction = Transaction::new_with_payer(
+        &[split_instruction(&accounts, 50, &accounts.other_obligation)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let source = accounts
+        .source_obligation
+        .get_state(&mut banks_client)
+        .await;
+    let other = accounts.other_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        source
+            .deposited_value
+            .try_add(other.deposited_value)
+            .unwrap(),
+        merged.deposited_value
+    );
+    assert_eq!(
+        source.borrowed_value.try_add(other.borrowed_value).unwrap(),
+        merged.borrowed_value
+    );
+    assert_eq!(
+        source
+            .allowed_borrow_value
+            .try_add(other.allowed_borrow_value)
+            .unwrap(),
+        merged.allowed_borrow_value
+    );
+}
+
+#[tokio::test]
+async fn test_fail_unhealthy_result() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // half the source's collateral can't back the unbacked obligation's borrow
+    let mut transaction = Transaction::new_with_payer(
+        &[split_instruction(
+            &accounts,
+            50,
+            &accounts.unbacked_obligation,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ObligationUnhealthy as u32)
+        )
+    );
+
+    // nor can all of it
+    let mut transaction = Transaction::new_with_payer(
+        &[merge_obligation(
+            port_finance_variable_rate_lending::id(),
+            accounts.source_obligation.pubkey,
+            accounts.unbacked_obligation.pubkey,
+            accounts.lending_market.pubkey,
+            accounts.user_accounts_owner.pubkey(),
+            reserve_pubkeys(&accounts),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ObligationUnhealthy as u32)
+        )
+    );
+
+    accounts
+        .source_obligation
+        .validate_state(&mut banks_client)
+        .await;
+    accounts
+        .unbacked_obligation
+        .validate_state(&mut banks_client)
+        .await;
+}
+
+#[tokio::test]
+async fn test_fail_invalid_split() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    for split_percent in [0, 100] {
+        let mut transaction = Transaction::new_with_payer(
+            &[split_instruction(
+                &accounts,
+                split_percent,
+                &accounts.empty_obligation,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(LendingError::InvalidAmount as u32)
+            )
+        );
+    }
+
+    // only the owner of both obligations can move positions between them
+    let not_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[split_obligation(
+            port_finance_variable_rate_lending::id(),
+            50,
+            accounts.source_obligation.pubkey,
+            accounts.empty_obligation.pubkey,
+            accounts.lending_market.pubkey,
+            not_owner.pubkey(),
+            reserve_pubkeys(&accounts),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &not_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidObligationOwner as u32)
+        )
+    );
+
+    // every reserve of the obligations must be provided
+    let mut transaction = Transaction::new_with_payer(
+        &[split_obligation(
+            port_finance_variable_rate_lending::id(),
+            50,
+            accounts.source_obligation.pubkey,
+            accounts.empty_obligation.pubkey,
+            accounts.lending_market.pubkey,
+            accounts.user_accounts_owner.pubkey(),
+            vec![accounts.sol_test_reserve.pubkey],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+
+    accounts
+        .source_obligation
+        .validate_state(&mut banks_client)
+        .await;
+}
@@ -0,0 +1,289 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, refresh_obligation, refresh_obligation_conservative,
+        refresh_reserve, set_allow_conservative_refresh, set_strict_instruction_order,
+    },
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+use solana_program::{instruction::Instruction, system_instruction};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+
+struct StrictOrderAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+fn setup() -> (ProgramTest, StrictOrderAccounts) {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        StrictOrderAccounts {
+            user_accounts_owner,
+            lending_market,
+            sol_test_reserve,
+            usdc_test_reserve,
+            test_obligation,
+        },
+    )
+}
+
+/// Enable strict ordering, refresh both reserves and the obligation, then borrow,
+/// optionally slipping `interleaved` in right before the borrow.
+fn strict_borrow_instructions(
+    accounts: &StrictOrderAccounts,
+    interleaved: Option<Instruction>,
+) -> Vec<Instruction> {
+    let mut instructions = vec![
+        set_strict_instruction_order(
+            port_finance_variable_rate_lending::id(),
+            accounts.lending_market.pubkey,
+            accounts.lending_market.owner.pubkey(),
+            true,
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            accounts.usdc_test_reserve.pubkey,
+            accounts.usdc_test_reserve.liquidity_oracle_pubkey,
+        ),
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            accounts.sol_test_reserve.pubkey,
+            accounts.sol_test_reserve.liquidity_oracle_pubkey,
+        ),
+        refresh_obligation(
+            port_finance_variable_rate_lending::id(),
+            accounts.test_obligation.pubkey,
+            vec![accounts.sol_test_reserve.pubkey],
+        ),
+    ];
+    instructions.extend(interleaved);
+    instructions.push(borrow_obligation_liquidity(
+        port_finance_variable_rate_lending::id(),
+        USDC_BORROW_AMOUNT_FRACTIONAL,
+        accounts.usdc_test_reserve.liquidity_supply_pubkey,
+        accounts.usdc_test_reserve.user_liquidity_pubkey,
+        accounts.usdc_test_reserve.pubkey,
+        accounts.usdc_test_reserve.liquidity_fee_receiver_pubkey,
+        accounts.test_obligation.pubkey,
+        accounts.lending_market.pubkey,
+        accounts.test_obligation.owner,
+        None,
+        None,
+    ));
+    instructions
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &strict_borrow_instructions(&accounts, None),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let lending_market_info = accounts.lending_market.get_state(&mut banks_client).await;
+    assert!(lending_market_info.strict_instruction_order);
+
+    let borrow_amount = get_token_balance(
+        &mut banks_client,
+        accounts.usdc_test_reserve.user_liquidity_pubkey,
+    )
+    .await;
+    assert!(borrow_amount > 0);
+}
+
+#[tokio::test]
+async fn test_interleaved_instruction() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &strict_borrow_instructions(
+            &accounts,
+            Some(system_instruction::transfer(
+                &payer.pubkey(),
+                &payer.pubkey(),
+                1,
+            )),
+        ),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            5,
+            InstructionError::Custom(LendingError::UnexpectedInstructionOrder as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_missing_reserve_refresh() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // drop the deposit reserve refresh so the obligation refresh is not fully covered
+    let mut instructions = strict_borrow_instructions(&accounts, None);
+    instructions.remove(2);
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::UnexpectedInstructionOrder as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_conservative_refresh() {
+    let (test, accounts) = setup();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // the lending market and obligation owner trailing a conservative refresh aren't reserves
+    let mut instructions = strict_borrow_instructions(&accounts, None);
+    instructions[3] = refresh_obligation_conservative(
+        port_finance_variable_rate_lending::id(),
+        accounts.test_obligation.pubkey,
+        vec![accounts.sol_test_reserve.pubkey],
+        accounts.lending_market.pubkey,
+        accounts.test_obligation.owner,
+    );
+    instructions.insert(
+        0,
+        set_allow_conservative_refresh(
+            port_finance_variable_rate_lending::id(),
+            accounts.lending_market.pubkey,
+            accounts.lending_market.owner.pubkey(),
+            true,
+        ),
+    );
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let borrow_amount = get_token_balance(
+        &mut banks_client,
+        accounts.usdc_test_reserve.user_liquidity_pubkey,
+    )
+    .await;
+    assert!(borrow_amount > 0);
+}
@@ -0,0 +1,193 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program::{hash::Hash, instruction::Instruction};
+use solana_program_test::*;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    event::{SupplyAction, SupplyEvent},
+    instruction::{deposit_reserve_liquidity, redeem_reserve_collateral, refresh_reserve},
+    processor::process_instruction,
+};
+
+mod helpers;
+
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 50 * FRACTIONAL_TO_USDC;
+const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+/// Supply events logged by `instructions`, read from a preflight simulation that a trailing
+/// invalid instruction makes fail so nothing is committed. The banks client only returns the
+/// logs of failed preflights.
+async fn simulate_supply_events(
+    banks_client: &mut BanksClient,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+    recent_blockhash: Hash,
+) -> Vec<SupplyEvent> {
+    let mut instructions = instructions.to_vec();
+    instructions.push(Instruction {
+        program_id: port_finance_variable_rate_lending::id(),
+        accounts: vec![],
+        data: vec![u8::MAX],
+    });
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&signers[0].pubkey()));
+    transaction.sign(&signers.to_vec(), recent_blockhash);
+
+    match banks_client
+        .process_transaction_with_preflight(transaction)
+        .await
+    {
+        Err(BanksClientError::SimulationError { logs, .. }) => logs
+            .iter()
+            .filter_map(|log| SupplyEvent::parse(log))
+            .collect(),
+        result => panic!("expected the simulation to fail, got {:?}", result),
+    }
+}
+
+#[tokio::test]
+async fn test_deposit_and_redeem_events() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            user_liquidity_amount: USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            collateral_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL + USDC_BORROW_AMOUNT_FRACTIONAL,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(2).unwrap();
+    let payer = Keypair::from_bytes(&test_context.payer.to_bytes()).unwrap();
+    let signers = [&payer, &user_accounts_owner];
+
+    let deposit_instructions = [
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.liquidity_oracle_pubkey,
+        ),
+        deposit_reserve_liquidity(
+            port_finance_variable_rate_lending::id(),
+            USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            usdc_test_reserve.user_liquidity_pubkey,
+            usdc_test_reserve.user_collateral_pubkey,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            usdc_test_reserve.collateral_mint_pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+        ),
+    ];
+    let deposit_events = simulate_supply_events(
+        &mut test_context.banks_client,
+        &deposit_instructions,
+        &signers,
+        test_context.last_blockhash,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(&deposit_instructions, Some(&payer.pubkey()));
+    transaction.sign(&signers, test_context.last_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let collateral_amount = get_token_balance(
+        &mut test_context.banks_client,
+        usdc_test_reserve.user_collateral_pubkey,
+    )
+    .await;
+    assert_eq!(deposit_events.len(), 1);
+    let deposit_event = &deposit_events[0];
+    assert_eq!(deposit_event.action, SupplyAction::Deposit);
+    assert_eq!(deposit_event.reserve, usdc_test_reserve.pubkey);
+    assert_eq!(
+        deposit_event.liquidity_amount,
+        USDC_DEPOSIT_AMOUNT_FRACTIONAL
+    );
+    assert_eq!(deposit_event.collateral_amount, collateral_amount);
+
+    // interest accrues on the borrows, so collateral is worth more liquidity when redeemed
+    test_context.warp_to_slot(1_000).unwrap();
+    let recent_blockhash = test_context
+        .banks_client
+        .get_latest_blockhash()
+        .await
+        .unwrap();
+
+    let redeem_instructions = [
+        refresh_reserve(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.liquidity_oracle_pubkey,
+        ),
+        redeem_reserve_collateral(
+            port_finance_variable_rate_lending::id(),
+            collateral_amount,
+            usdc_test_reserve.user_collateral_pubkey,
+            usdc_test_reserve.user_liquidity_pubkey,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.collateral_mint_pubkey,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+        ),
+    ];
+    let redeem_events = simulate_supply_events(
+        &mut test_context.banks_client,
+        &redeem_instructions,
+        &signers,
+        recent_blockhash,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(&redeem_instructions, Some(&payer.pubkey()));
+    transaction.sign(&signers, recent_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let liquidity_amount = get_token_balance(
+        &mut test_context.banks_client,
+        usdc_test_reserve.user_liquidity_pubkey,
+    )
+    .await;
+    assert_eq!(redeem_events.len(), 1);
+    let redeem_event = &redeem_events[0];
+    assert_eq!(redeem_event.action, SupplyAction::Redeem);
+    assert_eq!(redeem_event.reserve, usdc_test_reserve.pubkey);
+    assert_eq!(redeem_event.collateral_amount, collateral_amount);
+    assert_eq!(redeem_event.liquidity_amount, liquidity_amount);
+    assert!(redeem_event.liquidity_amount > USDC_DEPOSIT_AMOUNT_FRACTIONAL);
+    assert!(redeem_event.exchange_rate > deposit_event.exchange_rate);
+}
@@ -61,6 +61,22 @@ async fn test_update_reserve() {
             host_fee_percentage: 15,
         },
         deposit_staking_pool: COption::None,
+        use_twap: false,
+        twap_window_slots: 0,
+        borrow_fee_discount_staking_pool: COption::None,
+        borrow_fee_discount_percentage: 0,
+        max_accrual_slots: 0,
+        borrowing_disabled: false,
+        liquidation_bonus_ramp_slots: 0,
+        min_initial_deposit: 0,
+        max_price_staleness_slots: 0,
+        max_slot_liquidity_change_percent: 0,
+        max_oracle_deviation_bps: 0,
+        isolated_debt_ceiling: 0,
+        collateral_disabled: false,
+        max_liquidation_bonus: 0,
+        borrow_fee_discount_min_stake: 0,
+        min_liquidation_bonus: 0,
     };
     let before_test_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
     assert_ne!(before_test_reserve.config, new_config);
@@ -104,3 +120,134 @@ async fn test_update_reserve() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_set_and_clear_deposit_staking_pool() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let staking_pool = add_staking_pool(&mut test, lending_market.authority, 0);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 42,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    for deposit_staking_pool in [
+        COption::Some(staking_pool.staking_pool_pubkey),
+        COption::None,
+    ] {
+        let new_config = ReserveConfig {
+            deposit_staking_pool,
+            ..TEST_RESERVE_CONFIG
+        };
+        let mut transaction = Transaction::new_with_payer(
+            &[update_reserve(
+                port_finance_variable_rate_lending::id(),
+                new_config,
+                usdc_test_reserve.pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let test_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+        assert_eq!(
+            test_reserve.config.deposit_staking_pool,
+            deposit_staking_pool
+        );
+        assert_eq!(test_reserve.config, new_config);
+    }
+}
+
+#[tokio::test]
+async fn test_min_max_liquidation_bonus() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 42,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    for (min_liquidation_bonus, max_liquidation_bonus, valid) in [
+        (5, 15, true),
+        (0, 15, true),
+        (10, 10, true),
+        (0, 0, true),
+        (11, 10, false),
+        (5, 101, false),
+    ] {
+        let new_config = ReserveConfig {
+            min_liquidation_bonus,
+            max_liquidation_bonus,
+            ..TEST_RESERVE_CONFIG
+        };
+        let mut transaction = Transaction::new_with_payer(
+            &[update_reserve(
+                port_finance_variable_rate_lending::id(),
+                new_config,
+                usdc_test_reserve.pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+        let result = banks_client.process_transaction(transaction).await;
+
+        if valid {
+            result.unwrap();
+            let test_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+            assert_eq!(test_reserve.config, new_config);
+        } else {
+            assert_eq!(
+                result.unwrap_err().unwrap(),
+                TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(LendingError::InvalidConfig as u32),
+                )
+            );
+        }
+    }
+}
@@ -0,0 +1,368 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, deposit_reserve_liquidity, redeem_reserve_collateral,
+        refresh_obligation, refresh_reserve, repay_obligation_liquidity, set_reserve_wind_down,
+        withdraw_obligation_collateral,
+    },
+    math::Decimal,
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+
+mod helpers;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 400 * FRACTIONAL_TO_USDC;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+const USDC_DEPOSIT_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC * INITIAL_COLLATERAL_RATIO;
+
+struct WindDownAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+/// A USDC reserve with an obligation borrowing from it against SOL and USDC collateral. The
+/// USDC reserve accrues no interest, so it can be drained to exactly zero.
+fn setup(test: &mut ProgramTest) -> WindDownAccounts {
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(test);
+
+    let sol_oracle = add_sol_pyth_oracle(test);
+    let sol_test_reserve = add_reserve(
+        test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(test);
+    let usdc_oracle = add_usdc_pyth_oracle(test);
+    let usdc_test_reserve = add_reserve(
+        test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            collateral_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL + USDC_DEPOSIT_AMOUNT_FRACTIONAL,
+            config: ReserveConfig {
+                optimal_borrow_rate: 0,
+                max_borrow_rate: 0,
+                ..TEST_RESERVE_CONFIG
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[
+                (&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS),
+                (&usdc_test_reserve, USDC_DEPOSIT_AMOUNT_FRACTIONAL),
+            ],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    WindDownAccounts {
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+    }
+}
+
+impl WindDownAccounts {
+    fn set_wind_down(&self, wind_down: bool) -> Instruction {
+        set_reserve_wind_down(
+            port_finance_variable_rate_lending::id(),
+            wind_down,
+            self.usdc_test_reserve.pubkey,
+            self.lending_market.pubkey,
+            self.lending_market.owner.pubkey(),
+        )
+    }
+
+    fn refresh(&self) -> Vec<Instruction> {
+        vec![
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                self.sol_test_reserve.pubkey,
+                self.sol_test_reserve.liquidity_oracle_pubkey,
+            ),
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                self.usdc_test_reserve.pubkey,
+                self.usdc_test_reserve.liquidity_oracle_pubkey,
+            ),
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                self.test_obligation.pubkey,
+                vec![
+                    self.sol_test_reserve.pubkey,
+                    self.usdc_test_reserve.pubkey,
+                    self.usdc_test_reserve.pubkey,
+                ],
+            ),
+        ]
+    }
+}
+
+#[tokio::test]
+async fn test_set_reserve_wind_down() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+    let accounts = setup(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction =
+        Transaction::new_with_payer(&[accounts.set_wind_down(true)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &accounts.lending_market.owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let usdc_reserve = accounts
+        .usdc_test_reserve
+        .get_state(&mut banks_client)
+        .await;
+    assert!(usdc_reserve.is_wind_down);
+    assert!(!usdc_reserve.is_paused);
+
+    // only the lending market owner can reopen the reserve
+    let not_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_reserve_wind_down(
+            port_finance_variable_rate_lending::id(),
+            false,
+            accounts.usdc_test_reserve.pubkey,
+            accounts.lending_market.pubkey,
+            not_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &not_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+
+    let mut transaction =
+        Transaction::new_with_payer(&[accounts.set_wind_down(false)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &accounts.lending_market.owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let usdc_reserve = accounts
+        .usdc_test_reserve
+        .get_state(&mut banks_client)
+        .await;
+    assert!(!usdc_reserve.is_wind_down);
+}
+
+#[tokio::test]
+async fn test_wind_down_blocks_deposit_and_borrow() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+    let accounts = setup(&mut test);
+    let usdc_test_reserve = &accounts.usdc_test_reserve;
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction =
+        Transaction::new_with_payer(&[accounts.set_wind_down(true)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &accounts.lending_market.owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let mut instructions = accounts.refresh();
+    instructions.push(deposit_reserve_liquidity(
+        port_finance_variable_rate_lending::id(),
+        FRACTIONAL_TO_USDC,
+        usdc_test_reserve.user_liquidity_pubkey,
+        usdc_test_reserve.user_collateral_pubkey,
+        usdc_test_reserve.pubkey,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.collateral_mint_pubkey,
+        accounts.lending_market.pubkey,
+        accounts.user_accounts_owner.pubkey(),
+    ));
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::ReserveWindingDown as u32)
+        )
+    );
+
+    let mut instructions = accounts.refresh();
+    instructions.push(borrow_obligation_liquidity(
+        port_finance_variable_rate_lending::id(),
+        FRACTIONAL_TO_USDC,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.user_liquidity_pubkey,
+        usdc_test_reserve.pubkey,
+        usdc_test_reserve.liquidity_fee_receiver_pubkey,
+        accounts.test_obligation.pubkey,
+        accounts.lending_market.pubkey,
+        accounts.test_obligation.owner,
+        None,
+        None,
+    ));
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::ReserveWindingDown as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_wind_down_drains_reserve() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+    let accounts = setup(&mut test);
+    let usdc_test_reserve = &accounts.usdc_test_reserve;
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_user_collateral_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_collateral_pubkey).await;
+
+    let mut instructions = vec![accounts.set_wind_down(true)];
+    instructions.extend(accounts.refresh());
+    instructions.push(repay_obligation_liquidity(
+        port_finance_variable_rate_lending::id(),
+        u64::MAX,
+        usdc_test_reserve.user_liquidity_pubkey,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.pubkey,
+        accounts.test_obligation.pubkey,
+        accounts.lending_market.pubkey,
+        accounts.user_accounts_owner.pubkey(),
+    ));
+    instructions.extend(accounts.refresh());
+    instructions.push(withdraw_obligation_collateral(
+        port_finance_variable_rate_lending::id(),
+        u64::MAX,
+        usdc_test_reserve.collateral_supply_pubkey,
+        usdc_test_reserve.user_collateral_pubkey,
+        usdc_test_reserve.pubkey,
+        accounts.test_obligation.pubkey,
+        accounts.lending_market.pubkey,
+        accounts.test_obligation.owner,
+        None,
+        None,
+    ));
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(
+        &[
+            &payer,
+            &accounts.lending_market.owner,
+            &accounts.user_accounts_owner,
+        ],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_collateral_pubkey).await,
+        initial_user_collateral_balance + USDC_DEPOSIT_AMOUNT_FRACTIONAL
+    );
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.borrows.is_empty());
+    assert_eq!(obligation.deposits.len(), 1);
+
+    // the reserve liquidity and the repaid borrow are redeemed by the reserve's collateral
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                port_finance_variable_rate_lending::id(),
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_oracle_pubkey,
+            ),
+            redeem_reserve_collateral(
+                port_finance_variable_rate_lending::id(),
+                USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+                usdc_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                accounts.lending_market.pubkey,
+                accounts.user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert!(usdc_reserve.is_wind_down);
+    assert_eq!(usdc_reserve.liquidity.available_amount, 0);
+    assert_eq!(usdc_reserve.liquidity.borrowed_amount_wads, Decimal::zero());
+    assert_eq!(usdc_reserve.collateral.mint_total_supply, 0);
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await,
+        USDC_RESERVE_LIQUIDITY_FRACTIONAL + USDC_BORROW_AMOUNT_FRACTIONAL
+    );
+}
@@ -11,8 +11,11 @@ use spl_token::instruction::transfer;
 
 use helpers::*;
 use port_finance_variable_rate_lending::error::LendingError;
-use port_finance_variable_rate_lending::instruction::withdraw_fee;
+use port_finance_variable_rate_lending::instruction::{
+    borrow_obligation_liquidity, refresh_obligation, withdraw_fee,
+};
 use port_finance_variable_rate_lending::processor::process_instruction;
+use port_finance_variable_rate_lending::state::INITIAL_COLLATERAL_RATIO;
 
 mod helpers;
 
@@ -124,3 +127,129 @@ async fn test_withdraw_fee() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_withdraw_fee_sweeps_borrow_fees() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 500 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // the pending protocol fees `show-reserve` reports are the fee receiver balance
+    let pending_fees = get_token_balance(
+        &mut banks_client,
+        usdc_test_reserve.liquidity_fee_receiver_pubkey,
+    )
+    .await;
+    assert!(pending_fees > 0);
+    let destination_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_fee(
+            port_finance_variable_rate_lending::id(),
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            usdc_test_reserve.liquidity_fee_receiver_pubkey,
+            usdc_test_reserve.user_liquidity_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            usdc_test_reserve.liquidity_fee_receiver_pubkey
+        )
+        .await,
+        0
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await,
+        destination_balance + pending_fees
+    );
+}
@@ -369,6 +369,125 @@ async fn test_withdraw_too_large() {
     );
 }
 
+#[tokio::test]
+async fn test_withdraw_at_borrow_limit() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // 1,000 USDC borrowed at 50% loan to value needs exactly 100 SOL of the 200 SOL deposited
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 200 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const WITHDRAW_AMOUNT: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let withdraw = |collateral_amount| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                refresh_obligation(
+                    port_finance_variable_rate_lending::id(),
+                    test_obligation.pubkey,
+                    vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+                ),
+                withdraw_obligation_collateral(
+                    port_finance_variable_rate_lending::id(),
+                    collateral_amount,
+                    sol_test_reserve.collateral_supply_pubkey,
+                    sol_test_reserve.user_collateral_pubkey,
+                    sol_test_reserve.pubkey,
+                    test_obligation.pubkey,
+                    lending_market.pubkey,
+                    test_obligation.owner,
+                    None,
+                    None,
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    // one unit beyond the limit is rejected
+    assert_eq!(
+        banks_client
+            .process_transaction(withdraw(WITHDRAW_AMOUNT + 1))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::WithdrawTooLarge as u32)
+        )
+    );
+
+    banks_client
+        .process_transaction(withdraw(WITHDRAW_AMOUNT))
+        .await
+        .unwrap();
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS - WITHDRAW_AMOUNT
+    );
+}
+
 #[tokio::test]
 async fn test_withdraw_fixed_amount_liquidity_mining() {
     let mut test = ProgramTest::new(
@@ -811,7 +930,7 @@ async fn test_withdraw_fixed_amount_liquidity_mining_fail() {
             .unwrap(),
         TransactionError::InstructionError(
             1,
-            InstructionError::Custom(LendingError::InvalidStakingPool as u32),
+            InstructionError::Custom(LendingError::MissingStakingAccounts as u32),
         )
     );
     let after_staking_pool = staking_pool.get_state(&mut banks_client).await;
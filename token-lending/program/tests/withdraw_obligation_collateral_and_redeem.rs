@@ -0,0 +1,282 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    program_option::COption,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{refresh_obligation, withdraw_obligation_collateral_and_redeem},
+    processor::process_instruction,
+    state::{ReserveConfig, INITIAL_COLLATERAL_RATIO},
+};
+
+mod helpers;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 200 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const SOL_RESERVE_LIQUIDITY_LAMPORTS: u64 =
+    2 * SOL_DEPOSIT_AMOUNT_LAMPORTS / INITIAL_COLLATERAL_RATIO;
+
+struct WithdrawAndRedeemAccounts {
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+/// A SOL reserve with liquidity to redeem from and an obligation with collateral deposited in
+/// it, staked in `deposit_staking_pool` if there is one
+fn setup(
+    test: &mut ProgramTest,
+    lending_market: TestLendingMarket,
+    user_accounts_owner: Keypair,
+    deposit_staking_pool: COption<Pubkey>,
+) -> WithdrawAndRedeemAccounts {
+    let sol_oracle = add_sol_pyth_oracle(test);
+    let sol_test_reserve = add_reserve(
+        test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: SOL_RESERVE_LIQUIDITY_LAMPORTS,
+            user_liquidity_amount: 0,
+            collateral_amount: SOL_RESERVE_LIQUIDITY_LAMPORTS * INITIAL_COLLATERAL_RATIO,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: ReserveConfig {
+                deposit_staking_pool,
+                ..TEST_RESERVE_CONFIG
+            },
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    WithdrawAndRedeemAccounts {
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserve,
+        test_obligation,
+    }
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+    let lending_market = add_lending_market(&mut test);
+    let accounts = setup(&mut test, lending_market, Keypair::new(), COption::None);
+    let sol_test_reserve = &accounts.sol_test_reserve;
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_user_collateral_balance =
+        get_token_balance(&mut banks_client, sol_test_reserve.user_collateral_pubkey).await;
+    let initial_user_liquidity_balance =
+        get_token_balance(&mut banks_client, sol_test_reserve.user_liquidity_pubkey).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                accounts.test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            withdraw_obligation_collateral_and_redeem(
+                port_finance_variable_rate_lending::id(),
+                u64::MAX,
+                sol_test_reserve.collateral_supply_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.collateral_mint_pubkey,
+                sol_test_reserve.liquidity_supply_pubkey,
+                accounts.test_obligation.pubkey,
+                accounts.lending_market.pubkey,
+                accounts.test_obligation.owner,
+                None,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // the withdrawn collateral was redeemed, none of it is left in the user's account
+    assert_eq!(
+        get_token_balance(&mut banks_client, sol_test_reserve.user_collateral_pubkey).await,
+        initial_user_collateral_balance
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, sol_test_reserve.user_liquidity_pubkey).await,
+        initial_user_liquidity_balance + SOL_DEPOSIT_AMOUNT_LAMPORTS / INITIAL_COLLATERAL_RATIO
+    );
+
+    let obligation = accounts.test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.deposits.is_empty());
+}
+
+#[tokio::test]
+async fn test_success_liquidity_mining() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+    test.prefer_bpf(false);
+    test.add_program(
+        "port_finance_staking",
+        port_finance_staking::id(),
+        processor!(port_finance_staking::processor::process_instruction),
+    );
+
+    const WITHDRAW_AMOUNT: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+
+    let lending_market = add_lending_market(&mut test);
+    let staking_pool = add_staking_pool(
+        &mut test,
+        lending_market.authority,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS,
+    );
+    let accounts = setup(
+        &mut test,
+        lending_market,
+        Keypair::new(),
+        COption::Some(staking_pool.staking_pool_pubkey),
+    );
+    let stake_account = add_stake_account(
+        &mut test,
+        staking_pool.staking_pool_pubkey,
+        &accounts.user_accounts_owner,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS,
+    );
+    let sol_test_reserve = &accounts.sol_test_reserve;
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_user_collateral_balance =
+        get_token_balance(&mut banks_client, sol_test_reserve.user_collateral_pubkey).await;
+    let initial_user_liquidity_balance =
+        get_token_balance(&mut banks_client, sol_test_reserve.user_liquidity_pubkey).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                port_finance_variable_rate_lending::id(),
+                accounts.test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            withdraw_obligation_collateral_and_redeem(
+                port_finance_variable_rate_lending::id(),
+                WITHDRAW_AMOUNT,
+                sol_test_reserve.collateral_supply_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.collateral_mint_pubkey,
+                sol_test_reserve.liquidity_supply_pubkey,
+                accounts.test_obligation.pubkey,
+                accounts.lending_market.pubkey,
+                accounts.test_obligation.owner,
+                Some(stake_account.pubkey),
+                Some(staking_pool.staking_pool_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut banks_client, sol_test_reserve.user_collateral_pubkey).await,
+        initial_user_collateral_balance
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, sol_test_reserve.user_liquidity_pubkey).await,
+        initial_user_liquidity_balance + WITHDRAW_AMOUNT / INITIAL_COLLATERAL_RATIO
+    );
+
+    // the withdrawn collateral was unstaked
+    assert_eq!(
+        staking_pool.get_state(&mut banks_client).await.pool_size,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS - WITHDRAW_AMOUNT
+    );
+    assert_eq!(
+        stake_account
+            .get_state(&mut banks_client)
+            .await
+            .deposited_amount,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS - WITHDRAW_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn test_fail_missing_staking_accounts() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+    let lending_market = add_lending_market(&mut test);
+    let accounts = setup(
+        &mut test,
+        lending_market,
+        Keypair::new(),
+        COption::Some(Pubkey::new_unique()),
+    );
+    let sol_test_reserve = &accounts.sol_test_reserve;
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw_obligation_collateral_and_redeem(
+            port_finance_variable_rate_lending::id(),
+            u64::MAX,
+            sol_test_reserve.collateral_supply_pubkey,
+            sol_test_reserve.user_collateral_pubkey,
+            sol_test_reserve.user_liquidity_pubkey,
+            sol_test_reserve.pubkey,
+            sol_test_reserve.collateral_mint_pubkey,
+            sol_test_reserve.liquidity_supply_pubkey,
+            accounts.test_obligation.pubkey,
+            accounts.lending_market.pubkey,
+            accounts.test_obligation.owner,
+            None,
+            None,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &accounts.user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::MissingStakingAccounts as u32)
+        )
+    );
+}
@@ -0,0 +1,211 @@
+#![cfg(feature = "test-bpf")]
+
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use helpers::*;
+use port_finance_variable_rate_lending::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, deposit_and_borrow, deposit_reserve_liquidity,
+        liquidate_obligation, redeem_reserve_collateral, repay_obligation_liquidity,
+    },
+    processor::process_instruction,
+    state::INITIAL_COLLATERAL_RATIO,
+};
+
+mod helpers;
+
+#[tokio::test]
+async fn test_zero_amount() {
+    let mut test = ProgramTest::new(
+        "port_finance_variable_rate_lending",
+        port_finance_variable_rate_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_pyth_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_pyth_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let deposit_and_borrow_with = |liquidity_amount, borrow_amount| {
+        deposit_and_borrow(
+            port_finance_variable_rate_lending::id(),
+            liquidity_amount,
+            borrow_amount,
+            sol_test_reserve.user_liquidity_pubkey,
+            sol_test_reserve.user_collateral_pubkey,
+            sol_test_reserve.pubkey,
+            sol_test_reserve.liquidity_supply_pubkey,
+            sol_test_reserve.collateral_mint_pubkey,
+            lending_market.pubkey,
+            sol_test_reserve.collateral_supply_pubkey,
+            test_obligation.pubkey,
+            test_obligation.owner,
+            user_accounts_owner.pubkey(),
+            usdc_test_reserve.liquidity_supply_pubkey,
+            usdc_test_reserve.user_liquidity_pubkey,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.liquidity_fee_receiver_pubkey,
+            vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+        )
+    };
+
+    let instructions: Vec<(&str, Instruction)> = vec![
+        (
+            "deposit reserve liquidity",
+            deposit_reserve_liquidity(
+                port_finance_variable_rate_lending::id(),
+                0,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            ),
+        ),
+        (
+            "redeem reserve collateral",
+            redeem_reserve_collateral(
+                port_finance_variable_rate_lending::id(),
+                0,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.collateral_mint_pubkey,
+                sol_test_reserve.liquidity_supply_pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            ),
+        ),
+        (
+            "borrow obligation liquidity",
+            borrow_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                0,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                None,
+                None,
+            ),
+        ),
+        (
+            "repay obligation liquidity",
+            repay_obligation_liquidity(
+                port_finance_variable_rate_lending::id(),
+                0,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            ),
+        ),
+        (
+            "liquidate obligation",
+            liquidate_obligation(
+                port_finance_variable_rate_lending::id(),
+                0,
+                usdc_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.collateral_supply_pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                None,
+                None,
+            ),
+        ),
+        (
+            "deposit and borrow, zero deposit",
+            deposit_and_borrow_with(0, USDC_BORROW_AMOUNT_FRACTIONAL),
+        ),
+        (
+            "deposit and borrow, zero borrow",
+            deposit_and_borrow_with(SOL_DEPOSIT_AMOUNT_LAMPORTS, 0),
+        ),
+    ];
+
+    for (name, instruction) in instructions {
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(LendingError::InvalidAmount as u32)
+            ),
+            "{}",
+            name
+        );
+    }
+}